@@ -37,5 +37,45 @@ fn bench_intrinsics_against_morton_encoding(c: &mut Criterion) {
     }
 }
 
-criterion_group!(benches, bench_intrinsics_against_morton_encoding);
+fn bench_batch(c: &mut Criterion) {
+    let points = (0..4096u64)
+        .map(|i| {
+            let value = i.wrapping_mul(0x9e3779b97f4a7c15);
+            [value as u16, (value >> 16) as u16, (value >> 32) as u16]
+        })
+        .collect::<Vec<_>>();
+    let [x, y, z] = [0, 1, 2].map(|i| points.iter().map(|p| p[i]).collect::<Vec<_>>());
+    let mut codes = vec![0; points.len()];
+
+    let mut group = c.benchmark_group("encode 4096 [u16; 3]");
+
+    group.bench_function("single", |b| {
+        b.iter(|| {
+            for (point, code) in black_box(&points).iter().zip(&mut codes) {
+                *code = point.morton_encode();
+            }
+        })
+    });
+
+    group.bench_function("batch", |b| {
+        b.iter(|| morton::batch::encode_batch(black_box(&points), &mut codes))
+    });
+
+    group.bench_function("batch_transposed", |b| {
+        b.iter(|| {
+            morton::batch::encode_batch_transposed(
+                black_box([x.as_slice(), y.as_slice(), z.as_slice()]),
+                &mut codes,
+            )
+        })
+    });
+
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bench_intrinsics_against_morton_encoding,
+    bench_batch
+);
 criterion_main!(benches);
@@ -0,0 +1,158 @@
+//! Encoding and decoding many coordinates at once.
+//!
+//! `pdep` and `pext` only work on one integer at a time, so these always use
+//! the [magic numbers](crate::magic). They only shift and mask, which the
+//! compiler turns into SIMD instructions. This works best with the transposed
+//! functions, which take a separate slice for each axis, as the coordinates
+//! don't have to be shuffled into SIMD lanes first.
+//!
+//! The codes are the same as with [`Morton`](crate::Morton).
+
+use crate::{
+    MortonCode,
+    magic::Dilate,
+};
+
+/// Encodes `points` into `codes`.
+///
+/// # Panics
+///
+/// Panics if the slices don't have the same length.
+pub fn encode_batch<T, const N: usize>(points: &[[T; N]], codes: &mut [T::Dilated])
+where
+    T: Dilate<N> + Copy,
+    T::Dilated: MortonCode,
+{
+    assert_eq!(points.len(), codes.len());
+
+    for (point, code) in points.iter().zip(codes) {
+        *code = encode_one(|i| point[i]);
+    }
+}
+
+/// Decodes `codes` into `points`.
+///
+/// # Panics
+///
+/// Panics if the slices don't have the same length.
+pub fn decode_batch<T, const N: usize>(codes: &[T::Dilated], points: &mut [[T; N]])
+where
+    T: Dilate<N> + Copy,
+    T::Dilated: MortonCode,
+{
+    assert_eq!(codes.len(), points.len());
+
+    for (code, point) in codes.iter().zip(points) {
+        *point = std::array::from_fn(|i| decode_one(*code, i));
+    }
+}
+
+/// Encodes points that are given as one slice of coordinates per axis.
+///
+/// # Panics
+///
+/// Panics if the slices don't have the same length.
+pub fn encode_batch_transposed<T, const N: usize>(coordinates: [&[T]; N], codes: &mut [T::Dilated])
+where
+    T: Dilate<N> + Copy,
+    T::Dilated: MortonCode,
+{
+    assert!(coordinates.iter().all(|axis| axis.len() == codes.len()));
+
+    codes.fill(T::Dilated::ZERO);
+
+    // one axis at a time, so each loop only reads from one slice
+    for (i, axis) in coordinates.iter().enumerate() {
+        let shift = (N - i - 1) as u32;
+        for (coordinate, code) in axis.iter().zip(codes.iter_mut()) {
+            *code = *code | (coordinate.dilate() << shift);
+        }
+    }
+}
+
+/// Decodes codes into one slice of coordinates per axis.
+///
+/// # Panics
+///
+/// Panics if the slices don't have the same length.
+pub fn decode_batch_transposed<T, const N: usize>(
+    codes: &[T::Dilated],
+    mut coordinates: [&mut [T]; N],
+) where
+    T: Dilate<N> + Copy,
+    T::Dilated: MortonCode,
+{
+    assert!(coordinates.iter().all(|axis| axis.len() == codes.len()));
+
+    // one axis at a time, so each loop only writes to one slice
+    for (i, axis) in coordinates.iter_mut().enumerate() {
+        for (code, coordinate) in codes.iter().zip(axis.iter_mut()) {
+            *coordinate = decode_one(*code, i);
+        }
+    }
+}
+
+#[inline(always)]
+fn encode_one<T, const N: usize>(coordinate: impl Fn(usize) -> T) -> T::Dilated
+where
+    T: Dilate<N>,
+    T::Dilated: MortonCode,
+{
+    (0..N).fold(T::Dilated::ZERO, |code, i| {
+        code | (coordinate(i).dilate() << (N - i - 1) as u32)
+    })
+}
+
+#[inline(always)]
+fn decode_one<T, const N: usize>(code: T::Dilated, axis: usize) -> T
+where
+    T: Dilate<N>,
+    T::Dilated: MortonCode,
+{
+    T::contract(code >> (N - axis - 1) as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        Morton,
+        batch::{
+            decode_batch,
+            decode_batch_transposed,
+            encode_batch,
+            encode_batch_transposed,
+        },
+    };
+
+    #[test]
+    fn it_matches_single_encodes() {
+        let points = (0..1000u64)
+            .map(|i| {
+                let value = i.wrapping_mul(0x9e3779b97f4a7c15);
+                [value as u16, (value >> 16) as u16, (value >> 32) as u16]
+            })
+            .collect::<Vec<_>>();
+        let expected = points
+            .iter()
+            .map(|point| point.morton_encode())
+            .collect::<Vec<_>>();
+
+        let mut codes = vec![0; points.len()];
+        encode_batch(&points, &mut codes);
+        assert_eq!(codes, expected);
+
+        let mut decoded = vec![[0; 3]; codes.len()];
+        decode_batch(&codes, &mut decoded);
+        assert_eq!(decoded, points);
+
+        let [x, y, z] = [0, 1, 2].map(|i| points.iter().map(|p| p[i]).collect::<Vec<_>>());
+        let mut codes = vec![0; points.len()];
+        encode_batch_transposed([&x, &y, &z], &mut codes);
+        assert_eq!(codes, expected);
+
+        let mut decoded = [0, 1, 2].map(|_| vec![0; codes.len()]);
+        let [dx, dy, dz] = &mut decoded;
+        decode_batch_transposed(&codes, [dx, dy, dz]);
+        assert_eq!(decoded, [x, y, z]);
+    }
+}
@@ -44,34 +44,83 @@ macro_rules! impl_with_cast {
     };
 }
 
-macro_rules! impl_with_fallback {
-    ($ty:ty) => {
+macro_rules! impl_portable {
+    ($ty:ty, $deposit:ident, $extract:ident) => {
+        /// Deposits the low bits of `value` at the set bits of `mask`, one bit
+        /// at a time.
+        #[inline]
+        pub fn $deposit(value: $ty, mut mask: $ty) -> $ty {
+            let mut result = 0;
+            let mut bit = 1;
+
+            while mask != 0 {
+                let lowest = mask & mask.wrapping_neg();
+                if value & bit != 0 {
+                    result |= lowest;
+                }
+                mask ^= lowest;
+                bit <<= 1;
+            }
+
+            result
+        }
+
+        /// Extracts the bits of `value` at the set bits of `mask` into the low
+        /// bits, one bit at a time.
+        #[inline]
+        pub fn $extract(value: $ty, mut mask: $ty) -> $ty {
+            let mut result = 0;
+            let mut bit = 1;
+
+            while mask != 0 {
+                let lowest = mask & mask.wrapping_neg();
+                if value & lowest != 0 {
+                    result |= bit;
+                }
+                mask ^= lowest;
+                bit <<= 1;
+            }
+
+            result
+        }
+    };
+}
+
+macro_rules! impl_with_portable {
+    ($ty:ty, $deposit:ident, $extract:ident) => {
         impl BitDeposit for $ty {
             #[inline]
             fn deposit(self, mask: $ty) -> $ty {
-                <$ty>::deposit_bits(self, mask)
+                portable::$deposit(self, mask)
             }
         }
 
         impl BitExtract for $ty {
             #[inline]
             fn extract(self, mask: $ty) -> $ty {
-                <$ty>::extract_bits(self, mask)
+                portable::$extract(self, mask)
             }
         }
     };
 }
 
-#[cfg(target_arch = "x86_64")]
+/// Implementations that work on any target.
+pub mod portable {
+    impl_portable!(u64, deposit_u64, extract_u64);
+    impl_portable!(u32, deposit_u32, extract_u32);
+    impl_portable!(u16, deposit_u16, extract_u16);
+}
+
+#[cfg(all(target_arch = "x86_64", not(feature = "portable")))]
 const _: () = {
     impl_with_instrinsics!(u64, x86_64, _pdep_u64, _pext_u64);
     impl_with_instrinsics!(u32, x86_64, _pdep_u32, _pext_u32);
     impl_with_cast!(u16 as u32);
 };
 
-#[cfg(not(target_arch = "x86_64"))]
+#[cfg(not(all(target_arch = "x86_64", not(feature = "portable"))))]
 const _: () = {
-    impl_with_fallback!(u64);
-    impl_with_fallback!(u32);
-    impl_with_fallback!(u16);
+    impl_with_portable!(u64, deposit_u64, extract_u64);
+    impl_with_portable!(u32, deposit_u32, extract_u32);
+    impl_with_portable!(u16, deposit_u16, extract_u16);
 };
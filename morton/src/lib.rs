@@ -1,12 +1,42 @@
-#![feature(uint_gather_scatter_bits)]
+//! Morton encoding (Z-order curves).
+//!
+//! On x86_64 this uses the BMI2 instructions `pdep` and `pext`. Everywhere
+//! else, or with the `portable` feature (e.g. for CPUs without BMI2, or where
+//! it's slow), the coordinates are interleaved with magic numbers.
+//!
+//! Signed coordinates are mapped to unsigned ones by flipping the sign bit.
+//! This keeps their order (unlike zig-zag encoding), so a box of signed
+//! coordinates still maps to a contiguous range of each coordinate, and
+//! coordinates on either side of 0 are only far apart in the highest bit.
 
+pub mod batch;
 pub mod bitops;
+pub mod hilbert;
+pub mod magic;
+pub mod neighbors;
+pub mod range;
 
+use std::{
+    fmt::Debug,
+    ops::{
+        BitAnd,
+        BitOr,
+        BitXor,
+        Not,
+        Shl,
+        Shr,
+    },
+};
+
+#[cfg(all(target_arch = "x86_64", not(feature = "portable")))]
 use bitops::{
     BitDeposit,
     BitExtract,
 };
+#[cfg(not(all(target_arch = "x86_64", not(feature = "portable"))))]
+use magic::Dilate;
 
+#[cfg(all(target_arch = "x86_64", not(feature = "portable")))]
 macro_rules! make_base_mask {
     ($encoded:ty, $decoded:ty, $n:expr) => {
         const {
@@ -30,6 +60,91 @@ pub trait Morton {
     fn morton_decode(code: Self::Code) -> Self;
 }
 
+/// Integer type of a Morton code.
+pub trait MortonCode:
+    Copy
+    + Ord
+    + Debug
+    + BitAnd<Output = Self>
+    + BitOr<Output = Self>
+    + BitXor<Output = Self>
+    + Not<Output = Self>
+    + Shl<u32, Output = Self>
+    + Shr<u32, Output = Self>
+{
+    const ZERO: Self;
+    const ONE: Self;
+    const BITS: u32;
+
+    fn leading_zeros(self) -> u32;
+    fn wrapping_add(self, other: Self) -> Self;
+    fn wrapping_sub(self, other: Self) -> Self;
+}
+
+macro_rules! impl_morton_code {
+    ($($ty:ty),*) => {
+        $(
+            impl MortonCode for $ty {
+                const ZERO: Self = 0;
+                const ONE: Self = 1;
+                const BITS: u32 = <$ty>::BITS;
+
+                #[inline]
+                fn leading_zeros(self) -> u32 {
+                    <$ty>::leading_zeros(self)
+                }
+
+                #[inline]
+                fn wrapping_add(self, other: Self) -> Self {
+                    <$ty>::wrapping_add(self, other)
+                }
+
+                #[inline]
+                fn wrapping_sub(self, other: Self) -> Self {
+                    <$ty>::wrapping_sub(self, other)
+                }
+            }
+        )*
+    };
+}
+
+impl_morton_code!(u16, u32, u64, u128);
+
+/// Coordinates whose Morton codes can be worked with directly, without
+/// decoding them.
+///
+/// The coordinate on axis `i` of `N` is stored in every `N`-th bit, starting
+/// at bit `N - i - 1`.
+pub trait MortonAxes: Morton<Code: MortonCode> {
+    const DIMENSIONS: usize;
+
+    /// The bits of a code that belong to the coordinate on `axis`.
+    fn axis_mask(axis: usize) -> Self::Code;
+}
+
+macro_rules! impl_morton_axes {
+    ($decoded:ty, $n:literal) => {
+        impl MortonAxes for [$decoded; $n] {
+            const DIMENSIONS: usize = $n;
+
+            #[inline]
+            fn axis_mask(axis: usize) -> Self::Code {
+                // signed coordinates are offset, so `MIN` is encoded as 0
+                std::array::from_fn::<$decoded, $n, _>(|i| {
+                    if i == axis {
+                        <$decoded>::MAX
+                    }
+                    else {
+                        <$decoded>::MIN
+                    }
+                })
+                .morton_encode()
+            }
+        }
+    };
+}
+
+#[cfg(all(target_arch = "x86_64", not(feature = "portable")))]
 macro_rules! impl_morton {
     ($encoded:tt, $decoded:ty, [$($i:literal),*], $n:literal) => {
         const _: () = {
@@ -76,6 +191,31 @@ where
     T::morton_decode(x)
 }
 
+#[cfg(not(all(target_arch = "x86_64", not(feature = "portable"))))]
+macro_rules! impl_morton {
+    ($encoded:tt, $decoded:ty, [$($i:literal),*], $n:literal) => {
+        impl Morton for [$decoded; $n] {
+            type Code = $encoded;
+
+            #[inline]
+            fn morton_encode(self) -> $encoded {
+                $(
+                    <$decoded as Dilate<$n>>::dilate(self[$i]) << ($n - $i - 1)
+                )|*
+            }
+
+            #[inline]
+            fn morton_decode(code: $encoded) -> [$decoded; $n] {
+                [
+                    $(
+                        <$decoded as Dilate<$n>>::contract(code >> ($n - $i - 1))
+                    ),*
+                ]
+            }
+        }
+    };
+}
+
 impl_morton!(u16, u8, [0, 1], 2);
 impl_morton!(u32, u8, [0, 1, 2], 3);
 impl_morton!(u32, u8, [0, 1, 2, 3], 4);
@@ -84,9 +224,102 @@ impl_morton!(u32, u16, [0, 1], 2);
 impl_morton!(u64, u16, [0, 1, 2], 3);
 impl_morton!(u64, u16, [0, 1, 2, 3], 4);
 
+impl_morton!(u64, u32, [0, 1], 2);
+
+/// There is no 128 bit `pdep`, so we encode the lower and upper halves of the
+/// coordinates separately.
+impl Morton for [u32; 3] {
+    type Code = u128;
+
+    #[inline]
+    fn morton_encode(self) -> u128 {
+        let low = self.map(|x| x as u16).morton_encode();
+        let high = self.map(|x| (x >> 16) as u16).morton_encode();
+        u128::from(low) | (u128::from(high) << 48)
+    }
+
+    #[inline]
+    fn morton_decode(code: u128) -> [u32; 3] {
+        const MASK: u128 = (1 << 48) - 1;
+
+        let low = <[u16; 3]>::morton_decode((code & MASK) as u64);
+        let high = <[u16; 3]>::morton_decode(((code >> 48) & MASK) as u64);
+        std::array::from_fn(|i| u32::from(low[i]) | (u32::from(high[i]) << 16))
+    }
+}
+
+macro_rules! impl_morton_signed {
+    ($signed:ty as $unsigned:ty, $n:literal) => {
+        impl Morton for [$signed; $n] {
+            type Code = <[$unsigned; $n] as Morton>::Code;
+
+            #[inline]
+            fn morton_encode(self) -> Self::Code {
+                const SIGN: $unsigned = 1 << (<$unsigned>::BITS - 1);
+                self.map(|x| (x as $unsigned) ^ SIGN).morton_encode()
+            }
+
+            #[inline]
+            fn morton_decode(code: Self::Code) -> Self {
+                const SIGN: $unsigned = 1 << (<$unsigned>::BITS - 1);
+                <[$unsigned; $n]>::morton_decode(code).map(|x| (x ^ SIGN) as $signed)
+            }
+        }
+    };
+}
+
+impl_morton_signed!(i8 as u8, 2);
+impl_morton_signed!(i8 as u8, 3);
+impl_morton_signed!(i8 as u8, 4);
+
+impl_morton_signed!(i16 as u16, 2);
+impl_morton_signed!(i16 as u16, 3);
+impl_morton_signed!(i16 as u16, 4);
+
+impl_morton_signed!(i32 as u32, 2);
+impl_morton_signed!(i32 as u32, 3);
+
+impl_morton_axes!(u8, 2);
+impl_morton_axes!(u8, 3);
+impl_morton_axes!(u8, 4);
+impl_morton_axes!(u16, 2);
+impl_morton_axes!(u16, 3);
+impl_morton_axes!(u16, 4);
+impl_morton_axes!(u32, 2);
+impl_morton_axes!(u32, 3);
+impl_morton_axes!(i8, 2);
+impl_morton_axes!(i8, 3);
+impl_morton_axes!(i8, 4);
+impl_morton_axes!(i16, 2);
+impl_morton_axes!(i16, 3);
+impl_morton_axes!(i16, 4);
+impl_morton_axes!(i32, 2);
+impl_morton_axes!(i32, 3);
+
 #[cfg(test)]
 mod tests {
-    use crate::Morton;
+    use crate::{
+        Morton,
+        bitops::{
+            BitDeposit,
+            BitExtract,
+            portable,
+        },
+        magic::Dilate,
+    };
+
+    /// Deterministic pseudo-random numbers (xorshift), so we don't need a
+    /// dependency.
+    fn random_values() -> impl Iterator<Item = u64> {
+        let mut state = 0x2545f4914f6cdd1d_u64;
+        std::iter::from_fn(move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            Some(state)
+        })
+        .take(10000)
+    }
 
     // 123 = 0b001111011 -> 0b00010101010001010
     // 456 = 0b111001000 -> 0b10101000001000000
@@ -144,4 +377,113 @@ mod tests {
     fn test_decode_u16_3() {
         assert_eq!(<[u16; 3]>::morton_decode(190471269), [123, 456, 789]);
     }
+
+    /// Interleaves the bits one at a time.
+    fn reference_encode<const N: usize>(coordinates: [u32; N]) -> u128 {
+        let mut code = 0;
+        for bit in 0..32 {
+            for (i, coordinate) in coordinates.iter().enumerate() {
+                let value = u128::from((coordinate >> bit) & 1);
+                code |= value << (N * bit + N - i - 1);
+            }
+        }
+        code
+    }
+
+    #[test]
+    fn test_u32_against_reference() {
+        for value in random_values() {
+            let [x, y, z] = [
+                value as u32,
+                (value >> 32) as u32,
+                value.rotate_left(16) as u32,
+            ];
+
+            let code = [x, y, z].morton_encode();
+            assert_eq!(code, reference_encode([x, y, z]));
+            assert_eq!(<[u32; 3]>::morton_decode(code), [x, y, z]);
+
+            let code = [x, y].morton_encode();
+            assert_eq!(u128::from(code), reference_encode([x, y]));
+            assert_eq!(<[u32; 2]>::morton_decode(code), [x, y]);
+        }
+    }
+
+    #[test]
+    fn test_signed() {
+        for value in random_values() {
+            let [x, y, z] = [
+                value as i32,
+                (value >> 32) as i32,
+                value.rotate_left(16) as i32,
+            ];
+            assert_eq!(
+                <[i32; 3]>::morton_decode([x, y, z].morton_encode()),
+                [x, y, z]
+            );
+
+            let [x, y] = [x as i16, y as i16];
+            assert_eq!(<[i16; 2]>::morton_decode([x, y].morton_encode()), [x, y]);
+        }
+
+        // the order along each axis is kept
+        assert!([-1i32, 0, 0].morton_encode() < [0i32, 0, 0].morton_encode());
+        assert!([i32::MIN, 0, 0].morton_encode() < [-1i32, 0, 0].morton_encode());
+        assert_eq!([i16::MIN; 3].morton_encode(), 0);
+        assert_eq!(
+            [0i16, 0].morton_encode(),
+            [0x8000u16, 0x8000].morton_encode()
+        );
+    }
+
+    #[test]
+    fn test_portable_bitops_agree() {
+        for (value, mask) in random_values().zip(random_values().skip(1)) {
+            assert_eq!(
+                portable::deposit_u64(value, mask),
+                value.deposit(mask),
+                "deposit {value:#x} {mask:#x}"
+            );
+            assert_eq!(
+                portable::extract_u64(value, mask),
+                value.extract(mask),
+                "extract {value:#x} {mask:#x}"
+            );
+
+            let (value, mask) = (value as u16, mask as u16);
+            assert_eq!(portable::deposit_u16(value, mask), value.deposit(mask));
+            assert_eq!(portable::extract_u16(value, mask), value.extract(mask));
+        }
+    }
+
+    #[test]
+    fn test_magic_numbers_agree() {
+        for value in random_values() {
+            let [x, y, z, w] = [0, 16, 32, 48].map(|shift| (value >> shift) as u16);
+
+            let code = (<u16 as Dilate<3>>::dilate(x) << 2)
+                | (<u16 as Dilate<3>>::dilate(y) << 1)
+                | <u16 as Dilate<3>>::dilate(z);
+            assert_eq!([x, y, z].morton_encode(), code);
+            assert_eq!(<[u16; 3]>::morton_decode(code), [x, y, z]);
+
+            let code = (<u16 as Dilate<2>>::dilate(x) << 1) | <u16 as Dilate<2>>::dilate(y);
+            assert_eq!([x, y].morton_encode(), code);
+            assert_eq!(<[u16; 2]>::morton_decode(code), [x, y]);
+
+            let code = [x, y, z, w].morton_encode();
+            assert_eq!(<u16 as Dilate<4>>::contract(code >> 1), z);
+            assert_eq!(<[u16; 4]>::morton_decode(code), [x, y, z, w]);
+
+            let [a, b, c] = [x as u8, y as u8, z as u8];
+            assert_eq!(
+                <[u8; 3]>::morton_decode([a, b, c].morton_encode()),
+                [a, b, c]
+            );
+            assert_eq!(
+                <u8 as Dilate<2>>::dilate(a) << 1 | <u8 as Dilate<2>>::dilate(b),
+                [a, b].morton_encode()
+            );
+        }
+    }
 }
@@ -0,0 +1,85 @@
+//! Morton encoding with magic numbers, for targets without `pdep` and `pext`
+//! instructions.
+//!
+//! The bits of each coordinate are spread out (dilated) by repeatedly shifting
+//! the upper half of each group of bits and masking, which only takes
+//! `log2(bits)` steps.
+
+pub trait Dilate<const N: usize>: Sized {
+    type Dilated;
+
+    /// Spreads the bits, such that there are `N - 1` zero bits between them.
+    fn dilate(self) -> Self::Dilated;
+
+    /// Inverse of [`dilate`](Self::dilate). Bits that are not at a multiple of
+    /// `N` are ignored.
+    fn contract(dilated: Self::Dilated) -> Self;
+}
+
+macro_rules! impl_dilate {
+    ($encoded:ty, $decoded:ty, $n:literal) => {
+        const _: () = {
+            const STEPS: usize = <$decoded>::BITS.trailing_zeros() as usize;
+
+            // masks[k] selects groups of 2^k bits, every n * 2^k bits.
+            const MASKS: [$encoded; STEPS + 1] = const {
+                let mut masks = [0; STEPS + 1];
+                let mut k = 0;
+
+                while k <= STEPS {
+                    let group = 1 << k;
+                    let mut i = 0;
+
+                    while i < $n * <$decoded>::BITS {
+                        if i % ($n * group) < group {
+                            masks[k] |= 1 << i;
+                        }
+                        i += 1;
+                    }
+
+                    k += 1;
+                }
+
+                masks
+            };
+
+            impl Dilate<$n> for $decoded {
+                type Dilated = $encoded;
+
+                #[inline]
+                fn dilate(self) -> $encoded {
+                    let mut x = self as $encoded;
+                    let mut k = STEPS;
+
+                    while k > 0 {
+                        k -= 1;
+                        x = (x | (x << (($n - 1) << k))) & MASKS[k];
+                    }
+
+                    x
+                }
+
+                #[inline]
+                fn contract(dilated: $encoded) -> $decoded {
+                    let mut x = dilated & MASKS[0];
+
+                    for k in 0..STEPS {
+                        x = (x | (x >> (($n - 1) << k))) & MASKS[k + 1];
+                    }
+
+                    x as $decoded
+                }
+            }
+        };
+    };
+}
+
+impl_dilate!(u16, u8, 2);
+impl_dilate!(u32, u8, 3);
+impl_dilate!(u32, u8, 4);
+
+impl_dilate!(u32, u16, 2);
+impl_dilate!(u64, u16, 3);
+impl_dilate!(u64, u16, 4);
+
+impl_dilate!(u64, u32, 2);
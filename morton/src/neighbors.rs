@@ -0,0 +1,145 @@
+//! Arithmetic on Morton codes, without decoding them.
+//!
+//! To add to one coordinate, the bits of the other coordinates are set to 1,
+//! so that carries propagate over them. Subtracting works the same way, but
+//! with the other bits cleared, so that borrows propagate over them.
+//! Coordinates wrap around on overflow, like with `wrapping_add`.
+//!
+//! Signed coordinates are encoded with an offset, so a signed offset has to be
+//! encoded as the unsigned integers with the same bits, e.g. `[-1i16, 2]` as
+//! `[u16::MAX, 2]`.
+
+use crate::{
+    MortonAxes,
+    MortonCode,
+};
+
+/// Adds the coordinates of `b` to the coordinates of `a`.
+pub fn add<T: MortonAxes>(a: T::Code, b: T::Code) -> T::Code {
+    (0..T::DIMENSIONS).fold(T::Code::ZERO, |sum, axis| {
+        let mask = T::axis_mask(axis);
+        sum | ((a | !mask).wrapping_add(b & mask) & mask)
+    })
+}
+
+/// Subtracts the coordinates of `b` from the coordinates of `a`.
+pub fn sub<T: MortonAxes>(a: T::Code, b: T::Code) -> T::Code {
+    (0..T::DIMENSIONS).fold(T::Code::ZERO, |difference, axis| {
+        let mask = T::axis_mask(axis);
+        difference | ((a & mask).wrapping_sub(b & mask) & mask)
+    })
+}
+
+/// Moves one step along `axis`, in the positive direction if `forward` is
+/// set. Returns `None` instead of wrapping around.
+pub fn step<T: MortonAxes>(code: T::Code, axis: usize, forward: bool) -> Option<T::Code> {
+    let mask = T::axis_mask(axis);
+    let one = T::Code::ONE << (T::DIMENSIONS - axis - 1) as u32;
+    let coordinate = code & mask;
+
+    let coordinate = if forward {
+        (coordinate != mask).then(|| (code | !mask).wrapping_add(one) & mask)?
+    }
+    else {
+        (coordinate != T::Code::ZERO).then(|| coordinate.wrapping_sub(one) & mask)?
+    };
+
+    Some((code & !mask) | coordinate)
+}
+
+/// Neighbors that share a face, e.g. the 6 neighbors in 3D. Neighbors outside
+/// of the coordinate range are skipped.
+pub fn face_neighbors<T: MortonAxes>(code: T::Code) -> impl Iterator<Item = T::Code> {
+    (0..T::DIMENSIONS)
+        .flat_map(|axis| [(axis, false), (axis, true)])
+        .filter_map(move |(axis, forward)| step::<T>(code, axis, forward))
+}
+
+/// Neighbors that share a face, edge or corner, e.g. the 26 neighbors in 3D.
+/// Neighbors outside of the coordinate range are skipped.
+pub fn neighbors<T: MortonAxes>(code: T::Code) -> impl Iterator<Item = T::Code> {
+    let num_offsets = 3usize.pow(T::DIMENSIONS as u32);
+    let center = num_offsets / 2;
+
+    // each base-3 digit is the offset -1, 0 or 1 on one axis
+    (0..num_offsets)
+        .filter(move |offsets| *offsets != center)
+        .filter_map(move |mut offsets| {
+            let mut neighbor = code;
+            for axis in 0..T::DIMENSIONS {
+                match offsets % 3 {
+                    0 => neighbor = step::<T>(neighbor, axis, false)?,
+                    2 => neighbor = step::<T>(neighbor, axis, true)?,
+                    _ => {}
+                }
+                offsets /= 3;
+            }
+            Some(neighbor)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        Morton,
+        neighbors::{
+            add,
+            face_neighbors,
+            neighbors,
+            sub,
+        },
+    };
+
+    #[test]
+    fn it_computes_neighbors() {
+        for [x, y, z] in [[5i16, -7, 0], [0, 0, 0], [i16::MAX, 3, i16::MIN]] {
+            let code = [x, y, z].morton_encode();
+
+            let mut expected = vec![];
+            for dx in -1..=1 {
+                for dy in -1..=1 {
+                    for dz in -1..=1 {
+                        let offset = [dx, dy, dz];
+                        if let [Some(x), Some(y), Some(z)] =
+                            [x.checked_add(dx), y.checked_add(dy), z.checked_add(dz)]
+                            && offset != [0, 0, 0]
+                        {
+                            expected.push(([x, y, z].morton_encode(), offset));
+                        }
+                    }
+                }
+            }
+
+            let mut all = neighbors::<[i16; 3]>(code).collect::<Vec<_>>();
+            all.sort();
+            let mut all_expected = expected.iter().map(|(code, _)| *code).collect::<Vec<_>>();
+            all_expected.sort();
+            assert_eq!(all, all_expected);
+
+            let mut faces = face_neighbors::<[i16; 3]>(code).collect::<Vec<_>>();
+            faces.sort();
+            let mut faces_expected = expected
+                .iter()
+                .filter(|(_, offset)| offset.iter().filter(|d| **d != 0).count() == 1)
+                .map(|(code, _)| *code)
+                .collect::<Vec<_>>();
+            faces_expected.sort();
+            assert_eq!(faces, faces_expected);
+        }
+    }
+
+    #[test]
+    fn it_adds_and_subtracts() {
+        let a = [1000u16, 65535, 7];
+        let b = [24u16, 2, 9];
+
+        assert_eq!(
+            <[u16; 3]>::morton_decode(add::<[u16; 3]>(a.morton_encode(), b.morton_encode())),
+            [1024, 1, 16]
+        );
+        assert_eq!(
+            <[u16; 3]>::morton_decode(sub::<[u16; 3]>(a.morton_encode(), b.morton_encode())),
+            [976, 65533, 65534]
+        );
+    }
+}
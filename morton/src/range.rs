@@ -0,0 +1,220 @@
+//! Morton codes of axis-aligned boxes.
+//!
+//! The codes of the points in a box are not contiguous, but they consist of
+//! contiguous runs. To find them, the box is split at the highest bit in which
+//! the codes of its corners differ: into a lower box, that ends at the LITMAX
+//! code, and an upper box, that starts at the BIGMIN code. This is repeated
+//! until a box covers all codes between its corners.
+
+use std::{
+    fmt::Debug,
+    marker::PhantomData,
+    ops::RangeInclusive,
+};
+
+use crate::{
+    MortonAxes,
+    MortonCode,
+};
+
+/// An axis-aligned box, given by the Morton codes of its corners.
+pub struct MortonBox<T: MortonAxes> {
+    min: T::Code,
+    max: T::Code,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T: MortonAxes> MortonBox<T> {
+    /// Box with the corners `a` and `b` (inclusive), which don't need to be
+    /// ordered.
+    pub fn new(a: T, b: T) -> Self {
+        Self::from_codes(a.morton_encode(), b.morton_encode())
+    }
+
+    /// Like [`new`](Self::new), but with the codes of the corners.
+    pub fn from_codes(a: T::Code, b: T::Code) -> Self {
+        let mut min = T::Code::ZERO;
+        let mut max = T::Code::ZERO;
+
+        // the masked codes of a coordinate are ordered like the coordinate
+        for axis in 0..T::DIMENSIONS {
+            let mask = T::axis_mask(axis);
+            min = min | (a & mask).min(b & mask);
+            max = max | (a & mask).max(b & mask);
+        }
+
+        Self {
+            min,
+            max,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Code of the minimum corner, which is the smallest code in the box.
+    pub fn min(&self) -> T::Code {
+        self.min
+    }
+
+    /// Code of the maximum corner, which is the largest code in the box.
+    pub fn max(&self) -> T::Code {
+        self.max
+    }
+
+    pub fn contains(&self, code: T::Code) -> bool {
+        (0..T::DIMENSIONS).all(|axis| {
+            let mask = T::axis_mask(axis);
+            (self.min & mask..=self.max & mask).contains(&(code & mask))
+        })
+    }
+
+    /// Splits the box into the part with codes up to LITMAX and the part with
+    /// codes from BIGMIN, or returns `None` if all codes between its corners
+    /// are in the box.
+    pub fn split(&self) -> Option<(Self, Self)> {
+        let diff = self.min ^ self.max;
+        if diff == T::Code::ZERO {
+            return None;
+        }
+
+        let bit_index = T::Code::BITS - 1 - diff.leading_zeros();
+        let bit = T::Code::ONE << bit_index;
+        let below = bit.wrapping_sub(T::Code::ONE);
+
+        if self.min & below == T::Code::ZERO && self.max & below == below {
+            return None;
+        }
+
+        let axis = T::DIMENSIONS - 1 - bit_index as usize % T::DIMENSIONS;
+        let axis_below = T::axis_mask(axis) & below;
+
+        let litmax = (self.max & !bit) | axis_below;
+        let bigmin = (self.min & !axis_below) | bit;
+
+        Some((
+            Self {
+                min: self.min,
+                max: litmax,
+                _marker: PhantomData,
+            },
+            Self {
+                min: bigmin,
+                max: self.max,
+                _marker: PhantomData,
+            },
+        ))
+    }
+
+    /// The contiguous runs of codes in the box, in ascending order.
+    pub fn ranges(&self) -> Ranges<T> {
+        Ranges {
+            stack: vec![*self],
+            run: None,
+        }
+    }
+
+    /// All codes in the box, in ascending order.
+    pub fn codes(&self) -> impl Iterator<Item = T::Code> + use<T> {
+        self.ranges().flat_map(|range| {
+            let (start, end) = range.into_inner();
+            std::iter::successors(Some(start), move |code| {
+                (*code != end).then(|| code.wrapping_add(T::Code::ONE))
+            })
+        })
+    }
+}
+
+impl<T: MortonAxes> Clone for MortonBox<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T: MortonAxes> Copy for MortonBox<T> {}
+
+impl<T: MortonAxes> Debug for MortonBox<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MortonBox")
+            .field("min", &self.min)
+            .field("max", &self.max)
+            .finish()
+    }
+}
+
+/// Iterator over the contiguous runs of codes in a [`MortonBox`].
+#[derive(Debug)]
+pub struct Ranges<T: MortonAxes> {
+    stack: Vec<MortonBox<T>>,
+    run: Option<(T::Code, T::Code)>,
+}
+
+impl<T: MortonAxes> Iterator for Ranges<T> {
+    type Item = RangeInclusive<T::Code>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(morton_box) = self.stack.pop() {
+            if let Some((lower, upper)) = morton_box.split() {
+                self.stack.push(upper);
+                self.stack.push(lower);
+                continue;
+            }
+
+            // boxes that end up next to each other are merged
+            match &mut self.run {
+                Some((_, end)) if end.wrapping_add(T::Code::ONE) == morton_box.min => {
+                    *end = morton_box.max;
+                }
+                run => {
+                    if let Some((start, end)) = run.replace((morton_box.min, morton_box.max)) {
+                        return Some(start..=end);
+                    }
+                }
+            }
+        }
+
+        self.run.take().map(|(start, end)| start..=end)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        Morton,
+        range::MortonBox,
+    };
+
+    #[test]
+    fn it_finds_all_codes_in_a_box() {
+        let corners: [([i8; 3], [i8; 3]); 4] = [
+            ([-3, 0, 2], [4, 5, 9]),
+            ([7, -1, -8], [0, 2, 1]),
+            ([1, 1, 1], [1, 1, 1]),
+            ([-4, -4, -4], [3, 3, 3]),
+        ];
+
+        for (a, b) in corners {
+            let morton_box = MortonBox::new(a, b);
+
+            let mut expected = vec![];
+            for x in a[0].min(b[0])..=a[0].max(b[0]) {
+                for y in a[1].min(b[1])..=a[1].max(b[1]) {
+                    for z in a[2].min(b[2])..=a[2].max(b[2]) {
+                        expected.push([x, y, z].morton_encode());
+                    }
+                }
+            }
+            expected.sort();
+
+            assert_eq!(morton_box.codes().collect::<Vec<_>>(), expected);
+            assert!(expected.iter().all(|code| morton_box.contains(*code)));
+
+            let ranges = morton_box.ranges().collect::<Vec<_>>();
+            assert!(ranges.windows(2).all(|r| *r[0].end() + 1 < *r[1].start()));
+        }
+
+        // the whole space is one run
+        let ranges = MortonBox::new([0u8; 2], [u8::MAX; 2])
+            .ranges()
+            .collect::<Vec<_>>();
+        assert_eq!(ranges, [0..=u16::MAX]);
+    }
+}
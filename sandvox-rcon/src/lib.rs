@@ -1,3 +1,12 @@
+use std::{
+    num::{
+        ParseFloatError,
+        ParseIntError,
+    },
+    path::PathBuf,
+    str::FromStr,
+};
+
 use serde::{
     Deserialize,
     Serialize,
@@ -7,24 +16,451 @@ use serde::{
 #[serde(transparent)]
 pub struct Entity(pub u64);
 
-#[derive(Clone, Copy, Debug, Serialize, Deserialize, clap::Args)]
-pub struct Vec3 {
-    pub x: f32,
-    pub y: f32,
-    pub z: f32,
+/// Position of a block, written as `x,y,z`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlockPosition {
+    pub x: i32,
+    pub y: i32,
+    pub z: i32,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum InvalidBlockPosition {
+    #[error("Expected 3 comma-separated coordinates")]
+    NumCoordinates,
+
+    #[error("Invalid coordinate")]
+    Coordinate(#[from] ParseIntError),
 }
 
+impl FromStr for BlockPosition {
+    type Err = InvalidBlockPosition;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut coordinates = s.split(',').map(|c| c.trim().parse::<i32>());
+        let mut next = || {
+            coordinates
+                .next()
+                .ok_or(InvalidBlockPosition::NumCoordinates)
+        };
+
+        let position = Self {
+            x: next()??,
+            y: next()??,
+            z: next()??,
+        };
+
+        if coordinates.next().is_some() {
+            return Err(InvalidBlockPosition::NumCoordinates);
+        }
+
+        Ok(position)
+    }
+}
+
+/// Teleport an entity, by default the player.
 #[derive(Clone, Debug, Serialize, Deserialize, clap::Parser)]
+#[clap(subcommand_negates_reqs = true)]
 pub struct TeleportCommand {
-    #[clap(short, long)]
+    #[clap(short, long, global = true)]
     pub entity: Option<Entity>,
 
-    #[clap(flatten)]
-    pub destination: Vec3,
+    /// Either the name of a saved location, or the x, y and z coordinates.
+    /// Coordinates prefixed with `~` are relative to the entity's position,
+    /// e.g. `~ ~10 ~`.
+    #[clap(
+        required = true,
+        num_args = 1..=3,
+        value_names = ["X|LOCATION", "Y", "Z"],
+        allow_negative_numbers = true,
+    )]
+    pub destination: Vec<String>,
+
+    #[clap(subcommand)]
+    pub action: Option<TeleportAction>,
+}
+
+impl TeleportCommand {
+    pub fn destination(&self) -> Result<Destination, InvalidDestination> {
+        match self.destination.as_slice() {
+            [name] => Ok(Destination::Location(name.clone())),
+            [x, y, z] => Ok(Destination::Position([x.parse()?, y.parse()?, z.parse()?])),
+            _ => Err(InvalidDestination::NumArgs(self.destination.len())),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, clap::Subcommand)]
+#[serde(rename_all = "kebab-case")]
+pub enum TeleportAction {
+    /// Save the entity's position as a named location.
+    Save { name: String },
+
+    /// Remove a saved location.
+    Remove { name: String },
+
+    /// Write all saved locations to the server log.
+    List,
+
+    /// Teleport back to where the entity was before its last teleport.
+    Back,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Destination {
+    Position([Coordinate; 3]),
+    Location(String),
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum InvalidDestination {
+    #[error("Expected a location name or 3 coordinates, but got {0} arguments")]
+    NumArgs(usize),
+
+    #[error("Invalid coordinate")]
+    Coordinate(#[from] ParseFloatError),
+}
+
+/// A coordinate of a teleport destination.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Coordinate {
+    Absolute(f32),
+
+    /// Offset from the current position. Written as `~` followed by an
+    /// optional offset.
+    Relative(f32),
+}
+
+impl Coordinate {
+    pub fn resolve(&self, current: f32) -> f32 {
+        match self {
+            Self::Absolute(value) => *value,
+            Self::Relative(offset) => current + offset,
+        }
+    }
+}
+
+impl FromStr for Coordinate {
+    type Err = ParseFloatError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(offset) = s.strip_prefix('~') {
+            if offset.is_empty() {
+                Ok(Self::Relative(0.0))
+            }
+            else {
+                Ok(Self::Relative(offset.parse()?))
+            }
+        }
+        else {
+            Ok(Self::Absolute(s.parse()?))
+        }
+    }
+}
+
+/// Modify the game clock.
+#[derive(Clone, Debug, Serialize, Deserialize, clap::Subcommand)]
+#[serde(rename_all = "kebab-case")]
+pub enum TimeCommand {
+    /// Set the time. Accepts either an RFC 3339 date-time, or a time of day
+    /// (`HH:MM`) which keeps the current date.
+    Set { time: String },
+
+    /// Advance (or with a negative value rewind) the time.
+    Add {
+        #[clap(allow_negative_numbers = true)]
+        seconds: f64,
+    },
+
+    /// Stop the clock.
+    Freeze,
+
+    /// Resume the clock.
+    Unfreeze,
+}
+
+/// Profile the game.
+#[derive(Clone, Debug, Serialize, Deserialize, clap::Subcommand)]
+#[serde(rename_all = "kebab-case")]
+pub enum ProfileCommand {
+    /// Record CPU spans, GPU spans and memory usage, and write them to a
+    /// Chrome trace file (viewable with `chrome://tracing` or Perfetto).
+    Capture {
+        seconds: f64,
+
+        /// Path of the trace file, relative to the server's data directory.
+        path: PathBuf,
+    },
+}
+
+/// Write statistics to the server log.
+#[derive(Clone, Debug, Serialize, Deserialize, clap::Subcommand)]
+#[serde(rename_all = "kebab-case")]
+pub enum StatsCommand {
+    /// CPU time spent in each system, most expensive first.
+    Systems {
+        /// Number of systems to list.
+        #[clap(short = 'n', long, default_value_t = 20)]
+        count: usize,
+    },
+}
+
+/// Inspect the connections to clients.
+#[derive(Clone, Debug, Serialize, Deserialize, clap::Subcommand)]
+#[serde(rename_all = "kebab-case")]
+pub enum NetCommand {
+    /// Write traffic, packet loss and replication statistics of every
+    /// connection to the server log.
+    Stats,
+}
+
+/// Save or restore the reflectable components of all entities.
+#[derive(Clone, Debug, Serialize, Deserialize, clap::Subcommand)]
+#[serde(rename_all = "kebab-case")]
+pub enum SnapshotCommand {
+    /// Write a snapshot of the world to a RON file.
+    Save {
+        /// Path of the snapshot file, relative to the server's data directory.
+        path: PathBuf,
+
+        /// Only include these components (type path or short type path).
+        #[clap(short, long = "component")]
+        components: Vec<String>,
+    },
+
+    /// Restore a snapshot from a RON file.
+    Load {
+        /// Path of the snapshot file, relative to the server's data directory.
+        path: PathBuf,
+    },
+}
+
+/// Manage waypoints, which are shown on the compass.
+#[derive(Clone, Debug, Serialize, Deserialize, clap::Subcommand)]
+#[serde(rename_all = "kebab-case")]
+pub enum WaypointCommand {
+    /// Add a waypoint, or move it if a waypoint with that name exists.
+    Set {
+        name: String,
+
+        /// Position of the waypoint. Defaults to the player's position.
+        #[clap(requires_all = ["y", "z"])]
+        x: Option<f32>,
+        y: Option<f32>,
+        z: Option<f32>,
+
+        /// Show a beacon beam at the waypoint.
+        #[clap(short, long)]
+        beacon: bool,
+    },
+
+    /// Remove a waypoint.
+    Remove { name: String },
+
+    /// Write all waypoints to the server log.
+    List,
+}
+
+/// Save the blocks in a cuboid to a schematic file.
+#[derive(Clone, Debug, Serialize, Deserialize, clap::Parser)]
+pub struct ExportCommand {
+    /// One corner of the cuboid.
+    #[clap(allow_hyphen_values = true)]
+    pub min: BlockPosition,
+
+    /// The opposite corner of the cuboid. It's included in the schematic.
+    #[clap(allow_hyphen_values = true)]
+    pub max: BlockPosition,
+
+    /// Path of the schematic file, relative to the server's data directory.
+    pub path: PathBuf,
+}
+
+/// Place the blocks from a schematic file into the world.
+#[derive(Clone, Debug, Serialize, Deserialize, clap::Parser)]
+pub struct ImportCommand {
+    /// Path of the schematic file, relative to the server's data directory.
+    pub path: PathBuf,
+
+    /// Where the minimum corner of the schematic is placed.
+    #[clap(allow_hyphen_values = true)]
+    pub position: BlockPosition,
+
+    /// Keep the blocks in the world where the schematic has air.
+    #[clap(long)]
+    pub skip_air: bool,
+}
+
+/// Generate all chunks around a position ahead of time. Progress is written to
+/// the server log.
+///
+/// The chunks stay loaded until the server exits, so at most 16384 chunks can
+/// be pregenerated at once.
+#[derive(Clone, Debug, Serialize, Deserialize, clap::Parser)]
+pub struct PregenerateCommand {
+    /// Horizontal radius in chunks.
+    pub radius: u32,
+
+    /// Vertical radius in chunks. Defaults to the horizontal radius.
+    #[clap(long)]
+    pub vertical_radius: Option<u32>,
+
+    /// Block position at the center. Defaults to the player's position.
+    #[clap(short, long, allow_hyphen_values = true)]
+    pub center: Option<BlockPosition>,
+}
+
+/// Make something explode.
+#[derive(Clone, Debug, Serialize, Deserialize, clap::Parser)]
+pub struct ExplodeCommand {
+    /// Radius in blocks.
+    #[clap(default_value_t = 4.0)]
+    pub radius: f32,
+
+    /// Strength at the center, which is compared to the blocks' resistance.
+    /// Defaults to twice the radius.
+    #[clap(long)]
+    pub power: Option<f32>,
+
+    /// Block position at the center. Defaults to the block the player is
+    /// looking at.
+    #[clap(short, long, allow_hyphen_values = true)]
+    pub center: Option<BlockPosition>,
+}
+
+/// Set all blocks in a region to one block type.
+#[derive(Clone, Debug, Serialize, Deserialize, clap::Parser)]
+pub struct FillCommand {
+    /// One corner of the region.
+    #[clap(allow_hyphen_values = true)]
+    pub from: BlockPosition,
+
+    /// The opposite corner of the region (inclusive).
+    #[clap(allow_hyphen_values = true)]
+    pub to: BlockPosition,
+
+    /// Name of the block type, e.g. `stone` or `air`.
+    pub block: String,
+}
+
+/// Select a region and edit its blocks.
+#[derive(Clone, Debug, Serialize, Deserialize, clap::Subcommand)]
+#[serde(rename_all = "kebab-case")]
+pub enum RegionCommand {
+    /// Set the first corner of the selection. Defaults to the block the
+    /// player is looking at.
+    Pos1 {
+        #[clap(allow_hyphen_values = true)]
+        position: Option<BlockPosition>,
+    },
+
+    /// Set the second corner of the selection. Defaults to the block the
+    /// player is looking at.
+    Pos2 {
+        #[clap(allow_hyphen_values = true)]
+        position: Option<BlockPosition>,
+    },
+
+    /// Clear the selection.
+    Clear,
+
+    /// Set all blocks in the selection to one block type.
+    Set {
+        /// Name of the block type, e.g. `stone` or `air`.
+        block: String,
+    },
+
+    /// Replace one block type with another in the selection.
+    Replace { from: String, to: String },
+
+    /// Copy the blocks in the selection to the clipboard.
+    Copy,
+
+    /// Place the blocks from the clipboard into the world.
+    Paste {
+        /// Where the minimum corner is placed. Defaults to the block in front
+        /// of the one the player is looking at.
+        #[clap(allow_hyphen_values = true)]
+        position: Option<BlockPosition>,
+
+        /// Keep the blocks in the world where the clipboard has air.
+        #[clap(long)]
+        skip_air: bool,
+    },
+}
+
+/// Revert the player's last edits.
+#[derive(Clone, Debug, Serialize, Deserialize, clap::Parser)]
+pub struct UndoCommand {
+    /// Number of edits to undo.
+    #[clap(default_value_t = 1)]
+    pub count: usize,
+}
+
+/// Apply edits reverted with `undo` again.
+#[derive(Clone, Debug, Serialize, Deserialize, clap::Parser)]
+pub struct RedoCommand {
+    /// Number of edits to redo.
+    #[clap(default_value_t = 1)]
+    pub count: usize,
+}
+
+/// Switch the player's game mode, or write it to the server log.
+#[derive(Clone, Debug, Serialize, Deserialize, clap::Parser)]
+pub struct GameModeCommand {
+    pub mode: Option<GameMode>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum GameMode {
+    /// Fly, break blocks instantly and have infinite items.
+    Creative,
+
+    /// Walk, take time to break blocks and collect items.
+    Survival,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize, clap::Subcommand)]
 #[serde(rename_all = "kebab-case")]
 pub enum Command {
+    #[clap(name = "teleport", visible_alias = "tp")]
     TeleportCommand(TeleportCommand),
+
+    #[clap(subcommand)]
+    Time(TimeCommand),
+
+    #[clap(subcommand)]
+    Profile(ProfileCommand),
+
+    #[clap(subcommand)]
+    Stats(StatsCommand),
+
+    #[clap(subcommand)]
+    Net(NetCommand),
+
+    #[clap(subcommand)]
+    Snapshot(SnapshotCommand),
+
+    #[clap(subcommand)]
+    Waypoint(WaypointCommand),
+
+    Export(ExportCommand),
+
+    Import(ImportCommand),
+
+    Pregenerate(PregenerateCommand),
+
+    Explode(ExplodeCommand),
+
+    Fill(FillCommand),
+
+    #[clap(subcommand)]
+    Region(RegionCommand),
+
+    Undo(UndoCommand),
+
+    Redo(RedoCommand),
+
+    GameMode(GameModeCommand),
 }
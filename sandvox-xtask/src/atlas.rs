@@ -0,0 +1,357 @@
+//! Packs the block textures and UI sprites like the game's texture atlas does,
+//! and checks them for problems.
+//!
+//! This reports:
+//!
+//! - images that can't be loaded, or sprites that are outside of their source
+//!   image,
+//! - nine-patch margins that don't fit into their sprite,
+//! - images that don't fit into the atlas, even with its padding,
+//! - block textures that aren't a power of two, or don't have the same size as
+//!   most block textures (they're tiled and should have the same texel
+//!   density),
+//! - duplicate images,
+//! - how much space the atlas wastes.
+
+use std::{
+    collections::{
+        BTreeMap,
+        BTreeSet,
+        HashMap,
+    },
+    fmt::Display,
+    path::{
+        Path,
+        PathBuf,
+    },
+};
+
+use color_eyre::eyre::{
+    Error,
+    bail,
+};
+use image::{
+    GenericImage,
+    GenericImageView,
+    RgbaImage,
+};
+use serde::Deserialize;
+
+#[derive(Clone, Debug, clap::Args)]
+pub struct CheckAtlasArgs {
+    #[clap(long, default_value = "assets/blocks.toml")]
+    pub blocks: PathBuf,
+
+    #[clap(long, default_value = "assets/ui.toml")]
+    pub sprites: PathBuf,
+
+    /// Padding around each image in the atlas.
+    #[clap(long, default_value = "1")]
+    pub padding: u32,
+
+    #[clap(long, default_value = "256")]
+    pub initial_size: u32,
+
+    #[clap(long, default_value = "8192")]
+    pub size_limit: u32,
+
+    /// Write the packed atlas to this file.
+    #[clap(short, long)]
+    pub output: Option<PathBuf>,
+}
+
+pub fn check_atlas(args: &CheckAtlasArgs) -> Result<(), Error> {
+    let mut problems = Problems::default();
+
+    let mut inputs = load_block_textures(&args.blocks, &mut problems)?;
+    inputs.extend(load_sprites(&args.sprites, &mut problems)?);
+
+    check_block_texture_sizes(&inputs, &mut problems);
+    check_duplicates(&inputs, &mut problems);
+
+    if let Some(atlas) = pack(&inputs, args, &mut problems) {
+        let used = inputs
+            .iter()
+            .map(|input| u64::from(input.image.width()) * u64::from(input.image.height()))
+            .sum::<u64>();
+        let total = u64::from(atlas.width()) * u64::from(atlas.height());
+
+        println!(
+            "packed {} images into a {}x{} atlas, {:.1}% wasted",
+            inputs.len(),
+            atlas.width(),
+            atlas.height(),
+            100.0 * (1.0 - used as f64 / total as f64)
+        );
+
+        if let Some(output) = &args.output {
+            atlas.save(output)?;
+        }
+    }
+
+    problems.print();
+
+    if problems.num_errors > 0 {
+        bail!("{} errors found", problems.num_errors);
+    }
+
+    Ok(())
+}
+
+#[derive(Debug)]
+struct Input {
+    name: String,
+    image: RgbaImage,
+    is_block_texture: bool,
+}
+
+#[derive(Debug, Default)]
+struct Problems {
+    messages: Vec<(Severity, String, String)>,
+    num_errors: usize,
+}
+
+impl Problems {
+    fn error(&mut self, input: impl Display, message: impl Display) {
+        self.messages
+            .push((Severity::Error, input.to_string(), message.to_string()));
+        self.num_errors += 1;
+    }
+
+    fn warning(&mut self, input: impl Display, message: impl Display) {
+        self.messages
+            .push((Severity::Warning, input.to_string(), message.to_string()));
+    }
+
+    fn print(&self) {
+        for (severity, input, message) in &self.messages {
+            println!("{severity:?}: {input}: {message}");
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Severity {
+    Error,
+    Warning,
+}
+
+#[derive(Debug, Deserialize)]
+struct SpriteDef {
+    source: PathBuf,
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+    nine_patch: Option<NinePatchDef>,
+}
+
+#[derive(Debug, Deserialize)]
+struct NinePatchDef {
+    margin: u32,
+}
+
+fn load_block_textures(path: &Path, problems: &mut Problems) -> Result<Vec<Input>, Error> {
+    let directory = path.parent().unwrap_or(Path::new("."));
+    let blocks: toml::Table = toml::from_slice(&std::fs::read(path)?)?;
+
+    // textures are loaded once, even if multiple blocks use them
+    let mut paths = BTreeSet::new();
+    for block in blocks.values() {
+        match block.get("texture") {
+            Some(toml::Value::String(path)) => {
+                paths.insert(path.clone());
+            }
+            Some(toml::Value::Table(faces)) => {
+                paths.extend(
+                    faces
+                        .values()
+                        .filter_map(|path| path.as_str().map(ToOwned::to_owned)),
+                );
+            }
+            _ => {}
+        }
+    }
+
+    let mut inputs = Vec::with_capacity(paths.len());
+
+    for path in paths {
+        match image::open(directory.join(&path)) {
+            Ok(image) => {
+                inputs.push(Input {
+                    name: path,
+                    image: image.into_rgba8(),
+                    is_block_texture: true,
+                });
+            }
+            Err(error) => problems.error(&path, error),
+        }
+    }
+
+    Ok(inputs)
+}
+
+fn load_sprites(path: &Path, problems: &mut Problems) -> Result<Vec<Input>, Error> {
+    let directory = path.parent().unwrap_or(Path::new("."));
+    let sprites: BTreeMap<String, SpriteDef> = toml::from_slice(&std::fs::read(path)?)?;
+
+    let mut sources = HashMap::new();
+    let mut inputs = Vec::with_capacity(sprites.len());
+
+    for (name, sprite) in sprites {
+        let name = format!("sprite `{name}`");
+
+        if !sources.contains_key(&sprite.source) {
+            match image::open(directory.join(&sprite.source)) {
+                Ok(image) => {
+                    sources.insert(sprite.source.clone(), image.into_rgba8());
+                }
+                Err(error) => {
+                    problems.error(&name, format_args!("{}: {error}", sprite.source.display()));
+                    continue;
+                }
+            }
+        }
+        let source = &sources[&sprite.source];
+
+        if sprite.x + sprite.width > source.width() || sprite.y + sprite.height > source.height() {
+            problems.error(
+                &name,
+                format_args!(
+                    "outside of {} ({}x{})",
+                    sprite.source.display(),
+                    source.width(),
+                    source.height()
+                ),
+            );
+            continue;
+        }
+
+        if let Some(nine_patch) = sprite.nine_patch
+            && 2 * nine_patch.margin >= sprite.width.min(sprite.height)
+        {
+            problems.error(
+                &name,
+                format_args!(
+                    "nine-patch margin {} doesn't fit into {}x{}",
+                    nine_patch.margin, sprite.width, sprite.height
+                ),
+            );
+        }
+
+        inputs.push(Input {
+            name,
+            image: source
+                .view(sprite.x, sprite.y, sprite.width, sprite.height)
+                .to_image(),
+            is_block_texture: false,
+        });
+    }
+
+    Ok(inputs)
+}
+
+fn check_block_texture_sizes(inputs: &[Input], problems: &mut Problems) {
+    let mut counts = HashMap::new();
+    for input in inputs.iter().filter(|input| input.is_block_texture) {
+        *counts.entry(input.image.dimensions()).or_insert(0) += 1;
+    }
+
+    let most_common = counts
+        .into_iter()
+        .max_by_key(|(size, count)| (*count, *size))
+        .map(|(size, _)| size);
+
+    for input in inputs.iter().filter(|input| input.is_block_texture) {
+        let (width, height) = input.image.dimensions();
+
+        if !width.is_power_of_two() || !height.is_power_of_two() {
+            problems.warning(
+                &input.name,
+                format_args!("size {width}x{height} is not a power of two"),
+            );
+        }
+
+        if let Some((common_width, common_height)) = most_common
+            && (width, height) != (common_width, common_height)
+        {
+            problems.warning(
+                &input.name,
+                format_args!(
+                    "size {width}x{height} differs from most block textures ({common_width}x{common_height})"
+                ),
+            );
+        }
+    }
+}
+
+fn check_duplicates(inputs: &[Input], problems: &mut Problems) {
+    let mut seen: HashMap<(u32, u32, &[u8]), &str> = HashMap::new();
+
+    for input in inputs {
+        let key = (
+            input.image.width(),
+            input.image.height(),
+            input.image.as_raw().as_slice(),
+        );
+        if let Some(first) = seen.get(&key) {
+            problems.warning(&input.name, format_args!("same image as {first}"));
+        }
+        else {
+            seen.insert(key, &input.name);
+        }
+    }
+}
+
+/// Packs the images in the order the game inserts them.
+fn pack(inputs: &[Input], args: &CheckAtlasArgs, problems: &mut Problems) -> Option<RgbaImage> {
+    let to_size = |width: u32, height: u32| guillotiere::size2(width as i32, height as i32);
+
+    let mut size = args.initial_size;
+    let mut allocator = guillotiere::AtlasAllocator::new(to_size(size, size));
+    let mut placements = Vec::with_capacity(inputs.len());
+
+    for input in inputs {
+        let width = input.image.width() + 2 * args.padding;
+        let height = input.image.height() + 2 * args.padding;
+
+        if width > args.size_limit || height > args.size_limit {
+            problems.error(
+                &input.name,
+                format_args!(
+                    "{width}x{height} with padding doesn't fit into the atlas size limit of {}",
+                    args.size_limit
+                ),
+            );
+            continue;
+        }
+
+        loop {
+            if let Some(allocation) = allocator.allocate(to_size(width, height)) {
+                placements.push((input, allocation.rectangle.min));
+                break;
+            }
+            else if size < args.size_limit {
+                size = (2 * size).min(args.size_limit);
+                allocator.grow(to_size(size, size));
+            }
+            else {
+                problems.error(&input.name, "atlas is full");
+                return None;
+            }
+        }
+    }
+
+    let mut atlas = RgbaImage::new(size, size);
+    for (input, position) in placements {
+        atlas
+            .copy_from(
+                &input.image,
+                position.x as u32 + args.padding,
+                position.y as u32 + args.padding,
+            )
+            .unwrap();
+    }
+
+    Some(atlas)
+}
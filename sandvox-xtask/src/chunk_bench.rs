@@ -0,0 +1,237 @@
+//! Benchmarks the terrain generator and the greedy mesher without a GPU.
+//!
+//! Chunks are generated for a cuboid of chunk positions around the origin, for
+//! each seed and radius, and the non-empty chunks are meshed into CPU buffers.
+
+use std::{
+    path::PathBuf,
+    time::Instant,
+};
+
+use color_eyre::eyre::Error;
+use nalgebra::Point3;
+use sandvox::{
+    game::{
+        ChunkShape,
+        block_type::BlockTypes,
+        terrain::{
+            TerrainGenerator,
+            TerrainVoxel,
+            WorldConfig,
+            WorldSeed,
+        },
+    },
+    render::mesh::MeshBuilder,
+    util::{
+        format_size,
+        percentile,
+        stats_alloc::{
+            self,
+            MemoryTag,
+            with_memory_tag,
+        },
+    },
+    voxel::{
+        BlockFace,
+        VoxelData,
+        chunk_generator::ChunkGenerator,
+        mesh::{
+            ChunkMesher,
+            greedy_quads::GreedyMesher,
+        },
+    },
+};
+
+#[derive(Clone, Debug, clap::Args)]
+pub struct ChunkBenchArgs {
+    #[clap(long, default_value = "assets/blocks.toml")]
+    pub blocks: PathBuf,
+
+    /// World seeds to generate chunks for. Can be given multiple times.
+    #[clap(short, long = "seed", default_value = "0")]
+    pub seeds: Vec<u64>,
+
+    /// Horizontal radius in chunks. Can be given multiple times.
+    #[clap(short, long = "radius", default_value = "4")]
+    pub radii: Vec<u32>,
+
+    /// Vertical radius in chunks.
+    #[clap(long, default_value = "2")]
+    pub vertical_radius: u32,
+
+    /// Print the timings of every chunk.
+    #[clap(long)]
+    pub per_chunk: bool,
+}
+
+pub fn run(args: &ChunkBenchArgs) -> Result<(), Error> {
+    // the textures don't matter, but the ids should be distinct, so the mesher
+    // doesn't merge different textures
+    let mut num_textures = 0;
+    let block_types = BlockTypes::load(&args.blocks, |_path, _image| {
+        num_textures += 1;
+        Ok(num_textures)
+    })?;
+
+    for seed in &args.seeds {
+        let world_config = WorldConfig {
+            seed: WorldSeed(*seed),
+            ..Default::default()
+        };
+        let generator = TerrainGenerator::new(&world_config, &block_types);
+
+        for radius in &args.radii {
+            let radius = *radius as i32;
+            let vertical_radius = args.vertical_radius as i32;
+
+            let positions = (-radius..=radius).flat_map(|x| {
+                (-vertical_radius..=vertical_radius)
+                    .flat_map(move |y| (-radius..=radius).map(move |z| Point3::new(x, y, z)))
+            });
+
+            let results = bench_chunks(&generator, &block_types, positions, args.per_chunk);
+            results.print(*seed, radius);
+        }
+    }
+
+    Ok(())
+}
+
+fn bench_chunks(
+    generator: &TerrainGenerator,
+    block_types: &BlockTypes<u32>,
+    positions: impl Iterator<Item = Point3<i32>>,
+    per_chunk: bool,
+) -> Results {
+    let shape = ChunkShape::default();
+    let voxel_data = BenchVoxelData(block_types.clone());
+    let mut mesher = <GreedyMesher<TerrainVoxel> as ChunkMesher<_, ChunkShape>>::new(&shape);
+    let mut mesh_builder = MeshBuilder::default();
+
+    let mut results = Results::default();
+    let mut chunks = vec![];
+    let bytes_before = stats_alloc::bytes_allocated_with_tag(MemoryTag::Chunks);
+
+    for position in positions {
+        if ChunkGenerator::<TerrainVoxel, ChunkShape>::early_discard(generator, position, &shape) {
+            continue;
+        }
+
+        let start_time = Instant::now();
+        let chunk = with_memory_tag(MemoryTag::Chunks, || {
+            generator.generate_chunk(position, shape)
+        });
+        let generate_time = start_time.elapsed();
+        results
+            .generate_times
+            .push(generate_time.as_secs_f64() * 1e6);
+
+        let Some(chunk) = chunk
+        else {
+            results.num_empty += 1;
+            if per_chunk {
+                println!("{position:?}: generated in {generate_time:?}, empty");
+            }
+            continue;
+        };
+
+        let start_time = Instant::now();
+        with_memory_tag(MemoryTag::Meshing, || {
+            mesher.mesh_chunk(&chunk, &mut mesh_builder, &voxel_data);
+        });
+        let mesh_time = start_time.elapsed();
+        results.mesh_times.push(mesh_time.as_secs_f64() * 1e6);
+        results.num_faces += mesh_builder.num_faces();
+        results.peak_meshing_bytes = results
+            .peak_meshing_bytes
+            .max(stats_alloc::bytes_allocated_with_tag(MemoryTag::Meshing));
+
+        if per_chunk {
+            println!(
+                "{position:?}: generated in {generate_time:?}, meshed in {mesh_time:?}, {} faces",
+                mesh_builder.num_faces()
+            );
+        }
+
+        mesh_builder.clear();
+
+        // keep the chunks, to measure how much memory they use
+        chunks.push(chunk);
+    }
+
+    results.chunk_bytes =
+        stats_alloc::bytes_allocated_with_tag(MemoryTag::Chunks).saturating_sub(bytes_before);
+
+    results
+}
+
+#[derive(Debug, Default)]
+struct Results {
+    /// In microseconds.
+    generate_times: Vec<f64>,
+
+    /// In microseconds.
+    mesh_times: Vec<f64>,
+
+    num_empty: usize,
+    num_faces: usize,
+    chunk_bytes: usize,
+    peak_meshing_bytes: usize,
+}
+
+impl Results {
+    fn print(mut self, seed: u64, radius: i32) {
+        self.generate_times.sort_by(f64::total_cmp);
+        self.mesh_times.sort_by(f64::total_cmp);
+
+        let stats = |times: &[f64]| {
+            format!(
+                "mean {:.1} µs, p50 {:.1} µs, p99 {:.1} µs",
+                times.iter().sum::<f64>() / times.len().max(1) as f64,
+                percentile(times, 0.5),
+                percentile(times, 0.99)
+            )
+        };
+
+        println!("seed {seed}, radius {radius}:");
+        println!(
+            "  generated {} chunks ({} empty): {}",
+            self.generate_times.len(),
+            self.num_empty,
+            stats(&self.generate_times)
+        );
+        println!(
+            "  meshed {} chunks ({} faces): {}",
+            self.mesh_times.len(),
+            self.num_faces,
+            stats(&self.mesh_times)
+        );
+        println!(
+            "  chunk memory: {}, peak meshing memory: {}",
+            format_size(self.chunk_bytes),
+            format_size(self.peak_meshing_bytes)
+        );
+    }
+}
+
+/// Same as the game's [`VoxelData`] for [`BlockTypes`], but the texture ids
+/// don't come from an atlas.
+#[derive(Clone, Debug)]
+struct BenchVoxelData(BlockTypes<u32>);
+
+impl VoxelData<TerrainVoxel> for BenchVoxelData {
+    #[inline]
+    fn texture(&self, voxel: &TerrainVoxel, face: BlockFace) -> Option<u32> {
+        self.0[voxel.block_type].face_texture(face).copied()
+    }
+
+    #[inline]
+    fn is_opaque(&self, voxel: &TerrainVoxel) -> bool {
+        self.0[voxel.block_type].is_opaque
+    }
+
+    #[inline]
+    fn can_merge(&self, first: &TerrainVoxel, second: &TerrainVoxel) -> bool {
+        first.block_type == second.block_type
+    }
+}
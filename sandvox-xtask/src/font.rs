@@ -0,0 +1,145 @@
+//! Prebuilds font sheets, so the game doesn't have to lay out the glyphs (and
+//! generate distance fields) when it starts. TTF fonts are rasterized at a
+//! fixed size.
+
+use std::path::{
+    Path,
+    PathBuf,
+};
+
+use ab_glyph::{
+    Font,
+    FontVec,
+    ScaleFont,
+};
+use color_eyre::eyre::{
+    Error,
+    bail,
+};
+use image::{
+    GrayImage,
+    Luma,
+};
+use nalgebra::Vector2;
+use sandvox::render::text::{
+    FontSheet,
+    GlyphBitmap,
+};
+
+#[derive(Clone, Debug, clap::Args)]
+pub struct MakeFontSheetArgs {
+    /// Where to write the font sheet. The image is written next to it.
+    #[clap(short, long)]
+    pub output: PathBuf,
+
+    /// Height of a line in pixels. Only used for TTF fonts.
+    #[clap(short, long, default_value = "16")]
+    pub size: f32,
+
+    /// Generate the distance fields for the SDF font rendering.
+    #[clap(long)]
+    pub sdf: bool,
+
+    /// BDF or TTF font.
+    pub input: PathBuf,
+}
+
+pub fn make_font_sheet(args: &MakeFontSheetArgs) -> Result<(), Error> {
+    let mut sheet = match args
+        .input
+        .extension()
+        .and_then(|extension| extension.to_str())
+    {
+        Some("bdf") => FontSheet::from_bdf(&std::fs::read_to_string(&args.input)?)?,
+        Some("ttf" | "otf") => rasterize(&args.input, args.size)?,
+        _ => bail!("Unsupported font: {}", args.input.display()),
+    };
+
+    if args.sdf {
+        sheet = sheet.into_sdf();
+    }
+
+    sheet.save(&args.output)?;
+
+    println!(
+        "{} glyphs, {}x{} image",
+        sheet.num_glyphs(),
+        sheet.image().width(),
+        sheet.image().height()
+    );
+
+    Ok(())
+}
+
+fn rasterize(path: &Path, size: f32) -> Result<FontSheet, Error> {
+    let font = FontVec::try_from_vec(std::fs::read(path)?)?;
+    let scaled = font.as_scaled(size);
+
+    let mut codepoints = font
+        .codepoint_ids()
+        .filter(|(_, character)| !character.is_control())
+        .collect::<Vec<_>>();
+    codepoints.sort_by_key(|(_, character)| *character);
+
+    // the baseline is at the ascent, so that the glyphs are below the top of
+    // their cells
+    let mut outlines = Vec::with_capacity(codepoints.len());
+    let mut min = Vector2::<f32>::zeros();
+    let mut max = Vector2::new(0.0, scaled.height());
+
+    for (glyph_id, character) in codepoints {
+        let glyph = glyph_id.with_scale_and_position(size, ab_glyph::point(0.0, scaled.ascent()));
+        let outline = font.outline_glyph(glyph);
+
+        if let Some(outline) = &outline {
+            let bounds = outline.px_bounds();
+            min = min.inf(&Vector2::new(bounds.min.x, bounds.min.y));
+            max = max.sup(&Vector2::new(bounds.max.x, bounds.max.y));
+        }
+
+        outlines.push((character, outline));
+    }
+
+    let origin = min.map(f32::floor);
+    let cell_size = (max - origin).map(|c| c.ceil() as u32);
+
+    let glyphs = outlines
+        .into_iter()
+        .map(|(character, outline)| {
+            let Some(outline) = outline
+            else {
+                return GlyphBitmap {
+                    character,
+                    offset: Vector2::zeros(),
+                    bitmap: GrayImage::new(0, 0),
+                };
+            };
+
+            let bounds = outline.px_bounds();
+            let mut bitmap = GrayImage::new(bounds.width() as u32, bounds.height() as u32);
+            outline.draw(|x, y, coverage| {
+                if x < bitmap.width() && y < bitmap.height() {
+                    bitmap.put_pixel(x, y, Luma([(coverage * 255.0).round() as u8]));
+                }
+            });
+
+            GlyphBitmap {
+                character,
+                offset: (Vector2::new(bounds.min.x, bounds.min.y) - origin).map(|c| c as u32),
+                bitmap,
+            }
+        })
+        .collect();
+
+    let glyph_displacement = Vector2::new(
+        scaled.h_advance(font.glyph_id('0')),
+        scaled.height() + scaled.line_gap(),
+    );
+
+    Ok(FontSheet::new(
+        cell_size,
+        glyph_displacement,
+        glyphs,
+        Some(char::REPLACEMENT_CHARACTER),
+    ))
+}
@@ -1,6 +1,10 @@
+pub mod atlas;
+pub mod chunk_bench;
+pub mod font;
 pub mod model;
 pub mod skybox;
 pub mod tres;
+pub mod world;
 
 use std::path::PathBuf;
 
@@ -32,6 +36,17 @@ enum Command {
         #[clap(short, long, default_value = "1024")]
         size: u32,
 
+        /// Draw the stars from a catalog, instead of using a star map image as
+        /// a layer. It has the same format as the game's `stars.csv`: right
+        /// ascension and declination in degrees, visual magnitude and
+        /// optionally the B-V color index, separated by commas.
+        #[clap(long)]
+        star_catalog: Option<PathBuf>,
+
+        /// Faintest stars from the catalog that are drawn.
+        #[clap(long, default_value = "6.5")]
+        magnitude_limit: f32,
+
         layers: Vec<PathBuf>,
     },
     Rcon {
@@ -45,6 +60,24 @@ enum Command {
         #[clap(short, long)]
         json_output: Option<PathBuf>,
 
+        path: PathBuf,
+    },
+    /// Pack the block textures and sprites into an atlas and check them for
+    /// problems.
+    CheckAtlas(atlas::CheckAtlasArgs),
+    /// Lay out the glyphs of a BDF or TTF font into a font sheet, which the
+    /// game loads faster than the font itself.
+    MakeFontSheet(font::MakeFontSheetArgs),
+    /// Benchmark chunk generation and meshing without rendering.
+    ChunkBench(chunk_bench::ChunkBenchArgs),
+    /// Print the world config and game state stored in a world file.
+    WorldInfo { path: PathBuf },
+    /// Check a world file for corrupted data.
+    WorldFsck {
+        /// Repair the database and remove game state that can't be decoded.
+        #[clap(long)]
+        repair: bool,
+
         path: PathBuf,
     },
 }
@@ -64,10 +97,17 @@ async fn main() -> Result<(), Error> {
         Command::MakeSkybox {
             output,
             size,
-
+            star_catalog,
+            magnitude_limit,
             layers,
         } => {
-            skybox::make_skybox(layers, size, output)?;
+            skybox::make_skybox(
+                layers,
+                star_catalog.as_deref(),
+                magnitude_limit,
+                size,
+                output,
+            )?;
         }
         Command::Rcon { address, command } => {
             let mut client = RconClient::connect(&address).await?;
@@ -76,6 +116,21 @@ async fn main() -> Result<(), Error> {
         Command::PrintGltf { json_output, path } => {
             model::print(path, json_output.as_deref())?;
         }
+        Command::CheckAtlas(args) => {
+            atlas::check_atlas(&args)?;
+        }
+        Command::MakeFontSheet(args) => {
+            font::make_font_sheet(&args)?;
+        }
+        Command::ChunkBench(args) => {
+            chunk_bench::run(&args)?;
+        }
+        Command::WorldInfo { path } => {
+            world::print_info(path)?;
+        }
+        Command::WorldFsck { repair, path } => {
+            world::fsck(path, repair)?;
+        }
     }
 
     Ok(())
@@ -28,9 +28,12 @@ use rayon::iter::{
     IntoParallelRefMutIterator,
     ParallelIterator,
 };
+use sandvox::render::star_catalog::StarCatalog;
 
 pub fn make_skybox(
     layers: impl IntoIterator<Item = impl AsRef<Path>>,
+    star_catalog: Option<&Path>,
+    magnitude_limit: f32,
     size: u32,
     output: impl AsRef<Path>,
 ) -> Result<(), Error> {
@@ -58,7 +61,9 @@ pub fn make_skybox(
         })
         .collect::<Result<Vec<_>, Error>>()?;
 
-    if layers.is_empty() {
+    let star_catalog = star_catalog.map(StarCatalog::load).transpose()?;
+
+    if layers.is_empty() && star_catalog.is_none() {
         bail!("No layers provided");
     }
 
@@ -91,6 +96,10 @@ pub fn make_skybox(
                 });
         });
 
+    if let Some(star_catalog) = &star_catalog {
+        draw_stars(star_catalog, &mut face_images, magnitude_limit);
+    }
+
     const FILENAMES: [&str; 6] = ["px", "nx", "py", "ny", "pz", "nz"];
 
     for i in 0..6 {
@@ -147,6 +156,126 @@ fn map_uv(uv: Vector2<f32>, face: usize) -> Vector2<f32> {
     uv
 }
 
+/// Maps a direction to a face of the cube map and the pixel on it. This is the
+/// inverse of [`map_uv`]'s mapping from faces to directions.
+fn project(direction: Vector3<f32>, size: u32) -> (usize, Vector2<f32>) {
+    let w = direction / direction.abs().max();
+
+    let (face, uv) = if w.x.abs() >= 1.0 {
+        if w.x > 0.0 {
+            (0, Vector2::new(-w.z, -w.y))
+        }
+        else {
+            (1, Vector2::new(w.z, -w.y))
+        }
+    }
+    else if w.y.abs() >= 1.0 {
+        if w.y > 0.0 {
+            (2, Vector2::new(w.x, w.z))
+        }
+        else {
+            (3, Vector2::new(w.x, -w.z))
+        }
+    }
+    else if w.z > 0.0 {
+        (4, Vector2::new(w.x, -w.y))
+    }
+    else {
+        (5, Vector2::new(-w.x, -w.y))
+    };
+
+    let uv = 0.5 * (uv + Vector2::repeat(1.0));
+    (face, uv * (size - 1) as f32)
+}
+
+/// Magnitude at which stars are drawn with full brightness and size.
+/// Sirius, the brightest star, has -1.46.
+const BRIGHTEST_MAGNITUDE: f32 = -1.5;
+
+/// Brightness of stars at the magnitude limit, so they're still visible.
+const MIN_INTENSITY: f32 = 0.15;
+
+/// Draws all stars from the catalog up to the magnitude limit into the faces
+/// of the cube map, as a layer of the skybox instead of a star map image.
+/// Brighter stars are drawn larger and brighter.
+///
+/// The catalog has the same format as the `stars.csv` the game loads from the
+/// skybox directory, see [`StarCatalog::load`].
+fn draw_stars(star_catalog: &StarCatalog, face_images: &mut [RgbImage; 6], magnitude_limit: f32) {
+    let size = face_images[0].width();
+    let pixel_scale = size as f32 / 1024.0;
+
+    for star in &star_catalog.stars {
+        if star.magnitude > magnitude_limit {
+            continue;
+        }
+
+        // magnitudes are logarithmic, as is our perception of brightness
+        let t = ((magnitude_limit - star.magnitude) / (magnitude_limit - BRIGHTEST_MAGNITUDE))
+            .clamp(0.0, 1.0);
+        let intensity = MIN_INTENSITY + (1.0 - MIN_INTENSITY) * t;
+        let radius = ((0.6 + 1.4 * t) * pixel_scale).max(0.5);
+        let color = star.color_index.map_or(Vector3::repeat(1.0), star_color);
+
+        let (face, center) = project(star.direction, size);
+        let face_image = &mut face_images[face];
+
+        let extent = (2.0 * radius).ceil() as i32;
+        let center_pixel = center.map(|c| c.round() as i32);
+
+        for y in -extent..=extent {
+            for x in -extent..=extent {
+                let pixel = center_pixel + Vector2::new(x, y);
+                if pixel.x < 0 || pixel.y < 0 || pixel.x >= size as i32 || pixel.y >= size as i32 {
+                    continue;
+                }
+
+                let distance_squared = (pixel.cast::<f32>() - center).norm_squared();
+                let weight = (-distance_squared / (2.0 * radius * radius)).exp();
+
+                let target = face_image.get_pixel_mut(pixel.x as u32, pixel.y as u32);
+                for c in 0..3 {
+                    let value = (255.0 * intensity * weight * color[c]).round() as u8;
+                    target.0[c] = target.0[c].saturating_add(value);
+                }
+            }
+        }
+    }
+}
+
+/// Approximate color of a star with the given B-V color index.
+fn star_color(color_index: f32) -> Vector3<f32> {
+    // colors of O, B, A, F, G, K and M stars
+    const COLORS: [(f32, [u8; 3]); 6] = [
+        (-0.4, [155, 176, 255]),
+        (0.0, [202, 215, 255]),
+        (0.4, [248, 247, 255]),
+        (0.8, [255, 244, 234]),
+        (1.2, [255, 210, 161]),
+        (2.0, [255, 204, 111]),
+    ];
+
+    let to_vector = |color: [u8; 3]| Vector3::from(color).cast::<f32>() / 255.0;
+
+    let i = COLORS
+        .iter()
+        .position(|(index, _)| color_index < *index)
+        .unwrap_or(COLORS.len());
+
+    if i == 0 {
+        to_vector(COLORS[0].1)
+    }
+    else if i == COLORS.len() {
+        to_vector(COLORS[i - 1].1)
+    }
+    else {
+        let (x0, c0) = COLORS[i - 1];
+        let (x1, c1) = COLORS[i];
+        let t = (color_index - x0) / (x1 - x0);
+        to_vector(c0) * (1.0 - t) + to_vector(c1) * t
+    }
+}
+
 #[inline]
 fn sample<P>(image: &impl GenericImageView<Pixel = P>, uv: Vector2<f32>) -> P
 where
@@ -0,0 +1,82 @@
+//! Inspecting and repairing world files, e.g. when save bugs are reported.
+//!
+//! World files only contain the world config and game state like item drops
+//! and waypoints. Chunks aren't saved; they're generated from the seed.
+
+use std::path::Path;
+
+use color_eyre::eyre::{
+    Error,
+    bail,
+};
+use sandvox::game::file::WorldFile;
+
+pub fn print_info(path: impl AsRef<Path>) -> Result<(), Error> {
+    let world_file = WorldFile::open(path)?;
+    let info = world_file.info()?;
+    println!("{}", serde_json::to_string_pretty(&info)?);
+    Ok(())
+}
+
+/// Checks a world file. With `repair` the database is repaired, game state
+/// that doesn't match its checksum or can't be decoded is removed, and the file
+/// is compacted.
+pub fn fsck(path: impl AsRef<Path>, repair: bool) -> Result<(), Error> {
+    let mut world_file = WorldFile::open(path)?;
+
+    if repair {
+        if world_file.check_integrity()? {
+            println!("database is intact");
+        }
+        else {
+            println!("database was repaired");
+        }
+    }
+
+    let mut num_problems = 0;
+
+    let integrity = world_file.verify()?;
+    for damaged in &integrity.damaged {
+        num_problems += 1;
+
+        if repair {
+            world_file.remove_state(&damaged.key)?;
+            println!("removed state `{}`: {}", damaged.key, damaged.problem);
+        }
+        else {
+            println!("{damaged}");
+        }
+    }
+
+    for key in &integrity.unlisted {
+        println!("state `{key}` has no checksum");
+    }
+
+    let info = world_file.info()?;
+    for state in &info.state {
+        if let Some(error) = &state.error {
+            num_problems += 1;
+
+            if repair {
+                world_file.remove_state(&state.key)?;
+                println!("removed state `{}`: {error}", state.key);
+            }
+            else {
+                println!("state `{}` is corrupted: {error}", state.key);
+            }
+        }
+    }
+
+    for table in &info.unknown_tables {
+        println!("unknown table `{table}`");
+    }
+
+    if repair {
+        world_file.compact()?;
+    }
+    else if num_problems > 0 {
+        bail!("{num_problems} problems found. Run with --repair to fix them.");
+    }
+
+    Ok(())
+}
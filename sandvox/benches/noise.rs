@@ -0,0 +1,88 @@
+use std::hint::black_box;
+
+use criterion::{
+    Criterion,
+    criterion_group,
+    criterion_main,
+};
+use nalgebra::{
+    Point2,
+    Point3,
+};
+use rand::{
+    Rng,
+    SeedableRng,
+};
+use rand_xoshiro::Xoroshiro128PlusPlus;
+use sandvox::util::noise::{
+    FractalNoise,
+    NoiseFn,
+    NoiseFnExt,
+    PerlinNoise,
+    RidgedNoise,
+    WorleyNoise,
+    WorleyOutput,
+};
+
+/// Evaluates the noise for a 32x32 grid of columns, like the terrain generator
+/// does for a chunk.
+fn evaluate_grid(noise: &impl NoiseFn<Point2<f32>>) -> f32 {
+    let mut sum = 0.0;
+    for x in 0..32 {
+        for z in 0..32 {
+            sum += noise.evaluate_at(black_box(Point2::new(x as f32, z as f32)));
+        }
+    }
+    sum
+}
+
+/// Same as [`evaluate_grid`], but with [`NoiseFn::evaluate_batch`].
+fn evaluate_grid_batch(noise: &impl NoiseFn<Point2<f32>>, output: &mut [f32]) {
+    let points = (0..32 * 32)
+        .map(|i| Point2::new((i / 32) as f32, (i % 32) as f32))
+        .collect::<Vec<_>>();
+    noise.evaluate_batch(black_box(&points), output);
+}
+
+fn bench_noise(c: &mut Criterion) {
+    let mut rng = Xoroshiro128PlusPlus::seed_from_u64(0);
+
+    let mut group = c.benchmark_group("noise 32x32");
+
+    let perlin: PerlinNoise = rng.random();
+    group.bench_function("perlin", |b| b.iter(|| evaluate_grid(&perlin)));
+
+    let fractal = FractalNoise::<PerlinNoise>::new(|| rng.random(), 4, 1.0 / 128.0, 2.0, 0.5);
+    group.bench_function("fractal", |b| b.iter(|| evaluate_grid(&fractal)));
+
+    let mut output = vec![0.0; 32 * 32];
+    group.bench_function("fractal_batch", |b| {
+        b.iter(|| evaluate_grid_batch(&fractal, &mut output))
+    });
+
+    let ridged = RidgedNoise::<PerlinNoise>::new(|| rng.random(), 4, 1.0 / 128.0, 2.0, 0.5);
+    group.bench_function("ridged", |b| b.iter(|| evaluate_grid(&ridged)));
+
+    let worley = rng
+        .random::<WorleyNoise>()
+        .with_output(WorleyOutput::Edge)
+        .with_frequency(1.0 / 16.0);
+    group.bench_function("worley", |b| b.iter(|| evaluate_grid(&worley)));
+
+    let warped = FractalNoise::<PerlinNoise>::new(|| rng.random(), 4, 1.0 / 128.0, 2.0, 0.5)
+        .with_domain_warp(
+            FractalNoise::<PerlinNoise>::new(|| rng.random(), 2, 1.0 / 64.0, 2.0, 0.5),
+            16.0,
+        );
+    group.bench_function("domain_warp", |b| b.iter(|| evaluate_grid(&warped)));
+
+    group.finish();
+
+    let worley: WorleyNoise = rng.random();
+    c.bench_function("worley 3d", |b| {
+        b.iter(|| worley.evaluate_at(black_box(Point3::new(1.5, 2.5, 3.5))))
+    });
+}
+
+criterion_group!(benches, bench_noise);
+criterion_main!(benches);
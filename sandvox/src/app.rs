@@ -1,7 +1,10 @@
 use std::{
     collections::HashMap,
     num::NonZero,
-    path::PathBuf,
+    path::{
+        Path,
+        PathBuf,
+    },
     sync::Arc,
     time::{
         Duration,
@@ -23,10 +26,12 @@ use bevy_ecs::{
         Without,
     },
     resource::Resource,
+    schedule::IntoScheduleConfigs,
     system::{
         Commands,
         In,
         InRef,
+        Populated,
         Query,
         Res,
         ResMut,
@@ -55,24 +60,47 @@ use nalgebra::{
     Vector2,
     Vector3,
 };
+use serde::{
+    Deserialize,
+    Serialize,
+};
 use winit::{
     application::ApplicationHandler,
+    dpi::{
+        PhysicalPosition,
+        PhysicalSize,
+    },
     event::StartCause,
     event_loop::{
         ActiveEventLoop,
         ControlFlow,
         EventLoop,
     },
-    keyboard::PhysicalKey,
+    keyboard::{
+        KeyCode,
+        PhysicalKey,
+    },
+    monitor::MonitorHandle,
     window::{
         CursorGrabMode,
+        Fullscreen,
+        Icon,
         WindowAttributes,
     },
 };
 
 use crate::{
+    bench::{
+        BenchConfig,
+        BenchPlugin,
+    },
     build_info::BUILD_INFO,
-    config::Config,
+    config::{
+        Config,
+        ConfigOverride,
+        ConfigWatcherPlugin,
+    },
+    crash,
     ecs::{
         background_tasks::BackgroundTaskPlugin,
         plugin::{
@@ -80,6 +108,7 @@ use crate::{
             WorldBuilder,
         },
         schedule,
+        snapshot::TypeRegistryPlugin,
         transform::TransformHierarchyPlugin,
     },
     game::{
@@ -93,18 +122,38 @@ use crate::{
     },
     input::{
         InputPlugin,
+        Keys,
         MouseButton,
     },
-    profiler::Profiler,
+    logging::file::{
+        close_log_file,
+        open_log_file,
+    },
+    paths::{
+        PathArgs,
+        Paths,
+    },
+    profiler::{
+        Profiler,
+        capture::ProfileCapturePlugin,
+        systems::SystemTimingsPlugin,
+    },
     render::{
         RenderPlugin,
+        billboard::BillboardPlugin,
         camera::CameraPlugin,
+        exposure::ExposurePlugin,
         fps_counter::FpsCounterPlugin,
         mesh::MeshPlugin,
+        mesh_culling::MeshCullingPlugin,
     },
     sound::SoundPlugin,
     ui::UiPlugin,
-    wgpu::WgpuPlugin,
+    wgpu::{
+        WgpuConfig,
+        WgpuContext,
+        WgpuPlugin,
+    },
 };
 
 #[derive(Clone, Debug, Default, Parser)]
@@ -115,11 +164,26 @@ pub struct Args {
     #[clap(long)]
     pub num_threads: Option<NonZero<usize>>,
 
+    /// World file to play in. Relative paths are relative to the saves
+    /// directory.
     #[clap(short = 'w', long = "world-file")]
     pub world_file: Option<PathBuf>,
 
     #[clap(short = 'c', long = "create-world")]
     pub create_world: Option<PathBuf>,
+
+    /// Override a config key, e.g. `--set graphics.vsync=false`.
+    ///
+    /// This can be passed multiple times.
+    #[clap(long = "set", value_name = "KEY=VALUE")]
+    pub config_overrides: Vec<ConfigOverride>,
+
+    #[clap(flatten)]
+    pub paths: PathArgs,
+
+    /// Run a benchmark instead of the normal game. See `sandvox bench`.
+    #[clap(skip)]
+    pub bench: Option<BenchConfig>,
 }
 
 #[derive(Debug)]
@@ -131,8 +195,68 @@ impl App {
     pub fn new(args: Args) -> Result<Self, Error> {
         tracing::info!(?BUILD_INFO);
 
-        // todo: load from proper location
-        let config = Config::load("config.toml")?;
+        let paths = Paths::new(&args.paths);
+        crash::set_directory(paths.crashes_dir());
+        paths.copy_legacy_files();
+
+        let world_file = args
+            .world_file
+            .as_ref()
+            .map(|world_file| paths.world_file(world_file));
+
+        let init_world = if let Some(world_config_file) = &args.create_world {
+            if let Some(world_file) = &world_file
+                && world_file.exists()
+            {
+                bail!("--create-world passed, but world-file already exists");
+            }
+
+            let world_config_toml = std::fs::read(world_config_file)
+                .with_note(|| world_config_file.display().to_string())?;
+            let world_config: WorldConfig = toml::from_slice(&world_config_toml)?;
+            InitWorld::Create {
+                world_config,
+                world_file,
+            }
+        }
+        else {
+            if let Some(world_file) = world_file {
+                InitWorld::Load { world_file }
+            }
+            else {
+                tracing::info!(
+                    "Neither --world-file, nor --create-world passed. Creating default world."
+                );
+                InitWorld::Create {
+                    world_config: {
+                        // special world config for development
+                        WorldConfig {
+                            seed: WorldSeed::FIXED_DEFAULT,
+                            bounds: WorldBounds {
+                                min: Vector3::new(None, Some(-2), Some(-1)),
+                                max: Default::default(),
+                            },
+                            ..Default::default()
+                        }
+                    },
+                    world_file: None,
+                }
+            }
+        };
+
+        let world_overrides = init_world.config_overrides()?;
+
+        let config_path = paths.config_file();
+        let config = Config::load(&config_path, &world_overrides, &args.config_overrides)?;
+
+        match &config.log_file {
+            Some(log_file_config) => {
+                if let Err(error) = open_log_file(log_file_config, &paths) {
+                    tracing::warn!(?error, "could not open log file");
+                }
+            }
+            None => close_log_file(),
+        }
 
         let profiler = config
             .profiler
@@ -141,6 +265,14 @@ impl App {
             .transpose()?;
 
         let mut world_builder = WorldBuilder::default();
+        world_builder.insert_resource(paths);
+
+        world_builder.add_plugin(ConfigWatcherPlugin {
+            path: config_path,
+            world_overrides,
+            overrides: args.config_overrides.clone(),
+            config: config.clone(),
+        })?;
 
         if let Some(profiler) = profiler {
             world_builder.insert_resource(profiler);
@@ -178,7 +310,10 @@ impl App {
                 }
             })
             .add_plugin(AppPlugin)?
+            .add_plugin(ProfileCapturePlugin)?
+            .add_plugin(SystemTimingsPlugin)?
             .add_plugin(TransformHierarchyPlugin)?
+            .add_plugin(TypeRegistryPlugin)?
             .add_plugin(InputPlugin)?
             .add_plugin(WgpuPlugin {
                 config: config.graphics.wgpu,
@@ -188,6 +323,9 @@ impl App {
             })?
             .add_plugin(FpsCounterPlugin::default())?
             .add_plugin(MeshPlugin)?
+            .add_plugin(MeshCullingPlugin)?
+            .add_plugin(ExposurePlugin)?
+            .add_plugin(BillboardPlugin)?
             .add_plugin(CameraPlugin)?
             .add_plugin(UiPlugin)?;
 
@@ -195,59 +333,23 @@ impl App {
             world_builder.add_plugin(SoundPlugin { config })?;
         }
 
-        let init_world = if let Some(world_config_file) = &args.create_world {
-            if let Some(world_file) = &args.world_file
-                && world_file.exists()
-            {
-                bail!("--create-world passed, but world-file already exists");
+        world_builder.add_plugin({
+            GamePlugin {
+                game_config: config.game,
+                init_world,
             }
+        })?;
 
-            let world_config_toml = std::fs::read(world_config_file)
-                .with_note(|| world_config_file.display().to_string())?;
-            let world_config: WorldConfig = toml::from_slice(&world_config_toml)?;
-            InitWorld::Create {
-                world_config,
-                world_file: args.world_file,
-            }
+        if let Some(config) = args.bench {
+            world_builder.add_plugin(BenchPlugin { config })?;
         }
-        else {
-            if let Some(world_file) = args.world_file {
-                InitWorld::Load { world_file }
-            }
-            else {
-                tracing::info!(
-                    "Neither --world-file, nor --create-world passed. Creating default world."
-                );
-                InitWorld::Create {
-                    world_config: {
-                        // special world config for development
-                        WorldConfig {
-                            seed: WorldSeed::FIXED_DEFAULT,
-                            bounds: WorldBounds {
-                                min: Vector3::new(None, Some(-2), Some(-1)),
-                                max: Default::default(),
-                            },
-                        }
-                    },
-                    world_file: None,
-                }
-            }
-        };
-
-        world_builder
-            .add_plugin({
-                GamePlugin {
-                    game_config: config.game,
-                    init_world,
-                }
-            })?
-            .add_systems(schedule::PostUpdate, update_window_config);
 
         if let Some(path) = args.generate_schedule_graphs {
+            world_builder.setup_plugins()?;
             world_builder.write_schedule_graphs_to_dot(path)?;
         }
 
-        let world = world_builder.build();
+        let world = world_builder.build()?;
 
         Ok(Self { world })
     }
@@ -289,6 +391,41 @@ impl App {
 
         profiling::finish_frame!();
     }
+
+    /// Recovers from a lost GPU device.
+    ///
+    /// The world is kept. Only the wgpu context is created again, and the
+    /// [`GpuSetup`](schedule::GpuSetup) schedule then recreates the surfaces,
+    /// pipelines and other GPU resources on the existing world.
+    fn reset_gpu(&mut self, reason: String) -> Result<(), Error> {
+        tracing::warn!(reason, "GPU device lost. resetting.");
+
+        self.world.remove_resource::<WgpuContext>();
+
+        // removed first, so that it's added again on a second reset
+        self.world.remove_resource::<GpuReset>();
+        self.world.insert_resource(GpuReset { reason });
+
+        let config = self.world.resource::<WgpuConfig>().clone();
+
+        self.world
+            .insert_resource(crate::wgpu::WgpuContextBuilder::new(config)?);
+        self.world.run_schedule(schedule::GpuSetup);
+
+        Ok(())
+    }
+
+    /// Runs `f`, and if it panics, saves the game and writes a crash report
+    /// before the panic continues.
+    ///
+    /// Only what the shutdown schedule saves survives the crash. Chunks are
+    /// generated again anyway.
+    fn guarded(&mut self, f: impl FnOnce(&mut Self)) {
+        crash::guard(self, f, |app| {
+            tracing::error!("panicked. trying to save the game.");
+            app.world.run_schedule(schedule::Shutdown);
+        });
+    }
 }
 
 impl ApplicationHandler<AppEvent> for App {
@@ -310,9 +447,11 @@ impl ApplicationHandler<AppEvent> for App {
         window_id: winit::window::WindowId,
         event: winit::event::WindowEvent,
     ) {
-        self.world
-            .run_system_cached_with(handle_window_event, (event_loop, window_id, event))
-            .unwrap();
+        self.guarded(|app| {
+            app.world
+                .run_system_cached_with(handle_window_event, (event_loop, window_id, event))
+                .unwrap();
+        });
     }
 
     fn device_event(
@@ -330,12 +469,28 @@ impl ApplicationHandler<AppEvent> for App {
         event_loop.set_control_flow(ControlFlow::Poll);
     }
 
-    fn new_events(&mut self, event_loop: &ActiveEventLoop, cause: StartCause) {
+    fn exiting(&mut self, event_loop: &ActiveEventLoop) {
         let _ = event_loop;
 
+        tracing::debug!("exiting");
+        self.world.run_schedule(schedule::Shutdown);
+    }
+
+    fn new_events(&mut self, event_loop: &ActiveEventLoop, cause: StartCause) {
         match cause {
             StartCause::Poll => {
-                self.update();
+                self.guarded(Self::update);
+
+                let device_lost = self
+                    .world
+                    .get_resource::<WgpuContext>()
+                    .and_then(|wgpu| wgpu.device_lost.reason());
+                if let Some(reason) = device_lost
+                    && let Err(error) = self.reset_gpu(reason)
+                {
+                    tracing::error!(?error, "could not reset GPU");
+                    event_loop.exit();
+                }
             }
             _ => {}
         }
@@ -413,7 +568,12 @@ impl Plugin for AppPlugin {
         builder
             .add_message::<WindowEvent>()
             .insert_resource(AppState::Suspended)
-            .insert_resource(WindowIdMap::default());
+            .insert_resource(WindowIdMap::default())
+            .add_systems(
+                schedule::PostUpdate,
+                (toggle_fullscreen, update_window_config).chain(),
+            )
+            .add_systems(schedule::Shutdown, save_window_states);
         Ok(())
     }
 }
@@ -643,6 +803,7 @@ fn handle_device_event(
 struct CreateWindows<'w, 's> {
     requests: Query<'w, 's, (Entity, &'static WindowConfig), Without<WindowHandle>>,
     window_id_map: ResMut<'w, WindowIdMap>,
+    paths: Res<'w, Paths>,
     commands: Commands<'w, 's>,
     window_events: MessageWriter<'w, WindowEvent>,
 }
@@ -650,9 +811,58 @@ struct CreateWindows<'w, 's> {
 impl<'world, 'state> CreateWindows<'world, 'state> {
     pub fn create_windows(&mut self, event_loop: &ActiveEventLoop) {
         for (entity, config) in self.requests {
-            let window = event_loop
-                .create_window(WindowAttributes::default().with_title(config.title.clone()))
-                .unwrap();
+            let mut config = config.clone();
+            let saved_state = if config.persist {
+                SavedWindowState::load(&self.paths.window_state_file())
+            }
+            else {
+                Default::default()
+            };
+            if let Some(mode) = saved_state.mode {
+                config.mode = mode;
+            }
+
+            let monitor = select_monitor(
+                config.monitor,
+                None,
+                event_loop.primary_monitor(),
+                event_loop.available_monitors(),
+            );
+
+            let mut attributes = WindowAttributes::default()
+                .with_title(config.title.clone())
+                .with_fullscreen(fullscreen_for_mode(config.mode, monitor.clone()));
+
+            if let Some(size) = saved_state.size.or(config.size) {
+                attributes = attributes.with_inner_size(PhysicalSize::new(size.x, size.y));
+            }
+
+            if let Some(position) = saved_state.position.or(config.position) {
+                attributes =
+                    attributes.with_position(PhysicalPosition::new(position.x, position.y));
+            }
+            else if config.monitor != MonitorSelection::Current
+                && let Some(monitor) = &monitor
+            {
+                // place the window on the selected monitor
+                attributes = attributes.with_position(monitor.position());
+            }
+
+            if let Some(min_size) = config.min_size {
+                attributes =
+                    attributes.with_min_inner_size(PhysicalSize::new(min_size.x, min_size.y));
+            }
+
+            if let Some(path) = &config.icon {
+                match load_window_icon(path) {
+                    Ok(icon) => attributes = attributes.with_window_icon(Some(icon)),
+                    Err(error) => {
+                        tracing::warn!(path = %path.display(), "couldn't load window icon: {error}")
+                    }
+                }
+            }
+
+            let window = event_loop.create_window(attributes).unwrap();
             let size = window.inner_size();
             let size = Vector2::new(size.width, size.height);
 
@@ -665,6 +875,7 @@ impl<'world, 'state> CreateWindows<'world, 'state> {
                     window: Arc::new(window),
                 },
                 WindowSize { size },
+                config,
             ));
 
             self.window_events
@@ -673,15 +884,207 @@ impl<'world, 'state> CreateWindows<'world, 'state> {
     }
 }
 
+/// Applies changes to the [`WindowConfig`].
+///
+/// Size, position and icon are only used when the window is created.
 fn update_window_config(windows: Query<(&WindowConfig, &WindowHandle), Changed<WindowConfig>>) {
     for (config, handle) in windows {
-        handle.window.set_title(&config.title);
+        let window = &handle.window;
+
+        window.set_title(&config.title);
+        window.set_min_inner_size(
+            config
+                .min_size
+                .map(|min_size| PhysicalSize::new(min_size.x, min_size.y)),
+        );
+
+        let monitor = select_monitor(
+            config.monitor,
+            window.current_monitor(),
+            window.primary_monitor(),
+            window.available_monitors(),
+        );
+        let fullscreen = fullscreen_for_mode(config.mode, monitor);
+        if window.fullscreen() != fullscreen {
+            tracing::debug!(mode = ?config.mode, "changing window mode");
+            window.set_fullscreen(fullscreen);
+        }
     }
 }
 
-#[derive(Clone, Debug, Component)]
+fn toggle_fullscreen(windows: Populated<(&Keys, &mut WindowConfig), Changed<Keys>>) {
+    for (keys, mut config) in windows {
+        if keys.just_pressed.contains(&KeyCode::F11) {
+            config.mode = match config.mode {
+                WindowMode::Windowed => WindowMode::Borderless,
+                WindowMode::Borderless | WindowMode::Fullscreen => WindowMode::Windowed,
+            };
+        }
+    }
+}
+
+fn save_window_states(windows: Query<(&WindowConfig, &WindowHandle)>, paths: Res<Paths>) {
+    let path = paths.window_state_file();
+
+    for (config, handle) in windows {
+        if !config.persist {
+            continue;
+        }
+
+        let mut state = SavedWindowState::load(&path);
+        state.mode = Some(config.mode);
+
+        // keep the windowed geometry, so that a window that was closed in fullscreen
+        // mode has its old size when switching back.
+        if config.mode == WindowMode::Windowed {
+            let size = handle.window.inner_size();
+            state.size = Some(Vector2::new(size.width, size.height));
+            state.position = handle
+                .window
+                .outer_position()
+                .ok()
+                .map(|position| Point2::new(position.x, position.y));
+        }
+
+        if let Err(error) = state.save(&path) {
+            tracing::warn!("couldn't save window state: {error}");
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Component)]
 pub struct WindowConfig {
     pub title: String,
+
+    pub mode: WindowMode,
+
+    /// Monitor used for fullscreen modes, and that the window is placed on
+    /// initially.
+    pub monitor: MonitorSelection,
+
+    /// Initial inner size in pixels.
+    pub size: Option<Vector2<u32>>,
+
+    /// Initial position of the window's top-left corner on the desktop.
+    pub position: Option<Point2<i32>>,
+
+    /// Minimum inner size in pixels.
+    pub min_size: Option<Vector2<u32>>,
+
+    /// Image file with the window icon.
+    pub icon: Option<PathBuf>,
+
+    /// Restore size, position and mode from the last time the app ran, and
+    /// save them when it exits.
+    pub persist: bool,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum WindowMode {
+    #[default]
+    Windowed,
+
+    /// Fullscreen window without borders, at the monitor's resolution.
+    Borderless,
+
+    /// Exclusive fullscreen with the monitor's largest video mode.
+    Fullscreen,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MonitorSelection {
+    /// The monitor the window is on.
+    #[default]
+    Current,
+    Primary,
+    /// Index into the available monitors.
+    Index(usize),
+}
+
+fn select_monitor(
+    selection: MonitorSelection,
+    current: Option<MonitorHandle>,
+    primary: Option<MonitorHandle>,
+    mut available: impl Iterator<Item = MonitorHandle>,
+) -> Option<MonitorHandle> {
+    match selection {
+        MonitorSelection::Current => current.or(primary),
+        MonitorSelection::Primary => primary.or(current),
+        MonitorSelection::Index(index) => {
+            available.nth(index).or_else(|| {
+                tracing::warn!(index, "selected monitor doesn't exist");
+                current.or(primary)
+            })
+        }
+    }
+}
+
+fn fullscreen_for_mode(mode: WindowMode, monitor: Option<MonitorHandle>) -> Option<Fullscreen> {
+    match mode {
+        WindowMode::Windowed => None,
+        WindowMode::Borderless => Some(Fullscreen::Borderless(monitor)),
+        WindowMode::Fullscreen => {
+            let video_mode = monitor.and_then(|monitor| {
+                monitor.video_modes().max_by_key(|video_mode| {
+                    let size = video_mode.size();
+                    (
+                        size.width * size.height,
+                        video_mode.refresh_rate_millihertz(),
+                        video_mode.bit_depth(),
+                    )
+                })
+            });
+
+            if let Some(video_mode) = video_mode {
+                Some(Fullscreen::Exclusive(video_mode))
+            }
+            else {
+                tracing::warn!("no video mode for exclusive fullscreen. using borderless instead");
+                Some(Fullscreen::Borderless(None))
+            }
+        }
+    }
+}
+
+fn load_window_icon(path: &Path) -> Result<Icon, Error> {
+    let image = image::open(path)?.into_rgba8();
+    let (width, height) = image.dimensions();
+    Ok(Icon::from_rgba(image.into_raw(), width, height)?)
+}
+
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize)]
+struct SavedWindowState {
+    mode: Option<WindowMode>,
+    size: Option<Vector2<u32>>,
+    position: Option<Point2<i32>>,
+}
+
+impl SavedWindowState {
+    /// Loads the state that [`WindowConfig::persist`] stored.
+    fn load(path: &Path) -> Self {
+        match std::fs::read_to_string(path) {
+            Ok(toml) => {
+                toml::from_str(&toml).unwrap_or_else(|error| {
+                    tracing::warn!(path = %path.display(), "invalid window state: {error}");
+                    Default::default()
+                })
+            }
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => Default::default(),
+            Err(error) => {
+                tracing::warn!(path = %path.display(), "couldn't read window state: {error}");
+                Default::default()
+            }
+        }
+    }
+
+    fn save(&self, path: &Path) -> Result<(), Error> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, toml::to_string_pretty(self)?)?;
+        Ok(())
+    }
 }
 
 #[derive(Clone, Debug, Component)]
@@ -753,12 +1156,19 @@ pub enum WindowEvent {
     },
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Debug)]
 enum AppEvent {
     GrabCursor { window: Entity },
     UngrabCursor { window: Entity },
 }
 
+/// Inserted when the GPU device was lost. The GPU resources are recreated in
+/// place, so the world and game state are kept.
+#[derive(Clone, Debug, Resource)]
+pub struct GpuReset {
+    pub reason: String,
+}
+
 #[derive(Clone, Debug, Resource)]
 struct EventLoopProxy(winit::event_loop::EventLoopProxy<AppEvent>);
 
@@ -0,0 +1,653 @@
+//! Loading and decoding of assets on the [`BackgroundTaskPool`].
+//!
+//! [`AssetServer::load`] returns an [`AssetHandle`] right away and the asset is
+//! read and decoded on a background thread. Systems poll the handle and e.g.
+//! upload the asset to the GPU once it's loaded.
+//!
+//! Assets loaded with [`AssetServer::load_required`] are needed before the game
+//! can start. [`required_assets_loaded`] can be used as a run condition to wait
+//! for them.
+//!
+//! Assets that are needed to draw the loading screen (e.g. the sprites) are
+//! loaded synchronously with [`AssetServer::load_blocking`].
+//!
+//! # Hot-reloading
+//!
+//! If hot-reloading is enabled, the files of all assets that still have a
+//! handle are polled for modifications and modified assets are loaded again.
+//! Systems that use an asset check for new versions with
+//! [`AssetHandle::get_if_changed`].
+
+use std::{
+    collections::VecDeque,
+    fmt::Debug,
+    path::{
+        Path,
+        PathBuf,
+    },
+    sync::{
+        Arc,
+        Weak,
+    },
+    time::{
+        Duration,
+        Instant,
+        SystemTime,
+    },
+};
+
+use bevy_ecs::{
+    resource::Resource,
+    schedule::IntoScheduleConfigs,
+    system::Res,
+    world::CommandQueue,
+};
+use color_eyre::{
+    Section,
+    eyre::Error,
+};
+use image::{
+    Rgba32FImage,
+    RgbaImage,
+};
+use parking_lot::Mutex;
+
+use crate::{
+    ecs::{
+        background_tasks::{
+            BackgroundTaskConfig,
+            BackgroundTaskPlugin,
+            BackgroundTaskPool,
+            Task,
+            TaskPriority,
+            WorldBuilderBackgroundTaskExt,
+        },
+        plugin::{
+            Dependencies,
+            Plugin,
+            WorldBuilder,
+        },
+        schedule,
+    },
+    util::{
+        image::ImageLoadExt,
+        modified_time,
+    },
+};
+
+#[derive(Clone, Copy, Debug)]
+pub struct AssetPlugin {
+    pub task_config: BackgroundTaskConfig,
+}
+
+impl Default for AssetPlugin {
+    fn default() -> Self {
+        Self {
+            // assets block the loading screen, so they go before anything else.
+            task_config: BackgroundTaskConfig {
+                priority: TaskPriority::High,
+                ..Default::default()
+            },
+        }
+    }
+}
+
+impl Plugin for AssetPlugin {
+    fn dependencies(&self, dependencies: &mut Dependencies) -> Result<(), Error> {
+        dependencies.require_plugin::<BackgroundTaskPlugin>();
+        Ok(())
+    }
+
+    fn setup(&self, builder: &mut WorldBuilder) -> Result<(), Error> {
+        builder.configure_background_task_queue::<LoadAssetTask>(self.task_config);
+
+        builder.init_resource::<AssetServer>().add_systems(
+            schedule::PreUpdate,
+            (
+                watch_assets.run_if(|assets: Res<AssetServer>| assets.hot_reload()),
+                dispatch_asset_loads,
+            )
+                .chain(),
+        );
+
+        Ok(())
+    }
+}
+
+/// How often the files of assets are checked for modifications.
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Something that can be loaded from a file on a background thread.
+pub trait Asset: Sized + Send + Sync + 'static {
+    fn load(path: &Path) -> Result<Self, Error>;
+
+    /// Other files that the asset was loaded from, e.g. the images referenced
+    /// by a `.toml` file. These are watched for modifications in addition to
+    /// the asset's path.
+    fn dependencies(&self) -> Vec<PathBuf> {
+        vec![]
+    }
+}
+
+impl Asset for RgbaImage {
+    fn load(path: &Path) -> Result<Self, Error> {
+        Ok(RgbaImage::from_path(path)?)
+    }
+}
+
+impl Asset for Rgba32FImage {
+    fn load(path: &Path) -> Result<Self, Error> {
+        Ok(Rgba32FImage::from_path(path)?)
+    }
+}
+
+#[derive(Debug, Default, Resource)]
+pub struct AssetServer {
+    state: Mutex<AssetServerState>,
+}
+
+#[derive(Debug, Default)]
+struct AssetServerState {
+    pending: VecDeque<LoadAssetTask>,
+    required: Vec<Arc<dyn DynAssetSlot>>,
+    watched: Vec<Weak<dyn DynAssetSlot>>,
+    hot_reload: bool,
+    last_poll: Option<Instant>,
+}
+
+impl AssetServer {
+    /// Starts loading an asset in the background.
+    pub fn load<A>(&self, path: impl AsRef<Path>) -> AssetHandle<A>
+    where
+        A: Asset,
+    {
+        let path = path.as_ref().to_owned();
+        tracing::debug!(?path, "loading asset");
+
+        let slot = Arc::new(AssetSlot::new(path));
+
+        let mut state = self.state.lock();
+        state.pending.push_back(slot.clone().load_task());
+        state.watched.push(Arc::downgrade(&slot) as _);
+
+        AssetHandle { slot }
+    }
+
+    /// Starts loading an asset that the game can't start without.
+    ///
+    /// The loading screen is shown until all required assets are loaded.
+    pub fn load_required<A>(&self, path: impl AsRef<Path>) -> AssetHandle<A>
+    where
+        A: Asset,
+    {
+        let handle = self.load(path);
+        self.state.lock().required.push(handle.slot.clone());
+        handle
+    }
+
+    /// Loads an asset on the calling thread.
+    ///
+    /// The returned handle is already loaded (or failed), but is hot-reloaded
+    /// like any other handle.
+    pub fn load_blocking<A>(&self, path: impl AsRef<Path>) -> AssetHandle<A>
+    where
+        A: Asset,
+    {
+        let path = path.as_ref().to_owned();
+        tracing::debug!(?path, "loading asset");
+
+        let slot = Arc::new(AssetSlot::new(path));
+        slot.load();
+        self.state.lock().watched.push(Arc::downgrade(&slot) as _);
+
+        AssetHandle { slot }
+    }
+
+    pub fn progress(&self) -> LoadingProgress {
+        let state = self.state.lock();
+
+        let mut progress = LoadingProgress {
+            num_total: state.required.len(),
+            ..Default::default()
+        };
+
+        for slot in &state.required {
+            match slot.status() {
+                AssetStatus::Loading => {}
+                AssetStatus::Loaded => progress.num_loaded += 1,
+                AssetStatus::Failed => progress.num_failed += 1,
+            }
+        }
+
+        progress
+    }
+
+    pub fn hot_reload(&self) -> bool {
+        self.state.lock().hot_reload
+    }
+
+    pub fn set_hot_reload(&self, hot_reload: bool) {
+        let mut state = self.state.lock();
+        if state.hot_reload != hot_reload {
+            tracing::info!(hot_reload, "asset hot-reloading");
+            state.hot_reload = hot_reload;
+        }
+    }
+
+    /// Loads all assets again whose files were modified since they were last
+    /// loaded.
+    ///
+    /// This is called periodically if hot-reloading is enabled.
+    pub fn reload_modified(&self) {
+        let mut state = self.state.lock();
+        let state = &mut *state;
+
+        state.watched.retain(|slot| {
+            let Some(slot) = slot.upgrade()
+            else {
+                return false;
+            };
+
+            if let Some(task) = slot.reload_if_modified() {
+                state.pending.push_back(task);
+            }
+
+            true
+        });
+    }
+}
+
+/// How many of the required assets are loaded.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct LoadingProgress {
+    pub num_loaded: usize,
+    pub num_failed: usize,
+    pub num_total: usize,
+}
+
+impl LoadingProgress {
+    /// Whether no required asset is loading anymore, including assets that
+    /// failed to load.
+    pub fn is_done(&self) -> bool {
+        self.num_loaded + self.num_failed == self.num_total
+    }
+}
+
+/// Run condition that is true once all required assets are loaded.
+pub fn required_assets_loaded(assets: Res<AssetServer>) -> bool {
+    let progress = assets.progress();
+    progress.num_loaded == progress.num_total
+}
+
+/// Status of the most recent load of an asset.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AssetStatus {
+    Loading,
+    Loaded,
+    Failed,
+}
+
+pub struct AssetHandle<A> {
+    slot: Arc<AssetSlot<A>>,
+}
+
+impl<A> AssetHandle<A> {
+    pub fn path(&self) -> &Path {
+        &self.slot.path
+    }
+
+    pub fn status(&self) -> AssetStatus {
+        self.slot.status()
+    }
+
+    /// Returns the asset or the error why it couldn't be loaded, or `None` if
+    /// it's still loading.
+    ///
+    /// While the asset is reloaded, this still returns the previous result.
+    pub fn get(&self) -> Option<Result<Arc<A>, Arc<Error>>> {
+        self.slot.state.lock().result.clone()
+    }
+
+    /// Like [`get`][Self::get], but only returns the result if the asset was
+    /// loaded again since `version` was last updated.
+    ///
+    /// Start with a `version` of 0.
+    pub fn get_if_changed(&self, version: &mut u64) -> Option<Result<Arc<A>, Arc<Error>>> {
+        let state = self.slot.state.lock();
+        if state.version == *version {
+            return None;
+        }
+        *version = state.version;
+        state.result.clone()
+    }
+}
+
+impl<A> Clone for AssetHandle<A> {
+    fn clone(&self) -> Self {
+        Self {
+            slot: self.slot.clone(),
+        }
+    }
+}
+
+impl<A> Debug for AssetHandle<A> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AssetHandle")
+            .field("path", &self.slot.path)
+            .field("status", &self.status())
+            .finish()
+    }
+}
+
+struct AssetSlot<A> {
+    path: PathBuf,
+    state: Mutex<AssetState<A>>,
+}
+
+struct AssetState<A> {
+    /// Result of the most recent load. `None` until it's loaded the first
+    /// time.
+    result: Option<Result<Arc<A>, Arc<Error>>>,
+
+    /// Number of completed loads.
+    version: u64,
+
+    reloading: bool,
+
+    /// Files that the asset was loaded from and when they were modified.
+    files: Vec<(PathBuf, Option<SystemTime>)>,
+}
+
+impl<A> AssetSlot<A> {
+    fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            state: Mutex::new(AssetState {
+                result: None,
+                version: 0,
+                reloading: false,
+                files: vec![],
+            }),
+        }
+    }
+
+    fn status(&self) -> AssetStatus {
+        match &self.state.lock().result {
+            None => AssetStatus::Loading,
+            Some(Ok(_)) => AssetStatus::Loaded,
+            Some(Err(_)) => AssetStatus::Failed,
+        }
+    }
+}
+
+impl<A> AssetSlot<A>
+where
+    A: Asset,
+{
+    fn load(&self) {
+        let t_start = Instant::now();
+
+        let result = A::load(&self.path).with_note(|| self.path.display().to_string());
+
+        let mut state = self.state.lock();
+
+        match &result {
+            Ok(asset) => {
+                tracing::debug!(path = ?self.path, time = ?t_start.elapsed(), "loaded asset");
+                state.files = std::iter::once(self.path.clone())
+                    .chain(asset.dependencies())
+                    .map(|path| {
+                        let modified = modified_time(&path);
+                        (path, modified)
+                    })
+                    .collect();
+            }
+            Err(error) => {
+                tracing::error!(path = ?self.path, "failed to load asset: {error}");
+
+                // we don't know the dependencies, so we keep the ones from the last successful
+                // load, but update their modification times, so that we don't try again until
+                // they're modified.
+                if state.files.is_empty() {
+                    state.files.push((self.path.clone(), None));
+                }
+                for (path, modified) in &mut state.files {
+                    *modified = modified_time(path);
+                }
+            }
+        }
+
+        state.result = Some(result.map(Arc::new).map_err(Arc::new));
+        state.version += 1;
+        state.reloading = false;
+    }
+
+    fn load_task(self: Arc<Self>) -> LoadAssetTask {
+        LoadAssetTask {
+            path: self.path.clone(),
+            load: Box::new(move || self.load()),
+        }
+    }
+}
+
+/// Type-erased [`AssetSlot`], so that the [`AssetServer`] can track assets of
+/// all types.
+trait DynAssetSlot: Send + Sync + 'static {
+    fn status(&self) -> AssetStatus;
+
+    fn reload_if_modified(self: Arc<Self>) -> Option<LoadAssetTask>;
+}
+
+impl Debug for dyn DynAssetSlot {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.status().fmt(f)
+    }
+}
+
+impl<A> DynAssetSlot for AssetSlot<A>
+where
+    A: Asset,
+{
+    fn status(&self) -> AssetStatus {
+        AssetSlot::status(self)
+    }
+
+    fn reload_if_modified(self: Arc<Self>) -> Option<LoadAssetTask> {
+        {
+            let mut state = self.state.lock();
+
+            // files are only known once the asset is loaded
+            if state.result.is_none() || state.reloading {
+                return None;
+            }
+
+            let modified = state
+                .files
+                .iter()
+                .any(|(path, modified)| modified_time(path) != *modified);
+            if !modified {
+                return None;
+            }
+
+            state.reloading = true;
+        }
+
+        tracing::info!(path = ?self.path, "asset modified, reloading");
+        Some(self.load_task())
+    }
+}
+
+#[derive(derive_more::Debug)]
+struct LoadAssetTask {
+    path: PathBuf,
+    #[debug(skip)]
+    load: Box<dyn FnOnce() + Send + Sync>,
+}
+
+impl Task for LoadAssetTask {
+    const NAME: &'static str = "load asset";
+
+    fn run(self, _world_modifications: &mut CommandQueue) {
+        let _span = tracing::debug_span!("load asset", path = ?self.path).entered();
+        (self.load)();
+    }
+}
+
+fn watch_assets(assets: Res<AssetServer>) {
+    let now = Instant::now();
+
+    {
+        let mut state = assets.state.lock();
+        if state
+            .last_poll
+            .is_some_and(|last_poll| now.duration_since(last_poll) < POLL_INTERVAL)
+        {
+            return;
+        }
+        state.last_poll = Some(now);
+    }
+
+    assets.reload_modified();
+}
+
+fn dispatch_asset_loads(assets: Res<AssetServer>, background_tasks: Res<BackgroundTaskPool>) {
+    let mut state = assets.state.lock();
+
+    // the pool only takes as many tasks as fit into the queue, the rest stays
+    // pending until the next frame.
+    if !state.pending.is_empty() {
+        background_tasks.push_tasks(std::iter::from_fn(|| state.pending.pop_front()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        path::{
+            Path,
+            PathBuf,
+        },
+        time::{
+            Duration,
+            Instant,
+            SystemTime,
+        },
+    };
+
+    use bevy_ecs::world::World;
+    use color_eyre::eyre::{
+        Error,
+        bail,
+    };
+
+    use crate::{
+        assets::{
+            Asset,
+            AssetPlugin,
+            AssetServer,
+            AssetStatus,
+            LoadingProgress,
+        },
+        ecs::{
+            background_tasks::BackgroundTaskPlugin,
+            plugin::WorldBuilder,
+            schedule,
+        },
+    };
+
+    #[derive(Debug)]
+    struct FileName(String);
+
+    impl Asset for FileName {
+        fn load(path: &Path) -> Result<Self, Error> {
+            if path == Path::new("missing.txt") {
+                bail!("not found");
+            }
+            Ok(Self(path.display().to_string()))
+        }
+    }
+
+    #[derive(Debug)]
+    struct FileContents(String);
+
+    impl Asset for FileContents {
+        fn load(path: &Path) -> Result<Self, Error> {
+            Ok(Self(std::fs::read_to_string(path)?))
+        }
+    }
+
+    fn build_world() -> World {
+        let mut builder = WorldBuilder::default();
+        builder
+            .add_plugin(BackgroundTaskPlugin::max_threads())
+            .unwrap()
+            .add_plugin(AssetPlugin::default())
+            .unwrap();
+        builder.build().unwrap()
+    }
+
+    fn run_until(world: &mut World, mut done: impl FnMut(&World) -> bool) {
+        let t_start = Instant::now();
+        while !done(world) {
+            assert!(t_start.elapsed() < Duration::from_secs(10), "timeout");
+            world.run_schedule(schedule::PreUpdate);
+            std::thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    #[test]
+    fn it_loads_assets_in_the_background() {
+        let mut world = build_world();
+
+        let assets = world.resource::<AssetServer>();
+        let found = assets.load_required::<FileName>("found.txt");
+        let missing = assets.load_required::<FileName>("missing.txt");
+        assert_eq!(found.status(), AssetStatus::Loading);
+
+        run_until(&mut world, |world| {
+            world.resource::<AssetServer>().progress().is_done()
+        });
+
+        assert_eq!(found.get().unwrap().unwrap().0, "found.txt");
+        assert_eq!(missing.status(), AssetStatus::Failed);
+        assert_eq!(
+            world.resource::<AssetServer>().progress(),
+            LoadingProgress {
+                num_loaded: 1,
+                num_failed: 1,
+                num_total: 2,
+            }
+        );
+    }
+
+    #[test]
+    fn it_reloads_modified_assets() {
+        let path: PathBuf =
+            std::env::temp_dir().join(format!("sandvox-asset-test-{}.txt", std::process::id()));
+        let write = |contents: &str, modified: SystemTime| {
+            let file = std::fs::File::create(&path).unwrap();
+            std::io::Write::write_all(&mut &file, contents.as_bytes()).unwrap();
+            file.set_modified(modified).unwrap();
+        };
+
+        let mut world = build_world();
+
+        write("old", SystemTime::UNIX_EPOCH);
+        let handle = world
+            .resource::<AssetServer>()
+            .load_blocking::<FileContents>(&path);
+        let mut version = 0;
+        assert_eq!(
+            handle.get_if_changed(&mut version).unwrap().unwrap().0,
+            "old"
+        );
+        assert!(handle.get_if_changed(&mut version).is_none());
+
+        write("new", SystemTime::UNIX_EPOCH + Duration::from_secs(1));
+        world.resource::<AssetServer>().reload_modified();
+        run_until(&mut world, |_| handle.get().unwrap().unwrap().0 == "new");
+        assert!(handle.get_if_changed(&mut version).is_some());
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,424 @@
+//! Benchmark that flies the camera along a fixed path and reports frame times,
+//! chunk throughput and memory usage as JSON, e.g. to track performance in CI.
+//!
+//! Without a world file, the development world is used, which always has the
+//! same seed.
+
+use std::{
+    collections::BTreeMap,
+    path::{
+        Path,
+        PathBuf,
+    },
+    time::{
+        Duration,
+        Instant,
+    },
+};
+
+use bevy_ecs::{
+    entity::Entity,
+    query::With,
+    resource::Resource,
+    schedule::{
+        IntoScheduleConfigs,
+        common_conditions::{
+            not,
+            resource_exists,
+        },
+    },
+    system::{
+        Commands,
+        Res,
+        ResMut,
+        Single,
+    },
+};
+use color_eyre::eyre::{
+    Error,
+    bail,
+};
+use nalgebra::{
+    Point3,
+    UnitQuaternion,
+    Vector3,
+};
+use serde::{
+    Deserialize,
+    Serialize,
+};
+
+use crate::{
+    app::{
+        Args,
+        CloseApp,
+        Time,
+    },
+    ecs::{
+        plugin::{
+            Plugin,
+            WorldBuilder,
+        },
+        schedule,
+        transform::LocalTransform,
+    },
+    game::{
+        Player,
+        camera_controller::CameraControllerState,
+    },
+    util::{
+        percentile,
+        stats_alloc::{
+            self,
+            MemoryTag,
+        },
+    },
+    voxel::chunk_map::ChunkStatistics,
+};
+
+#[derive(Clone, Debug, clap::Parser)]
+pub struct BenchArgs {
+    #[clap(flatten)]
+    pub app: Args,
+
+    /// How long the camera flies along the path, in seconds.
+    #[clap(short, long, default_value_t = 30.0)]
+    pub duration: f64,
+
+    /// TOML file with the camera path. Defaults to a circle around the
+    /// origin.
+    #[clap(long)]
+    pub camera_path: Option<PathBuf>,
+
+    /// Where to write the results. Defaults to stdout.
+    #[clap(short, long)]
+    pub output: Option<PathBuf>,
+}
+
+impl BenchArgs {
+    /// Arguments for the app that runs the benchmark.
+    pub fn into_app_args(self) -> Result<Args, Error> {
+        let camera_path = self
+            .camera_path
+            .map(CameraPath::load)
+            .transpose()?
+            .unwrap_or_default();
+
+        Ok(Args {
+            bench: Some(BenchConfig {
+                duration: Duration::try_from_secs_f64(self.duration)?,
+                camera_path,
+                output: self.output,
+            }),
+            ..self.app
+        })
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct BenchConfig {
+    pub duration: Duration,
+    pub camera_path: CameraPath,
+    pub output: Option<PathBuf>,
+}
+
+/// A Catmull-Rom spline through the points. The camera looks along the path.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CameraPath {
+    pub points: Vec<Point3<f32>>,
+}
+
+impl CameraPath {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let toml = std::fs::read(path)?;
+        let camera_path: Self = toml::from_slice(&toml)?;
+
+        if camera_path.points.len() < 2 {
+            bail!("Camera path needs at least 2 points");
+        }
+
+        Ok(camera_path)
+    }
+
+    /// Position on the path, with `t` going from 0 to 1.
+    pub fn position(&self, t: f32) -> Point3<f32> {
+        let num_segments = self.points.len() - 1;
+        let s = t.clamp(0.0, 1.0) * num_segments as f32;
+        let segment = (s.floor() as usize).min(num_segments - 1);
+        let f = s - segment as f32;
+
+        // the end points are repeated, so the spline passes through them
+        let p0 = self.points[segment.saturating_sub(1)].coords;
+        let p1 = self.points[segment].coords;
+        let p2 = self.points[segment + 1].coords;
+        let p3 = self.points[(segment + 2).min(num_segments)].coords;
+
+        let position = 0.5
+            * (2.0 * p1
+                + (p2 - p0) * f
+                + (2.0 * p0 - 5.0 * p1 + 4.0 * p2 - p3) * f.powi(2)
+                + (3.0 * p1 - p0 - 3.0 * p2 + p3) * f.powi(3));
+
+        position.into()
+    }
+}
+
+impl Default for CameraPath {
+    fn default() -> Self {
+        const NUM_POINTS: usize = 8;
+        const RADIUS: f32 = 96.0;
+        const HEIGHT: f32 = 24.0;
+
+        let points = (0..=NUM_POINTS)
+            .map(|i| {
+                let angle = std::f32::consts::TAU * i as f32 / NUM_POINTS as f32;
+                Point3::new(RADIUS * angle.cos(), HEIGHT, RADIUS * angle.sin())
+            })
+            .collect();
+
+        Self { points }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct BenchPlugin {
+    pub config: BenchConfig,
+}
+
+impl Plugin for BenchPlugin {
+    fn setup(&self, builder: &mut WorldBuilder) -> Result<(), Error> {
+        builder.insert_resource(Bench {
+            config: self.config.clone(),
+            state: None,
+        });
+
+        builder.add_systems(
+            schedule::Update,
+            (
+                start_bench.run_if(not(resource_exists::<BenchFinished>)),
+                update_bench.run_if(not(resource_exists::<BenchFinished>)),
+            )
+                .chain(),
+        );
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Resource)]
+struct Bench {
+    config: BenchConfig,
+    state: Option<BenchState>,
+}
+
+#[derive(Debug)]
+struct BenchState {
+    start_time: Instant,
+    last_tick: Instant,
+    frame_times: Vec<Duration>,
+    chunk_statistics: ChunkStatistics,
+    peak_bytes_allocated: usize,
+    peak_bytes_allocated_by_tag: [usize; MemoryTag::COUNT],
+}
+
+/// Inserted when the results are written, while the app is shutting down.
+#[derive(Debug, Resource)]
+struct BenchFinished;
+
+fn start_bench(
+    mut bench: ResMut<Bench>,
+    time: Res<Time>,
+    chunk_statistics: Res<ChunkStatistics>,
+    player: Single<Entity, With<Player>>,
+    mut commands: Commands,
+) {
+    if bench.state.is_some() {
+        return;
+    }
+
+    tracing::info!(duration = ?bench.config.duration, "starting benchmark");
+
+    // the camera is moved by the benchmark from now on
+    commands.entity(*player).remove::<CameraControllerState>();
+
+    bench.state = Some(BenchState {
+        start_time: time.tick_start,
+        last_tick: time.tick_start,
+        frame_times: vec![],
+        chunk_statistics: *chunk_statistics,
+        peak_bytes_allocated: 0,
+        peak_bytes_allocated_by_tag: [0; MemoryTag::COUNT],
+    });
+}
+
+fn update_bench(
+    mut bench: ResMut<Bench>,
+    time: Res<Time>,
+    chunk_statistics: Res<ChunkStatistics>,
+    player: Single<&mut LocalTransform, With<Player>>,
+    mut close_app: CloseApp,
+    mut commands: Commands,
+) {
+    let Bench { config, state } = &mut *bench;
+    let Some(state) = state
+    else {
+        return;
+    };
+
+    if time.tick_start > state.last_tick {
+        state.frame_times.push(time.tick_start - state.last_tick);
+        state.last_tick = time.tick_start;
+    }
+
+    state.peak_bytes_allocated = state
+        .peak_bytes_allocated
+        .max(stats_alloc::bytes_allocated());
+    for tag in MemoryTag::ALL {
+        let peak = &mut state.peak_bytes_allocated_by_tag[tag as usize];
+        *peak = (*peak).max(stats_alloc::bytes_allocated_with_tag(tag));
+    }
+
+    let elapsed = time.tick_start - state.start_time;
+    let t = elapsed.as_secs_f32() / config.duration.as_secs_f32();
+
+    if t < 1.0 {
+        let mut transform = player.into_inner();
+        let position = config.camera_path.position(t);
+        let ahead = config.camera_path.position(t + 0.001);
+
+        transform.isometry.translation.vector = position.coords;
+        if let Some(rotation) = look_along(ahead - position) {
+            transform.isometry.rotation = rotation;
+        }
+    }
+    else {
+        let report = BenchReport::new(state, &chunk_statistics, elapsed);
+
+        if let Err(error) = report.write(config.output.as_deref()) {
+            tracing::error!(?error, "could not write benchmark results");
+        }
+
+        commands.insert_resource(BenchFinished);
+        close_app.request_close();
+    }
+}
+
+/// Rotation that makes the camera look along `direction`.
+fn look_along(direction: Vector3<f32>) -> Option<UnitQuaternion<f32>> {
+    // the camera looks along +z
+    (direction.norm_squared() > 1e-8)
+        .then(|| UnitQuaternion::face_towards(&direction, &Vector3::y()))
+}
+
+#[derive(Debug, Serialize)]
+struct BenchReport {
+    duration_seconds: f64,
+    num_frames: usize,
+    frame_time_ms: FrameTimes,
+    chunks_generated: usize,
+    chunks_generated_per_second: f64,
+    chunks_meshed: usize,
+    chunks_meshed_per_second: f64,
+    peak_bytes_allocated: usize,
+    peak_bytes_allocated_by_tag: BTreeMap<&'static str, usize>,
+}
+
+#[derive(Debug, Serialize)]
+struct FrameTimes {
+    mean: f64,
+    p50: f64,
+    p90: f64,
+    p99: f64,
+    max: f64,
+}
+
+impl BenchReport {
+    fn new(state: &BenchState, chunk_statistics: &ChunkStatistics, elapsed: Duration) -> Self {
+        let seconds = elapsed.as_secs_f64();
+
+        let mut frame_times = state
+            .frame_times
+            .iter()
+            .map(|frame_time| frame_time.as_secs_f64() * 1000.0)
+            .collect::<Vec<_>>();
+        frame_times.sort_by(f64::total_cmp);
+
+        let chunks_generated =
+            chunk_statistics.num_chunks_loaded - state.chunk_statistics.num_chunks_loaded;
+        let chunks_meshed =
+            chunk_statistics.num_chunks_meshed - state.chunk_statistics.num_chunks_meshed;
+
+        Self {
+            duration_seconds: seconds,
+            num_frames: frame_times.len(),
+            frame_time_ms: FrameTimes {
+                mean: frame_times.iter().sum::<f64>() / frame_times.len().max(1) as f64,
+                p50: percentile(&frame_times, 0.5),
+                p90: percentile(&frame_times, 0.9),
+                p99: percentile(&frame_times, 0.99),
+                max: frame_times.last().copied().unwrap_or_default(),
+            },
+            chunks_generated,
+            chunks_generated_per_second: chunks_generated as f64 / seconds,
+            chunks_meshed,
+            chunks_meshed_per_second: chunks_meshed as f64 / seconds,
+            peak_bytes_allocated: state.peak_bytes_allocated,
+            peak_bytes_allocated_by_tag: MemoryTag::ALL
+                .iter()
+                .map(|tag| (tag.name(), state.peak_bytes_allocated_by_tag[*tag as usize]))
+                .collect(),
+        }
+    }
+
+    fn write(&self, path: Option<&Path>) -> Result<(), Error> {
+        let json = serde_json::to_string_pretty(self)?;
+
+        if let Some(path) = path {
+            std::fs::write(path, json)?;
+            tracing::info!(path = %path.display(), "benchmark results written");
+        }
+        else {
+            println!("{json}");
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::Point3;
+
+    use crate::{
+        bench::CameraPath,
+        util::percentile,
+    };
+
+    #[test]
+    fn it_passes_through_the_control_points() {
+        let camera_path = CameraPath {
+            points: vec![
+                Point3::new(0.0, 0.0, 0.0),
+                Point3::new(10.0, 5.0, 0.0),
+                Point3::new(10.0, 5.0, 10.0),
+            ],
+        };
+
+        for (t, point) in [0.0, 0.5, 1.0].into_iter().zip(&camera_path.points) {
+            let position = camera_path.position(t);
+            assert!(
+                (position - point).norm() < 1e-4,
+                "t = {t}: {position:?} != {point:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn it_computes_nearest_rank_percentiles() {
+        let values = (1..=100).map(f64::from).collect::<Vec<_>>();
+        assert_eq!(percentile(&values, 0.5), 50.0);
+        assert_eq!(percentile(&values, 0.99), 99.0);
+        assert_eq!(percentile(&values, 1.0), 100.0);
+        assert_eq!(percentile(&[], 0.5), 0.0);
+    }
+}
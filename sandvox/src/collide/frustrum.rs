@@ -4,8 +4,11 @@ use crate::collide::Aabb;
 
 #[derive(Clone, Copy, Debug)]
 pub struct Frustrum {
-    /// Matrix that projects the frustrum to a [-1, 1]^3 cube (e.g. a camera
-    /// projection matrix).
+    /// Matrix that projects the frustrum to a [-1, 1]^2 x [0, 1] box (e.g. a
+    /// camera projection matrix).
+    ///
+    /// Reverse-Z projections work too, since they only swap which end of the
+    /// depth range is the near plane.
     pub matrix: Matrix4<f32>,
 }
 
@@ -0,0 +1,544 @@
+mod preset;
+mod validation;
+
+use std::{
+    fs::File,
+    io::{
+        BufWriter,
+        Write,
+    },
+    num::NonZero,
+    path::{
+        Path,
+        PathBuf,
+    },
+    str::FromStr,
+    time::{
+        Duration,
+        Instant,
+        SystemTime,
+    },
+};
+
+use bevy_ecs::{
+    message::{
+        Message,
+        Messages,
+    },
+    resource::Resource,
+    world::World,
+};
+use color_eyre::eyre::{
+    Error,
+    bail,
+    eyre,
+};
+use serde::{
+    Deserialize,
+    Serialize,
+};
+
+pub use crate::config::{
+    preset::{
+        AutoGraphicsPresetPlugin,
+        GraphicsPreset,
+    },
+    validation::ConfigProblem,
+};
+#[cfg(feature = "rcon")]
+use crate::rcon::RconConfig;
+use crate::{
+    app::Time,
+    config::{
+        preset::{
+            apply_preset,
+            remove_default_preset_keys,
+        },
+        validation::deserialize_config,
+    },
+    ecs::{
+        plugin::{
+            Dependencies,
+            Plugin,
+            WorldBuilder,
+        },
+        schedule,
+    },
+    game::GameConfig,
+    logging::file::LogFileConfig,
+    profiler::ProfilerConfig,
+    render::RenderConfig,
+    sound::SoundConfig,
+    util::modified_time,
+    wgpu::WgpuConfig,
+};
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    #[serde(default)]
+    pub graphics: GraphicsConfig,
+
+    pub sound: Option<SoundConfig>,
+
+    pub num_threads: Option<NonZero<usize>>,
+
+    #[serde(flatten, default)]
+    pub game: GameConfig,
+
+    pub profiler: Option<ProfilerConfig>,
+
+    /// Also log to files, if set.
+    pub log_file: Option<LogFileConfig>,
+
+    #[cfg(feature = "rcon")]
+    pub rcon: Option<RconConfig>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            graphics: Default::default(),
+            sound: None,
+            num_threads: None,
+            game: Default::default(),
+            profiler: None,
+            log_file: None,
+            #[cfg(feature = "rcon")]
+            rcon: None,
+        }
+    }
+}
+
+impl Config {
+    /// Loads the config file and applies overrides from the world,
+    /// environment variables and `overrides` (usually from the command line).
+    ///
+    /// If the config file doesn't exist, it's created with the default config.
+    pub fn load(
+        path: impl AsRef<Path>,
+        world_overrides: &[ConfigOverride],
+        overrides: &[ConfigOverride],
+    ) -> Result<Self, Error> {
+        if !path.as_ref().exists() {
+            Self::default().save(&path)?;
+        }
+
+        let config = Self::read(path, world_overrides, overrides)?;
+
+        tracing::debug!(?config);
+
+        Ok(config)
+    }
+
+    /// Reads the config file and applies overrides. Unlike
+    /// [`load`][Self::load] this fails if the file doesn't exist.
+    ///
+    /// The config is layered: Defaults are overridden by the config file, which
+    /// is overridden by the world's config, which is overridden by environment
+    /// variables, which are overridden by `overrides`. A [`GraphicsPreset`]
+    /// selected in any of these layers only fills in the keys that are not
+    /// set explicitly.
+    pub fn read(
+        path: impl AsRef<Path>,
+        world_overrides: &[ConfigOverride],
+        overrides: &[ConfigOverride],
+    ) -> Result<Self, Error> {
+        tracing::debug!(path = %path.as_ref().display(), "reading config file");
+
+        let toml = std::fs::read_to_string(path)?;
+        let mut table: toml::Table = toml.parse()?;
+
+        for config_override in world_overrides
+            .iter()
+            .chain(&ConfigOverride::from_env())
+            .chain(overrides)
+        {
+            tracing::debug!(?config_override, "overriding config");
+            config_override.apply(&mut table)?;
+        }
+
+        apply_preset(&mut table)?;
+
+        let (config, problems) = deserialize_config(table);
+
+        if !problems.is_empty() {
+            for problem in &problems {
+                tracing::warn!("config: {problem}");
+            }
+            tracing::warn!(
+                num_problems = problems.len(),
+                "found problems in config. using defaults for the affected keys."
+            );
+        }
+
+        Ok(config)
+    }
+
+    /// Writes the config file.
+    ///
+    /// Keys set by a [`GraphicsPreset`] are left out if they have their default
+    /// value, so that selecting a preset in the file later still has an effect.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), Error> {
+        tracing::debug!(path = %path.as_ref().display(), "writing config file");
+
+        let mut table = toml::Table::try_from(self)?;
+        remove_default_preset_keys(&mut table, &toml::Table::try_from(Self::default())?);
+
+        if let Some(parent) = path.as_ref().parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let mut writer = BufWriter::new(File::create(&path)?);
+        writer.write_all(
+            "# This file will be modified by the game. Any manual changes might be lost.\n\n"
+                .as_bytes(),
+        )?;
+
+        writer.write_all(toml::to_string_pretty(&table)?.as_bytes())?;
+
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct GraphicsConfig {
+    #[serde(default)]
+    pub preset: Option<GraphicsPreset>,
+
+    #[serde(flatten)]
+    pub wgpu: WgpuConfig,
+
+    #[serde(flatten)]
+    pub render: RenderConfig,
+}
+
+/// Prefix for environment variables that override config keys.
+///
+/// Nested keys are separated by `__`, e.g. `SANDVOX_GRAPHICS__VSYNC=false`.
+const ENV_PREFIX: &str = "SANDVOX_";
+
+/// Overrides a single config key, e.g. `graphics.vsync=false`.
+///
+/// The value is parsed as TOML. If that fails, it's used as a string.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ConfigOverride {
+    pub key: Vec<String>,
+    pub value: toml::Value,
+}
+
+impl ConfigOverride {
+    pub fn new(key: &str, value: &str) -> Self {
+        let key = key.split('.').map(|part| part.trim().to_owned()).collect();
+
+        // there's no way to parse only a value, so we wrap it in a document
+        let value = format!("value = {value}")
+            .parse::<toml::Table>()
+            .ok()
+            .and_then(|mut table| table.remove("value"))
+            .unwrap_or_else(|| toml::Value::String(value.to_owned()));
+
+        Self { key, value }
+    }
+
+    /// Reads overrides from environment variables starting with `SANDVOX_`.
+    pub fn from_env() -> Vec<Self> {
+        std::env::vars()
+            .filter_map(|(name, value)| {
+                let key = name
+                    .strip_prefix(ENV_PREFIX)?
+                    .to_lowercase()
+                    .replace("__", ".");
+                Some(Self::new(&key, &value))
+            })
+            .collect()
+    }
+
+    /// Turns every value in `table` into an override.
+    ///
+    /// Nested tables are merged key by key instead of being replaced.
+    pub fn from_table(table: &toml::Table) -> Vec<Self> {
+        fn flatten(
+            table: &toml::Table,
+            path: &mut Vec<String>,
+            overrides: &mut Vec<ConfigOverride>,
+        ) {
+            for (key, value) in table {
+                path.push(key.clone());
+
+                if let toml::Value::Table(table) = value {
+                    flatten(table, path, overrides);
+                }
+                else {
+                    overrides.push(ConfigOverride {
+                        key: path.clone(),
+                        value: value.clone(),
+                    });
+                }
+
+                path.pop();
+            }
+        }
+
+        let mut overrides = vec![];
+        flatten(table, &mut vec![], &mut overrides);
+        overrides
+    }
+
+    /// Returns the current value of the key in `table`.
+    pub fn get<'a>(&self, table: &'a toml::Table) -> Option<&'a toml::Value> {
+        let (last, parents) = self.key.split_last()?;
+
+        let mut table = table;
+        for part in parents {
+            table = table.get(part)?.as_table()?;
+        }
+
+        table.get(last)
+    }
+
+    pub fn apply(&self, table: &mut toml::Table) -> Result<(), Error> {
+        let Some((last, parents)) = self.key.split_last()
+        else {
+            bail!("Empty config key");
+        };
+
+        let mut table = table;
+        for part in parents {
+            let value = table
+                .entry(part.clone())
+                .or_insert_with(|| toml::Value::Table(Default::default()));
+
+            table = value.as_table_mut().ok_or_else(|| {
+                eyre!(
+                    "Can't override {}: {part} is not a table",
+                    self.key.join(".")
+                )
+            })?;
+        }
+
+        table.insert(last.clone(), self.value.clone());
+
+        Ok(())
+    }
+}
+
+impl FromStr for ConfigOverride {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (key, value) = s
+            .split_once('=')
+            .ok_or_else(|| eyre!("Expected KEY=VALUE: {s}"))?;
+        Ok(Self::new(key, value))
+    }
+}
+
+/// How often the config file is checked for modifications.
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Reloads the config file when it's modified and applies the changes to the
+/// config resources.
+///
+/// Systems that need to react to a changed config can listen for
+/// [`ConfigChanged`] messages.
+#[derive(Clone, Debug)]
+pub struct ConfigWatcherPlugin {
+    pub path: PathBuf,
+
+    /// Overrides from the world that is loaded.
+    pub world_overrides: Vec<ConfigOverride>,
+
+    /// Overrides that are applied every time the config is reloaded.
+    pub overrides: Vec<ConfigOverride>,
+
+    /// The config that is currently in use.
+    pub config: Config,
+}
+
+impl Plugin for ConfigWatcherPlugin {
+    fn dependencies(&self, dependencies: &mut Dependencies) -> Result<(), Error> {
+        if self.config.graphics.preset == Some(GraphicsPreset::Auto) {
+            dependencies.add_plugin(AutoGraphicsPresetPlugin)?;
+        }
+
+        Ok(())
+    }
+
+    fn setup(&self, builder: &mut WorldBuilder) -> Result<(), Error> {
+        builder
+            .add_message::<ConfigChanged>()
+            .insert_resource(ConfigWatcher {
+                path: self.path.clone(),
+                world_overrides: self.world_overrides.clone(),
+                overrides: self.overrides.clone(),
+                modified: modified_time(&self.path),
+                last_poll: None,
+                config: self.config.clone(),
+            })
+            .add_systems(schedule::PreUpdate, watch_config);
+
+        Ok(())
+    }
+}
+
+/// Sent when a section of the config was reloaded.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Message)]
+pub enum ConfigChanged {
+    Render,
+    Wgpu,
+    Sound,
+    Game,
+}
+
+#[derive(Debug, Resource)]
+struct ConfigWatcher {
+    path: PathBuf,
+    world_overrides: Vec<ConfigOverride>,
+    overrides: Vec<ConfigOverride>,
+    modified: Option<SystemTime>,
+    last_poll: Option<Instant>,
+    config: Config,
+}
+
+impl ConfigWatcher {
+    /// Adds an override and reloads the config on the next poll.
+    fn override_and_reload(&mut self, config_override: ConfigOverride) {
+        self.overrides.push(config_override);
+        self.modified = None;
+        self.last_poll = None;
+    }
+}
+
+fn watch_config(world: &mut World) {
+    let now = world.resource::<Time>().tick_start;
+    let mut watcher = world.resource_mut::<ConfigWatcher>();
+
+    if watcher
+        .last_poll
+        .is_some_and(|last_poll| now.duration_since(last_poll) < POLL_INTERVAL)
+    {
+        return;
+    }
+    watcher.last_poll = Some(now);
+
+    let modified = modified_time(&watcher.path);
+    if modified.is_none() || modified == watcher.modified {
+        return;
+    }
+    watcher.modified = modified;
+
+    tracing::info!(path = %watcher.path.display(), "config file modified");
+
+    let config = match Config::read(&watcher.path, &watcher.world_overrides, &watcher.overrides) {
+        Ok(config) => config,
+        Err(error) => {
+            tracing::error!(?error, "could not reload config");
+            return;
+        }
+    };
+
+    let previous = std::mem::replace(&mut watcher.config, config.clone());
+    let mut changed = vec![];
+
+    if config.graphics.render != previous.graphics.render {
+        world.insert_resource(config.graphics.render);
+        changed.push(ConfigChanged::Render);
+    }
+
+    if config.graphics.wgpu != previous.graphics.wgpu {
+        // the wgpu config is only used when creating the context
+        tracing::warn!("changes to the wgpu config only take effect after a restart");
+        changed.push(ConfigChanged::Wgpu);
+    }
+
+    if config.sound != previous.sound {
+        // the sound systems pick this up via change detection
+        if let Some(sound_config) = config.sound {
+            world.insert_resource(sound_config);
+        }
+        else {
+            world.remove_resource::<SoundConfig>();
+        }
+        changed.push(ConfigChanged::Sound);
+    }
+
+    if config.game != previous.game {
+        world.insert_resource(config.game);
+        changed.push(ConfigChanged::Game);
+    }
+
+    if config.num_threads != previous.num_threads {
+        tracing::warn!("changes to num_threads only take effect after a restart");
+    }
+
+    tracing::debug!(?changed, "applied config changes");
+
+    world
+        .resource_mut::<Messages<ConfigChanged>>()
+        .write_batch(changed);
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::ConfigOverride;
+
+    #[test]
+    fn it_parses_overrides() {
+        let config_override: ConfigOverride = "graphics.vsync=false".parse().unwrap();
+        assert_eq!(config_override.key, ["graphics", "vsync"]);
+        assert_eq!(config_override.value, toml::Value::Boolean(false));
+
+        let config_override: ConfigOverride = "sound.host=alsa".parse().unwrap();
+        assert_eq!(
+            config_override.value,
+            toml::Value::String("alsa".to_owned())
+        );
+
+        assert!("graphics.vsync".parse::<ConfigOverride>().is_err());
+    }
+
+    #[test]
+    fn it_applies_overrides_to_nested_tables() {
+        let mut table: toml::Table = "[graphics]\nvsync = true\nfov = 60.0".parse().unwrap();
+
+        ConfigOverride::new("graphics.vsync", "false")
+            .apply(&mut table)
+            .unwrap();
+        ConfigOverride::new("graphics.lighting.moon.intensity", "0.5")
+            .apply(&mut table)
+            .unwrap();
+
+        let graphics = table["graphics"].as_table().unwrap();
+        assert_eq!(graphics["vsync"].as_bool(), Some(false));
+        assert_eq!(graphics["fov"].as_float(), Some(60.0));
+        assert_eq!(
+            graphics["lighting"]["moon"]["intensity"].as_float(),
+            Some(0.5)
+        );
+
+        assert!(
+            ConfigOverride::new("graphics.vsync.foo", "1")
+                .apply(&mut table)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn it_flattens_tables_into_overrides() {
+        let table: toml::Table = "chunk_load_distance = 8\n[item_drops]\ndespawn_time = 60.0"
+            .parse()
+            .unwrap();
+
+        let overrides = ConfigOverride::from_table(&table);
+
+        assert_eq!(
+            overrides,
+            [
+                ConfigOverride::new("chunk_load_distance", "8"),
+                ConfigOverride::new("item_drops.despawn_time", "60.0"),
+            ]
+        );
+    }
+}
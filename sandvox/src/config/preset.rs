@@ -0,0 +1,331 @@
+use std::time::{
+    Duration,
+    Instant,
+};
+
+use bevy_ecs::{
+    resource::Resource,
+    system::{
+        Query,
+        Res,
+        ResMut,
+    },
+};
+use color_eyre::eyre::Error;
+use serde::{
+    Deserialize,
+    Serialize,
+};
+
+use crate::{
+    app::Time,
+    config::{
+        ConfigOverride,
+        ConfigWatcher,
+    },
+    ecs::{
+        plugin::{
+            Plugin,
+            WorldBuilder,
+        },
+        schedule,
+    },
+    render::{
+        RenderConfig,
+        surface::Surface,
+    },
+    util::percentile,
+    wgpu::WgpuContext,
+};
+
+/// Time after startup before the benchmark starts measuring. Chunk loading
+/// and pipeline creation make the first frames unrepresentative.
+const BENCHMARK_WARMUP: Duration = Duration::from_secs(3);
+
+/// How long the benchmark measures frame times.
+const BENCHMARK_DURATION: Duration = Duration::from_secs(5);
+
+/// The preset is picked from this percentile of the frame times, so that
+/// regular stutter counts, but a single hitch doesn't.
+const BENCHMARK_PERCENTILE: f64 = 0.95;
+
+/// Named sets of graphics settings.
+///
+/// A preset only provides defaults: Keys that are set explicitly (in the config
+/// file, environment or command line) take precedence over the preset.
+///
+/// Presets only cover settings the renderer actually has. At the moment these
+/// are the render distance and the depth prepass. There's no settings UI
+/// either, so a preset is selected with `graphics.preset`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GraphicsPreset {
+    Low,
+    Medium,
+    High,
+    Ultra,
+
+    /// Starts with [`Medium`][Self::Medium], measures frame times for the
+    /// first few seconds and then switches to the preset the hardware can
+    /// handle.
+    Auto,
+}
+
+impl GraphicsPreset {
+    /// The config keys set by this preset.
+    pub fn settings(&self) -> &'static [(&'static str, &'static str)] {
+        match self {
+            Self::Low => {
+                &[
+                    ("chunk_load_distance", "2"),
+                    ("chunk_render_distance", "2"),
+                    ("graphics.depth_prepass", "false"),
+                ]
+            }
+            Self::Medium | Self::Auto => {
+                &[
+                    ("chunk_load_distance", "4"),
+                    ("chunk_render_distance", "4"),
+                    ("graphics.depth_prepass", "false"),
+                ]
+            }
+            Self::High => {
+                &[
+                    ("chunk_load_distance", "6"),
+                    ("chunk_render_distance", "6"),
+                    ("graphics.depth_prepass", "true"),
+                ]
+            }
+            Self::Ultra => {
+                &[
+                    ("chunk_load_distance", "8"),
+                    ("chunk_render_distance", "8"),
+                    ("graphics.depth_prepass", "true"),
+                ]
+            }
+        }
+    }
+
+    /// Picks a preset from a frame time measured with the
+    /// [`Medium`][Self::Medium] preset and vsync disabled.
+    pub fn from_benchmark(frame_time: Duration) -> Self {
+        let fps = 1.0 / frame_time.as_secs_f32();
+
+        if fps >= 120.0 {
+            Self::Ultra
+        }
+        else if fps >= 60.0 {
+            Self::High
+        }
+        else if fps >= 30.0 {
+            Self::Medium
+        }
+        else {
+            Self::Low
+        }
+    }
+
+    /// Sets the keys of the preset that are not already set in `table`.
+    pub(super) fn apply_defaults(&self, table: &mut toml::Table) -> Result<(), Error> {
+        for (key, value) in self.settings() {
+            let config_override = ConfigOverride::new(key, value);
+            if config_override.get(table).is_none() {
+                config_override.apply(table)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Expands the preset selected in `table` (if any).
+///
+/// An invalid preset is left alone, so that config validation reports it.
+pub(super) fn apply_preset(table: &mut toml::Table) -> Result<(), Error> {
+    let preset = table
+        .get("graphics")
+        .and_then(|graphics| graphics.get("preset"))
+        .and_then(|preset| preset.clone().try_into::<GraphicsPreset>().ok());
+
+    if let Some(preset) = preset {
+        tracing::debug!(?preset, "applying graphics preset");
+        preset.apply_defaults(table)?;
+    }
+
+    Ok(())
+}
+
+/// Removes the keys set by presets from `table` if they have the same value as
+/// in `defaults`.
+///
+/// Keys in the config file take precedence over the preset, so a config file
+/// that contains all of them would make every preset a no-op.
+pub(super) fn remove_default_preset_keys(table: &mut toml::Table, defaults: &toml::Table) {
+    fn remove_if_default(table: &mut toml::Table, defaults: &toml::Table, key: &[&str]) {
+        match key {
+            [] => {}
+            [last] => {
+                if table
+                    .get(*last)
+                    .is_some_and(|value| defaults.get(*last) == Some(value))
+                {
+                    table.remove(*last);
+                }
+            }
+            [first, rest @ ..] => {
+                if let (Some(table), Some(defaults)) = (
+                    table.get_mut(*first).and_then(|value| value.as_table_mut()),
+                    defaults.get(*first).and_then(|value| value.as_table()),
+                ) {
+                    remove_if_default(table, defaults, rest);
+                }
+            }
+        }
+    }
+
+    // all presets set the same keys
+    for (key, _) in GraphicsPreset::Medium.settings() {
+        let key = key.split('.').collect::<Vec<_>>();
+        remove_if_default(table, defaults, &key);
+    }
+}
+
+/// Measures frame times for the first few seconds and then selects a graphics
+/// preset.
+///
+/// Vsync is disabled while measuring, since it would cap the frame rate at the
+/// refresh rate.
+///
+/// The selected preset is passed to the [`ConfigWatcher`] as an override, which
+/// reloads the config and applies the changes like it would for a modified
+/// config file.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct AutoGraphicsPresetPlugin;
+
+impl Plugin for AutoGraphicsPresetPlugin {
+    fn setup(&self, builder: &mut WorldBuilder) -> Result<(), Error> {
+        builder
+            .insert_resource(GraphicsBenchmark {
+                start: Instant::now(),
+                last_frame: None,
+                frame_times: vec![],
+                done: false,
+            })
+            .add_systems(schedule::Update, run_benchmark);
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Resource)]
+struct GraphicsBenchmark {
+    start: Instant,
+    last_frame: Option<Instant>,
+    frame_times: Vec<f64>,
+    done: bool,
+}
+
+fn run_benchmark(
+    time: Res<Time>,
+    wgpu: Res<WgpuContext>,
+    render_config: Res<RenderConfig>,
+    mut surfaces: Query<&mut Surface>,
+    mut benchmark: ResMut<GraphicsBenchmark>,
+    mut config_watcher: ResMut<ConfigWatcher>,
+) {
+    if benchmark.done {
+        return;
+    }
+
+    // surfaces can be created (or reconfigured by a config reload) while we're
+    // measuring
+    for mut surface in &mut surfaces {
+        surface.set_vsync(&wgpu, false);
+    }
+
+    let elapsed = time.tick_start.duration_since(benchmark.start);
+    if elapsed < BENCHMARK_WARMUP {
+        return;
+    }
+
+    if let Some(last_frame) = benchmark.last_frame.replace(time.tick_start) {
+        let frame_time = time.tick_start.duration_since(last_frame);
+        benchmark.frame_times.push(frame_time.as_secs_f64());
+    }
+
+    if elapsed < BENCHMARK_WARMUP + BENCHMARK_DURATION || benchmark.frame_times.is_empty() {
+        return;
+    }
+
+    benchmark.frame_times.sort_by(f64::total_cmp);
+    let frame_time =
+        Duration::from_secs_f64(percentile(&benchmark.frame_times, BENCHMARK_PERCENTILE));
+    let preset = GraphicsPreset::from_benchmark(frame_time);
+    benchmark.done = true;
+
+    for mut surface in &mut surfaces {
+        surface.set_vsync(&wgpu, render_config.vsync);
+    }
+
+    tracing::info!(?preset, ?frame_time, "selected graphics preset");
+
+    let preset = toml::Value::try_from(preset).expect("Graphics preset can't be serialized");
+    config_watcher.override_and_reload(ConfigOverride {
+        key: vec!["graphics".to_owned(), "preset".to_owned()],
+        value: preset,
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::config::{
+        Config,
+        ConfigOverride,
+        GraphicsPreset,
+        preset::apply_preset,
+    };
+
+    #[test]
+    fn it_applies_presets_without_overriding_explicit_keys() {
+        let mut table: toml::Table = "chunk_render_distance = 3\n[graphics]\npreset = \"ultra\""
+            .parse()
+            .unwrap();
+
+        apply_preset(&mut table).unwrap();
+
+        assert_eq!(table["chunk_render_distance"].as_integer(), Some(3));
+        assert_eq!(table["chunk_load_distance"].as_integer(), Some(8));
+        assert_eq!(table["graphics"]["depth_prepass"].as_bool(), Some(true));
+    }
+
+    #[test]
+    fn it_applies_presets_to_a_default_config_file() {
+        let path =
+            std::env::temp_dir().join(format!("sandvox-preset-test-{}.toml", std::process::id()));
+        Config::default().save(&path).unwrap();
+
+        let config = Config::read(
+            &path,
+            &[],
+            &[ConfigOverride::new("graphics.preset", "\"ultra\"")],
+        );
+        std::fs::remove_file(&path).unwrap();
+        let config = config.unwrap();
+
+        assert_eq!(config.game.chunk_load_distance, 8);
+        assert_eq!(config.game.chunk_render_distance, 8);
+        assert!(config.graphics.render.depth_prepass);
+    }
+
+    #[test]
+    fn it_picks_presets_from_frame_times() {
+        let preset = |ms| GraphicsPreset::from_benchmark(Duration::from_millis(ms));
+
+        assert_eq!(preset(5), GraphicsPreset::Ultra);
+        assert_eq!(preset(12), GraphicsPreset::High);
+        assert_eq!(preset(25), GraphicsPreset::Medium);
+        assert_eq!(preset(50), GraphicsPreset::Low);
+    }
+}
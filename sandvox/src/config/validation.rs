@@ -0,0 +1,530 @@
+use std::fmt::Display;
+
+use crate::{
+    config::Config,
+    sound::Volume,
+};
+
+/// A problem with the config that was fixed by falling back to the default.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ConfigProblem {
+    pub key: String,
+    pub message: String,
+}
+
+impl ConfigProblem {
+    fn new(key: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            key: key.into(),
+            message: message.into(),
+        }
+    }
+}
+
+impl Display for ConfigProblem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.key.is_empty() {
+            write!(f, "{}", self.message)
+        }
+        else {
+            write!(f, "{}: {}", self.key, self.message)
+        }
+    }
+}
+
+/// Deserializes the config, collecting all problems instead of failing on the
+/// first one.
+///
+/// If the config doesn't deserialize as a whole, values are added one by one
+/// to the default config, skipping the ones that are invalid. Tables are tried
+/// as a whole first, so e.g. maps replace the default instead of being merged
+/// into it.
+pub fn deserialize_config(table: toml::Table) -> (Config, Vec<ConfigProblem>) {
+    let mut problems = vec![];
+
+    let mut config = match deserialize(&table) {
+        Ok(config) => config,
+        Err(_) => {
+            let defaults = toml::Table::try_from(Config::default())
+                .expect("Default config can't be serialized");
+
+            let mut accepted = defaults.clone();
+            merge_valid(&mut accepted, &defaults, &table, &mut vec![], &mut problems);
+
+            deserialize(&accepted).unwrap_or_else(|error| {
+                problems.push(ConfigProblem::new("", error.message()));
+                Config::default()
+            })
+        }
+    };
+
+    config.validate(&mut problems);
+
+    (config, problems)
+}
+
+fn deserialize(table: &toml::Table) -> Result<Config, toml::de::Error> {
+    toml::Value::Table(table.clone()).try_into()
+}
+
+fn merge_valid(
+    accepted: &mut toml::Table,
+    defaults: &toml::Table,
+    table: &toml::Table,
+    path: &mut Vec<String>,
+    problems: &mut Vec<ConfigProblem>,
+) {
+    for (key, value) in table {
+        path.push(key.clone());
+
+        let previous = set(accepted, path, Some(value.clone()));
+
+        if let Err(error) = deserialize(accepted) {
+            if let toml::Value::Table(value) = value {
+                // try the table's entries one by one. if the table didn't exist before,
+                // start with an empty one.
+                set(
+                    accepted,
+                    path,
+                    Some(
+                        previous
+                            .clone()
+                            .unwrap_or_else(|| toml::Table::new().into()),
+                    ),
+                );
+
+                if deserialize(accepted).is_ok() {
+                    merge_valid(accepted, defaults, value, path, problems);
+                }
+                else {
+                    set(accepted, path, previous);
+                    problems.push(describe(&error, defaults, path));
+                }
+            }
+            else {
+                set(accepted, path, previous);
+                problems.push(describe(&error, defaults, path));
+            }
+        }
+
+        path.pop();
+    }
+}
+
+/// Sets (or removes) the value at `path`, returning the previous value.
+///
+/// Parent tables must exist.
+fn set(
+    table: &mut toml::Table,
+    path: &[String],
+    value: Option<toml::Value>,
+) -> Option<toml::Value> {
+    let (last, parents) = path.split_last().unwrap();
+
+    let mut table = table;
+    for part in parents {
+        table = table
+            .get_mut(part)
+            .and_then(|value| value.as_table_mut())
+            .unwrap();
+    }
+
+    if let Some(value) = value {
+        table.insert(last.clone(), value)
+    }
+    else {
+        table.remove(last)
+    }
+}
+
+fn describe(error: &toml::de::Error, defaults: &toml::Table, path: &[String]) -> ConfigProblem {
+    let key = path.join(".");
+    let message = error.message();
+
+    if message.starts_with("unknown field") {
+        let (last, parents) = path.split_last().unwrap();
+
+        // known keys are the ones serde tells us about, and the ones in the default
+        // config.
+        let mut candidates = message
+            .split_once("expected")
+            .map(|(_, expected)| {
+                expected
+                    .split('`')
+                    .skip(1)
+                    .step_by(2)
+                    .map(ToOwned::to_owned)
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+
+        let mut table = Some(defaults);
+        for part in parents {
+            table = table
+                .and_then(|table| table.get(part))
+                .and_then(|value| value.as_table());
+        }
+        candidates.extend(table.into_iter().flat_map(|table| table.keys().cloned()));
+
+        let suggestion = candidates
+            .iter()
+            .map(|candidate| (candidate, strsim::jaro_winkler(last, candidate)))
+            .filter(|(_, similarity)| *similarity > 0.8)
+            .max_by(|(_, a), (_, b)| a.total_cmp(b));
+
+        if let Some((suggestion, _)) = suggestion {
+            ConfigProblem::new(key, format!("unknown key, did you mean `{suggestion}`?"))
+        }
+        else {
+            ConfigProblem::new(key, "unknown key")
+        }
+    }
+    else {
+        ConfigProblem::new(key, message)
+    }
+}
+
+impl Config {
+    /// Checks that values are in range, and resets them to their defaults
+    /// otherwise.
+    fn validate(&mut self, problems: &mut Vec<ConfigProblem>) {
+        let defaults = Config::default();
+
+        check(
+            problems,
+            "graphics.fov",
+            &mut self.graphics.render.fov,
+            defaults.graphics.render.fov,
+            |fov| fov > 0.0 && fov < 180.0,
+            "a value between 0 and 180 degrees",
+        );
+
+        if let Some(far_plane) = &mut self.graphics.render.far_plane {
+            // an infinite far plane is the same as the default
+            check(
+                problems,
+                "graphics.far_plane",
+                far_plane,
+                f32::INFINITY,
+                |far_plane| far_plane >= 1.0,
+                "a distance of at least 1 block",
+            );
+        }
+
+        let color_grading = &mut self.graphics.render.color_grading;
+        let default_color_grading = defaults.graphics.render.color_grading;
+        check(
+            problems,
+            "graphics.color_grading.brightness",
+            &mut color_grading.brightness,
+            default_color_grading.brightness,
+            |brightness| (-1.0..=1.0).contains(&brightness),
+            "a value between -1 and 1",
+        );
+        check(
+            problems,
+            "graphics.color_grading.contrast",
+            &mut color_grading.contrast,
+            default_color_grading.contrast,
+            |contrast| contrast >= 0.0,
+            "a non-negative value",
+        );
+        check(
+            problems,
+            "graphics.color_grading.gamma",
+            &mut color_grading.gamma,
+            default_color_grading.gamma,
+            |gamma| gamma > 0.0,
+            "a positive value",
+        );
+
+        let exposure = &mut self.graphics.render.exposure;
+        let default_exposure = defaults.graphics.render.exposure;
+        check(
+            problems,
+            "graphics.exposure.adaptation_speed",
+            &mut exposure.adaptation_speed,
+            default_exposure.adaptation_speed,
+            |speed| speed >= 0.0,
+            "a non-negative value",
+        );
+        let min = exposure.min;
+        check(
+            problems,
+            "graphics.exposure.max",
+            &mut exposure.max,
+            default_exposure.max.max(min),
+            |max| max >= min,
+            "a value of at least `min`",
+        );
+
+        if let Some(sound) = &mut self.sound {
+            for (key, volume) in [
+                ("sound.master_volume", &mut sound.master_volume),
+                ("sound.effect_volume", &mut sound.effect_volume),
+                ("sound.music_volume", &mut sound.music_volume),
+            ] {
+                check(
+                    problems,
+                    key,
+                    &mut volume.0,
+                    Volume::default().0,
+                    |volume| volume >= 0.0,
+                    "a non-negative volume",
+                );
+            }
+        }
+
+        let game = &mut self.game;
+        for (key, distance, default) in [
+            (
+                "chunk_load_distance",
+                &mut game.chunk_load_distance,
+                defaults.game.chunk_load_distance,
+            ),
+            (
+                "chunk_render_distance",
+                &mut game.chunk_render_distance,
+                defaults.game.chunk_render_distance,
+            ),
+        ] {
+            if *distance == 0 {
+                problems.push(ConfigProblem::new(key, "expected at least 1 chunk"));
+                *distance = default;
+            }
+        }
+
+        let camera_controller = &mut game.camera_controller;
+        for (key, value, default) in [
+            (
+                "camera_controller.mouse_sensitivity",
+                &mut camera_controller.mouse_sensitivity,
+                defaults.game.camera_controller.mouse_sensitivity,
+            ),
+            (
+                "camera_controller.movement_speed",
+                &mut camera_controller.movement_speed,
+                defaults.game.camera_controller.movement_speed,
+            ),
+        ] {
+            check(
+                problems,
+                key,
+                value,
+                default,
+                |value| value > 0.0,
+                "a positive value",
+            );
+        }
+
+        let item_drops = &mut game.item_drops;
+        for (key, value, default) in [
+            (
+                "item_drops.despawn_time",
+                &mut item_drops.despawn_time,
+                defaults.game.item_drops.despawn_time,
+            ),
+            (
+                "item_drops.pickup_delay",
+                &mut item_drops.pickup_delay,
+                defaults.game.item_drops.pickup_delay,
+            ),
+            (
+                "item_drops.magnet_radius",
+                &mut item_drops.magnet_radius,
+                defaults.game.item_drops.magnet_radius,
+            ),
+            (
+                "item_drops.pickup_radius",
+                &mut item_drops.pickup_radius,
+                defaults.game.item_drops.pickup_radius,
+            ),
+            (
+                "item_drops.magnet_speed",
+                &mut item_drops.magnet_speed,
+                defaults.game.item_drops.magnet_speed,
+            ),
+        ] {
+            check(
+                problems,
+                key,
+                value,
+                default,
+                |value| value >= 0.0,
+                "a non-negative value",
+            );
+        }
+
+        let validation = &mut game.validation;
+        for (key, value, default) in [
+            (
+                "validation.max_horizontal_speed",
+                &mut validation.max_horizontal_speed,
+                defaults.game.validation.max_horizontal_speed,
+            ),
+            (
+                "validation.max_vertical_speed",
+                &mut validation.max_vertical_speed,
+                defaults.game.validation.max_vertical_speed,
+            ),
+            (
+                "validation.max_teleport_distance",
+                &mut validation.max_teleport_distance,
+                defaults.game.validation.max_teleport_distance,
+            ),
+            (
+                "validation.edits_per_second",
+                &mut validation.edits_per_second,
+                defaults.game.validation.edits_per_second,
+            ),
+        ] {
+            check(
+                problems,
+                key,
+                value,
+                default,
+                |value| value > 0.0,
+                "a positive value",
+            );
+        }
+
+        // a smaller bucket could never hold a token
+        check(
+            problems,
+            "validation.edit_burst",
+            &mut validation.edit_burst,
+            defaults.game.validation.edit_burst,
+            |value| value >= 1.0,
+            "at least 1",
+        );
+
+        let interest = &mut game.interest;
+        for (key, value, default) in [
+            (
+                "interest.radius",
+                &mut interest.radius,
+                defaults.game.interest.radius,
+            ),
+            (
+                "interest.bandwidth",
+                &mut interest.bandwidth,
+                defaults.game.interest.bandwidth,
+            ),
+        ] {
+            check(
+                problems,
+                key,
+                value,
+                default,
+                |value| value > 0.0,
+                "a positive value",
+            );
+        }
+        check(
+            problems,
+            "interest.movement_priority",
+            &mut interest.movement_priority,
+            defaults.game.interest.movement_priority,
+            |value| value >= 0.0,
+            "a non-negative value",
+        );
+    }
+}
+
+fn check(
+    problems: &mut Vec<ConfigProblem>,
+    key: &str,
+    value: &mut f32,
+    default: f32,
+    is_valid: impl FnOnce(f32) -> bool,
+    expected: &str,
+) {
+    // note: this also catches NaN
+    if !is_valid(*value) {
+        problems.push(ConfigProblem::new(
+            key,
+            format!("expected {expected}, but got {value}"),
+        ));
+        *value = default;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::validation::{
+        ConfigProblem,
+        deserialize_config,
+    };
+
+    fn parse(toml: &str) -> toml::Table {
+        toml.parse().unwrap()
+    }
+
+    #[test]
+    fn it_accepts_a_valid_config() {
+        let (config, problems) = deserialize_config(parse(
+            "chunk_load_distance = 8\n[graphics]\nvsync = false\nfov = 90.0",
+        ));
+
+        assert!(problems.is_empty());
+        assert_eq!(config.game.chunk_load_distance, 8);
+        assert!(!config.graphics.render.vsync);
+        assert_eq!(config.graphics.render.fov, 90.0);
+    }
+
+    #[test]
+    fn it_reports_all_problems_and_keeps_valid_values() {
+        let (config, problems) = deserialize_config(parse(
+            "chunk_load_distance = 8\n[graphics]\nvsyn = false\nfov = -10.0\nstaging_chunk_size = \
+             0\ndepth_prepass = true",
+        ));
+
+        assert_eq!(
+            problems,
+            [
+                ConfigProblem::new("graphics.vsyn", "unknown key, did you mean `vsync`?"),
+                ConfigProblem::new("graphics.staging_chunk_size", problems[1].message.clone()),
+                ConfigProblem::new(
+                    "graphics.fov",
+                    "expected a value between 0 and 180 degrees, but got -10"
+                ),
+            ]
+        );
+
+        assert_eq!(config.game.chunk_load_distance, 8);
+        assert!(config.graphics.render.depth_prepass);
+        assert_eq!(config.graphics.render.fov, 60.0);
+        assert_eq!(config.graphics.wgpu.staging_chunk_size.get(), 0x100_000);
+    }
+
+    #[test]
+    fn it_resets_invalid_color_grading() {
+        let (config, problems) = deserialize_config(parse(
+            "[graphics.color_grading]\nbrightness = 0.1\ngamma = 0.0",
+        ));
+
+        assert_eq!(
+            problems,
+            [ConfigProblem::new(
+                "graphics.color_grading.gamma",
+                "expected a positive value, but got 0"
+            )]
+        );
+
+        assert_eq!(config.graphics.render.color_grading.brightness, 0.1);
+        assert_eq!(config.graphics.render.color_grading.gamma, 1.0);
+    }
+
+    #[test]
+    fn it_suggests_top_level_keys() {
+        let (_config, problems) = deserialize_config(parse("chunk_load_distanse = 8"));
+
+        assert_eq!(
+            problems,
+            [ConfigProblem::new(
+                "chunk_load_distanse",
+                "unknown key, did you mean `chunk_load_distance`?"
+            )]
+        );
+    }
+}
@@ -0,0 +1,231 @@
+//! Writes a crash report when the game panics.
+//!
+//! [`install`] sets up a panic hook that remembers the panic and its backtrace.
+//! The main loop runs in a [`guard`], which catches panics, so that the game
+//! can be saved before it goes down. The report is written after that, so
+//! it can tell whether saving worked. Panics anywhere else are reported right
+//! away.
+//!
+//! A report contains the panic message and backtrace, the build info, the GPU
+//! adapter and the most recent log records. Reports are written to the
+//! directory set with [`set_directory`], and can be attached to bug reports.
+
+use std::{
+    any::Any,
+    backtrace::Backtrace,
+    fmt::Write,
+    panic::{
+        AssertUnwindSafe,
+        PanicHookInfo,
+    },
+    path::{
+        Path,
+        PathBuf,
+    },
+    sync::atomic::{
+        AtomicUsize,
+        Ordering,
+    },
+};
+
+use chrono::{
+    DateTime,
+    Utc,
+};
+use color_eyre::eyre::Error;
+use parking_lot::Mutex;
+
+use crate::{
+    build_info::BUILD_INFO,
+    logging::recent_logs,
+};
+
+/// Used until [`set_directory`] is called.
+const DEFAULT_DIRECTORY: &str = "crashes";
+
+static CRASH: Mutex<CrashState> = parking_lot::const_mutex(CrashState {
+    panic: None,
+    adapter: None,
+    directory: None,
+});
+
+/// Number of [`guard`]s that are running. While this is non-zero,
+/// the report is left to them.
+static CATCHING: AtomicUsize = AtomicUsize::new(0);
+
+#[derive(Debug)]
+struct CrashState {
+    /// The first panic that wasn't reported yet.
+    panic: Option<PanicReport>,
+
+    adapter: Option<String>,
+
+    directory: Option<PathBuf>,
+}
+
+#[derive(Debug)]
+struct PanicReport {
+    time: DateTime<Utc>,
+    message: String,
+    location: Option<String>,
+    thread: String,
+    backtrace: Backtrace,
+}
+
+/// Whether the game was saved before the crash report was written.
+#[derive(Debug)]
+pub enum EmergencySave {
+    Saved,
+    Failed { reason: String },
+    NotAttempted,
+}
+
+/// Installs the panic hook.
+///
+/// This must be called after `color_eyre::install`, since the hook that was
+/// installed before is still called after the panic was recorded.
+pub fn install() {
+    let previous = std::panic::take_hook();
+
+    std::panic::set_hook(Box::new(move |info| {
+        let report = PanicReport::new(info);
+
+        let unreported = if CATCHING.load(Ordering::Acquire) > 0 {
+            // the main loop will report it after saving the game. if something panics
+            // while saving, only the first panic is reported.
+            let mut state = CRASH.lock();
+            if state.panic.is_none() {
+                state.panic = Some(report);
+            }
+            None
+        }
+        else {
+            Some(report)
+        };
+
+        previous(info);
+
+        if let Some(report) = unreported {
+            write_report(report, EmergencySave::NotAttempted);
+        }
+    }));
+}
+
+/// Remembers the GPU adapter, so it's included in crash reports.
+pub fn set_adapter(adapter: &wgpu::AdapterInfo) {
+    CRASH.lock().adapter = Some(format!("{adapter:#?}"));
+}
+
+/// Sets where crash reports are written to.
+pub fn set_directory(directory: impl Into<PathBuf>) {
+    CRASH.lock().directory = Some(directory.into());
+}
+
+/// Runs `f`, and if it panics, runs `save` before writing the crash report
+/// and resuming the panic.
+pub fn guard<T>(state: &mut T, f: impl FnOnce(&mut T), save: impl FnOnce(&mut T)) {
+    CATCHING.fetch_add(1, Ordering::AcqRel);
+    let result = std::panic::catch_unwind(AssertUnwindSafe(|| f(state)));
+
+    let save = result.as_ref().err().map(|_| {
+        match std::panic::catch_unwind(AssertUnwindSafe(|| save(state))) {
+            Ok(()) => EmergencySave::Saved,
+            Err(payload) => {
+                EmergencySave::Failed {
+                    reason: panic_message(&*payload).to_owned(),
+                }
+            }
+        }
+    });
+
+    if CATCHING.fetch_sub(1, Ordering::AcqRel) == 1 {
+        // without a panic here, this is one from another thread that didn't make it
+        // here, e.g. from a background task. it's still worth a report.
+        let report = CRASH.lock().panic.take();
+        if let Some(report) = report {
+            write_report(report, save.unwrap_or(EmergencySave::NotAttempted));
+        }
+    }
+
+    if let Err(payload) = result {
+        std::panic::resume_unwind(payload);
+    }
+}
+
+/// Message of a panic payload.
+pub fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    payload
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| payload.downcast_ref::<String>().map(|s| s.as_str()))
+        .unwrap_or("Box<dyn Any>")
+}
+
+impl PanicReport {
+    fn new(info: &PanicHookInfo) -> Self {
+        Self {
+            time: Utc::now(),
+            message: panic_message(info.payload()).to_owned(),
+            location: info.location().map(|location| location.to_string()),
+            thread: std::thread::current()
+                .name()
+                .unwrap_or("<unnamed>")
+                .to_owned(),
+            backtrace: Backtrace::force_capture(),
+        }
+    }
+}
+
+fn write_report(report: PanicReport, save: EmergencySave) {
+    let directory = CRASH
+        .lock()
+        .directory
+        .clone()
+        .unwrap_or_else(|| PathBuf::from(DEFAULT_DIRECTORY));
+    let path = directory.join(format!("crash-{}.txt", report.time.format("%Y%m%d-%H%M%S")));
+
+    // don't panic in the panic hook
+    match try_write_report(&path, &report, &save) {
+        Ok(()) => eprintln!("Crash report written to {}", path.display()),
+        Err(error) => eprintln!("Could not write crash report: {error}"),
+    }
+}
+
+fn try_write_report(path: &Path, report: &PanicReport, save: &EmergencySave) -> Result<(), Error> {
+    let adapter = CRASH.lock().adapter.clone();
+
+    let mut text = String::new();
+    writeln!(text, "sandvox crashed at {}", report.time.to_rfc3339())?;
+    writeln!(text)?;
+    writeln!(text, "panic: {}", report.message)?;
+    if let Some(location) = &report.location {
+        writeln!(text, "location: {location}")?;
+    }
+    writeln!(text, "thread: {}", report.thread)?;
+    match save {
+        EmergencySave::Saved => writeln!(text, "emergency save: saved")?,
+        EmergencySave::Failed { reason } => writeln!(text, "emergency save: failed: {reason}")?,
+        EmergencySave::NotAttempted => writeln!(text, "emergency save: not attempted")?,
+    }
+    writeln!(text)?;
+    writeln!(text, "build: {BUILD_INFO:#?}")?;
+    writeln!(text)?;
+    writeln!(
+        text,
+        "adapter: {}",
+        adapter.as_deref().unwrap_or("not created yet")
+    )?;
+    writeln!(text)?;
+    writeln!(text, "backtrace:\n{}", report.backtrace)?;
+    writeln!(text, "recent log:")?;
+    for record in recent_logs() {
+        writeln!(text, "{record}")?;
+    }
+
+    if let Some(directory) = path.parent() {
+        std::fs::create_dir_all(directory)?;
+    }
+    std::fs::write(path, text)?;
+
+    Ok(())
+}
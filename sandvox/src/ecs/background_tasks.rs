@@ -11,6 +11,10 @@ use std::{
     fmt::Debug,
     num::NonZero,
     sync::Arc,
+    time::{
+        Duration,
+        Instant,
+    },
 };
 
 use bevy_ecs::{
@@ -98,11 +102,58 @@ fn apply_background_modifications(pool: Res<BackgroundTaskPool>, mut commands: C
     commands.append(&mut state.world_modifications);
 }
 
-#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize)]
+#[derive(Clone, Copy, Debug, PartialEq, Default, Serialize, Deserialize)]
 #[serde(deny_unknown_fields)]
 pub struct BackgroundTaskConfig {
     pub queue_size: Option<NonZero<usize>>,
+
+    /// How many tasks of this queue can run at the same time. Defaults to the
+    /// number of threads in the pool.
     pub num_threads: Option<NonZero<usize>>,
+
+    #[serde(default)]
+    pub priority: TaskPriority,
+}
+
+/// Free threads pick tasks from queues with higher priority first. Queues with
+/// the same priority take turns.
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum TaskPriority {
+    Low,
+    #[default]
+    Normal,
+    High,
+}
+
+impl TaskPriority {
+    /// All priorities, highest first.
+    pub const ALL: [Self; 3] = [Self::High, Self::Normal, Self::Low];
+}
+
+/// Smoothing factor for the exponential moving averages of the task timings.
+const TIMING_SMOOTHING: f32 = 0.05;
+
+#[derive(Clone, Debug)]
+pub struct TaskQueueStatistics {
+    pub name: &'static str,
+    pub priority: TaskPriority,
+    pub queue_size: usize,
+    pub num_queued: usize,
+    pub num_active: usize,
+
+    /// Maximum number of tasks that can run at the same time.
+    pub num_threads: usize,
+
+    pub num_completed: u64,
+
+    /// Smoothed time tasks spent in the queue before they started.
+    pub wait_ms: f32,
+
+    /// Smoothed time tasks took to run.
+    pub run_ms: f32,
 }
 
 pub trait WorldBuilderBackgroundTaskExt {
@@ -151,17 +202,43 @@ impl BackgroundTaskPool {
                 let task_queue = &mut state.task_queues[*occupied_entry.get()];
                 task_queue.num_threads = num_threads;
                 task_queue.queue_size = queue_size;
+                task_queue.priority = config.priority;
             }
             hash_map::Entry::Vacant(vacant_entry) => {
                 let index = state.task_queues.len();
-                state
-                    .task_queues
-                    .push(TaskQueue::new::<T>(queue_size, num_threads));
+                state.task_queues.push(TaskQueue::new::<T>(
+                    queue_size,
+                    num_threads,
+                    config.priority,
+                ));
                 vacant_entry.insert(index);
             }
         }
     }
 
+    /// Queue depths, load and timings of all task queues.
+    pub fn statistics(&self) -> Vec<TaskQueueStatistics> {
+        let state = self.shared.state.lock();
+
+        state
+            .task_queues
+            .iter()
+            .map(|task_queue| {
+                TaskQueueStatistics {
+                    name: task_queue.name,
+                    priority: task_queue.priority,
+                    queue_size: task_queue.queue_size.get(),
+                    num_queued: task_queue.num_queued,
+                    num_active: task_queue.num_active,
+                    num_threads: task_queue.num_threads.get(),
+                    num_completed: task_queue.num_completed,
+                    wait_ms: task_queue.wait_ms,
+                    run_ms: task_queue.run_ms,
+                }
+            })
+            .collect()
+    }
+
     pub fn push_tasks<T>(&self, tasks: impl IntoIterator<Item = T>)
     where
         T: Task,
@@ -178,6 +255,7 @@ impl BackgroundTaskPool {
                     state.task_queues.push(TaskQueue::new::<T>(
                         default_queue_size(state.num_threads),
                         state.num_threads,
+                        TaskPriority::default(),
                     ));
                     index
                 });
@@ -191,11 +269,12 @@ impl BackgroundTaskPool {
                 .downcast_mut::<TaskQueueInner<T>>()
                 .unwrap();
 
+            let now = Instant::now();
             let num_queued = tasks
                 .into_iter()
                 .take(num_free)
                 .map(|task| {
-                    inner.queue.push_back(task);
+                    inner.queue.push_back((now, task));
                 })
                 .count();
 
@@ -216,6 +295,9 @@ impl Drop for BackgroundTaskPool {
 }
 
 pub trait Task: Send + Sync + 'static {
+    /// Name of the task queue in statistics.
+    const NAME: &'static str;
+
     fn run(self, world_modifications: &mut CommandQueue);
 }
 
@@ -236,53 +318,81 @@ struct State {
 
 #[derive(derive_more::Debug)]
 struct TaskQueue {
+    name: &'static str,
     queue_size: NonZero<usize>,
     num_threads: NonZero<usize>,
+    priority: TaskPriority,
     num_queued: usize,
     num_active: usize,
+    num_completed: u64,
+    wait_ms: f32,
+    run_ms: f32,
     #[debug(skip)]
     inner: Box<dyn DynTaskQueueInner>,
 }
 
 impl TaskQueue {
-    fn new<T>(queue_size: NonZero<usize>, num_threads: NonZero<usize>) -> Self
+    fn new<T>(
+        queue_size: NonZero<usize>,
+        num_threads: NonZero<usize>,
+        priority: TaskPriority,
+    ) -> Self
     where
         T: Task,
     {
         Self {
+            name: T::NAME,
             queue_size,
             num_threads,
+            priority,
             num_queued: 0,
             num_active: 0,
+            num_completed: 0,
+            wait_ms: 0.0,
+            run_ms: 0.0,
             inner: Box::new(TaskQueueInner::<T> {
                 queue: VecDeque::with_capacity(queue_size.get()),
             }),
         }
     }
+
+    fn can_start_task(&self) -> bool {
+        self.num_queued > 0 && self.num_active < self.num_threads.get()
+    }
+}
+
+fn smooth(average: &mut f32, sample: Duration) {
+    *average += TIMING_SMOOTHING * (sample.as_secs_f32() * 1000.0 - *average);
 }
 
 const fn default_queue_size(num_threads: NonZero<usize>) -> NonZero<usize> {
     NonZero::new(num_threads.get() * 2).unwrap()
 }
 
+type DynTask = Box<dyn FnOnce(&mut CommandQueue)>;
+
 trait DynTaskQueueInner: Send + Sync + Any + 'static {
-    fn pop(&mut self) -> Box<dyn FnOnce(&mut CommandQueue)>;
+    /// Returns the task and when it was pushed.
+    fn pop(&mut self) -> (Instant, DynTask);
 }
 
 struct TaskQueueInner<T>
 where
     T: Task,
 {
-    queue: VecDeque<T>,
+    queue: VecDeque<(Instant, T)>,
 }
 
 impl<T> DynTaskQueueInner for TaskQueueInner<T>
 where
     T: Task,
 {
-    fn pop(&mut self) -> Box<dyn FnOnce(&mut CommandQueue)> {
-        let task = self.queue.pop_front().unwrap();
-        Box::new(move |world_modifications| task.run(world_modifications))
+    fn pop(&mut self) -> (Instant, DynTask) {
+        let (pushed, task) = self.queue.pop_front().unwrap();
+        (
+            pushed,
+            Box::new(move |world_modifications| task.run(world_modifications)),
+        )
     }
 }
 
@@ -291,7 +401,7 @@ fn worker_thread(thread_id: usize, shared: Arc<Shared>) {
     let _guard = span.enter();
 
     let mut world_modifications = CommandQueue::default();
-    let mut active_task: Option<usize> = None;
+    let mut active_task: Option<(usize, Duration)> = None;
 
     loop {
         let task = 'get_task: {
@@ -304,9 +414,11 @@ fn worker_thread(thread_id: usize, shared: Arc<Shared>) {
             // if we just processed a task, make sure to decrement the active counter.
             // this also returns from which position in the task_queues array we'll scan for
             // the next item
-            let cursor = if let Some(task_id) = active_task.take() {
+            let cursor = if let Some((task_id, run_time)) = active_task.take() {
                 let task_queue = &mut state.task_queues[task_id];
                 task_queue.num_active -= 1;
+                task_queue.num_completed += 1;
+                smooth(&mut task_queue.run_ms, run_time);
 
                 // scan for next item starting from the next queue
                 let num_task_queues = state.task_queues.len();
@@ -326,16 +438,19 @@ fn worker_thread(thread_id: usize, shared: Arc<Shared>) {
                 // which queues have items, but the number of queues is expected to be very low,
                 // so this might be faster.
                 let num_task_queues = state.task_queues.len();
-                for task_id in (cursor..num_task_queues).into_iter().chain(0..cursor) {
-                    let task_queue = &mut state.task_queues[task_id];
-
-                    if task_queue.num_queued > 0
-                        && task_queue.num_active < task_queue.num_threads.get()
-                    {
-                        task_queue.num_queued -= 1;
-                        task_queue.num_active += 1;
-                        active_task = Some(task_id);
-                        break 'get_task task_queue.inner.pop();
+                for priority in TaskPriority::ALL {
+                    for task_id in (cursor..num_task_queues).chain(0..cursor) {
+                        let task_queue = &mut state.task_queues[task_id];
+
+                        if task_queue.priority == priority && task_queue.can_start_task() {
+                            task_queue.num_queued -= 1;
+                            task_queue.num_active += 1;
+
+                            let (pushed, task) = task_queue.inner.pop();
+                            smooth(&mut task_queue.wait_ms, pushed.elapsed());
+
+                            break 'get_task (task_id, task);
+                        }
                     }
                 }
 
@@ -345,6 +460,9 @@ fn worker_thread(thread_id: usize, shared: Arc<Shared>) {
         };
 
         // run task
+        let (task_id, task) = task;
+        let start = Instant::now();
         task(&mut world_modifications);
+        active_task = Some((task_id, start.elapsed()));
     }
 }
@@ -1,5 +1,6 @@
 pub mod background_tasks;
 pub mod plugin;
 pub mod schedule;
+pub mod snapshot;
 pub mod transform;
 pub mod workspace;
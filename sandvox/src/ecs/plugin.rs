@@ -1,22 +1,21 @@
 use std::{
     any::{
-        Any,
         TypeId,
         type_name,
     },
-    collections::{
-        HashMap,
-        HashSet,
-    },
+    collections::HashMap,
     fs::File,
     io::{
         BufWriter,
         Write,
     },
     path::Path,
+    sync::Arc,
 };
 
 use bevy_ecs::{
+    bundle::Bundle,
+    event::Event,
     message::{
         Message,
         MessageRegistry,
@@ -32,13 +31,20 @@ use bevy_ecs::{
         ScheduleLabel,
         Schedules,
     },
-    system::ScheduleSystem,
+    system::{
+        IntoObserverSystem,
+        ScheduleSystem,
+    },
     world::{
         FromWorld,
         World,
     },
 };
-use color_eyre::eyre::Error;
+use color_eyre::eyre::{
+    Error,
+    WrapErr,
+    bail,
+};
 
 use crate::ecs::schedule;
 
@@ -47,19 +53,28 @@ pub trait Plugin: Send + Sync + 'static {
         type_name::<Self>()
     }
 
+    /// Declares the plugins this plugin depends on.
+    ///
+    /// This is called for all plugins before any of them is set up, so that
+    /// they can be set up in dependency order.
+    fn dependencies(&self, _dependencies: &mut Dependencies) -> Result<(), Error> {
+        Ok(())
+    }
+
     fn setup(&self, builder: &mut WorldBuilder) -> Result<(), Error>;
 }
 
 #[derive(Debug)]
 pub struct WorldBuilder {
     pub world: World,
-    registered_plugins: HashSet<TypeId>,
+    plugins: PluginRegistry,
 }
 
 impl Default for WorldBuilder {
     fn default() -> Self {
         let mut schedules = Schedules::new();
 
+        schedules.insert(Schedule::new(schedule::GpuSetup));
         schedules.insert(Schedule::new(schedule::Startup));
         schedules.insert(Schedule::new(schedule::PostStartup));
 
@@ -69,6 +84,8 @@ impl Default for WorldBuilder {
 
         schedules.insert(Schedule::new(schedule::Render));
 
+        schedules.insert(Schedule::new(schedule::Shutdown));
+
         schedules.add_systems(schedule::PreUpdate, message_update_system);
 
         let mut world = World::new();
@@ -76,26 +93,12 @@ impl Default for WorldBuilder {
 
         Self {
             world,
-            registered_plugins: HashSet::new(),
+            plugins: PluginRegistry::default(),
         }
     }
 }
 
 impl WorldBuilder {
-    #[track_caller]
-    pub fn require_plugin<P>(&mut self) -> &mut Self
-    where
-        P: Plugin,
-    {
-        if !self.registered_plugins.contains(&TypeId::of::<P>()) {
-            panic!(
-                "Required plugin `{}` is not yet registered",
-                type_name::<P>()
-            );
-        }
-        self
-    }
-
     pub fn write_schedule_graphs_to_dot(&self, path: impl AsRef<Path>) -> Result<(), Error> {
         tracing::debug!(path = %path.as_ref().display(), "writing schedule graphs to file");
 
@@ -105,20 +108,54 @@ impl WorldBuilder {
         Ok(())
     }
 
-    pub fn build(&mut self) -> World {
+    pub fn build(&mut self) -> Result<World, Error> {
+        self.setup_plugins()?;
+
+        self.world.run_schedule(schedule::GpuSetup);
         self.world.run_schedule(schedule::Startup);
         self.world.run_schedule(schedule::PostStartup);
 
-        std::mem::take(&mut self.world)
+        Ok(std::mem::take(&mut self.world))
     }
 
-    pub fn add_plugin(&mut self, plugin: impl Plugin) -> Result<&mut Self, Error> {
-        if self.registered_plugins.insert(plugin.type_id()) {
-            plugin.setup(self)?;
-        }
+    /// Adds a plugin.
+    ///
+    /// Plugins are set up when the world is built, after the plugins they
+    /// depend on. Adding a plugin twice is an error, since its systems would be
+    /// added twice.
+    pub fn add_plugin<P>(&mut self, plugin: P) -> Result<&mut Self, Error>
+    where
+        P: Plugin,
+    {
+        self.plugins.add(plugin)?;
         Ok(self)
     }
 
+    /// Sets up all plugins that were added so far.
+    ///
+    /// This is done by [`build`](Self::build), but can be called before to
+    /// inspect the schedules. No plugins can be added afterwards.
+    pub fn setup_plugins(&mut self) -> Result<(), Error> {
+        if self.plugins.setup_started {
+            return Ok(());
+        }
+
+        self.plugins.resolve_dependencies()?;
+        let order = self.plugins.setup_order()?;
+        self.plugins.setup_started = true;
+
+        for type_id in order {
+            let plugin = self.plugins.plugins[&type_id].plugin.clone();
+
+            tracing::debug!(plugin = plugin.name(), "setting up plugin");
+            plugin
+                .setup(self)
+                .wrap_err_with(|| format!("Failed to set up plugin `{}`", plugin.name()))?;
+        }
+
+        Ok(())
+    }
+
     pub fn init_resource<R>(&mut self) -> &mut Self
     where
         R: Resource + FromWorld,
@@ -152,6 +189,16 @@ impl WorldBuilder {
         self
     }
 
+    /// Adds a global observer, which runs whenever its event is triggered.
+    pub fn add_observer<E, B, M>(&mut self, observer: impl IntoObserverSystem<E, B, M>) -> &mut Self
+    where
+        E: Event,
+        B: Bundle,
+    {
+        self.world.add_observer(observer);
+        self
+    }
+
     pub fn add_message<M>(&mut self) -> &mut Self
     where
         M: Message,
@@ -163,6 +210,214 @@ impl WorldBuilder {
     }
 }
 
+/// Passed to [`Plugin::dependencies`] to declare the plugins a plugin depends
+/// on.
+#[derive(Debug)]
+pub struct Dependencies<'a> {
+    registry: &'a mut PluginRegistry,
+    dependent: TypeId,
+}
+
+impl Dependencies<'_> {
+    /// Makes sure that plugin `P` is set up before the dependent plugin.
+    ///
+    /// If `P` isn't added by the time all plugins are added, its default is
+    /// used.
+    pub fn require_plugin<P>(&mut self) -> &mut Self
+    where
+        P: Plugin + Default,
+    {
+        let type_id = TypeId::of::<P>();
+
+        self.registry
+            .plugins
+            .get_mut(&self.dependent)
+            .unwrap()
+            .requires
+            .push(type_id);
+        self.registry
+            .defaults
+            .entry(type_id)
+            .or_insert(default_plugin::<P>);
+
+        self
+    }
+
+    /// Adds a plugin that comes with the dependent plugin.
+    ///
+    /// Unlike a required plugin, it's not necessarily set up before the
+    /// dependent plugin.
+    pub fn add_plugin<P>(&mut self, plugin: P) -> Result<&mut Self, Error>
+    where
+        P: Plugin,
+    {
+        self.registry.add(plugin)?;
+        Ok(self)
+    }
+}
+
+#[derive(Debug, Default)]
+struct PluginRegistry {
+    plugins: HashMap<TypeId, RegisteredPlugin>,
+
+    /// Plugins in the order they were added.
+    order: Vec<TypeId>,
+
+    /// Constructors for the defaults of required plugins.
+    defaults: HashMap<TypeId, fn() -> Arc<dyn Plugin>>,
+
+    /// Once set up started, no plugins can be added anymore.
+    setup_started: bool,
+}
+
+impl PluginRegistry {
+    fn add<P>(&mut self, plugin: P) -> Result<(), Error>
+    where
+        P: Plugin,
+    {
+        if self.setup_started {
+            bail!(
+                "Plugin `{}` was added after plugins were set up",
+                plugin.name()
+            );
+        }
+
+        match self.plugins.get(&TypeId::of::<P>()) {
+            Some(registered) if registered.is_default => {
+                bail!(
+                    "Plugin `{}` was added after its default was used for a plugin that requires it",
+                    plugin.name()
+                );
+            }
+            Some(_) => bail!("Plugin `{}` was added twice", plugin.name()),
+            None => {}
+        }
+
+        self.register(TypeId::of::<P>(), Arc::new(plugin), false);
+        Ok(())
+    }
+
+    fn register(&mut self, type_id: TypeId, plugin: Arc<dyn Plugin>, is_default: bool) {
+        self.plugins.insert(
+            type_id,
+            RegisteredPlugin {
+                plugin,
+                requires: vec![],
+                is_default,
+            },
+        );
+        self.order.push(type_id);
+    }
+
+    /// Collects the dependencies of all plugins.
+    ///
+    /// Defaults for required plugins are only added once no plugin adds any
+    /// more plugins, so that a plugin can still be added with its actual
+    /// configuration after another plugin required it.
+    fn resolve_dependencies(&mut self) -> Result<(), Error> {
+        let mut index = 0;
+
+        loop {
+            // plugins can add more plugins while we're iterating
+            while let Some(type_id) = self.order.get(index).copied() {
+                index += 1;
+
+                let plugin = self.plugins[&type_id].plugin.clone();
+
+                plugin
+                    .dependencies(&mut Dependencies {
+                        registry: self,
+                        dependent: type_id,
+                    })
+                    .wrap_err_with(|| {
+                        format!("Failed to add dependencies of plugin `{}`", plugin.name())
+                    })?;
+            }
+
+            let missing = self
+                .order
+                .iter()
+                .flat_map(|type_id| &self.plugins[type_id].requires)
+                .filter(|type_id| !self.plugins.contains_key(*type_id))
+                .copied()
+                .collect::<Vec<_>>();
+
+            if missing.is_empty() {
+                return Ok(());
+            }
+
+            for type_id in missing {
+                if !self.plugins.contains_key(&type_id) {
+                    let plugin = self.defaults[&type_id]();
+                    tracing::debug!(plugin = plugin.name(), "adding required plugin");
+                    self.register(type_id, plugin, true);
+                }
+            }
+        }
+    }
+
+    /// Orders the plugins such that every plugin comes after the plugins it
+    /// requires, and otherwise in the order they were added.
+    fn setup_order(&self) -> Result<Vec<TypeId>, Error> {
+        let mut visited = HashMap::with_capacity(self.plugins.len());
+        let mut order = Vec::with_capacity(self.plugins.len());
+
+        for type_id in &self.order {
+            self.visit(*type_id, &mut visited, &mut order)?;
+        }
+
+        Ok(order)
+    }
+
+    fn visit(
+        &self,
+        type_id: TypeId,
+        visited: &mut HashMap<TypeId, bool>,
+        order: &mut Vec<TypeId>,
+    ) -> Result<(), Error> {
+        let registered = &self.plugins[&type_id];
+
+        match visited.get(&type_id).copied() {
+            Some(true) => return Ok(()),
+            Some(false) => {
+                bail!(
+                    "Plugin `{}` requires itself through its dependencies",
+                    registered.plugin.name()
+                );
+            }
+            None => {}
+        }
+
+        visited.insert(type_id, false);
+        for required in &registered.requires {
+            self.visit(*required, visited, order)
+                .wrap_err_with(|| format!("Required by plugin `{}`", registered.plugin.name()))?;
+        }
+        visited.insert(type_id, true);
+
+        order.push(type_id);
+        Ok(())
+    }
+}
+
+fn default_plugin<P>() -> Arc<dyn Plugin>
+where
+    P: Plugin + Default,
+{
+    Arc::new(P::default())
+}
+
+#[derive(derive_more::Debug)]
+struct RegisteredPlugin {
+    #[debug("{}", plugin.name())]
+    plugin: Arc<dyn Plugin>,
+
+    /// Plugins that must be set up before this one.
+    requires: Vec<TypeId>,
+
+    /// Whether this plugin was added because another plugin required it.
+    is_default: bool,
+}
 fn write_schedule_graphs_to_dot<W>(schedules: &Schedules, mut writer: W) -> Result<(), Error>
 where
     W: Write,
@@ -221,3 +476,121 @@ where
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use bevy_ecs::resource::Resource;
+    use color_eyre::eyre::Error;
+
+    use crate::ecs::plugin::{
+        Dependencies,
+        Plugin,
+        WorldBuilder,
+    };
+
+    #[derive(Debug, Default, Resource)]
+    struct SetupOrder(Vec<String>);
+
+    fn push_setup(builder: &mut WorldBuilder, name: String) {
+        builder
+            .world
+            .get_resource_or_init::<SetupOrder>()
+            .0
+            .push(name);
+    }
+
+    #[derive(Debug, Default)]
+    struct First;
+
+    impl Plugin for First {
+        fn setup(&self, builder: &mut WorldBuilder) -> Result<(), Error> {
+            push_setup(builder, "first".to_owned());
+            Ok(())
+        }
+    }
+
+    #[derive(Debug, Default)]
+    struct Second {
+        value: u32,
+    }
+
+    impl Plugin for Second {
+        fn dependencies(&self, dependencies: &mut Dependencies) -> Result<(), Error> {
+            dependencies.require_plugin::<First>();
+            Ok(())
+        }
+
+        fn setup(&self, builder: &mut WorldBuilder) -> Result<(), Error> {
+            push_setup(builder, format!("second {}", self.value));
+            Ok(())
+        }
+    }
+
+    #[derive(Debug, Default)]
+    struct Third;
+
+    impl Plugin for Third {
+        fn dependencies(&self, dependencies: &mut Dependencies) -> Result<(), Error> {
+            dependencies.require_plugin::<Second>();
+            Ok(())
+        }
+
+        fn setup(&self, builder: &mut WorldBuilder) -> Result<(), Error> {
+            push_setup(builder, "third".to_owned());
+            Ok(())
+        }
+    }
+
+    #[derive(Debug, Default)]
+    struct Cyclic;
+
+    impl Plugin for Cyclic {
+        fn dependencies(&self, dependencies: &mut Dependencies) -> Result<(), Error> {
+            dependencies.require_plugin::<Cyclic>();
+            Ok(())
+        }
+
+        fn setup(&self, _builder: &mut WorldBuilder) -> Result<(), Error> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn it_sets_up_required_plugins_first() {
+        let mut builder = WorldBuilder::default();
+        builder.add_plugin(Third).unwrap();
+        let world = builder.build().unwrap();
+
+        assert_eq!(
+            world.resource::<SetupOrder>().0,
+            ["first", "second 0", "third"]
+        );
+    }
+
+    #[test]
+    fn it_uses_plugins_added_after_they_were_required() {
+        let mut builder = WorldBuilder::default();
+        builder.add_plugin(Third).unwrap();
+        builder.add_plugin(Second { value: 1 }).unwrap();
+        let world = builder.build().unwrap();
+
+        assert_eq!(
+            world.resource::<SetupOrder>().0,
+            ["first", "second 1", "third"]
+        );
+    }
+
+    #[test]
+    fn it_rejects_plugins_added_twice() {
+        let mut builder = WorldBuilder::default();
+        builder.add_plugin(First).unwrap();
+        assert!(builder.add_plugin(First).is_err());
+    }
+
+    #[test]
+    fn it_rejects_cyclic_dependencies() {
+        let mut builder = WorldBuilder::default();
+        builder.add_plugin(Cyclic).unwrap();
+        assert!(builder.build().is_err());
+    }
+}
@@ -1,5 +1,14 @@
 use bevy_ecs::schedule::ScheduleLabel;
 
+/// Creates the GPU context and the resources that live on the GPU.
+///
+/// Runs once before [`Startup`], and again on the existing world after the GPU
+/// device was lost. Systems in here must then replace what they created
+/// before, and remove components that hold resources of the lost device, so
+/// that they're created again.
+#[derive(Clone, Debug, Hash, Eq, PartialEq, ScheduleLabel)]
+pub struct GpuSetup;
+
 #[derive(Clone, Debug, Hash, Eq, PartialEq, ScheduleLabel)]
 pub struct Startup;
 
@@ -17,3 +26,7 @@ pub struct PostUpdate;
 
 #[derive(Clone, Debug, Hash, Eq, PartialEq, ScheduleLabel)]
 pub struct Render;
+
+/// Runs once when the app is exiting.
+#[derive(Clone, Debug, Hash, Eq, PartialEq, ScheduleLabel)]
+pub struct Shutdown;
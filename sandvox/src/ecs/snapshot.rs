@@ -0,0 +1,498 @@
+//! Snapshots of the ECS world for debugging.
+//!
+//! A snapshot contains all components that are registered with
+//! `#[reflect(Component)]`, grouped by entity, and is written as RON. Other
+//! components (GPU resources, task handles, etc.) are not part of the snapshot
+//! and are left alone when it's restored.
+//!
+//! Restoring a snapshot overwrites the components of entities that are still
+//! alive (startup is deterministic, so entity IDs usually survive a restart)
+//! and spawns new entities for the others. Entity references in components are
+//! mapped to the restored entities.
+
+use std::{
+    collections::BTreeMap,
+    fmt,
+    path::Path,
+};
+
+use bevy_ecs::{
+    entity::{
+        Entity,
+        EntityHashMap,
+    },
+    reflect::{
+        AppTypeRegistry,
+        ReflectComponent,
+    },
+    relationship::RelationshipHookMode,
+    world::{
+        EntityRef,
+        World,
+    },
+};
+use bevy_reflect::{
+    PartialReflect,
+    Reflect,
+    ReflectFromReflect,
+    TypeRegistry,
+    serde::{
+        ReflectDeserializer,
+        ReflectSerializer,
+    },
+};
+use color_eyre::eyre::{
+    Error,
+    OptionExt,
+    eyre,
+};
+use serde::{
+    Deserializer,
+    Serialize,
+    Serializer,
+    de::{
+        DeserializeSeed,
+        MapAccess,
+        SeqAccess,
+        Visitor,
+    },
+    ser::{
+        SerializeMap,
+        SerializeSeq,
+    },
+};
+
+use crate::ecs::plugin::{
+    Plugin,
+    WorldBuilder,
+};
+
+/// Inserts the [`AppTypeRegistry`] with all types that derive `Reflect`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TypeRegistryPlugin;
+
+impl Plugin for TypeRegistryPlugin {
+    fn setup(&self, builder: &mut WorldBuilder) -> Result<(), Error> {
+        let type_registry = AppTypeRegistry::default();
+        type_registry.write().register_derived_types();
+        builder.insert_resource(type_registry);
+        Ok(())
+    }
+}
+
+/// Selects the components that go into a snapshot.
+#[derive(Clone, Debug, Default)]
+pub struct SnapshotFilter {
+    /// Only include components whose type path or short type path is in this
+    /// list. If it's empty, all reflectable components are included.
+    pub components: Vec<String>,
+}
+
+impl SnapshotFilter {
+    fn matches(&self, type_path: &str, short_type_path: &str) -> bool {
+        self.components.is_empty()
+            || self
+                .components
+                .iter()
+                .any(|name| name == type_path || name == short_type_path)
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct WorldSnapshot {
+    entities: BTreeMap<Entity, Vec<Box<dyn PartialReflect>>>,
+}
+
+impl WorldSnapshot {
+    pub fn capture(world: &World, filter: &SnapshotFilter) -> Result<Self, Error> {
+        let type_registry = world
+            .get_resource::<AppTypeRegistry>()
+            .ok_or_eyre("No type registry")?
+            .read();
+
+        let component_types = type_registry
+            .iter_with_data::<ReflectComponent>()
+            .filter(|(registration, _)| {
+                let type_path = registration.type_info().type_path_table();
+                filter.matches(type_path.path(), type_path.short_path())
+            })
+            .collect::<Vec<_>>();
+
+        let mut entities = BTreeMap::new();
+
+        let mut entity_query = world
+            .try_query::<EntityRef>()
+            .ok_or_eyre("Can't query entities")?;
+
+        for entity in entity_query.iter(world) {
+            let components = component_types
+                .iter()
+                .filter_map(|(registration, reflect_component)| {
+                    let component = reflect_component.reflect(entity)?;
+
+                    // a dynamic clone would lose fields with `#[reflect(ignore)]`, which are
+                    // still serialized if the component has `#[reflect(Serialize)]`.
+                    component
+                        .reflect_clone()
+                        .inspect_err(|error| {
+                            tracing::warn!(
+                                component = registration.type_info().type_path(),
+                                "can't snapshot component: {error}"
+                            );
+                        })
+                        .ok()
+                })
+                .map(|component| component.into_partial_reflect())
+                .collect::<Vec<_>>();
+
+            if !components.is_empty() {
+                entities.insert(entity.id(), components);
+            }
+        }
+
+        Ok(Self { entities })
+    }
+
+    pub fn num_entities(&self) -> usize {
+        self.entities.len()
+    }
+
+    pub fn to_ron(&self, type_registry: &TypeRegistry) -> Result<String, Error> {
+        Ok(ron::ser::to_string_pretty(
+            &SnapshotSerializer {
+                snapshot: self,
+                type_registry,
+            },
+            Default::default(),
+        )?)
+    }
+
+    pub fn from_ron(ron: &str, type_registry: &TypeRegistry) -> Result<Self, Error> {
+        let mut deserializer = ron::Deserializer::from_str(ron)?;
+        let snapshot = SnapshotDeserializer { type_registry }.deserialize(&mut deserializer)?;
+        deserializer.end()?;
+        Ok(snapshot)
+    }
+
+    /// Restores the components in this snapshot.
+    ///
+    /// All components are checked before anything is changed, so a snapshot
+    /// that doesn't match the code anymore is not restored halfway.
+    ///
+    /// Relationship hooks don't run, so a snapshot filtered to only one side of
+    /// a relationship (e.g. `ChildOf` without `Children`) can leave the
+    /// hierarchy inconsistent.
+    pub fn restore(&self, world: &mut World) -> Result<(), Error> {
+        let type_registry = world
+            .get_resource::<AppTypeRegistry>()
+            .ok_or_eyre("No type registry")?
+            .clone();
+        let type_registry = type_registry.read();
+
+        let entities = self
+            .entities
+            .iter()
+            .map(|(snapshot_entity, components)| {
+                let components = components
+                    .iter()
+                    .map(|component| convert_component(&type_registry, &**component))
+                    .collect::<Result<Vec<_>, Error>>()?;
+                Ok((*snapshot_entity, components))
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+
+        let mut entity_map = EntityHashMap::default();
+        for (snapshot_entity, _) in &entities {
+            let entity = if world.get_entity(*snapshot_entity).is_ok() {
+                *snapshot_entity
+            }
+            else {
+                world.spawn_empty().id()
+            };
+            entity_map.insert(*snapshot_entity, entity);
+        }
+
+        for (snapshot_entity, components) in entities {
+            let mut entity_mut = world.entity_mut(entity_map[&snapshot_entity]);
+
+            for (reflect_component, mut value) in components {
+                if let Some(mut existing) = reflect_component.reflect_mut(&mut entity_mut) {
+                    // applying the component would skip fields with `#[reflect(ignore)]`, so we
+                    // replace the whole value instead.
+                    reflect_component.map_entities(&mut *value, &mut entity_map);
+                    existing
+                        .set(value)
+                        .expect("converted value has the component's type");
+                }
+                else {
+                    reflect_component.apply_or_insert_mapped(
+                        &mut entity_mut,
+                        value.as_partial_reflect(),
+                        &type_registry,
+                        &mut entity_map,
+                        RelationshipHookMode::Skip,
+                    );
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Converts a deserialized component to its actual type. The transforms
+/// implement `FromReflect` for this.
+fn convert_component<'a>(
+    type_registry: &'a TypeRegistry,
+    component: &dyn PartialReflect,
+) -> Result<(&'a ReflectComponent, Box<dyn Reflect>), Error> {
+    let type_info = component
+        .get_represented_type_info()
+        .ok_or_eyre("Snapshot component without type info")?;
+    let registration = type_registry
+        .get(type_info.type_id())
+        .ok_or_else(|| eyre!("Type not registered: {}", type_info.type_path()))?;
+    let reflect_component = registration
+        .data::<ReflectComponent>()
+        .ok_or_else(|| eyre!("Not a component: {}", type_info.type_path()))?;
+    let value = registration
+        .data::<ReflectFromReflect>()
+        .and_then(|from_reflect| from_reflect.from_reflect(component))
+        .ok_or_else(|| eyre!("Can't convert component: {}", type_info.type_path()))?;
+
+    Ok((reflect_component, value))
+}
+
+/// Writes a snapshot of `world` to a RON file.
+pub fn save_snapshot(
+    world: &World,
+    filter: &SnapshotFilter,
+    path: impl AsRef<Path>,
+) -> Result<(), Error> {
+    let path = path.as_ref();
+    let snapshot = WorldSnapshot::capture(world, filter)?;
+
+    let type_registry = world.resource::<AppTypeRegistry>().read();
+    std::fs::write(path, snapshot.to_ron(&type_registry)?)?;
+
+    tracing::info!(
+        path = %path.display(),
+        entities = snapshot.num_entities(),
+        "world snapshot saved"
+    );
+
+    Ok(())
+}
+
+/// Restores a snapshot from a RON file written by [`save_snapshot`].
+pub fn load_snapshot(world: &mut World, path: impl AsRef<Path>) -> Result<(), Error> {
+    let path = path.as_ref();
+    let ron = std::fs::read_to_string(path)?;
+
+    let snapshot = {
+        let type_registry = world
+            .get_resource::<AppTypeRegistry>()
+            .ok_or_eyre("No type registry")?
+            .read();
+        WorldSnapshot::from_ron(&ron, &type_registry)?
+    };
+    snapshot.restore(world)?;
+
+    tracing::info!(
+        path = %path.display(),
+        entities = snapshot.num_entities(),
+        "world snapshot loaded"
+    );
+
+    Ok(())
+}
+
+struct SnapshotSerializer<'a> {
+    snapshot: &'a WorldSnapshot,
+    type_registry: &'a TypeRegistry,
+}
+
+impl Serialize for SnapshotSerializer<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.snapshot.entities.len()))?;
+        for (entity, components) in &self.snapshot.entities {
+            map.serialize_entry(
+                &entity.to_bits(),
+                &ComponentsSerializer {
+                    components,
+                    type_registry: self.type_registry,
+                },
+            )?;
+        }
+        map.end()
+    }
+}
+
+struct ComponentsSerializer<'a> {
+    components: &'a [Box<dyn PartialReflect>],
+    type_registry: &'a TypeRegistry,
+}
+
+impl Serialize for ComponentsSerializer<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut seq = serializer.serialize_seq(Some(self.components.len()))?;
+        for component in self.components {
+            seq.serialize_element(&ReflectSerializer::new(&**component, self.type_registry))?;
+        }
+        seq.end()
+    }
+}
+
+struct SnapshotDeserializer<'a> {
+    type_registry: &'a TypeRegistry,
+}
+
+impl<'de> DeserializeSeed<'de> for SnapshotDeserializer<'_> {
+    type Value = WorldSnapshot;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de> Visitor<'de> for SnapshotDeserializer<'_> {
+    type Value = WorldSnapshot;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a map of entities to their components")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut entities = BTreeMap::new();
+
+        while let Some(entity) = map.next_key::<u64>()? {
+            let entity = Entity::try_from_bits(entity)
+                .ok_or_else(|| serde::de::Error::custom(format!("Invalid entity: {entity}")))?;
+            let components = map.next_value_seed(ComponentsDeserializer {
+                type_registry: self.type_registry,
+            })?;
+            entities.insert(entity, components);
+        }
+
+        Ok(WorldSnapshot { entities })
+    }
+}
+
+struct ComponentsDeserializer<'a> {
+    type_registry: &'a TypeRegistry,
+}
+
+impl<'de> DeserializeSeed<'de> for ComponentsDeserializer<'_> {
+    type Value = Vec<Box<dyn PartialReflect>>;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+impl<'de> Visitor<'de> for ComponentsDeserializer<'_> {
+    type Value = Vec<Box<dyn PartialReflect>>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a list of components")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let mut components = vec![];
+        while let Some(component) =
+            seq.next_element_seed(ReflectDeserializer::new(self.type_registry))?
+        {
+            components.push(component);
+        }
+        Ok(components)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use bevy_ecs::{
+        entity::Entity,
+        reflect::AppTypeRegistry,
+        world::World,
+    };
+    use bevy_reflect::{
+        DynamicStruct,
+        PartialReflect,
+        Typed,
+    };
+    use nalgebra::{
+        Isometry3,
+        Vector3,
+    };
+
+    use crate::ecs::{
+        snapshot::{
+            SnapshotFilter,
+            WorldSnapshot,
+        },
+        transform::LocalTransform,
+    };
+
+    #[test]
+    fn it_round_trips_through_ron() {
+        let mut world = World::new();
+        let type_registry = AppTypeRegistry::default();
+        type_registry.write().register_derived_types();
+        world.insert_resource(type_registry.clone());
+
+        let translation = Vector3::new(1.0, 2.0, 3.0);
+        let entity = world
+            .spawn(LocalTransform::from(Isometry3::from(translation)))
+            .id();
+
+        let snapshot = WorldSnapshot::capture(&world, &SnapshotFilter::default()).unwrap();
+        let ron = snapshot.to_ron(&type_registry.read()).unwrap();
+
+        world.entity_mut(entity).insert(LocalTransform::identity());
+
+        let snapshot = WorldSnapshot::from_ron(&ron, &type_registry.read()).unwrap();
+        snapshot.restore(&mut world).unwrap();
+
+        let transform = world.entity(entity).get::<LocalTransform>().unwrap();
+        assert_eq!(transform.isometry.translation.vector, translation);
+    }
+
+    #[test]
+    fn it_restores_nothing_if_a_component_is_invalid() {
+        let mut world = World::new();
+        let type_registry = AppTypeRegistry::default();
+        type_registry.write().register_derived_types();
+        world.insert_resource(type_registry.clone());
+
+        let entity = world.spawn(LocalTransform::identity()).id();
+        let despawned = world.spawn_empty().id();
+        world.despawn(despawned);
+
+        // a component that can't be converted to a `LocalTransform`
+        let mut invalid = DynamicStruct::default();
+        invalid.set_represented_type(Some(LocalTransform::type_info()));
+
+        let translation = Vector3::new(1.0, 2.0, 3.0);
+        let snapshot = WorldSnapshot {
+            entities: BTreeMap::from([
+                (
+                    entity,
+                    vec![Box::new(LocalTransform::from(Isometry3::from(translation)))
+                        as Box<dyn PartialReflect>],
+                ),
+                (
+                    despawned,
+                    vec![Box::new(invalid) as Box<dyn PartialReflect>],
+                ),
+            ]),
+        };
+
+        assert!(snapshot.restore(&mut world).is_err());
+
+        let transform = world.entity(entity).get::<LocalTransform>().unwrap();
+        assert_eq!(transform.isometry.translation.vector, Vector3::zeros());
+        assert_eq!(world.query::<Entity>().iter(&world).count(), 1);
+    }
+}
@@ -2,23 +2,52 @@ use bevy_ecs::{
     component::Component,
     reflect::ReflectComponent,
 };
-use bevy_reflect::Reflect;
+use bevy_reflect::{
+    FromReflect,
+    PartialReflect,
+    Reflect,
+    ReflectDeserialize,
+    ReflectFromReflect,
+    ReflectSerialize,
+};
 use nalgebra::{
     Isometry3,
+    Matrix4,
     Point3,
+    Vector3,
 };
 use serde::{
     Deserialize,
     Serialize,
 };
 
-use crate::ecs::transform::LocalTransform;
+use crate::ecs::transform::{
+    LocalTransform,
+    local::default_scale,
+};
 
 #[derive(Clone, Copy, Debug, PartialEq, Component, Reflect, Serialize, Deserialize)]
-#[reflect(Component)]
+#[reflect(Component, Clone, FromReflect, Serialize, Deserialize)]
+#[reflect(from_reflect = false)]
 pub struct GlobalTransform {
     #[reflect(ignore)]
     pub isometry: Isometry3<f32>,
+
+    /// Accumulated scale of the entity and its ancestors.
+    ///
+    /// Non-uniform scale of a parent with a rotated child would shear the
+    /// child, which can't be represented here. It's approximated by
+    /// multiplying the scales component-wise.
+    #[reflect(ignore)]
+    #[serde(default = "default_scale")]
+    pub scale: Vector3<f32>,
+}
+
+// the isometry isn't reflected, so we can only convert from the concrete type.
+impl FromReflect for GlobalTransform {
+    fn from_reflect(reflect: &dyn PartialReflect) -> Option<Self> {
+        reflect.try_downcast_ref::<Self>().copied()
+    }
 }
 
 impl GlobalTransform {
@@ -26,13 +55,21 @@ impl GlobalTransform {
     pub fn identity() -> Self {
         Self {
             isometry: Isometry3::identity(),
+            scale: default_scale(),
         }
     }
 
     #[inline]
     pub fn with_local(self, local: &LocalTransform) -> Self {
+        let mut isometry = self.isometry;
+        isometry.translation.vector += isometry
+            .rotation
+            .transform_vector(&self.scale.component_mul(&local.isometry.translation.vector));
+        isometry.rotation *= local.isometry.rotation;
+
         Self {
-            isometry: self.isometry * local.isometry,
+            isometry,
+            scale: self.scale.component_mul(&local.scale),
         }
     }
 
@@ -40,6 +77,24 @@ impl GlobalTransform {
     pub fn position(&self) -> Point3<f32> {
         self.isometry.translation.vector.into()
     }
+
+    /// Returns the matrix that scales and then applies the isometry.
+    #[inline]
+    pub fn to_matrix(&self) -> Matrix4<f32> {
+        self.isometry.to_homogeneous() * Matrix4::new_nonuniform_scaling(&self.scale)
+    }
+
+    #[inline]
+    pub fn transform_point(&self, point: &Point3<f32>) -> Point3<f32> {
+        self.isometry
+            .transform_point(&Point3::from(self.scale.component_mul(&point.coords)))
+    }
+
+    #[inline]
+    pub fn transform_vector(&self, vector: &Vector3<f32>) -> Vector3<f32> {
+        self.isometry
+            .transform_vector(&self.scale.component_mul(vector))
+    }
 }
 
 impl From<LocalTransform> for GlobalTransform {
@@ -47,6 +102,38 @@ impl From<LocalTransform> for GlobalTransform {
     fn from(value: LocalTransform) -> Self {
         Self {
             isometry: value.isometry,
+            scale: value.scale,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::{
+        Point3,
+        UnitQuaternion,
+        Vector3,
+    };
+
+    use crate::ecs::transform::{
+        GlobalTransform,
+        LocalTransform,
+    };
+
+    #[test]
+    fn it_scales_children() {
+        let parent = GlobalTransform::from(
+            LocalTransform::from(UnitQuaternion::from_euler_angles(0.0, 1.0, 0.0))
+                .with_scale(Vector3::new(2.0, 3.0, 4.0)),
+        );
+        let child = LocalTransform::from(Vector3::new(1.0, 1.0, 1.0)).with_uniform_scale(0.5);
+
+        let global = parent.with_local(&child);
+        assert_eq!(global.scale, Vector3::new(1.0, 1.5, 2.0));
+
+        let point = Point3::new(1.0, 2.0, 3.0);
+        let expected = parent.transform_point(&(child.position() + 0.5 * point.coords));
+        assert!((global.transform_point(&point) - expected).norm() < 1e-5);
+        assert!((global.to_matrix().transform_point(&point) - expected).norm() < 1e-5);
+    }
+}
@@ -3,7 +3,11 @@ use bevy_ecs::{
     reflect::ReflectComponent,
 };
 use bevy_reflect::{
+    FromReflect,
+    PartialReflect,
     Reflect,
+    ReflectDeserialize,
+    ReflectFromReflect,
     ReflectSerialize,
 };
 use nalgebra::{
@@ -20,12 +24,30 @@ use serde::{
 };
 
 #[derive(Clone, Copy, Debug, Default, Component, Reflect, Serialize, Deserialize)]
-#[reflect(Component, Serialize)]
+#[reflect(Component, Clone, FromReflect, Serialize, Deserialize)]
+#[reflect(from_reflect = false)]
 pub struct LocalTransform {
     /// Rotation followed by translation that transforms points from the
     /// object's local frame to the global frame.
     #[reflect(ignore)]
     pub isometry: Isometry3<f32>,
+
+    /// Scale along the object's local axes, applied before the
+    /// [`isometry`](Self::isometry).
+    #[reflect(ignore)]
+    #[serde(default = "default_scale")]
+    pub scale: Vector3<f32>,
+}
+
+pub(super) fn default_scale() -> Vector3<f32> {
+    Vector3::repeat(1.0)
+}
+
+// the isometry isn't reflected, so we can only convert from the concrete type.
+impl FromReflect for LocalTransform {
+    fn from_reflect(reflect: &dyn PartialReflect) -> Option<Self> {
+        reflect.try_downcast_ref::<Self>().copied()
+    }
 }
 
 impl LocalTransform {
@@ -33,9 +55,21 @@ impl LocalTransform {
     pub fn identity() -> Self {
         Self {
             isometry: Isometry3::identity(),
+            scale: default_scale(),
         }
     }
 
+    #[inline]
+    pub fn with_scale(mut self, scale: Vector3<f32>) -> Self {
+        self.scale = scale;
+        self
+    }
+
+    #[inline]
+    pub fn with_uniform_scale(self, scale: f32) -> Self {
+        self.with_scale(Vector3::repeat(scale))
+    }
+
     #[inline]
     pub fn translate_local(&mut self, translation: &Translation3<f32>) {
         self.isometry.translation.vector +=
@@ -67,6 +101,7 @@ impl LocalTransform {
     pub fn look_at(eye: &Point3<f32>, target: &Point3<f32>, up: &Vector3<f32>) -> Self {
         Self {
             isometry: Isometry3::face_towards(eye, target, up),
+            scale: default_scale(),
         }
     }
 
@@ -93,7 +128,10 @@ impl LocalTransform {
 impl From<Isometry3<f32>> for LocalTransform {
     #[inline]
     fn from(value: Isometry3<f32>) -> Self {
-        Self { isometry: value }
+        Self {
+            isometry: value,
+            scale: default_scale(),
+        }
     }
 }
 
@@ -0,0 +1,128 @@
+//! Accessibility settings for players who are sensitive to motion or flashing
+//! lights.
+//!
+//! The settings live in one resource, so that effect systems (camera motion,
+//! flashes, the sky) query them instead of each having their own option.
+
+use bevy_ecs::resource::Resource;
+use color_eyre::eyre::Error;
+use palette::LinSrgb;
+use serde::{
+    Deserialize,
+    Serialize,
+};
+
+use crate::ecs::plugin::{
+    Plugin,
+    WorldBuilder,
+};
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct AccessibilityPlugin {
+    pub config: AccessibilityConfig,
+}
+
+impl Plugin for AccessibilityPlugin {
+    fn setup(&self, builder: &mut WorldBuilder) -> Result<(), Error> {
+        builder.insert_resource(self.config);
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Resource, Serialize, Deserialize)]
+pub struct AccessibilityConfig {
+    /// Disable motion that the player doesn't cause themselves, e.g. screen
+    /// shake, view bobbing and bobbing item drops.
+    #[serde(default)]
+    pub reduced_motion: bool,
+
+    /// Maximum change of relative luminance (between 0 and 1) that a flashing
+    /// effect, e.g. lightning or an explosion, may cause. 1 doesn't limit
+    /// flashes.
+    #[serde(default = "default_max_flash_luminance")]
+    pub max_flash_luminance: f32,
+
+    /// Don't rotate the stars with the time of day.
+    #[serde(default)]
+    pub static_skybox: bool,
+}
+
+impl Default for AccessibilityConfig {
+    fn default() -> Self {
+        Self {
+            reduced_motion: false,
+            max_flash_luminance: default_max_flash_luminance(),
+            static_skybox: false,
+        }
+    }
+}
+
+fn default_max_flash_luminance() -> f32 {
+    1.0
+}
+
+impl AccessibilityConfig {
+    /// Factor for the amplitude of motion effects: 0 with reduced motion, 1
+    /// otherwise.
+    pub fn motion_scale(&self) -> f32 {
+        if self.reduced_motion { 0.0 } else { 1.0 }
+    }
+
+    /// Limits a flash from the `base` color to the `flash` color, such that
+    /// the relative luminance changes by at most
+    /// [`max_flash_luminance`][Self::max_flash_luminance].
+    ///
+    /// Returns the color that should be shown at the peak of the flash.
+    pub fn limit_flash(&self, base: LinSrgb<f32>, flash: LinSrgb<f32>) -> LinSrgb<f32> {
+        let delta = (relative_luminance(flash) - relative_luminance(base)).abs();
+        let max_delta = self.max_flash_luminance.max(0.0);
+
+        if delta <= max_delta {
+            flash
+        }
+        else {
+            // the luminance is linear in the color, so we can just interpolate
+            base + (flash - base) * (max_delta / delta)
+        }
+    }
+}
+
+/// Relative luminance as defined in [WCAG 2](https://www.w3.org/TR/WCAG22/#dfn-relative-luminance).
+fn relative_luminance(color: LinSrgb<f32>) -> f32 {
+    0.2126 * color.red + 0.7152 * color.green + 0.0722 * color.blue
+}
+
+#[cfg(test)]
+mod tests {
+    use palette::LinSrgb;
+
+    use crate::game::accessibility::{
+        AccessibilityConfig,
+        relative_luminance,
+    };
+
+    #[test]
+    fn it_limits_flashes_to_the_luminance_delta() {
+        let config = AccessibilityConfig {
+            max_flash_luminance: 0.1,
+            ..Default::default()
+        };
+
+        let base = LinSrgb::new(0.1, 0.1, 0.1);
+        let flash = config.limit_flash(base, LinSrgb::new(1.0, 1.0, 1.0));
+
+        let delta = relative_luminance(flash) - relative_luminance(base);
+        assert!((delta - 0.1).abs() < 1e-5, "delta = {delta}");
+    }
+
+    #[test]
+    fn it_keeps_flashes_below_the_limit() {
+        let config = AccessibilityConfig::default();
+
+        let flash = LinSrgb::new(1.0, 0.5, 0.0);
+        assert_eq!(
+            config.limit_flash(LinSrgb::new(0.0, 0.0, 0.0), flash),
+            flash
+        );
+    }
+}
@@ -0,0 +1,381 @@
+//! Breaking blocks over time in survival mode.
+//!
+//! The player breaks the [targeted block][TargetedBlock] by holding the left
+//! mouse button. How long this takes depends on the block's
+//! [hardness][crate::game::block_type::BlockTypeData::hardness]. Releasing the
+//! button or looking at another block starts over.
+//!
+//! The progress is shown by cracks on the block's faces. Their textures are
+//! generated when the game starts.
+
+use std::f32::consts::TAU;
+
+use bevy_ecs::{
+    change_detection::DetectChangesMut,
+    component::Component,
+    entity::Entity,
+    name::Name,
+    query::With,
+    resource::Resource,
+    schedule::{
+        IntoScheduleConfigs,
+        SystemCondition,
+        common_conditions::{
+            not,
+            resource_changed,
+            resource_exists,
+        },
+    },
+    system::{
+        Commands,
+        Populated,
+        Query,
+        Res,
+        ResMut,
+        Single,
+    },
+};
+use color_eyre::eyre::Error;
+use image::{
+    Rgba,
+    RgbaImage,
+};
+use nalgebra::{
+    Isometry3,
+    Point2,
+    Point3,
+    Vector2,
+    Vector3,
+};
+use rand::{
+    Rng,
+    SeedableRng,
+};
+use rand_xoshiro::Xoroshiro128PlusPlus;
+
+use crate::{
+    app::{
+        GrabCursor,
+        Time,
+    },
+    ecs::{
+        plugin::{
+            Dependencies,
+            Plugin,
+            WorldBuilder,
+        },
+        schedule,
+        transform::{
+            GlobalTransform,
+            LocalTransform,
+        },
+    },
+    game::{
+        Player,
+        block_type::BlockTypes,
+        game_mode::{
+            GameMode,
+            in_game_mode,
+        },
+        interaction::{
+            BlockEditor,
+            InteractionPlugin,
+            TargetedBlock,
+            use_target,
+        },
+    },
+    input::{
+        MouseButton,
+        MouseButtons,
+    },
+    render::{
+        DefaultAtlas,
+        RenderSystems,
+        atlas::AtlasHandle,
+        billboard::{
+            Billboard,
+            BillboardPlugin,
+        },
+        render_target::RenderTarget,
+        staging::Staging,
+    },
+    voxel::BlockFace,
+    wgpu::WgpuContext,
+};
+
+/// Number of crack textures, from a few cracks to a shattered block.
+const NUM_CRACK_STAGES: usize = 8;
+
+/// Width and height of the crack textures (in pixels).
+const CRACK_TEXTURE_SIZE: u32 = 16;
+
+/// Number of cracks that run from the middle of the face to its edges.
+const NUM_CRACKS: usize = 6;
+
+/// The cracks look the same every time.
+const CRACK_SEED: u64 = 0x5eed;
+
+const CRACK_COLOR: Rgba<u8> = Rgba([24, 24, 24, 200]);
+
+/// Distance of the cracks in front of the block's faces, so that they don't
+/// z-fight with them.
+const CRACK_OFFSET: f32 = 0.002;
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct BlockBreakingPlugin;
+
+impl Plugin for BlockBreakingPlugin {
+    fn dependencies(&self, dependencies: &mut Dependencies) -> Result<(), Error> {
+        dependencies
+            .require_plugin::<InteractionPlugin>()
+            .require_plugin::<BillboardPlugin>();
+        Ok(())
+    }
+
+    fn setup(&self, builder: &mut WorldBuilder) -> Result<(), Error> {
+        builder
+            .init_resource::<BreakingBlock>()
+            .add_systems(
+                schedule::Startup,
+                (
+                    create_crack_textures.after(RenderSystems::Setup),
+                    spawn_crack_overlay,
+                ),
+            )
+            .add_systems(
+                schedule::Update,
+                (
+                    update_breaking_block.run_if(
+                        resource_exists::<BlockTypes>.and(in_game_mode(GameMode::Survival)),
+                    ),
+                    stop_breaking_block.run_if(not(in_game_mode(GameMode::Survival))),
+                    update_crack_overlay.run_if(
+                        resource_exists::<CrackTextures>.and(resource_changed::<BreakingBlock>),
+                    ),
+                )
+                    .chain()
+                    .after(use_target),
+            );
+
+        Ok(())
+    }
+}
+
+/// The block that the player is breaking.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Resource)]
+pub struct BreakingBlock(pub Option<BreakProgress>);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BreakProgress {
+    pub position: Point3<i32>,
+
+    /// From 0 to 1, at which point the block breaks.
+    pub progress: f32,
+}
+
+/// The crack textures, with more cracks in later stages.
+#[derive(Debug, Resource)]
+struct CrackTextures(Vec<AtlasHandle>);
+
+/// Shows the cracks on one face of the [`BreakingBlock`].
+#[derive(Clone, Copy, Debug, Component)]
+struct CrackOverlay {
+    face: BlockFace,
+}
+
+fn update_breaking_block(
+    player: Single<(Entity, &RenderTarget), With<Player>>,
+    windows: Query<&MouseButtons, With<GrabCursor>>,
+    time: Res<Time>,
+    targeted_block: Res<TargetedBlock>,
+    mut editor: BlockEditor,
+    mut breaking_block: ResMut<BreakingBlock>,
+) {
+    let (player_entity, render_target) = *player;
+
+    let holding = windows
+        .get(render_target.0)
+        .is_ok_and(|mouse_buttons| mouse_buttons.pressed(MouseButton::Left));
+
+    let (Some(hit), true) = (targeted_block.0, holding)
+    else {
+        breaking_block.set_if_neq(BreakingBlock(None));
+        return;
+    };
+
+    let Some(voxel) = editor.voxels.get(hit.block)
+    else {
+        return;
+    };
+
+    // looking at another block starts over
+    let progress = breaking_block
+        .0
+        .filter(|breaking| breaking.position == hit.block)
+        .map_or(0.0, |breaking| breaking.progress);
+
+    let hardness = editor.block_types[voxel.block_type].hardness;
+    let progress = if hardness > 0.0 {
+        progress + time.delta_seconds() / hardness
+    }
+    else {
+        1.0
+    };
+
+    if progress >= 1.0 {
+        breaking_block.0 = None;
+        editor.break_block(hit.block, player_entity);
+    }
+    else {
+        breaking_block.0 = Some(BreakProgress {
+            position: hit.block,
+            progress,
+        });
+    }
+}
+
+/// Forgets the progress if the player switched to a game mode in which blocks
+/// break instantly.
+fn stop_breaking_block(mut breaking_block: ResMut<BreakingBlock>) {
+    breaking_block.set_if_neq(BreakingBlock(None));
+}
+
+fn create_crack_textures(
+    wgpu: Res<WgpuContext>,
+    mut atlas: ResMut<DefaultAtlas>,
+    mut staging: ResMut<Staging>,
+    mut commands: Commands,
+) {
+    let textures = crack_images(NUM_CRACK_STAGES)
+        .iter()
+        .map(|image| atlas.insert_image(image, None, &wgpu.device, &mut staging))
+        .collect::<Result<Vec<_>, _>>();
+
+    match textures {
+        Ok(textures) => commands.insert_resource(CrackTextures(textures)),
+        Err(error) => tracing::error!(?error, "could not create crack textures"),
+    }
+}
+
+/// Draws the crack textures.
+///
+/// The cracks are random walks from the middle of the face to its edges. Each
+/// stage draws a longer part of every crack, and the last stage draws them
+/// completely.
+fn crack_images(num_stages: usize) -> Vec<RgbaImage> {
+    let mut rng = Xoroshiro128PlusPlus::seed_from_u64(CRACK_SEED);
+    let size = CRACK_TEXTURE_SIZE as f32;
+
+    let cracks = (0..NUM_CRACKS)
+        .map(|_| {
+            let mut position = Point2::new(
+                0.5 * size + rng.random_range(-1.0..1.0),
+                0.5 * size + rng.random_range(-1.0..1.0),
+            );
+            let mut angle = rng.random_range(0.0..TAU);
+            let mut pixels = vec![];
+
+            while (0.0..size).contains(&position.x) && (0.0..size).contains(&position.y) {
+                pixels.push(position.map(|c| c as u32));
+                position += Vector2::new(angle.cos(), angle.sin());
+                angle += rng.random_range(-0.6..0.6);
+            }
+
+            pixels
+        })
+        .collect::<Vec<_>>();
+
+    (1..=num_stages)
+        .map(|stage| {
+            let mut image = RgbaImage::new(CRACK_TEXTURE_SIZE, CRACK_TEXTURE_SIZE);
+
+            for pixels in &cracks {
+                let length = pixels.len() * stage / num_stages;
+                for pixel in &pixels[..length] {
+                    image.put_pixel(pixel.x, pixel.y, CRACK_COLOR);
+                }
+            }
+
+            image
+        })
+        .collect()
+}
+
+fn spawn_crack_overlay(mut commands: Commands) {
+    for face in BlockFace::ALL {
+        commands.spawn((
+            Name::new("crack overlay"),
+            CrackOverlay { face },
+            GlobalTransform::identity(),
+        ));
+    }
+}
+
+fn update_crack_overlay(
+    overlays: Populated<(Entity, &CrackOverlay, &mut GlobalTransform)>,
+    breaking_block: Res<BreakingBlock>,
+    crack_textures: Res<CrackTextures>,
+    mut commands: Commands,
+) {
+    for (entity, overlay, mut transform) in overlays {
+        if let Some(breaking) = breaking_block.0 {
+            let stage = ((breaking.progress * crack_textures.0.len() as f32) as usize)
+                .min(crack_textures.0.len() - 1);
+
+            let normal = overlay.face.neighbor().cast::<f32>();
+            let center = breaking.position.cast::<f32>() + Vector3::repeat(0.5);
+            let position = center + (0.5 + CRACK_OFFSET) * normal;
+
+            // the billboard faces along its z axis
+            let up = if normal.y == 0.0 {
+                Vector3::y()
+            }
+            else {
+                Vector3::z()
+            };
+            *transform = LocalTransform::from(Isometry3::face_towards(
+                &position,
+                &(position + normal),
+                &up,
+            ))
+            .into();
+
+            commands.entity(entity).insert(
+                Billboard::new(crack_textures.0[stage].clone(), Vector2::repeat(1.0))
+                    .surface_aligned(),
+            );
+        }
+        else {
+            commands.entity(entity).remove::<Billboard>();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::game::block_breaking::crack_images;
+
+    #[test]
+    fn it_grows_cracks_with_each_stage() {
+        let images = crack_images(4);
+        assert_eq!(images.len(), 4);
+
+        let num_cracked = images
+            .iter()
+            .map(|image| image.pixels().filter(|pixel| pixel.0[3] > 0).count())
+            .collect::<Vec<_>>();
+
+        assert!(num_cracked[0] > 0);
+        assert!(num_cracked.is_sorted());
+
+        // later stages only add cracks
+        for (earlier, later) in images.iter().zip(&images[1..]) {
+            assert!(
+                earlier
+                    .pixels()
+                    .zip(later.pixels())
+                    .all(|(earlier, later)| earlier.0[3] == 0 || later == earlier)
+            );
+        }
+    }
+}
@@ -0,0 +1,164 @@
+//! Meshes for entities that look like a single block, e.g. item drops or
+//! falling blocks.
+//!
+//! There's one mesh per block type, which is shared by all entities showing
+//! that block.
+
+use std::collections::HashMap;
+
+use bevy_ecs::{
+    component::Component,
+    entity::Entity,
+    query::{
+        With,
+        Without,
+    },
+    resource::Resource,
+    schedule::{
+        IntoScheduleConfigs,
+        common_conditions::{
+            resource_exists,
+            resource_exists_and_changed,
+        },
+    },
+    system::{
+        Commands,
+        Populated,
+        Query,
+        Res,
+        ResMut,
+    },
+};
+use color_eyre::eyre::Error;
+use nalgebra::{
+    Point2,
+    Vector3,
+};
+
+use crate::{
+    ecs::{
+        plugin::{
+            Plugin,
+            WorldBuilder,
+        },
+        schedule,
+    },
+    game::block_type::{
+        BlockType,
+        BlockTypes,
+    },
+    render::mesh::{
+        Mesh,
+        MeshBuilder,
+        MeshPipelineLayout,
+    },
+    voxel::{
+        BlockFace,
+        mesh::UnorientedQuad,
+    },
+    wgpu::WgpuContext,
+};
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct BlockMeshPlugin;
+
+impl Plugin for BlockMeshPlugin {
+    fn setup(&self, builder: &mut WorldBuilder) -> Result<(), Error> {
+        builder
+            .init_resource::<BlockMeshes>()
+            .add_systems(schedule::GpuSetup, clear_block_meshes)
+            .add_systems(
+                schedule::Render,
+                (
+                    clear_block_meshes.run_if(resource_exists_and_changed::<BlockTypes>),
+                    create_block_meshes.run_if(resource_exists::<BlockTypes>),
+                )
+                    .chain(),
+            );
+
+        Ok(())
+    }
+}
+
+/// Renders the entity as a unit cube of this block type, centered around its
+/// transform.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Component)]
+pub struct BlockMesh(pub BlockType);
+
+/// Cached meshes, one per block type.
+#[derive(Debug, Default, Resource)]
+struct BlockMeshes {
+    meshes: HashMap<BlockType, Option<Mesh>>,
+}
+
+/// Drops the cached meshes when the block types were reloaded, so that they're
+/// created again with the new textures, or when the GPU device was lost.
+fn clear_block_meshes(
+    mut block_meshes: ResMut<BlockMeshes>,
+    entities: Query<Entity, (With<BlockMesh>, With<Mesh>)>,
+    mut commands: Commands,
+) {
+    block_meshes.meshes.clear();
+
+    for entity in entities {
+        commands.entity(entity).remove::<Mesh>();
+    }
+}
+
+fn create_block_meshes(
+    wgpu: Res<WgpuContext>,
+    layout: Res<MeshPipelineLayout>,
+    block_types: Res<BlockTypes>,
+    mut block_meshes: ResMut<BlockMeshes>,
+    entities: Populated<(Entity, &BlockMesh), Without<Mesh>>,
+    mut commands: Commands,
+) {
+    for (entity, block_mesh) in entities {
+        let mesh = block_meshes
+            .meshes
+            .entry(block_mesh.0)
+            .or_insert_with(|| create_block_mesh(&wgpu, &layout, &block_types, block_mesh.0));
+
+        if let Some(mesh) = mesh {
+            commands.entity(entity).insert(mesh.clone());
+        }
+    }
+}
+
+fn create_block_mesh(
+    wgpu: &WgpuContext,
+    layout: &MeshPipelineLayout,
+    block_types: &BlockTypes,
+    block_type: BlockType,
+) -> Option<Mesh> {
+    let block_type_data = &block_types[block_type];
+
+    let quad = UnorientedQuad {
+        ij0: Point2::new(0, 0),
+        ij1: Point2::new(1, 1),
+        k: 0,
+    };
+
+    let mut mesh_builder = MeshBuilder::default();
+
+    for face in BlockFace::ALL {
+        if let Some(texture) = block_type_data.face_texture(face) {
+            let mut quad_mesh = quad.mesh(face, texture.id());
+
+            // center the unit cube around the origin. it can be scaled with the entity's
+            // transform.
+            for vertex in &mut quad_mesh.vertices {
+                let position = vertex.position.xyz() - Vector3::repeat(0.5);
+                vertex.position = position.push(1.0);
+            }
+
+            mesh_builder.push(quad_mesh.vertices, quad_mesh.faces);
+        }
+    }
+
+    mesh_builder.finish(
+        wgpu,
+        &format!("block: {}", block_type_data.name),
+        &layout.mesh_bind_group_layout,
+    )
+}
@@ -9,6 +9,7 @@ use std::{
         PathBuf,
     },
     sync::Arc,
+    time::Duration,
 };
 
 use arrayvec::ArrayVec;
@@ -18,11 +19,19 @@ use bevy_ecs::{
 };
 use color_eyre::{
     Section,
-    eyre::Error,
+    eyre::{
+        Error,
+        eyre,
+    },
 };
 use image::RgbaImage;
+use serde::{
+    Deserialize,
+    Serialize,
+};
 
 use crate::{
+    assets::Asset,
     render::atlas::AtlasHandle,
     util::image::ImageLoadExt,
     voxel::BlockFace,
@@ -32,7 +41,7 @@ use crate::{
 pub struct BlockType(u32);
 
 impl BlockType {
-    fn from_usize(i: usize) -> Self {
+    pub(crate) fn from_usize(i: usize) -> Self {
         let id = u32::try_from(i).expect("block type overflow");
         Self(id)
     }
@@ -53,7 +62,7 @@ impl<Tex> BlockTypes<Tex> {
     #[profiling::function]
     pub fn load(
         path: impl AsRef<Path>,
-        mut insert_image: impl FnMut(&RgbaImage) -> Result<Tex, Error>,
+        mut insert_image: impl FnMut(&Path, RgbaImage) -> Result<Tex, Error>,
     ) -> Result<Self, Error>
     where
         Tex: Debug + Clone,
@@ -67,6 +76,11 @@ impl<Tex> BlockTypes<Tex> {
 
         let mut texture_cache: HashMap<PathBuf, Tex> = HashMap::new();
 
+        // these refer to other blocks by name, so they're resolved once all blocks are
+        // known.
+        let mut use_defs = vec![];
+        let mut signal_defs = vec![];
+
         for (i, (name, mut block_def)) in block_defs.block_defs.into_iter().enumerate() {
             if block_def.texture.is_none() && block_def.is_opaque {
                 tracing::warn!("Block without texture defined as opaque: {name}");
@@ -87,7 +101,7 @@ impl<Tex> BlockTypes<Tex> {
                         let image = RgbaImage::from_path(&full_path)
                             .with_note(|| full_path.display().to_string())?;
 
-                        let atlas_handle = insert_image(&image)?;
+                        let atlas_handle = insert_image(&full_path, image)?;
 
                         tracing::debug!(path = ?full_path, ?atlas_handle, "loaded texture");
 
@@ -101,14 +115,85 @@ impl<Tex> BlockTypes<Tex> {
                 textures = Some(faces.into_inner().unwrap());
             }
 
+            if let Some(use_def) = block_def.on_use {
+                use_defs.push((i, use_def));
+            }
+            if let Some(signal_def) = block_def.signal {
+                signal_defs.push((i, signal_def));
+            }
+
             by_name.insert(name.clone(), BlockType::from_usize(i));
             blocks.push(BlockTypeData {
                 name,
                 textures,
                 is_opaque: block_def.is_opaque,
+                medium: block_def.medium,
+                falls: block_def.falls,
+                resistance: block_def.resistance,
+                hardness: block_def.hardness,
+                prompt: block_def.prompt,
+                on_use: None,
+                signal: None,
             });
         }
 
+        let lookup_for = |i: usize, name: &str| {
+            by_name.get(name).copied().ok_or_else(|| {
+                eyre!(
+                    "Block `{}` refers to unknown block `{name}`",
+                    blocks[i].name
+                )
+            })
+        };
+
+        let mut on_uses = Vec::with_capacity(use_defs.len());
+        for (i, use_def) in use_defs {
+            let lookup = |name: &str| lookup_for(i, name);
+
+            let on_use = match use_def {
+                config::UseDef::Container => BlockUse::Container,
+                config::UseDef::Replace(with) => BlockUse::Replace(lookup(&with)?),
+                config::UseDef::Press { with, duration } => {
+                    BlockUse::Press {
+                        with: lookup(&with)?,
+                        duration: Duration::from_secs_f32(duration),
+                    }
+                }
+            };
+
+            on_uses.push((i, on_use));
+        }
+
+        let mut signals = Vec::with_capacity(signal_defs.len());
+        for (i, signal_def) in signal_defs {
+            let lookup = |name: &str| lookup_for(i, name);
+
+            let signal = match signal_def {
+                config::SignalDef::Source => BlockSignal::Source,
+                config::SignalDef::Wire { off, on } => {
+                    BlockSignal::Wire {
+                        off: lookup(&off)?,
+                        on: lookup(&on)?,
+                    }
+                }
+                config::SignalDef::Lamp { off, on } => {
+                    BlockSignal::Lamp {
+                        off: lookup(&off)?,
+                        on: lookup(&on)?,
+                    }
+                }
+            };
+
+            signals.push((i, signal));
+        }
+
+        for (i, on_use) in on_uses {
+            blocks[i].on_use = Some(on_use);
+        }
+        for (i, signal) in signals {
+            blocks[i].signal = Some(signal);
+        }
+
         for (i, data) in blocks.iter().enumerate() {
             tracing::debug!("block_type: {i} => {}", data.name);
         }
@@ -122,6 +207,83 @@ impl<Tex> BlockTypes<Tex> {
     pub fn lookup(&self, name: &str) -> Option<BlockType> {
         self.inner.by_name.get(name).copied()
     }
+
+    /// Returns whether both define the same block types in the same order.
+    ///
+    /// Block types are referred to by their index, so only block types that
+    /// match this way can replace each other while the game is running.
+    pub fn has_same_blocks<U>(&self, other: &BlockTypes<U>) -> bool {
+        self.inner.blocks.len() == other.inner.blocks.len()
+            && self
+                .inner
+                .blocks
+                .iter()
+                .zip(&other.inner.blocks)
+                .all(|(a, b)| a.name == b.name)
+    }
+
+    /// Returns the same block types with their textures replaced by `f`.
+    pub fn map_textures<U>(&self, mut f: impl FnMut(&Tex) -> U) -> BlockTypes<U> {
+        let blocks = self
+            .inner
+            .blocks
+            .iter()
+            .map(|data| {
+                BlockTypeData {
+                    name: data.name.clone(),
+                    textures: data
+                        .textures
+                        .as_ref()
+                        .map(|textures| textures.each_ref().map(&mut f)),
+                    is_opaque: data.is_opaque,
+                    medium: data.medium,
+                    falls: data.falls,
+                    resistance: data.resistance,
+                    hardness: data.hardness,
+                    prompt: data.prompt.clone(),
+                    on_use: data.on_use,
+                    signal: data.signal,
+                }
+            })
+            .collect();
+
+        BlockTypes {
+            inner: Arc::new(Inner {
+                blocks,
+                by_name: self.inner.by_name.clone(),
+            }),
+        }
+    }
+}
+
+/// Block types with their textures decoded, but not yet inserted into the
+/// atlas. The textures are indices into [`images`][Self::images].
+#[derive(Debug)]
+pub struct BlockTypeImages {
+    pub block_types: BlockTypes<usize>,
+    pub images: Vec<RgbaImage>,
+    image_paths: Vec<PathBuf>,
+}
+
+impl Asset for BlockTypeImages {
+    fn load(path: &Path) -> Result<Self, Error> {
+        let mut images = vec![];
+        let mut image_paths = vec![];
+        let block_types = BlockTypes::load(path, |path, image| {
+            images.push(image);
+            image_paths.push(path.to_owned());
+            Ok(images.len() - 1)
+        })?;
+        Ok(Self {
+            block_types,
+            images,
+            image_paths,
+        })
+    }
+
+    fn dependencies(&self) -> Vec<PathBuf> {
+        self.image_paths.clone()
+    }
 }
 
 impl<Tex> Index<BlockType> for BlockTypes<Tex> {
@@ -145,6 +307,26 @@ pub struct BlockTypeData<Tex> {
     pub name: String,
     pub textures: Option<[Tex; 6]>,
     pub is_opaque: bool,
+    pub medium: Medium,
+
+    /// Whether the block falls down when there's nothing below it, e.g. sand.
+    pub falls: bool,
+
+    /// How much of an explosion's strength the block can withstand.
+    pub resistance: f32,
+
+    /// How long it takes to break the block in survival mode (in seconds).
+    pub hardness: f32,
+
+    /// What using the block does, e.g. "open". This is shown when the player
+    /// looks at the block.
+    pub prompt: Option<String>,
+
+    /// What happens when the player uses the block.
+    pub on_use: Option<BlockUse>,
+
+    /// How the block takes part in signal networks.
+    pub signal: Option<BlockSignal>,
 }
 
 impl<Tex> BlockTypeData<Tex> {
@@ -154,6 +336,52 @@ impl<Tex> BlockTypeData<Tex> {
             .as_ref()
             .map(|faces| &faces[usize::from(face as u8)])
     }
+
+    /// Whether entities collide with the block. Blocks without textures (e.g.
+    /// air) and liquids can be walked through.
+    pub fn is_solid(&self) -> bool {
+        self.textures.is_some() && self.medium == Medium::Air
+    }
+}
+
+/// What a block is filled with, i.e. what the camera is in when it's inside
+/// the block.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Medium {
+    #[default]
+    Air,
+    Water,
+    Lava,
+}
+
+/// What happens when a block is used.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BlockUse {
+    /// Opens the block's inventory, e.g. for a chest.
+    Container,
+
+    /// Replaces the block with another one, e.g. to open a door.
+    Replace(BlockType),
+
+    /// Replaces the block for a while, e.g. to press a button.
+    Press { with: BlockType, duration: Duration },
+}
+
+/// How a block takes part in signal networks. See [`crate::game::signal`].
+///
+/// Whether a wire or lamp is powered is part of its block type, so every one
+/// of them comes in an `off` and an `on` variant.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BlockSignal {
+    /// Always powered, e.g. a pressed button.
+    Source,
+
+    /// Carries the signal to its neighbors.
+    Wire { off: BlockType, on: BlockType },
+
+    /// Is powered by its neighbors, but doesn't carry the signal any further.
+    Lamp { off: BlockType, on: BlockType },
 }
 
 mod config {
@@ -168,7 +396,10 @@ mod config {
         Serialize,
     };
 
-    use crate::util::serde::default_true;
+    use crate::{
+        game::block_type::Medium,
+        util::serde::default_true,
+    };
 
     #[derive(Clone, Debug, Serialize, Deserialize)]
     #[serde(transparent)]
@@ -183,6 +414,53 @@ mod config {
 
         #[serde(default = "default_true")]
         pub is_opaque: bool,
+
+        #[serde(default)]
+        pub medium: Medium,
+
+        #[serde(default)]
+        pub falls: bool,
+
+        #[serde(default = "default_resistance")]
+        pub resistance: f32,
+
+        #[serde(default = "default_hardness")]
+        pub hardness: f32,
+
+        pub prompt: Option<String>,
+
+        #[serde(rename = "use")]
+        pub on_use: Option<UseDef>,
+
+        pub signal: Option<SignalDef>,
+    }
+
+    #[derive(Clone, Debug, Serialize, Deserialize)]
+    #[serde(rename_all = "snake_case")]
+    pub enum SignalDef {
+        Source,
+        Wire { off: String, on: String },
+        Lamp { off: String, on: String },
+    }
+
+    fn default_resistance() -> f32 {
+        1.0
+    }
+
+    fn default_hardness() -> f32 {
+        1.0
+    }
+
+    #[derive(Clone, Debug, Serialize, Deserialize)]
+    #[serde(rename_all = "snake_case")]
+    pub enum UseDef {
+        Container,
+        Replace(String),
+        Press {
+            with: String,
+            /// In seconds.
+            duration: f32,
+        },
     }
 
     #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -233,3 +511,31 @@ mod config {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::game::block_type::config::{
+        BlockDefs,
+        UseDef,
+    };
+
+    #[test]
+    fn it_parses_block_uses() {
+        let block_defs: BlockDefs =
+            toml::from_str(include_str!("../../../assets/blocks.toml")).unwrap();
+        let block_defs = &block_defs.block_defs;
+
+        assert!(matches!(
+            block_defs["chest"].on_use,
+            Some(UseDef::Container)
+        ));
+        assert!(matches!(
+            &block_defs["door"].on_use,
+            Some(UseDef::Replace(with)) if with == "door_open"
+        ));
+        assert!(matches!(
+            &block_defs["button"].on_use,
+            Some(UseDef::Press { with, duration }) if with == "button_pressed" && *duration == 1.0
+        ));
+    }
+}
@@ -0,0 +1,422 @@
+//! Blocks that do something when the player uses them, as configured by their
+//! [`BlockUse`].
+//!
+//! Doors are replaced by their open (or closed) variant, buttons are pressed
+//! for a while and containers open a panel showing their contents. Every use
+//! sends a [`BlockUsed`] message, e.g. to play a sound.
+
+use std::{
+    collections::HashMap,
+    time::Instant,
+};
+
+use bevy_ecs::{
+    change_detection::DetectChangesMut,
+    component::Component,
+    entity::Entity,
+    hierarchy::ChildOf,
+    message::{
+        Message,
+        MessageReader,
+        MessageWriter,
+    },
+    name::Name,
+    query::With,
+    resource::Resource,
+    schedule::{
+        IntoScheduleConfigs,
+        common_conditions::{
+            resource_changed,
+            resource_exists,
+        },
+    },
+    system::{
+        Commands,
+        Query,
+        Res,
+        ResMut,
+        Single,
+        SystemParam,
+    },
+};
+use color_eyre::eyre::Error;
+use nalgebra::Point3;
+use taffy::prelude::TaffyAuto;
+
+use crate::{
+    app::Time,
+    ecs::{
+        plugin::{
+            Dependencies,
+            Plugin,
+            WorldBuilder,
+        },
+        schedule,
+    },
+    game::{
+        ChunkShape,
+        block_type::{
+            BlockType,
+            BlockTypes,
+            BlockUse,
+        },
+        interaction::{
+            BlockBroken,
+            BlockEditor,
+            Interacted,
+            InteractionPlugin,
+            InteractionTarget,
+            TargetedInteraction,
+            use_target,
+        },
+        inventory::Inventory,
+        item_drop::ItemDrop,
+        terrain::TerrainVoxel,
+    },
+    render::text::{
+        Text,
+        TextSize,
+    },
+    ui::{
+        Background,
+        PaletteRole,
+        Sprites,
+        Style,
+        ThemeColor,
+        View,
+    },
+    voxel::access::{
+        Voxels,
+        block_center,
+    },
+};
+
+const PIXEL_SIZE: f32 = 2.0;
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct BlockUsePlugin;
+
+impl Plugin for BlockUsePlugin {
+    fn dependencies(&self, dependencies: &mut Dependencies) -> Result<(), Error> {
+        dependencies.require_plugin::<InteractionPlugin>();
+        Ok(())
+    }
+
+    fn setup(&self, builder: &mut WorldBuilder) -> Result<(), Error> {
+        builder
+            .init_resource::<PressedBlocks>()
+            .init_resource::<Containers>()
+            .init_resource::<OpenContainer>()
+            .add_message::<BlockUsed>()
+            .add_systems(
+                schedule::Update,
+                (
+                    use_blocks,
+                    release_pressed_blocks,
+                    drop_container_contents,
+                    close_container,
+                    update_container_panel.run_if(resource_changed::<OpenContainer>),
+                )
+                    .chain()
+                    .after(use_target)
+                    .run_if(resource_exists::<BlockTypes>),
+            );
+
+        Ok(())
+    }
+}
+
+/// Sent when a block was used by an entity.
+#[derive(Clone, Copy, Debug, Message)]
+pub struct BlockUsed {
+    pub position: Point3<i32>,
+
+    /// The block before it was used.
+    pub block_type: BlockType,
+
+    pub used_by: Entity,
+}
+
+/// The [`Inventory`] of a container block is stored in an entity with this
+/// component.
+#[derive(Clone, Copy, Debug, Component)]
+pub struct Container {
+    pub position: Point3<i32>,
+}
+
+/// Container entities by the position of their block.
+///
+/// They're created when a container is used for the first time.
+#[derive(Debug, Default, Resource)]
+pub struct Containers {
+    by_position: HashMap<Point3<i32>, Entity>,
+}
+
+impl Containers {
+    pub fn get(&self, position: Point3<i32>) -> Option<Entity> {
+        self.by_position.get(&position).copied()
+    }
+}
+
+/// The container whose panel is shown.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Resource)]
+struct OpenContainer(Option<Entity>);
+
+#[derive(Clone, Copy, Debug, Component)]
+struct ContainerPanel;
+
+/// Buttons that are pressed and will be released.
+#[derive(Debug, Default, Resource)]
+struct PressedBlocks {
+    pressed: Vec<PressedBlock>,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct PressedBlock {
+    position: Point3<i32>,
+    pressed: BlockType,
+    released: BlockType,
+    release_at: Instant,
+}
+
+/// The state of the blocks that were used.
+#[derive(SystemParam)]
+struct UsedBlocks<'w> {
+    time: Res<'w, Time>,
+    pressed_blocks: ResMut<'w, PressedBlocks>,
+    containers: ResMut<'w, Containers>,
+    open_container: ResMut<'w, OpenContainer>,
+}
+
+fn use_blocks(
+    mut interacted: MessageReader<Interacted>,
+    mut editor: BlockEditor,
+    mut used_blocks: UsedBlocks,
+    mut block_used: MessageWriter<BlockUsed>,
+    mut commands: Commands,
+) {
+    for message in interacted.read() {
+        let InteractionTarget::Block(position) = message.target
+        else {
+            continue;
+        };
+
+        if editor
+            .validator
+            .check_edit(message.used_by, position)
+            .is_err()
+        {
+            continue;
+        }
+
+        let Some(voxel) = editor.voxels.get(position).copied()
+        else {
+            continue;
+        };
+        let Some(on_use) = editor.block_types[voxel.block_type].on_use
+        else {
+            continue;
+        };
+
+        tracing::debug!(?position, block_type = ?voxel.block_type, ?on_use, "block used");
+
+        match on_use {
+            BlockUse::Container => {
+                let container = *used_blocks
+                    .containers
+                    .by_position
+                    .entry(position)
+                    .or_insert_with(|| {
+                        commands
+                            .spawn((
+                                Name::new("container"),
+                                Container { position },
+                                Inventory::default(),
+                            ))
+                            .id()
+                    });
+
+                // using the container again closes it
+                let open = (used_blocks.open_container.0 != Some(container)).then_some(container);
+                used_blocks.open_container.set_if_neq(OpenContainer(open));
+            }
+            BlockUse::Replace(with) => {
+                editor
+                    .voxels
+                    .set(position, TerrainVoxel { block_type: with });
+            }
+            BlockUse::Press { with, duration } => {
+                editor
+                    .voxels
+                    .set(position, TerrainVoxel { block_type: with });
+
+                used_blocks.pressed_blocks.pressed.push(PressedBlock {
+                    position,
+                    pressed: with,
+                    released: voxel.block_type,
+                    release_at: used_blocks.time.tick_start + duration,
+                });
+            }
+        }
+
+        block_used.write(BlockUsed {
+            position,
+            block_type: voxel.block_type,
+            used_by: message.used_by,
+        });
+    }
+}
+
+fn release_pressed_blocks(
+    time: Res<Time>,
+    mut voxels: Voxels<TerrainVoxel, ChunkShape>,
+    mut pressed_blocks: ResMut<PressedBlocks>,
+) {
+    pressed_blocks.pressed.retain(|pressed_block| {
+        if time.tick_start < pressed_block.release_at {
+            return true;
+        }
+
+        // don't restore the button if it was replaced in the meantime
+        if voxels
+            .get(pressed_block.position)
+            .is_some_and(|voxel| voxel.block_type == pressed_block.pressed)
+        {
+            voxels.set(
+                pressed_block.position,
+                TerrainVoxel {
+                    block_type: pressed_block.released,
+                },
+            );
+        }
+
+        false
+    });
+}
+
+/// Drops the contents of containers that were broken.
+fn drop_container_contents(
+    mut block_broken: MessageReader<BlockBroken>,
+    mut containers: ResMut<Containers>,
+    inventories: Query<&Inventory, With<Container>>,
+    mut commands: Commands,
+) {
+    for message in block_broken.read() {
+        let Some(container) = containers.by_position.remove(&message.position)
+        else {
+            continue;
+        };
+
+        if let Ok(inventory) = inventories.get(container) {
+            for (block_type, count) in inventory.iter() {
+                ItemDrop {
+                    block_type,
+                    count,
+                    position: block_center(message.position),
+                    age: 0.0,
+                }
+                .spawn(&mut commands);
+            }
+        }
+
+        commands.entity(container).despawn();
+    }
+}
+
+/// Closes the container panel when the player looks away from the container,
+/// or it was broken.
+fn close_container(
+    targeted_interaction: Res<TargetedInteraction>,
+    containers: Query<&Container>,
+    mut open_container: ResMut<OpenContainer>,
+) {
+    let Some(container) = open_container.0
+    else {
+        return;
+    };
+
+    let targeted = containers.get(container).is_ok_and(|container| {
+        targeted_interaction.0.as_ref().is_some_and(|interaction| {
+            interaction.target == InteractionTarget::Block(container.position)
+        })
+    });
+
+    if !targeted {
+        open_container.0 = None;
+    }
+}
+
+fn update_container_panel(
+    open_container: Res<OpenContainer>,
+    panel: Option<Single<Entity, With<ContainerPanel>>>,
+    view: Single<Entity, With<View>>,
+    containers: Query<&Inventory, With<Container>>,
+    block_types: Res<BlockTypes>,
+    sprites: Res<Sprites>,
+    mut commands: Commands,
+) {
+    if let Some(panel) = panel {
+        commands.entity(*panel).despawn();
+    }
+
+    let Some(inventory) = open_container
+        .0
+        .and_then(|container| containers.get(container).ok())
+    else {
+        return;
+    };
+
+    let sprite = &sprites["panel"];
+    let mut style = Style::default();
+    style.display = taffy::style::Display::Flex;
+    style.flex_direction = taffy::style::FlexDirection::Column;
+    // centered on the screen
+    style.position = taffy::Position::Absolute;
+    style.inset = taffy::Rect::zero();
+    style.margin = taffy::Rect {
+        left: taffy::LengthPercentageAuto::AUTO,
+        right: taffy::LengthPercentageAuto::AUTO,
+        top: taffy::LengthPercentageAuto::AUTO,
+        bottom: taffy::LengthPercentageAuto::AUTO,
+    };
+    if let Some(padding) = sprite.padding(PIXEL_SIZE) {
+        style.padding = padding;
+    }
+
+    let text_size = TextSize {
+        scaling: PIXEL_SIZE,
+    };
+
+    let lines = if inventory.is_empty() {
+        vec!["EMPTY".to_owned()]
+    }
+    else {
+        inventory
+            .iter()
+            .map(|(block_type, count)| format!("{} x{count}", block_types[block_type].name))
+            .collect()
+    };
+
+    commands
+        .spawn((
+            Name::new("container_panel"),
+            style,
+            Background {
+                sprite: sprite.clone(),
+                pixel_size: PIXEL_SIZE,
+            },
+            ContainerPanel,
+            ChildOf(*view),
+        ))
+        .with_children(|panel| {
+            for line in lines {
+                panel.spawn((
+                    Name::new("container_item"),
+                    Text::from(line),
+                    text_size,
+                    ThemeColor::from(PaletteRole::Text),
+                    Style::default(),
+                ));
+            }
+        });
+}
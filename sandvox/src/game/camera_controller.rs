@@ -15,10 +15,12 @@ use bevy_ecs::{
     },
     query::{
         Has,
+        QueryData,
         With,
     },
     schedule::{
         IntoScheduleConfigs,
+        SystemSet,
         common_conditions::on_message,
     },
     system::{
@@ -74,7 +76,7 @@ impl Plugin for CameraControllerPlugin {
             schedule::Update,
             (
                 grab_cursor.run_if(on_message::<ControllerMessage>),
-                update_camera,
+                update_camera.in_set(CameraControllerSystems::Update),
             )
                 .after(InputSystems::Update),
         );
@@ -83,6 +85,11 @@ impl Plugin for CameraControllerPlugin {
     }
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, SystemSet)]
+pub enum CameraControllerSystems {
+    Update,
+}
+
 #[derive(Clone, Copy, Debug, Default, Component)]
 #[component(on_add = controller_added, on_remove = controller_removed)]
 pub struct CameraControllerState {
@@ -99,7 +106,7 @@ impl CameraControllerState {
     }
 }
 
-#[derive(Clone, Debug, Component, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Component, Serialize, Deserialize)]
 #[serde(deny_unknown_fields)]
 pub struct CameraControllerConfig {
     // rad / pixel
@@ -148,6 +155,40 @@ impl Default for CameraControllerConfig {
     }
 }
 
+/// Makes a camera controller walk instead of fly.
+///
+/// Movement is restricted to the horizontal plane and isn't applied to the
+/// transform, but is left to physics (e.g. to collide with blocks). Moving up
+/// jumps and moving down is ignored.
+#[derive(Clone, Copy, Debug, Default, Component)]
+pub struct Walking {
+    /// Horizontal movement requested this frame (in blocks).
+    pub movement: Vector3<f32>,
+
+    /// Whether a jump was requested this frame.
+    pub jump: bool,
+}
+
+impl Walking {
+    fn walk(&mut self, movement: &Movement, transform: &LocalTransform, distance: f32) {
+        let direction = match movement {
+            Movement::Local(direction) => transform.isometry.rotation * direction,
+            Movement::Global(direction) => {
+                if direction.y > 0.0 {
+                    self.jump = true;
+                }
+                *direction
+            }
+        };
+
+        // looking down doesn't make the player walk slower
+        let horizontal = Vector3::new(direction.x, 0.0, direction.z);
+        if let Some(horizontal) = horizontal.try_normalize(1e-6) {
+            self.movement += distance * direction.norm() * horizontal;
+        }
+    }
+}
+
 #[derive(Clone, Debug, Default, Bundle)]
 pub struct CameraController {
     pub state: CameraControllerState,
@@ -202,19 +243,25 @@ fn update_camera(
         &Keys,
         Has<GrabCursor>,
     )>,
-    cameras: Populated<(
-        &mut LocalTransform,
-        &mut CameraControllerState,
-        &CameraControllerConfig,
-        &RenderTarget,
-    )>,
+    cameras: Populated<CameraQuery>,
     mut commands: Commands,
 ) {
-    for (mut transform, mut state, config, render_target) in cameras {
+    for CameraQueryItem {
+        mut transform,
+        mut state,
+        config,
+        render_target,
+        mut walking,
+    } in cameras
+    {
         if state.is_added() {
             state.apply(&mut transform);
         }
 
+        if let Some(walking) = &mut walking {
+            **walking = Walking::default();
+        }
+
         if let Ok((window_entity, mouse_position, mouse_buttons, keys, cursor_grabbed)) =
             windows.get(render_target.0)
         {
@@ -248,7 +295,12 @@ fn update_camera(
                                     commands.entity(window_entity).try_remove::<GrabCursor>();
                                 }
                                 Action::Movement(movement) => {
-                                    movement.apply(&mut transform, speed);
+                                    if let Some(walking) = &mut walking {
+                                        walking.walk(movement, &transform, speed);
+                                    }
+                                    else {
+                                        movement.apply(&mut transform, speed);
+                                    }
                                 }
                             }
                         }
@@ -264,14 +316,24 @@ fn update_camera(
     }
 }
 
-#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[derive(QueryData)]
+#[query_data(mutable)]
+struct CameraQuery {
+    transform: &'static mut LocalTransform,
+    state: &'static mut CameraControllerState,
+    config: &'static CameraControllerConfig,
+    render_target: &'static RenderTarget,
+    walking: Option<&'static mut Walking>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 #[serde(deny_unknown_fields, rename_all = "kebab-case")]
 pub enum Action {
     ReleaseCursor,
     Movement(Movement),
 }
 
-#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 #[serde(deny_unknown_fields, rename_all = "lowercase")]
 pub enum Movement {
     Local(Vector3<f32>),
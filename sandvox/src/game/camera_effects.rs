@@ -0,0 +1,277 @@
+//! View bobbing while the player walks, and camera shake that gameplay can
+//! trigger, e.g. for explosions or damage.
+//!
+//! Both move the view with a [`CameraOffset`], so they don't affect the
+//! player's actual position. They're scaled by the
+//! [motion scale][AccessibilityConfig::motion_scale], so with reduced motion
+//! the camera doesn't move at all.
+
+use std::f32::consts::TAU;
+
+use bevy_ecs::{
+    component::Component,
+    query::With,
+    schedule::IntoScheduleConfigs,
+    system::{
+        Res,
+        Single,
+    },
+};
+use color_eyre::eyre::Error;
+use nalgebra::{
+    Isometry3,
+    Point2,
+    Translation3,
+    UnitQuaternion,
+    Vector2,
+    Vector3,
+};
+
+use crate::{
+    app::Time,
+    ecs::{
+        plugin::{
+            Plugin,
+            WorldBuilder,
+        },
+        schedule,
+        transform::GlobalTransform,
+    },
+    game::{
+        GameConfig,
+        Player,
+        accessibility::AccessibilityConfig,
+    },
+    render::camera::CameraOffset,
+    util::noise::{
+        NoiseFn,
+        PerlinNoise,
+    },
+};
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct CameraEffectsPlugin;
+
+impl Plugin for CameraEffectsPlugin {
+    fn setup(&self, builder: &mut WorldBuilder) -> Result<(), Error> {
+        builder.add_systems(
+            schedule::Update,
+            (
+                update_view_bobbing,
+                update_camera_shake,
+                update_camera_offset,
+            )
+                .chain(),
+        );
+
+        Ok(())
+    }
+}
+
+/// Distance (in blocks) for one full bobbing cycle, i.e. two steps.
+const BOBBING_STRIDE: f32 = 3.0;
+
+/// How much the camera moves sideways and up while bobbing (in blocks).
+const BOBBING_AMPLITUDE: Vector2<f32> = Vector2::new(0.03, 0.04);
+
+/// How fast the bobbing fades in and out when the player starts or stops
+/// walking (per second).
+const BOBBING_FADE: f32 = 4.0;
+
+/// Bobs the camera up and down while the player walks.
+///
+/// Only horizontal movement counts as walking.
+#[derive(Clone, Copy, Debug, Default, Component)]
+pub struct ViewBobbing {
+    /// Where in the bobbing cycle the camera is, between 0 and 2π.
+    phase: f32,
+
+    /// Fades in while walking and out while standing still, between 0 and 1.
+    amplitude: f32,
+
+    last_position: Option<Vector2<f32>>,
+}
+
+impl ViewBobbing {
+    fn advance(&mut self, distance: f32, dt: f32) {
+        self.phase = (self.phase + TAU * distance / BOBBING_STRIDE).rem_euclid(TAU);
+
+        let target = if distance > 0.0 { 1.0 } else { 0.0 };
+        let step = BOBBING_FADE * dt;
+        self.amplitude += (target - self.amplitude).clamp(-step, step);
+
+        // the next walk starts with a step
+        if self.amplitude == 0.0 {
+            self.phase = 0.0;
+        }
+    }
+
+    fn offset(&self) -> Vector3<f32> {
+        // one sideways sway per cycle, and one bounce per step
+        Vector3::new(
+            BOBBING_AMPLITUDE.x * self.phase.sin(),
+            BOBBING_AMPLITUDE.y * (2.0 * self.phase).sin().abs(),
+            0.0,
+        ) * self.amplitude
+    }
+}
+
+/// Maximum rotation of the camera (yaw, pitch, roll) at full trauma.
+const MAX_SHAKE_ANGLES: Vector3<f32> = Vector3::new(0.05, 0.05, 0.08);
+
+/// How fast the shake wobbles.
+const SHAKE_FREQUENCY: f32 = 15.0;
+
+/// Shakes the camera.
+///
+/// Gameplay adds trauma, e.g. depending on how close an explosion was, and the
+/// trauma decays over time. The shake grows with the square of the trauma, so
+/// small hits barely move the camera, while big ones add up quickly.
+#[derive(Clone, Copy, Debug, Component)]
+pub struct CameraShake {
+    /// Between 0 and 1.
+    trauma: f32,
+
+    /// How much trauma decays per second.
+    pub decay: f32,
+
+    noise: PerlinNoise,
+    time: f32,
+}
+
+impl Default for CameraShake {
+    fn default() -> Self {
+        Self {
+            trauma: 0.0,
+            decay: 1.0,
+            noise: PerlinNoise::new(0),
+            time: 0.0,
+        }
+    }
+}
+
+impl CameraShake {
+    /// Adds trauma, e.g. 0.2 for taking damage or 1.0 for an explosion right
+    /// next to the player. Trauma is capped at 1.
+    pub fn add_trauma(&mut self, trauma: f32) {
+        self.trauma = (self.trauma + trauma.max(0.0)).min(1.0);
+    }
+
+    pub fn trauma(&self) -> f32 {
+        self.trauma
+    }
+
+    /// How strong the shake is right now, between 0 and 1.
+    pub fn intensity(&self) -> f32 {
+        self.trauma * self.trauma
+    }
+
+    fn advance(&mut self, dt: f32) {
+        self.trauma = (self.trauma - self.decay * dt).max(0.0);
+        self.time += dt;
+    }
+
+    fn rotation(&self) -> UnitQuaternion<f32> {
+        let intensity = self.intensity();
+        if intensity == 0.0 {
+            return UnitQuaternion::identity();
+        }
+
+        // each axis samples its own row of the noise
+        let angle = |axis: usize| {
+            let point = Point2::new(SHAKE_FREQUENCY * self.time, 10.5 * axis as f32);
+            intensity * MAX_SHAKE_ANGLES[axis] * self.noise.evaluate_at(point)
+        };
+
+        UnitQuaternion::from_euler_angles(angle(1), angle(0), angle(2))
+    }
+}
+
+fn update_view_bobbing(
+    player: Single<(&GlobalTransform, &mut ViewBobbing), With<Player>>,
+    time: Res<Time>,
+) {
+    let (transform, mut bobbing) = player.into_inner();
+
+    let position = transform.position().xz().coords;
+    let distance = bobbing
+        .last_position
+        .map_or(0.0, |last_position| (position - last_position).norm());
+
+    bobbing.last_position = Some(position);
+    bobbing.advance(distance, time.delta_seconds());
+}
+
+fn update_camera_shake(mut shake: Single<&mut CameraShake, With<Player>>, time: Res<Time>) {
+    if shake.trauma > 0.0 {
+        shake.advance(time.delta_seconds());
+    }
+}
+
+fn update_camera_offset(
+    player: Single<(&ViewBobbing, &CameraShake, &mut CameraOffset), With<Player>>,
+    game_config: Res<GameConfig>,
+    accessibility_config: Res<AccessibilityConfig>,
+) {
+    let (bobbing, shake, mut offset) = player.into_inner();
+    let motion_scale = accessibility_config.motion_scale();
+
+    let translation = if game_config.view_bobbing {
+        bobbing.offset() * motion_scale
+    }
+    else {
+        Vector3::zeros()
+    };
+
+    let rotation = UnitQuaternion::identity().slerp(&shake.rotation(), motion_scale);
+
+    let isometry = Isometry3::from_parts(Translation3::from(translation), rotation);
+    if offset.0 != isometry {
+        offset.0 = isometry;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::UnitQuaternion;
+
+    use crate::game::camera_effects::{
+        CameraShake,
+        ViewBobbing,
+    };
+
+    #[test]
+    fn it_decays_and_caps_trauma() {
+        let mut shake = CameraShake::default();
+
+        shake.add_trauma(0.7);
+        shake.add_trauma(0.7);
+        assert_eq!(shake.trauma(), 1.0);
+        assert_eq!(shake.intensity(), 1.0);
+
+        shake.advance(0.5);
+        assert_eq!(shake.trauma(), 0.5);
+        assert_eq!(shake.intensity(), 0.25);
+
+        shake.advance(1.0);
+        assert_eq!(shake.trauma(), 0.0);
+        assert_eq!(shake.rotation(), UnitQuaternion::identity());
+    }
+
+    #[test]
+    fn it_fades_out_bobbing_when_standing_still() {
+        let mut bobbing = ViewBobbing::default();
+
+        for _ in 0..10 {
+            bobbing.advance(0.1, 0.1);
+        }
+        assert!((bobbing.amplitude - 1.0).abs() < 1e-6);
+        assert!(bobbing.offset().norm() > 0.0);
+
+        for _ in 0..10 {
+            bobbing.advance(0.0, 0.1);
+        }
+        assert_eq!(bobbing.amplitude, 0.0);
+        assert_eq!(bobbing.offset().norm(), 0.0);
+    }
+}
@@ -1,4 +1,7 @@
-use std::f64::consts::PI;
+use std::f64::consts::{
+    PI,
+    TAU,
+};
 
 use chrono::{
     DateTime,
@@ -23,8 +26,116 @@ pub struct GeoCoords<T> {
     pub latitude: T,
 }
 
+impl GeoCoords<f64> {
+    pub fn from_degrees(latitude: f64, longitude: f64) -> Self {
+        Self {
+            longitude: longitude.to_radians(),
+            latitude: latitude.to_radians(),
+        }
+    }
+}
+
+/// Geographic location of the world origin.
+///
+/// Unlike [`GeoCoords`] this is in degrees, since it's meant to be written by
+/// humans.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WorldLocation {
+    pub latitude: f64,
+    pub longitude: f64,
+}
+
+impl WorldLocation {
+    pub fn to_geo(&self) -> GeoCoords<f64> {
+        GeoCoords::from_degrees(self.latitude, self.longitude)
+    }
+}
+
+impl Default for WorldLocation {
+    fn default() -> Self {
+        // what's here?
+        Self {
+            latitude: 51.283889,
+            longitude: 11.52,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Season {
+    Spring,
+    Summer,
+    Autumn,
+    Winter,
+}
+
+impl Season {
+    /// Meteorological season for a date and latitude (in radians).
+    pub fn new(time: DateTime<Utc>, latitude: f64) -> Self {
+        let northern = match time.month() {
+            3..=5 => Self::Spring,
+            6..=8 => Self::Summer,
+            9..=11 => Self::Autumn,
+            _ => Self::Winter,
+        };
+
+        if latitude >= 0.0 {
+            northern
+        }
+        else {
+            match northern {
+                Self::Spring => Self::Autumn,
+                Self::Summer => Self::Winter,
+                Self::Autumn => Self::Spring,
+                Self::Winter => Self::Summer,
+            }
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Spring => "spring",
+            Self::Summer => "summer",
+            Self::Autumn => "autumn",
+            Self::Winter => "winter",
+        }
+    }
+}
+
+/// Compresses the calendar, such that a full year passes in `days_per_year`
+/// days.
+///
+/// The time of day is kept as is, but the date advances faster (or slower), so
+/// that seasons - and with them the length of the day and the elevation of the
+/// sun - change at a playable pace. The compression counts days since the Unix
+/// epoch, so the seasons keep cycling across new year instead of restarting
+/// with every calendar year. The result stays in the same year as `time`.
+///
+/// `time` is returned unchanged if `days_per_year` isn't positive.
+pub fn seasonal_time(time: DateTime<Utc>, days_per_year: f32) -> DateTime<Utc> {
+    const SECONDS_PER_DAY: i64 = 24 * 60 * 60;
+
+    if !(days_per_year.is_finite() && days_per_year > 0.0) {
+        return time;
+    }
+
+    let date = time.date_naive();
+    let days_in_year = if date.leap_year() { 366 } else { 365 };
+
+    let day_count = time.timestamp().div_euclid(SECONDS_PER_DAY) as f64;
+    let year_fraction = (day_count / f64::from(days_per_year)).rem_euclid(1.0);
+    let seasonal_day = ((year_fraction * f64::from(days_in_year)) as u32).min(days_in_year - 1);
+
+    date.with_ordinal0(seasonal_day)
+        .unwrap_or(date)
+        .and_time(time.time())
+        .and_utc()
+}
+
 #[derive(Clone, Copy, Debug)]
 pub struct CelestialFrame {
+    time: DateTime<Utc>,
     jd: f64,
     mean_obliqueness: f64,
     hour_angle: f64,
@@ -39,6 +150,7 @@ impl CelestialFrame {
         let hour_angle = mean_sidereal + observer_position.longitude;
 
         Self {
+            time,
             jd,
             mean_obliqueness,
             hour_angle,
@@ -46,7 +158,9 @@ impl CelestialFrame {
         }
     }
 
-    fn ecliptic_to_world_rotation(&self, ecl_pos: astro::coords::EclPoint) -> UnitQuaternion<f32> {
+    /// Converts ecliptic coordinates to horizontal coordinates (altitude,
+    /// azimuth) for the observer.
+    fn ecliptic_to_horizontal(&self, ecl_pos: astro::coords::EclPoint) -> (f64, f64) {
         // equatorial
         let right_ascension =
             astro::coords::asc_frm_ecl(ecl_pos.long, ecl_pos.lat, self.mean_obliqueness);
@@ -61,6 +175,12 @@ impl CelestialFrame {
         let azimuth =
             astro::coords::az_frm_eq(hour_angle, declination, self.observer_position.latitude);
 
+        (altitude, azimuth)
+    }
+
+    fn ecliptic_to_world_rotation(&self, ecl_pos: astro::coords::EclPoint) -> UnitQuaternion<f32> {
+        let (altitude, azimuth) = self.ecliptic_to_horizontal(ecl_pos);
+
         // not sure why we need that + PI, but then the sun is where it is supposed to
         // be :3
         (UnitQuaternion::from_axis_angle(&Vector3::y_axis(), azimuth + PI)
@@ -83,6 +203,84 @@ impl CelestialFrame {
         let (ecl_pos, _distance_km) = astro::lunar::geocent_ecl_pos(self.jd);
         self.ecliptic_to_world_rotation(ecl_pos)
     }
+
+    /// Altitude of the sun above the horizon (in radians).
+    pub fn sun_altitude(&self) -> f64 {
+        let (ecl_pos, _distance_au) = astro::sun::geocent_ecl_pos(self.jd);
+        self.ecliptic_to_horizontal(ecl_pos).0
+    }
+
+    /// Altitude of the moon above the horizon (in radians).
+    pub fn moon_altitude(&self) -> f64 {
+        let (ecl_pos, _distance_km) = astro::lunar::geocent_ecl_pos(self.jd);
+        self.ecliptic_to_horizontal(ecl_pos).0
+    }
+
+    /// How dark the sky is, from 0 (day) to 1 (night).
+    ///
+    /// This ramps from sunset (sun at the horizon) to the end of astronomical
+    /// twilight (sun 18° below the horizon).
+    pub fn darkness(&self) -> f32 {
+        const TWILIGHT: f64 = 18.0;
+
+        let sun_altitude = self.sun_altitude().to_degrees();
+        (-sun_altitude / TWILIGHT).clamp(0.0, 1.0) as f32
+    }
+
+    pub fn season(&self) -> Season {
+        Season::new(self.time, self.observer_position.latitude)
+    }
+}
+
+/// Phase of the moon, derived from the angle between sun and moon along the
+/// ecliptic.
+#[derive(Clone, Copy, Debug)]
+pub struct MoonPhase {
+    /// Ecliptic longitude of the moon relative to the sun (in radians, 0 to
+    /// 2π). 0 is new moon, π is full moon.
+    pub elongation: f64,
+}
+
+impl MoonPhase {
+    /// Phase of the moon at `time`.
+    ///
+    /// Unlike the sun, the moon isn't placed with the seasonal time, so that
+    /// its phase keeps following the actual date.
+    pub fn new(time: DateTime<Utc>) -> Self {
+        let jd = utc_to_jd(time);
+        let (sun_pos, _distance_au) = astro::sun::geocent_ecl_pos(jd);
+        let (moon_pos, _distance_km) = astro::lunar::geocent_ecl_pos(jd);
+
+        Self {
+            elongation: (moon_pos.long - sun_pos.long).rem_euclid(TAU),
+        }
+    }
+
+    /// Fraction of the moon's disk that is illuminated (0 to 1).
+    pub fn illuminated_fraction(&self) -> f64 {
+        0.5 * (1.0 - self.elongation.cos())
+    }
+
+    pub fn is_waxing(&self) -> bool {
+        self.elongation < PI
+    }
+
+    pub fn name(&self) -> &'static str {
+        const NAMES: [&str; 8] = [
+            "new moon",
+            "waxing crescent",
+            "first quarter",
+            "waxing gibbous",
+            "full moon",
+            "waning gibbous",
+            "last quarter",
+            "waning crescent",
+        ];
+
+        // each phase covers 1/8 of the cycle, centered around its exact angle
+        let index = ((self.elongation / TAU * 8.0).round() as usize) % 8;
+        NAMES[index]
+    }
 }
 
 /// Calculates longitude and latitude from a world position
@@ -120,3 +318,83 @@ fn utc_to_astro_date(time: DateTime<Utc>) -> astro::time::Date {
         cal_type: astro::time::CalType::Gregorian,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use chrono::{
+        DateTime,
+        Datelike,
+        Timelike,
+        Utc,
+    };
+
+    use crate::game::celestial::{
+        Season,
+        seasonal_time,
+    };
+
+    #[test]
+    fn seasonal_time_keeps_time_of_day() {
+        let time: DateTime<Utc> = "2025-03-14T13:37:00Z".parse().unwrap();
+        let seasonal = seasonal_time(time, 28.0);
+
+        assert_eq!(seasonal.hour(), 13);
+        assert_eq!(seasonal.minute(), 37);
+    }
+
+    #[test]
+    fn seasonal_time_stays_in_the_same_year() {
+        let time: DateTime<Utc> = "2025-12-31T13:37:00Z".parse().unwrap();
+
+        for days_per_year in [f32::MIN_POSITIVE, 0.01, 1.0, 28.0, 365.0, 1e30] {
+            let seasonal = seasonal_time(time, days_per_year);
+            assert_eq!(seasonal.year(), 2025, "days_per_year = {days_per_year}");
+            assert_eq!(
+                seasonal.time(),
+                time.time(),
+                "days_per_year = {days_per_year}"
+            );
+        }
+    }
+
+    #[test]
+    fn seasonal_time_ignores_invalid_year_lengths() {
+        let time: DateTime<Utc> = "2025-03-14T13:37:00Z".parse().unwrap();
+
+        for days_per_year in [0.0, -28.0, f32::NAN, f32::INFINITY] {
+            assert_eq!(seasonal_time(time, days_per_year), time);
+        }
+    }
+
+    #[test]
+    fn seasonal_time_starts_at_the_epoch() {
+        let time: DateTime<Utc> = "1970-01-01T06:00:00Z".parse().unwrap();
+        assert_eq!(seasonal_time(time, 28.0), time);
+
+        // a week is a quarter of a year
+        let time: DateTime<Utc> = "1970-01-08T06:00:00Z".parse().unwrap();
+        assert_eq!(seasonal_time(time, 28.0).ordinal0(), 91);
+    }
+
+    #[test]
+    fn seasonal_time_continues_across_new_year() {
+        let last_day: DateTime<Utc> = "2025-12-31T12:00:00Z".parse().unwrap();
+        let first_day: DateTime<Utc> = "2026-01-01T12:00:00Z".parse().unwrap();
+
+        // one day is 1/28 of a year, i.e. 13 days in 365
+        let last_day = seasonal_time(last_day, 28.0).ordinal0();
+        let first_day = seasonal_time(first_day, 28.0).ordinal0();
+        assert_eq!(
+            (i64::from(first_day) - i64::from(last_day)).rem_euclid(365),
+            13
+        );
+    }
+
+    #[test]
+    fn seasons_are_flipped_on_southern_hemisphere() {
+        let time: DateTime<Utc> = "2025-07-01T12:00:00Z".parse().unwrap();
+
+        assert_eq!(Season::new(time, 0.5), Season::Summer);
+        assert_eq!(Season::new(time, -0.5), Season::Winter);
+    }
+}
@@ -0,0 +1,212 @@
+use std::time::Duration;
+
+use bevy_ecs::{
+    resource::Resource,
+    schedule::{
+        IntoScheduleConfigs,
+        SystemSet,
+        common_conditions::resource_exists,
+    },
+    system::{
+        Res,
+        ResMut,
+    },
+};
+use chrono::{
+    DateTime,
+    NaiveTime,
+    TimeDelta,
+    Utc,
+};
+use color_eyre::eyre::{
+    Error,
+    bail,
+    eyre,
+};
+use serde::{
+    Deserialize,
+    Serialize,
+};
+
+use crate::{
+    app::Time,
+    ecs::{
+        plugin::{
+            Plugin,
+            WorldBuilder,
+        },
+        schedule,
+    },
+    game::{
+        file::WorldFile,
+        terrain::WorldConfig,
+    },
+};
+
+/// Key under which the clock is stored in the world file.
+const WORLD_FILE_KEY: &str = "clock";
+
+/// Seconds in an astronomical day.
+const SECONDS_PER_DAY: f32 = 24.0 * 60.0 * 60.0;
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct GameClockPlugin;
+
+impl Plugin for GameClockPlugin {
+    fn setup(&self, builder: &mut WorldBuilder) -> Result<(), Error> {
+        let clock = builder
+            .world
+            .get_resource::<WorldFile>()
+            .map_or(Ok(None), |world_file| {
+                world_file.load_state::<GameClock>(WORLD_FILE_KEY)
+            });
+
+        let clock = match clock {
+            Ok(clock) => clock,
+            Err(error) => {
+                tracing::error!(?error, "could not load game clock");
+                None
+            }
+        }
+        .unwrap_or_else(|| GameClock::new(Utc::now()));
+
+        tracing::debug!(?clock, "game clock");
+
+        builder
+            .insert_resource(clock)
+            .add_systems(
+                schedule::Update,
+                advance_clock.in_set(GameClockSystems::Advance),
+            )
+            .add_systems(
+                schedule::Shutdown,
+                save_clock.run_if(resource_exists::<WorldFile>),
+            );
+
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, SystemSet)]
+pub enum GameClockSystems {
+    Advance,
+}
+
+/// The in-game date and time.
+///
+/// This drives the celestial simulation. It advances with a speed determined
+/// by [`WorldConfig::day_length`] and is persisted in the world file.
+#[derive(Clone, Copy, Debug, Resource, Serialize, Deserialize)]
+pub struct GameClock {
+    pub time: DateTime<Utc>,
+
+    #[serde(default)]
+    pub frozen: bool,
+}
+
+impl GameClock {
+    pub fn new(time: DateTime<Utc>) -> Self {
+        Self {
+            time,
+            frozen: false,
+        }
+    }
+
+    /// Advances the clock by `real_time`, which is scaled such that an
+    /// in-game day is `day_length` seconds long.
+    ///
+    /// `day_length` must be positive, see [`WorldConfig::validate`]. The clock
+    /// stops at the end of the representable time.
+    pub fn advance(&mut self, real_time: Duration, day_length: f32) {
+        if self.frozen {
+            return;
+        }
+
+        let time_warp = SECONDS_PER_DAY / day_length;
+        let advanced = Duration::try_from_secs_f32(real_time.as_secs_f32() * time_warp)
+            .map_err(Error::from)
+            .and_then(|duration| self.add(duration));
+
+        if let Err(error) = advanced {
+            tracing::warn!(clock = ?self, "can't advance game clock: {error}");
+            self.frozen = true;
+        }
+    }
+
+    pub fn add(&mut self, duration: Duration) -> Result<(), Error> {
+        self.time = TimeDelta::from_std(duration)
+            .ok()
+            .and_then(|delta| self.time.checked_add_signed(delta))
+            .ok_or_else(|| eyre!("Game time out of range: {} + {duration:?}", self.time))?;
+        Ok(())
+    }
+
+    pub fn subtract(&mut self, duration: Duration) -> Result<(), Error> {
+        self.time = TimeDelta::from_std(duration)
+            .ok()
+            .and_then(|delta| self.time.checked_sub_signed(delta))
+            .ok_or_else(|| eyre!("Game time out of range: {} - {duration:?}", self.time))?;
+        Ok(())
+    }
+
+    /// Sets the time from a string.
+    ///
+    /// This accepts either a full RFC 3339 date-time, or just a time of day
+    /// (`HH:MM` or `HH:MM:SS`), in which case the date stays the same.
+    pub fn set_from_str(&mut self, time: &str) -> Result<(), Error> {
+        if let Ok(time) = DateTime::parse_from_rfc3339(time) {
+            self.time = time.to_utc();
+        }
+        else if let Ok(time_of_day) = NaiveTime::parse_from_str(time, "%H:%M:%S")
+            .or_else(|_| NaiveTime::parse_from_str(time, "%H:%M"))
+        {
+            self.time = self.time.date_naive().and_time(time_of_day).and_utc();
+        }
+        else {
+            bail!("Invalid time: {time}");
+        }
+
+        Ok(())
+    }
+}
+
+fn advance_clock(time: Res<Time>, world_config: Res<WorldConfig>, mut clock: ResMut<GameClock>) {
+    clock.advance(time.tick_delta, world_config.day_length);
+}
+
+fn save_clock(world_file: Res<WorldFile>, clock: Res<GameClock>) {
+    tracing::debug!(clock = ?*clock, "saving game clock");
+
+    if let Err(error) = world_file.store_state(WORLD_FILE_KEY, &*clock) {
+        tracing::error!(?error, "could not save game clock");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use chrono::Utc;
+
+    use crate::game::clock::GameClock;
+
+    #[test]
+    fn it_rejects_adding_time_out_of_range() {
+        let now = Utc::now();
+        let mut clock = GameClock::new(now);
+
+        assert!(clock.add(Duration::from_secs_f64(1e13)).is_err());
+        assert!(clock.subtract(Duration::from_secs_f64(1e13)).is_err());
+        assert_eq!(clock.time, now);
+
+        clock.add(Duration::from_secs(60)).unwrap();
+        assert_eq!(clock.time, now + Duration::from_secs(60));
+    }
+
+    #[test]
+    fn it_stops_advancing_at_the_end_of_time() {
+        let mut clock = GameClock::new(Utc::now());
+        clock.advance(Duration::from_secs(1), f32::MIN_POSITIVE);
+        assert!(clock.frozen);
+    }
+}
@@ -0,0 +1,293 @@
+//! Undo and redo for world edits.
+//!
+//! Every player has an [`EditHistory`] that records the blocks replaced by
+//! their edits: broken blocks, region edits, fills and pasted schematics. It's
+//! only kept for the session and isn't saved with the world.
+//!
+//! In creative mode, edits are undone with
+//! Ctrl+[undo key][EditHistoryConfig::undo_key] and redone with
+//! Ctrl+[redo key][EditHistoryConfig::redo_key]. The `undo` and `redo` RCON
+//! commands work in any game mode.
+
+use std::collections::VecDeque;
+
+use bevy_ecs::{
+    component::Component,
+    query::With,
+    resource::Resource,
+    schedule::{
+        IntoScheduleConfigs,
+        SystemCondition,
+        common_conditions::resource_exists,
+    },
+    system::{
+        Query,
+        Res,
+        Single,
+    },
+};
+use color_eyre::eyre::Error;
+use nalgebra::Point3;
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use winit::keyboard::KeyCode;
+
+use crate::{
+    app::GrabCursor,
+    ecs::{
+        plugin::{
+            Dependencies,
+            Plugin,
+            WorldBuilder,
+        },
+        schedule,
+    },
+    game::{
+        ChunkShape,
+        Player,
+        block_type::BlockTypes,
+        game_mode::{
+            GameMode,
+            GameModePlugin,
+            in_game_mode,
+        },
+        terrain::TerrainVoxel,
+    },
+    input::{
+        InputSystems,
+        Keys,
+    },
+    render::render_target::RenderTarget,
+    voxel::{
+        access::Voxels,
+        edit::ChunkEditBatch,
+    },
+};
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct EditHistoryPlugin {
+    pub config: EditHistoryConfig,
+}
+
+impl Plugin for EditHistoryPlugin {
+    fn dependencies(&self, dependencies: &mut Dependencies) -> Result<(), Error> {
+        dependencies.require_plugin::<GameModePlugin>();
+        Ok(())
+    }
+
+    fn setup(&self, builder: &mut WorldBuilder) -> Result<(), Error> {
+        builder.insert_resource(self.config).add_systems(
+            schedule::Update,
+            undo_with_keys
+                .after(InputSystems::Update)
+                .run_if(resource_exists::<BlockTypes>.and(in_game_mode(GameMode::Creative))),
+        );
+
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize, Resource)]
+pub struct EditHistoryConfig {
+    /// Number of edits that can be undone. Older edits are forgotten.
+    #[serde(default = "default_capacity")]
+    pub capacity: usize,
+
+    /// Undoes the last edit while Ctrl is held.
+    #[serde(default = "default_undo_key")]
+    pub undo_key: KeyCode,
+
+    /// Redoes the last undone edit while Ctrl is held.
+    #[serde(default = "default_redo_key")]
+    pub redo_key: KeyCode,
+}
+
+impl Default for EditHistoryConfig {
+    fn default() -> Self {
+        Self {
+            capacity: default_capacity(),
+            undo_key: default_undo_key(),
+            redo_key: default_redo_key(),
+        }
+    }
+}
+
+fn default_capacity() -> usize {
+    100
+}
+
+fn default_undo_key() -> KeyCode {
+    KeyCode::KeyZ
+}
+
+fn default_redo_key() -> KeyCode {
+    KeyCode::KeyY
+}
+
+/// The blocks replaced by an edit, in the order they were written.
+type Replaced = Vec<(Point3<i32>, TerrainVoxel)>;
+
+/// Edits made by a player that can be undone and redone.
+#[derive(Debug, Component)]
+pub struct EditHistory {
+    /// Oldest edit first.
+    undo: VecDeque<Replaced>,
+
+    /// Most recently undone edit last.
+    redo: Vec<Replaced>,
+
+    capacity: usize,
+}
+
+impl EditHistory {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            undo: VecDeque::new(),
+            redo: vec![],
+            capacity,
+        }
+    }
+
+    /// Changes the number of edits that are remembered, forgetting the oldest
+    /// ones if there are too many.
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        self.truncate();
+    }
+
+    /// Records an edit by the blocks it replaced. This clears the edits that
+    /// could be redone.
+    pub fn push(&mut self, replaced: Replaced) {
+        if replaced.is_empty() {
+            return;
+        }
+
+        self.redo.clear();
+        self.undo.push_back(replaced);
+        self.truncate();
+    }
+
+    /// Applies the batch and records it. Returns the number of blocks changed.
+    pub fn apply(
+        &mut self,
+        voxels: &mut Voxels<TerrainVoxel, ChunkShape>,
+        batch: ChunkEditBatch<TerrainVoxel, ChunkShape>,
+    ) -> usize {
+        let replaced = voxels.apply(batch);
+        let num_changed = replaced.len();
+        self.push(replaced);
+        num_changed
+    }
+
+    /// Reverts the last edit. Returns the number of blocks restored, or `None`
+    /// if there's nothing to undo.
+    pub fn undo(&mut self, voxels: &mut Voxels<TerrainVoxel, ChunkShape>) -> Option<usize> {
+        let replaced = self.undo.pop_back()?;
+        let redone = voxels.apply(revert(replaced));
+        let num_restored = redone.len();
+        self.redo.push(redone);
+        Some(num_restored)
+    }
+
+    /// Applies the last undone edit again. Returns the number of blocks
+    /// changed, or `None` if there's nothing to redo.
+    pub fn redo(&mut self, voxels: &mut Voxels<TerrainVoxel, ChunkShape>) -> Option<usize> {
+        let replaced = self.redo.pop()?;
+        let undone = voxels.apply(revert(replaced));
+        let num_changed = undone.len();
+        // not `push`, since that would clear the other redos
+        self.undo.push_back(undone);
+        self.truncate();
+        Some(num_changed)
+    }
+
+    fn truncate(&mut self) {
+        while self.undo.len() > self.capacity {
+            self.undo.pop_front();
+        }
+    }
+}
+
+/// Writes the replaced blocks back.
+///
+/// A position can be replaced several times by one edit, so the blocks are
+/// written in reverse and the oldest one wins. The batch then replaces the
+/// blocks in the same order, so reverting it again redoes the edit.
+fn revert(replaced: Replaced) -> ChunkEditBatch<TerrainVoxel, ChunkShape> {
+    let mut batch = ChunkEditBatch::default();
+    for (position, voxel) in replaced.into_iter().rev() {
+        batch.set(position, voxel);
+    }
+    batch
+}
+
+fn undo_with_keys(
+    player: Single<(&RenderTarget, &mut EditHistory), With<Player>>,
+    windows: Query<&Keys, With<GrabCursor>>,
+    config: Res<EditHistoryConfig>,
+    mut voxels: Voxels<TerrainVoxel, ChunkShape>,
+) {
+    let (render_target, mut history) = player.into_inner();
+
+    let Ok(keys) = windows.get(render_target.0)
+    else {
+        return;
+    };
+
+    if !keys.pressed.contains(&KeyCode::ControlLeft)
+        && !keys.pressed.contains(&KeyCode::ControlRight)
+    {
+        return;
+    }
+
+    if keys.just_pressed.contains(&config.undo_key)
+        && let Some(num_restored) = history.undo(&mut voxels)
+    {
+        tracing::debug!(num_restored, "edit undone");
+    }
+    else if keys.just_pressed.contains(&config.redo_key)
+        && let Some(num_changed) = history.redo(&mut voxels)
+    {
+        tracing::debug!(num_changed, "edit redone");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::Point3;
+
+    use crate::game::{
+        block_type::BlockType,
+        edit_history::EditHistory,
+        terrain::TerrainVoxel,
+    };
+
+    #[test]
+    fn it_forgets_the_oldest_edits() {
+        let edit = |x| {
+            vec![(
+                Point3::new(x, 0, 0),
+                TerrainVoxel {
+                    block_type: BlockType::from_usize(0),
+                },
+            )]
+        };
+
+        let mut history = EditHistory::new(2);
+        history.push(edit(0));
+        history.push(edit(1));
+        history.push(edit(2));
+        history.push(vec![]);
+
+        let oldest = |history: &EditHistory| history.undo[0][0].0.x;
+
+        assert_eq!(history.undo.len(), 2);
+        assert_eq!(oldest(&history), 1);
+
+        history.set_capacity(1);
+        assert_eq!(history.undo.len(), 1);
+        assert_eq!(oldest(&history), 2);
+    }
+}
@@ -0,0 +1,283 @@
+//! Explosions remove the blocks around them, throw debris and shake the
+//! camera.
+//!
+//! An explosion's strength falls off linearly from its center to its radius,
+//! and a block is destroyed if the strength at its center exceeds its
+//! [resistance][crate::game::block_type::BlockTypeData::resistance]. All
+//! blocks destroyed by an explosion are removed at once, so every chunk is
+//! only remeshed once.
+
+use std::f32::consts::TAU;
+
+use bevy_ecs::{
+    component::Component,
+    entity::Entity,
+    message::{
+        Message,
+        MessageReader,
+    },
+    name::Name,
+    query::With,
+    schedule::{
+        IntoScheduleConfigs,
+        common_conditions::resource_exists,
+    },
+    system::{
+        Commands,
+        Populated,
+        Query,
+        Res,
+    },
+};
+use color_eyre::eyre::Error;
+use nalgebra::{
+    Point3,
+    Translation3,
+    Vector3,
+};
+use rand::Rng;
+
+use crate::{
+    app::Time,
+    ecs::{
+        plugin::{
+            Dependencies,
+            Plugin,
+            WorldBuilder,
+        },
+        schedule,
+        transform::{
+            GlobalTransform,
+            LocalTransform,
+        },
+    },
+    game::{
+        ChunkShape,
+        Player,
+        block_mesh::{
+            BlockMesh,
+            BlockMeshPlugin,
+        },
+        block_type::BlockTypes,
+        camera_effects::CameraShake,
+        terrain::TerrainVoxel,
+    },
+    sound::{
+        output::SoundOutput,
+        sounds::Sounds,
+    },
+    voxel::{
+        access::{
+            Voxels,
+            block_center,
+        },
+        edit::ChunkEditBatch,
+    },
+};
+
+/// Maximum number of debris pieces thrown by one explosion.
+const MAX_DEBRIS: usize = 24;
+
+/// Side length of a debris piece.
+const DEBRIS_SIZE: f32 = 0.2;
+
+/// Seconds until debris despawns.
+const DEBRIS_LIFETIME: f32 = 1.5;
+
+/// Initial speed of debris (in blocks / s).
+const DEBRIS_SPEED: f32 = 8.0;
+
+/// Acceleration of debris (in blocks / s²).
+const GRAVITY: f32 = 20.0;
+
+/// Multiple of the radius up to which the camera shakes.
+const SHAKE_DISTANCE: f32 = 4.0;
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ExplosionPlugin;
+
+impl Plugin for ExplosionPlugin {
+    fn dependencies(&self, dependencies: &mut Dependencies) -> Result<(), Error> {
+        dependencies.require_plugin::<BlockMeshPlugin>();
+        Ok(())
+    }
+
+    fn setup(&self, builder: &mut WorldBuilder) -> Result<(), Error> {
+        builder.add_message::<Explosion>().add_systems(
+            schedule::Update,
+            (explode.run_if(resource_exists::<BlockTypes>), update_debris).chain(),
+        );
+
+        Ok(())
+    }
+}
+
+/// Send this to make something explode.
+#[derive(Clone, Copy, Debug, Message)]
+pub struct Explosion {
+    pub center: Point3<f32>,
+
+    /// Blocks further away are not affected (in blocks).
+    pub radius: f32,
+
+    /// Strength at the center, which is compared to the blocks' resistance.
+    pub power: f32,
+}
+
+impl Explosion {
+    /// Strength of the explosion at a point.
+    pub fn strength_at(&self, point: &Point3<f32>) -> f32 {
+        let distance = (point - self.center).norm();
+        self.power * (1.0 - distance / self.radius).max(0.0)
+    }
+
+    /// The corners of the region of blocks that can be affected.
+    pub fn bounds(&self) -> (Point3<i32>, Point3<i32>) {
+        let min = self.center.map(|c| (c - self.radius).floor() as i32);
+        let max = self.center.map(|c| (c + self.radius).ceil() as i32);
+        (min, max)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Component)]
+struct Debris {
+    position: Point3<f32>,
+    velocity: Vector3<f32>,
+    age: f32,
+}
+
+fn explode(
+    mut explosions: MessageReader<Explosion>,
+    block_types: Res<BlockTypes>,
+    mut voxels: Voxels<TerrainVoxel, ChunkShape>,
+    mut players: Query<(&GlobalTransform, &mut CameraShake), With<Player>>,
+    sounds: Option<Res<Sounds>>,
+    sound_output: Option<Res<SoundOutput>>,
+    mut commands: Commands,
+) {
+    let air = block_types.lookup("air").unwrap();
+    let mut rng = rand::rng();
+
+    for explosion in explosions.read() {
+        let (min, max) = explosion.bounds();
+
+        let mut batch = ChunkEditBatch::default();
+        for x in min.x..=max.x {
+            for y in min.y..=max.y {
+                for z in min.z..=max.z {
+                    let position = Point3::new(x, y, z);
+
+                    if let Some(voxel) = voxels.get(position)
+                        && voxel.block_type != air
+                        && explosion.strength_at(&block_center(position))
+                            > block_types[voxel.block_type].resistance
+                    {
+                        batch.set(position, TerrainVoxel { block_type: air });
+                    }
+                }
+            }
+        }
+
+        let destroyed = voxels.apply(batch);
+
+        tracing::debug!(center = ?explosion.center, radius = explosion.radius, destroyed = destroyed.len(), "explosion");
+
+        // throw debris from an even sample of the destroyed blocks
+        let step = destroyed.len().div_ceil(MAX_DEBRIS).max(1);
+        for (position, voxel) in destroyed.iter().step_by(step) {
+            let position = block_center(*position);
+
+            let outwards = (position - explosion.center)
+                .try_normalize(1e-3)
+                .unwrap_or_else(|| {
+                    let angle = rng.random_range(0.0..TAU);
+                    Vector3::new(angle.cos(), 0.0, angle.sin())
+                });
+            let velocity =
+                DEBRIS_SPEED * rng.random_range(0.5..1.0) * (outwards + Vector3::y()).normalize();
+
+            commands.spawn((
+                Name::new("debris"),
+                LocalTransform::from(position.coords).with_uniform_scale(DEBRIS_SIZE),
+                BlockMesh(voxel.block_type),
+                Debris {
+                    position,
+                    velocity,
+                    age: 0.0,
+                },
+            ));
+        }
+
+        for (transform, mut shake) in &mut players {
+            let distance = (transform.position() - explosion.center).norm();
+            shake.add_trauma(1.0 - distance / (SHAKE_DISTANCE * explosion.radius));
+        }
+
+        if let Some(sounds) = &sounds
+            && let Some(sound_output) = &sound_output
+            && let Some(sound) = sounds.lookup("explosion")
+        {
+            match sounds[sound].source() {
+                Ok(source) => sound_output.add(source),
+                Err(error) => tracing::warn!(?error, "could not play explosion sound"),
+            }
+        }
+    }
+}
+
+fn update_debris(
+    time: Res<Time>,
+    debris: Populated<(Entity, &mut Debris, &mut LocalTransform)>,
+    mut commands: Commands,
+) {
+    let dt = time.delta_seconds();
+
+    for (entity, mut debris, mut transform) in debris {
+        debris.age += dt;
+
+        if debris.age > DEBRIS_LIFETIME {
+            commands.entity(entity).despawn();
+        }
+        else {
+            debris.velocity.y -= GRAVITY * dt;
+            let velocity = debris.velocity;
+            debris.position += velocity * dt;
+            transform.isometry.translation = Translation3::from(debris.position.coords);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::Point3;
+
+    use crate::game::explosion::Explosion;
+
+    #[test]
+    fn it_falls_off_towards_the_radius() {
+        let explosion = Explosion {
+            center: Point3::new(0.0, 0.0, 0.0),
+            radius: 4.0,
+            power: 8.0,
+        };
+
+        assert_eq!(explosion.strength_at(&Point3::new(0.0, 0.0, 0.0)), 8.0);
+        assert_eq!(explosion.strength_at(&Point3::new(0.0, 2.0, 0.0)), 4.0);
+        assert_eq!(explosion.strength_at(&Point3::new(0.0, 0.0, -4.0)), 0.0);
+        assert_eq!(explosion.strength_at(&Point3::new(5.0, 0.0, 0.0)), 0.0);
+    }
+
+    #[test]
+    fn it_covers_the_radius() {
+        let explosion = Explosion {
+            center: Point3::new(0.5, 0.5, 0.5),
+            radius: 2.0,
+            power: 4.0,
+        };
+
+        assert_eq!(
+            explosion.bounds(),
+            (Point3::new(-2, -2, -2), Point3::new(3, 3, 3))
+        );
+    }
+}
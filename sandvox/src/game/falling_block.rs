@@ -0,0 +1,209 @@
+//! Blocks like sand and gravel fall down when the block below them is
+//! removed.
+//!
+//! While falling, the block is an entity that is rendered with a [`BlockMesh`].
+//! When it lands it's placed back into the world, or dropped as an item if
+//! there's no room for it.
+
+use bevy_ecs::{
+    component::Component,
+    entity::Entity,
+    message::MessageReader,
+    name::Name,
+    schedule::{
+        IntoScheduleConfigs,
+        common_conditions::resource_exists,
+    },
+    system::{
+        Commands,
+        Populated,
+        Res,
+    },
+};
+use color_eyre::eyre::Error;
+use nalgebra::{
+    Point3,
+    Translation3,
+    Vector3,
+};
+
+use crate::{
+    app::Time,
+    ecs::{
+        plugin::{
+            Dependencies,
+            Plugin,
+            WorldBuilder,
+        },
+        schedule,
+        transform::LocalTransform,
+    },
+    game::{
+        ChunkShape,
+        block_mesh::{
+            BlockMesh,
+            BlockMeshPlugin,
+        },
+        block_type::{
+            BlockType,
+            BlockTypes,
+        },
+        interaction::BlockBroken,
+        item_drop::ItemDrop,
+        terrain::TerrainVoxel,
+    },
+    net::interest::Replicated,
+    voxel::access::{
+        Voxels,
+        block_center,
+        block_containing,
+    },
+};
+
+/// Acceleration of falling blocks (in blocks / s²).
+const GRAVITY: f32 = 20.0;
+
+/// Maximum speed of falling blocks (in blocks / s).
+const TERMINAL_VELOCITY: f32 = 40.0;
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FallingBlockPlugin;
+
+impl Plugin for FallingBlockPlugin {
+    fn dependencies(&self, dependencies: &mut Dependencies) -> Result<(), Error> {
+        dependencies.require_plugin::<BlockMeshPlugin>();
+        Ok(())
+    }
+
+    fn setup(&self, builder: &mut WorldBuilder) -> Result<(), Error> {
+        builder.add_systems(
+            schedule::Update,
+            (detach_unsupported_blocks, update_falling_blocks)
+                .chain()
+                .run_if(resource_exists::<BlockTypes>),
+        );
+
+        Ok(())
+    }
+}
+
+/// A block that was detached from the world and is falling down.
+#[derive(Clone, Copy, Debug, Component)]
+pub struct FallingBlock {
+    pub block_type: BlockType,
+
+    /// Center of the block.
+    pub position: Point3<f32>,
+
+    /// Downwards speed (in blocks / s).
+    pub velocity: f32,
+}
+
+impl FallingBlock {
+    pub fn spawn(self, commands: &mut Commands) -> Entity {
+        commands
+            .spawn((
+                Name::new("falling_block"),
+                LocalTransform::from(self.position.coords),
+                BlockMesh(self.block_type),
+                Replicated,
+                self,
+            ))
+            .id()
+    }
+}
+
+/// Detaches the blocks above removed blocks, if they fall.
+///
+/// Whole columns of falling blocks are detached at once, so they fall together.
+fn detach_unsupported_blocks(
+    mut block_broken: MessageReader<BlockBroken>,
+    block_types: Res<BlockTypes>,
+    mut voxels: Voxels<TerrainVoxel, ChunkShape>,
+    mut commands: Commands,
+) {
+    let air = block_types.lookup("air").unwrap();
+
+    for message in block_broken.read() {
+        let mut position = message.position + Vector3::y();
+
+        while let Some(voxel) = voxels.get(position).copied()
+            && block_types[voxel.block_type].falls
+        {
+            tracing::debug!(?position, block_type = ?voxel.block_type, "block detached");
+
+            voxels.set(position, TerrainVoxel { block_type: air });
+
+            FallingBlock {
+                block_type: voxel.block_type,
+                position: block_center(position),
+                velocity: 0.0,
+            }
+            .spawn(&mut commands);
+
+            position.y += 1;
+        }
+    }
+}
+
+fn update_falling_blocks(
+    time: Res<Time>,
+    block_types: Res<BlockTypes>,
+    mut voxels: Voxels<TerrainVoxel, ChunkShape>,
+    falling_blocks: Populated<(Entity, &mut FallingBlock, &mut LocalTransform)>,
+    mut commands: Commands,
+) {
+    let air = block_types.lookup("air").unwrap();
+    let dt = time.delta_seconds();
+
+    for (entity, mut falling_block, mut transform) in falling_blocks {
+        falling_block.velocity = (falling_block.velocity + GRAVITY * dt).min(TERMINAL_VELOCITY);
+
+        let mut next_position = falling_block.position;
+        next_position.y -= falling_block.velocity * dt;
+
+        // check every block that the bottom of the falling block passes this frame, so
+        // fast blocks don't fall through thin floors. blocks in unloaded chunks count
+        // as solid.
+        let bottom = |position: Point3<f32>| block_containing(&(position - Vector3::y() * 0.5));
+        let from = bottom(falling_block.position);
+        let to = bottom(next_position);
+
+        let Some(below) = (to.y..from.y)
+            .rev()
+            .map(|y| Point3::new(from.x, y, from.z))
+            .find(|position| {
+                voxels
+                    .get(*position)
+                    .is_none_or(|voxel| voxel.block_type != air)
+            })
+        else {
+            falling_block.position = next_position;
+            transform.isometry.translation = Translation3::from(next_position.coords);
+            continue;
+        };
+
+        let position = below + Vector3::y();
+        let block_type = falling_block.block_type;
+
+        if voxels
+            .get(position)
+            .is_some_and(|voxel| voxel.block_type == air)
+        {
+            tracing::debug!(?position, ?block_type, "falling block landed");
+            voxels.set(position, TerrainVoxel { block_type });
+        }
+        else {
+            // something is in the way, e.g. the player placed a block there
+            ItemDrop {
+                block_type,
+                count: 1,
+                position: block_center(position),
+                age: 0.0,
+            }
+            .spawn(&mut commands);
+        }
+
+        commands.entity(entity).despawn();
+    }
+}
@@ -1,4 +1,13 @@
-use std::path::Path;
+//! The world file, in which the world config and game state are saved.
+//!
+//! Every piece of game state is listed in a manifest with a CRC32 checksum of
+//! its encoded value. The checksums are verified when the world is loaded,
+//! and damaged state is reported and not loaded.
+
+use std::{
+    collections::HashMap,
+    path::Path,
+};
 
 use bevy_ecs::resource::Resource;
 use chrono::{
@@ -11,23 +20,34 @@ use color_eyre::eyre::{
 };
 use redb::{
     Database,
+    Key,
+    ReadOnlyTable,
+    ReadTransaction,
     ReadableDatabase,
+    ReadableTable,
     TableDefinition,
+    TableHandle,
+    Value,
 };
 use serde::{
     Deserialize,
     Serialize,
+    de::DeserializeOwned,
 };
 
 use crate::game::WorldConfig;
 
 #[derive(Debug, Resource)]
 pub struct WorldFile {
-    _database: Database,
+    database: Database,
     metadata: Metadata,
+    integrity: IntegrityReport,
 }
 
 impl WorldFile {
+    /// Opens a world file and verifies the checksums of the game state in it.
+    /// Damaged state is logged, and can be inspected with
+    /// [`integrity`](Self::integrity).
     pub fn open(path: impl AsRef<Path>) -> Result<Self, Error> {
         let database = Database::open(path)?;
 
@@ -36,15 +56,28 @@ impl WorldFile {
         let metadata: Metadata =
             serde_cbor::from_slice(&table.get(())?.ok_or_eyre("no metadata")?.value())?;
 
-        Ok(Self {
-            _database: database,
+        let mut world_file = Self {
+            database,
             metadata,
-        })
+            integrity: IntegrityReport::default(),
+        };
+
+        world_file.integrity = world_file.verify()?;
+        for damaged in &world_file.integrity.damaged {
+            tracing::warn!(key = %damaged.key, problem = %damaged.problem, "damaged game state in world file");
+        }
+
+        Ok(world_file)
     }
 
     pub fn create(path: impl AsRef<Path>, world_config: WorldConfig) -> Result<Self, Error> {
-        let database = Database::create(path)?;
+        if let Some(parent) = path.as_ref().parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        Self::create_in(Database::create(path)?, world_config)
+    }
 
+    fn create_in(database: Database, world_config: WorldConfig) -> Result<Self, Error> {
         let time = Local::now();
         let metadata = Metadata {
             time_created: time,
@@ -60,17 +93,328 @@ impl WorldFile {
         write_transaction.commit()?;
 
         Ok(Self {
-            _database: database,
+            database,
             metadata,
+            integrity: IntegrityReport::default(),
         })
     }
 
     pub fn world_config(&self) -> &WorldConfig {
         &self.metadata.world_config
     }
+
+    /// What was found when the checksums were verified while opening the
+    /// file.
+    pub fn integrity(&self) -> &IntegrityReport {
+        &self.integrity
+    }
+
+    /// Reads a piece of game state that was previously stored with
+    /// [`store_state`](Self::store_state).
+    ///
+    /// Fails if the state doesn't match its checksum.
+    pub fn load_state<T>(&self, key: &str) -> Result<Option<T>, Error>
+    where
+        T: DeserializeOwned,
+    {
+        let read_transaction = self.database.begin_read()?;
+
+        let Some(table) = open_optional_table(&read_transaction, STATE)?
+        else {
+            return Ok(None);
+        };
+        let Some(value) = table.get(key)?
+        else {
+            return Ok(None);
+        };
+        let value = value.value();
+
+        // state from before the manifest existed can't be checked
+        if let Some(manifest) = open_optional_table(&read_transaction, MANIFEST)?
+            && let Some(entry) = manifest.get(key)?
+        {
+            let entry: ManifestEntry = serde_cbor::from_slice(&entry.value())?;
+            if let Some(problem) = entry.check(&value) {
+                return Err(DamagedState {
+                    key: key.to_owned(),
+                    problem,
+                }
+                .into());
+            }
+        }
+
+        Ok(Some(serde_cbor::from_slice(&value)?))
+    }
+
+    /// Removes a piece of game state. Returns whether it existed.
+    pub fn remove_state(&self, key: &str) -> Result<bool, Error> {
+        let write_transaction = self.database.begin_write()?;
+        let removed = {
+            let mut table = write_transaction.open_table(STATE)?;
+            let mut manifest = write_transaction.open_table(MANIFEST)?;
+            let listed = manifest.remove(key)?.is_some();
+            table.remove(key)?.is_some() || listed
+        };
+        write_transaction.commit()?;
+
+        Ok(removed)
+    }
+
+    /// Stores a piece of game state (e.g. item drops) in the world file.
+    pub fn store_state<T>(&self, key: &str, value: &T) -> Result<(), Error>
+    where
+        T: Serialize,
+    {
+        let value = serde_cbor::to_vec(value)?;
+        let entry = ManifestEntry {
+            checksum: crc32fast::hash(&value),
+            size: value.len() as u64,
+            time_written: Local::now(),
+        };
+
+        // the state and its checksum are written in one transaction, so they
+        // always match, unless the file is damaged afterwards
+        let write_transaction = self.database.begin_write()?;
+        {
+            let mut table = write_transaction.open_table(STATE)?;
+            table.insert(key, value)?;
+            let mut manifest = write_transaction.open_table(MANIFEST)?;
+            manifest.insert(key, serde_cbor::to_vec(&entry)?)?;
+        }
+        write_transaction.commit()?;
+
+        Ok(())
+    }
+
+    /// Checks all game state against the checksums in the manifest.
+    pub fn verify(&self) -> Result<IntegrityReport, Error> {
+        let read_transaction = self.database.begin_read()?;
+        let mut report = IntegrityReport::default();
+
+        let mut manifest = HashMap::new();
+        if let Some(table) = open_optional_table(&read_transaction, MANIFEST)? {
+            for entry in table.iter()? {
+                let (key, value) = entry?;
+                manifest.insert(
+                    key.value().to_owned(),
+                    serde_cbor::from_slice::<ManifestEntry>(&value.value()),
+                );
+            }
+        }
+
+        if let Some(table) = open_optional_table(&read_transaction, STATE)? {
+            for entry in table.iter()? {
+                let (key, value) = entry?;
+                let key = key.value();
+
+                let problem = match manifest.remove(key) {
+                    Some(Ok(entry)) => entry.check(&value.value()),
+                    Some(Err(error)) => {
+                        Some(StateProblem::DamagedManifest {
+                            error: error.to_string(),
+                        })
+                    }
+                    None => {
+                        report.unlisted.push(key.to_owned());
+                        continue;
+                    }
+                };
+
+                match problem {
+                    Some(problem) => {
+                        report.damaged.push(DamagedState {
+                            key: key.to_owned(),
+                            problem,
+                        });
+                    }
+                    None => report.num_intact += 1,
+                }
+            }
+        }
+
+        for key in manifest.into_keys() {
+            report.damaged.push(DamagedState {
+                key,
+                problem: StateProblem::Missing,
+            });
+        }
+
+        report.damaged.sort_by(|a, b| a.key.cmp(&b.key));
+        report.unlisted.sort();
+
+        Ok(report)
+    }
+
+    /// Summarizes the contents of the world file, e.g. to debug save bugs.
+    ///
+    /// Game state is only checked to be valid CBOR, since the world file
+    /// doesn't know the types stored in it.
+    pub fn info(&self) -> Result<WorldInfo, Error> {
+        let read_transaction = self.database.begin_read()?;
+
+        let mut info = WorldInfo {
+            time_created: self.metadata.time_created,
+            time_last_written: self.metadata.time_last_written,
+            world_config: self.metadata.world_config.clone(),
+            state: vec![],
+            integrity: self.verify()?,
+            unknown_tables: vec![],
+        };
+
+        for table in read_transaction.list_tables()? {
+            if ![METADATA.name(), STATE.name(), MANIFEST.name()].contains(&table.name()) {
+                info.unknown_tables.push(table.name().to_owned());
+            }
+        }
+
+        let table = match read_transaction.open_table(STATE) {
+            Ok(table) => table,
+            Err(redb::TableError::TableDoesNotExist(_)) => return Ok(info),
+            Err(error) => return Err(error.into()),
+        };
+
+        for entry in table.iter()? {
+            let (key, value) = entry?;
+            let value = value.value();
+
+            info.state.push(StateInfo {
+                key: key.value().to_owned(),
+                size: value.len(),
+                error: serde_cbor::from_slice::<serde_cbor::Value>(&value)
+                    .err()
+                    .map(|error| error.to_string()),
+            });
+        }
+
+        Ok(info)
+    }
+
+    /// Checks the integrity of the database, and repairs it if possible.
+    ///
+    /// Returns `false` if the database had to be repaired.
+    pub fn check_integrity(&mut self) -> Result<bool, Error> {
+        Ok(self.database.check_integrity()?)
+    }
+
+    /// Frees unused space in the file.
+    pub fn compact(&mut self) -> Result<(), Error> {
+        self.database.compact()?;
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct WorldInfo {
+    pub time_created: DateTime<Local>,
+    pub time_last_written: DateTime<Local>,
+    pub world_config: WorldConfig,
+    pub state: Vec<StateInfo>,
+    pub integrity: IntegrityReport,
+
+    /// Tables that the game doesn't use, e.g. from a newer version.
+    pub unknown_tables: Vec<String>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct StateInfo {
+    pub key: String,
+
+    /// Size in bytes.
+    pub size: usize,
+
+    /// Set if the value can't be decoded.
+    pub error: Option<String>,
+}
+
+/// Result of checking the game state in a world file against its checksums.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct IntegrityReport {
+    /// Number of pieces of game state that match their checksum.
+    pub num_intact: usize,
+
+    pub damaged: Vec<DamagedState>,
+
+    /// Game state without a checksum, e.g. because it was written by an older
+    /// version. It gets one the next time it's stored.
+    pub unlisted: Vec<String>,
+}
+
+impl IntegrityReport {
+    pub fn is_intact(&self) -> bool {
+        self.damaged.is_empty()
+    }
+}
+
+#[derive(Clone, Debug, Serialize, thiserror::Error)]
+#[error("game state `{key}` is damaged: {problem}")]
+pub struct DamagedState {
+    pub key: String,
+    pub problem: StateProblem,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, thiserror::Error)]
+pub enum StateProblem {
+    #[error("checksum is {actual:08x}, but {expected:08x} was stored")]
+    ChecksumMismatch { expected: u32, actual: u32 },
+
+    #[error("size is {actual} bytes, but {expected} bytes were stored")]
+    SizeMismatch { expected: u64, actual: u64 },
+
+    #[error("listed in the manifest, but missing")]
+    Missing,
+
+    #[error("manifest entry can't be decoded: {error}")]
+    DamagedManifest { error: String },
 }
 
 const METADATA: TableDefinition<(), Vec<u8>> = TableDefinition::new("metadata");
+const STATE: TableDefinition<&str, Vec<u8>> = TableDefinition::new("state");
+
+/// Checksums of the game state, with the same keys.
+const MANIFEST: TableDefinition<&str, Vec<u8>> = TableDefinition::new("manifest");
+
+/// Opens a table that doesn't exist until something was written to it.
+fn open_optional_table<K, V>(
+    read_transaction: &ReadTransaction,
+    definition: TableDefinition<K, V>,
+) -> Result<Option<ReadOnlyTable<K, V>>, Error>
+where
+    K: Key + 'static,
+    V: Value + 'static,
+{
+    match read_transaction.open_table(definition) {
+        Ok(table) => Ok(Some(table)),
+        Err(redb::TableError::TableDoesNotExist(_)) => Ok(None),
+        Err(error) => Err(error.into()),
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ManifestEntry {
+    /// CRC32 of the encoded state.
+    checksum: u32,
+
+    size: u64,
+    time_written: DateTime<Local>,
+}
+
+impl ManifestEntry {
+    fn check(&self, value: &[u8]) -> Option<StateProblem> {
+        let size = value.len() as u64;
+        if size != self.size {
+            return Some(StateProblem::SizeMismatch {
+                expected: self.size,
+                actual: size,
+            });
+        }
+
+        let checksum = crc32fast::hash(value);
+        (checksum != self.checksum).then_some(StateProblem::ChecksumMismatch {
+            expected: self.checksum,
+            actual: checksum,
+        })
+    }
+}
 
 #[derive(Debug, Serialize, Deserialize)]
 struct Metadata {
@@ -78,3 +422,57 @@ struct Metadata {
     time_last_written: DateTime<Local>,
     world_config: WorldConfig,
 }
+
+#[cfg(test)]
+mod tests {
+    use redb::{
+        Database,
+        ReadableTable,
+        backends::InMemoryBackend,
+    };
+
+    use crate::game::file::{
+        STATE,
+        StateProblem,
+        WorldFile,
+    };
+
+    #[test]
+    fn it_reports_damaged_state() {
+        let database = Database::builder()
+            .create_with_backend(InMemoryBackend::new())
+            .unwrap();
+        let world_file = WorldFile::create_in(database, Default::default()).unwrap();
+
+        world_file.store_state("intact", &vec![1, 2, 3]).unwrap();
+        world_file.store_state("damaged", &vec![4, 5, 6]).unwrap();
+
+        // flip a bit behind the world file's back
+        let write_transaction = world_file.database.begin_write().unwrap();
+        {
+            let mut table = write_transaction.open_table(STATE).unwrap();
+            let mut value = table.get("damaged").unwrap().unwrap().value();
+            value[1] ^= 1;
+            table.insert("damaged", value).unwrap();
+        }
+        write_transaction.commit().unwrap();
+
+        let report = world_file.verify().unwrap();
+        assert_eq!(report.num_intact, 1);
+        assert_eq!(report.damaged.len(), 1);
+        assert_eq!(report.damaged[0].key, "damaged");
+        assert!(matches!(
+            report.damaged[0].problem,
+            StateProblem::ChecksumMismatch { .. }
+        ));
+
+        assert_eq!(
+            world_file.load_state::<Vec<i32>>("intact").unwrap(),
+            Some(vec![1, 2, 3])
+        );
+        assert!(world_file.load_state::<Vec<i32>>("damaged").is_err());
+
+        world_file.remove_state("damaged").unwrap();
+        assert!(world_file.verify().unwrap().is_intact());
+    }
+}
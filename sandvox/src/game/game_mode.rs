@@ -0,0 +1,164 @@
+//! Creative and survival game modes.
+//!
+//! In creative mode players fly, break blocks instantly and have infinite
+//! items, so broken blocks don't drop items. In survival mode players walk with
+//! [physics][crate::game::player_physics], breaking blocks takes time and their
+//! inventory can only hold [`SURVIVAL_STACK_LIMIT`] items of each block type.
+//!
+//! Systems that only apply to one game mode are gated with [`in_game_mode`].
+//! The player's game mode is stored in the world file.
+
+use bevy_ecs::{
+    component::Component,
+    entity::Entity,
+    query::{
+        Changed,
+        With,
+        Without,
+    },
+    schedule::{
+        IntoScheduleConfigs,
+        common_conditions::resource_exists,
+    },
+    system::{
+        Commands,
+        Populated,
+        Query,
+        Res,
+        Single,
+    },
+};
+use color_eyre::eyre::Error;
+use serde::{
+    Deserialize,
+    Serialize,
+};
+
+use crate::{
+    ecs::{
+        plugin::{
+            Dependencies,
+            Plugin,
+            WorldBuilder,
+        },
+        schedule,
+    },
+    game::{
+        Player,
+        camera_controller::Walking,
+        file::WorldFile,
+        inventory::Inventory,
+        player_physics::{
+            PlayerPhysics,
+            PlayerPhysicsPlugin,
+        },
+    },
+};
+
+/// Key under which the player's game mode is stored in the world file.
+const WORLD_FILE_KEY: &str = "game_mode";
+
+/// Maximum number of items of each block type in survival mode.
+pub const SURVIVAL_STACK_LIMIT: u32 = 64;
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct GameModePlugin;
+
+impl Plugin for GameModePlugin {
+    fn dependencies(&self, dependencies: &mut Dependencies) -> Result<(), Error> {
+        dependencies.require_plugin::<PlayerPhysicsPlugin>();
+        Ok(())
+    }
+
+    fn setup(&self, builder: &mut WorldBuilder) -> Result<(), Error> {
+        builder
+            .add_systems(schedule::Update, (load_game_mode, apply_game_mode).chain())
+            .add_systems(
+                schedule::Shutdown,
+                save_game_mode.run_if(resource_exists::<WorldFile>),
+            );
+
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Component, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GameMode {
+    #[default]
+    Creative,
+    Survival,
+}
+
+/// Run condition that is true if the player is in the game mode.
+pub fn in_game_mode(
+    game_mode: GameMode,
+) -> impl FnMut(Option<Single<&GameMode, With<Player>>>) -> bool + Clone {
+    move |player: Option<Single<&GameMode, With<Player>>>| {
+        player.is_some_and(|player| **player == game_mode)
+    }
+}
+
+/// Gives new players the game mode from the world file.
+fn load_game_mode(
+    players: Populated<Entity, (With<Player>, Without<GameMode>)>,
+    world_file: Option<Res<WorldFile>>,
+    mut commands: Commands,
+) {
+    let game_mode = world_file.map_or(Ok(None), |world_file| {
+        world_file.load_state::<GameMode>(WORLD_FILE_KEY)
+    });
+
+    let game_mode = match game_mode {
+        Ok(game_mode) => game_mode.unwrap_or_default(),
+        Err(error) => {
+            tracing::error!(?error, "could not load game mode");
+            GameMode::default()
+        }
+    };
+
+    for player in players {
+        tracing::debug!(%player, ?game_mode, "loading game mode");
+        commands.entity(player).insert(game_mode);
+    }
+}
+
+fn apply_game_mode(
+    players: Query<(Entity, &GameMode, Option<&mut Inventory>), Changed<GameMode>>,
+    mut commands: Commands,
+) {
+    for (player, game_mode, inventory) in players {
+        tracing::info!(%player, ?game_mode, "game mode changed");
+
+        match game_mode {
+            GameMode::Creative => {
+                commands.entity(player).remove::<(Walking, PlayerPhysics)>();
+            }
+            GameMode::Survival => {
+                commands
+                    .entity(player)
+                    .insert((Walking::default(), PlayerPhysics::default()));
+            }
+        }
+
+        if let Some(mut inventory) = inventory {
+            inventory.set_limit(match game_mode {
+                GameMode::Creative => None,
+                GameMode::Survival => Some(SURVIVAL_STACK_LIMIT),
+            });
+        }
+    }
+}
+
+fn save_game_mode(world_file: Res<WorldFile>, player: Option<Single<&GameMode, With<Player>>>) {
+    let Some(game_mode) = player
+    else {
+        return;
+    };
+
+    tracing::debug!(game_mode = ?*game_mode, "saving game mode");
+
+    if let Err(error) = world_file.store_state(WORLD_FILE_KEY, &**game_mode) {
+        tracing::error!(?error, "could not save game mode");
+    }
+}
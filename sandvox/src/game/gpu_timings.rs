@@ -0,0 +1,287 @@
+use std::fmt::Write;
+
+use bevy_ecs::{
+    component::Component,
+    entity::Entity,
+    hierarchy::ChildOf,
+    name::Name,
+    query::{
+        Changed,
+        With,
+    },
+    schedule::{
+        IntoScheduleConfigs,
+        SystemCondition,
+        common_conditions::{
+            any_with_component,
+            resource_changed,
+        },
+    },
+    system::{
+        Commands,
+        Populated,
+        Query,
+        Res,
+        Single,
+    },
+};
+use color_eyre::eyre::Error;
+use taffy::prelude::{
+    TaffyAuto,
+    TaffyZero,
+};
+use winit::keyboard::KeyCode;
+
+use crate::{
+    ecs::{
+        plugin::{
+            Plugin,
+            WorldBuilder,
+        },
+        schedule,
+    },
+    input::Keys,
+    profiler::wgpu::GpuTimings,
+    render::{
+        fps_counter::FpsCounter,
+        text::{
+            Text,
+            TextSize,
+        },
+    },
+    ui::{
+        Background,
+        BarGraph,
+        PaletteRole,
+        Sprites,
+        Style,
+        ThemeColor,
+        ThemeConfig,
+        View,
+    },
+};
+
+/// Key that toggles the GPU timings overlay.
+const TOGGLE_KEY: KeyCode = KeyCode::F8;
+
+const PIXEL_SIZE: f32 = 2.0;
+
+/// Size of the graph in logical pixels.
+const GRAPH_SIZE: [f32; 2] = [240.0, 60.0];
+
+/// Shows the smoothed GPU time of each render pass and a graph of the last
+/// frames.
+///
+/// The timings are only available if the GPU is profiled, either with a
+/// profiler or with `graphics.gpu_timings` enabled.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct GpuTimingsOverlayPlugin;
+
+impl Plugin for GpuTimingsOverlayPlugin {
+    fn setup(&self, builder: &mut WorldBuilder) -> Result<(), Error> {
+        builder.add_systems(
+            schedule::Render,
+            (
+                toggle_overlay,
+                update_overlay.run_if(
+                    resource_changed::<FpsCounter>.and(any_with_component::<GpuTimingsOverlay>),
+                ),
+            ),
+        );
+
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, Debug, Component)]
+struct GpuTimingsOverlay;
+
+#[derive(Clone, Copy, Debug, Component)]
+struct GpuTimingsGraph;
+
+/// Legend entry for the render pass with this index.
+#[derive(Clone, Copy, Debug, Component)]
+struct GpuTimingsLegend(usize);
+
+fn toggle_overlay(
+    keys: Populated<&Keys, Changed<Keys>>,
+    overlay: Option<Single<Entity, With<GpuTimingsOverlay>>>,
+    view: Single<Entity, With<View>>,
+    sprites: Res<Sprites>,
+    gpu_timings: Option<Res<GpuTimings>>,
+    theme: Res<ThemeConfig>,
+    mut commands: Commands,
+) {
+    if !keys
+        .iter()
+        .any(|keys| keys.just_pressed.contains(&TOGGLE_KEY))
+    {
+        return;
+    }
+
+    if let Some(overlay) = overlay {
+        tracing::debug!("hide gpu timings");
+        commands.entity(*overlay).despawn();
+        return;
+    }
+
+    tracing::debug!("show gpu timings");
+
+    let sprite = &sprites["panel"];
+    let mut style = Style::default();
+    style.display = taffy::style::Display::Flex;
+    style.flex_direction = taffy::style::FlexDirection::Column;
+    style.position = taffy::Position::Absolute;
+    style.inset = taffy::Rect {
+        left: taffy::LengthPercentageAuto::AUTO,
+        right: taffy::LengthPercentageAuto::ZERO,
+        top: taffy::LengthPercentageAuto::ZERO,
+        bottom: taffy::LengthPercentageAuto::AUTO,
+    };
+    if let Some(padding) = sprite.padding(PIXEL_SIZE) {
+        style.padding = padding;
+    }
+
+    let text_size = TextSize {
+        scaling: PIXEL_SIZE,
+    };
+
+    commands
+        .spawn((
+            Name::new("gpu_timings"),
+            style,
+            Background {
+                sprite: sprite.clone(),
+                pixel_size: PIXEL_SIZE,
+            },
+            GpuTimingsOverlay,
+            ChildOf(*view),
+        ))
+        .with_children(|panel| {
+            if gpu_timings.is_some() {
+                let mut style = Style::default();
+                style.size = taffy::Size::from_lengths(
+                    GRAPH_SIZE[0] * PIXEL_SIZE,
+                    GRAPH_SIZE[1] * PIXEL_SIZE,
+                );
+
+                panel.spawn((
+                    Name::new("gpu_timings_graph"),
+                    style,
+                    BarGraph {
+                        // the passes are colored by their index
+                        colors: (0..theme.palette.palette().series.len())
+                            .map(|index| theme.color(PaletteRole::Series(index)))
+                            .collect(),
+                        ..Default::default()
+                    },
+                    GpuTimingsGraph,
+                ));
+            }
+            else {
+                panel.spawn((
+                    Name::new("gpu_timings_disabled"),
+                    Text::from("GPU TIMINGS DISABLED".to_owned()),
+                    text_size,
+                    ThemeColor::from(PaletteRole::Text),
+                    Style::default(),
+                ));
+            }
+        });
+}
+
+fn update_overlay(
+    gpu_timings: Option<Res<GpuTimings>>,
+    overlay: Single<Entity, With<GpuTimingsOverlay>>,
+    mut graph: Single<&mut BarGraph, With<GpuTimingsGraph>>,
+    mut legend: Query<(&GpuTimingsLegend, &mut Text)>,
+    mut commands: Commands,
+) {
+    let Some(gpu_timings) = gpu_timings
+    else {
+        return;
+    };
+
+    let timings = gpu_timings.snapshot();
+    let passes = timings
+        .iter()
+        .filter(|timing| timing.pass.is_none())
+        .collect::<Vec<_>>();
+
+    // one bar per frame, with one segment per pass. the histories of the passes
+    // are aligned at the most recent frame.
+    let num_bars = passes
+        .iter()
+        .map(|pass| pass.history.len())
+        .max()
+        .unwrap_or_default();
+
+    let graph = &mut **graph;
+    graph.bars.clear();
+    graph.bars.extend((0..num_bars).map(|i| {
+        passes
+            .iter()
+            .map(|pass| {
+                (i + pass.history.len())
+                    .checked_sub(num_bars)
+                    .map_or(0.0, |index| pass.history[index])
+            })
+            .collect::<Vec<_>>()
+    }));
+
+    // scale to the slowest frame, but at least 1 ms
+    graph.max_value = graph
+        .bars
+        .iter()
+        .map(|bar| bar.iter().sum::<f32>())
+        .fold(1.0, f32::max);
+
+    // update legend
+    let mut legend_texts = vec![String::new(); passes.len()];
+    for (index, pass) in passes.iter().enumerate() {
+        let text = &mut legend_texts[index];
+        write!(
+            text,
+            "{}: {:.2}MS",
+            pass.label.to_uppercase(),
+            pass.smoothed_ms
+        )
+        .unwrap();
+
+        for span in timings
+            .iter()
+            .filter(|timing| timing.pass == Some(pass.label))
+        {
+            write!(
+                text,
+                "\n  {}: {:.2}MS",
+                span.label.to_uppercase(),
+                span.smoothed_ms
+            )
+            .unwrap();
+        }
+    }
+
+    for (entry, mut text) in &mut legend {
+        if let Some(legend_text) = legend_texts.get_mut(entry.0) {
+            text.text = std::mem::take(legend_text);
+        }
+    }
+
+    // spawn legend entries for new passes
+    for (index, text) in legend_texts.into_iter().enumerate() {
+        if !text.is_empty() {
+            commands.spawn((
+                Name::new(format!("gpu_timings_legend_{index}")),
+                Text::from(text),
+                TextSize {
+                    scaling: PIXEL_SIZE,
+                },
+                ThemeColor::from(PaletteRole::Series(index)),
+                Style::default(),
+                GpuTimingsLegend(index),
+                ChildOf(*overlay),
+            ));
+        }
+    }
+}
@@ -0,0 +1,729 @@
+use bevy_ecs::{
+    change_detection::DetectChangesMut,
+    component::Component,
+    entity::Entity,
+    hierarchy::ChildOf,
+    message::{
+        Message,
+        MessageWriter,
+    },
+    name::Name,
+    query::With,
+    resource::Resource,
+    schedule::{
+        IntoScheduleConfigs,
+        SystemCondition,
+        common_conditions::{
+            any_with_component,
+            not,
+            resource_changed,
+            resource_exists,
+        },
+    },
+    system::{
+        Commands,
+        Query,
+        Res,
+        ResMut,
+        Single,
+        SystemParam,
+    },
+};
+use color_eyre::eyre::Error;
+use nalgebra::{
+    Point3,
+    Vector2,
+    Vector3,
+};
+use palette::{
+    Srgba,
+    WithAlpha,
+};
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use winit::keyboard::KeyCode;
+
+use crate::{
+    app::GrabCursor,
+    ecs::{
+        plugin::{
+            Dependencies,
+            Plugin,
+            WorldBuilder,
+        },
+        schedule,
+        transform::{
+            GlobalTransform,
+            LocalTransform,
+        },
+    },
+    game::{
+        ChunkShape,
+        Player,
+        block_type::{
+            BlockType,
+            BlockTypes,
+        },
+        edit_history::EditHistory,
+        game_mode::{
+            GameMode,
+            GameModePlugin,
+            in_game_mode,
+        },
+        terrain::TerrainVoxel,
+        validation::ActionValidator,
+    },
+    input::{
+        InputSystems,
+        Keys,
+        MouseButton,
+        MouseButtons,
+    },
+    render::{
+        outline::{
+            Outline,
+            OutlinePlugin,
+        },
+        render_target::RenderTarget,
+        text::{
+            Text,
+            TextShadow,
+            TextSize,
+        },
+    },
+    ui::{
+        Background,
+        LayoutCache,
+        PaletteRole,
+        Sprites,
+        Style,
+        ThemeColor,
+    },
+    voxel::{
+        access::Voxels,
+        raycast::{
+            RaycastHit,
+            raycast,
+        },
+    },
+};
+
+/// The outline is slightly larger than the block, so that it doesn't z-fight
+/// with the block's faces.
+const OUTLINE_SIZE: f32 = 1.005;
+
+/// Radius around an [`Interactable`] entity in which the player can target it
+/// (in blocks), if it doesn't specify one.
+const DEFAULT_INTERACTION_RADIUS: f32 = 0.5;
+
+/// Gap between the crosshair and the interaction prompt (in logical pixels).
+const PROMPT_SPACING: f32 = 8.0;
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct InteractionPlugin {
+    pub block_outline: BlockOutlineConfig,
+    pub interaction: InteractionConfig,
+}
+
+impl Plugin for InteractionPlugin {
+    fn dependencies(&self, dependencies: &mut Dependencies) -> Result<(), Error> {
+        dependencies
+            .require_plugin::<OutlinePlugin>()
+            .require_plugin::<GameModePlugin>();
+        Ok(())
+    }
+
+    fn setup(&self, builder: &mut WorldBuilder) -> Result<(), Error> {
+        builder
+            .insert_resource(self.block_outline)
+            .insert_resource(self.interaction)
+            .init_resource::<TargetedBlock>()
+            .init_resource::<TargetedInteraction>()
+            .add_message::<BlockBroken>()
+            .add_message::<Interacted>()
+            .add_systems(schedule::Startup, spawn_block_outline)
+            .add_systems(
+                schedule::Update,
+                (
+                    update_targeted_block,
+                    update_targeted_interaction,
+                    use_target,
+                    // breaking blocks in survival takes time, see `block_breaking`
+                    break_block.run_if(in_game_mode(GameMode::Creative)),
+                    update_block_outline.run_if(
+                        resource_changed::<TargetedBlock>
+                            .or(resource_changed::<BlockOutlineConfig>),
+                    ),
+                )
+                    .chain()
+                    .after(InputSystems::Update)
+                    .run_if(resource_exists::<BlockTypes>),
+            )
+            .add_systems(
+                schedule::Update,
+                (
+                    spawn_prompt_label.run_if(not(any_with_component::<PromptLabel>)),
+                    update_crosshair.run_if(
+                        resource_changed::<TargetedInteraction>
+                            .or(resource_changed::<InteractionConfig>),
+                    ),
+                )
+                    .chain()
+                    .after(update_targeted_interaction),
+            );
+
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize, Resource)]
+pub struct InteractionConfig {
+    /// Key to use the block or entity that the player is looking at.
+    #[serde(default = "default_use_key")]
+    pub use_key: KeyCode,
+
+    /// How far players in creative mode can reach to break, place and use
+    /// blocks and entities (in blocks).
+    #[serde(default = "default_creative_reach")]
+    pub creative_reach: f32,
+
+    /// How far players in survival mode can reach (in blocks).
+    #[serde(default = "default_survival_reach")]
+    pub survival_reach: f32,
+}
+
+impl InteractionConfig {
+    pub fn reach(&self, game_mode: GameMode) -> f32 {
+        match game_mode {
+            GameMode::Creative => self.creative_reach,
+            GameMode::Survival => self.survival_reach,
+        }
+    }
+}
+
+impl Default for InteractionConfig {
+    fn default() -> Self {
+        Self {
+            use_key: default_use_key(),
+            creative_reach: default_creative_reach(),
+            survival_reach: default_survival_reach(),
+        }
+    }
+}
+
+fn default_use_key() -> KeyCode {
+    KeyCode::KeyE
+}
+
+fn default_creative_reach() -> f32 {
+    5.0
+}
+
+fn default_survival_reach() -> f32 {
+    4.5
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize, Resource)]
+pub struct BlockOutlineConfig {
+    /// Whether to outline the block that the player is looking at.
+    #[serde(default = "default_outline_enabled")]
+    pub enabled: bool,
+
+    #[serde(default = "default_outline_color")]
+    pub color: Srgba<f32>,
+}
+
+impl Default for BlockOutlineConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_outline_enabled(),
+            color: default_outline_color(),
+        }
+    }
+}
+
+fn default_outline_enabled() -> bool {
+    true
+}
+
+fn default_outline_color() -> Srgba<f32> {
+    palette::named::BLACK.into_format().with_alpha(0.5)
+}
+
+/// How far entities can reach, depending on their game mode.
+///
+/// The player's targets are limited by this, and actions that other entities
+/// ask for, e.g. through [`Interacted`] messages, should be checked with it
+/// before they're applied. Entities without a [`GameMode`] have the reach of
+/// the default game mode.
+#[derive(SystemParam)]
+pub struct Reach<'w, 's> {
+    config: Res<'w, InteractionConfig>,
+    entities: Query<'w, 's, (&'static GlobalTransform, Option<&'static GameMode>)>,
+}
+
+impl<'w, 's> Reach<'w, 's> {
+    /// How far the entity can reach (in blocks).
+    pub fn of(&self, entity: Entity) -> f32 {
+        let game_mode = self
+            .entities
+            .get(entity)
+            .ok()
+            .and_then(|(_, game_mode)| game_mode.copied())
+            .unwrap_or_default();
+        self.config.reach(game_mode)
+    }
+
+    /// Distance from the entity to the closest point of the block, or `None`
+    /// if the entity has no transform.
+    pub fn distance_to_block(&self, entity: Entity, position: Point3<i32>) -> Option<f32> {
+        let (transform, _) = self.entities.get(entity).ok()?;
+        Some(block_distance(&transform.position(), position))
+    }
+}
+
+/// Distance from a point to the closest point of a block.
+fn block_distance(point: &Point3<f32>, position: Point3<i32>) -> f32 {
+    let min = position.cast::<f32>();
+    let max = min + Vector3::repeat(1.0);
+    let closest = point.sup(&min).inf(&max);
+    (closest - point).norm()
+}
+
+/// The block that the player is looking at, if it's within reach.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Resource)]
+pub struct TargetedBlock(pub Option<RaycastHit>);
+
+/// An entity that the player can use, e.g. a lever or a door.
+///
+/// It can be targeted within a sphere around its position.
+#[derive(Clone, Debug, Component)]
+pub struct Interactable {
+    /// What using the entity does, e.g. "open".
+    pub prompt: String,
+
+    pub radius: f32,
+}
+
+impl Interactable {
+    pub fn new(prompt: impl Into<String>) -> Self {
+        Self {
+            prompt: prompt.into(),
+            radius: DEFAULT_INTERACTION_RADIUS,
+        }
+    }
+}
+
+/// The block or entity that the player would use with the
+/// [use key][InteractionConfig::use_key].
+#[derive(Clone, Debug, Default, PartialEq, Resource)]
+pub struct TargetedInteraction(pub Option<InteractionPrompt>);
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct InteractionPrompt {
+    pub target: InteractionTarget,
+
+    /// What using the target does, e.g. "open".
+    pub prompt: String,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum InteractionTarget {
+    Block(Point3<i32>),
+    Entity(Entity),
+}
+
+/// Marks the crosshair, which changes when the player looks at something they
+/// can use.
+#[derive(Clone, Copy, Debug, Component)]
+pub struct Crosshair;
+
+/// The "E to open" label next to the [`Crosshair`].
+#[derive(Clone, Copy, Debug, Component)]
+struct PromptLabel;
+
+/// Marks the entity that outlines the [`TargetedBlock`].
+#[derive(Clone, Copy, Debug, Component)]
+struct BlockOutline;
+
+/// Sent when an entity used the block or entity it was targeting.
+#[derive(Clone, Copy, Debug, Message)]
+pub struct Interacted {
+    pub target: InteractionTarget,
+    pub used_by: Entity,
+}
+
+/// Sent when a block was removed from the world by an entity.
+#[derive(Clone, Copy, Debug, Message)]
+pub struct BlockBroken {
+    pub position: Point3<i32>,
+    pub block_type: BlockType,
+    pub broken_by: Entity,
+}
+
+fn update_targeted_block(
+    player: Single<(Entity, &GlobalTransform), With<Player>>,
+    reach: Reach,
+    block_types: Res<BlockTypes>,
+    voxels: Voxels<TerrainVoxel, ChunkShape>,
+    mut targeted_block: ResMut<TargetedBlock>,
+) {
+    let (player_entity, transform) = *player;
+    let air = block_types.lookup("air").unwrap();
+
+    let hit = raycast(
+        transform.position(),
+        transform.isometry * Vector3::z(),
+        reach.of(player_entity),
+        |position| {
+            voxels
+                .get(position)
+                .is_some_and(|voxel| voxel.block_type != air)
+        },
+    );
+
+    // only trigger change detection if the target actually changed
+    targeted_block.set_if_neq(TargetedBlock(hit));
+}
+
+fn break_block(
+    player: Single<(Entity, &RenderTarget), With<Player>>,
+    windows: Query<&MouseButtons, With<GrabCursor>>,
+    targeted_block: Res<TargetedBlock>,
+    mut editor: BlockEditor,
+) {
+    let (player_entity, render_target) = *player;
+
+    let Ok(mouse_buttons) = windows.get(render_target.0)
+    else {
+        return;
+    };
+
+    if !mouse_buttons.just_pressed(MouseButton::Left) {
+        return;
+    }
+
+    if let Some(hit) = targeted_block.0 {
+        editor.break_block(hit.block, player_entity);
+    }
+}
+
+/// Access to the blocks for edits that entities ask for, e.g. by breaking a
+/// block.
+#[derive(SystemParam)]
+pub(super) struct BlockEditor<'w, 's> {
+    pub validator: ActionValidator<'w, 's>,
+    pub voxels: Voxels<'w, 's, TerrainVoxel, ChunkShape>,
+    pub block_types: Res<'w, BlockTypes>,
+    histories: Query<'w, 's, &'static mut EditHistory>,
+    block_broken: MessageWriter<'w, BlockBroken>,
+}
+
+impl BlockEditor<'_, '_> {
+    /// Replaces a block with air, if the entity is allowed to. The block is
+    /// recorded in the entity's edit history and [`BlockBroken`] is sent.
+    pub fn break_block(&mut self, position: Point3<i32>, broken_by: Entity) {
+        if self.validator.check_edit(broken_by, position).is_err() {
+            return;
+        }
+
+        let air = self.block_types.lookup("air").unwrap();
+
+        if let Some(voxel) = self.voxels.set(position, TerrainVoxel { block_type: air }) {
+            tracing::debug!(?position, block_type = ?voxel.block_type, "block broken");
+
+            if let Ok(mut history) = self.histories.get_mut(broken_by) {
+                history.push(vec![(position, voxel)]);
+            }
+
+            self.block_broken.write(BlockBroken {
+                position,
+                block_type: voxel.block_type,
+                broken_by,
+            });
+        }
+    }
+}
+
+fn spawn_block_outline(mut commands: Commands) {
+    commands.spawn((
+        Name::new("block outline"),
+        BlockOutline,
+        GlobalTransform::identity(),
+    ));
+}
+
+fn update_block_outline(
+    outline: Single<(Entity, &mut GlobalTransform), With<BlockOutline>>,
+    targeted_block: Res<TargetedBlock>,
+    config: Res<BlockOutlineConfig>,
+    mut commands: Commands,
+) {
+    let (entity, mut transform) = outline.into_inner();
+
+    if let Some(hit) = targeted_block.0
+        && config.enabled
+    {
+        let center = hit.block.cast::<f32>() + Vector3::repeat(0.5);
+        *transform = LocalTransform::from(center).into();
+
+        commands.entity(entity).insert(Outline {
+            size: Vector3::repeat(OUTLINE_SIZE),
+            color: config.color,
+            fill: None,
+        });
+    }
+    else {
+        commands.entity(entity).remove::<Outline>();
+    }
+}
+
+/// Finds what the player would use: the closest [`Interactable`] entity in
+/// front of the [`TargetedBlock`], or the targeted block itself if it has a
+/// prompt.
+fn update_targeted_interaction(
+    player: Single<(Entity, &GlobalTransform), With<Player>>,
+    reach: Reach,
+    block_types: Res<BlockTypes>,
+    voxels: Voxels<TerrainVoxel, ChunkShape>,
+    targeted_block: Res<TargetedBlock>,
+    interactables: Query<(Entity, &GlobalTransform, &Interactable)>,
+    mut targeted_interaction: ResMut<TargetedInteraction>,
+) {
+    let (player_entity, transform) = *player;
+    let origin = transform.position();
+    let direction = transform.isometry * Vector3::z();
+    let max_distance = targeted_block
+        .0
+        .map_or_else(|| reach.of(player_entity), |hit| hit.distance);
+
+    let entity = interactables
+        .iter()
+        .filter_map(|(entity, transform, interactable)| {
+            let distance = ray_sphere_distance(
+                &origin,
+                &direction,
+                &transform.position(),
+                interactable.radius,
+            )?;
+            (distance <= max_distance).then_some((distance, entity, interactable))
+        })
+        .min_by(|(a, ..), (b, ..)| a.total_cmp(b));
+
+    let interaction = if let Some((_, entity, interactable)) = entity {
+        Some(InteractionPrompt {
+            target: InteractionTarget::Entity(entity),
+            prompt: interactable.prompt.clone(),
+        })
+    }
+    else {
+        targeted_block.0.and_then(|hit| {
+            let voxel = voxels.get(hit.block)?;
+            let prompt = block_types[voxel.block_type].prompt.clone()?;
+            Some(InteractionPrompt {
+                target: InteractionTarget::Block(hit.block),
+                prompt,
+            })
+        })
+    };
+
+    targeted_interaction.set_if_neq(TargetedInteraction(interaction));
+}
+
+pub fn use_target(
+    player: Single<(Entity, &RenderTarget), With<Player>>,
+    windows: Query<&Keys, With<GrabCursor>>,
+    config: Res<InteractionConfig>,
+    targeted_interaction: Res<TargetedInteraction>,
+    mut interacted: MessageWriter<Interacted>,
+) {
+    let (player_entity, render_target) = *player;
+
+    let Ok(keys) = windows.get(render_target.0)
+    else {
+        return;
+    };
+
+    if !keys.just_pressed.contains(&config.use_key) {
+        return;
+    }
+
+    if let Some(interaction) = &targeted_interaction.0 {
+        tracing::debug!(target = ?interaction.target, "used target");
+
+        interacted.write(Interacted {
+            target: interaction.target,
+            used_by: player_entity,
+        });
+    }
+}
+
+/// Distance along a ray until it hits a sphere, or 0 if it starts inside the
+/// sphere.
+///
+/// `direction` must be normalized.
+fn ray_sphere_distance(
+    origin: &Point3<f32>,
+    direction: &Vector3<f32>,
+    center: &Point3<f32>,
+    radius: f32,
+) -> Option<f32> {
+    let to_center = center - origin;
+    let closest = to_center.dot(direction);
+    let distance_squared = to_center.norm_squared() - closest * closest;
+
+    let radius_squared = radius * radius;
+    if distance_squared > radius_squared {
+        return None;
+    }
+
+    let half_chord = (radius_squared - distance_squared).sqrt();
+    if closest + half_chord < 0.0 {
+        // the sphere is behind the origin
+        None
+    }
+    else {
+        Some((closest - half_chord).max(0.0))
+    }
+}
+
+fn spawn_prompt_label(crosshair: Single<Entity, With<Crosshair>>, mut commands: Commands) {
+    let pixel_size = 2.0;
+
+    let mut style = Style::default();
+    style.position = taffy::Position::Absolute;
+    style.inset.left = taffy::LengthPercentageAuto::percent(1.0);
+    style.margin.left = taffy::LengthPercentageAuto::length(PROMPT_SPACING);
+    // hidden until the player looks at something
+    style.display = taffy::Display::None;
+
+    commands.spawn((
+        Name::new("interaction_prompt"),
+        Text::default(),
+        TextSize {
+            scaling: pixel_size,
+        },
+        ThemeColor::from(PaletteRole::Text),
+        TextShadow {
+            color: palette::named::BLACK.into_format().with_alpha(0.8),
+            offset: Vector2::new(1.0, 1.0),
+        },
+        style,
+        PromptLabel,
+        ChildOf(*crosshair),
+    ));
+}
+
+/// Switches the crosshair's sprite and shows the prompt while the player looks
+/// at something they can use.
+fn update_crosshair(
+    targeted_interaction: Res<TargetedInteraction>,
+    config: Res<InteractionConfig>,
+    sprites: Res<Sprites>,
+    mut crosshair: Single<&mut Background, With<Crosshair>>,
+    mut label_text: Single<&mut Text, With<PromptLabel>>,
+    label_layout: Single<(&mut Style, &mut LayoutCache), With<PromptLabel>>,
+) {
+    let (mut style, mut layout_cache) = label_layout.into_inner();
+
+    let (sprite, prompt) = if let Some(interaction) = &targeted_interaction.0 {
+        (
+            sprites
+                .lookup("crosshair_interact")
+                .unwrap_or_else(|| sprites.lookup("crosshair").unwrap()),
+            Some(format!(
+                "{} to {}",
+                key_label(config.use_key),
+                interaction.prompt
+            )),
+        )
+    }
+    else {
+        (sprites.lookup("crosshair").unwrap(), None)
+    };
+
+    crosshair.sprite = sprites[sprite].clone();
+
+    let display = if prompt.is_some() {
+        taffy::Display::Block
+    }
+    else {
+        taffy::Display::None
+    };
+    if let Some(prompt) = prompt {
+        label_text.text = prompt;
+    }
+
+    // only touch the style if it changes, since this triggers a new layout
+    if style.display != display {
+        style.display = display;
+        layout_cache.clear();
+    }
+}
+
+/// A short name for a key, e.g. "E" for [`KeyCode::KeyE`].
+fn key_label(key: KeyCode) -> String {
+    let name = format!("{key:?}");
+
+    ["Key", "Digit"]
+        .iter()
+        .find_map(|prefix| name.strip_prefix(prefix))
+        .unwrap_or(&name)
+        .to_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::{
+        Point3,
+        Vector3,
+    };
+    use winit::keyboard::KeyCode;
+
+    use crate::game::interaction::{
+        block_distance,
+        key_label,
+        ray_sphere_distance,
+    };
+
+    #[test]
+    fn it_hits_spheres_in_front_of_the_ray() {
+        let origin = Point3::origin();
+        let direction = Vector3::z();
+
+        assert_eq!(
+            ray_sphere_distance(&origin, &direction, &Point3::new(0.0, 0.0, 3.0), 0.5),
+            Some(2.5)
+        );
+        assert_eq!(
+            ray_sphere_distance(&origin, &direction, &Point3::new(0.0, 0.0, 0.2), 0.5),
+            Some(0.0)
+        );
+        assert_eq!(
+            ray_sphere_distance(&origin, &direction, &Point3::new(0.0, 0.0, -3.0), 0.5),
+            None
+        );
+        assert_eq!(
+            ray_sphere_distance(&origin, &direction, &Point3::new(1.0, 0.0, 3.0), 0.5),
+            None
+        );
+    }
+
+    #[test]
+    fn it_measures_the_distance_to_blocks() {
+        let block = Point3::new(2, 0, 0);
+
+        assert_eq!(block_distance(&Point3::new(2.5, 0.5, 0.5), block), 0.0);
+        assert_eq!(block_distance(&Point3::new(0.0, 0.5, 0.5), block), 2.0);
+        assert_eq!(block_distance(&Point3::new(6.0, 5.0, 0.5), block), 5.0);
+    }
+
+    #[test]
+    fn it_labels_keys() {
+        assert_eq!(key_label(KeyCode::KeyE), "E");
+        assert_eq!(key_label(KeyCode::Digit1), "1");
+        assert_eq!(key_label(KeyCode::Space), "Space");
+    }
+}
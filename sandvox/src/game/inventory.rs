@@ -0,0 +1,96 @@
+use std::collections::BTreeMap;
+
+use bevy_ecs::component::Component;
+
+use crate::game::block_type::BlockType;
+
+/// Items held by an entity (e.g. the player).
+///
+/// For now items are just blocks.
+#[derive(Clone, Debug, Default, Component)]
+pub struct Inventory {
+    items: BTreeMap<BlockType, u32>,
+
+    /// Maximum number of items of each block type, or `None` if unlimited.
+    limit: Option<u32>,
+}
+
+impl Inventory {
+    /// Inserts up to `count` items, as many as fit, and returns how many were
+    /// inserted.
+    pub fn insert(&mut self, block_type: BlockType, count: u32) -> u32 {
+        let held = self.count(block_type);
+        let inserted = self
+            .limit
+            .map_or(count, |limit| count.min(limit.saturating_sub(held)));
+
+        if inserted > 0 {
+            self.items.insert(block_type, held + inserted);
+        }
+
+        inserted
+    }
+
+    /// Removes up to `count` items and returns how many were removed.
+    pub fn remove(&mut self, block_type: BlockType, count: u32) -> u32 {
+        if let Some(held) = self.items.get_mut(&block_type) {
+            let removed = count.min(*held);
+            *held -= removed;
+            if *held == 0 {
+                self.items.remove(&block_type);
+            }
+            removed
+        }
+        else {
+            0
+        }
+    }
+
+    pub fn count(&self, block_type: BlockType) -> u32 {
+        self.items.get(&block_type).copied().unwrap_or_default()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (BlockType, u32)> {
+        self.items
+            .iter()
+            .map(|(block_type, count)| (*block_type, *count))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    pub fn limit(&self) -> Option<u32> {
+        self.limit
+    }
+
+    /// Sets the maximum number of items of each block type. Items that are
+    /// already held are kept, even if there are more than that.
+    pub fn set_limit(&mut self, limit: Option<u32>) {
+        self.limit = limit;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::game::{
+        block_type::BlockType,
+        inventory::Inventory,
+    };
+
+    #[test]
+    fn it_inserts_up_to_the_limit() {
+        let stone = BlockType::from_usize(1);
+
+        let mut inventory = Inventory::default();
+        inventory.set_limit(Some(10));
+
+        assert_eq!(inventory.insert(stone, 6), 6);
+        assert_eq!(inventory.insert(stone, 6), 4);
+        assert_eq!(inventory.insert(stone, 1), 0);
+        assert_eq!(inventory.count(stone), 10);
+
+        inventory.set_limit(None);
+        assert_eq!(inventory.insert(stone, 100), 100);
+    }
+}
@@ -0,0 +1,378 @@
+use std::{
+    f32::consts::TAU,
+    time::{
+        Duration,
+        Instant,
+    },
+};
+
+use bevy_ecs::{
+    component::Component,
+    entity::Entity,
+    message::MessageReader,
+    name::Name,
+    query::With,
+    resource::Resource,
+    schedule::{
+        IntoScheduleConfigs,
+        SystemCondition,
+        common_conditions::{
+            resource_added,
+            resource_exists,
+        },
+    },
+    system::{
+        Commands,
+        Local,
+        Populated,
+        Query,
+        Res,
+        Single,
+    },
+};
+use color_eyre::eyre::Error;
+use nalgebra::{
+    Point3,
+    Translation3,
+    UnitQuaternion,
+    Vector3,
+};
+use serde::{
+    Deserialize,
+    Serialize,
+};
+
+use crate::{
+    app::Time,
+    ecs::{
+        plugin::{
+            Dependencies,
+            Plugin,
+            WorldBuilder,
+        },
+        schedule,
+        transform::{
+            GlobalTransform,
+            LocalTransform,
+        },
+    },
+    game::{
+        Player,
+        accessibility::{
+            AccessibilityConfig,
+            AccessibilityPlugin,
+        },
+        block_mesh::{
+            BlockMesh,
+            BlockMeshPlugin,
+        },
+        block_type::{
+            BlockType,
+            BlockTypes,
+        },
+        file::WorldFile,
+        game_mode::{
+            GameMode,
+            GameModePlugin,
+            in_game_mode,
+        },
+        interaction::BlockBroken,
+        inventory::Inventory,
+    },
+    net::interest::Replicated,
+    voxel::access::block_center,
+};
+
+/// Side length of the mini block that is rendered for an item drop.
+const ITEM_SIZE: f32 = 0.25;
+
+/// Key under which item drops are stored in the world file.
+const WORLD_FILE_KEY: &str = "item_drops";
+
+/// Item drops are saved this often while playing, and not only at shutdown,
+/// so that they aren't lost if the game is killed.
+const AUTOSAVE_INTERVAL: Duration = Duration::from_secs(60);
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ItemDropPlugin {
+    pub config: ItemDropConfig,
+}
+
+impl Plugin for ItemDropPlugin {
+    fn dependencies(&self, dependencies: &mut Dependencies) -> Result<(), Error> {
+        dependencies
+            .require_plugin::<AccessibilityPlugin>()
+            .require_plugin::<BlockMeshPlugin>()
+            .require_plugin::<GameModePlugin>();
+        Ok(())
+    }
+
+    fn setup(&self, builder: &mut WorldBuilder) -> Result<(), Error> {
+        builder
+            .insert_resource(self.config)
+            .add_systems(
+                schedule::Update,
+                (
+                    // item drops are saved by block type name, so we can only load them once the
+                    // block types are loaded.
+                    load_item_drops
+                        .run_if(resource_exists::<WorldFile>.and(resource_added::<BlockTypes>)),
+                    // players in creative mode have infinite items
+                    spawn_item_drops.run_if(in_game_mode(GameMode::Survival)),
+                    update_item_drops,
+                    pick_up_item_drops,
+                    save_item_drops.run_if(
+                        resource_exists::<WorldFile>
+                            .and(resource_exists::<BlockTypes>)
+                            .and(autosave_due),
+                    ),
+                )
+                    .chain(),
+            )
+            .add_systems(
+                schedule::Shutdown,
+                save_item_drops
+                    .run_if(resource_exists::<WorldFile>.and(resource_exists::<BlockTypes>)),
+            );
+
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Resource, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ItemDropConfig {
+    /// Seconds after which an item drop that wasn't picked up despawns.
+    #[serde(default = "default_despawn_time")]
+    pub despawn_time: f32,
+
+    /// Seconds after spawning before an item drop can be picked up.
+    #[serde(default = "default_pickup_delay")]
+    pub pickup_delay: f32,
+
+    /// Distance at which item drops start moving towards the player.
+    #[serde(default = "default_magnet_radius")]
+    pub magnet_radius: f32,
+
+    /// Distance at which item drops are collected into the inventory.
+    #[serde(default = "default_pickup_radius")]
+    pub pickup_radius: f32,
+
+    // block / second
+    #[serde(default = "default_magnet_speed")]
+    pub magnet_speed: f32,
+}
+
+fn default_despawn_time() -> f32 {
+    300.0
+}
+
+fn default_pickup_delay() -> f32 {
+    0.5
+}
+
+fn default_magnet_radius() -> f32 {
+    3.0
+}
+
+fn default_pickup_radius() -> f32 {
+    1.0
+}
+
+fn default_magnet_speed() -> f32 {
+    8.0
+}
+
+impl Default for ItemDropConfig {
+    fn default() -> Self {
+        Self {
+            despawn_time: default_despawn_time(),
+            pickup_delay: default_pickup_delay(),
+            magnet_radius: default_magnet_radius(),
+            pickup_radius: default_pickup_radius(),
+            magnet_speed: default_magnet_speed(),
+        }
+    }
+}
+
+/// An item lying around in the world, waiting to be picked up.
+#[derive(Clone, Copy, Debug, Component)]
+pub struct ItemDrop {
+    pub block_type: BlockType,
+    pub count: u32,
+
+    /// Resting position. The rendered position bobs around this.
+    pub position: Point3<f32>,
+
+    /// Seconds since the item was dropped.
+    pub age: f32,
+}
+
+impl ItemDrop {
+    pub fn spawn(self, commands: &mut Commands) -> Entity {
+        commands
+            .spawn((
+                Name::new("item_drop"),
+                LocalTransform::from(self.position.coords).with_uniform_scale(ITEM_SIZE),
+                BlockMesh(self.block_type),
+                Replicated,
+                self,
+            ))
+            .id()
+    }
+}
+
+fn spawn_item_drops(mut block_broken: MessageReader<BlockBroken>, mut commands: Commands) {
+    for message in block_broken.read() {
+        ItemDrop {
+            block_type: message.block_type,
+            count: 1,
+            position: block_center(message.position),
+            age: 0.0,
+        }
+        .spawn(&mut commands);
+    }
+}
+
+fn update_item_drops(
+    config: Res<ItemDropConfig>,
+    accessibility_config: Res<AccessibilityConfig>,
+    time: Res<Time>,
+    item_drops: Populated<(Entity, &mut ItemDrop, &mut LocalTransform)>,
+    mut commands: Commands,
+) {
+    let dt = time.delta_seconds();
+
+    for (entity, mut item_drop, mut transform) in item_drops {
+        item_drop.age += dt;
+
+        if item_drop.age > config.despawn_time {
+            tracing::trace!(?entity, "item drop despawned");
+            commands.entity(entity).despawn();
+        }
+        else {
+            // bob up and down, and slowly spin
+            let motion_scale = accessibility_config.motion_scale();
+            let bob = motion_scale * 0.1 * (item_drop.age * 2.0).sin();
+            let spin = (motion_scale * 0.5 * item_drop.age).rem_euclid(TAU);
+
+            transform.isometry.translation =
+                Translation3::from(item_drop.position.coords + Vector3::new(0.0, bob, 0.0));
+            transform.isometry.rotation = UnitQuaternion::from_axis_angle(&Vector3::y_axis(), spin);
+        }
+    }
+}
+
+fn pick_up_item_drops(
+    config: Res<ItemDropConfig>,
+    time: Res<Time>,
+    player: Single<(&GlobalTransform, &mut Inventory), With<Player>>,
+    item_drops: Populated<(Entity, &mut ItemDrop)>,
+    mut commands: Commands,
+) {
+    let (player_transform, mut inventory) = player.into_inner();
+    let player_position = player_transform.position();
+    let dt = time.delta_seconds();
+
+    for (entity, mut item_drop) in item_drops {
+        if item_drop.age < config.pickup_delay {
+            continue;
+        }
+
+        let offset = player_position - item_drop.position;
+        let distance = offset.norm();
+
+        if distance <= config.pickup_radius {
+            // items that don't fit into the inventory stay on the ground
+            let picked_up = inventory.insert(item_drop.block_type, item_drop.count);
+            if picked_up > 0 {
+                tracing::debug!(block_type = ?item_drop.block_type, count = picked_up, "picked up item");
+            }
+
+            item_drop.count -= picked_up;
+            if item_drop.count == 0 {
+                commands.entity(entity).despawn();
+            }
+        }
+        else if distance <= config.magnet_radius {
+            let step = (config.magnet_speed * dt).min(distance);
+            item_drop.position += offset * (step / distance);
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ItemDropData {
+    block_type: String,
+    count: u32,
+    position: Point3<f32>,
+    age: f32,
+}
+
+fn load_item_drops(
+    world_file: Res<WorldFile>,
+    block_types: Res<BlockTypes>,
+    mut commands: Commands,
+) {
+    let item_drops: Vec<ItemDropData> = match world_file.load_state(WORLD_FILE_KEY) {
+        Ok(item_drops) => item_drops.unwrap_or_default(),
+        Err(error) => {
+            tracing::error!(?error, "could not load item drops");
+            return;
+        }
+    };
+
+    tracing::debug!(count = item_drops.len(), "loading item drops");
+
+    for data in item_drops {
+        if let Some(block_type) = block_types.lookup(&data.block_type) {
+            ItemDrop {
+                block_type,
+                count: data.count,
+                position: data.position,
+                age: data.age,
+            }
+            .spawn(&mut commands);
+        }
+        else {
+            tracing::warn!(block_type = %data.block_type, "dropping item with unknown block type");
+        }
+    }
+}
+
+/// Run condition that is true every [`AUTOSAVE_INTERVAL`].
+fn autosave_due(time: Res<Time>, mut next_save: Local<Option<Instant>>) -> bool {
+    let next_save = next_save.get_or_insert(time.tick_start + AUTOSAVE_INTERVAL);
+
+    if time.tick_start >= *next_save {
+        *next_save = time.tick_start + AUTOSAVE_INTERVAL;
+        true
+    }
+    else {
+        false
+    }
+}
+
+fn save_item_drops(
+    world_file: Res<WorldFile>,
+    block_types: Res<BlockTypes>,
+    item_drops: Query<&ItemDrop>,
+) {
+    let item_drops = item_drops
+        .iter()
+        .map(|item_drop| {
+            ItemDropData {
+                block_type: block_types[item_drop.block_type].name.clone(),
+                count: item_drop.count,
+                position: item_drop.position,
+                age: item_drop.age,
+            }
+        })
+        .collect::<Vec<_>>();
+
+    tracing::debug!(count = item_drops.len(), "saving item drops");
+
+    if let Err(error) = world_file.store_state(WORLD_FILE_KEY, &item_drops) {
+        tracing::error!(?error, "could not save item drops");
+    }
+}
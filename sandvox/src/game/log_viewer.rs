@@ -0,0 +1,312 @@
+use bevy_ecs::{
+    change_detection::DetectChangesMut,
+    component::Component,
+    entity::Entity,
+    hierarchy::ChildOf,
+    name::Name,
+    query::{
+        Changed,
+        With,
+        Without,
+    },
+    schedule::{
+        IntoScheduleConfigs,
+        common_conditions::any_with_component,
+    },
+    system::{
+        Commands,
+        Populated,
+        Query,
+        Res,
+        Single,
+    },
+};
+use color_eyre::eyre::Error;
+use taffy::prelude::{
+    TaffyAuto,
+    TaffyZero,
+};
+use tracing::Level;
+use winit::keyboard::KeyCode;
+
+use crate::{
+    ecs::{
+        plugin::{
+            Plugin,
+            WorldBuilder,
+        },
+        schedule,
+    },
+    input::Keys,
+    logging::{
+        LogFilter,
+        num_records_logged,
+        recent_crates,
+        recent_logs_filtered,
+    },
+    render::text::{
+        Text,
+        TextSize,
+    },
+    ui::{
+        Background,
+        PaletteRole,
+        Sprites,
+        Style,
+        ThemeColor,
+        View,
+    },
+};
+
+/// Key that toggles the log viewer.
+const TOGGLE_KEY: KeyCode = KeyCode::F4;
+
+/// Cycles the minimum level of the shown records.
+const LEVEL_KEY: KeyCode = KeyCode::KeyL;
+
+/// Cycles through the crates that the shown records come from.
+const TARGET_KEY: KeyCode = KeyCode::KeyT;
+
+const PIXEL_SIZE: f32 = 2.0;
+
+/// Number of records shown at once.
+const NUM_LINES: usize = 20;
+
+/// Width of the log viewer, relative to the view.
+const WIDTH: f32 = 0.6;
+
+/// Minimum levels that [`LEVEL_KEY`] cycles through.
+const LEVELS: [Option<Level>; 4] = [
+    None,
+    Some(Level::INFO),
+    Some(Level::WARN),
+    Some(Level::ERROR),
+];
+
+/// Shows the most recent log records, so that warnings can be reported without
+/// digging through the terminal output.
+///
+/// While it's open, Page Up and Page Down scroll, End jumps back to the most
+/// recent records, and the records can be filtered by level and crate.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct LogViewerPlugin;
+
+impl Plugin for LogViewerPlugin {
+    fn setup(&self, builder: &mut WorldBuilder) -> Result<(), Error> {
+        builder.add_systems(
+            schedule::Render,
+            (
+                toggle_viewer,
+                (handle_keys, update_viewer)
+                    .chain()
+                    .run_if(any_with_component::<LogViewer>),
+            )
+                .chain(),
+        );
+
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, Default, Component)]
+struct LogViewer {
+    filter: LogFilter,
+
+    /// Number of matching records below the shown ones.
+    scroll: usize,
+
+    /// [`num_records_logged`] when the viewer was last updated.
+    num_records_logged: Option<u64>,
+}
+
+impl LogViewer {
+    fn invalidate(&mut self) {
+        self.num_records_logged = None;
+    }
+}
+
+#[derive(Clone, Copy, Debug, Component)]
+struct LogViewerHeader;
+
+/// Shows the record at this index, counted from the top.
+#[derive(Clone, Copy, Debug, Component)]
+struct LogViewerLine(usize);
+
+fn toggle_viewer(
+    keys: Populated<&Keys, Changed<Keys>>,
+    viewer: Option<Single<Entity, With<LogViewer>>>,
+    view: Single<Entity, With<View>>,
+    sprites: Res<Sprites>,
+    mut commands: Commands,
+) {
+    if !keys
+        .iter()
+        .any(|keys| keys.just_pressed.contains(&TOGGLE_KEY))
+    {
+        return;
+    }
+
+    if let Some(viewer) = viewer {
+        tracing::debug!("hide log viewer");
+        commands.entity(*viewer).despawn();
+        return;
+    }
+
+    tracing::debug!("show log viewer");
+
+    let sprite = &sprites["panel"];
+    let mut style = Style::default();
+    style.display = taffy::style::Display::Flex;
+    style.flex_direction = taffy::style::FlexDirection::Column;
+    style.position = taffy::Position::Absolute;
+    style.inset = taffy::Rect {
+        left: taffy::LengthPercentageAuto::ZERO,
+        right: taffy::LengthPercentageAuto::AUTO,
+        top: taffy::LengthPercentageAuto::AUTO,
+        bottom: taffy::LengthPercentageAuto::ZERO,
+    };
+    style.size.width = taffy::Dimension::percent(WIDTH);
+    if let Some(padding) = sprite.padding(PIXEL_SIZE) {
+        style.padding = padding;
+    }
+
+    let text_size = TextSize {
+        scaling: PIXEL_SIZE,
+    };
+
+    commands
+        .spawn((
+            Name::new("log_viewer"),
+            style,
+            Background {
+                sprite: sprite.clone(),
+                pixel_size: PIXEL_SIZE,
+            },
+            LogViewer::default(),
+            ChildOf(*view),
+        ))
+        .with_children(|panel| {
+            panel.spawn((
+                Name::new("log_viewer_header"),
+                Text::default(),
+                text_size,
+                ThemeColor::from(PaletteRole::Series(1)),
+                Style::default(),
+                LogViewerHeader,
+            ));
+
+            for index in 0..NUM_LINES {
+                panel.spawn((
+                    Name::new(format!("log_viewer_line_{index}")),
+                    Text::default(),
+                    text_size,
+                    ThemeColor::from(PaletteRole::Text),
+                    Style::default(),
+                    LogViewerLine(index),
+                ));
+            }
+        });
+}
+
+fn handle_keys(keys: Populated<&Keys, Changed<Keys>>, mut viewer: Single<&mut LogViewer>) {
+    let viewer = &mut **viewer;
+
+    for keys in keys {
+        if keys.just_pressed.contains(&KeyCode::PageUp) {
+            viewer.scroll += NUM_LINES;
+            viewer.invalidate();
+        }
+
+        if keys.just_pressed.contains(&KeyCode::PageDown) {
+            viewer.scroll = viewer.scroll.saturating_sub(NUM_LINES);
+            viewer.invalidate();
+        }
+
+        if keys.just_pressed.contains(&KeyCode::End) {
+            viewer.scroll = 0;
+            viewer.invalidate();
+        }
+
+        if keys.just_pressed.contains(&LEVEL_KEY) {
+            let index = LEVELS
+                .iter()
+                .position(|level| *level == viewer.filter.level)
+                .unwrap_or_default();
+            viewer.filter.level = LEVELS[(index + 1) % LEVELS.len()];
+            viewer.scroll = 0;
+            viewer.invalidate();
+        }
+
+        if keys.just_pressed.contains(&TARGET_KEY) {
+            // cycles through all crates, and then back to no filter
+            let crates = recent_crates();
+            let next = match &viewer.filter.target {
+                None => 0,
+                Some(target) => {
+                    crates
+                        .iter()
+                        .position(|name| name == target)
+                        .map_or(0, |index| index + 1)
+                }
+            };
+            viewer.filter.target = crates.get(next).cloned();
+            viewer.scroll = 0;
+            viewer.invalidate();
+        }
+    }
+}
+
+fn update_viewer(
+    mut viewer: Single<&mut LogViewer>,
+    mut header: Single<&mut Text, With<LogViewerHeader>>,
+    mut lines: Query<(&LogViewerLine, &mut Text, &mut ThemeColor), Without<LogViewerHeader>>,
+) {
+    let num_records_logged = num_records_logged();
+    if viewer.num_records_logged == Some(num_records_logged) {
+        return;
+    }
+
+    let viewer = &mut **viewer;
+    viewer.num_records_logged = Some(num_records_logged);
+
+    let records = recent_logs_filtered(&viewer.filter);
+    viewer.scroll = viewer.scroll.min(records.len().saturating_sub(NUM_LINES));
+    let end = records.len() - viewer.scroll;
+    let shown = &records[end.saturating_sub(NUM_LINES)..end];
+
+    header.text = format!(
+        "LOG: {} RECORDS, LEVEL: {}, CRATE: {}{}",
+        records.len(),
+        viewer
+            .filter
+            .level
+            .map_or_else(|| "ALL".to_owned(), |level| level.to_string()),
+        viewer
+            .filter
+            .target
+            .as_deref()
+            .map_or_else(|| "ALL".to_owned(), |target| target.to_uppercase()),
+        if viewer.scroll > 0 {
+            format!(" (SCROLLED UP {})", viewer.scroll)
+        }
+        else {
+            String::new()
+        },
+    );
+
+    for (line, mut text, mut theme_color) in &mut lines {
+        let record = shown.get(line.0);
+
+        let line_text = record.map(ToString::to_string).unwrap_or_default();
+        if text.text != line_text {
+            text.text = line_text;
+        }
+
+        let role = match record.map(|record| record.level) {
+            Some(Level::ERROR) => PaletteRole::Negative,
+            Some(Level::WARN) => PaletteRole::Warning,
+            _ => PaletteRole::Text,
+        };
+        theme_color.set_if_neq(ThemeColor::from(role));
+    }
+}
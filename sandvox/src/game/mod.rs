@@ -1,22 +1,51 @@
+pub mod accessibility;
+pub mod block_breaking;
+pub mod block_mesh;
 pub mod block_type;
+pub mod block_use;
 pub mod camera_controller;
+pub mod camera_effects;
 pub mod celestial;
+pub mod clock;
+pub mod edit_history;
+pub mod explosion;
+pub mod falling_block;
 pub mod file;
+pub mod game_mode;
+pub mod gpu_timings;
+pub mod interaction;
+pub mod inventory;
+pub mod item_drop;
+pub mod log_viewer;
+pub mod player_physics;
+pub mod schematic;
+pub mod signal;
+pub mod submerged;
+pub mod teleport;
 pub mod terrain;
+pub mod validation;
+pub mod waypoint;
+pub mod world_edit;
 
 use std::{
+    collections::HashMap,
     fmt::Write,
     path::PathBuf,
     time::Duration,
 };
 
 use bevy_ecs::{
+    change_detection::DetectChangesMut,
     component::Component,
     entity::Entity,
+    message::{
+        Message,
+        MessageReader,
+        MessageWriter,
+    },
     name::Name,
     query::{
         Changed,
-        Has,
         With,
     },
     resource::Resource,
@@ -25,17 +54,23 @@ use bevy_ecs::{
         SystemCondition,
         common_conditions::{
             any_with_component,
+            on_message,
+            resource_added,
             resource_changed,
+            resource_equals,
+            resource_exists,
         },
     },
     system::{
         Commands,
+        Local,
         ParamSet,
         Populated,
         Query,
         Res,
         ResMut,
         Single,
+        SystemParam,
     },
 };
 use chrono::{
@@ -43,12 +78,17 @@ use chrono::{
     Utc,
 };
 use color_eyre::eyre::Error;
-use image::RgbaImage;
 use nalgebra::{
     Point3,
+    UnitQuaternion,
+    Vector2,
     Vector3,
 };
-use palette::WithAlpha;
+use palette::{
+    LinSrgb,
+    Mix,
+    WithAlpha,
+};
 use serde::{
     Deserialize,
     Serialize,
@@ -61,13 +101,28 @@ use winit::keyboard::KeyCode;
 
 use crate::{
     app::{
+        GpuReset,
         Time,
         WindowConfig,
     },
+    assets::{
+        AssetHandle,
+        AssetPlugin,
+        AssetServer,
+    },
     build_info::BUILD_INFO,
+    config::{
+        ConfigChanged,
+        ConfigOverride,
+    },
     ecs::{
-        background_tasks::BackgroundTaskConfig,
+        background_tasks::{
+            BackgroundTaskConfig,
+            BackgroundTaskPool,
+            TaskPriority,
+        },
         plugin::{
+            Dependencies,
             Plugin,
             WorldBuilder,
         },
@@ -75,29 +130,97 @@ use crate::{
         transform::{
             GlobalTransform,
             LocalTransform,
+            TransformSystems,
         },
     },
     game::{
-        block_type::BlockTypes,
+        accessibility::{
+            AccessibilityConfig,
+            AccessibilityPlugin,
+        },
+        block_breaking::BlockBreakingPlugin,
+        block_type::{
+            BlockTypeImages,
+            BlockTypes,
+        },
+        block_use::BlockUsePlugin,
         camera_controller::{
             CameraController,
             CameraControllerConfig,
             CameraControllerPlugin,
             CameraControllerState,
         },
+        camera_effects::{
+            CameraEffectsPlugin,
+            CameraShake,
+            ViewBobbing,
+        },
         celestial::{
             CelestialFrame,
-            GeoCoords,
+            MoonPhase,
+            seasonal_time,
             world_to_geo,
         },
+        clock::{
+            GameClock,
+            GameClockPlugin,
+            GameClockSystems,
+        },
+        edit_history::{
+            EditHistory,
+            EditHistoryConfig,
+            EditHistoryPlugin,
+        },
+        explosion::ExplosionPlugin,
+        falling_block::FallingBlockPlugin,
         file::WorldFile,
+        gpu_timings::GpuTimingsOverlayPlugin,
+        interaction::{
+            BlockOutlineConfig,
+            Crosshair,
+            InteractionConfig,
+            InteractionPlugin,
+        },
+        inventory::Inventory,
+        item_drop::{
+            ItemDropConfig,
+            ItemDropPlugin,
+        },
+        log_viewer::LogViewerPlugin,
+        signal::SignalPlugin,
+        submerged::SubmergedPlugin,
+        teleport::TeleportPlugin,
         terrain::{
             TerrainGenerator,
             TerrainVoxel,
             WorldConfig,
         },
+        validation::{
+            ValidationConfig,
+            ValidationPlugin,
+        },
+        waypoint::WaypointPlugin,
+        world_edit::{
+            WorldEditConfig,
+            WorldEditPlugin,
+        },
     },
     input::Keys,
+    net::{
+        interest::{
+            Interest,
+            InterestConfig,
+            InterestPlugin,
+            Replicated,
+        },
+        replication::{
+            Connection,
+            LocalClient,
+            ReplicationPlugin,
+        },
+        transport::loopback,
+    },
+    profiler::systems::SystemTimings,
     render::{
         DefaultAtlas,
         RenderConfig,
@@ -107,42 +230,68 @@ use crate::{
             PaddingFill,
             PaddingMode,
         },
-        camera::Camera,
+        camera::{
+            Camera,
+            CameraOffset,
+        },
+        fog::Fog,
         fps_counter::{
             FpsCounter,
             FpsCounterConfig,
         },
+        lighting::SceneLighting,
         mesh::RenderMeshStatistics,
-        model::ModelLoader,
-        pass::main_pass::{
-            DepthPrepass,
-            Wireframe,
+        model::{
+            ModelLoader,
+            ModelPlugin,
+        },
+        pass::{
+            main_pass::{
+                DepthPrepass,
+                Wireframe,
+            },
+            statistics::MainPassStatistics,
         },
         render_target::RenderTarget,
+        scene::SceneLoader,
         skybox::{
-            Planet,
             Skybox,
+            SkyboxDaylight,
             SkyboxPlugin,
         },
-        staging::Staging,
+        staging::{
+            Staging,
+            StagingStatistics,
+        },
         surface::ClearColor,
         text::{
             Text,
-            TextColor,
+            TextShadow,
             TextSize,
         },
+        transient::TransientPoolStatistics,
     },
     ui::{
         Background,
+        PaletteRole,
         ShowDebugOutlines,
         Sprites,
         Style,
+        ThemeColor,
+        ThemeConfig,
+        Toast,
+        Toasts,
         View,
     },
     util::{
         format_size,
-        image::ImageLoadExt,
-        stats_alloc::bytes_allocated,
+        serde::default_true,
+        stats_alloc::{
+            MEMORY_TAGS_ENABLED,
+            MemoryTag,
+            bytes_allocated,
+            bytes_allocated_with_tag,
+        },
     },
     voxel::{
         chunk::MortonShape,
@@ -174,7 +323,7 @@ pub struct GamePlugin {
     pub init_world: InitWorld,
 }
 
-#[derive(Clone, Debug, Resource, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Resource, Serialize, Deserialize)]
 pub struct GameConfig {
     #[serde(default = "default_chunk_distance")]
     pub chunk_load_distance: u32,
@@ -182,24 +331,88 @@ pub struct GameConfig {
     #[serde(default = "default_chunk_distance")]
     pub chunk_render_distance: u32,
 
-    #[serde(default)]
+    #[serde(default = "default_chunk_generator_config")]
     pub chunk_generator_config: BackgroundTaskConfig,
 
+    #[serde(default)]
+    pub chunk_mesher_config: BackgroundTaskConfig,
+
     #[serde(default)]
     pub camera_controller: CameraControllerConfig,
+
+    #[serde(default)]
+    pub item_drops: ItemDropConfig,
+
+    #[serde(default)]
+    pub block_outline: BlockOutlineConfig,
+
+    #[serde(default)]
+    pub interaction: InteractionConfig,
+
+    #[serde(default)]
+    pub accessibility: AccessibilityConfig,
+
+    #[serde(default)]
+    pub world_edit: WorldEditConfig,
+
+    #[serde(default)]
+    pub edit_history: EditHistoryConfig,
+
+    #[serde(default)]
+    pub validation: ValidationConfig,
+
+    #[serde(default)]
+    pub interest: InterestConfig,
+
+    /// Bob the camera up and down while walking.
+    #[serde(default = "default_true")]
+    pub view_bobbing: bool,
+
+    #[serde(default)]
+    pub ui_theme: ThemeConfig,
+
+    /// Reload textures, sprites, models and `blocks.toml` when they're
+    /// modified. Enabled by default in debug builds.
+    #[serde(default = "default_hot_reload_assets")]
+    pub hot_reload_assets: bool,
 }
 
 fn default_chunk_distance() -> u32 {
     4
 }
 
+fn default_hot_reload_assets() -> bool {
+    cfg!(debug_assertions)
+}
+
+fn default_chunk_generator_config() -> BackgroundTaskConfig {
+    // chunks need to be generated before they can be meshed, so meshing shouldn't
+    // take the threads away from generation
+    BackgroundTaskConfig {
+        priority: TaskPriority::High,
+        ..Default::default()
+    }
+}
+
 impl Default for GameConfig {
     fn default() -> Self {
         Self {
             chunk_load_distance: default_chunk_distance(),
             chunk_render_distance: default_chunk_distance(),
-            chunk_generator_config: Default::default(),
+            chunk_generator_config: default_chunk_generator_config(),
+            chunk_mesher_config: Default::default(),
             camera_controller: Default::default(),
+            item_drops: Default::default(),
+            block_outline: Default::default(),
+            interaction: Default::default(),
+            accessibility: Default::default(),
+            world_edit: Default::default(),
+            edit_history: Default::default(),
+            validation: Default::default(),
+            interest: Default::default(),
+            view_bobbing: true,
+            ui_theme: Default::default(),
+            hot_reload_assets: default_hot_reload_assets(),
         }
     }
 }
@@ -215,6 +428,22 @@ pub enum InitWorld {
     },
 }
 
+impl InitWorld {
+    /// Config overrides stored in the world.
+    ///
+    /// For an existing world this has to open the world file.
+    pub fn config_overrides(&self) -> Result<Vec<ConfigOverride>, Error> {
+        let config = match self {
+            InitWorld::Load { world_file } => {
+                WorldFile::open(world_file)?.world_config().config.clone()
+            }
+            InitWorld::Create { world_config, .. } => world_config.config.clone(),
+        };
+
+        Ok(ConfigOverride::from_table(&config))
+    }
+}
+
 impl Default for InitWorld {
     fn default() -> Self {
         Self::Create {
@@ -225,10 +454,71 @@ impl Default for InitWorld {
 }
 
 impl Plugin for GamePlugin {
+    fn dependencies(&self, dependencies: &mut Dependencies) -> Result<(), Error> {
+        dependencies
+            .require_plugin::<AssetPlugin>()
+            .require_plugin::<ModelPlugin>()
+            .add_plugin(GameClockPlugin)?
+            .add_plugin(CameraControllerPlugin)?
+            .add_plugin(CameraEffectsPlugin)?
+            .add_plugin(ChunkMeshPlugin::<
+                TerrainVoxel,
+                ChunkShape,
+                BlockTypes,
+                GreedyMesher<TerrainVoxel>,
+            >::new(self.game_config.chunk_mesher_config))?
+            .add_plugin(ChunkMapPlugin)?
+            .add_plugin(ChunkLoaderPlugin {
+                shape: ChunkShape::default(),
+            })?
+            .add_plugin(ChunkGeneratorPlugin::<
+                TerrainVoxel,
+                ChunkShape,
+                TerrainGenerator,
+                //TestChunkGenerator,
+            >::new(self.game_config.chunk_generator_config))?
+            .add_plugin(SkyboxPlugin)?
+            .add_plugin(AccessibilityPlugin {
+                config: self.game_config.accessibility,
+            })?
+            .add_plugin(InteractionPlugin {
+                block_outline: self.game_config.block_outline,
+                interaction: self.game_config.interaction,
+            })?
+            .add_plugin(BlockUsePlugin)?
+            .add_plugin(GpuTimingsOverlayPlugin)?
+            .add_plugin(LogViewerPlugin)?
+            .add_plugin(ItemDropPlugin {
+                config: self.game_config.item_drops,
+            })?
+            .add_plugin(TeleportPlugin)?
+            .add_plugin(WaypointPlugin)?
+            .add_plugin(SubmergedPlugin)?
+            .add_plugin(SignalPlugin)?
+            .add_plugin(FallingBlockPlugin)?
+            .add_plugin(ExplosionPlugin)?
+            .add_plugin(WorldEditPlugin {
+                config: self.game_config.world_edit,
+            })?
+            .add_plugin(EditHistoryPlugin {
+                config: self.game_config.edit_history,
+            })?
+            .add_plugin(BlockBreakingPlugin)?
+            .add_plugin(ValidationPlugin {
+                config: self.game_config.validation,
+            })?
+            .add_plugin(InterestPlugin {
+                config: self.game_config.interest,
+            })?
+            .add_plugin(ReplicationPlugin)?;
+        Ok(())
+    }
+
     fn setup(&self, builder: &mut WorldBuilder) -> Result<(), Error> {
         match &self.init_world {
             InitWorld::Load { world_file } => {
                 let world_file = WorldFile::open(world_file)?;
+                world_file.world_config().validate()?;
 
                 builder
                     .insert_resource(world_file.world_config().clone())
@@ -238,6 +528,7 @@ impl Plugin for GamePlugin {
                 world_config,
                 world_file,
             } => {
+                world_config.validate()?;
                 builder.insert_resource(world_config.clone());
 
                 if let Some(world_file) = world_file {
@@ -249,43 +540,51 @@ impl Plugin for GamePlugin {
 
         builder
             .insert_resource(self.game_config.clone())
-            .insert_resource({
-                // for debugging
-                AstroTime(Utc::now())
-            })
-            .add_plugin(CameraControllerPlugin)?
-            .add_plugin(ChunkMeshPlugin::<
-                TerrainVoxel,
-                ChunkShape,
-                BlockTypes,
-                GreedyMesher<TerrainVoxel>,
-            >::default())?
-            .add_plugin(ChunkMapPlugin)?
-            .add_plugin(ChunkLoaderPlugin {
-                shape: ChunkShape::default(),
-            })?
-            .add_plugin(ChunkGeneratorPlugin::<
-                TerrainVoxel,
-                ChunkShape,
-                TerrainGenerator,
-                //TestChunkGenerator,
-            >::new(self.game_config.chunk_generator_config))?
-            .add_plugin(SkyboxPlugin)?
+            .insert_resource(self.game_config.ui_theme)
             .add_systems(
                 schedule::Startup,
                 (
-                    (load_block_types, create_skybox).in_set(RenderSystems::Setup),
-                    create_terrain_generator.after(load_block_types),
+                    configure_assets,
+                    load_block_types,
+                    create_skybox.in_set(RenderSystems::Setup),
                     init_player.after(RenderSystems::Setup),
+                    show_damaged_world_file_notice.run_if(resource_exists::<WorldFile>),
+                ),
+            )
+            .add_message::<ConfigChanged>()
+            .add_message::<PlayerMoved>()
+            .init_resource::<DebugOverlayPage>()
+            .add_systems(
+                schedule::PostUpdate,
+                detect_player_movement.after(TransformSystems::Propagate),
+            )
+            .add_systems(
+                schedule::Update,
+                (
+                    update_sky.after(GameClockSystems::Advance),
+                    apply_config_changes.run_if(on_message::<ConfigChanged>),
+                    show_gpu_reset_notice.run_if(resource_added::<GpuReset>),
+                    update_block_types.run_if(resource_exists::<BlockTypesAsset>),
+                    create_terrain_generator
+                        .run_if(resource_added::<BlockTypes>)
+                        .after(update_block_types),
+                    update_loading_screen,
                 ),
             )
-            .add_systems(schedule::Update, update_sky)
             .add_systems(
                 schedule::Render,
                 (
-                    update_debug_overlay.run_if(
-                        resource_changed::<FpsCounter>.and(any_with_component::<DebugOverlay>),
-                    ),
+                    (
+                        update_debug_overlay.run_if(resource_equals(DebugOverlayPage::General)),
+                        update_system_timings_page
+                            .run_if(resource_equals(DebugOverlayPage::Systems)),
+                    )
+                        .run_if(
+                            resource_changed::<FpsCounter>
+                                .or(resource_changed::<DebugOverlayPage>)
+                                .and(any_with_component::<DebugOverlay>),
+                        )
+                        .after(handle_keys),
                     handle_keys,
                 ),
             );
@@ -294,78 +593,106 @@ impl Plugin for GamePlugin {
     }
 }
 
-fn load_block_types(
-    mut atlas: ResMut<DefaultAtlas>,
-    wgpu: Res<WgpuContext>,
-    mut staging: ResMut<Staging>,
-    mut commands: Commands,
-) {
-    let block_types = BlockTypes::load("assets/blocks.toml", |image| {
-        Ok(atlas.insert_image(
-            image,
-            Some(PaddingMode {
-                padding: Padding::uniform(1),
-                fill: PaddingFill::REPEAT,
-            }),
-            &wgpu.device,
-            &mut *staging,
-        )?)
-    })
-    .unwrap();
-    commands.insert_resource(block_types);
+fn configure_assets(config: Res<GameConfig>, assets: Res<AssetServer>) {
+    assets.set_hot_reload(config.hot_reload_assets);
 }
 
-fn create_skybox(
-    wgpu: Res<WgpuContext>,
+/// The block types are reloaded when `blocks.toml` or one of the textures is
+/// modified.
+///
+/// Only the textures and properties of existing block types can be changed
+/// while the game is running, because the terrain generator and the chunks
+/// refer to block types by their index.
+#[derive(Debug, Resource)]
+struct BlockTypesAsset {
+    handle: AssetHandle<BlockTypeImages>,
+    version: u64,
+}
+
+fn load_block_types(assets: Res<AssetServer>, mut commands: Commands) {
+    commands.insert_resource(BlockTypesAsset {
+        handle: assets.load_required("assets/blocks.toml"),
+        version: 0,
+    });
+}
+
+fn update_block_types(
+    mut asset: ResMut<BlockTypesAsset>,
     mut atlas: ResMut<DefaultAtlas>,
+    wgpu: Res<WgpuContext>,
     mut staging: ResMut<Staging>,
+    block_types: Option<Res<BlockTypes>>,
     mut commands: Commands,
 ) {
-    let skybox = Skybox::load(&wgpu, "assets/skybox").unwrap();
+    let asset = &mut *asset;
+    let Some(result) = asset.handle.get_if_changed(&mut asset.version)
+    else {
+        return;
+    };
 
-    let mut make_planet = |id, path, size| {
-        // with a realistic planet size the sun and moon would only be a few pixels in
-        // diameter. e.g. with a fov of 60°, an angular diameter of 0.5° and a
-        // screen size of 1024 pixels, the planet would only be 8.5 pixels.
-        //
-        // thus we just make it larger
-        let size = size * 4.0;
+    let block_type_images = match result {
+        Ok(block_type_images) => block_type_images,
+        Err(error) if block_types.is_none() => panic!("Error while loading block types: {error}"),
+        Err(error) => {
+            tracing::error!("could not reload block types: {error}");
+            return;
+        }
+    };
 
-        let image = RgbaImage::from_path(path).unwrap();
+    if let Some(block_types) = &block_types
+        && !block_types.has_same_blocks(&block_type_images.block_types)
+    {
+        tracing::error!(
+            "could not reload block types: block types can't be added, removed or reordered while the game is running"
+        );
+        return;
+    }
 
-        let atlas_handle = atlas
-            .insert_image(&image, None, &wgpu.device, &mut staging)
-            .unwrap();
+    let textures = block_type_images
+        .images
+        .iter()
+        .map(|image| {
+            atlas.insert_image(
+                image,
+                Some(PaddingMode {
+                    padding: Padding::uniform(1),
+                    fill: PaddingFill::REPEAT,
+                }),
+                &wgpu.device,
+                &mut staging,
+            )
+        })
+        .collect::<Result<Vec<_>, _>>();
+
+    let textures = match textures {
+        Ok(textures) => textures,
+        Err(error) if block_types.is_none() => panic!("Error while loading block types: {error}"),
+        Err(error) => {
+            // the textures that were already inserted are removed from the atlas again when
+            // they're dropped
+            tracing::error!("could not reload block types: {error}");
+            return;
+        }
+    };
 
-        tracing::debug!(?path, ?atlas_handle, "loaded texture");
+    // the old textures are removed from the atlas when the old block types are
+    // dropped. the chunks are meshed again when the block types change.
+    commands.insert_resource(
+        block_type_images
+            .block_types
+            .map_textures(|index| textures[*index].clone()),
+    );
+}
 
-        (
-            Name::new(format!("{id:?}")),
-            Planet {
-                texture: atlas_handle,
-                size,
-            },
-            GlobalTransform::identity(),
-            id,
-        )
-    };
+fn create_skybox(mut scene_loader: SceneLoader, mut commands: Commands) {
+    let scene = scene_loader.load_scene("assets/skybox.ron").unwrap();
 
-    commands
-        .spawn((skybox, GlobalTransform::identity()))
-        .with_children(|spawner| {
-            spawner.spawn(make_planet(
-                PlanetId::Sun,
-                "assets/skybox/sun.png",
-                // average angular size
-                0.536f32.to_radians(),
-            ));
-            spawner.spawn(make_planet(
-                PlanetId::Moon,
-                "assets/skybox/moon.png",
-                // average angular size
-                0.528f32.to_radians(),
-            ));
-        });
+    for id in [PlanetId::Sun, PlanetId::Moon] {
+        let entity = scene
+            .get(&format!("{id:?}"))
+            .unwrap_or_else(|| panic!("skybox scene without {id:?}"));
+        commands.entity(entity).insert(id);
+    }
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Component)]
@@ -403,6 +730,9 @@ fn init_player(
             Name::new("main_window"),
             WindowConfig {
                 title: "SandVox".to_owned(),
+                min_size: Some(Vector2::new(640, 360)),
+                persist: true,
+                ..Default::default()
             },
         ))
         .id();
@@ -417,8 +747,10 @@ fn init_player(
                 aspect_ratio: 1.0,
                 fovy: render_config.fov.to_radians(),
                 z_near: 0.1,
-                z_far: config.chunk_render_distance as f32 * CHUNK_SIZE as f32,
+                z_far: render_config.far_plane.unwrap_or(f32::INFINITY),
+                reverse_z: false,
             },
+            player_fog(&config, Default::default()),
             LocalTransform::from(Vector3::new(0.0, 2.0, -2.0)),
             CameraController {
                 state: CameraControllerState {
@@ -431,11 +763,22 @@ fn init_player(
                 radius: Vector3::repeat(config.chunk_load_distance),
             },
             Player,
+            (Replicated, Interest::default()),
+            Inventory::default(),
+            EditHistory::new(config.edit_history.capacity),
+            ViewBobbing::default(),
+            CameraShake::default(),
+            CameraOffset::default(),
         ));
 
         if render_config.depth_prepass {
             camera.insert(DepthPrepass);
         }
+
+        // singleplayer replicates to a client in this process
+        let (server_end, client_end) = loopback();
+        camera.insert(Connection::sharing_world(server_end));
+        commands.insert_resource(LocalClient::new(client_end));
     }
 
     {
@@ -452,8 +795,11 @@ fn init_player(
             TextSize {
                 scaling: pixel_size,
             },
-            TextColor {
-                color: palette::named::WHITESMOKE.into_format().with_alpha(1.0),
+            ThemeColor::from(PaletteRole::Text),
+            // keeps the text readable in front of a bright sky
+            TextShadow {
+                color: palette::named::BLACK.into_format().with_alpha(0.8),
+                offset: Vector2::new(1.0, 1.0),
             },
         );
 
@@ -516,12 +862,61 @@ fn init_player(
                         sprite.size.y as f32 * pixel_size,
                     );
 
-                    (Name::new("crosshair"), style, background)
+                    (Name::new("crosshair"), style, background, Crosshair)
+                });
+
+                // create loading screen
+                ui.spawn({
+                    let mut style = Style::default();
+                    style.position = taffy::Position::Absolute;
+                    style.margin = taffy::Rect::auto();
+
+                    (
+                        Name::new("loading_screen"),
+                        Text::from("LOADING"),
+                        text_style,
+                        style,
+                        LoadingScreen,
+                    )
                 });
             });
     }
 }
 
+#[derive(Clone, Copy, Debug, Default, Component)]
+struct LoadingScreen;
+
+/// Shows how many of the required assets are loaded and removes the loading
+/// screen once all of them are.
+fn update_loading_screen(
+    assets: Res<AssetServer>,
+    loading_screen: Populated<(Entity, &mut Text), With<LoadingScreen>>,
+    mut commands: Commands,
+) {
+    let progress = assets.progress();
+
+    for (entity, mut text) in loading_screen {
+        if progress.num_loaded == progress.num_total {
+            commands.entity(entity).despawn();
+            continue;
+        }
+
+        let status = if progress.num_failed > 0 {
+            format!(
+                "LOADING FAILED: {} OF {} ASSETS",
+                progress.num_failed, progress.num_total
+            )
+        }
+        else {
+            format!("LOADING {}/{}", progress.num_loaded, progress.num_total)
+        };
+
+        if text.text != status {
+            text.text = status;
+        }
+    }
+}
+
 fn format_build_tag() -> String {
     let mut s = String::with_capacity(64);
 
@@ -548,6 +943,26 @@ fn format_build_tag() -> String {
 #[derive(Clone, Copy, Debug, Default, Component)]
 struct DebugOverlay;
 
+/// What the debug overlay shows. Cycled with F9.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Resource)]
+enum DebugOverlayPage {
+    #[default]
+    General,
+    Systems,
+}
+
+impl DebugOverlayPage {
+    fn next(self) -> Self {
+        match self {
+            Self::General => Self::Systems,
+            Self::Systems => Self::General,
+        }
+    }
+}
+
+/// Number of systems shown on the systems page of the debug overlay.
+const DEBUG_OVERLAY_NUM_SYSTEMS: usize = 16;
+
 fn update_debug_overlay(
     fps_counter: Res<FpsCounter>,
     wgpu: Res<WgpuContext>,
@@ -555,23 +970,53 @@ fn update_debug_overlay(
     render_mesh: Res<RenderMeshStatistics>,
     mut debug_overlay: Single<&mut Text, With<DebugOverlay>>,
     player: Option<Single<&GlobalTransform, With<Player>>>,
-    astro_time: Res<AstroTime>,
+    clock: Res<GameClock>,
+    world_config: Res<WorldConfig>,
     chunks: Query<(), With<ChunkPosition>>,
     chunk_statistics: Res<ChunkStatistics>,
+    staging_statistics: Res<StagingStatistics>,
+    transient_statistics: Res<TransientPoolStatistics>,
+    main_pass_statistics: Option<Single<&MainPassStatistics, With<Player>>>,
+    local_client: Option<Res<LocalClient>>,
+    player_connection: Option<Single<&Connection, With<Player>>>,
 ) {
     debug_overlay.text.clear();
 
     writeln!(
         &mut debug_overlay.text,
-        "TIME: N={}, T={:.1}s, DT={:.1}ms, W={}",
+        "TIME: N={}, T={:.1}s, DT={:.1}ms, W={}{}",
         time.tick_count,
         time.tick_start_seconds(),
         time.delta_seconds() * 1000.0,
-        astro_time.0.format("%Y-%m-%d %H:%M"),
+        clock.time.format("%Y-%m-%d %H:%M"),
+        if clock.frozen { " (FROZEN)" } else { "" },
+    )
+    .unwrap();
+
+    let frame = CelestialFrame::new(
+        world_config.location.to_geo(),
+        celestial_time(&clock, &world_config),
+    );
+    let moon_phase = MoonPhase::new(clock.time);
+    writeln!(
+        &mut debug_overlay.text,
+        "SKY: SEASON={}, MOON={} ({:.0}%)",
+        frame.season().name().to_uppercase(),
+        moon_phase.name().to_uppercase(),
+        moon_phase.illuminated_fraction() * 100.0,
     )
     .unwrap();
 
-    writeln!(&mut debug_overlay.text, "FPS: {:.1}", fps_counter.fps).unwrap();
+    writeln!(
+        &mut debug_overlay.text,
+        "FPS: {:.1}, 1% LOW={:.1}ms, 0.1% LOW={:.1}ms, MAX={:.1}ms, OVER BUDGET={}",
+        fps_counter.fps,
+        fps_counter.low_1_percent.as_secs_f32() * 1000.0,
+        fps_counter.low_0_1_percent.as_secs_f32() * 1000.0,
+        fps_counter.max_frame_time.as_secs_f32() * 1000.0,
+        fps_counter.frames_over_budget,
+    )
+    .unwrap();
 
     write!(
         &mut debug_overlay.text,
@@ -592,6 +1037,22 @@ fn update_debug_overlay(
         writeln!(&mut debug_overlay.text, "").unwrap();
     }
 
+    if MEMORY_TAGS_ENABLED {
+        write!(&mut debug_overlay.text, "MEM CPU:").unwrap();
+        for tag in MemoryTag::ALL {
+            if tag != MemoryTag::Untagged {
+                write!(
+                    &mut debug_overlay.text,
+                    " {}={}",
+                    tag.name().to_uppercase(),
+                    format_size(bytes_allocated_with_tag(tag))
+                )
+                .unwrap();
+            }
+        }
+        writeln!(&mut debug_overlay.text).unwrap();
+    }
+
     let staging_info = wgpu.staging_pool.info();
     writeln!(
         &mut debug_overlay.text,
@@ -603,6 +1064,27 @@ fn update_debug_overlay(
     )
     .unwrap();
 
+    writeln!(
+        &mut debug_overlay.text,
+        "UPLOAD: B={}, N={}, MAX={}",
+        format_size(staging_statistics.frame.bytes),
+        staging_statistics.frame.copies,
+        format_size(staging_statistics.frame.largest_allocation),
+    )
+    .unwrap();
+
+    writeln!(
+        &mut debug_overlay.text,
+        "TRANSIENT: TEX={}/{} (+{}), BUF={}/{} (+{})",
+        transient_statistics.textures.allocated,
+        format_size(transient_statistics.textures.bytes),
+        transient_statistics.textures.created,
+        transient_statistics.buffers.allocated,
+        format_size(transient_statistics.buffers.bytes),
+        transient_statistics.buffers.created,
+    )
+    .unwrap();
+
     writeln!(
         &mut debug_overlay.text,
         "MESH: DRAW={}, VERT={}, CULL={}",
@@ -610,6 +1092,30 @@ fn update_debug_overlay(
     )
     .unwrap();
 
+    if let Some(main_pass_statistics) = main_pass_statistics {
+        let statistics = main_pass_statistics.get();
+        write!(
+            &mut debug_overlay.text,
+            "PASS: OVERDRAW={:.2}, FRAG={}, VERT={}, PRIM={}",
+            statistics.overdraw(),
+            statistics.main_pass.fragment_shader_invocations,
+            statistics.main_pass.vertex_shader_invocations,
+            statistics.main_pass.clipper_primitives_out,
+        )
+        .unwrap();
+
+        if let Some(depth_prepass) = statistics.depth_prepass {
+            write!(
+                &mut debug_overlay.text,
+                ", PREPASS VERT={}",
+                depth_prepass.vertex_shader_invocations,
+            )
+            .unwrap();
+        }
+
+        writeln!(&mut debug_overlay.text).unwrap();
+    }
+
     writeln!(
         &mut debug_overlay.text,
         "CHUNK: T={}, L={}/{}, M={}/{}",
@@ -621,6 +1127,43 @@ fn update_debug_overlay(
     )
     .unwrap();
 
+    if let Some(local_client) = local_client {
+        let stats = local_client.stats();
+        write!(&mut debug_overlay.text, "NET: PING=").unwrap();
+        match local_client.ping() {
+            Some(ping) => {
+                write!(
+                    &mut debug_overlay.text,
+                    "{:.1}MS",
+                    ping.as_secs_f32() * 1000.0
+                )
+            }
+            None => write!(&mut debug_overlay.text, "?"),
+        }
+        .unwrap();
+        write!(
+            &mut debug_overlay.text,
+            ", LOSS={:.1}%, UP={}, DOWN={}, ENT={}",
+            stats.packet_loss() * 100.0,
+            format_size(stats.bytes_sent),
+            format_size(stats.bytes_received),
+            local_client.replica.num_entities(),
+        )
+        .unwrap();
+
+        if let Some(connection) = player_connection {
+            write!(
+                &mut debug_overlay.text,
+                ", CHUNK Q={}, RATIO={:.0}%",
+                connection.num_pending_chunks(),
+                connection.chunks().stats().ratio() * 100.0,
+            )
+            .unwrap();
+        }
+
+        writeln!(&mut debug_overlay.text).unwrap();
+    }
+
     if let Some(transform) = player {
         let position = transform.position();
         let look_dir = transform.isometry * Vector3::z();
@@ -633,24 +1176,95 @@ fn update_debug_overlay(
     }
 }
 
+fn update_system_timings_page(
+    system_timings: Res<SystemTimings>,
+    background_tasks: Res<BackgroundTaskPool>,
+    mut debug_overlay: Single<&mut Text, With<DebugOverlay>>,
+) {
+    debug_overlay.text.clear();
+
+    for queue in background_tasks.statistics() {
+        writeln!(
+            &mut debug_overlay.text,
+            "TASKS {}: Q={}/{}, A={}/{}, WAIT={:.2}MS, RUN={:.2}MS",
+            queue.name.to_uppercase(),
+            queue.num_queued,
+            queue.queue_size,
+            queue.num_active,
+            queue.num_threads,
+            queue.wait_ms,
+            queue.run_ms,
+        )
+        .unwrap();
+    }
+
+    if system_timings.is_empty() {
+        writeln!(&mut debug_overlay.text, "SYSTEMS: NO TIMINGS").unwrap();
+        return;
+    }
+
+    writeln!(
+        &mut debug_overlay.text,
+        "SYSTEMS: {} (AVG/PEAK)",
+        system_timings.len()
+    )
+    .unwrap();
+
+    for timing in system_timings.worst(DEBUG_OVERLAY_NUM_SYSTEMS) {
+        writeln!(
+            &mut debug_overlay.text,
+            "{:.2}/{:.2}MS {}",
+            timing.smoothed_ms,
+            timing.peak_ms,
+            timing.name.to_uppercase(),
+        )
+        .unwrap();
+    }
+}
+
+fn show_gpu_reset_notice(gpu_reset: Res<GpuReset>, mut toasts: ResMut<Toasts>) {
+    tracing::info!(reason = gpu_reset.reason, "showing GPU reset notice");
+    toasts.push(Toast::warning("GPU RESET: DEVICE LOST"));
+}
+
+/// Tells the player if state in the world file was damaged. It's left out
+/// when the game is loaded.
+fn show_damaged_world_file_notice(world_file: Res<WorldFile>, mut toasts: ResMut<Toasts>) {
+    let num_damaged = world_file.integrity().damaged.len();
+    if num_damaged > 0 {
+        toasts.push(
+            Toast::error(format!("WORLD FILE: {num_damaged} DAMAGED ENTRIES"))
+                .with_timeout(Duration::from_secs(10)),
+        );
+    }
+}
+
 fn handle_keys(
     keys: Populated<&Keys, Changed<Keys>>,
-    player_camera: Single<(Entity, Has<Wireframe>), With<Player>>,
+    player_camera: Single<(Entity, Option<&Wireframe>), With<Player>>,
     show_ui_layout: Option<Res<ShowDebugOutlines>>,
+    mut debug_overlay_page: ResMut<DebugOverlayPage>,
     mut commands: Commands,
 ) {
     for keys in keys {
         if keys.just_pressed.contains(&KeyCode::F6) {
-            let (player_entity, wireframe_enabled) = *player_camera;
+            let (player_entity, wireframe) = *player_camera;
             let mut player = commands.entity(player_entity);
 
-            if wireframe_enabled {
-                tracing::debug!("disable wireframe");
-                player.remove::<Wireframe>();
-            }
-            else {
-                tracing::debug!("enable wireframe");
-                player.insert(Wireframe);
+            // cycles through: off, overlay, wireframe only
+            match wireframe {
+                None => {
+                    tracing::debug!("enable wireframe overlay");
+                    player.insert(Wireframe::Overlay);
+                }
+                Some(Wireframe::Overlay) => {
+                    tracing::debug!("enable wireframe only");
+                    player.insert(Wireframe::Only);
+                }
+                Some(Wireframe::Only) => {
+                    tracing::debug!("disable wireframe");
+                    player.remove::<Wireframe>();
+                }
             }
         }
 
@@ -664,36 +1278,174 @@ fn handle_keys(
                 commands.remove_resource::<ShowDebugOutlines>();
             }
         }
+
+        if keys.just_pressed.contains(&KeyCode::F9) {
+            *debug_overlay_page = debug_overlay_page.next();
+            tracing::debug!(page = ?*debug_overlay_page, "switch debug overlay page");
+        }
     }
 }
 
 #[derive(Clone, Copy, Debug, Default, Component)]
 pub struct Player;
 
+/// Sent when a player's global position changed.
+#[derive(Clone, Copy, Debug, Message)]
+pub struct PlayerMoved {
+    pub entity: Entity,
+    pub from: Point3<f32>,
+    pub to: Point3<f32>,
+}
+
+fn detect_player_movement(
+    players: Query<(Entity, &GlobalTransform), With<Player>>,
+    mut last_positions: Local<HashMap<Entity, Point3<f32>>>,
+    mut player_moved: MessageWriter<PlayerMoved>,
+) {
+    for (entity, transform) in &players {
+        let to = transform.position();
+
+        match last_positions.insert(entity, to) {
+            Some(from) if from != to => {
+                player_moved.write(PlayerMoved { entity, from, to });
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Resources and components that are derived from the [`GameConfig`].
+#[derive(SystemParam)]
+struct DerivedGameConfig<'w, 's> {
+    item_drops: ResMut<'w, ItemDropConfig>,
+    block_outline: ResMut<'w, BlockOutlineConfig>,
+    interaction: ResMut<'w, InteractionConfig>,
+    accessibility: ResMut<'w, AccessibilityConfig>,
+    world_edit: ResMut<'w, WorldEditConfig>,
+    edit_history: ResMut<'w, EditHistoryConfig>,
+    validation: ResMut<'w, ValidationConfig>,
+    interest: ResMut<'w, InterestConfig>,
+    ui_theme: ResMut<'w, ThemeConfig>,
+    edit_histories: Query<'w, 's, &'static mut EditHistory, With<Player>>,
+}
+
+impl DerivedGameConfig<'_, '_> {
+    fn apply(&mut self, game_config: &GameConfig) {
+        *self.item_drops = game_config.item_drops;
+        *self.block_outline = game_config.block_outline;
+        self.interaction.set_if_neq(game_config.interaction);
+        self.accessibility.set_if_neq(game_config.accessibility);
+        self.world_edit.set_if_neq(game_config.world_edit);
+        self.edit_history.set_if_neq(game_config.edit_history);
+        for mut edit_history in &mut self.edit_histories {
+            edit_history.set_capacity(game_config.edit_history.capacity);
+        }
+        self.validation.set_if_neq(game_config.validation);
+        self.interest.set_if_neq(game_config.interest);
+        self.ui_theme.set_if_neq(game_config.ui_theme);
+    }
+}
+
+/// Applies a reloaded config to the player and the resources derived from the
+/// game config.
+fn apply_config_changes(
+    mut config_changed: MessageReader<ConfigChanged>,
+    game_config: Res<GameConfig>,
+    render_config: Res<RenderConfig>,
+    mut derived_game_config: DerivedGameConfig,
+    assets: Res<AssetServer>,
+    player: Single<
+        (
+            Entity,
+            &mut Camera,
+            &mut Fog,
+            &mut CameraControllerConfig,
+            &mut ChunkLoader,
+        ),
+        With<Player>,
+    >,
+    mut commands: Commands,
+) {
+    let (player, mut camera, mut fog, mut camera_controller_config, mut chunk_loader) =
+        player.into_inner();
+
+    for changed in config_changed.read() {
+        match changed {
+            ConfigChanged::Game => {
+                derived_game_config.apply(&game_config);
+                assets.set_hot_reload(game_config.hot_reload_assets);
+                *camera_controller_config = game_config.camera_controller.clone();
+                chunk_loader.radius = Vector3::repeat(game_config.chunk_load_distance);
+                *fog = player_fog(&game_config, fog.color);
+            }
+            ConfigChanged::Render => {
+                camera.fovy = render_config.fov.to_radians();
+                camera.z_far = render_config.far_plane.unwrap_or(f32::INFINITY);
+
+                // the render pipelines are recreated when this changes
+                if render_config.depth_prepass {
+                    commands.entity(player).insert(DepthPrepass);
+                }
+                else {
+                    commands.entity(player).remove::<DepthPrepass>();
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Fog that ends at the chunk render distance.
+///
+/// The color is updated with the sky in [`update_sky`].
+fn player_fog(game_config: &GameConfig, color: LinSrgb<f32>) -> Fog {
+    Fog::until(
+        game_config.chunk_render_distance as f32 * CHUNK_SIZE as f32,
+        0.75,
+        color,
+    )
+}
+
 fn update_sky(
     mut params: ParamSet<(
         Single<&GlobalTransform, With<Player>>,
-        Single<&mut GlobalTransform, With<Skybox>>,
+        Single<(&mut GlobalTransform, &mut SkyboxDaylight), With<Skybox>>,
         Query<(&mut GlobalTransform, &PlanetId)>,
     )>,
-    time: Res<Time>,
-    mut astro_time: ResMut<AstroTime>,
+    clock: Res<GameClock>,
+    world_config: Res<WorldConfig>,
+    render_config: Res<RenderConfig>,
+    accessibility_config: Res<AccessibilityConfig>,
+    mut lighting: ResMut<SceneLighting>,
+    mut fog: Single<&mut Fog, With<Player>>,
 ) {
-    const WORLD_ORIGIN: GeoCoords<f64> = GeoCoords {
-        // what's here?
-        latitude: 51.283889f64.to_radians(),
-        longitude: 11.52f64.to_radians(),
-    };
-
-    const DAY_LENGTH: f32 = 600.0;
-    const TIME_WARP: f32 = 24.0 * 60.0 * 60.0 / DAY_LENGTH;
+    let observer = world_to_geo(params.p0().position(), world_config.location.to_geo());
+    let frame = CelestialFrame::new(observer, celestial_time(&clock, &world_config));
 
-    let observer = world_to_geo(params.p0().position(), WORLD_ORIGIN);
-    let time = time.app_start_utc + Duration::from_secs_f32(TIME_WARP * time.tick_start_seconds());
-    //let time = Utc::now();
-    let frame = CelestialFrame::new(observer, time);
+    {
+        let (mut skybox_transform, mut daylight) = params.p1().into_inner();
+        // the sun and moon still move, since they light the scene
+        skybox_transform.isometry.rotation = if accessibility_config.static_skybox {
+            UnitQuaternion::identity()
+        }
+        else {
+            frame.sky()
+        };
+        daylight.set_if_neq(SkyboxDaylight {
+            night: frame.darkness(),
+            ..*daylight
+        });
 
-    params.p1().isometry.rotation = frame.sky();
+        // fade into the sky at the horizon
+        let color = daylight
+            .day_color
+            .color
+            .into_linear()
+            .mix(LinSrgb::new(0.005, 0.005, 0.01), daylight.night);
+        if fog.color != color {
+            fog.color = color;
+        }
+    }
 
     for (mut planet_transform, planet_id) in params.p2() {
         planet_transform.isometry.rotation = match planet_id {
@@ -702,8 +1454,21 @@ fn update_sky(
         };
     }
 
-    astro_time.0 = time;
+    *lighting = SceneLighting::from_sky(
+        &render_config.lighting,
+        frame.sun() * Vector3::z_axis(),
+        frame.moon() * Vector3::z_axis(),
+        MoonPhase::new(clock.time).illuminated_fraction() as f32,
+    );
 }
 
-#[derive(Debug, Resource)]
-struct AstroTime(DateTime<Utc>);
+/// The time used for the celestial simulation, with the calendar compressed if
+/// the world has a custom year length.
+fn celestial_time(clock: &GameClock, world_config: &WorldConfig) -> DateTime<Utc> {
+    if let Some(days_per_year) = world_config.days_per_year {
+        seasonal_time(clock.time, days_per_year)
+    }
+    else {
+        clock.time
+    }
+}
@@ -0,0 +1,239 @@
+//! Gravity and collisions for walking players.
+//!
+//! Players with [`PlayerPhysics`] fall, jump and collide with solid blocks.
+//! They're moved by the camera controller's [`Walking`] requests instead of
+//! flying. The player's transform is their eye, and their body is a box below
+//! it.
+
+use bevy_ecs::{
+    component::Component,
+    schedule::{
+        IntoScheduleConfigs,
+        common_conditions::resource_exists,
+    },
+    system::{
+        Populated,
+        Res,
+    },
+};
+use color_eyre::eyre::Error;
+use nalgebra::{
+    Point3,
+    Translation3,
+    Vector3,
+};
+
+use crate::{
+    app::Time,
+    ecs::{
+        plugin::{
+            Plugin,
+            WorldBuilder,
+        },
+        schedule,
+        transform::LocalTransform,
+    },
+    game::{
+        ChunkShape,
+        block_type::BlockTypes,
+        camera_controller::{
+            CameraControllerSystems,
+            Walking,
+        },
+        terrain::TerrainVoxel,
+    },
+    voxel::access::Voxels,
+};
+
+/// Acceleration of falling players (in blocks / s²).
+const GRAVITY: f32 = 28.0;
+
+/// Maximum falling speed (in blocks / s).
+const TERMINAL_VELOCITY: f32 = 50.0;
+
+/// Upwards speed when jumping (in blocks / s). This clears one block.
+const JUMP_SPEED: f32 = 9.0;
+
+/// Size of the player's body.
+const BODY_SIZE: Vector3<f32> = Vector3::new(0.6, 1.8, 0.6);
+
+/// Height of the eye above the bottom of the body.
+const EYE_HEIGHT: f32 = 1.6;
+
+/// Maximum distance that a body moves before it's checked for collisions.
+/// This must be less than a block, so bodies can't skip over blocks.
+const MAX_STEP: f32 = 0.5;
+
+/// Bodies touching a block don't collide with it.
+const EPSILON: f32 = 1e-4;
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct PlayerPhysicsPlugin;
+
+impl Plugin for PlayerPhysicsPlugin {
+    fn setup(&self, builder: &mut WorldBuilder) -> Result<(), Error> {
+        builder.add_systems(
+            schedule::Update,
+            update_player_physics
+                .after(CameraControllerSystems::Update)
+                .run_if(resource_exists::<BlockTypes>),
+        );
+
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Component)]
+pub struct PlayerPhysics {
+    /// Vertical speed (in blocks / s).
+    pub vertical_velocity: f32,
+
+    /// Whether the player is standing on a block.
+    pub on_ground: bool,
+}
+
+fn update_player_physics(
+    time: Res<Time>,
+    block_types: Res<BlockTypes>,
+    voxels: Voxels<TerrainVoxel, ChunkShape>,
+    players: Populated<(&mut LocalTransform, &mut PlayerPhysics, &Walking)>,
+) {
+    let dt = time.delta_seconds();
+
+    // blocks in chunks that aren't loaded are solid, so players don't fall out of
+    // the world while it's generated.
+    let is_solid = |position| {
+        voxels
+            .get(position)
+            .is_none_or(|voxel| block_types[voxel.block_type].is_solid())
+    };
+
+    for (mut transform, mut physics, walking) in players {
+        if walking.jump && physics.on_ground {
+            physics.vertical_velocity = JUMP_SPEED;
+        }
+        physics.vertical_velocity =
+            (physics.vertical_velocity - GRAVITY * dt).max(-TERMINAL_VELOCITY);
+
+        let delta = Vector3::new(
+            walking.movement.x,
+            physics.vertical_velocity * dt,
+            walking.movement.z,
+        );
+
+        let eye = Point3::from(transform.isometry.translation.vector);
+        let min = body_min(eye);
+        let (moved, blocked) = move_and_collide(min, BODY_SIZE, delta, is_solid);
+
+        physics.on_ground = blocked.y && delta.y < 0.0;
+        if blocked.y {
+            physics.vertical_velocity = 0.0;
+        }
+
+        if moved != min {
+            transform.isometry.translation = Translation3::from(eye + (moved - min));
+        }
+    }
+}
+
+/// Minimum corner of the body of a player whose eye is at `eye`.
+fn body_min(eye: Point3<f32>) -> Point3<f32> {
+    eye - Vector3::new(0.5 * BODY_SIZE.x, EYE_HEIGHT, 0.5 * BODY_SIZE.z)
+}
+
+/// Moves a box by `delta`, one axis after the other, and stops it at solid
+/// blocks.
+///
+/// Returns the new minimum corner and along which axes the box was stopped. A
+/// box that's already stuck in a block isn't stopped, so it can get out.
+fn move_and_collide(
+    min: Point3<f32>,
+    size: Vector3<f32>,
+    delta: Vector3<f32>,
+    is_solid: impl Fn(Point3<i32>) -> bool,
+) -> (Point3<f32>, Vector3<bool>) {
+    let mut blocked = Vector3::repeat(false);
+
+    if overlaps_solid(min, size, &is_solid) {
+        return (min + delta, blocked);
+    }
+
+    let num_steps = (delta.abs().max() / MAX_STEP).ceil().max(1.0);
+    let step = delta / num_steps;
+
+    let mut min = min;
+    for _ in 0..(num_steps as usize) {
+        for axis in 0..3 {
+            if blocked[axis] || step[axis] == 0.0 {
+                continue;
+            }
+
+            let mut moved = min;
+            moved[axis] += step[axis];
+
+            if overlaps_solid(moved, size, &is_solid) {
+                // the box was free before, so it hit the layer of blocks at its leading
+                // edge.
+                moved[axis] = if step[axis] > 0.0 {
+                    (moved[axis] + size[axis] - EPSILON).floor() - size[axis]
+                }
+                else {
+                    (moved[axis] + EPSILON).floor() + 1.0
+                };
+                blocked[axis] = true;
+            }
+
+            min = moved;
+        }
+    }
+
+    (min, blocked)
+}
+
+fn overlaps_solid(
+    min: Point3<f32>,
+    size: Vector3<f32>,
+    is_solid: impl Fn(Point3<i32>) -> bool,
+) -> bool {
+    let from = min.map(|c| (c + EPSILON).floor() as i32);
+    let to = (min + size).map(|c| (c - EPSILON).floor() as i32);
+
+    (from.x..=to.x)
+        .any(|x| (from.y..=to.y).any(|y| (from.z..=to.z).any(|z| is_solid(Point3::new(x, y, z)))))
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::{
+        Point3,
+        Vector3,
+    };
+
+    use crate::game::player_physics::move_and_collide;
+
+    #[test]
+    fn it_stops_at_solid_blocks() {
+        let size = Vector3::new(0.6, 1.8, 0.6);
+
+        // floor below y = 0
+        let (min, blocked) = move_and_collide(
+            Point3::new(0.2, 0.5, 0.2),
+            size,
+            Vector3::new(0.0, -2.0, 0.0),
+            |position| position.y < 0,
+        );
+        assert!((min.y - 0.0).abs() < 1e-3);
+        assert_eq!(blocked, Vector3::new(false, true, false));
+
+        // wall at x = 2, sliding along it
+        let (min, blocked) = move_and_collide(
+            Point3::new(0.2, 0.0, 0.2),
+            size,
+            Vector3::new(3.0, 0.0, 1.0),
+            |position| position.x >= 2,
+        );
+        assert!((min.x - 1.4).abs() < 1e-3);
+        assert!((min.z - 1.2).abs() < 1e-3);
+        assert_eq!(blocked, Vector3::new(true, false, false));
+    }
+}
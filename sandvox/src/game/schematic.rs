@@ -0,0 +1,305 @@
+//! Schematics are cuboids of blocks that can be saved to a file and placed
+//! into a world, e.g. to share builds or to set up test scenes.
+//!
+//! Blocks are stored by the name of their block type, so schematics work across
+//! worlds with different block type ids. On disk the blocks are run-length
+//! encoded and the whole schematic is written as CBOR.
+
+use std::path::Path;
+
+use color_eyre::eyre::{
+    Error,
+    bail,
+    eyre,
+};
+use indexmap::IndexSet;
+use nalgebra::{
+    Point3,
+    Vector3,
+};
+use serde::{
+    Deserialize,
+    Serialize,
+};
+
+use crate::{
+    game::{
+        ChunkShape,
+        block_type::BlockTypes,
+        terrain::TerrainVoxel,
+    },
+    voxel::{
+        access::Voxels,
+        edit::ChunkEditBatch,
+    },
+};
+
+/// Increased when the file format changes.
+const FORMAT_VERSION: u32 = 1;
+
+#[derive(Clone, Debug)]
+pub struct Schematic {
+    size: Vector3<u32>,
+
+    /// Names of the block types used in the schematic.
+    palette: IndexSet<String>,
+
+    /// Indices into the palette, x first, then y, then z.
+    blocks: Vec<u32>,
+}
+
+impl Schematic {
+    /// Creates a schematic filled with `fill`.
+    pub fn new(size: Vector3<u32>, fill: &str) -> Self {
+        let num_blocks = size.x as usize * size.y as usize * size.z as usize;
+
+        Self {
+            size,
+            palette: IndexSet::from([fill.to_owned()]),
+            blocks: vec![0; num_blocks],
+        }
+    }
+
+    pub fn size(&self) -> Vector3<u32> {
+        self.size
+    }
+
+    pub fn get(&self, offset: Point3<u32>) -> Option<&str> {
+        let index = self.index(offset)?;
+        let block = self.blocks[index];
+        Some(&self.palette[block as usize])
+    }
+
+    pub fn set(&mut self, offset: Point3<u32>, block_type: &str) {
+        let index = self
+            .index(offset)
+            .unwrap_or_else(|| panic!("offset {offset:?} outside of schematic"));
+
+        let block = self
+            .palette
+            .get_index_of(block_type)
+            .unwrap_or_else(|| self.palette.insert_full(block_type.to_owned()).0);
+        self.blocks[index] = block.try_into().unwrap();
+    }
+
+    /// Iterates over the blocks with their offsets in the schematic.
+    pub fn iter(&self) -> impl Iterator<Item = (Point3<u32>, &str)> {
+        self.blocks
+            .iter()
+            .enumerate()
+            .map(|(index, block)| (self.offset(index), self.palette[*block as usize].as_str()))
+    }
+
+    fn offset(&self, index: usize) -> Point3<u32> {
+        let index = index as u32;
+        Point3::new(
+            index % self.size.x,
+            (index / self.size.x) % self.size.y,
+            index / (self.size.x * self.size.y),
+        )
+    }
+
+    fn index(&self, offset: Point3<u32>) -> Option<usize> {
+        (offset.x < self.size.x && offset.y < self.size.y && offset.z < self.size.z).then(|| {
+            offset.x as usize
+                + self.size.x as usize
+                    * (offset.y as usize + self.size.y as usize * offset.z as usize)
+        })
+    }
+
+    /// Copies the blocks between `min` and `max` (both inclusive) out of the
+    /// world.
+    ///
+    /// All chunks in that range must be loaded.
+    pub fn export(
+        voxels: &Voxels<TerrainVoxel, ChunkShape>,
+        block_types: &BlockTypes,
+        min: Point3<i32>,
+        max: Point3<i32>,
+    ) -> Result<Self, Error> {
+        let (min, max) = (min.inf(&max), min.sup(&max));
+        let size = (max - min).map(|c| c as u32 + 1);
+
+        let mut schematic = Self::new(size, "air");
+
+        for z in 0..size.z {
+            for y in 0..size.y {
+                for x in 0..size.x {
+                    let offset = Point3::new(x, y, z);
+                    let position = min + offset.coords.cast::<i32>();
+
+                    let voxel = voxels
+                        .get(position)
+                        .ok_or_else(|| eyre!("Block at {position:?} is not loaded"))?;
+
+                    schematic.set(offset, &block_types[voxel.block_type].name);
+                }
+            }
+        }
+
+        Ok(schematic)
+    }
+
+    /// Places the schematic into the world, with its minimum corner at
+    /// `position`.
+    ///
+    /// Blocks in chunks that aren't loaded are skipped. With `skip_air` air in
+    /// the schematic doesn't replace blocks in the world. Returns the number of
+    /// blocks placed.
+    pub fn place(
+        &self,
+        voxels: &mut Voxels<TerrainVoxel, ChunkShape>,
+        block_types: &BlockTypes,
+        position: Point3<i32>,
+        skip_air: bool,
+    ) -> Result<usize, Error> {
+        let batch = self.edits(block_types, position, skip_air)?;
+
+        let num_blocks = batch.len();
+        let num_placed = voxels.apply(batch).len();
+        let num_skipped = num_blocks - num_placed;
+
+        if num_skipped > 0 {
+            tracing::warn!(num_skipped, "skipped blocks in chunks that aren't loaded");
+        }
+
+        Ok(num_placed)
+    }
+
+    /// The writes that [`place`][Self::place] applies, e.g. to keep the
+    /// replaced blocks around.
+    pub fn edits(
+        &self,
+        block_types: &BlockTypes,
+        position: Point3<i32>,
+        skip_air: bool,
+    ) -> Result<ChunkEditBatch<TerrainVoxel, ChunkShape>, Error> {
+        // resolve the names first, so we don't place half of the schematic
+        let palette = self
+            .palette
+            .iter()
+            .map(|name| {
+                block_types
+                    .lookup(name)
+                    .ok_or_else(|| eyre!("Unknown block type `{name}`"))
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+        let air = block_types.lookup("air");
+
+        let mut batch = ChunkEditBatch::default();
+
+        for (index, block) in self.blocks.iter().enumerate() {
+            let block_type = palette[*block as usize];
+            if skip_air && Some(block_type) == air {
+                continue;
+            }
+
+            let offset = self.offset(index).coords.cast::<i32>();
+            batch.set(position + offset, TerrainVoxel { block_type });
+        }
+
+        Ok(batch)
+    }
+
+    pub fn read(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let data = std::fs::read(path)?;
+        let file: SchematicFile = serde_cbor::from_slice(&data)?;
+        file.try_into()
+    }
+
+    pub fn write(&self, path: impl AsRef<Path>) -> Result<(), Error> {
+        let data = serde_cbor::to_vec(&SchematicFile::from(self))?;
+        std::fs::write(path, data)?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct SchematicFile {
+    version: u32,
+    size: Vector3<u32>,
+    palette: IndexSet<String>,
+
+    /// Pairs of run length and palette index.
+    runs: Vec<(u32, u32)>,
+}
+
+impl From<&Schematic> for SchematicFile {
+    fn from(value: &Schematic) -> Self {
+        let mut runs: Vec<(u32, u32)> = vec![];
+
+        for block in &value.blocks {
+            match runs.last_mut() {
+                Some((length, last)) if last == block => *length += 1,
+                _ => runs.push((1, *block)),
+            }
+        }
+
+        Self {
+            version: FORMAT_VERSION,
+            size: value.size,
+            palette: value.palette.clone(),
+            runs,
+        }
+    }
+}
+
+impl TryFrom<SchematicFile> for Schematic {
+    type Error = Error;
+
+    fn try_from(value: SchematicFile) -> Result<Self, Self::Error> {
+        if value.version != FORMAT_VERSION {
+            bail!("Unsupported schematic version: {}", value.version);
+        }
+
+        let num_blocks = value.size.x as usize * value.size.y as usize * value.size.z as usize;
+
+        let mut blocks = Vec::with_capacity(num_blocks);
+        for (length, block) in value.runs {
+            if block as usize >= value.palette.len() {
+                bail!("Invalid palette index: {block}");
+            }
+            blocks.extend(std::iter::repeat_n(block, length as usize));
+        }
+
+        if blocks.len() != num_blocks {
+            bail!("Expected {num_blocks} blocks, but got {}", blocks.len());
+        }
+
+        Ok(Self {
+            size: value.size,
+            palette: value.palette,
+            blocks,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::{
+        Point3,
+        Vector3,
+    };
+
+    use crate::game::schematic::{
+        Schematic,
+        SchematicFile,
+    };
+
+    #[test]
+    fn it_round_trips_through_the_file_format() {
+        let mut schematic = Schematic::new(Vector3::new(3, 4, 5), "air");
+        schematic.set(Point3::new(0, 0, 0), "stone");
+        schematic.set(Point3::new(2, 3, 4), "dirt");
+        schematic.set(Point3::new(1, 2, 0), "stone");
+
+        let file = SchematicFile::from(&schematic);
+        assert_eq!(file.runs.len(), 5);
+
+        let decoded = Schematic::try_from(file).unwrap();
+        assert_eq!(decoded.size(), schematic.size());
+        assert!(decoded.iter().eq(schematic.iter()));
+        assert_eq!(decoded.get(Point3::new(2, 3, 4)), Some("dirt"));
+        assert_eq!(decoded.get(Point3::new(3, 0, 0)), None);
+    }
+}
@@ -0,0 +1,279 @@
+//! Binary signal networks, similar to redstone.
+//!
+//! Sources power the wires connected to them, and wires power lamps next to
+//! them (see [`BlockSignal`]). Whether a block is powered is part of its block
+//! type, so circuits are saved with the chunks and powered blocks are meshed
+//! with their own textures.
+//!
+//! Signal blocks are found by scanning chunks when they change. The network is
+//! only updated on signal ticks, which batches edits and limits how often
+//! chunks have to be remeshed.
+
+use std::{
+    collections::{
+        HashMap,
+        HashSet,
+        VecDeque,
+    },
+    time::{
+        Duration,
+        Instant,
+    },
+};
+
+use bevy_ecs::{
+    message::MessageReader,
+    query::Changed,
+    resource::Resource,
+    schedule::{
+        IntoScheduleConfigs,
+        common_conditions::resource_exists,
+    },
+    system::{
+        Query,
+        Res,
+        ResMut,
+    },
+};
+use color_eyre::eyre::Error;
+use nalgebra::{
+    Point3,
+    Vector3,
+};
+
+use crate::{
+    app::Time,
+    ecs::{
+        plugin::{
+            Plugin,
+            WorldBuilder,
+        },
+        schedule,
+    },
+    game::{
+        ChunkShape,
+        block_type::{
+            BlockSignal,
+            BlockTypes,
+        },
+        terrain::TerrainVoxel,
+    },
+    voxel::{
+        access::Voxels,
+        chunk::{
+            Chunk,
+            ChunkShape as _,
+        },
+        chunk_map::{
+            ChunkPosition,
+            ChunkUnloaded,
+        },
+    },
+};
+
+/// Time between signal ticks.
+const SIGNAL_TICK: Duration = Duration::from_millis(100);
+
+const NEIGHBORS: [Vector3<i32>; 6] = [
+    Vector3::new(-1, 0, 0),
+    Vector3::new(1, 0, 0),
+    Vector3::new(0, -1, 0),
+    Vector3::new(0, 1, 0),
+    Vector3::new(0, 0, -1),
+    Vector3::new(0, 0, 1),
+];
+
+type TerrainChunk = Chunk<TerrainVoxel, ChunkShape>;
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SignalPlugin;
+
+impl Plugin for SignalPlugin {
+    fn setup(&self, builder: &mut WorldBuilder) -> Result<(), Error> {
+        builder.init_resource::<SignalNetwork>().add_systems(
+            schedule::Update,
+            (scan_chunks, update_signals)
+                .chain()
+                .run_if(resource_exists::<BlockTypes>),
+        );
+
+        Ok(())
+    }
+}
+
+/// Positions of all signal blocks in loaded chunks.
+#[derive(Debug, Default, Resource)]
+pub struct SignalNetwork {
+    by_chunk: HashMap<Point3<i32>, Vec<Point3<i32>>>,
+
+    /// Whether signal blocks changed since the last signal tick.
+    dirty: bool,
+
+    last_tick: Option<Instant>,
+}
+
+fn scan_chunks(
+    block_types: Res<BlockTypes>,
+    chunks: Query<(&ChunkPosition, &TerrainChunk), Changed<TerrainChunk>>,
+    mut chunk_unloaded: MessageReader<ChunkUnloaded>,
+    mut network: ResMut<SignalNetwork>,
+) {
+    for message in chunk_unloaded.read() {
+        if network.by_chunk.remove(&message.position).is_some() {
+            network.dirty = true;
+        }
+    }
+
+    for (chunk_position, chunk) in &chunks {
+        let origin = chunk_position.0 * i32::try_from(chunk.shape().side_length()).unwrap();
+
+        let positions = chunk
+            .iter()
+            .filter(|(_, voxel)| block_types[voxel.block_type].signal.is_some())
+            .map(|(offset, _)| origin + offset.coords.cast::<i32>())
+            .collect::<Vec<_>>();
+
+        // this also picks up the changes made by the last signal tick, which then
+        // just finds nothing to do.
+        let previous = if positions.is_empty() {
+            network.by_chunk.remove(&chunk_position.0)
+        }
+        else {
+            network.by_chunk.insert(chunk_position.0, positions)
+        };
+
+        if previous.is_some() || network.by_chunk.contains_key(&chunk_position.0) {
+            network.dirty = true;
+        }
+    }
+}
+
+fn update_signals(
+    time: Res<Time>,
+    block_types: Res<BlockTypes>,
+    mut voxels: Voxels<TerrainVoxel, ChunkShape>,
+    mut network: ResMut<SignalNetwork>,
+) {
+    if !network.dirty
+        || network
+            .last_tick
+            .is_some_and(|last_tick| time.tick_start < last_tick + SIGNAL_TICK)
+    {
+        return;
+    }
+
+    network.dirty = false;
+    network.last_tick = Some(time.tick_start);
+
+    let signals = network
+        .by_chunk
+        .values()
+        .flatten()
+        .filter_map(|position| {
+            let voxel = voxels.get(*position)?;
+            Some((*position, block_types[voxel.block_type].signal?))
+        })
+        .collect::<HashMap<_, _>>();
+
+    let powered = powered_blocks(&signals);
+
+    let mut changed = 0;
+    for (position, signal) in &signals {
+        let (BlockSignal::Wire { off, on } | BlockSignal::Lamp { off, on }) = *signal
+        else {
+            continue;
+        };
+
+        let block_type = if powered.contains(position) { on } else { off };
+
+        if voxels
+            .get(*position)
+            .is_some_and(|voxel| voxel.block_type != block_type)
+        {
+            voxels.set(*position, TerrainVoxel { block_type });
+            changed += 1;
+        }
+    }
+
+    tracing::trace!(blocks = signals.len(), changed, "signal tick");
+}
+
+/// Finds the blocks that are powered, by following wires from the sources.
+fn powered_blocks(signals: &HashMap<Point3<i32>, BlockSignal>) -> HashSet<Point3<i32>> {
+    let mut powered = HashSet::new();
+
+    let mut queue = signals
+        .iter()
+        .filter(|(_, signal)| matches!(signal, BlockSignal::Source))
+        .map(|(position, _)| *position)
+        .collect::<VecDeque<_>>();
+    powered.extend(queue.iter().copied());
+
+    while let Some(position) = queue.pop_front() {
+        for offset in NEIGHBORS {
+            let neighbor = position + offset;
+
+            match signals.get(&neighbor) {
+                Some(BlockSignal::Wire { .. }) => {
+                    if powered.insert(neighbor) {
+                        queue.push_back(neighbor);
+                    }
+                }
+                Some(BlockSignal::Lamp { .. }) => {
+                    powered.insert(neighbor);
+                }
+                Some(BlockSignal::Source) | None => {}
+            }
+        }
+    }
+
+    powered
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use nalgebra::Point3;
+
+    use crate::game::{
+        block_type::{
+            BlockSignal,
+            BlockType,
+        },
+        signal::powered_blocks,
+    };
+
+    #[test]
+    fn it_powers_wires_and_lamps_connected_to_a_source() {
+        let wire = BlockSignal::Wire {
+            off: BlockType::from_usize(0),
+            on: BlockType::from_usize(1),
+        };
+        let lamp = BlockSignal::Lamp {
+            off: BlockType::from_usize(2),
+            on: BlockType::from_usize(3),
+        };
+
+        let signals = HashMap::from([
+            (Point3::new(0, 0, 0), BlockSignal::Source),
+            (Point3::new(1, 0, 0), wire),
+            (Point3::new(2, 0, 0), wire),
+            (Point3::new(3, 0, 0), lamp),
+            // lamps don't carry the signal
+            (Point3::new(4, 0, 0), wire),
+            // not connected
+            (Point3::new(0, 2, 0), wire),
+            (Point3::new(0, 3, 0), lamp),
+        ]);
+
+        let powered = powered_blocks(&signals);
+
+        for x in 0..4 {
+            assert!(powered.contains(&Point3::new(x, 0, 0)));
+        }
+        assert!(!powered.contains(&Point3::new(4, 0, 0)));
+        assert!(!powered.contains(&Point3::new(0, 2, 0)));
+        assert!(!powered.contains(&Point3::new(0, 3, 0)));
+    }
+}
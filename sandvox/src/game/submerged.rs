@@ -0,0 +1,189 @@
+//! Effects while the player's camera is inside a water or lava block: the
+//! screen is tinted and wobbles, the fog closes in and sounds are muffled.
+
+use bevy_ecs::{
+    change_detection::{
+        DetectChanges,
+        DetectChangesMut,
+    },
+    entity::Entity,
+    query::With,
+    schedule::{
+        IntoScheduleConfigs,
+        common_conditions::resource_exists,
+    },
+    system::{
+        Commands,
+        Local,
+        Res,
+        Single,
+        SystemParam,
+    },
+};
+use color_eyre::eyre::Error;
+use nalgebra::Point3;
+use palette::{
+    LinSrgb,
+    LinSrgba,
+};
+
+use crate::{
+    ecs::{
+        plugin::{
+            Dependencies,
+            Plugin,
+            WorldBuilder,
+        },
+        schedule,
+        transform::GlobalTransform,
+    },
+    game::{
+        ChunkShape,
+        GameConfig,
+        Player,
+        accessibility::AccessibilityConfig,
+        apply_config_changes,
+        block_type::{
+            BlockTypes,
+            Medium,
+        },
+        player_fog,
+        terrain::TerrainVoxel,
+        update_sky,
+    },
+    render::{
+        fog::Fog,
+        overlay::{
+            OverlayPlugin,
+            ScreenOverlay,
+        },
+    },
+    sound::output::SoundOutput,
+    voxel::access::Voxels,
+};
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SubmergedPlugin;
+
+impl Plugin for SubmergedPlugin {
+    fn dependencies(&self, dependencies: &mut Dependencies) -> Result<(), Error> {
+        dependencies.require_plugin::<OverlayPlugin>();
+        Ok(())
+    }
+
+    fn setup(&self, builder: &mut WorldBuilder) -> Result<(), Error> {
+        builder.add_systems(
+            schedule::Update,
+            update_submerged
+                // both of them reset the fog
+                .after(update_sky)
+                .after(apply_config_changes)
+                .run_if(resource_exists::<BlockTypes>),
+        );
+
+        Ok(())
+    }
+}
+
+/// How a medium looks and sounds from the inside.
+#[derive(Clone, Copy, Debug)]
+struct MediumEffects {
+    tint: LinSrgba<f32>,
+    distortion: f32,
+    fog_color: LinSrgb<f32>,
+    visibility: f32,
+
+    /// Cutoff frequency (in Hz) of the low-pass filter for sounds.
+    muffle: u32,
+}
+
+impl MediumEffects {
+    fn of(medium: Medium) -> Option<Self> {
+        match medium {
+            Medium::Air => None,
+            Medium::Water => {
+                Some(Self {
+                    tint: LinSrgba::new(0.02, 0.12, 0.3, 0.35),
+                    distortion: 0.004,
+                    fog_color: LinSrgb::new(0.01, 0.06, 0.15),
+                    visibility: 24.0,
+                    muffle: 800,
+                })
+            }
+            Medium::Lava => {
+                Some(Self {
+                    tint: LinSrgba::new(0.9, 0.25, 0.02, 0.7),
+                    distortion: 0.008,
+                    fog_color: LinSrgb::new(0.6, 0.12, 0.0),
+                    visibility: 2.0,
+                    muffle: 300,
+                })
+            }
+        }
+    }
+}
+
+/// Looks up the [`Medium`] of the blocks.
+#[derive(SystemParam)]
+struct Media<'w, 's> {
+    block_types: Res<'w, BlockTypes>,
+    voxels: Voxels<'w, 's, TerrainVoxel, ChunkShape>,
+}
+
+impl Media<'_, '_> {
+    /// The medium at a point. Outside of loaded chunks it's air.
+    fn at(&self, point: &Point3<f32>) -> Medium {
+        self.voxels.get_at(point).map_or(Medium::Air, |voxel| {
+            self.block_types[voxel.block_type].medium
+        })
+    }
+}
+
+fn update_submerged(
+    player: Single<(Entity, &GlobalTransform, &mut Fog), With<Player>>,
+    media: Media,
+    game_config: Res<GameConfig>,
+    accessibility_config: Res<AccessibilityConfig>,
+    sound_output: Option<Res<SoundOutput>>,
+    mut last_medium: Local<Medium>,
+    mut commands: Commands,
+) {
+    let (player, transform, mut fog) = player.into_inner();
+
+    let medium = media.at(&transform.position());
+    let effects = MediumEffects::of(medium);
+
+    // the sound output is recreated when its config changes, so this is set every
+    // frame.
+    if let Some(sound_output) = sound_output {
+        sound_output.set_muffle(effects.map(|effects| effects.muffle));
+    }
+
+    if let Some(effects) = effects {
+        // the sky and config changes reset the fog, so it's overridden every frame
+        fog.set_if_neq(Fog::until(effects.visibility, 0.0, effects.fog_color));
+    }
+
+    let entered = medium != *last_medium;
+    if !entered && !accessibility_config.is_changed() {
+        return;
+    }
+
+    if entered {
+        tracing::debug!(?medium, "camera entered medium");
+        *last_medium = medium;
+    }
+
+    if let Some(effects) = effects {
+        commands.entity(player).insert(ScreenOverlay {
+            color: effects.tint,
+            distortion: effects.distortion * accessibility_config.motion_scale(),
+        });
+    }
+    else if entered {
+        commands.entity(player).remove::<ScreenOverlay>();
+
+        // the sky already set the fog color this frame
+        *fog = player_fog(&game_config, fog.color);
+    }
+}
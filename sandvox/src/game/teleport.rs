@@ -0,0 +1,137 @@
+//! State for the `teleport` command: named locations, which are stored in the
+//! world file, and the positions entities were teleported from.
+
+use std::collections::{
+    BTreeMap,
+    VecDeque,
+};
+
+use bevy_ecs::{
+    component::Component,
+    resource::Resource,
+    schedule::{
+        IntoScheduleConfigs,
+        common_conditions::resource_exists,
+    },
+    system::{
+        Commands,
+        Res,
+    },
+};
+use color_eyre::eyre::Error;
+use nalgebra::Point3;
+use serde::{
+    Deserialize,
+    Serialize,
+};
+
+use crate::{
+    ecs::{
+        plugin::{
+            Plugin,
+            WorldBuilder,
+        },
+        schedule,
+    },
+    game::file::WorldFile,
+};
+
+/// Key under which the locations are stored in the world file.
+const WORLD_FILE_KEY: &str = "teleport_locations";
+
+/// Number of positions kept in the [`TeleportHistory`].
+const HISTORY_LENGTH: usize = 16;
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TeleportPlugin;
+
+impl Plugin for TeleportPlugin {
+    fn setup(&self, builder: &mut WorldBuilder) -> Result<(), Error> {
+        builder
+            .init_resource::<TeleportLocations>()
+            .add_systems(
+                schedule::Startup,
+                load_locations.run_if(resource_exists::<WorldFile>),
+            )
+            .add_systems(
+                schedule::Shutdown,
+                save_locations.run_if(resource_exists::<WorldFile>),
+            );
+
+        Ok(())
+    }
+}
+
+/// Named locations that entities can be teleported to.
+#[derive(Clone, Debug, Default, Resource, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct TeleportLocations {
+    locations: BTreeMap<String, Point3<f32>>,
+}
+
+impl TeleportLocations {
+    pub fn get(&self, name: &str) -> Option<Point3<f32>> {
+        self.locations.get(name).copied()
+    }
+
+    /// Saves a location, replacing an existing one with the same name.
+    pub fn insert(&mut self, name: String, position: Point3<f32>) {
+        self.locations.insert(name, position);
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<Point3<f32>> {
+        self.locations.remove(name)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, Point3<f32>)> {
+        self.locations
+            .iter()
+            .map(|(name, position)| (name.as_str(), *position))
+    }
+
+    pub fn len(&self) -> usize {
+        self.locations.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.locations.is_empty()
+    }
+}
+
+/// Positions an entity was teleported from, most recent last.
+#[derive(Clone, Debug, Default, Component)]
+pub struct TeleportHistory {
+    positions: VecDeque<Point3<f32>>,
+}
+
+impl TeleportHistory {
+    pub fn push(&mut self, position: Point3<f32>) {
+        if self.positions.len() == HISTORY_LENGTH {
+            self.positions.pop_front();
+        }
+        self.positions.push_back(position);
+    }
+
+    pub fn pop(&mut self) -> Option<Point3<f32>> {
+        self.positions.pop_back()
+    }
+}
+
+fn load_locations(world_file: Res<WorldFile>, mut commands: Commands) {
+    match world_file.load_state::<TeleportLocations>(WORLD_FILE_KEY) {
+        Ok(locations) => {
+            let locations = locations.unwrap_or_default();
+            tracing::debug!(count = locations.len(), "loading teleport locations");
+            commands.insert_resource(locations);
+        }
+        Err(error) => tracing::error!(?error, "could not load teleport locations"),
+    }
+}
+
+fn save_locations(world_file: Res<WorldFile>, locations: Res<TeleportLocations>) {
+    tracing::debug!(count = locations.len(), "saving teleport locations");
+
+    if let Err(error) = world_file.store_state(WORLD_FILE_KEY, &*locations) {
+        tracing::error!(?error, "could not save teleport locations");
+    }
+}
@@ -1,6 +1,10 @@
 use std::time::Instant;
 
 use bevy_ecs::resource::Resource;
+use color_eyre::eyre::{
+    Error,
+    bail,
+};
 use nalgebra::{
     Point3,
     Vector2,
@@ -21,9 +25,12 @@ use serde::{
 };
 
 use crate::{
-    game::block_type::{
-        BlockType,
-        BlockTypes,
+    game::{
+        block_type::{
+            BlockType,
+            BlockTypes,
+        },
+        celestial::WorldLocation,
     },
     util::noise::{
         FractalNoise,
@@ -45,10 +52,68 @@ use crate::{
     },
 };
 
-#[derive(Clone, Debug, Default, Resource, Serialize, Deserialize)]
+#[derive(Clone, Debug, Resource, Serialize, Deserialize)]
 pub struct WorldConfig {
     pub seed: WorldSeed,
     pub bounds: WorldBounds,
+
+    /// Length of an in-game day in (real) seconds.
+    #[serde(default = "default_day_length")]
+    pub day_length: f32,
+
+    /// Where on earth the world origin is. This determines the path of the sun
+    /// and moon across the sky.
+    #[serde(default)]
+    pub location: WorldLocation,
+
+    /// If set, a full year (and thus all seasons) passes in this many in-game
+    /// days.
+    #[serde(default)]
+    pub days_per_year: Option<f32>,
+
+    /// Config overrides for this world.
+    ///
+    /// These use the same keys as the config file and are merged over it when
+    /// the world is loaded, e.g. to give a world its own item drop rules.
+    #[serde(default, skip_serializing_if = "toml::Table::is_empty")]
+    pub config: toml::Table,
+}
+
+fn default_day_length() -> f32 {
+    600.0
+}
+
+impl WorldConfig {
+    /// Checks values that would otherwise break the simulation.
+    pub fn validate(&self) -> Result<(), Error> {
+        if !(self.day_length.is_finite() && self.day_length > 0.0) {
+            bail!(
+                "Invalid day_length: expected a positive number of seconds, but got {}",
+                self.day_length
+            );
+        }
+
+        if let Some(days_per_year) = self.days_per_year
+            && !(days_per_year.is_finite() && days_per_year > 0.0)
+        {
+            bail!("Invalid days_per_year: expected a positive number, but got {days_per_year}");
+        }
+
+        Ok(())
+    }
+}
+
+impl Default for WorldConfig {
+    fn default() -> Self {
+        Self {
+            seed: Default::default(),
+            bounds: Default::default(),
+            day_length: default_day_length(),
+            location: Default::default(),
+            days_per_year: None,
+            config: Default::default(),
+        }
+    }
 }
 
 #[derive(Clone, Copy, Debug, Default, Serialize, Deserialize)]
@@ -57,7 +122,7 @@ pub struct WorldBounds {
     pub max: Vector3<Option<i32>>,
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub struct TerrainVoxel {
     pub block_type: BlockType,
 }
@@ -125,6 +190,43 @@ impl TerrainGenerator {
             //sand: block_types.lookup("sand").unwrap(),
         }
     }
+
+    /// Evaluates the noise for all columns of a chunk at once.
+    ///
+    /// The columns are in linear order (x, then z), so the voxels can look them
+    /// up without any Morton encoding.
+    fn generate_columns(&self, position: Point3<i32>, chunk_size: usize) -> Vec<Column> {
+        let points = (0..(chunk_size * chunk_size))
+            .map(|i| {
+                let chunk_offset = Vector2::new(i / chunk_size, i % chunk_size);
+                position.xz().cast::<f32>() * chunk_size as f32 + chunk_offset.cast::<f32>()
+            })
+            .collect::<Vec<_>>();
+
+        let mut surface_heights = vec![0.0; points.len()];
+        self.surface_height
+            .evaluate_batch(&points, &mut surface_heights);
+
+        let mut dirt_depths = vec![0.0; points.len()];
+        self.dirt_depth.evaluate_batch(&points, &mut dirt_depths);
+
+        surface_heights
+            .into_iter()
+            .zip(dirt_depths)
+            .map(|(surface_height, dirt_depth)| {
+                Column {
+                    surface_height: surface_height as i64,
+                    dirt_depth: dirt_depth as i64,
+                }
+            })
+            .collect()
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+struct Column {
+    surface_height: i64,
+    dirt_depth: i64,
 }
 
 impl<S> ChunkGenerator<TerrainVoxel, S> for TerrainGenerator
@@ -151,50 +253,43 @@ where
         let start_time = Instant::now();
 
         let chunk_size = shape.side_length();
-
-        #[derive(Debug, Default)]
-        struct Cell {
-            surface_height: i64,
-            dirt_depth: i64,
-        }
-
-        let mut any_blocks = false;
         let chunk_y = position.y as i64 * chunk_size as i64;
+        let chunk_top = chunk_y + chunk_size as i64 - 1;
+
+        let columns = self.generate_columns(position, chunk_size);
+        let any_blocks = columns
+            .iter()
+            .any(|column| chunk_y <= column.surface_height);
+
+        // this doesn't depend on how the noise is evaluated, but most chunks below the
+        // surface are only stone, and filling them is a lot cheaper than deciding
+        // each voxel.
+        let only_stone = columns
+            .iter()
+            .all(|column| chunk_top < column.surface_height - column.dirt_depth.max(0));
+
+        let chunk = if only_stone {
+            Some(Chunk::filled(
+                shape,
+                TerrainVoxel {
+                    block_type: self.stone,
+                },
+            ))
+        }
+        else if any_blocks {
+            Some(Chunk::from_fn(shape, move |point| {
+                let column = &columns[point.x as usize * chunk_size + point.z as usize];
+                let y = chunk_y + point.y as i64;
 
-        let cells = (0..(chunk_size * chunk_size))
-            .map(|i| {
-                let chunk_offset = Vector2::from(morton::decode::<[u16; 2]>(i as u32));
-                let point =
-                    position.xz().cast::<f32>() * chunk_size as f32 + chunk_offset.cast::<f32>();
-
-                let surface_height = self.surface_height.evaluate_at(point) as i64;
-                let dirt_depth = self.dirt_depth.evaluate_at(point) as i64;
-
-                if chunk_y <= surface_height {
-                    any_blocks = true;
-                }
-
-                Cell {
-                    surface_height,
-                    dirt_depth,
-                }
-            })
-            .collect::<Vec<_>>();
-
-        let mut chunk = None;
-
-        if any_blocks {
-            chunk = Some(Chunk::from_fn(shape, move |point| {
-                let cell = &cells[morton::encode::<[u16; 2]>(point.xz().into()) as usize];
-                let y = position.y as i64 * chunk_size as i64 + point.y as i64;
-
-                let block_type = if y > cell.surface_height {
+                let block_type = if y > column.surface_height {
                     self.air
                 }
-                else if y == cell.surface_height && cell.dirt_depth >= 1 {
+                else if y == column.surface_height && column.dirt_depth >= 1 {
                     self.grass
                 }
-                else if y < cell.surface_height && y >= cell.surface_height - cell.dirt_depth {
+                else if y < column.surface_height
+                    && y >= column.surface_height - column.dirt_depth
+                {
                     self.dirt
                 }
                 else {
@@ -202,8 +297,13 @@ where
                 };
 
                 TerrainVoxel { block_type }
-            }));
+            }))
+        }
+        else {
+            None
+        };
 
+        if chunk.is_some() {
             let elapsed = start_time.elapsed();
             tracing::trace!(?position, ?elapsed, "generated chunk");
         }
@@ -282,7 +382,10 @@ where
 
 #[cfg(test)]
 mod tests {
-    use crate::game::terrain::WorldSeed;
+    use crate::game::terrain::{
+        WorldConfig,
+        WorldSeed,
+    };
 
     #[test]
     fn world_seed_hashing_is_stable() {
@@ -291,4 +394,25 @@ mod tests {
             WorldSeed(0xbba0b10a3f32e802)
         );
     }
+
+    #[test]
+    fn it_rejects_a_day_length_of_zero() {
+        let world_config = WorldConfig {
+            day_length: 0.0,
+            ..Default::default()
+        };
+        assert!(world_config.validate().is_err());
+        assert!(WorldConfig::default().validate().is_ok());
+    }
+
+    #[test]
+    fn it_rejects_non_positive_years() {
+        for days_per_year in [0.0, -1.0, f32::NAN] {
+            let world_config = WorldConfig {
+                days_per_year: Some(days_per_year),
+                ..Default::default()
+            };
+            assert!(world_config.validate().is_err());
+        }
+    }
 }
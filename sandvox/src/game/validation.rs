@@ -0,0 +1,467 @@
+//! Sanity checks for what clients ask the game to do.
+//!
+//! Entities that are controlled by a client, e.g. remote players, are marked
+//! with [`ClientValidation`]. Their movement is checked every tick against the
+//! speed and teleport limits in [`ValidationConfig`], and entities that moved
+//! too far are put back where they were. Edits are checked with
+//! [`ActionValidator`] before they're applied: blocks must be within
+//! [reach][crate::game::interaction::Reach] and client-controlled entities can
+//! only make so many edits per second.
+//!
+//! Violations are logged and the action is rejected. The local player is
+//! trusted, so only the reach is checked for it.
+
+use std::time::Instant;
+
+use bevy_ecs::{
+    component::Component,
+    entity::Entity,
+    resource::Resource,
+    schedule::IntoScheduleConfigs,
+    system::{
+        Populated,
+        Query,
+        Res,
+        SystemParam,
+    },
+};
+use color_eyre::eyre::Error;
+use nalgebra::{
+    Point3,
+    Vector3,
+};
+use serde::{
+    Deserialize,
+    Serialize,
+};
+
+use crate::{
+    app::Time,
+    ecs::{
+        plugin::{
+            Dependencies,
+            Plugin,
+            WorldBuilder,
+        },
+        schedule,
+        transform::{
+            LocalTransform,
+            TransformSystems,
+        },
+    },
+    game::interaction::{
+        InteractionPlugin,
+        Reach,
+    },
+};
+
+/// Movement that is allowed on top of the speed limits in every tick (in
+/// blocks), e.g. for rounding errors.
+const MOVEMENT_TOLERANCE: f32 = 0.05;
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ValidationPlugin {
+    pub config: ValidationConfig,
+}
+
+impl Plugin for ValidationPlugin {
+    fn dependencies(&self, dependencies: &mut Dependencies) -> Result<(), Error> {
+        dependencies.require_plugin::<InteractionPlugin>();
+        Ok(())
+    }
+
+    fn setup(&self, builder: &mut WorldBuilder) -> Result<(), Error> {
+        builder.insert_resource(self.config).add_systems(
+            schedule::PostUpdate,
+            validate_movement.before(TransformSystems::Propagate),
+        );
+
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize, Resource)]
+pub struct ValidationConfig {
+    /// How fast client-controlled entities can move horizontally (in blocks /
+    /// s).
+    #[serde(default = "default_max_horizontal_speed")]
+    pub max_horizontal_speed: f32,
+
+    /// How fast client-controlled entities can move vertically (in blocks /
+    /// s). This must allow for falling.
+    #[serde(default = "default_max_vertical_speed")]
+    pub max_vertical_speed: f32,
+
+    /// Moving further than this in one tick is rejected as teleporting, no
+    /// matter how long the tick was (in blocks).
+    #[serde(default = "default_max_teleport_distance")]
+    pub max_teleport_distance: f32,
+
+    /// How many edits per second a client-controlled entity can make on
+    /// average.
+    #[serde(default = "default_edits_per_second")]
+    pub edits_per_second: f32,
+
+    /// How many edits a client-controlled entity can make at once, after it
+    /// didn't edit anything for a while.
+    #[serde(default = "default_edit_burst")]
+    pub edit_burst: f32,
+}
+
+impl Default for ValidationConfig {
+    fn default() -> Self {
+        Self {
+            max_horizontal_speed: default_max_horizontal_speed(),
+            max_vertical_speed: default_max_vertical_speed(),
+            max_teleport_distance: default_max_teleport_distance(),
+            edits_per_second: default_edits_per_second(),
+            edit_burst: default_edit_burst(),
+        }
+    }
+}
+
+fn default_max_horizontal_speed() -> f32 {
+    24.0
+}
+
+fn default_max_vertical_speed() -> f32 {
+    64.0
+}
+
+fn default_max_teleport_distance() -> f32 {
+    8.0
+}
+
+fn default_edits_per_second() -> f32 {
+    20.0
+}
+
+fn default_edit_burst() -> f32 {
+    40.0
+}
+
+/// Why an action of a client-controlled entity was rejected.
+#[derive(Clone, Copy, Debug, PartialEq, thiserror::Error)]
+pub enum Violation {
+    #[error("moved {distance:.2} blocks in one tick, teleporting is limited to {max_distance:.2}")]
+    Teleported { distance: f32, max_distance: f32 },
+
+    #[error("moved {distance:.2} blocks horizontally, but only {max_distance:.2} are allowed")]
+    TooFastHorizontally { distance: f32, max_distance: f32 },
+
+    #[error("moved {distance:.2} blocks vertically, but only {max_distance:.2} are allowed")]
+    TooFastVertically { distance: f32, max_distance: f32 },
+
+    /// The position has a NaN or infinite coordinate. Any comparison with it
+    /// would be false, so it must be rejected before the limits are checked.
+    #[error("moved to {position:?}, which is not a finite position")]
+    NonFinitePosition { position: Point3<f32> },
+
+    #[error(
+        "edited block at {position:?}, {distance:.2} blocks away, but can only reach {reach:.2}"
+    )]
+    OutOfReach {
+        position: Point3<i32>,
+        distance: f32,
+        reach: f32,
+    },
+
+    /// The entity has no transform, so it's unknown whether it could reach the
+    /// block.
+    #[error("edited block at {position:?} without having a position")]
+    NoPosition { position: Point3<i32> },
+
+    #[error("edited blocks too often")]
+    RateLimited,
+}
+
+/// Marks an entity that is controlled by a client, whose actions are checked
+/// before they're accepted.
+#[derive(Clone, Debug, Default, Component)]
+pub struct ClientValidation {
+    /// Where the entity was at the end of the last tick. It's moved back here
+    /// if it moved too far.
+    last_position: Option<Point3<f32>>,
+
+    edits: RateLimiter,
+
+    /// Number of rejected actions.
+    pub num_violations: u64,
+}
+
+impl ClientValidation {
+    /// Accepts the entity's position without checking how far it moved, e.g.
+    /// after the server teleported it. Non-finite positions are ignored.
+    pub fn accept_position(&mut self, position: Point3<f32>) {
+        if is_finite(&position) {
+            self.last_position = Some(position);
+        }
+    }
+}
+
+/// Limits how often something can happen with a token bucket.
+///
+/// The bucket holds up to `burst` tokens and is refilled with `rate` tokens per
+/// second. Every action takes one token.
+#[derive(Clone, Copy, Debug, Default)]
+struct RateLimiter {
+    tokens: f32,
+
+    /// `None` if the bucket was never used, which means that it's full.
+    last_refill: Option<Instant>,
+}
+
+impl RateLimiter {
+    /// Takes a token if there is one.
+    fn try_acquire(&mut self, now: Instant, rate: f32, burst: f32) -> bool {
+        let tokens = self.last_refill.map_or(burst, |last_refill| {
+            let elapsed = now.saturating_duration_since(last_refill).as_secs_f32();
+            (self.tokens + rate * elapsed).min(burst)
+        });
+        self.last_refill = Some(now);
+
+        if tokens >= 1.0 {
+            self.tokens = tokens - 1.0;
+            true
+        }
+        else {
+            self.tokens = tokens;
+            false
+        }
+    }
+}
+
+/// Checks a movement from `from` to `to` during a tick of length `dt` (in
+/// seconds).
+///
+/// The limits are checked with `!(distance <= max)`, so that a NaN distance is
+/// rejected too.
+#[allow(clippy::neg_cmp_op_on_partial_ord)]
+fn check_movement(
+    from: &Point3<f32>,
+    to: &Point3<f32>,
+    dt: f32,
+    config: &ValidationConfig,
+) -> Result<(), Violation> {
+    if !is_finite(to) {
+        return Err(Violation::NonFinitePosition { position: *to });
+    }
+
+    let delta = to - from;
+
+    let distance = delta.norm();
+    if !(distance <= config.max_teleport_distance) {
+        return Err(Violation::Teleported {
+            distance,
+            max_distance: config.max_teleport_distance,
+        });
+    }
+
+    let horizontal = Vector3::new(delta.x, 0.0, delta.z).norm();
+    let max_horizontal = config.max_horizontal_speed * dt + MOVEMENT_TOLERANCE;
+    if !(horizontal <= max_horizontal) {
+        return Err(Violation::TooFastHorizontally {
+            distance: horizontal,
+            max_distance: max_horizontal,
+        });
+    }
+
+    let vertical = delta.y.abs();
+    let max_vertical = config.max_vertical_speed * dt + MOVEMENT_TOLERANCE;
+    if !(vertical <= max_vertical) {
+        return Err(Violation::TooFastVertically {
+            distance: vertical,
+            max_distance: max_vertical,
+        });
+    }
+
+    Ok(())
+}
+
+fn is_finite(position: &Point3<f32>) -> bool {
+    position.iter().all(|x| x.is_finite())
+}
+
+fn validate_movement(
+    time: Res<Time>,
+    config: Res<ValidationConfig>,
+    entities: Populated<(Entity, &mut LocalTransform, &mut ClientValidation)>,
+) {
+    let dt = time.delta_seconds();
+
+    for (entity, mut transform, mut validation) in entities {
+        let position = transform.position();
+
+        let Some(last_position) = validation.last_position
+        else {
+            if is_finite(&position) {
+                validation.last_position = Some(position);
+            }
+            else {
+                // there's nowhere to put it back to, but it must not become the
+                // position that later movement is checked against
+                let violation = Violation::NonFinitePosition { position };
+                tracing::warn!(%entity, %violation, "movement rejected");
+                validation.num_violations += 1;
+            }
+            continue;
+        };
+
+        if position == last_position {
+            continue;
+        }
+
+        match check_movement(&last_position, &position, dt, &config) {
+            Ok(()) => {
+                validation.last_position = Some(position);
+            }
+            Err(violation) => {
+                tracing::warn!(%entity, %violation, "movement rejected");
+                validation.num_violations += 1;
+                transform.isometry.translation.vector = last_position.coords;
+            }
+        }
+    }
+}
+
+/// Checks whether entities are allowed to do what they're asking for.
+#[derive(SystemParam)]
+pub struct ActionValidator<'w, 's> {
+    time: Res<'w, Time>,
+    config: Res<'w, ValidationConfig>,
+    reach: Reach<'w, 's>,
+    clients: Query<'w, 's, &'static mut ClientValidation>,
+}
+
+impl<'w, 's> ActionValidator<'w, 's> {
+    /// Checks whether the entity can edit the block at `position`, e.g. break
+    /// or use it. Rejected edits are logged and must not be applied.
+    pub fn check_edit(&mut self, entity: Entity, position: Point3<i32>) -> Result<(), Violation> {
+        let result = self.check_edit_inner(entity, position);
+
+        if let Err(violation) = &result {
+            tracing::warn!(%entity, %violation, "edit rejected");
+
+            if let Ok(mut validation) = self.clients.get_mut(entity) {
+                validation.num_violations += 1;
+            }
+        }
+
+        result
+    }
+
+    #[allow(clippy::neg_cmp_op_on_partial_ord)]
+    fn check_edit_inner(&mut self, entity: Entity, position: Point3<i32>) -> Result<(), Violation> {
+        let reach = self.reach.of(entity);
+        let distance = self
+            .reach
+            .distance_to_block(entity, position)
+            .ok_or(Violation::NoPosition { position })?;
+        if !(distance <= reach) {
+            return Err(Violation::OutOfReach {
+                position,
+                distance,
+                reach,
+            });
+        }
+
+        if let Ok(mut validation) = self.clients.get_mut(entity)
+            && !validation.edits.try_acquire(
+                self.time.tick_start,
+                self.config.edits_per_second,
+                self.config.edit_burst,
+            )
+        {
+            return Err(Violation::RateLimited);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{
+        Duration,
+        Instant,
+    };
+
+    use nalgebra::Point3;
+
+    use crate::game::validation::{
+        RateLimiter,
+        ValidationConfig,
+        Violation,
+        check_movement,
+    };
+
+    #[test]
+    fn it_limits_the_rate() {
+        let start = Instant::now();
+        let mut limiter = RateLimiter::default();
+
+        // a full bucket allows a burst
+        assert!((0..3).all(|_| limiter.try_acquire(start, 1.0, 3.0)));
+        assert!(!limiter.try_acquire(start, 1.0, 3.0));
+
+        // and is refilled over time
+        let later = start + Duration::from_millis(1500);
+        assert!(limiter.try_acquire(later, 1.0, 3.0));
+        assert!(!limiter.try_acquire(later, 1.0, 3.0));
+    }
+
+    #[test]
+    fn it_rejects_fast_movement() {
+        let config = ValidationConfig {
+            max_horizontal_speed: 10.0,
+            max_vertical_speed: 50.0,
+            max_teleport_distance: 8.0,
+            ..Default::default()
+        };
+        let origin = Point3::origin();
+
+        assert_eq!(
+            check_movement(&origin, &Point3::new(1.0, -2.0, 0.0), 0.1, &config),
+            Ok(())
+        );
+        assert!(matches!(
+            check_movement(&origin, &Point3::new(2.0, 0.0, 0.0), 0.1, &config),
+            Err(Violation::TooFastHorizontally { .. })
+        ));
+        assert!(matches!(
+            check_movement(&origin, &Point3::new(0.0, 6.0, 0.0), 0.1, &config),
+            Err(Violation::TooFastVertically { .. })
+        ));
+        assert!(matches!(
+            check_movement(&origin, &Point3::new(0.0, 0.0, 9.0), 10.0, &config),
+            Err(Violation::Teleported { .. })
+        ));
+    }
+
+    #[test]
+    fn it_rejects_non_finite_positions() {
+        let config = ValidationConfig::default();
+        let origin = Point3::origin();
+
+        for to in [
+            Point3::new(f32::NAN, 0.0, 0.0),
+            Point3::new(0.0, f32::INFINITY, 0.0),
+            Point3::new(0.0, 0.0, f32::NEG_INFINITY),
+        ] {
+            assert!(matches!(
+                check_movement(&origin, &to, 0.1, &config),
+                Err(Violation::NonFinitePosition { .. })
+            ));
+        }
+
+        // a NaN limit doesn't let anything through either
+        let config = ValidationConfig {
+            max_teleport_distance: f32::NAN,
+            ..config
+        };
+        assert!(matches!(
+            check_movement(&origin, &Point3::new(0.1, 0.0, 0.0), 0.1, &config),
+            Err(Violation::Teleported { .. })
+        ));
+    }
+}
@@ -0,0 +1,481 @@
+//! Waypoints mark positions in the world.
+//!
+//! They're shown on the compass at the top of the screen and, if they have
+//! one, with a beacon beam in the world. Waypoints are set with the `waypoint`
+//! command and are stored in the world file.
+
+use std::f32::consts::{
+    PI,
+    TAU,
+};
+
+use bevy_ecs::{
+    component::Component,
+    entity::Entity,
+    hierarchy::ChildOf,
+    lifecycle::RemovedComponents,
+    name::Name,
+    query::{
+        Added,
+        With,
+    },
+    resource::Resource,
+    schedule::{
+        IntoScheduleConfigs,
+        common_conditions::{
+            any_with_component,
+            not,
+            resource_exists,
+        },
+    },
+    system::{
+        Commands,
+        Populated,
+        Query,
+        Res,
+        ResMut,
+        Single,
+    },
+    world::Mut,
+};
+use color_eyre::eyre::Error;
+use image::{
+    Rgba,
+    RgbaImage,
+};
+use nalgebra::{
+    Point3,
+    UnitQuaternion,
+    Vector2,
+    Vector3,
+};
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use taffy::prelude::TaffyAuto;
+
+use crate::{
+    ecs::{
+        plugin::{
+            Plugin,
+            WorldBuilder,
+        },
+        schedule,
+        transform::{
+            GlobalTransform,
+            LocalTransform,
+        },
+    },
+    game::{
+        Player,
+        file::WorldFile,
+    },
+    render::{
+        DefaultAtlas,
+        DefaultFont,
+        RenderSystems,
+        atlas::AtlasHandle,
+        billboard::Billboard,
+        staging::Staging,
+        text::{
+            Text,
+            TextSize,
+        },
+    },
+    ui::{
+        Background,
+        FinalLayout,
+        LayoutCache,
+        PaletteRole,
+        Sprites,
+        Style,
+        ThemeColor,
+        View,
+    },
+    wgpu::WgpuContext,
+};
+
+/// Key under which waypoints are stored in the world file.
+const WORLD_FILE_KEY: &str = "waypoints";
+
+const PIXEL_SIZE: f32 = 2.0;
+
+/// Width of the compass in logical pixels.
+const COMPASS_WIDTH: f32 = 200.0;
+
+/// Angle that the compass covers. Markers outside of it are hidden.
+const COMPASS_ANGLE: f32 = PI;
+
+const BEACON_WIDTH: f32 = 0.25;
+const BEACON_HEIGHT: f32 = 256.0;
+const BEACON_COLOR: Rgba<u8> = Rgba([255, 215, 0, 255]);
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct WaypointPlugin;
+
+impl Plugin for WaypointPlugin {
+    fn setup(&self, builder: &mut WorldBuilder) -> Result<(), Error> {
+        builder
+            .add_systems(
+                schedule::Startup,
+                (
+                    load_waypoints.run_if(resource_exists::<WorldFile>),
+                    create_beacon_texture.after(RenderSystems::Setup),
+                ),
+            )
+            .add_systems(
+                schedule::Update,
+                (
+                    spawn_compass.run_if(not(any_with_component::<Compass>)),
+                    (spawn_beacons, update_compass_markers, update_compass).chain(),
+                )
+                    .chain(),
+            )
+            .add_systems(
+                schedule::Shutdown,
+                save_waypoints.run_if(resource_exists::<WorldFile>),
+            );
+
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, Component)]
+pub struct Waypoint {
+    pub name: String,
+
+    /// Show a beacon beam at the waypoint.
+    pub beacon: bool,
+}
+
+impl Waypoint {
+    /// Spawns a waypoint entity at `position`.
+    pub fn spawn(self, position: Point3<f32>, commands: &mut Commands) -> Entity {
+        commands
+            .spawn((
+                Name::new(format!("waypoint_{}", self.name)),
+                LocalTransform::from(position),
+                self,
+            ))
+            .id()
+    }
+}
+
+/// How waypoints are stored in the world file.
+#[derive(Debug, Serialize, Deserialize)]
+struct WaypointData {
+    name: String,
+    position: Point3<f32>,
+    beacon: bool,
+}
+
+fn load_waypoints(world_file: Res<WorldFile>, mut commands: Commands) {
+    let waypoints: Vec<WaypointData> = match world_file.load_state(WORLD_FILE_KEY) {
+        Ok(waypoints) => waypoints.unwrap_or_default(),
+        Err(error) => {
+            tracing::error!(?error, "could not load waypoints");
+            return;
+        }
+    };
+
+    tracing::debug!(count = waypoints.len(), "loading waypoints");
+
+    for data in waypoints {
+        Waypoint {
+            name: data.name,
+            beacon: data.beacon,
+        }
+        .spawn(data.position, &mut commands);
+    }
+}
+
+fn save_waypoints(world_file: Res<WorldFile>, waypoints: Query<(&Waypoint, &LocalTransform)>) {
+    let waypoints = waypoints
+        .iter()
+        .map(|(waypoint, transform)| {
+            WaypointData {
+                name: waypoint.name.clone(),
+                position: transform.isometry.translation.vector.into(),
+                beacon: waypoint.beacon,
+            }
+        })
+        .collect::<Vec<_>>();
+
+    tracing::debug!(count = waypoints.len(), "saving waypoints");
+
+    if let Err(error) = world_file.store_state(WORLD_FILE_KEY, &waypoints) {
+        tracing::error!(?error, "could not save waypoints");
+    }
+}
+
+/// Texture of the beacon beams.
+#[derive(Debug, Resource)]
+struct BeaconTexture(AtlasHandle);
+
+fn create_beacon_texture(
+    wgpu: Res<WgpuContext>,
+    mut atlas: ResMut<DefaultAtlas>,
+    mut staging: ResMut<Staging>,
+    mut commands: Commands,
+) {
+    let image = RgbaImage::from_pixel(1, 1, BEACON_COLOR);
+
+    match atlas.insert_image(&image, None, &wgpu.device, &mut staging) {
+        Ok(texture) => commands.insert_resource(BeaconTexture(texture)),
+        Err(error) => tracing::error!(?error, "could not create beacon texture"),
+    }
+}
+
+/// The beam is a child of the waypoint, so it's despawned with it.
+fn spawn_beacons(
+    beacon_texture: Option<Res<BeaconTexture>>,
+    waypoints: Populated<(Entity, &Waypoint), Added<Waypoint>>,
+    mut commands: Commands,
+) {
+    let Some(beacon_texture) = beacon_texture
+    else {
+        return;
+    };
+
+    for (entity, waypoint) in waypoints {
+        if waypoint.beacon {
+            commands.spawn((
+                Name::new("beacon"),
+                Billboard::new(
+                    beacon_texture.0.clone(),
+                    Vector2::new(BEACON_WIDTH, BEACON_HEIGHT),
+                )
+                .upright(),
+                // the billboard is centered on the entity
+                LocalTransform::from(Vector3::new(0.0, 0.5 * BEACON_HEIGHT, 0.0)),
+                ChildOf(entity),
+            ));
+        }
+    }
+}
+
+/// The strip at the top of the screen that shows the directions.
+#[derive(Clone, Copy, Debug, Component)]
+struct Compass;
+
+#[derive(Clone, Copy, Debug, Component)]
+struct CompassMarker {
+    target: CompassTarget,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum CompassTarget {
+    /// A direction in the world, e.g. north.
+    Direction(Vector3<f32>),
+
+    Waypoint(Entity),
+}
+
+fn spawn_compass(
+    view: Single<Entity, With<View>>,
+    sprites: Res<Sprites>,
+    font: Res<DefaultFont>,
+    waypoints: Query<(Entity, &Waypoint)>,
+    mut commands: Commands,
+) {
+    let sprite = &sprites["panel"];
+    let padding = sprite.padding.map_or(Vector2::<f32>::zeros(), |padding| {
+        Vector2::new(padding.left + padding.right, padding.top + padding.bottom).cast::<f32>()
+    });
+    let line_height = font.glyph_displacement().y;
+
+    let mut style = Style::default();
+    style.position = taffy::Position::Absolute;
+    style.margin = taffy::Rect {
+        left: taffy::LengthPercentageAuto::AUTO,
+        right: taffy::LengthPercentageAuto::AUTO,
+        top: taffy::LengthPercentageAuto::length(0.0),
+        bottom: taffy::LengthPercentageAuto::AUTO,
+    };
+    style.size = taffy::Size::from_lengths(
+        (COMPASS_WIDTH + padding.x) * PIXEL_SIZE,
+        (line_height + padding.y) * PIXEL_SIZE,
+    );
+    if let Some(padding) = sprite.padding(PIXEL_SIZE) {
+        style.padding = padding;
+    }
+
+    // the sun rises in the east, which is +x. see `celestial.rs`
+    let directions = [
+        ("N", Vector3::z()),
+        ("E", Vector3::x()),
+        ("S", -Vector3::z()),
+        ("W", -Vector3::x()),
+    ];
+
+    commands
+        .spawn((
+            Name::new("compass"),
+            style,
+            Background {
+                sprite: sprite.clone(),
+                pixel_size: PIXEL_SIZE,
+            },
+            Compass,
+            ChildOf(*view),
+        ))
+        .with_children(|compass| {
+            for (label, direction) in directions {
+                compass.spawn(compass_marker(
+                    label.to_owned(),
+                    CompassTarget::Direction(direction),
+                    PaletteRole::Text,
+                ));
+            }
+        });
+
+    // markers for waypoints are added when the waypoints are spawned, but they
+    // might have been spawned before the compass.
+    for (entity, waypoint) in waypoints {
+        commands
+            .spawn(compass_marker(
+                waypoint.name.clone(),
+                CompassTarget::Waypoint(entity),
+                PaletteRole::Series(0),
+            ))
+            .insert(ChildOf(*view));
+    }
+}
+
+fn compass_marker(
+    label: String,
+    target: CompassTarget,
+    role: PaletteRole,
+) -> (Name, Text, TextSize, ThemeColor, Style, CompassMarker) {
+    let mut style = Style::default();
+    style.position = taffy::Position::Absolute;
+    // hidden until its position is known
+    style.display = taffy::Display::None;
+
+    (
+        Name::new(format!("compass_marker_{label}")),
+        Text::from(label),
+        TextSize {
+            scaling: PIXEL_SIZE,
+        },
+        ThemeColor::from(role),
+        style,
+        CompassMarker { target },
+    )
+}
+
+/// Adds and removes markers for waypoints.
+fn update_compass_markers(
+    compass: Single<Entity, With<Compass>>,
+    waypoints: Query<(Entity, &Waypoint), Added<Waypoint>>,
+    mut removed_waypoints: RemovedComponents<Waypoint>,
+    markers: Query<(Entity, &CompassMarker)>,
+    mut commands: Commands,
+) {
+    for (entity, waypoint) in &waypoints {
+        commands.spawn((
+            compass_marker(
+                waypoint.name.clone(),
+                CompassTarget::Waypoint(entity),
+                PaletteRole::Series(0),
+            ),
+            ChildOf(*compass),
+        ));
+    }
+
+    for removed in removed_waypoints.read() {
+        for (entity, marker) in &markers {
+            if marker.target == CompassTarget::Waypoint(removed) {
+                commands.entity(entity).despawn();
+            }
+        }
+    }
+}
+
+/// Moves the markers on the compass to the direction of their targets,
+/// relative to where the player is looking.
+fn update_compass(
+    player: Single<&GlobalTransform, With<Player>>,
+    compass: Single<&FinalLayout, With<Compass>>,
+    waypoints: Query<&GlobalTransform, With<Waypoint>>,
+    markers: Query<(&CompassMarker, &FinalLayout, Mut<Style>, Mut<LayoutCache>)>,
+) {
+    let player = player.into_inner();
+
+    for (marker, marker_layout, mut style, mut layout_cache) in markers {
+        let direction = match marker.target {
+            CompassTarget::Direction(direction) => direction,
+            CompassTarget::Waypoint(entity) => {
+                let Ok(waypoint) = waypoints.get(entity)
+                else {
+                    continue;
+                };
+                waypoint.isometry.translation.vector - player.isometry.translation.vector
+            }
+        };
+
+        let angle = compass_angle(&player.isometry.rotation, &direction);
+
+        let (display, left) = if angle.abs() <= 0.5 * COMPASS_ANGLE {
+            let left = compass.padding.left
+                + compass.content_box_width() * (0.5 + angle / COMPASS_ANGLE)
+                - 0.5 * marker_layout.size.width;
+            (
+                taffy::Display::Block,
+                taffy::LengthPercentageAuto::length(left.round()),
+            )
+        }
+        else {
+            (taffy::Display::None, taffy::LengthPercentageAuto::AUTO)
+        };
+
+        // only touch the style if it changes, since this triggers a new layout
+        if style.display != display || style.inset.left != left {
+            style.display = display;
+            style.inset.left = left;
+            style.inset.top = taffy::LengthPercentageAuto::length(compass.padding.top);
+            layout_cache.clear();
+        }
+    }
+}
+
+/// Angle of `direction` to the right of the view direction of a camera with
+/// this rotation. The camera looks along +z, and +x is to its right.
+fn compass_angle(rotation: &UnitQuaternion<f32>, direction: &Vector3<f32>) -> f32 {
+    let local = rotation.inverse_transform_vector(direction);
+    wrap_angle(local.x.atan2(local.z))
+}
+
+/// Wraps an angle into `-PI..=PI`.
+fn wrap_angle(angle: f32) -> f32 {
+    let angle = angle.rem_euclid(TAU);
+    if angle > PI { angle - TAU } else { angle }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::FRAC_PI_2;
+
+    use nalgebra::{
+        UnitQuaternion,
+        Vector3,
+    };
+
+    use crate::game::waypoint::compass_angle;
+
+    #[test]
+    fn it_measures_compass_angles_relative_to_the_view_direction() {
+        // looking along +x, so +z is to the left and -z to the right
+        let rotation = UnitQuaternion::from_axis_angle(&Vector3::y_axis(), FRAC_PI_2);
+
+        let ahead = compass_angle(&rotation, &Vector3::x());
+        assert!(ahead.abs() < 1e-5, "ahead = {ahead}");
+
+        let left = compass_angle(&rotation, &Vector3::z());
+        assert!((left + FRAC_PI_2).abs() < 1e-5, "left = {left}");
+
+        let right = compass_angle(&rotation, &-Vector3::z());
+        assert!((right - FRAC_PI_2).abs() < 1e-5, "right = {right}");
+    }
+}
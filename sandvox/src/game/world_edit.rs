@@ -0,0 +1,273 @@
+//! WorldEdit-style region editing.
+//!
+//! The player selects a region by marking two corners, either with the
+//! [position keys][WorldEditConfig::pos1_key] on the block they're looking at,
+//! or with the `region pos1` and `region pos2` RCON commands. The region
+//! commands then fill, replace, copy and paste blocks through
+//! [`ChunkEditBatch`]es, and are recorded in the player's
+//! [`EditHistory`][crate::game::edit_history::EditHistory].
+//!
+//! The selection is shown as a translucent box.
+
+use bevy_ecs::{
+    component::Component,
+    entity::Entity,
+    name::Name,
+    query::With,
+    resource::Resource,
+    schedule::{
+        IntoScheduleConfigs,
+        SystemCondition,
+        common_conditions::resource_changed,
+    },
+    system::{
+        Commands,
+        Query,
+        Res,
+        ResMut,
+        Single,
+    },
+};
+use color_eyre::eyre::Error;
+use nalgebra::{
+    Point3,
+    Vector3,
+};
+use palette::{
+    Srgba,
+    WithAlpha,
+};
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use winit::keyboard::KeyCode;
+
+use crate::{
+    app::GrabCursor,
+    ecs::{
+        plugin::{
+            Dependencies,
+            Plugin,
+            WorldBuilder,
+        },
+        schedule,
+        transform::{
+            GlobalTransform,
+            LocalTransform,
+        },
+    },
+    game::{
+        Player,
+        interaction::{
+            InteractionPlugin,
+            TargetedBlock,
+            use_target,
+        },
+        schematic::Schematic,
+    },
+    input::Keys,
+    render::{
+        outline::Outline,
+        render_target::RenderTarget,
+    },
+};
+
+/// The selection box is slightly larger than the region, so that it doesn't
+/// z-fight with the blocks' faces.
+const SELECTION_PADDING: f32 = 0.01;
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct WorldEditPlugin {
+    pub config: WorldEditConfig,
+}
+
+impl Plugin for WorldEditPlugin {
+    fn dependencies(&self, dependencies: &mut Dependencies) -> Result<(), Error> {
+        dependencies.require_plugin::<InteractionPlugin>();
+        Ok(())
+    }
+
+    fn setup(&self, builder: &mut WorldBuilder) -> Result<(), Error> {
+        builder
+            .insert_resource(self.config)
+            .init_resource::<Selection>()
+            .init_resource::<Clipboard>()
+            .add_systems(schedule::Startup, spawn_selection_box)
+            .add_systems(
+                schedule::Update,
+                (
+                    select_with_keys,
+                    update_selection_box.run_if(
+                        resource_changed::<Selection>.or(resource_changed::<WorldEditConfig>),
+                    ),
+                )
+                    .chain()
+                    .after(use_target),
+            );
+
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize, Resource)]
+pub struct WorldEditConfig {
+    /// Key to mark the block that the player is looking at as the first
+    /// corner of the selection.
+    #[serde(default = "default_pos1_key")]
+    pub pos1_key: KeyCode,
+
+    /// Key to mark the block that the player is looking at as the second
+    /// corner of the selection.
+    #[serde(default = "default_pos2_key")]
+    pub pos2_key: KeyCode,
+
+    /// Color of the selection box's edges.
+    #[serde(default = "default_selection_color")]
+    pub color: Srgba<f32>,
+
+    /// Color of the selection box's faces.
+    #[serde(default = "default_selection_fill")]
+    pub fill: Srgba<f32>,
+}
+
+impl Default for WorldEditConfig {
+    fn default() -> Self {
+        Self {
+            pos1_key: default_pos1_key(),
+            pos2_key: default_pos2_key(),
+            color: default_selection_color(),
+            fill: default_selection_fill(),
+        }
+    }
+}
+
+fn default_pos1_key() -> KeyCode {
+    KeyCode::BracketLeft
+}
+
+fn default_pos2_key() -> KeyCode {
+    KeyCode::BracketRight
+}
+
+fn default_selection_color() -> Srgba<f32> {
+    palette::named::ORANGE.into_format().with_alpha(0.8)
+}
+
+fn default_selection_fill() -> Srgba<f32> {
+    palette::named::ORANGE.into_format().with_alpha(0.15)
+}
+
+/// The two corners of the selected region.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Resource)]
+pub struct Selection {
+    pub pos1: Option<Point3<i32>>,
+    pub pos2: Option<Point3<i32>>,
+}
+
+impl Selection {
+    /// Minimum and maximum corner (both inclusive), if both corners are set.
+    pub fn region(&self) -> Option<(Point3<i32>, Point3<i32>)> {
+        let pos1 = self.pos1?;
+        let pos2 = self.pos2?;
+        Some((pos1.inf(&pos2), pos1.sup(&pos2)))
+    }
+}
+
+/// Blocks copied with `region copy`.
+#[derive(Clone, Debug, Default, Resource)]
+pub struct Clipboard(pub Option<Schematic>);
+
+/// Marks the entity that shows the [`Selection`].
+#[derive(Clone, Copy, Debug, Component)]
+struct SelectionBox;
+
+fn select_with_keys(
+    player: Single<&RenderTarget, With<Player>>,
+    windows: Query<&Keys, With<GrabCursor>>,
+    config: Res<WorldEditConfig>,
+    targeted_block: Res<TargetedBlock>,
+    mut selection: ResMut<Selection>,
+) {
+    let Ok(keys) = windows.get(player.0)
+    else {
+        return;
+    };
+
+    let Some(hit) = targeted_block.0
+    else {
+        return;
+    };
+
+    if keys.just_pressed.contains(&config.pos1_key) {
+        tracing::info!(position = ?hit.block, "selection pos1 set");
+        selection.pos1 = Some(hit.block);
+    }
+
+    if keys.just_pressed.contains(&config.pos2_key) {
+        tracing::info!(position = ?hit.block, "selection pos2 set");
+        selection.pos2 = Some(hit.block);
+    }
+}
+
+fn spawn_selection_box(mut commands: Commands) {
+    commands.spawn((
+        Name::new("selection box"),
+        SelectionBox,
+        GlobalTransform::identity(),
+    ));
+}
+
+fn update_selection_box(
+    selection_box: Single<(Entity, &mut GlobalTransform), With<SelectionBox>>,
+    selection: Res<Selection>,
+    config: Res<WorldEditConfig>,
+    mut commands: Commands,
+) {
+    let (entity, mut transform) = selection_box.into_inner();
+
+    // with only one corner set, that block is shown
+    let corners = selection
+        .pos1
+        .or(selection.pos2)
+        .zip(selection.pos2.or(selection.pos1));
+
+    if let Some((pos1, pos2)) = corners {
+        let min = pos1.inf(&pos2).cast::<f32>();
+        let max = pos1.sup(&pos2).cast::<f32>() + Vector3::repeat(1.0);
+
+        let center = nalgebra::center(&min, &max);
+        *transform = LocalTransform::from(center).into();
+
+        commands.entity(entity).insert(Outline {
+            size: max - min + Vector3::repeat(2.0 * SELECTION_PADDING),
+            color: config.color,
+            fill: Some(config.fill),
+        });
+    }
+    else {
+        commands.entity(entity).remove::<Outline>();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::Point3;
+
+    use crate::game::world_edit::Selection;
+
+    #[test]
+    fn it_orders_the_selection_corners() {
+        let mut selection = Selection {
+            pos1: Some(Point3::new(4, -2, 0)),
+            pos2: None,
+        };
+        assert_eq!(selection.region(), None);
+
+        selection.pos2 = Some(Point3::new(-1, 3, 0));
+        assert_eq!(
+            selection.region(),
+            Some((Point3::new(-1, -2, 0), Point3::new(4, 3, 0)))
+        );
+    }
+}
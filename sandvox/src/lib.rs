@@ -2,12 +2,18 @@
 #![feature(allocator_api)]
 
 pub mod app;
+pub mod assets;
+pub mod bench;
 pub mod build_info;
 pub mod collide;
 pub mod config;
+pub mod crash;
 pub mod ecs;
 pub mod game;
 pub mod input;
+pub mod logging;
+pub mod net;
+pub mod paths;
 pub mod profiler;
 #[cfg(feature = "rcon")]
 pub mod rcon;
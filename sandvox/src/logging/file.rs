@@ -0,0 +1,495 @@
+//! Writes log records to a file.
+//!
+//! Every run gets its own log file in the configured directory. When a file
+//! gets too big, logging continues in a new one, and only the most recent
+//! files are kept.
+//!
+//! The [`LogFileLayer`] is installed in the tracing subscriber before the
+//! config is loaded, so it doesn't log anything until [`open_log_file`] is
+//! called.
+
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{
+        LineWriter,
+        Write,
+    },
+    path::{
+        Path,
+        PathBuf,
+    },
+};
+
+use chrono::Utc;
+use color_eyre::eyre::Error;
+use parking_lot::{
+    Mutex,
+    RwLock,
+};
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use tracing::{
+    Event,
+    Metadata,
+    Subscriber,
+    field::{
+        Field,
+        Visit,
+    },
+    level_filters::LevelFilter,
+    subscriber::Interest,
+};
+use tracing_subscriber::{
+    Layer,
+    filter::Targets,
+    layer::{
+        Context,
+        Filter,
+    },
+    registry::LookupSpan,
+};
+
+use crate::{
+    logging::MessageVisitor,
+    paths::Paths,
+};
+
+static LOG_FILE: Mutex<Option<LogFile>> = parking_lot::const_mutex(None);
+
+/// Levels of the open log file. This is separate from [`LOG_FILE`], because
+/// it's checked for every event.
+static LOG_FILE_FILTER: RwLock<Option<LogFileFilter>> = parking_lot::const_rwlock(None);
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LogFileConfig {
+    /// Relative to the data directory.
+    #[serde(default = "default_directory")]
+    pub directory: PathBuf,
+
+    #[serde(default)]
+    pub format: LogFormat,
+
+    /// Records from targets without a level in [`targets`][Self::targets]
+    /// are logged if they're at least this severe.
+    #[serde(default = "default_level")]
+    pub level: LogLevel,
+
+    /// Levels for targets and their submodules, e.g. `wgpu_core = "warn"`.
+    #[serde(default)]
+    pub targets: BTreeMap<String, LogLevel>,
+
+    /// Logging continues in a new file once a file is this big (in bytes).
+    #[serde(default = "default_max_file_size")]
+    pub max_file_size: u64,
+
+    /// Older log files are deleted, so that at most this many are kept.
+    #[serde(default = "default_max_files")]
+    pub max_files: usize,
+}
+
+impl Default for LogFileConfig {
+    fn default() -> Self {
+        Self {
+            directory: default_directory(),
+            format: Default::default(),
+            level: default_level(),
+            targets: Default::default(),
+            max_file_size: default_max_file_size(),
+            max_files: default_max_files(),
+        }
+    }
+}
+
+fn default_directory() -> PathBuf {
+    PathBuf::from("logs")
+}
+
+fn default_level() -> LogLevel {
+    LogLevel::Info
+}
+
+fn default_max_file_size() -> u64 {
+    // 16 MiB
+    0x1_000_000
+}
+
+fn default_max_files() -> usize {
+    10
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LogFormat {
+    /// One line of text per record.
+    #[default]
+    Compact,
+
+    /// One JSON object per line.
+    Json,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LogLevel {
+    Off,
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl From<LogLevel> for LevelFilter {
+    fn from(value: LogLevel) -> Self {
+        match value {
+            LogLevel::Off => LevelFilter::OFF,
+            LogLevel::Error => LevelFilter::ERROR,
+            LogLevel::Warn => LevelFilter::WARN,
+            LogLevel::Info => LevelFilter::INFO,
+            LogLevel::Debug => LevelFilter::DEBUG,
+            LogLevel::Trace => LevelFilter::TRACE,
+        }
+    }
+}
+
+/// Starts logging to a new file.
+///
+/// Nothing happens if a file with the same config is already open, e.g. when
+/// the app is recreated after a GPU reset.
+pub fn open_log_file(config: &LogFileConfig, paths: &Paths) -> Result<(), Error> {
+    let config = LogFileConfig {
+        directory: paths.data_file(&config.directory),
+        ..config.clone()
+    };
+
+    let mut log_file = LOG_FILE.lock();
+    if log_file
+        .as_ref()
+        .is_some_and(|log_file| log_file.config == config)
+    {
+        return Ok(());
+    }
+
+    let stem = format!("sandvox-{}", Utc::now().format("%Y%m%d-%H%M%S"));
+    *log_file = Some(LogFile::open(config.clone(), stem)?);
+    drop(log_file);
+
+    *LOG_FILE_FILTER.write() = Some(LogFileFilter::new(&config));
+
+    // callsites that were disabled before might be enabled now
+    tracing::callsite::rebuild_interest_cache();
+
+    Ok(())
+}
+
+/// Stops logging to a file.
+pub fn close_log_file() {
+    *LOG_FILE_FILTER.write() = None;
+    *LOG_FILE.lock() = None;
+    tracing::callsite::rebuild_interest_cache();
+}
+
+#[derive(Debug)]
+struct LogFile {
+    config: LogFileConfig,
+
+    /// File name without the index and extension.
+    stem: String,
+
+    /// Index of the current file. Every run starts at 0, and it's incremented
+    /// when the file gets too big.
+    index: usize,
+
+    writer: LineWriter<File>,
+    size: u64,
+}
+
+impl LogFile {
+    fn open(config: LogFileConfig, stem: String) -> Result<Self, Error> {
+        std::fs::create_dir_all(&config.directory)?;
+        let writer = LineWriter::new(File::create(log_file_path(&config.directory, &stem, 0))?);
+
+        let log_file = Self {
+            config,
+            stem,
+            index: 0,
+            writer,
+            size: 0,
+        };
+        log_file.remove_old_files();
+
+        Ok(log_file)
+    }
+
+    fn write_line(&mut self, line: &str) -> Result<(), Error> {
+        if self.size > 0 && self.size + line.len() as u64 > self.config.max_file_size {
+            self.index += 1;
+            self.writer = LineWriter::new(File::create(log_file_path(
+                &self.config.directory,
+                &self.stem,
+                self.index,
+            ))?);
+            self.size = 0;
+            self.remove_old_files();
+        }
+
+        self.writer.write_all(line.as_bytes())?;
+        self.size += line.len() as u64;
+
+        Ok(())
+    }
+
+    /// Deletes the oldest log files, so that at most `max_files` are left.
+    fn remove_old_files(&self) {
+        // the names sort by when the files were created
+        let mut paths = std::fs::read_dir(&self.config.directory)
+            .into_iter()
+            .flatten()
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter_map(|path| {
+                let key = log_file_sort_key(&path)?;
+                Some((key, path))
+            })
+            .collect::<Vec<_>>();
+        paths.sort();
+
+        let num_removed = paths.len().saturating_sub(self.config.max_files.max(1));
+        for (_, path) in &paths[..num_removed] {
+            if let Err(error) = std::fs::remove_file(path) {
+                eprintln!("Could not remove old log file {}: {error}", path.display());
+            }
+        }
+    }
+}
+
+fn log_file_path(directory: &Path, stem: &str, index: usize) -> PathBuf {
+    if index == 0 {
+        directory.join(format!("{stem}.log"))
+    }
+    else {
+        directory.join(format!("{stem}.{index}.log"))
+    }
+}
+
+/// Sorts log files by the time in their name and then by their index. Other
+/// files are ignored.
+fn log_file_sort_key(path: &Path) -> Option<(String, usize)> {
+    let name = path.file_name()?.to_str()?;
+    let name = name.strip_prefix("sandvox-")?.strip_suffix(".log")?;
+
+    Some(match name.split_once('.') {
+        Some((time, index)) => (time.to_owned(), index.parse().ok()?),
+        None => (name.to_owned(), 0),
+    })
+}
+
+#[derive(Debug)]
+struct LogFileFilter {
+    targets: Targets,
+    format: LogFormat,
+}
+
+impl LogFileFilter {
+    fn new(config: &LogFileConfig) -> Self {
+        let targets = Targets::new()
+            .with_default(LevelFilter::from(config.level))
+            .with_targets(
+                config
+                    .targets
+                    .iter()
+                    .map(|(target, level)| (target.clone(), LevelFilter::from(*level))),
+            );
+
+        Self {
+            targets,
+            format: config.format,
+        }
+    }
+}
+
+/// Only events are written to the log file.
+fn is_enabled(metadata: &Metadata<'_>) -> bool {
+    metadata.is_event()
+        && LOG_FILE_FILTER.read().as_ref().is_some_and(|filter| {
+            filter
+                .targets
+                .would_enable(metadata.target(), metadata.level())
+        })
+}
+
+/// Writes events to the log file opened with [`open_log_file`].
+///
+/// Install it with [`filtered`][Self::filtered], so that it only gets the
+/// events that the log file's config asks for.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct LogFileLayer;
+
+impl LogFileLayer {
+    /// The layer with its filter, ready to be installed in the subscriber.
+    pub fn filtered<S>(self) -> impl Layer<S>
+    where
+        S: Subscriber + for<'a> LookupSpan<'a>,
+    {
+        self.with_filter(LogFileLayerFilter)
+    }
+}
+
+impl<S> Layer<S> for LogFileLayer
+where
+    S: Subscriber,
+{
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let Some(format) = LOG_FILE_FILTER.read().as_ref().map(|filter| filter.format)
+        else {
+            return;
+        };
+
+        let line = match format {
+            LogFormat::Compact => format_compact(event),
+            LogFormat::Json => format_json(event),
+        };
+
+        if let Some(log_file) = &mut *LOG_FILE.lock()
+            && let Err(error) = log_file.write_line(&line)
+        {
+            // logging this would end up here again
+            eprintln!("Could not write to log file: {error}");
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+struct LogFileLayerFilter;
+
+impl<S> Filter<S> for LogFileLayerFilter {
+    fn enabled(&self, metadata: &Metadata<'_>, _cx: &Context<'_, S>) -> bool {
+        is_enabled(metadata)
+    }
+
+    fn callsite_enabled(&self, metadata: &'static Metadata<'static>) -> Interest {
+        // this is reevaluated when a log file is opened
+        if is_enabled(metadata) {
+            Interest::always()
+        }
+        else {
+            Interest::never()
+        }
+    }
+
+    fn max_level_hint(&self) -> Option<LevelFilter> {
+        let filter = LOG_FILE_FILTER.read();
+        let Some(filter) = &*filter
+        else {
+            return Some(LevelFilter::OFF);
+        };
+
+        filter
+            .targets
+            .default_level()
+            .into_iter()
+            .chain(filter.targets.iter().map(|(_, level)| level))
+            .max()
+    }
+}
+
+fn format_compact(event: &Event<'_>) -> String {
+    let metadata = event.metadata();
+
+    let mut visitor = MessageVisitor::default();
+    event.record(&mut visitor);
+
+    format!(
+        "{} {:>5} {}: {}\n",
+        Utc::now().to_rfc3339(),
+        metadata.level(),
+        metadata.target(),
+        visitor.finish()
+    )
+}
+
+fn format_json(event: &Event<'_>) -> String {
+    let metadata = event.metadata();
+
+    let mut visitor = JsonVisitor::default();
+    event.record(&mut visitor);
+
+    let mut object = serde_json::Map::new();
+    object.insert("time".to_owned(), Utc::now().to_rfc3339().into());
+    object.insert("level".to_owned(), metadata.level().as_str().into());
+    object.insert("target".to_owned(), metadata.target().into());
+    object.insert("fields".to_owned(), visitor.fields.into());
+
+    let mut line = serde_json::Value::Object(object).to_string();
+    line.push('\n');
+    line
+}
+
+#[derive(Debug, Default)]
+struct JsonVisitor {
+    fields: serde_json::Map<String, serde_json::Value>,
+}
+
+impl Visit for JsonVisitor {
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.fields.insert(field.name().to_owned(), value.into());
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.fields.insert(field.name().to_owned(), value.into());
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.fields.insert(field.name().to_owned(), value.into());
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.fields.insert(field.name().to_owned(), value.into());
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.fields.insert(field.name().to_owned(), value.into());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        self.fields
+            .insert(field.name().to_owned(), format!("{value:?}").into());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use crate::logging::file::{
+        log_file_path,
+        log_file_sort_key,
+    };
+
+    #[test]
+    fn it_sorts_rotated_log_files() {
+        let directory = Path::new("logs");
+        let mut paths = vec![
+            log_file_path(directory, "sandvox-20260102-120000", 0),
+            log_file_path(directory, "sandvox-20260101-120000", 10),
+            log_file_path(directory, "sandvox-20260101-120000", 2),
+            log_file_path(directory, "sandvox-20260101-120000", 0),
+        ];
+        paths.sort_by_key(|path| log_file_sort_key(path).unwrap());
+
+        assert_eq!(
+            paths,
+            [
+                Path::new("logs/sandvox-20260101-120000.log"),
+                Path::new("logs/sandvox-20260101-120000.2.log"),
+                Path::new("logs/sandvox-20260101-120000.10.log"),
+                Path::new("logs/sandvox-20260102-120000.log"),
+            ]
+        );
+        assert_eq!(log_file_sort_key(Path::new("logs/notes.txt")), None);
+    }
+}
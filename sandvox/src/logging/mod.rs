@@ -0,0 +1,256 @@
+//! Where log records go, besides the terminal.
+//!
+//! The [`LogBufferLayer`] keeps the most recent records in a ring buffer, which
+//! e.g. is included in crash reports and shown in the in-game log viewer. The
+//! [`LogFileLayer`][file::LogFileLayer] writes them to a log file, if one is
+//! configured. Both must be installed in the tracing subscriber.
+
+pub mod file;
+
+use std::{
+    collections::VecDeque,
+    fmt::{
+        Display,
+        Write,
+    },
+    sync::atomic::{
+        AtomicU64,
+        Ordering,
+    },
+};
+
+use chrono::{
+    DateTime,
+    Utc,
+};
+use parking_lot::Mutex;
+use tracing::{
+    Event,
+    Level,
+    Subscriber,
+    field::{
+        Field,
+        Visit,
+    },
+};
+use tracing_subscriber::{
+    Layer,
+    layer::Context,
+};
+
+/// How many records are kept.
+pub const LOG_BUFFER_CAPACITY: usize = 1000;
+
+static LOG_BUFFER: Mutex<VecDeque<LogRecord>> = parking_lot::const_mutex(VecDeque::new());
+
+static NUM_RECORDS: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Clone, Debug)]
+pub struct LogRecord {
+    pub time: DateTime<Utc>,
+    pub level: Level,
+    pub target: String,
+
+    /// The message, followed by the other fields as `name=value`.
+    pub message: String,
+}
+
+impl Display for LogRecord {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {:>5} {}: {}",
+            self.time.format("%H:%M:%S%.3f"),
+            self.level,
+            self.target,
+            self.message
+        )
+    }
+}
+
+/// Returns the most recent log records, oldest first.
+pub fn recent_logs() -> Vec<LogRecord> {
+    LOG_BUFFER.lock().iter().cloned().collect()
+}
+
+/// Returns the most recent log records that match the filter, oldest first.
+pub fn recent_logs_filtered(filter: &LogFilter) -> Vec<LogRecord> {
+    LOG_BUFFER
+        .lock()
+        .iter()
+        .filter(|record| filter.matches(record))
+        .cloned()
+        .collect()
+}
+
+/// Crates that the records in the buffer came from, sorted by name.
+pub fn recent_crates() -> Vec<String> {
+    let buffer = LOG_BUFFER.lock();
+    let mut crates = buffer
+        .iter()
+        .map(|record| record.target.split("::").next().unwrap_or_default())
+        .collect::<Vec<_>>();
+    crates.sort_unstable();
+    crates.dedup();
+    crates.into_iter().map(ToOwned::to_owned).collect()
+}
+
+/// Number of records logged since the start, including those that were
+/// dropped from the buffer. This can be used to tell if there are new
+/// records.
+pub fn num_records_logged() -> u64 {
+    NUM_RECORDS.load(Ordering::Relaxed)
+}
+
+/// Selects log records by their level and target.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct LogFilter {
+    /// Only records at least this severe match.
+    pub level: Option<Level>,
+
+    /// Only records from this target or its submodules match, e.g. `wgpu_core`
+    /// matches `wgpu_core::device`.
+    pub target: Option<String>,
+}
+
+impl LogFilter {
+    pub fn matches(&self, record: &LogRecord) -> bool {
+        // more severe levels compare as smaller
+        let level_matches = self.level.is_none_or(|level| record.level <= level);
+
+        let target_matches = self.target.as_ref().is_none_or(|target| {
+            record
+                .target
+                .strip_prefix(target.as_str())
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with("::"))
+        });
+
+        level_matches && target_matches
+    }
+}
+
+fn push_record(buffer: &mut VecDeque<LogRecord>, record: LogRecord, capacity: usize) {
+    while buffer.len() >= capacity {
+        buffer.pop_front();
+    }
+    buffer.push_back(record);
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct LogBufferLayer;
+
+impl<S> Layer<S> for LogBufferLayer
+where
+    S: Subscriber,
+{
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let metadata = event.metadata();
+
+        let mut visitor = MessageVisitor::default();
+        event.record(&mut visitor);
+
+        let record = LogRecord {
+            time: Utc::now(),
+            level: *metadata.level(),
+            target: metadata.target().to_owned(),
+            message: visitor.finish(),
+        };
+
+        push_record(&mut LOG_BUFFER.lock(), record, LOG_BUFFER_CAPACITY);
+        NUM_RECORDS.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+#[derive(Debug, Default)]
+struct MessageVisitor {
+    message: String,
+    fields: String,
+}
+
+impl MessageVisitor {
+    fn finish(mut self) -> String {
+        if self.message.is_empty() {
+            self.fields.trim_start().to_owned()
+        }
+        else {
+            self.message.push_str(&self.fields);
+            self.message
+        }
+    }
+}
+
+impl Visit for MessageVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.message.push_str(value);
+        }
+        else {
+            let _ = write!(self.fields, " {}={value:?}", field.name());
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if field.name() == "message" {
+            let _ = write!(self.message, "{value:?}");
+        }
+        else {
+            let _ = write!(self.fields, " {}={value:?}", field.name());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+
+    use chrono::Utc;
+    use tracing::Level;
+
+    use crate::logging::{
+        LogFilter,
+        LogRecord,
+        push_record,
+    };
+
+    fn record(level: Level, target: &str, message: &str) -> LogRecord {
+        LogRecord {
+            time: Utc::now(),
+            level,
+            target: target.to_owned(),
+            message: message.to_owned(),
+        }
+    }
+
+    #[test]
+    fn it_keeps_the_latest_records() {
+        let mut buffer = VecDeque::new();
+        for i in 0..5 {
+            push_record(
+                &mut buffer,
+                record(Level::INFO, "sandvox", &i.to_string()),
+                3,
+            );
+        }
+
+        let messages = buffer
+            .iter()
+            .map(|record| record.message.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(messages, ["2", "3", "4"]);
+    }
+
+    #[test]
+    fn it_filters_by_level_and_target() {
+        let filter = LogFilter {
+            level: Some(Level::WARN),
+            target: Some("wgpu_core".to_owned()),
+        };
+
+        assert!(filter.matches(&record(Level::WARN, "wgpu_core::device", "")));
+        assert!(filter.matches(&record(Level::ERROR, "wgpu_core", "")));
+        assert!(!filter.matches(&record(Level::INFO, "wgpu_core", "")));
+        assert!(!filter.matches(&record(Level::WARN, "wgpu_core_extra", "")));
+        assert!(!filter.matches(&record(Level::WARN, "sandvox", "")));
+        assert!(LogFilter::default().matches(&record(Level::TRACE, "sandvox", "")));
+    }
+}
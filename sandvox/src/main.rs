@@ -5,7 +5,25 @@ use clap::{
 use color_eyre::eyre::Error;
 use sandvox::{
     app::App,
-    wgpu::WgpuContextBuilder,
+    logging::{
+        LogBufferLayer,
+        file::LogFileLayer,
+    },
+    profiler::{
+        capture::ProfileCaptureLayer,
+        systems::SystemTimingsLayer,
+    },
+    wgpu::{
+        AdapterSummary,
+        WgpuContextBuilder,
+        available_adapters,
+    },
+};
+use tracing_subscriber::{
+    Layer,
+    filter::LevelFilter,
+    layer::SubscriberExt,
+    util::SubscriberInitExt,
 };
 
 #[derive(Debug, Parser)]
@@ -18,6 +36,10 @@ pub struct Args {
 enum Command {
     Main(sandvox::app::Args),
     WgpuInfo,
+
+    /// Fly the camera along a path and print frame times, chunk throughput
+    /// and memory usage as JSON.
+    Bench(sandvox::bench::BenchArgs),
 }
 
 impl Default for Command {
@@ -29,7 +51,14 @@ impl Default for Command {
 fn main() -> Result<(), Error> {
     let _ = dotenvy::dotenv();
     color_eyre::install()?;
-    tracing_subscriber::fmt::init();
+    sandvox::crash::install();
+    tracing_subscriber::registry()
+        .with(tracing_subscriber::fmt::layer().with_filter(LevelFilter::INFO))
+        .with(ProfileCaptureLayer.with_filter(LevelFilter::INFO))
+        .with(SystemTimingsLayer.with_filter(LevelFilter::INFO))
+        .with(LogBufferLayer.with_filter(LevelFilter::INFO))
+        .with(LogFileLayer.filtered())
+        .init();
 
     let args = Args::parse();
 
@@ -42,6 +71,10 @@ fn main() -> Result<(), Error> {
         Command::WgpuInfo => {
             wgpu_info()?;
         }
+        Command::Bench(args) => {
+            let app = App::new(args.into_app_args()?)?;
+            app.run()?;
+        }
     }
 
     Ok(())
@@ -50,6 +83,22 @@ fn main() -> Result<(), Error> {
 fn wgpu_info() -> Result<(), Error> {
     let builder = WgpuContextBuilder::new(Default::default())?;
 
+    println!("available adapters:");
+    let adapters = pollster::block_on(available_adapters(
+        &builder.instance,
+        builder.config.backends,
+    ));
+    for (index, adapter) in adapters.iter().enumerate() {
+        let info = adapter.get_info();
+        let selected = if info == builder.adapter_info {
+            "*"
+        }
+        else {
+            " "
+        };
+        println!("{selected} {index}: {}", AdapterSummary(&info));
+    }
+
     println!("supported features:");
     for (feature, _) in builder.supported_features.iter_names() {
         println!("  {feature}");
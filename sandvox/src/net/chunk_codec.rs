@@ -0,0 +1,584 @@
+//! Compact encoding of chunks for sending them to clients.
+//!
+//! Chunks are split into sections of [`SECTION_SIZE`] voxels in the order of
+//! the chunk's shape. With a [`MortonShape`][crate::voxel::chunk::MortonShape]
+//! every section is a cube of 8³ voxels. Each section has its own palette of
+//! distinct voxels, and the voxels are stored as palette indices, which are
+//! either run-length encoded or bit-packed, whichever is smaller. Sections that
+//! only contain one kind of voxel are just their palette.
+//!
+//! A client that already has a chunk only receives the sections that changed
+//! since it was last replicated, see [`ChunkReplicator`].
+
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    hash::Hash,
+};
+
+use nalgebra::Point3;
+
+use crate::{
+    util::format_size,
+    voxel::chunk::{
+        Chunk,
+        ChunkShape,
+    },
+};
+
+/// Number of voxels in a section.
+pub const SECTION_SIZE: usize = 512;
+
+/// Sections of a chunk, encoded for sending them to a client.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ChunkUpdate<V> {
+    /// Whether all sections of the chunk are included. Otherwise only the
+    /// sections that changed are included, and the client must already have
+    /// the chunk.
+    pub full: bool,
+
+    pub sections: Vec<EncodedSection<V>>,
+}
+
+impl<V> ChunkUpdate<V> {
+    /// Approximate number of bytes needed to send the update.
+    pub fn encoded_size(&self) -> usize {
+        self.sections
+            .iter()
+            .map(|section| section.encoded_size())
+            .sum()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct EncodedSection<V> {
+    /// Index of the section in the chunk.
+    pub index: u16,
+
+    /// Distinct voxels in the section.
+    pub palette: Vec<V>,
+
+    pub indices: PaletteIndices,
+}
+
+impl<V> EncodedSection<V> {
+    /// Approximate number of bytes needed to send the section.
+    pub fn encoded_size(&self) -> usize {
+        // section index and palette length
+        let header = 4;
+        header + self.palette.len() * size_of::<V>() + self.indices.encoded_size()
+    }
+}
+
+/// The palette indices of a section's voxels.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PaletteIndices {
+    /// All voxels are the first palette entry.
+    Uniform,
+
+    /// Runs of the same palette index, as `(length, index)`.
+    RunLength(Vec<(u16, u16)>),
+
+    /// Palette indices with `bits` bits each, starting at the least significant
+    /// bits of each word. Indices don't cross word boundaries.
+    BitPacked { bits: u8, words: Vec<u64> },
+}
+
+impl PaletteIndices {
+    fn encoded_size(&self) -> usize {
+        // every variant starts with a tag
+        match self {
+            Self::Uniform => 1,
+            Self::RunLength(runs) => 1 + 4 * runs.len(),
+            Self::BitPacked { words, .. } => 2 + 8 * words.len(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, thiserror::Error)]
+pub enum DecodeError {
+    #[error("section {index} is out of bounds")]
+    SectionOutOfBounds { index: u16 },
+
+    #[error("section {index} has an empty palette")]
+    EmptyPalette { index: u16 },
+
+    #[error("section {index} refers to palette entry {entry}, but the palette has {palette_len}")]
+    InvalidPaletteIndex {
+        index: u16,
+        entry: u16,
+        palette_len: usize,
+    },
+
+    #[error("section {index} has {bits} bits per voxel")]
+    InvalidBitWidth { index: u16, bits: u8 },
+
+    #[error("section {index} has the wrong number of voxels")]
+    WrongLength { index: u16 },
+
+    #[error("the update doesn't contain the complete chunk")]
+    IncompleteChunk,
+}
+
+/// Encodes a section of a chunk.
+///
+/// # Panics
+///
+/// Panics if there are more than [`SECTION_SIZE`] voxels.
+pub fn encode_section<V>(index: u16, voxels: &[V]) -> EncodedSection<V>
+where
+    V: Copy + Eq + Hash,
+{
+    assert!(voxels.len() <= SECTION_SIZE);
+
+    let mut palette = vec![];
+    let mut palette_lookup = HashMap::new();
+    let entries = voxels
+        .iter()
+        .map(|voxel| {
+            *palette_lookup.entry(*voxel).or_insert_with(|| {
+                palette.push(*voxel);
+                (palette.len() - 1) as u16
+            })
+        })
+        .collect::<Vec<u16>>();
+
+    let indices = if palette.len() <= 1 {
+        PaletteIndices::Uniform
+    }
+    else {
+        let run_length = run_length_encode(&entries);
+        let bit_packed = bit_pack(&entries, bits_per_index(palette.len()));
+
+        if run_length.encoded_size() <= bit_packed.encoded_size() {
+            run_length
+        }
+        else {
+            bit_packed
+        }
+    };
+
+    EncodedSection {
+        index,
+        palette,
+        indices,
+    }
+}
+
+/// Decodes a section into `voxels`, which must have the length of the
+/// encoded section.
+pub fn decode_section<V>(section: &EncodedSection<V>, voxels: &mut [V]) -> Result<(), DecodeError>
+where
+    V: Copy,
+{
+    let index = section.index;
+
+    let lookup =
+        |entry: u16| {
+            section.palette.get(usize::from(entry)).copied().ok_or(
+                DecodeError::InvalidPaletteIndex {
+                    index,
+                    entry,
+                    palette_len: section.palette.len(),
+                },
+            )
+        };
+
+    match &section.indices {
+        PaletteIndices::Uniform => {
+            let voxel = *section
+                .palette
+                .first()
+                .ok_or(DecodeError::EmptyPalette { index })?;
+            voxels.fill(voxel);
+        }
+        PaletteIndices::RunLength(runs) => {
+            let mut voxels = voxels.iter_mut();
+            for (length, entry) in runs {
+                let voxel = lookup(*entry)?;
+                for _ in 0..*length {
+                    *voxels.next().ok_or(DecodeError::WrongLength { index })? = voxel;
+                }
+            }
+
+            if voxels.next().is_some() {
+                return Err(DecodeError::WrongLength { index });
+            }
+        }
+        PaletteIndices::BitPacked { bits, words } => {
+            if !(1..=16).contains(bits) {
+                return Err(DecodeError::InvalidBitWidth { index, bits: *bits });
+            }
+
+            let bits = usize::from(*bits);
+            let per_word = 64 / bits;
+            if words.len() != voxels.len().div_ceil(per_word) {
+                return Err(DecodeError::WrongLength { index });
+            }
+
+            let mask = (1 << bits) - 1;
+            for (i, voxel) in voxels.iter_mut().enumerate() {
+                let word = words[i / per_word];
+                let entry = (word >> ((i % per_word) * bits)) & mask;
+                *voxel = lookup(entry as u16)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Encodes all sections of a chunk.
+pub fn encode_chunk<V, S>(chunk: &Chunk<V, S>) -> ChunkUpdate<V>
+where
+    V: Copy + Eq + Hash,
+{
+    let sections = chunk
+        .as_ref()
+        .chunks(SECTION_SIZE)
+        .enumerate()
+        .map(|(index, voxels)| encode_section(index as u16, voxels))
+        .collect();
+
+    ChunkUpdate {
+        full: true,
+        sections,
+    }
+}
+
+/// Encodes the sections of `new` that are different in `old`.
+pub fn encode_delta<V, S>(old: &Chunk<V, S>, new: &Chunk<V, S>) -> ChunkUpdate<V>
+where
+    V: Copy + Eq + Hash,
+{
+    let sections = old
+        .as_ref()
+        .chunks(SECTION_SIZE)
+        .zip(new.as_ref().chunks(SECTION_SIZE))
+        .enumerate()
+        .filter(|(_, (old, new))| old != new)
+        .map(|(index, (_, new))| encode_section(index as u16, new))
+        .collect();
+
+    ChunkUpdate {
+        full: false,
+        sections,
+    }
+}
+
+/// Writes the sections of an update into a chunk.
+pub fn apply_update<V, S>(
+    chunk: &mut Chunk<V, S>,
+    update: &ChunkUpdate<V>,
+) -> Result<(), DecodeError>
+where
+    V: Copy,
+{
+    let voxels = chunk.as_mut();
+
+    for section in &update.sections {
+        let start = usize::from(section.index) * SECTION_SIZE;
+        if start >= voxels.len() {
+            return Err(DecodeError::SectionOutOfBounds {
+                index: section.index,
+            });
+        }
+        let end = (start + SECTION_SIZE).min(voxels.len());
+
+        decode_section(section, &mut voxels[start..end])?;
+    }
+
+    Ok(())
+}
+
+/// Creates a chunk from a [full][ChunkUpdate::full] update.
+pub fn decode_chunk<V, S>(shape: S, update: &ChunkUpdate<V>) -> Result<Chunk<V, S>, DecodeError>
+where
+    V: Copy,
+    S: ChunkShape,
+{
+    let side_length = shape.side_length();
+    let num_sections = (side_length * side_length * side_length).div_ceil(SECTION_SIZE);
+    if !update.full || update.sections.len() != num_sections {
+        return Err(DecodeError::IncompleteChunk);
+    }
+
+    // every voxel is overwritten, so any voxel will do here
+    let first = &update.sections[0];
+    let voxel = *first
+        .palette
+        .first()
+        .ok_or(DecodeError::EmptyPalette { index: first.index })?;
+
+    let mut chunk = Chunk::filled(shape, voxel);
+    apply_update(&mut chunk, update)?;
+    Ok(chunk)
+}
+
+fn run_length_encode(entries: &[u16]) -> PaletteIndices {
+    let mut runs: Vec<(u16, u16)> = vec![];
+
+    for entry in entries {
+        match runs.last_mut() {
+            Some((length, last)) if last == entry => *length += 1,
+            _ => runs.push((1, *entry)),
+        }
+    }
+
+    PaletteIndices::RunLength(runs)
+}
+
+fn bit_pack(entries: &[u16], bits: u8) -> PaletteIndices {
+    let per_word = 64 / usize::from(bits);
+
+    let words = entries
+        .chunks(per_word)
+        .map(|entries| {
+            entries.iter().enumerate().fold(0, |word, (i, entry)| {
+                word | (u64::from(*entry) << (i * usize::from(bits)))
+            })
+        })
+        .collect();
+
+    PaletteIndices::BitPacked { bits, words }
+}
+
+/// Number of bits needed for indices into a palette with `palette_len`
+/// entries.
+fn bits_per_index(palette_len: usize) -> u8 {
+    (usize::BITS - (palette_len.max(2) - 1).leading_zeros()) as u8
+}
+
+/// How well chunk updates were compressed.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct CompressionStats {
+    pub num_updates: u64,
+
+    /// Updates that contained the complete chunk.
+    pub num_full_updates: u64,
+
+    pub num_uniform_sections: u64,
+    pub num_run_length_sections: u64,
+    pub num_bit_packed_sections: u64,
+
+    /// Size of the sent sections as arrays of voxels.
+    pub raw_bytes: u64,
+
+    /// Approximate size of the sent sections.
+    pub encoded_bytes: u64,
+}
+
+impl CompressionStats {
+    pub fn record<V>(&mut self, update: &ChunkUpdate<V>) {
+        self.num_updates += 1;
+        if update.full {
+            self.num_full_updates += 1;
+        }
+
+        for section in &update.sections {
+            match section.indices {
+                PaletteIndices::Uniform => self.num_uniform_sections += 1,
+                PaletteIndices::RunLength(_) => self.num_run_length_sections += 1,
+                PaletteIndices::BitPacked { .. } => self.num_bit_packed_sections += 1,
+            }
+
+            self.raw_bytes += (SECTION_SIZE * size_of::<V>()) as u64;
+            self.encoded_bytes += section.encoded_size() as u64;
+        }
+    }
+
+    pub fn num_sections(&self) -> u64 {
+        self.num_uniform_sections + self.num_run_length_sections + self.num_bit_packed_sections
+    }
+
+    /// Encoded size relative to the raw size.
+    pub fn ratio(&self) -> f32 {
+        if self.raw_bytes == 0 {
+            1.0
+        }
+        else {
+            self.encoded_bytes as f32 / self.raw_bytes as f32
+        }
+    }
+}
+
+impl Display for CompressionStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} updates ({} full), {} sections ({} uniform, {} run-length, {} bit-packed), {} of {} ({:.1}%)",
+            self.num_updates,
+            self.num_full_updates,
+            self.num_sections(),
+            self.num_uniform_sections,
+            self.num_run_length_sections,
+            self.num_bit_packed_sections,
+            format_size(self.encoded_bytes),
+            format_size(self.raw_bytes),
+            100.0 * self.ratio(),
+        )
+    }
+}
+
+/// Remembers which chunks were sent to a client, so that only their changes
+/// are sent again.
+///
+/// Chunks are cheap to clone, so the replicated version of each chunk is kept
+/// around until it changes. Chunks that the client [shares][Self::share] with
+/// the server are only remembered by their position.
+#[derive(Debug)]
+pub struct ChunkReplicator<V, S> {
+    replicated: HashMap<Point3<i32>, Option<Chunk<V, S>>>,
+    stats: CompressionStats,
+}
+
+impl<V, S> Default for ChunkReplicator<V, S> {
+    fn default() -> Self {
+        Self {
+            replicated: HashMap::new(),
+            stats: CompressionStats::default(),
+        }
+    }
+}
+
+impl<V, S> ChunkReplicator<V, S>
+where
+    V: Copy + Eq + Hash,
+    S: ChunkShape,
+{
+    /// Encodes the chunk at `position` for the client: the full chunk if the
+    /// client doesn't have it yet, and otherwise the sections that changed.
+    ///
+    /// Returns `None` if the client already has this version of the chunk.
+    pub fn replicate(
+        &mut self,
+        position: Point3<i32>,
+        chunk: &Chunk<V, S>,
+    ) -> Option<ChunkUpdate<V>> {
+        let update = match self.replicated.get(&position) {
+            Some(Some(replicated)) => encode_delta(replicated, chunk),
+            _ => encode_chunk(chunk),
+        };
+        self.replicated.insert(position, Some(chunk.clone()));
+
+        if update.sections.is_empty() {
+            return None;
+        }
+
+        self.stats.record(&update);
+        Some(update)
+    }
+
+    /// Remembers that the client has the chunk at `position`, for a client that
+    /// shares the world with the server (e.g. in the same process). Nothing is
+    /// encoded and no copy of the chunk is kept, since the client sees the
+    /// server's version of the chunk anyway.
+    ///
+    /// Returns `false` if the client already had the chunk.
+    pub fn share(&mut self, position: Point3<i32>) -> bool {
+        self.replicated.insert(position, None).is_none()
+    }
+
+    /// Forgets that the client has the chunk, e.g. because it's out of the
+    /// client's range. It's sent completely the next time.
+    pub fn forget(&mut self, position: &Point3<i32>) -> bool {
+        self.replicated.remove(position).is_some()
+    }
+
+    pub fn contains(&self, position: &Point3<i32>) -> bool {
+        self.replicated.contains_key(position)
+    }
+
+    /// Positions of the chunks that the client has.
+    pub fn positions(&self) -> impl Iterator<Item = Point3<i32>> + '_ {
+        self.replicated.keys().copied()
+    }
+
+    pub fn num_chunks(&self) -> usize {
+        self.replicated.len()
+    }
+
+    pub fn stats(&self) -> &CompressionStats {
+        &self.stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::Point3;
+
+    use crate::{
+        net::chunk_codec::{
+            ChunkReplicator,
+            PaletteIndices,
+            SECTION_SIZE,
+            apply_update,
+            decode_chunk,
+            encode_chunk,
+        },
+        voxel::chunk::{
+            Chunk,
+            ChunkShape,
+            LinearShape,
+        },
+    };
+
+    type Shape = LinearShape<16>;
+
+    /// The first section is air, the second has layers and the rest is noise.
+    fn test_chunk() -> Chunk<u8, Shape> {
+        Chunk::from_fn(Shape::default(), |point| {
+            let i = Shape::default().encode(point);
+            match i / SECTION_SIZE {
+                0 => 0,
+                1 => ((i / 64) % 2) as u8,
+                _ => (i * 7919 % 13) as u8,
+            }
+        })
+    }
+
+    #[test]
+    fn it_round_trips_chunks() {
+        let chunk = test_chunk();
+        let update = encode_chunk(&chunk);
+
+        assert_eq!(update.sections.len(), 8);
+        assert_eq!(update.sections[0].indices, PaletteIndices::Uniform);
+        assert!(matches!(
+            update.sections[1].indices,
+            PaletteIndices::RunLength(_)
+        ));
+        assert!(matches!(
+            update.sections[2].indices,
+            PaletteIndices::BitPacked { bits: 4, .. }
+        ));
+        assert!(update.encoded_size() < chunk.byte_size());
+
+        let decoded = decode_chunk(Shape::default(), &update).unwrap();
+        assert_eq!(decoded.as_ref(), chunk.as_ref());
+    }
+
+    #[test]
+    fn it_only_sends_changed_sections() {
+        let mut chunk = test_chunk();
+        let position = Point3::origin();
+        let mut replicator = ChunkReplicator::default();
+
+        let update = replicator.replicate(position, &chunk).unwrap();
+        assert!(update.full);
+        let mut client = decode_chunk(Shape::default(), &update).unwrap();
+
+        assert_eq!(replicator.replicate(position, &chunk), None);
+
+        chunk.set(Point3::new(1, 2, 3), 42);
+        let update = replicator.replicate(position, &chunk).unwrap();
+        assert!(!update.full);
+        assert_eq!(update.sections.len(), 1);
+
+        apply_update(&mut client, &update).unwrap();
+        assert_eq!(client.as_ref(), chunk.as_ref());
+
+        assert_eq!(replicator.stats().num_updates, 2);
+        assert_eq!(replicator.stats().num_sections(), 9);
+    }
+}
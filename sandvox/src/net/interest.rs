@@ -0,0 +1,387 @@
+//! Which entities are replicated to which clients.
+//!
+//! Every client has an [`Interest`], which tracks the [`Replicated`] entities
+//! within its interest radius. Entities that come into the radius or leave it
+//! are announced with [`EntityEntered`] and [`EntityLeft`]. Entities only leave
+//! a bit outside the radius, so that they don't flicker in and out at the
+//! border.
+//!
+//! Updates for entities that moved are prioritized by how close they are and
+//! how far they moved since the client last got an update for them. Each client
+//! has a bandwidth budget, and entities that don't fit in it wait for a later
+//! tick. Waiting raises their priority, so that far away entities aren't
+//! starved by close ones.
+
+use std::collections::HashMap;
+
+use bevy_ecs::{
+    component::Component,
+    entity::Entity,
+    message::{
+        Message,
+        MessageWriter,
+    },
+    query::With,
+    resource::Resource,
+    schedule::IntoScheduleConfigs,
+    system::{
+        Populated,
+        Query,
+        Res,
+    },
+};
+use color_eyre::eyre::Error;
+use nalgebra::Point3;
+use serde::{
+    Deserialize,
+    Serialize,
+};
+
+use crate::{
+    app::Time,
+    ecs::{
+        plugin::{
+            Plugin,
+            WorldBuilder,
+        },
+        schedule,
+        transform::{
+            GlobalTransform,
+            TransformSystems,
+        },
+    },
+};
+
+/// Estimated size of an entity update (in bytes): the entity's ID, position,
+/// rotation and scale.
+pub const ENTITY_UPDATE_SIZE: usize = 40;
+
+/// Entities leave the interest this far outside the radius (relative to the
+/// radius).
+const LEAVE_MARGIN: f32 = 0.1;
+
+/// Movement since the last update that isn't worth another update (in blocks).
+const MIN_MOVEMENT: f32 = 0.001;
+
+/// Priority of entities that the client hasn't gotten an update for yet, on top
+/// of their distance.
+const ENTERED_PRIORITY: f32 = 1.0;
+
+/// How much the priority of an update grows every tick that it waits.
+const WAITING_PRIORITY: f32 = 0.01;
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct InterestPlugin {
+    pub config: InterestConfig,
+}
+
+impl Plugin for InterestPlugin {
+    fn setup(&self, builder: &mut WorldBuilder) -> Result<(), Error> {
+        builder
+            .insert_resource(self.config)
+            .add_message::<EntityEntered>()
+            .add_message::<EntityLeft>()
+            .add_systems(
+                schedule::PostUpdate,
+                update_interest.after(TransformSystems::Propagate),
+            );
+
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize, Resource)]
+pub struct InterestConfig {
+    /// Entities within this distance of a client are replicated to it, unless
+    /// the client asked for another radius (in blocks).
+    #[serde(default = "default_radius")]
+    pub radius: f32,
+
+    /// How much entity updates can send to each client, unless the client
+    /// asked for another limit (in bytes / s).
+    #[serde(default = "default_bandwidth")]
+    pub bandwidth: f32,
+
+    /// How much moving raises an entity's priority, compared to being close
+    /// (per block moved).
+    #[serde(default = "default_movement_priority")]
+    pub movement_priority: f32,
+}
+
+impl Default for InterestConfig {
+    fn default() -> Self {
+        Self {
+            radius: default_radius(),
+            bandwidth: default_bandwidth(),
+            movement_priority: default_movement_priority(),
+        }
+    }
+}
+
+fn default_radius() -> f32 {
+    96.0
+}
+
+fn default_bandwidth() -> f32 {
+    32.0 * 1024.0
+}
+
+fn default_movement_priority() -> f32 {
+    1.0
+}
+
+/// Marks an entity that is replicated to the clients that are interested in
+/// it.
+#[derive(Clone, Copy, Debug, Default, Component)]
+pub struct Replicated;
+
+/// The entities that a client is interested in, and which of them it gets
+/// updates for this tick.
+#[derive(Clone, Debug, Default, Component)]
+pub struct Interest {
+    /// Overrides [`InterestConfig::radius`] for this client.
+    pub radius: Option<f32>,
+
+    /// Overrides [`InterestConfig::bandwidth`] for this client.
+    pub bandwidth: Option<f32>,
+
+    entities: HashMap<Entity, ReplicationState>,
+
+    /// Bytes that can still be sent. This is refilled every tick, and can
+    /// hold up to one second of bandwidth.
+    budget: f32,
+
+    updates: Vec<Entity>,
+
+    num_deferred: usize,
+}
+
+impl Interest {
+    pub fn with_radius(mut self, radius: f32) -> Self {
+        self.radius = Some(radius);
+        self
+    }
+
+    pub fn contains(&self, entity: Entity) -> bool {
+        self.entities.contains_key(&entity)
+    }
+
+    /// Number of entities that are replicated to the client.
+    pub fn num_entities(&self) -> usize {
+        self.entities.len()
+    }
+
+    /// Entities that the client gets an update for this tick, with the highest
+    /// priority first.
+    pub fn updates(&self) -> &[Entity] {
+        &self.updates
+    }
+
+    /// Number of entities that need an update, but didn't fit in the bandwidth
+    /// budget this tick.
+    pub fn num_deferred(&self) -> usize {
+        self.num_deferred
+    }
+}
+
+/// What the client last got for an entity.
+#[derive(Clone, Copy, Debug, Default)]
+struct ReplicationState {
+    /// `None` if the client didn't get an update for the entity yet.
+    position: Option<Point3<f32>>,
+
+    tick: u64,
+}
+
+/// Sent when an entity came into a client's interest radius.
+#[derive(Clone, Copy, Debug, Message)]
+pub struct EntityEntered {
+    pub client: Entity,
+    pub entity: Entity,
+}
+
+/// Sent when an entity left a client's interest radius or was despawned.
+#[derive(Clone, Copy, Debug, Message)]
+pub struct EntityLeft {
+    pub client: Entity,
+    pub entity: Entity,
+}
+
+/// An entity that needs an update.
+#[derive(Clone, Copy, Debug)]
+struct Candidate {
+    entity: Entity,
+    position: Point3<f32>,
+    priority: f32,
+}
+
+/// Priority of an update for an entity `distance` blocks away from the client,
+/// that moved `moved` blocks since the client's last update for it.
+fn priority(
+    distance: f32,
+    radius: f32,
+    moved: Option<f32>,
+    ticks_waiting: u64,
+    config: &InterestConfig,
+) -> f32 {
+    let closeness = (1.0 - distance / radius).clamp(0.0, 1.0);
+    let urgency = moved.map_or(ENTERED_PRIORITY, |moved| config.movement_priority * moved);
+
+    closeness * (1.0 + urgency) + WAITING_PRIORITY * ticks_waiting as f32
+}
+
+/// Sorts the candidates by priority and returns how many of them fit in the
+/// budget. Their size is taken from the budget.
+fn select_updates(candidates: &mut [Candidate], budget: &mut f32) -> usize {
+    candidates.sort_by(|a, b| b.priority.total_cmp(&a.priority));
+
+    let num_selected =
+        ((*budget / ENTITY_UPDATE_SIZE as f32).max(0.0) as usize).min(candidates.len());
+    *budget -= (num_selected * ENTITY_UPDATE_SIZE) as f32;
+
+    num_selected
+}
+
+pub fn update_interest(
+    time: Res<Time>,
+    config: Res<InterestConfig>,
+    clients: Populated<(Entity, &GlobalTransform, &mut Interest)>,
+    replicated: Query<(Entity, &GlobalTransform), With<Replicated>>,
+    mut entered: MessageWriter<EntityEntered>,
+    mut left: MessageWriter<EntityLeft>,
+) {
+    let mut candidates = vec![];
+
+    for (client, client_transform, mut interest) in clients {
+        let interest = &mut *interest;
+        let center = client_transform.position();
+        let radius = interest.radius.unwrap_or(config.radius);
+        let leave_radius = radius * (1.0 + LEAVE_MARGIN);
+        let bandwidth = interest.bandwidth.unwrap_or(config.bandwidth);
+
+        interest.entities.retain(|entity, _| {
+            let keep = replicated
+                .get(*entity)
+                .is_ok_and(|(_, transform)| (transform.position() - center).norm() <= leave_radius);
+            if !keep {
+                left.write(EntityLeft {
+                    client,
+                    entity: *entity,
+                });
+            }
+            keep
+        });
+
+        candidates.clear();
+
+        for (entity, transform) in &replicated {
+            if entity == client {
+                continue;
+            }
+
+            let position = transform.position();
+            let distance = (position - center).norm();
+
+            let state = if let Some(state) = interest.entities.get(&entity) {
+                *state
+            }
+            else if distance <= radius {
+                entered.write(EntityEntered { client, entity });
+                let state = ReplicationState {
+                    position: None,
+                    tick: time.tick_count,
+                };
+                interest.entities.insert(entity, state);
+                state
+            }
+            else {
+                continue;
+            };
+
+            let moved = state.position.map(|last| (position - last).norm());
+            if moved.is_some_and(|moved| moved < MIN_MOVEMENT) {
+                continue;
+            }
+
+            candidates.push(Candidate {
+                entity,
+                position,
+                priority: priority(
+                    distance,
+                    radius,
+                    moved,
+                    time.tick_count.saturating_sub(state.tick),
+                    &config,
+                ),
+            });
+        }
+
+        interest.budget = (interest.budget + bandwidth * time.delta_seconds()).min(bandwidth);
+        let num_selected = select_updates(&mut candidates, &mut interest.budget);
+
+        interest.updates.clear();
+        for candidate in &candidates[..num_selected] {
+            interest.entities.insert(
+                candidate.entity,
+                ReplicationState {
+                    position: Some(candidate.position),
+                    tick: time.tick_count,
+                },
+            );
+            interest.updates.push(candidate.entity);
+        }
+        interest.num_deferred = candidates.len() - num_selected;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_ecs::entity::Entity;
+    use nalgebra::Point3;
+
+    use crate::net::interest::{
+        Candidate,
+        ENTITY_UPDATE_SIZE,
+        InterestConfig,
+        priority,
+        select_updates,
+    };
+
+    #[test]
+    fn it_prioritizes_close_and_moving_entities() {
+        let config = InterestConfig::default();
+
+        let close = priority(10.0, 100.0, Some(1.0), 0, &config);
+        let far = priority(80.0, 100.0, Some(1.0), 0, &config);
+        let fast = priority(10.0, 100.0, Some(5.0), 0, &config);
+        assert!(close > far);
+        assert!(fast > close);
+
+        // waiting eventually beats being close
+        assert!(priority(80.0, 100.0, Some(1.0), 1000, &config) > fast);
+    }
+
+    #[test]
+    fn it_caps_updates_by_bandwidth() {
+        let mut candidates = (0..10)
+            .map(|i| {
+                Candidate {
+                    entity: Entity::from_raw_u32(i + 1).unwrap(),
+                    position: Point3::origin(),
+                    priority: i as f32,
+                }
+            })
+            .collect::<Vec<_>>();
+        let mut budget = 3.5 * ENTITY_UPDATE_SIZE as f32;
+
+        let num_selected = select_updates(&mut candidates, &mut budget);
+        assert_eq!(num_selected, 3);
+        assert_eq!(budget, 0.5 * ENTITY_UPDATE_SIZE as f32);
+
+        let priorities = candidates[..num_selected]
+            .iter()
+            .map(|candidate| candidate.priority)
+            .collect::<Vec<_>>();
+        assert_eq!(priorities, [9.0, 8.0, 7.0]);
+    }
+}
@@ -0,0 +1,6 @@
+//! Replicating the game to clients.
+
+pub mod chunk_codec;
+pub mod interest;
+pub mod replication;
+pub mod transport;
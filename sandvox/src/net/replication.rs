@@ -0,0 +1,950 @@
+//! Replicating chunks and entities to clients over their [`Transport`].
+//!
+//! Every client has a [`Connection`] on the entity it controls. Chunks within
+//! the entity's [`ChunkLoader`] range are queued when the client doesn't have
+//! them yet or they changed, and a few of them are sent every tick, closest
+//! first. Entities are replicated as decided by the client's [`Interest`].
+//!
+//! Singleplayer connects the player to a [`LocalClient`] with a
+//! [loopback][crate::net::transport::loopback], so it goes through the same
+//! systems as a remote client would. Since the local client shares the world
+//! with the server, its chunks are only announced and not encoded, see
+//! [`Connection::sharing_world`].
+//!
+//! Other clients get a [`ClientValidation`] when they connect, so that their
+//! movement and edits are checked. The local client is trusted.
+
+use std::{
+    collections::{
+        HashMap,
+        HashSet,
+        VecDeque,
+    },
+    time::{
+        Duration,
+        Instant,
+    },
+};
+
+use bevy_ecs::{
+    change_detection::DetectChanges,
+    component::Component,
+    entity::Entity,
+    message::MessageReader,
+    query::{
+        Added,
+        With,
+        Without,
+    },
+    resource::Resource,
+    schedule::{
+        IntoScheduleConfigs,
+        common_conditions::resource_exists,
+    },
+    system::{
+        Commands,
+        Populated,
+        Query,
+        Res,
+        ResMut,
+    },
+    world::Ref,
+};
+use color_eyre::eyre::Error;
+use nalgebra::Point3;
+
+use crate::{
+    app::Time,
+    ecs::{
+        plugin::{
+            Dependencies,
+            Plugin,
+            WorldBuilder,
+        },
+        schedule,
+        transform::GlobalTransform,
+    },
+    game::{
+        ChunkShape,
+        terrain::TerrainVoxel,
+        validation::ClientValidation,
+    },
+    net::{
+        chunk_codec::{
+            ChunkReplicator,
+            ChunkUpdate,
+        },
+        interest::{
+            ENTITY_UPDATE_SIZE,
+            EntityEntered,
+            EntityLeft,
+            Interest,
+            InterestConfig,
+            InterestPlugin,
+            Replicated,
+            update_interest,
+        },
+        transport::{
+            Channel,
+            MessageSize,
+            Transport,
+            TransportError,
+            TransportStats,
+        },
+    },
+    ui::{
+        Toast,
+        Toasts,
+    },
+    voxel::{
+        chunk::Chunk,
+        chunk_map::{
+            ChunkMap,
+            ChunkMapPlugin,
+        },
+        loader::{
+            ChunkLoader,
+            all_chunks_in_range,
+            chunk_position_from_transform,
+        },
+    },
+};
+
+/// Number of chunk updates sent to each client per tick.
+const CHUNKS_PER_TICK: usize = 16;
+
+/// How often clients measure the round trip time to the server.
+const PING_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The server's end of a connection.
+pub type ServerTransport = Box<dyn Transport<Outgoing = ServerMessage, Incoming = ClientMessage>>;
+
+/// A client's end of a connection.
+pub type ClientTransport = Box<dyn Transport<Outgoing = ClientMessage, Incoming = ServerMessage>>;
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ReplicationPlugin;
+
+impl Plugin for ReplicationPlugin {
+    fn dependencies(&self, dependencies: &mut Dependencies) -> Result<(), Error> {
+        dependencies
+            .require_plugin::<InterestPlugin>()
+            .require_plugin::<ChunkMapPlugin>();
+        Ok(())
+    }
+
+    fn setup(&self, builder: &mut WorldBuilder) -> Result<(), Error> {
+        builder
+            .add_systems(
+                schedule::PreUpdate,
+                (validate_new_clients, receive_client_messages).chain(),
+            )
+            .add_systems(
+                schedule::PostUpdate,
+                (
+                    replicate_chunks,
+                    replicate_entities,
+                    receive_server_messages.run_if(resource_exists::<LocalClient>),
+                )
+                    .chain()
+                    .after(update_interest),
+            );
+
+        Ok(())
+    }
+}
+
+/// Sent from the server to a client.
+#[derive(Clone, Debug)]
+pub enum ServerMessage {
+    /// A chunk that the client doesn't have yet, or the sections of it that
+    /// changed.
+    Chunk {
+        position: Point3<i32>,
+        update: ChunkUpdate<TerrainVoxel>,
+    },
+
+    /// The client has the chunk, because it shares the world with the server.
+    /// Changes to the chunk are not sent.
+    ChunkShared {
+        position: Point3<i32>,
+    },
+
+    /// The client should forget the chunk.
+    ChunkUnloaded {
+        position: Point3<i32>,
+    },
+
+    EntityEntered {
+        entity: Entity,
+    },
+
+    EntityLeft {
+        entity: Entity,
+    },
+
+    /// Sent unreliably, so the client must ignore updates that are older than
+    /// the last one it got.
+    EntityMoved {
+        entity: Entity,
+        tick: u64,
+        transform: GlobalTransform,
+    },
+
+    /// Answers a [`ClientMessage::Ping`].
+    Pong {
+        sequence: u64,
+    },
+}
+
+impl MessageSize for ServerMessage {
+    fn message_size(&self) -> usize {
+        // every message starts with a tag
+        1 + match self {
+            Self::Chunk { update, .. } => 12 + update.encoded_size(),
+            Self::ChunkShared { .. } | Self::ChunkUnloaded { .. } => 12,
+            Self::EntityEntered { .. } | Self::EntityLeft { .. } => 8,
+            Self::EntityMoved { .. } => 8 + ENTITY_UPDATE_SIZE,
+            Self::Pong { .. } => 8,
+        }
+    }
+}
+
+/// Sent from a client to the server.
+#[derive(Clone, Copy, Debug)]
+pub enum ClientMessage {
+    /// Sets [`Interest::radius`] for the client, e.g. to match its render
+    /// distance. `None` uses the server's default.
+    SetInterestRadius { radius: Option<f32> },
+
+    /// Asks the server for a [`ServerMessage::Pong`] with the same sequence
+    /// number, to measure the round trip time.
+    Ping { sequence: u64 },
+}
+
+impl MessageSize for ClientMessage {
+    fn message_size(&self) -> usize {
+        1 + match self {
+            Self::SetInterestRadius { .. } => 5,
+            Self::Ping { .. } => 8,
+        }
+    }
+}
+
+/// The server's side of a client's connection.
+#[derive(Component)]
+pub struct Connection {
+    transport: ServerTransport,
+    chunks: ChunkReplicator<TerrainVoxel, ChunkShape>,
+    shares_world: bool,
+
+    /// Chunks that need to be sent, closest first.
+    pending_chunks: VecDeque<Point3<i32>>,
+    pending_set: HashSet<Point3<i32>>,
+}
+
+impl Connection {
+    pub fn new(
+        transport: impl Transport<Outgoing = ServerMessage, Incoming = ClientMessage>,
+    ) -> Self {
+        Self {
+            transport: Box::new(transport),
+            chunks: Default::default(),
+            shares_world: false,
+            pending_chunks: VecDeque::new(),
+            pending_set: HashSet::new(),
+        }
+    }
+
+    /// A connection to a client that shares the world with the server, e.g.
+    /// the [`LocalClient`] in singleplayer.
+    ///
+    /// The client sees the server's chunks, so they're only announced with
+    /// [`ServerMessage::ChunkShared`] instead of being encoded and diffed.
+    pub fn sharing_world(
+        transport: impl Transport<Outgoing = ServerMessage, Incoming = ClientMessage>,
+    ) -> Self {
+        Self {
+            shares_world: true,
+            ..Self::new(transport)
+        }
+    }
+
+    pub fn chunks(&self) -> &ChunkReplicator<TerrainVoxel, ChunkShape> {
+        &self.chunks
+    }
+
+    pub fn stats(&self) -> TransportStats {
+        self.transport.stats()
+    }
+
+    /// Number of chunks waiting to be sent.
+    pub fn num_pending_chunks(&self) -> usize {
+        self.pending_chunks.len()
+    }
+
+    fn queue_chunk(&mut self, position: Point3<i32>) {
+        if self.pending_set.insert(position) {
+            self.pending_chunks.push_back(position);
+        }
+    }
+
+    fn next_chunk(&mut self) -> Option<Point3<i32>> {
+        let position = self.pending_chunks.pop_front()?;
+        self.pending_set.remove(&position);
+        Some(position)
+    }
+}
+
+impl std::fmt::Debug for Connection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Connection")
+            .field("chunks", &self.chunks)
+            .field("shares_world", &self.shares_world)
+            .field("pending_chunks", &self.pending_chunks)
+            .finish_non_exhaustive()
+    }
+}
+
+/// The client in this process, e.g. in singleplayer.
+#[derive(Resource)]
+pub struct LocalClient {
+    transport: ClientTransport,
+    pub replica: Replica,
+
+    ping_sequence: u64,
+    ping_sent: Option<Instant>,
+    round_trip: Option<Duration>,
+}
+
+impl LocalClient {
+    pub fn new(
+        transport: impl Transport<Outgoing = ClientMessage, Incoming = ServerMessage>,
+    ) -> Self {
+        Self {
+            transport: Box::new(transport),
+            replica: Replica::default(),
+            ping_sequence: 0,
+            ping_sent: None,
+            round_trip: None,
+        }
+    }
+
+    pub fn send(&mut self, channel: Channel, message: ClientMessage) -> Result<(), TransportError> {
+        self.transport.send(channel, message)
+    }
+
+    pub fn stats(&self) -> TransportStats {
+        self.transport.stats()
+    }
+
+    /// The last measured round trip time to the server.
+    pub fn ping(&self) -> Option<Duration> {
+        self.round_trip
+    }
+}
+
+/// What a client knows about the server's world.
+///
+/// This only keeps track of which chunks the client has. A client in the same
+/// process shares the world with the server, and a remote client would apply
+/// the chunk updates to its own world.
+#[derive(Clone, Debug, Default)]
+pub struct Replica {
+    chunks: HashSet<Point3<i32>>,
+    entities: HashMap<Entity, Option<(u64, GlobalTransform)>>,
+}
+
+impl Replica {
+    pub fn handle_message(&mut self, message: ServerMessage) {
+        match message {
+            ServerMessage::Chunk { position, update } => {
+                if update.full {
+                    self.chunks.insert(position);
+                }
+                else if !self.chunks.contains(&position) {
+                    tracing::warn!(?position, "got a delta for a chunk we don't have");
+                }
+            }
+            ServerMessage::ChunkShared { position } => {
+                self.chunks.insert(position);
+            }
+            ServerMessage::ChunkUnloaded { position } => {
+                self.chunks.remove(&position);
+            }
+            ServerMessage::EntityEntered { entity } => {
+                self.entities.insert(entity, None);
+            }
+            ServerMessage::EntityLeft { entity } => {
+                self.entities.remove(&entity);
+            }
+            ServerMessage::EntityMoved {
+                entity,
+                tick,
+                transform,
+            } => {
+                if let Some(state) = self.entities.get_mut(&entity)
+                    && state.is_none_or(|(last_tick, _)| last_tick < tick)
+                {
+                    *state = Some((tick, transform));
+                }
+            }
+            ServerMessage::Pong { .. } => {}
+        }
+    }
+
+    pub fn contains_chunk(&self, position: &Point3<i32>) -> bool {
+        self.chunks.contains(position)
+    }
+
+    pub fn num_chunks(&self) -> usize {
+        self.chunks.len()
+    }
+
+    pub fn num_entities(&self) -> usize {
+        self.entities.len()
+    }
+
+    /// The last known transform of an entity, if it's replicated and was
+    /// updated at least once.
+    pub fn transform(&self, entity: Entity) -> Option<&GlobalTransform> {
+        self.entities
+            .get(&entity)?
+            .as_ref()
+            .map(|(_, transform)| transform)
+    }
+}
+
+fn validate_new_clients(
+    connections: Populated<(Entity, &Connection), (Added<Connection>, Without<ClientValidation>)>,
+    mut commands: Commands,
+) {
+    for (client, connection) in connections {
+        if !connection.shares_world {
+            commands.entity(client).insert(ClientValidation::default());
+        }
+    }
+}
+
+fn receive_client_messages(
+    config: Res<InterestConfig>,
+    connections: Populated<(Entity, &mut Connection, Option<&mut Interest>)>,
+    mut commands: Commands,
+) {
+    for (client, mut connection, mut interest) in connections {
+        loop {
+            match connection.transport.recv() {
+                Ok(Some((_, message))) => {
+                    match message {
+                        ClientMessage::SetInterestRadius { radius } => {
+                            if let Some(radius) = radius
+                                && !(radius.is_finite() && radius >= 0.0)
+                            {
+                                tracing::warn!(%client, radius, "invalid interest radius");
+                            }
+                            else if let Some(interest) = &mut interest {
+                                // clients can ask for less than the server's radius, but not
+                                // for more
+                                interest.radius = radius.map(|radius| radius.min(config.radius));
+                            }
+                        }
+                        ClientMessage::Ping { sequence } => {
+                            if let Err(error) = connection
+                                .transport
+                                .send(Channel::Unreliable, ServerMessage::Pong { sequence })
+                            {
+                                tracing::info!(%client, %error, "client disconnected");
+                                commands.entity(client).remove::<Connection>();
+                                break;
+                            }
+                        }
+                    }
+                }
+                Ok(None) => break,
+                Err(error) => {
+                    tracing::info!(%client, %error, "client disconnected");
+                    commands.entity(client).remove::<Connection>();
+                    break;
+                }
+            }
+        }
+    }
+}
+
+fn replicate_chunks(
+    connections: Populated<(Entity, &mut Connection, &GlobalTransform, &ChunkLoader)>,
+    chunk_map: Res<ChunkMap>,
+    chunks: Query<Ref<Chunk<TerrainVoxel, ChunkShape>>>,
+    mut commands: Commands,
+) {
+    for (client, mut connection, transform, chunk_loader) in connections {
+        let connection = &mut *connection;
+        let center = chunk_position_from_transform(&ChunkShape::default(), transform);
+
+        // only the chunks in range are looked up, instead of checking every loaded
+        // chunk for every client
+        let in_range = all_chunks_in_range(center, chunk_loader.radius)
+            .filter_map(|position| {
+                let chunk = chunks.get(chunk_map.get(position)?).ok()?;
+                Some((position, chunk))
+            })
+            .collect::<HashMap<_, _>>();
+
+        // forget chunks that were unloaded or are out of range now
+        let gone = connection
+            .chunks
+            .positions()
+            .filter(|position| !in_range.contains_key(position))
+            .collect::<Vec<_>>();
+        let mut result = Ok(());
+        for position in gone {
+            connection.chunks.forget(&position);
+            result = result.and_then(|()| {
+                connection
+                    .transport
+                    .send(Channel::Reliable, ServerMessage::ChunkUnloaded { position })
+            });
+        }
+        connection
+            .pending_chunks
+            .retain(|position| in_range.contains_key(position));
+        connection
+            .pending_set
+            .retain(|position| in_range.contains_key(position));
+
+        // a client that shares the world sees the changes itself
+        let mut queued = in_range
+            .iter()
+            .filter(|(position, chunk)| {
+                (chunk.is_changed() && !connection.shares_world)
+                    || !connection.chunks.contains(position)
+            })
+            .map(|(position, _)| *position)
+            .collect::<Vec<_>>();
+        queued.sort_by_key(|position| (position - center).abs().sum());
+        for position in queued {
+            connection.queue_chunk(position);
+        }
+
+        let mut num_sent = 0;
+        while num_sent < CHUNKS_PER_TICK
+            && result.is_ok()
+            && let Some(position) = connection.next_chunk()
+        {
+            if connection.shares_world {
+                if connection.chunks.share(position) {
+                    result = connection
+                        .transport
+                        .send(Channel::Reliable, ServerMessage::ChunkShared { position });
+                    num_sent += 1;
+                }
+            }
+            else if let Some(update) = connection.chunks.replicate(position, &in_range[&position])
+            {
+                result = connection
+                    .transport
+                    .send(Channel::Reliable, ServerMessage::Chunk { position, update });
+                num_sent += 1;
+            }
+        }
+
+        if let Err(error) = result {
+            tracing::info!(%client, %error, "client disconnected");
+            commands.entity(client).remove::<Connection>();
+        }
+    }
+}
+
+fn replicate_entities(
+    time: Res<Time>,
+    mut connections: Query<(Entity, &mut Connection, &Interest)>,
+    transforms: Query<&GlobalTransform, With<Replicated>>,
+    mut entered: MessageReader<EntityEntered>,
+    mut left: MessageReader<EntityLeft>,
+    mut commands: Commands,
+) {
+    // both are sent reliably, so the client sees them before the updates
+    let announcements = left
+        .read()
+        .map(|message| {
+            (
+                message.client,
+                ServerMessage::EntityLeft {
+                    entity: message.entity,
+                },
+            )
+        })
+        .chain(entered.read().map(|message| {
+            (
+                message.client,
+                ServerMessage::EntityEntered {
+                    entity: message.entity,
+                },
+            )
+        }));
+
+    let mut disconnected = HashSet::new();
+
+    for (client, message) in announcements {
+        if let Ok((_, mut connection, _)) = connections.get_mut(client)
+            && let Err(error) = connection.transport.send(Channel::Reliable, message)
+            && disconnected.insert(client)
+        {
+            tracing::info!(%client, %error, "client disconnected");
+        }
+    }
+
+    for (client, mut connection, interest) in &mut connections {
+        if disconnected.contains(&client) {
+            continue;
+        }
+
+        for entity in interest.updates() {
+            let Ok(transform) = transforms.get(*entity)
+            else {
+                continue;
+            };
+
+            let message = ServerMessage::EntityMoved {
+                entity: *entity,
+                tick: time.tick_count,
+                transform: *transform,
+            };
+            if let Err(error) = connection.transport.send(Channel::Unreliable, message) {
+                tracing::info!(%client, %error, "client disconnected");
+                disconnected.insert(client);
+                break;
+            }
+        }
+    }
+
+    for client in disconnected {
+        commands.entity(client).remove::<Connection>();
+    }
+}
+
+fn receive_server_messages(
+    mut local_client: ResMut<LocalClient>,
+    toasts: Option<ResMut<Toasts>>,
+    mut commands: Commands,
+) {
+    let local_client = &mut *local_client;
+    let now = Instant::now();
+
+    let mut result = loop {
+        match local_client.transport.recv() {
+            Ok(Some((_, ServerMessage::Pong { sequence }))) => {
+                if sequence == local_client.ping_sequence
+                    && let Some(ping_sent) = local_client.ping_sent
+                {
+                    local_client.round_trip = Some(now.saturating_duration_since(ping_sent));
+                }
+            }
+            Ok(Some((_, message))) => local_client.replica.handle_message(message),
+            Ok(None) => break Ok(()),
+            Err(error) => break Err(error),
+        }
+    };
+
+    // a ping that got lost is replaced by the next one
+    if result.is_ok()
+        && local_client
+            .ping_sent
+            .is_none_or(|ping_sent| now.saturating_duration_since(ping_sent) >= PING_INTERVAL)
+    {
+        local_client.ping_sequence += 1;
+        local_client.ping_sent = Some(now);
+        result = local_client.transport.send(
+            Channel::Unreliable,
+            ClientMessage::Ping {
+                sequence: local_client.ping_sequence,
+            },
+        );
+    }
+
+    if let Err(error) = result {
+        tracing::info!(%error, "disconnected from server");
+        if let Some(mut toasts) = toasts {
+            toasts.push(Toast::error("CONNECTION LOST"));
+        }
+        commands.remove_resource::<LocalClient>();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{
+        Duration,
+        Instant,
+    };
+
+    use bevy_ecs::world::World;
+    use bevy_tasks::{
+        ComputeTaskPool,
+        TaskPool,
+    };
+    use chrono::Utc;
+    use nalgebra::{
+        Point3,
+        Vector3,
+    };
+
+    use crate::{
+        app::Time,
+        ecs::{
+            plugin::WorldBuilder,
+            schedule,
+            transform::{
+                GlobalTransform,
+                LocalTransform,
+            },
+        },
+        game::{
+            ChunkShape,
+            block_type::BlockType,
+            terrain::TerrainVoxel,
+            validation::ClientValidation,
+        },
+        net::{
+            interest::{
+                Interest,
+                InterestConfig,
+                InterestPlugin,
+                Replicated,
+            },
+            replication::{
+                ClientMessage,
+                Connection,
+                ReplicationPlugin,
+                ServerMessage,
+            },
+            transport::{
+                Channel,
+                Transport,
+                loopback,
+            },
+        },
+        voxel::{
+            chunk::Chunk,
+            chunk_map::{
+                ChunkMapPlugin,
+                ChunkPosition,
+            },
+            loader::ChunkLoader,
+        },
+    };
+
+    fn voxel(block_type: usize) -> TerrainVoxel {
+        TerrainVoxel {
+            block_type: BlockType::from_usize(block_type),
+        }
+    }
+
+    fn drain(
+        client: &mut impl Transport<Incoming = ServerMessage>,
+    ) -> Vec<(Channel, ServerMessage)> {
+        std::iter::from_fn(|| client.recv().unwrap()).collect()
+    }
+
+    #[test]
+    fn it_replicates_over_a_loopback() {
+        ComputeTaskPool::get_or_init(TaskPool::default);
+
+        let mut builder = WorldBuilder::default();
+        builder
+            .add_plugin(ChunkMapPlugin)
+            .unwrap()
+            .add_plugin(ReplicationPlugin)
+            .unwrap()
+            .insert_resource(Time {
+                app_start: Instant::now(),
+                app_start_utc: Utc::now(),
+                tick_start: Instant::now(),
+                tick_delta: Duration::from_millis(20),
+                tick_count: 0,
+            });
+        let mut world = builder.build().unwrap();
+
+        let (server_end, mut client_end) = loopback();
+        let client = world
+            .spawn((
+                GlobalTransform::identity(),
+                ChunkLoader {
+                    radius: Vector3::repeat(1),
+                },
+                Interest::default(),
+                Connection::new(server_end),
+            ))
+            .id();
+
+        // remote clients are validated
+        world.run_schedule(schedule::PreUpdate);
+        assert!(world.get::<ClientValidation>(client).is_some());
+
+        let chunk = world
+            .spawn((
+                ChunkPosition(Point3::new(1, 0, 0)),
+                Chunk::filled(ChunkShape::default(), voxel(1)),
+            ))
+            .id();
+        let item = world
+            .spawn((
+                GlobalTransform::from(LocalTransform::from(Vector3::new(3.0, 0.0, 0.0))),
+                Replicated,
+            ))
+            .id();
+
+        world.run_schedule(schedule::Update);
+        world.run_schedule(schedule::PostUpdate);
+
+        let messages = drain(&mut client_end);
+        assert!(matches!(
+            messages[..],
+            [
+                (Channel::Reliable, ServerMessage::Chunk { position, ref update }),
+                (Channel::Reliable, ServerMessage::EntityEntered { entity }),
+                (Channel::Unreliable, ServerMessage::EntityMoved { entity: moved, .. }),
+            ] if position == Point3::new(1, 0, 0) && update.full && entity == item && moved == item
+        ));
+
+        // only the changed section is sent again
+        world
+            .get_mut::<Chunk<TerrainVoxel, ChunkShape>>(chunk)
+            .unwrap()
+            .set(Point3::new(1, 2, 3), voxel(2));
+        world.run_schedule(schedule::Update);
+        world.run_schedule(schedule::PostUpdate);
+
+        let messages = drain(&mut client_end);
+        assert!(matches!(
+            messages[..],
+            [(Channel::Reliable, ServerMessage::Chunk { ref update, .. })]
+                if !update.full && update.sections.len() == 1
+        ));
+
+        world.despawn(item);
+        world.despawn(chunk);
+        world.run_schedule(schedule::Update);
+        world.run_schedule(schedule::PostUpdate);
+
+        let messages = drain(&mut client_end);
+        assert!(matches!(
+            messages[..],
+            [
+                (Channel::Reliable, ServerMessage::ChunkUnloaded { .. }),
+                (Channel::Reliable, ServerMessage::EntityLeft { entity }),
+            ] if entity == item
+        ));
+
+        // the server notices when the client is gone
+        drop(client_end);
+        world.run_schedule(schedule::PreUpdate);
+        assert!(world.get::<Connection>(client).is_none());
+    }
+
+    #[test]
+    fn it_only_announces_chunks_to_clients_sharing_the_world() {
+        ComputeTaskPool::get_or_init(TaskPool::default);
+
+        let mut builder = WorldBuilder::default();
+        builder
+            .add_plugin(ChunkMapPlugin)
+            .unwrap()
+            .add_plugin(ReplicationPlugin)
+            .unwrap()
+            .insert_resource(Time {
+                app_start: Instant::now(),
+                app_start_utc: Utc::now(),
+                tick_start: Instant::now(),
+                tick_delta: Duration::from_millis(20),
+                tick_count: 0,
+            });
+        let mut world = builder.build().unwrap();
+
+        let (server_end, mut client_end) = loopback();
+        let client = world
+            .spawn((
+                GlobalTransform::identity(),
+                ChunkLoader {
+                    radius: Vector3::repeat(1),
+                },
+                Interest::default(),
+                Connection::sharing_world(server_end),
+            ))
+            .id();
+        let chunk = world
+            .spawn((
+                ChunkPosition(Point3::new(1, 0, 0)),
+                Chunk::filled(ChunkShape::default(), voxel(1)),
+            ))
+            .id();
+
+        // the local client is trusted
+        world.run_schedule(schedule::PreUpdate);
+        assert!(world.get::<ClientValidation>(client).is_none());
+
+        world.run_schedule(schedule::Update);
+        world.run_schedule(schedule::PostUpdate);
+
+        let messages = drain(&mut client_end);
+        assert!(matches!(
+            messages[..],
+            [(Channel::Reliable, ServerMessage::ChunkShared { position })]
+                if position == Point3::new(1, 0, 0)
+        ));
+
+        // changes are not sent
+        world
+            .get_mut::<Chunk<TerrainVoxel, ChunkShape>>(chunk)
+            .unwrap()
+            .set(Point3::new(1, 2, 3), voxel(2));
+        world.run_schedule(schedule::Update);
+        world.run_schedule(schedule::PostUpdate);
+
+        assert!(drain(&mut client_end).is_empty());
+    }
+
+    #[test]
+    fn it_clamps_the_interest_radius() {
+        ComputeTaskPool::get_or_init(TaskPool::default);
+
+        let mut builder = WorldBuilder::default();
+        builder
+            .add_plugin(InterestPlugin {
+                config: InterestConfig {
+                    radius: 64.0,
+                    ..Default::default()
+                },
+            })
+            .unwrap()
+            .add_plugin(ReplicationPlugin)
+            .unwrap();
+        let mut world = builder.build().unwrap();
+
+        let (server_end, mut client_end) = loopback();
+        let client = world
+            .spawn((Interest::default(), Connection::new(server_end)))
+            .id();
+
+        let mut set_radius = |world: &mut World, radius| {
+            client_end
+                .send(
+                    Channel::Reliable,
+                    ClientMessage::SetInterestRadius { radius },
+                )
+                .unwrap();
+            world.run_schedule(schedule::PreUpdate);
+            world.get::<Interest>(client).unwrap().radius
+        };
+
+        assert_eq!(set_radius(&mut world, Some(16.0)), Some(16.0));
+        assert_eq!(set_radius(&mut world, Some(1000.0)), Some(64.0));
+
+        // invalid radii are ignored
+        assert_eq!(set_radius(&mut world, Some(f32::NAN)), Some(64.0));
+        assert_eq!(set_radius(&mut world, Some(f32::INFINITY)), Some(64.0));
+        assert_eq!(set_radius(&mut world, Some(-1.0)), Some(64.0));
+
+        assert_eq!(set_radius(&mut world, None), None);
+    }
+}
@@ -0,0 +1,236 @@
+//! Sending messages between the server and its clients.
+//!
+//! Replication only talks to a [`Transport`], so it doesn't care whether the
+//! client is on the other side of a socket or in the same process. Singleplayer
+//! uses a [`Loopback`], which moves the messages from one end to the other
+//! without serializing or copying them.
+
+use std::{
+    collections::VecDeque,
+    sync::Arc,
+};
+
+use parking_lot::Mutex;
+
+/// How a message is delivered.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Channel {
+    /// Messages arrive in the order they were sent, and are resent if they
+    /// get lost.
+    Reliable,
+
+    /// Messages might get lost, e.g. updates that are superseded by the next
+    /// one anyway.
+    Unreliable,
+}
+
+/// Estimates how many bytes a message takes up when it's sent over the
+/// network, e.g. for the statistics of transports that don't serialize.
+pub trait MessageSize {
+    fn message_size(&self) -> usize;
+}
+
+/// What went over a connection so far, seen from one end.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TransportStats {
+    pub messages_sent: u64,
+    pub messages_received: u64,
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+
+    /// Messages from the other end that were sent unreliably and never
+    /// arrived.
+    pub messages_lost: u64,
+}
+
+impl TransportStats {
+    /// Fraction of messages from the other end that got lost.
+    pub fn packet_loss(&self) -> f32 {
+        let expected = self.messages_received + self.messages_lost;
+        if expected == 0 {
+            0.0
+        }
+        else {
+            self.messages_lost as f32 / expected as f32
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum TransportError {
+    #[error("connection closed")]
+    Closed,
+}
+
+/// One end of a connection.
+pub trait Transport: Send + Sync + 'static {
+    /// Messages sent to the other end.
+    type Outgoing;
+
+    /// Messages received from the other end.
+    type Incoming;
+
+    fn send(&mut self, channel: Channel, message: Self::Outgoing) -> Result<(), TransportError>;
+
+    /// Receives the next message, if one arrived.
+    ///
+    /// Fails once the other end closed the connection and all messages it sent
+    /// were received.
+    fn recv(&mut self) -> Result<Option<(Channel, Self::Incoming)>, TransportError>;
+
+    fn stats(&self) -> TransportStats;
+}
+
+impl<T> Transport for Box<T>
+where
+    T: Transport + ?Sized,
+{
+    type Outgoing = T::Outgoing;
+    type Incoming = T::Incoming;
+
+    fn send(&mut self, channel: Channel, message: Self::Outgoing) -> Result<(), TransportError> {
+        (**self).send(channel, message)
+    }
+
+    fn recv(&mut self) -> Result<Option<(Channel, Self::Incoming)>, TransportError> {
+        (**self).recv()
+    }
+
+    fn stats(&self) -> TransportStats {
+        (**self).stats()
+    }
+}
+
+/// Creates both ends of an in-process connection.
+///
+/// Nothing gets lost, not even on the unreliable channel. The connection is
+/// closed when either end is dropped.
+pub fn loopback<A, B>() -> (Loopback<A, B>, Loopback<B, A>) {
+    let a_to_b = Arc::new(Mutex::new(Queue::default()));
+    let b_to_a = Arc::new(Mutex::new(Queue::default()));
+
+    (
+        Loopback {
+            outgoing: a_to_b.clone(),
+            incoming: b_to_a.clone(),
+            stats: TransportStats::default(),
+        },
+        Loopback {
+            outgoing: b_to_a,
+            incoming: a_to_b,
+            stats: TransportStats::default(),
+        },
+    )
+}
+
+/// One end of an in-process connection, sending `O` and receiving `I`.
+#[derive(Debug)]
+pub struct Loopback<O, I> {
+    outgoing: Arc<Mutex<Queue<O>>>,
+    incoming: Arc<Mutex<Queue<I>>>,
+    stats: TransportStats,
+}
+
+impl<O, I> Transport for Loopback<O, I>
+where
+    O: MessageSize + Send + 'static,
+    I: MessageSize + Send + 'static,
+{
+    type Outgoing = O;
+    type Incoming = I;
+
+    fn send(&mut self, channel: Channel, message: O) -> Result<(), TransportError> {
+        let mut outgoing = self.outgoing.lock();
+        if outgoing.closed {
+            return Err(TransportError::Closed);
+        }
+
+        self.stats.messages_sent += 1;
+        self.stats.bytes_sent += message.message_size() as u64;
+
+        outgoing.messages.push_back((channel, message));
+        Ok(())
+    }
+
+    fn recv(&mut self) -> Result<Option<(Channel, I)>, TransportError> {
+        let mut incoming = self.incoming.lock();
+        match incoming.messages.pop_front() {
+            Some((channel, message)) => {
+                self.stats.messages_received += 1;
+                self.stats.bytes_received += message.message_size() as u64;
+                Ok(Some((channel, message)))
+            }
+            None if incoming.closed => Err(TransportError::Closed),
+            None => Ok(None),
+        }
+    }
+
+    fn stats(&self) -> TransportStats {
+        self.stats
+    }
+}
+
+impl<O, I> Drop for Loopback<O, I> {
+    fn drop(&mut self) {
+        self.outgoing.lock().closed = true;
+        self.incoming.lock().closed = true;
+    }
+}
+
+#[derive(Debug)]
+struct Queue<M> {
+    messages: VecDeque<(Channel, M)>,
+    closed: bool,
+}
+
+impl<M> Default for Queue<M> {
+    fn default() -> Self {
+        Self {
+            messages: VecDeque::new(),
+            closed: false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::net::transport::{
+        Channel,
+        MessageSize,
+        Transport,
+        TransportError,
+        loopback,
+    };
+
+    impl MessageSize for &'static str {
+        fn message_size(&self) -> usize {
+            self.len()
+        }
+    }
+
+    #[test]
+    fn it_counts_messages_until_closed() {
+        let (mut server, mut client) = loopback::<&'static str, &'static str>();
+
+        server.send(Channel::Reliable, "hello").unwrap();
+        server.send(Channel::Unreliable, "world!").unwrap();
+        drop(server);
+
+        assert_eq!(client.recv().unwrap(), Some((Channel::Reliable, "hello")));
+        assert_eq!(
+            client.recv().unwrap(),
+            Some((Channel::Unreliable, "world!"))
+        );
+        assert!(matches!(client.recv(), Err(TransportError::Closed)));
+        assert!(matches!(
+            client.send(Channel::Reliable, "anyone?"),
+            Err(TransportError::Closed)
+        ));
+
+        let stats = client.stats();
+        assert_eq!(stats.messages_received, 2);
+        assert_eq!(stats.bytes_received, 11);
+        assert_eq!(stats.messages_sent, 0);
+        assert_eq!(stats.packet_loss(), 0.0);
+    }
+}
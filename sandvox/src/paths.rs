@@ -0,0 +1,134 @@
+//! Where the game reads and writes its files.
+//!
+//! By default the platform's directories are used, e.g. on Linux the config
+//! goes to `~/.config/sandvox` and saves and logs go to
+//! `~/.local/share/sandvox`. Each directory can be overridden on the command
+//! line. Assets ship with the game, so they're still loaded relative to the
+//! working directory.
+//!
+//! Older versions kept everything in the working directory. Files found there
+//! are still used, see [`Paths::world_file`] and [`Paths::copy_legacy_files`].
+
+use std::path::{
+    Path,
+    PathBuf,
+};
+
+use bevy_ecs::resource::Resource;
+use directories::ProjectDirs;
+
+#[derive(Clone, Debug, Default, clap::Args)]
+pub struct PathArgs {
+    /// Directory for the config file and the window state.
+    #[clap(long)]
+    pub config_dir: Option<PathBuf>,
+
+    /// Directory for saves, logs and crash reports.
+    #[clap(long)]
+    pub data_dir: Option<PathBuf>,
+
+    /// Directory for files that can be recreated, e.g. debug dumps.
+    #[clap(long)]
+    pub cache_dir: Option<PathBuf>,
+}
+
+#[derive(Clone, Debug, Resource)]
+pub struct Paths {
+    config: PathBuf,
+    data: PathBuf,
+    cache: PathBuf,
+}
+
+impl Paths {
+    pub fn new(args: &PathArgs) -> Self {
+        let project_dirs = ProjectDirs::from("", "", "sandvox");
+        if project_dirs.is_none() {
+            tracing::warn!("No home directory found. Using the working directory instead.");
+        }
+
+        let dir = |arg: &Option<PathBuf>, default: fn(&ProjectDirs) -> &Path| {
+            arg.clone()
+                .or_else(|| project_dirs.as_ref().map(|dirs| default(dirs).to_owned()))
+                .unwrap_or_else(|| PathBuf::from("."))
+        };
+
+        let paths = Self {
+            config: dir(&args.config_dir, ProjectDirs::config_dir),
+            data: dir(&args.data_dir, ProjectDirs::data_dir),
+            cache: dir(&args.cache_dir, ProjectDirs::cache_dir),
+        };
+        tracing::info!(?paths);
+
+        paths
+    }
+
+    pub fn config_dir(&self) -> &Path {
+        &self.config
+    }
+
+    pub fn data_dir(&self) -> &Path {
+        &self.data
+    }
+
+    pub fn cache_dir(&self) -> &Path {
+        &self.cache
+    }
+
+    pub fn config_file(&self) -> PathBuf {
+        self.config.join("config.toml")
+    }
+
+    pub fn window_state_file(&self) -> PathBuf {
+        self.config.join("window.toml")
+    }
+
+    pub fn saves_dir(&self) -> PathBuf {
+        self.data.join("saves")
+    }
+
+    pub fn crashes_dir(&self) -> PathBuf {
+        self.data.join("crashes")
+    }
+
+    /// Resolves a path that's relative to the data directory, e.g. from an RCON
+    /// command. Absolute paths are left alone.
+    pub fn data_file(&self, path: impl AsRef<Path>) -> PathBuf {
+        self.data.join(path)
+    }
+
+    /// Resolves the path of a world file. Relative paths are relative to the
+    /// saves directory, unless the file exists in the working directory, where
+    /// worlds used to be saved.
+    pub fn world_file(&self, path: impl AsRef<Path>) -> PathBuf {
+        let path = path.as_ref();
+        if path.is_relative() && path.exists() {
+            tracing::info!(path = %path.display(), "using world file in the working directory");
+            path.to_owned()
+        }
+        else {
+            self.saves_dir().join(path)
+        }
+    }
+
+    /// Copies the config and window state from the working directory, where
+    /// they used to be, if they don't exist in the config directory yet.
+    pub fn copy_legacy_files(&self) {
+        for (legacy, path) in [
+            ("config.toml", self.config_file()),
+            ("window.toml", self.window_state_file()),
+        ] {
+            let legacy = Path::new(legacy);
+            if path.exists() || !legacy.exists() {
+                continue;
+            }
+
+            tracing::info!(from = %legacy.display(), to = %path.display(), "copying legacy file");
+
+            let result =
+                std::fs::create_dir_all(&self.config).and_then(|()| std::fs::copy(legacy, &path));
+            if let Err(error) = result {
+                tracing::warn!(?error, path = %legacy.display(), "could not copy legacy file");
+            }
+        }
+    }
+}
@@ -0,0 +1,367 @@
+//! Captures CPU spans, GPU spans and memory usage for a while, and writes them
+//! as a [Chrome trace][1].
+//!
+//! CPU spans are taken from `tracing` spans (bevy_ecs creates one for every
+//! system run) by the [`ProfileCaptureLayer`], which must be installed in the
+//! tracing subscriber.
+//!
+//! The trace can be viewed with `chrome://tracing` or [Perfetto][2].
+//!
+//! [1]: https://docs.google.com/document/d/1CvAClvFfyA5R-PhYUmn5OOQtYMH4h6I0nSsKchNAySU
+//! [2]: https://ui.perfetto.dev
+
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::BufWriter,
+    path::{
+        Path,
+        PathBuf,
+    },
+    sync::atomic::{
+        AtomicBool,
+        AtomicU64,
+        Ordering,
+    },
+    time::{
+        Duration,
+        Instant,
+    },
+};
+
+use bevy_ecs::system::Res;
+use color_eyre::eyre::{
+    Error,
+    bail,
+};
+use parking_lot::Mutex;
+use serde::Serialize;
+use tracing::{
+    Subscriber,
+    field::{
+        Field,
+        Visit,
+    },
+    span,
+};
+use tracing_subscriber::{
+    Layer,
+    layer::Context,
+    registry::LookupSpan,
+};
+
+use crate::{
+    ecs::{
+        plugin::{
+            Plugin,
+            WorldBuilder,
+        },
+        schedule,
+    },
+    util::stats_alloc::{
+        MemoryTag,
+        bytes_allocated_with_tag,
+    },
+    wgpu::WgpuContext,
+};
+
+/// Thread ID used for GPU spans.
+const GPU_THREAD_ID: u64 = 0;
+
+static CAPTURE: ProfileCapture = ProfileCapture {
+    active: AtomicBool::new(false),
+    state: parking_lot::const_mutex(None),
+};
+
+static NEXT_THREAD_ID: AtomicU64 = AtomicU64::new(GPU_THREAD_ID + 1);
+
+thread_local! {
+    static THREAD_ID: u64 = NEXT_THREAD_ID.fetch_add(1, Ordering::Relaxed);
+}
+
+struct ProfileCapture {
+    active: AtomicBool,
+    state: Mutex<Option<CaptureState>>,
+}
+
+#[derive(Debug)]
+struct CaptureState {
+    start: Instant,
+    duration: Duration,
+    path: PathBuf,
+    events: Vec<TraceEvent>,
+    thread_names: HashMap<u64, String>,
+}
+
+impl CaptureState {
+    fn timestamp(&self, instant: Instant) -> f64 {
+        instant.saturating_duration_since(self.start).as_secs_f64() * 1e6
+    }
+
+    fn push_counter(&mut self, name: String, time: Instant, values: serde_json::Value) {
+        let ts = self.timestamp(time);
+        self.events.push(TraceEvent {
+            name,
+            ph: "C",
+            ts,
+            dur: None,
+            pid: 1,
+            tid: THREAD_ID.with(|id| *id),
+            args: Some(values),
+        });
+    }
+
+    fn push_span(&mut self, name: String, thread_id: u64, start: Instant, duration: Duration) {
+        let ts = self.timestamp(start);
+        self.events.push(TraceEvent {
+            name,
+            ph: "X",
+            ts,
+            dur: Some(duration.as_secs_f64() * 1e6),
+            pid: 1,
+            tid: thread_id,
+            args: None,
+        });
+    }
+}
+
+/// Starts a capture that's written to `path` after `duration`.
+pub fn start_capture(duration: Duration, path: impl Into<PathBuf>) -> Result<(), Error> {
+    let mut state = CAPTURE.state.lock();
+    if state.is_some() {
+        bail!("A profile capture is already running");
+    }
+
+    let path = path.into();
+    tracing::info!(?duration, path = %path.display(), "starting profile capture");
+
+    *state = Some(CaptureState {
+        start: Instant::now(),
+        duration,
+        path,
+        events: vec![],
+        thread_names: HashMap::from([(GPU_THREAD_ID, "gpu".to_owned())]),
+    });
+    CAPTURE.active.store(true, Ordering::Release);
+
+    Ok(())
+}
+
+#[inline]
+pub fn is_capturing() -> bool {
+    CAPTURE.active.load(Ordering::Acquire)
+}
+
+/// Records a span on the GPU track.
+pub fn record_gpu_span(name: &str, start: Instant, duration: Duration) {
+    if is_capturing()
+        && let Some(state) = &mut *CAPTURE.state.lock()
+    {
+        state.push_span(name.to_owned(), GPU_THREAD_ID, start, duration);
+    }
+}
+
+/// Records counter values, which are shown as a graph named `name`.
+pub fn record_counter(name: &str, time: Instant, values: serde_json::Value) {
+    if is_capturing()
+        && let Some(state) = &mut *CAPTURE.state.lock()
+    {
+        state.push_counter(name.to_owned(), time, values);
+    }
+}
+
+/// Records a span on the track of the current thread.
+fn record_cpu_span(name: String, start: Instant, duration: Duration) {
+    let thread_id = THREAD_ID.with(|id| *id);
+
+    if let Some(state) = &mut *CAPTURE.state.lock() {
+        state.thread_names.entry(thread_id).or_insert_with(|| {
+            std::thread::current()
+                .name()
+                .map_or_else(|| format!("thread {thread_id}"), ToOwned::to_owned)
+        });
+        state.push_span(name, thread_id, start, duration);
+    }
+}
+
+/// Samples the memory usage and finishes the capture when its time is up.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ProfileCapturePlugin;
+
+impl Plugin for ProfileCapturePlugin {
+    fn setup(&self, builder: &mut WorldBuilder) -> Result<(), Error> {
+        builder.add_systems(schedule::PostUpdate, update_capture);
+        Ok(())
+    }
+}
+
+fn update_capture(wgpu: Option<Res<WgpuContext>>) {
+    if !is_capturing() {
+        return;
+    }
+
+    let now = Instant::now();
+    let gpu_bytes = wgpu
+        .and_then(|wgpu| wgpu.device.generate_allocator_report())
+        .map(|report| report.total_allocated_bytes);
+
+    // the tags add up to all CPU memory
+    let mut memory = serde_json::Map::new();
+    memory.insert("gpu_bytes".to_owned(), gpu_bytes.into());
+    for tag in MemoryTag::ALL {
+        memory.insert(
+            format!("cpu_{}_bytes", tag.name()),
+            bytes_allocated_with_tag(tag).into(),
+        );
+    }
+
+    let finished = {
+        let mut state_guard = CAPTURE.state.lock();
+        let state = state_guard.as_mut().unwrap();
+
+        state.push_counter("memory".to_owned(), now, memory.into());
+
+        if now.duration_since(state.start) >= state.duration {
+            CAPTURE.active.store(false, Ordering::Release);
+            state_guard.take()
+        }
+        else {
+            None
+        }
+    };
+
+    if let Some(state) = finished {
+        // writing a few seconds of spans takes a moment
+        std::thread::spawn(move || {
+            match write_trace(&state.path, state.events, state.thread_names) {
+                Ok(()) => {
+                    tracing::info!(path = %state.path.display(), "profile capture written");
+                }
+                Err(error) => {
+                    tracing::error!(path = %state.path.display(), "couldn't write profile capture: {error}");
+                }
+            }
+        });
+    }
+}
+
+fn write_trace(
+    path: &Path,
+    mut events: Vec<TraceEvent>,
+    thread_names: HashMap<u64, String>,
+) -> Result<(), Error> {
+    events.extend(thread_names.into_iter().map(|(thread_id, name)| {
+        TraceEvent {
+            name: "thread_name".to_owned(),
+            ph: "M",
+            ts: 0.0,
+            dur: None,
+            pid: 1,
+            tid: thread_id,
+            args: Some(serde_json::json!({ "name": name })),
+        }
+    }));
+
+    let writer = BufWriter::new(File::create(path)?);
+    serde_json::to_writer(
+        writer,
+        &TraceFile {
+            trace_events: events,
+            display_time_unit: "ms",
+        },
+    )?;
+
+    Ok(())
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct TraceFile {
+    trace_events: Vec<TraceEvent>,
+    display_time_unit: &'static str,
+}
+
+#[derive(Debug, Serialize)]
+struct TraceEvent {
+    name: String,
+    ph: &'static str,
+    ts: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    dur: Option<f64>,
+    pid: u32,
+    tid: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    args: Option<serde_json::Value>,
+}
+
+/// Records `tracing` spans while a capture is running.
+///
+/// The span's `name` field is used as its name if it has one, so that systems
+/// show up with their own names instead of as `system`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ProfileCaptureLayer;
+
+#[derive(Debug)]
+struct SpanName(String);
+
+#[derive(Debug)]
+struct SpanEntered(Instant);
+
+impl<S> Layer<S> for ProfileCaptureLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &span::Attributes<'_>, id: &span::Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id)
+        else {
+            return;
+        };
+
+        let mut visitor = NameVisitor(None);
+        attrs.record(&mut visitor);
+
+        let name = visitor
+            .0
+            .unwrap_or_else(|| attrs.metadata().name().to_owned());
+        span.extensions_mut().insert(SpanName(name));
+    }
+
+    fn on_enter(&self, id: &span::Id, ctx: Context<'_, S>) {
+        if is_capturing()
+            && let Some(span) = ctx.span(id)
+        {
+            span.extensions_mut().replace(SpanEntered(Instant::now()));
+        }
+    }
+
+    fn on_exit(&self, id: &span::Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id)
+        else {
+            return;
+        };
+
+        let mut extensions = span.extensions_mut();
+        if let Some(SpanEntered(entered)) = extensions.remove::<SpanEntered>()
+            && is_capturing()
+            && let Some(SpanName(name)) = extensions.get_mut::<SpanName>()
+        {
+            record_cpu_span(name.clone(), entered, entered.elapsed());
+        }
+    }
+}
+
+struct NameVisitor(Option<String>);
+
+impl Visit for NameVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "name" {
+            self.0 = Some(value.to_owned());
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if field.name() == "name" {
+            self.0 = Some(format!("{value:?}").trim_matches('"').to_owned());
+        }
+    }
+}
@@ -1,3 +1,5 @@
+pub mod capture;
+pub mod systems;
 pub mod wgpu;
 
 use bevy_ecs::resource::Resource;
@@ -0,0 +1,272 @@
+//! Per-system CPU timings.
+//!
+//! bevy_ecs enters a `system` span every time it runs a system. The
+//! [`SystemTimingsLayer`] measures these spans, and the
+//! [`SystemTimingsPlugin`] aggregates them once per frame into the
+//! [`SystemTimings`] resource.
+
+use std::{
+    collections::HashMap,
+    sync::{
+        Arc,
+        LazyLock,
+        atomic::{
+            AtomicU32,
+            AtomicU64,
+            Ordering,
+        },
+    },
+    time::Instant,
+};
+
+use bevy_ecs::{
+    resource::Resource,
+    system::ResMut,
+};
+use bevy_utils::prelude::DebugName;
+use color_eyre::eyre::Error;
+use parking_lot::Mutex;
+use tracing::{
+    Subscriber,
+    field::{
+        Field,
+        Visit,
+    },
+    span,
+};
+use tracing_subscriber::{
+    Layer,
+    layer::Context,
+    registry::LookupSpan,
+};
+
+use crate::ecs::{
+    plugin::{
+        Plugin,
+        WorldBuilder,
+    },
+    schedule,
+};
+
+/// Smoothing factor for the exponential moving average of the system timings.
+const SMOOTHING: f32 = 0.05;
+
+/// Accumulators of all systems that were seen by the [`SystemTimingsLayer`].
+static ACCUMULATORS: LazyLock<Mutex<Vec<Arc<SystemAccumulator>>>> = LazyLock::new(Default::default);
+
+/// Time spent in a system since it was last collected.
+#[derive(Debug)]
+struct SystemAccumulator {
+    name: Arc<str>,
+    nanos: AtomicU64,
+    runs: AtomicU32,
+}
+
+impl SystemAccumulator {
+    fn get_or_insert(name: &str) -> Arc<Self> {
+        let mut accumulators = ACCUMULATORS.lock();
+
+        // systems can be added more than once (e.g. to different schedules), but we
+        // report them by name.
+        if let Some(accumulator) = accumulators
+            .iter()
+            .find(|accumulator| &*accumulator.name == name)
+        {
+            return accumulator.clone();
+        }
+
+        let accumulator = Arc::new(Self {
+            name: DebugName::owned(name.to_owned())
+                .shortname()
+                .to_string()
+                .into(),
+            nanos: AtomicU64::new(0),
+            runs: AtomicU32::new(0),
+        });
+        accumulators.push(accumulator.clone());
+        accumulator
+    }
+}
+
+/// CPU time spent in each system, sorted by the smoothed time per frame with
+/// the most expensive system first.
+#[derive(Debug, Default, Resource)]
+pub struct SystemTimings {
+    systems: Vec<SystemTiming>,
+    index: HashMap<Arc<str>, usize>,
+}
+
+impl SystemTimings {
+    pub fn iter(&self) -> impl Iterator<Item = &SystemTiming> {
+        self.systems.iter()
+    }
+
+    /// The `count` most expensive systems.
+    pub fn worst(&self, count: usize) -> &[SystemTiming] {
+        &self.systems[..count.min(self.systems.len())]
+    }
+
+    pub fn get(&self, name: &str) -> Option<&SystemTiming> {
+        self.index.get(name).map(|index| &self.systems[*index])
+    }
+
+    pub fn len(&self) -> usize {
+        self.systems.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.systems.is_empty()
+    }
+
+    fn collect(&mut self) {
+        let accumulators = ACCUMULATORS.lock();
+
+        for accumulator in accumulators.iter() {
+            let nanos = accumulator.nanos.swap(0, Ordering::Relaxed);
+            let runs = accumulator.runs.swap(0, Ordering::Relaxed);
+            let frame_ms = nanos as f32 * 1e-6;
+
+            if let Some(index) = self.index.get(&accumulator.name) {
+                let timing = &mut self.systems[*index];
+                timing.frame_ms = frame_ms;
+                timing.smoothed_ms += SMOOTHING * (frame_ms - timing.smoothed_ms);
+                timing.peak_ms = timing.peak_ms.max(frame_ms);
+                timing.runs = runs;
+            }
+            else {
+                self.systems.push(SystemTiming {
+                    name: accumulator.name.clone(),
+                    frame_ms,
+                    smoothed_ms: frame_ms,
+                    peak_ms: frame_ms,
+                    runs,
+                });
+            }
+        }
+
+        self.systems
+            .sort_by(|a, b| b.smoothed_ms.total_cmp(&a.smoothed_ms));
+
+        self.index.clear();
+        self.index.extend(
+            self.systems
+                .iter()
+                .enumerate()
+                .map(|(index, timing)| (timing.name.clone(), index)),
+        );
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct SystemTiming {
+    /// Name of the system without module paths.
+    pub name: Arc<str>,
+
+    /// Time spent in the system during the last frame.
+    pub frame_ms: f32,
+
+    /// Smoothed time spent in the system per frame.
+    pub smoothed_ms: f32,
+
+    /// Most time spent in the system during a single frame.
+    pub peak_ms: f32,
+
+    /// How often the system ran during the last frame.
+    pub runs: u32,
+}
+
+/// Collects the system timings measured by the [`SystemTimingsLayer`] into
+/// the [`SystemTimings`] resource.
+///
+/// Without the layer installed in the tracing subscriber, the resource stays
+/// empty.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemTimingsPlugin;
+
+impl Plugin for SystemTimingsPlugin {
+    fn setup(&self, builder: &mut WorldBuilder) -> Result<(), Error> {
+        builder
+            .init_resource::<SystemTimings>()
+            .add_systems(schedule::PreUpdate, collect_system_timings);
+
+        Ok(())
+    }
+}
+
+/// Runs first in the frame, so the timings cover one whole frame.
+fn collect_system_timings(mut timings: ResMut<SystemTimings>) {
+    timings.collect();
+}
+
+/// Measures the `system` spans that bevy_ecs enters for every system run.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemTimingsLayer;
+
+#[derive(Debug)]
+struct SystemSpan {
+    accumulator: Arc<SystemAccumulator>,
+    entered: Option<Instant>,
+}
+
+impl<S> Layer<S> for SystemTimingsLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &span::Attributes<'_>, id: &span::Id, ctx: Context<'_, S>) {
+        if attrs.metadata().name() != "system" {
+            return;
+        }
+
+        let Some(span) = ctx.span(id)
+        else {
+            return;
+        };
+
+        let mut visitor = SystemNameVisitor(None);
+        attrs.record(&mut visitor);
+
+        if let Some(name) = visitor.0 {
+            span.extensions_mut().insert(SystemSpan {
+                accumulator: SystemAccumulator::get_or_insert(&name),
+                entered: None,
+            });
+        }
+    }
+
+    fn on_enter(&self, id: &span::Id, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.span(id)
+            && let Some(system_span) = span.extensions_mut().get_mut::<SystemSpan>()
+        {
+            system_span.entered = Some(Instant::now());
+        }
+    }
+
+    fn on_exit(&self, id: &span::Id, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.span(id)
+            && let Some(system_span) = span.extensions_mut().get_mut::<SystemSpan>()
+            && let Some(entered) = system_span.entered.take()
+        {
+            let accumulator = &system_span.accumulator;
+            accumulator
+                .nanos
+                .fetch_add(entered.elapsed().as_nanos() as u64, Ordering::Relaxed);
+            accumulator.runs.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+struct SystemNameVisitor(Option<String>);
+
+impl Visit for SystemNameVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "name" {
+            self.0 = Some(value.to_owned());
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if field.name() == "name" {
+            self.0 = Some(format!("{value:?}").trim_matches('"').to_owned());
+        }
+    }
+}
@@ -1,10 +1,24 @@
 use std::{
+    collections::VecDeque,
     panic::Location,
-    sync::mpsc,
+    sync::{
+        Arc,
+        mpsc,
+    },
+    time::{
+        Duration,
+        Instant,
+    },
 };
 
+use bevy_ecs::resource::Resource;
+use parking_lot::Mutex;
+
 use crate::{
-    profiler::Profiler,
+    profiler::{
+        Profiler,
+        capture,
+    },
     wgpu::query::{
         QuerySetAllocation,
         QuerySetPool,
@@ -17,14 +31,30 @@ use crate::{
 pub struct WgpuProfiler {
     pool: QuerySetPool,
     sink: WgpuProfilerSink,
+    timings: GpuTimings,
+    timestamp_period: f32,
 }
 
 impl WgpuProfiler {
-    pub fn new(device: &wgpu::Device, timestamp_period: f32, profiler: &Profiler) -> Self {
+    /// Creates a GPU profiler. Without a [`Profiler`] the results only go into
+    /// the [`GpuTimings`].
+    pub fn new(device: &wgpu::Device, timestamp_period: f32, profiler: Option<&Profiler>) -> Self {
         let pool = QuerySetPool::new(device, wgpu::QueryType::Timestamp, "profiler");
-        let sink = profiler.wgpu_sink(timestamp_period);
+        let sink = profiler.map_or_else(Default::default, |profiler| {
+            profiler.wgpu_sink(timestamp_period)
+        });
 
-        Self { pool, sink }
+        Self {
+            pool,
+            sink,
+            timings: Default::default(),
+            timestamp_period,
+        }
+    }
+
+    #[inline]
+    pub fn timings(&self) -> &GpuTimings {
+        &self.timings
     }
 
     #[track_caller]
@@ -37,6 +67,8 @@ impl WgpuProfiler {
             transaction,
             start_end: None,
             sink: self.sink.clone(),
+            timings: self.timings.clone(),
+            timestamp_period: self.timestamp_period,
             render_pass_caller,
             spans: vec![],
             label,
@@ -54,6 +86,8 @@ pub struct RenderPassProfiler {
     transaction: QuerySetTransaction,
     start_end: Option<QuerySetAllocation>,
     sink: WgpuProfilerSink,
+    timings: GpuTimings,
+    timestamp_period: f32,
     render_pass_caller: &'static Location<'static>,
     spans: Vec<QuerySpan>,
     label: &'static str,
@@ -139,6 +173,59 @@ impl RenderPassProfiler {
                     }
                 }
 
+                {
+                    let to_ms = |start: u64, end: u64| {
+                        end.saturating_sub(start) as f32 * self.timestamp_period * 1e-6
+                    };
+
+                    let mut timings = self.timings.inner.lock();
+                    timings.record(
+                        self.label,
+                        None,
+                        to_ms(render_pass_times[0], render_pass_times[1]),
+                    );
+                    for span in &self.spans {
+                        if let Some(exit) = &span.exit {
+                            timings.record(
+                                span.label,
+                                Some(self.label),
+                                to_ms(span.enter.timestamp, exit.timestamp),
+                            );
+                        }
+                    }
+                }
+
+                if capture::is_capturing() {
+                    // we don't know when exactly the pass ran on the CPU clock, so we pretend it
+                    // just ended.
+                    let now = Instant::now();
+                    let to_duration = |start: u64, end: u64| {
+                        Duration::from_nanos(
+                            (end.saturating_sub(start) as f64 * f64::from(self.timestamp_period))
+                                as u64,
+                        )
+                    };
+                    let to_instant = |timestamp: u64| {
+                        now.checked_sub(to_duration(timestamp, render_pass_times[1]))
+                            .unwrap_or(now)
+                    };
+
+                    capture::record_gpu_span(
+                        self.label,
+                        to_instant(render_pass_times[0]),
+                        to_duration(render_pass_times[0], render_pass_times[1]),
+                    );
+                    for span in &self.spans {
+                        if let Some(exit) = &span.exit {
+                            capture::record_gpu_span(
+                                span.label,
+                                to_instant(span.enter.timestamp),
+                                to_duration(span.enter.timestamp, exit.timestamp),
+                            );
+                        }
+                    }
+                }
+
                 self.sink.write(
                     reference_time,
                     RenderPassSpan {
@@ -154,6 +241,71 @@ impl RenderPassProfiler {
     }
 }
 
+/// How many samples of each timing are kept.
+pub const GPU_TIMINGS_HISTORY: usize = 120;
+
+/// Weight of a new sample in the smoothed timings.
+const GPU_TIMINGS_SMOOTHING: f32 = 0.1;
+
+/// GPU time spent in render passes and the spans inside them.
+///
+/// The timings arrive a few frames late, when the queries are read back.
+#[derive(Clone, Debug, Default, Resource)]
+pub struct GpuTimings {
+    inner: Arc<Mutex<GpuTimingsInner>>,
+}
+
+impl GpuTimings {
+    /// Copies the current timings, in the order they were first recorded.
+    pub fn snapshot(&self) -> Vec<GpuTiming> {
+        self.inner.lock().timings.clone()
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct GpuTiming {
+    pub label: &'static str,
+
+    /// The render pass a span is in, or `None` if this is a render pass.
+    pub pass: Option<&'static str>,
+
+    /// Exponentially smoothed time in milliseconds.
+    pub smoothed_ms: f32,
+
+    /// Most recent samples in milliseconds, oldest first.
+    pub history: VecDeque<f32>,
+}
+
+#[derive(Debug, Default)]
+struct GpuTimingsInner {
+    timings: Vec<GpuTiming>,
+}
+
+impl GpuTimingsInner {
+    fn record(&mut self, label: &'static str, pass: Option<&'static str>, ms: f32) {
+        if let Some(timing) = self
+            .timings
+            .iter_mut()
+            .find(|timing| timing.label == label && timing.pass == pass)
+        {
+            timing.smoothed_ms += GPU_TIMINGS_SMOOTHING * (ms - timing.smoothed_ms);
+
+            if timing.history.len() == GPU_TIMINGS_HISTORY {
+                timing.history.pop_front();
+            }
+            timing.history.push_back(ms);
+        }
+        else {
+            self.timings.push(GpuTiming {
+                label,
+                pass,
+                smoothed_ms: ms,
+                history: std::iter::once(ms).collect(),
+            });
+        }
+    }
+}
+
 #[inline]
 fn get_reference_timestamp() -> i64 {
     #![allow(unused)]
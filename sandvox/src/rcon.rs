@@ -1,27 +1,63 @@
-use std::net::SocketAddr;
+use std::{
+    fmt::Write,
+    net::SocketAddr,
+    time::Duration,
+};
 
 use bevy_ecs::{
+    change_detection::{
+        DetectChangesMut,
+        Mut,
+    },
     entity::Entity,
+    message::MessageWriter,
+    name::Name,
     query::With,
     resource::Resource,
     system::{
+        Commands,
         In,
         InMut,
         IntoSystem,
         Query,
+        Res,
+        ResMut,
         Single,
     },
     world::World,
 };
 use color_eyre::eyre::{
     Error,
+    OptionExt,
+    bail,
     eyre,
 };
 use futures_lite::StreamExt;
-use nalgebra::Vector3;
+use nalgebra::{
+    Point3,
+    Vector3,
+};
 use sandvox_rcon::{
+    BlockPosition,
     Command,
+    Destination,
+    ExplodeCommand,
+    ExportCommand,
+    FillCommand,
+    GameModeCommand,
+    ImportCommand,
+    NetCommand,
+    PregenerateCommand,
+    ProfileCommand,
+    RedoCommand,
+    RegionCommand,
+    SnapshotCommand,
+    StatsCommand,
+    TeleportAction,
     TeleportCommand,
+    TimeCommand,
+    UndoCommand,
+    WaypointCommand,
 };
 use serde::{
     Deserialize,
@@ -54,12 +90,70 @@ use crate::{
             WorldBuilder,
         },
         schedule,
+        snapshot::{
+            self,
+            SnapshotFilter,
+        },
         transform::LocalTransform,
     },
-    game::Player,
-    util::tokio::TokioRuntime,
+    game::{
+        ChunkShape,
+        Player,
+        block_type::BlockTypes,
+        clock::GameClock,
+        edit_history::EditHistory,
+        explosion::Explosion,
+        game_mode::GameMode,
+        interaction::TargetedBlock,
+        schematic::Schematic,
+        teleport::{
+            TeleportHistory,
+            TeleportLocations,
+        },
+        terrain::TerrainVoxel,
+        validation::ClientValidation,
+        waypoint::Waypoint,
+        world_edit::{
+            Clipboard,
+            Selection,
+        },
+    },
+    net::{
+        interest::Interest,
+        replication::{
+            Connection,
+            LocalClient,
+        },
+    },
+    paths::Paths,
+    profiler::{
+        capture,
+        systems::SystemTimings,
+    },
+    util::{
+        format_size,
+        tokio::TokioRuntime,
+    },
+    voxel::{
+        access::{
+            Voxels,
+            block_center,
+            split_position,
+        },
+        edit::ChunkEditBatch,
+        loader::PregenerateChunks,
+    },
 };
 
+/// Maximum number of blocks that the `fill` and `region` commands change at
+/// once.
+const MAX_FILL_VOLUME: u64 = 1 << 20;
+
+/// Maximum number of chunks that the `pregenerate` command generates at once.
+/// Pregenerated chunks stay loaded, so this also limits how much memory they
+/// take.
+const MAX_PREGENERATE_CHUNKS: u64 = 1 << 14;
+
 #[derive(Clone, Debug)]
 pub struct RconPlugin {
     pub config: RconConfig,
@@ -121,6 +215,23 @@ fn handle_commands(
                     Command::TeleportCommand(teleport_command) => {
                         teleport_command.handle_command(world)
                     }
+                    Command::Time(time_command) => time_command.handle_command(world),
+                    Command::Profile(profile_command) => profile_command.handle_command(world),
+                    Command::Stats(stats_command) => stats_command.handle_command(world),
+                    Command::Net(net_command) => net_command.handle_command(world),
+                    Command::Snapshot(snapshot_command) => snapshot_command.handle_command(world),
+                    Command::Waypoint(waypoint_command) => waypoint_command.handle_command(world),
+                    Command::Export(export_command) => export_command.handle_command(world),
+                    Command::Import(import_command) => import_command.handle_command(world),
+                    Command::Pregenerate(pregenerate_command) => {
+                        pregenerate_command.handle_command(world)
+                    }
+                    Command::Explode(explode_command) => explode_command.handle_command(world),
+                    Command::Fill(fill_command) => fill_command.handle_command(world),
+                    Command::Region(region_command) => region_command.handle_command(world),
+                    Command::Undo(undo_command) => undo_command.handle_command(world),
+                    Command::Redo(redo_command) => redo_command.handle_command(world),
+                    Command::GameMode(game_mode_command) => game_mode_command.handle_command(world),
                 };
 
                 if let Err(error) = result {
@@ -198,21 +309,320 @@ impl HandleCommand for TeleportCommand {
             .run_system_cached_with(
                 |In(command): In<TeleportCommand>,
                  player: Option<Single<Entity, With<Player>>>,
-                 mut entities: Query<&mut LocalTransform>| {
+                 mut entities: Query<(
+                    &mut LocalTransform,
+                    Option<&mut TeleportHistory>,
+                    Option<&mut ClientValidation>,
+                )>,
+                 mut locations: ResMut<TeleportLocations>,
+                 mut commands: Commands| {
                     let entity = command
                         .entity
                         .map(|entity| Entity::from_bits(entity.0))
                         .or_else(|| player.as_deref().copied())
                         .ok_or_else(|| eyre!("No entity specified and no player found"))?;
 
-                    let mut transform = entities.get_mut(entity)?;
-                    transform.isometry.translation.vector = Vector3::new(
-                        command.destination.x,
-                        command.destination.y,
-                        command.destination.z,
+                    let (mut transform, mut history, mut validation) = entities.get_mut(entity)?;
+                    let current = Point3::from(transform.isometry.translation.vector);
+
+                    let destination = match command.action {
+                        Some(TeleportAction::Save { name }) => {
+                            tracing::info!(%name, position = ?current, "location saved");
+                            locations.insert(name, current);
+                            return Ok(());
+                        }
+                        Some(TeleportAction::Remove { name }) => {
+                            locations
+                                .remove(&name)
+                                .ok_or_else(|| eyre!("No location named `{name}`"))?;
+                            return Ok(());
+                        }
+                        Some(TeleportAction::List) => {
+                            let mut table = String::new();
+                            for (name, position) in locations.iter() {
+                                writeln!(
+                                    &mut table,
+                                    "{:>8.1} {:>8.1} {:>8.1}  {name}",
+                                    position.x, position.y, position.z,
+                                )
+                                .unwrap();
+                            }
+
+                            tracing::info!("teleport locations ({}):\n{table}", locations.len());
+                            return Ok(());
+                        }
+                        Some(TeleportAction::Back) => {
+                            // going back doesn't add to the history, so it can be repeated
+                            let position = history
+                                .as_mut()
+                                .and_then(|history| history.pop())
+                                .ok_or_eyre("No previous position")?;
+                            transform.isometry.translation.vector = position.coords;
+
+                            // the server moved the entity, so this isn't a violation
+                            if let Some(validation) = &mut validation {
+                                validation.accept_position(position);
+                            }
+                            return Ok(());
+                        }
+                        None => {
+                            match command.destination()? {
+                                Destination::Position(coordinates) => {
+                                    Point3::new(
+                                        coordinates[0].resolve(current.x),
+                                        coordinates[1].resolve(current.y),
+                                        coordinates[2].resolve(current.z),
+                                    )
+                                }
+                                Destination::Location(name) => {
+                                    locations
+                                        .get(&name)
+                                        .ok_or_else(|| eyre!("No location named `{name}`"))?
+                                }
+                            }
+                        }
+                    };
+
+                    transform.isometry.translation.vector = destination.coords;
+
+                    if let Some(validation) = &mut validation {
+                        validation.accept_position(destination);
+                    }
+
+                    if let Some(history) = &mut history {
+                        history.push(current);
+                    }
+                    else {
+                        let mut history = TeleportHistory::default();
+                        history.push(current);
+                        commands.entity(entity).insert(history);
+                    }
+
+                    Ok::<(), Error>(())
+                },
+                self,
+            )
+            .unwrap()
+    }
+}
+
+impl HandleCommand for TimeCommand {
+    fn handle_command(self, world: &mut World) -> Result<(), Error> {
+        let mut clock = world
+            .get_resource_mut::<GameClock>()
+            .ok_or_eyre("No game clock")?;
+
+        match self {
+            TimeCommand::Set { time } => clock.set_from_str(&time)?,
+            TimeCommand::Add { seconds } => {
+                let duration = Duration::try_from_secs_f64(seconds.abs())?;
+                if seconds >= 0.0 {
+                    clock.add(duration)?;
+                }
+                else {
+                    clock.subtract(duration)?;
+                }
+            }
+            TimeCommand::Freeze => clock.frozen = true,
+            TimeCommand::Unfreeze => clock.frozen = false,
+        }
+
+        tracing::info!(clock = ?*clock, "game clock modified");
+
+        Ok(())
+    }
+}
+
+impl HandleCommand for ProfileCommand {
+    fn handle_command(self, world: &mut World) -> Result<(), Error> {
+        match self {
+            ProfileCommand::Capture { seconds, path } => {
+                let path = world.resource::<Paths>().data_file(path);
+                capture::start_capture(Duration::try_from_secs_f64(seconds)?, path)
+            }
+        }
+    }
+}
+
+impl HandleCommand for StatsCommand {
+    fn handle_command(self, world: &mut World) -> Result<(), Error> {
+        match self {
+            StatsCommand::Systems { count } => {
+                let timings = world
+                    .get_resource::<SystemTimings>()
+                    .ok_or_eyre("No system timings")?;
+
+                let mut table = String::new();
+                for timing in timings.worst(count) {
+                    writeln!(
+                        &mut table,
+                        "{:>8.3} ms avg {:>8.3} ms peak {:>3} runs  {}",
+                        timing.smoothed_ms, timing.peak_ms, timing.runs, timing.name,
+                    )
+                    .unwrap();
+                }
+
+                tracing::info!(
+                    "system timings ({} of {} systems):\n{table}",
+                    count.min(timings.len()),
+                    timings.len()
+                );
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl HandleCommand for NetCommand {
+    fn handle_command(self, world: &mut World) -> Result<(), Error> {
+        match self {
+            NetCommand::Stats => {
+                world
+                    .run_system_cached(
+                        |connections: Query<(Entity, &Connection, Option<&Interest>)>,
+                         local_client: Option<Res<LocalClient>>| {
+                            let mut table = String::new();
+                            for (client, connection, interest) in &connections {
+                                let stats = connection.stats();
+                                writeln!(
+                                    &mut table,
+                                    "{client}: sent {} in {} messages, received {} in {} messages, {:.1}% lost, {} entities, {} chunks ({} pending)\n    {}",
+                                    format_size(stats.bytes_sent),
+                                    stats.messages_sent,
+                                    format_size(stats.bytes_received),
+                                    stats.messages_received,
+                                    stats.packet_loss() * 100.0,
+                                    interest.map_or(0, |interest| interest.num_entities()),
+                                    connection.chunks().num_chunks(),
+                                    connection.num_pending_chunks(),
+                                    connection.chunks().stats(),
+                                )
+                                .unwrap();
+                            }
+
+                            tracing::info!("{} connections:\n{table}", connections.iter().count());
+
+                            if let Some(local_client) = local_client {
+                                tracing::info!(ping = ?local_client.ping(), "local client");
+                            }
+                        },
+                    )
+                    .unwrap();
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl HandleCommand for SnapshotCommand {
+    fn handle_command(self, world: &mut World) -> Result<(), Error> {
+        match self {
+            SnapshotCommand::Save { path, components } => {
+                let path = world.resource::<Paths>().data_file(path);
+                snapshot::save_snapshot(world, &SnapshotFilter { components }, path)
+            }
+            SnapshotCommand::Load { path } => {
+                let path = world.resource::<Paths>().data_file(path);
+                snapshot::load_snapshot(world, path)
+            }
+        }
+    }
+}
+
+impl HandleCommand for WaypointCommand {
+    fn handle_command(self, world: &mut World) -> Result<(), Error> {
+        world
+            .run_system_cached_with(
+                |In(command): In<WaypointCommand>,
+                 player: Option<Single<&LocalTransform, With<Player>>>,
+                 waypoints: Query<(Entity, &Waypoint, &LocalTransform)>,
+                 mut commands: Commands| {
+                    match command {
+                        WaypointCommand::Set {
+                            name,
+                            x,
+                            y,
+                            z,
+                            beacon,
+                        } => {
+                            let position = x
+                                .zip(y)
+                                .zip(z)
+                                .map(|((x, y), z)| Point3::new(x, y, z))
+                                .or_else(|| {
+                                    player.map(|player| player.isometry.translation.vector.into())
+                                })
+                                .ok_or_else(|| {
+                                    eyre!("No position specified and no player found")
+                                })?;
+
+                            // waypoint names are unique
+                            for (entity, waypoint, _) in &waypoints {
+                                if waypoint.name == name {
+                                    commands.entity(entity).despawn();
+                                }
+                            }
+
+                            Waypoint { name, beacon }.spawn(position, &mut commands);
+                        }
+                        WaypointCommand::Remove { name } => {
+                            let entity = waypoints
+                                .iter()
+                                .find_map(|(entity, waypoint, _)| {
+                                    (waypoint.name == name).then_some(entity)
+                                })
+                                .ok_or_else(|| eyre!("No waypoint named `{name}`"))?;
+                            commands.entity(entity).despawn();
+                        }
+                        WaypointCommand::List => {
+                            let mut table = String::new();
+                            for (_, waypoint, transform) in &waypoints {
+                                let position = transform.isometry.translation.vector;
+                                writeln!(
+                                    &mut table,
+                                    "{:>8.1} {:>8.1} {:>8.1}  {}",
+                                    position.x, position.y, position.z, waypoint.name,
+                                )
+                                .unwrap();
+                            }
+
+                            tracing::info!("waypoints ({}):\n{table}", waypoints.iter().len());
+                        }
+                    }
+
+                    Ok::<(), Error>(())
+                },
+                self,
+            )
+            .unwrap()
+    }
+}
+
+impl HandleCommand for ExportCommand {
+    fn handle_command(mut self, world: &mut World) -> Result<(), Error> {
+        self.path = world.resource::<Paths>().data_file(&self.path);
+
+        world
+            .run_system_cached_with(
+                |In(command): In<ExportCommand>,
+                 voxels: Voxels<TerrainVoxel, ChunkShape>,
+                 block_types: Res<BlockTypes>| {
+                    let schematic = Schematic::export(
+                        &voxels,
+                        &block_types,
+                        block_position(command.min),
+                        block_position(command.max),
+                    )?;
+                    schematic.write(&command.path)?;
+
+                    tracing::info!(
+                        path = %command.path.display(),
+                        size = ?schematic.size(),
+                        "schematic exported"
                     );
 
-                    //todo!();
                     Ok::<(), Error>(())
                 },
                 self,
@@ -220,3 +630,424 @@ impl HandleCommand for TeleportCommand {
             .unwrap()
     }
 }
+
+impl HandleCommand for ImportCommand {
+    fn handle_command(mut self, world: &mut World) -> Result<(), Error> {
+        self.path = world.resource::<Paths>().data_file(&self.path);
+        let schematic = Schematic::read(&self.path)?;
+
+        world
+            .run_system_cached_with(
+                |In((command, schematic)): In<(ImportCommand, Schematic)>,
+                 mut voxels: Voxels<TerrainVoxel, ChunkShape>,
+                 block_types: Res<BlockTypes>,
+                 history: Option<Single<&mut EditHistory, With<Player>>>| {
+                    let batch = schematic.edits(
+                        &block_types,
+                        block_position(command.position),
+                        command.skip_air,
+                    )?;
+                    let num_blocks = batch.len();
+
+                    let num_placed =
+                        apply_edit(&mut voxels, history.map(Single::into_inner), batch);
+                    tracing::info!(
+                        path = %command.path.display(),
+                        num_placed,
+                        "schematic imported"
+                    );
+
+                    if num_placed < num_blocks {
+                        tracing::warn!(
+                            num_skipped = num_blocks - num_placed,
+                            "skipped blocks in chunks that aren't loaded"
+                        );
+                    }
+
+                    Ok::<(), Error>(())
+                },
+                (self, schematic),
+            )
+            .unwrap()
+    }
+}
+
+impl HandleCommand for PregenerateCommand {
+    fn handle_command(self, world: &mut World) -> Result<(), Error> {
+        world
+            .run_system_cached_with(
+                |In(command): In<PregenerateCommand>,
+                 player: Option<Single<&LocalTransform, With<Player>>>,
+                 mut commands: Commands| {
+                    let center = command
+                        .center
+                        .map(block_position)
+                        .or_else(|| {
+                            player.map(|player| {
+                                player
+                                    .isometry
+                                    .translation
+                                    .vector
+                                    .map(|c| c.floor() as i32)
+                                    .into()
+                            })
+                        })
+                        .ok_or_else(|| eyre!("No center specified and no player found"))?;
+                    let (center, _) = split_position(&ChunkShape::default(), center);
+
+                    let radius = Vector3::new(
+                        command.radius,
+                        command.vertical_radius.unwrap_or(command.radius),
+                        command.radius,
+                    );
+                    check_pregenerate_radius(radius)?;
+
+                    commands.spawn((
+                        Name::new("pregenerate chunks"),
+                        PregenerateChunks { center, radius },
+                    ));
+
+                    Ok::<(), Error>(())
+                },
+                self,
+            )
+            .unwrap()
+    }
+}
+
+impl HandleCommand for ExplodeCommand {
+    fn handle_command(self, world: &mut World) -> Result<(), Error> {
+        world
+            .run_system_cached_with(
+                |In(command): In<ExplodeCommand>,
+                 targeted_block: Option<Res<TargetedBlock>>,
+                 mut explosions: MessageWriter<Explosion>| {
+                    let center = command
+                        .center
+                        .map(block_position)
+                        .or_else(|| {
+                            targeted_block
+                                .and_then(|targeted_block| targeted_block.0)
+                                .map(|hit| hit.block)
+                        })
+                        .ok_or_else(|| {
+                            eyre!("No center specified and player isn't looking at a block")
+                        })?;
+
+                    if !(command.radius.is_finite() && command.radius > 0.0) {
+                        bail!(
+                            "Invalid radius: expected a positive number of blocks, but got {}",
+                            command.radius
+                        );
+                    }
+
+                    let explosion = Explosion {
+                        center: block_center(center),
+                        radius: command.radius,
+                        power: command.power.unwrap_or(2.0 * command.radius),
+                    };
+
+                    // an explosion looks at every block in its bounds, like a fill
+                    let (min, max) = explosion.bounds();
+                    check_volume(min, max)?;
+
+                    explosions.write(explosion);
+
+                    Ok::<(), Error>(())
+                },
+                self,
+            )
+            .unwrap()
+    }
+}
+
+impl HandleCommand for FillCommand {
+    fn handle_command(self, world: &mut World) -> Result<(), Error> {
+        world
+            .run_system_cached_with(
+                |In(command): In<FillCommand>,
+                 mut voxels: Voxels<TerrainVoxel, ChunkShape>,
+                 block_types: Res<BlockTypes>,
+                 history: Option<Single<&mut EditHistory, With<Player>>>| {
+                    let block_type = block_types
+                        .lookup(&command.block)
+                        .ok_or_else(|| eyre!("Unknown block type `{}`", command.block))?;
+
+                    let from = block_position(command.from);
+                    let to = block_position(command.to);
+                    let min = from.inf(&to);
+                    let max = from.sup(&to);
+                    let volume = check_volume(min, max)?;
+
+                    let mut batch = ChunkEditBatch::default();
+                    batch.fill(min, max, TerrainVoxel { block_type });
+                    let num_chunks = batch.num_chunks();
+
+                    let num_filled =
+                        apply_edit(&mut voxels, history.map(Single::into_inner), batch);
+                    tracing::info!(num_filled, num_chunks, "region filled");
+
+                    if (num_filled as u64) < volume {
+                        tracing::warn!(
+                            num_skipped = volume - num_filled as u64,
+                            "skipped blocks in chunks that aren't loaded"
+                        );
+                    }
+
+                    Ok::<(), Error>(())
+                },
+                self,
+            )
+            .unwrap()
+    }
+}
+
+impl HandleCommand for RegionCommand {
+    fn handle_command(self, world: &mut World) -> Result<(), Error> {
+        world
+            .run_system_cached_with(
+                |In(command): In<RegionCommand>,
+                 targeted_block: Option<Res<TargetedBlock>>,
+                 mut selection: ResMut<Selection>,
+                 mut clipboard: ResMut<Clipboard>,
+                 history: Option<Single<&mut EditHistory, With<Player>>>,
+                 mut voxels: Voxels<TerrainVoxel, ChunkShape>,
+                 block_types: Res<BlockTypes>| {
+                    let history = history.map(Single::into_inner);
+                    let targeted_block = targeted_block.and_then(|targeted_block| targeted_block.0);
+                    let targeted_position = |position: Option<BlockPosition>| {
+                        position
+                            .map(block_position)
+                            .or_else(|| targeted_block.map(|hit| hit.block))
+                            .ok_or_else(|| {
+                                eyre!("No position specified and player isn't looking at a block")
+                            })
+                    };
+                    let lookup = |name: &str| {
+                        block_types
+                            .lookup(name)
+                            .ok_or_else(|| eyre!("Unknown block type `{name}`"))
+                    };
+
+                    match command {
+                        RegionCommand::Pos1 { position } => {
+                            let position = targeted_position(position)?;
+                            tracing::info!(?position, "selection pos1 set");
+                            selection.pos1 = Some(position);
+                        }
+                        RegionCommand::Pos2 { position } => {
+                            let position = targeted_position(position)?;
+                            tracing::info!(?position, "selection pos2 set");
+                            selection.pos2 = Some(position);
+                        }
+                        RegionCommand::Clear => {
+                            *selection = Selection::default();
+                        }
+                        RegionCommand::Set { block } => {
+                            let block_type = lookup(&block)?;
+                            let (min, max) = selected_region(&selection)?;
+                            check_volume(min, max)?;
+
+                            let mut batch = ChunkEditBatch::default();
+                            batch.fill(min, max, TerrainVoxel { block_type });
+
+                            let num_changed = apply_edit(&mut voxels, history, batch);
+                            tracing::info!(num_changed, "region set");
+                        }
+                        RegionCommand::Replace { from, to } => {
+                            let from = lookup(&from)?;
+                            let to = lookup(&to)?;
+                            let (min, max) = selected_region(&selection)?;
+                            check_volume(min, max)?;
+
+                            let mut batch = ChunkEditBatch::default();
+                            for z in min.z..=max.z {
+                                for y in min.y..=max.y {
+                                    for x in min.x..=max.x {
+                                        let position = Point3::new(x, y, z);
+                                        if voxels
+                                            .get(position)
+                                            .is_some_and(|voxel| voxel.block_type == from)
+                                        {
+                                            batch.set(position, TerrainVoxel { block_type: to });
+                                        }
+                                    }
+                                }
+                            }
+
+                            let num_changed = apply_edit(&mut voxels, history, batch);
+                            tracing::info!(num_changed, "region replaced");
+                        }
+                        RegionCommand::Copy => {
+                            let (min, max) = selected_region(&selection)?;
+                            check_volume(min, max)?;
+
+                            let schematic = Schematic::export(&voxels, &block_types, min, max)?;
+                            tracing::info!(size = ?schematic.size(), "region copied");
+                            clipboard.0 = Some(schematic);
+                        }
+                        RegionCommand::Paste { position, skip_air } => {
+                            let schematic =
+                                clipboard.0.as_ref().ok_or_eyre("Clipboard is empty")?;
+
+                            // paste on top of the targeted block, like placing a block
+                            let position = position
+                                .map(block_position)
+                                .or_else(|| {
+                                    targeted_block.map(|hit| hit.adjacent().unwrap_or(hit.block))
+                                })
+                                .ok_or_else(|| {
+                                    eyre!(
+                                        "No position specified and player isn't looking at a block"
+                                    )
+                                })?;
+
+                            let batch = schematic.edits(&block_types, position, skip_air)?;
+                            let num_changed = apply_edit(&mut voxels, history, batch);
+                            tracing::info!(?position, num_changed, "clipboard pasted");
+                        }
+                    }
+
+                    Ok::<(), Error>(())
+                },
+                self,
+            )
+            .unwrap()
+    }
+}
+
+impl HandleCommand for UndoCommand {
+    fn handle_command(self, world: &mut World) -> Result<(), Error> {
+        world
+            .run_system_cached_with(
+                |In(command): In<UndoCommand>,
+                 mut voxels: Voxels<TerrainVoxel, ChunkShape>,
+                 history: Option<Single<&mut EditHistory, With<Player>>>| {
+                    let mut history = history.ok_or_eyre("No player found")?;
+
+                    let mut num_undone = 0;
+                    let mut num_restored = 0;
+                    while num_undone < command.count
+                        && let Some(num_blocks) = history.undo(&mut voxels)
+                    {
+                        num_undone += 1;
+                        num_restored += num_blocks;
+                    }
+
+                    if num_undone == 0 {
+                        bail!("Nothing to undo");
+                    }
+                    tracing::info!(num_undone, num_restored, "edits undone");
+
+                    Ok::<(), Error>(())
+                },
+                self,
+            )
+            .unwrap()
+    }
+}
+
+impl HandleCommand for RedoCommand {
+    fn handle_command(self, world: &mut World) -> Result<(), Error> {
+        world
+            .run_system_cached_with(
+                |In(command): In<RedoCommand>,
+                 mut voxels: Voxels<TerrainVoxel, ChunkShape>,
+                 history: Option<Single<&mut EditHistory, With<Player>>>| {
+                    let mut history = history.ok_or_eyre("No player found")?;
+
+                    let mut num_redone = 0;
+                    let mut num_changed = 0;
+                    while num_redone < command.count
+                        && let Some(num_blocks) = history.redo(&mut voxels)
+                    {
+                        num_redone += 1;
+                        num_changed += num_blocks;
+                    }
+
+                    if num_redone == 0 {
+                        bail!("Nothing to redo");
+                    }
+                    tracing::info!(num_redone, num_changed, "edits redone");
+
+                    Ok::<(), Error>(())
+                },
+                self,
+            )
+            .unwrap()
+    }
+}
+
+impl HandleCommand for GameModeCommand {
+    fn handle_command(self, world: &mut World) -> Result<(), Error> {
+        world
+            .run_system_cached_with(
+                |In(command): In<GameModeCommand>,
+                 player: Option<Single<&mut GameMode, With<Player>>>| {
+                    let mut game_mode = player.ok_or_eyre("No player found")?;
+
+                    match command.mode {
+                        Some(mode) => {
+                            game_mode.set_if_neq(match mode {
+                                sandvox_rcon::GameMode::Creative => GameMode::Creative,
+                                sandvox_rcon::GameMode::Survival => GameMode::Survival,
+                            });
+                        }
+                        None => tracing::info!(game_mode = ?*game_mode, "current game mode"),
+                    }
+
+                    Ok::<(), Error>(())
+                },
+                self,
+            )
+            .unwrap()
+    }
+}
+
+/// Applies the batch, and records it in the player's edit history if there is
+/// a player. Returns the number of blocks changed.
+fn apply_edit(
+    voxels: &mut Voxels<TerrainVoxel, ChunkShape>,
+    history: Option<Mut<EditHistory>>,
+    batch: ChunkEditBatch<TerrainVoxel, ChunkShape>,
+) -> usize {
+    match history {
+        Some(mut history) => history.apply(voxels, batch),
+        None => voxels.apply(batch).len(),
+    }
+}
+
+fn selected_region(selection: &Selection) -> Result<(Point3<i32>, Point3<i32>), Error> {
+    selection
+        .region()
+        .ok_or_eyre("Select both corners of a region first")
+}
+
+/// Returns the number of blocks between `min` and `max`, or an error if it's
+/// more than [`MAX_FILL_VOLUME`].
+fn check_volume(min: Point3<i32>, max: Point3<i32>) -> Result<u64, Error> {
+    // the extent of the region might not fit into an i32, and its volume not into
+    // an u64.
+    let extent = max.cast::<i64>() - min.cast::<i64>();
+    let volume: u128 = extent.map(|c| c as u128 + 1).product();
+    if volume > u128::from(MAX_FILL_VOLUME) {
+        bail!("Region has {volume} blocks, but at most {MAX_FILL_VOLUME} can be changed at once");
+    }
+    Ok(volume as u64)
+}
+
+/// Returns the number of chunks within `radius`, or an error if it's more than
+/// [`MAX_PREGENERATE_CHUNKS`].
+fn check_pregenerate_radius(radius: Vector3<u32>) -> Result<u64, Error> {
+    let num_chunks: u128 = radius.map(|c| 2 * u128::from(c) + 1).product();
+    if num_chunks > u128::from(MAX_PREGENERATE_CHUNKS) {
+        bail!(
+            "Range has {num_chunks} chunks, but at most {MAX_PREGENERATE_CHUNKS} can be pregenerated at once"
+        );
+    }
+    Ok(num_chunks as u64)
+}
+
+fn block_position(position: BlockPosition) -> Point3<i32> {
+    Point3::new(position.x, position.y, position.z)
+}
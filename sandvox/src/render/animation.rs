@@ -0,0 +1,483 @@
+//! Keyframe animations imported from glTF models.
+//!
+//! The [`ModelImporter`](crate::render::model::ModelImporter) attaches a
+//! [`ModelAnimations`] component with the model's clips to the scene entity.
+//! Clips are played by an [`AnimationPlayer`] on the same entity, which samples
+//! them each tick and writes the result to the targets' [`LocalTransform`]s.
+//!
+//! Clips are looked up by name with [`ModelAnimations::get`], and
+//! [`AnimationPlayer::transition_to`] cross-fades from the current clip to
+//! another one. Models spawned by the
+//! [`ModelLoader`](crate::render::model::ModelLoader) loop their first clip.
+
+use std::sync::Arc;
+
+use bevy_ecs::{
+    component::Component,
+    entity::Entity,
+    system::{
+        Populated,
+        Query,
+        Res,
+    },
+};
+use color_eyre::eyre::Error;
+use nalgebra::{
+    Translation3,
+    UnitQuaternion,
+    Vector3,
+};
+
+use crate::{
+    app::Time,
+    ecs::{
+        plugin::{
+            Plugin,
+            WorldBuilder,
+        },
+        schedule,
+        transform::LocalTransform,
+    },
+};
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct AnimationPlugin;
+
+impl Plugin for AnimationPlugin {
+    fn setup(&self, builder: &mut WorldBuilder) -> Result<(), Error> {
+        builder.add_systems(schedule::Update, update_animation_players);
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct AnimationClip {
+    pub name: Option<String>,
+
+    /// Time of the last keyframe in seconds.
+    pub duration: f32,
+
+    pub channels: Vec<AnimationChannel>,
+}
+
+impl AnimationClip {
+    /// Applies the clip at `time` to the targets' transforms.
+    ///
+    /// With a `weight` less than 1 the transforms are only moved that far
+    /// towards the clip's pose.
+    fn apply(&self, time: f32, weight: f32, transforms: &mut Query<&mut LocalTransform>) {
+        for channel in &self.channels {
+            if let Ok(mut transform) = transforms.get_mut(channel.target) {
+                channel.apply_weighted(time, weight, &mut transform);
+            }
+        }
+    }
+}
+
+/// Keyframes for one property of one entity.
+#[derive(Clone, Debug)]
+pub struct AnimationChannel {
+    pub target: Entity,
+    pub interpolation: Interpolation,
+
+    /// Timestamps of the keyframes in seconds. These are sorted.
+    pub times: Vec<f32>,
+
+    pub keyframes: Keyframes,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Interpolation {
+    Step,
+    Linear,
+}
+
+#[derive(Clone, Debug)]
+pub enum Keyframes {
+    Translation(Vec<Vector3<f32>>),
+    Rotation(Vec<UnitQuaternion<f32>>),
+
+    Scale(Vec<Vector3<f32>>),
+}
+
+impl AnimationChannel {
+    /// Returns the two keyframes around `time` and how far `time` is between
+    /// them.
+    fn keyframes_at(&self, time: f32) -> (usize, usize, f32) {
+        let next = self.times.partition_point(|t| *t <= time);
+
+        if next == 0 {
+            (0, 0, 0.0)
+        }
+        else if next == self.times.len() {
+            (next - 1, next - 1, 0.0)
+        }
+        else {
+            let previous = next - 1;
+            let t = match self.interpolation {
+                Interpolation::Step => 0.0,
+                Interpolation::Linear => {
+                    (time - self.times[previous]) / (self.times[next] - self.times[previous])
+                }
+            };
+            (previous, next, t)
+        }
+    }
+
+    /// Applies the value of this channel at `time` to a transform.
+    pub fn apply(&self, time: f32, transform: &mut LocalTransform) {
+        self.apply_weighted(time, 1.0, transform);
+    }
+
+    /// Moves a transform by `weight` towards the value of this channel at
+    /// `time`.
+    pub fn apply_weighted(&self, time: f32, weight: f32, transform: &mut LocalTransform) {
+        let (previous, next, t) = self.keyframes_at(time);
+
+        match &self.keyframes {
+            Keyframes::Translation(translations) => {
+                let translation = translations[previous].lerp(&translations[next], t);
+                transform.isometry.translation = Translation3::from(
+                    transform
+                        .isometry
+                        .translation
+                        .vector
+                        .lerp(&translation, weight),
+                );
+            }
+            Keyframes::Rotation(rotations) => {
+                let rotation = slerp(&rotations[previous], &rotations[next], t);
+                transform.isometry.rotation =
+                    slerp(&transform.isometry.rotation, &rotation, weight);
+            }
+            Keyframes::Scale(scales) => {
+                let scale = scales[previous].lerp(&scales[next], t);
+                transform.scale = transform.scale.lerp(&scale, weight);
+            }
+        }
+    }
+}
+
+fn slerp(a: &UnitQuaternion<f32>, b: &UnitQuaternion<f32>, t: f32) -> UnitQuaternion<f32> {
+    // fails if the rotations are opposite, in which case there's no shortest path
+    a.try_slerp(b, t, 1e-6).unwrap_or(*a)
+}
+
+/// Animation clips of a model.
+#[derive(Clone, Debug, Default, Component)]
+pub struct ModelAnimations {
+    pub clips: Vec<Arc<AnimationClip>>,
+}
+
+impl ModelAnimations {
+    /// Returns the clip with that name.
+    pub fn get(&self, name: &str) -> Option<&Arc<AnimationClip>> {
+        self.clips
+            .iter()
+            .find(|clip| clip.name.as_deref() == Some(name))
+    }
+}
+
+/// Plays animation clips.
+///
+/// A clip can be faded into from the previously playing clip with
+/// [`transition_to`](Self::transition_to). Both clips keep playing while the
+/// transition lasts and their poses are blended.
+#[derive(Clone, Debug, Component)]
+pub struct AnimationPlayer {
+    current: Option<PlayingClip>,
+    transition: Option<Transition>,
+    paused: bool,
+
+    /// Playback speed. 1 is normal speed, negative values play clips
+    /// backwards.
+    pub speed: f32,
+}
+
+impl Default for AnimationPlayer {
+    fn default() -> Self {
+        Self {
+            current: None,
+            transition: None,
+            paused: false,
+            speed: 1.0,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+struct PlayingClip {
+    clip: Arc<AnimationClip>,
+    time: f32,
+    looping: bool,
+}
+
+impl PlayingClip {
+    fn new(clip: Arc<AnimationClip>) -> Self {
+        Self {
+            clip,
+            time: 0.0,
+            looping: false,
+        }
+    }
+
+    fn advance(&mut self, dt: f32) {
+        self.time += dt;
+
+        if self.looping && self.clip.duration > 0.0 {
+            self.time = self.time.rem_euclid(self.clip.duration);
+        }
+        else {
+            self.time = self.time.clamp(0.0, self.clip.duration);
+        }
+    }
+
+    fn is_finished(&self, speed: f32) -> bool {
+        !self.looping
+            && if speed < 0.0 {
+                self.time <= 0.0
+            }
+            else {
+                self.time >= self.clip.duration
+            }
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Transition {
+    from: PlayingClip,
+    elapsed: f32,
+    duration: f32,
+}
+
+impl AnimationPlayer {
+    /// Starts playing a clip from the beginning.
+    pub fn play(&mut self, clip: Arc<AnimationClip>) -> &mut Self {
+        self.current = Some(PlayingClip::new(clip));
+        self.transition = None;
+        self.paused = false;
+        self
+    }
+
+    /// Starts playing a clip and blends from the current clip to it over
+    /// `duration` seconds.
+    pub fn transition_to(&mut self, clip: Arc<AnimationClip>, duration: f32) -> &mut Self {
+        if duration <= 0.0 {
+            return self.play(clip);
+        }
+
+        self.transition = self.current.take().map(|from| {
+            Transition {
+                from,
+                elapsed: 0.0,
+                duration,
+            }
+        });
+        self.current = Some(PlayingClip::new(clip));
+        self.paused = false;
+        self
+    }
+
+    /// Stops playing and forgets the current clip.
+    pub fn stop(&mut self) -> &mut Self {
+        self.current = None;
+        self.transition = None;
+        self
+    }
+
+    pub fn pause(&mut self) -> &mut Self {
+        self.paused = true;
+        self
+    }
+
+    pub fn resume(&mut self) -> &mut Self {
+        self.paused = false;
+        self
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Sets whether the current clip starts over when it's finished.
+    pub fn set_looping(&mut self, looping: bool) -> &mut Self {
+        if let Some(current) = &mut self.current {
+            current.looping = looping;
+        }
+        self
+    }
+
+    pub fn set_speed(&mut self, speed: f32) -> &mut Self {
+        self.speed = speed;
+        self
+    }
+
+    /// Jumps to a point in the current clip.
+    pub fn seek(&mut self, time: f32) -> &mut Self {
+        if let Some(current) = &mut self.current {
+            current.time = time;
+            current.advance(0.0);
+        }
+        self
+    }
+
+    pub fn clip(&self) -> Option<&Arc<AnimationClip>> {
+        self.current.as_ref().map(|current| &current.clip)
+    }
+
+    /// Playback time of the current clip in seconds.
+    pub fn time(&self) -> Option<f32> {
+        self.current.as_ref().map(|current| current.time)
+    }
+
+    /// Whether the current clip has played to its end. Looping clips never
+    /// finish.
+    pub fn is_finished(&self) -> bool {
+        self.current
+            .as_ref()
+            .is_none_or(|current| current.is_finished(self.speed))
+    }
+
+    fn advance(&mut self, dt: f32) {
+        if self.paused {
+            return;
+        }
+
+        let dt = dt * self.speed;
+
+        if let Some(current) = &mut self.current {
+            current.advance(dt);
+        }
+
+        if let Some(transition) = &mut self.transition {
+            transition.from.advance(dt);
+            transition.elapsed += dt.abs();
+
+            if transition.elapsed >= transition.duration {
+                self.transition = None;
+            }
+        }
+    }
+
+    fn apply(&self, transforms: &mut Query<&mut LocalTransform>) {
+        let Some(current) = &self.current
+        else {
+            return;
+        };
+
+        let weight = if let Some(transition) = &self.transition {
+            transition
+                .from
+                .clip
+                .apply(transition.from.time, 1.0, transforms);
+            transition.elapsed / transition.duration
+        }
+        else {
+            1.0
+        };
+
+        current.clip.apply(current.time, weight, transforms);
+    }
+}
+
+fn update_animation_players(
+    time: Res<Time>,
+    players: Populated<&mut AnimationPlayer>,
+    mut transforms: Query<&mut LocalTransform>,
+) {
+    for mut player in players {
+        if player.paused {
+            continue;
+        }
+
+        player.advance(time.delta_seconds());
+        player.apply(&mut transforms);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use bevy_ecs::entity::Entity;
+    use nalgebra::Vector3;
+
+    use crate::{
+        ecs::transform::LocalTransform,
+        render::animation::{
+            AnimationChannel,
+            AnimationClip,
+            AnimationPlayer,
+            Interpolation,
+            Keyframes,
+        },
+    };
+
+    fn translation_channel(interpolation: Interpolation) -> AnimationChannel {
+        AnimationChannel {
+            target: Entity::PLACEHOLDER,
+            interpolation,
+            times: vec![0.0, 1.0, 3.0],
+            keyframes: Keyframes::Translation(vec![
+                Vector3::zeros(),
+                Vector3::new(2.0, 0.0, 0.0),
+                Vector3::new(2.0, 4.0, 0.0),
+            ]),
+        }
+    }
+
+    #[test]
+    fn it_interpolates_translations() {
+        let channel = translation_channel(Interpolation::Linear);
+        let mut transform = LocalTransform::identity();
+
+        channel.apply(0.5, &mut transform);
+        assert_eq!(
+            transform.isometry.translation.vector,
+            Vector3::new(1.0, 0.0, 0.0)
+        );
+
+        channel.apply(2.0, &mut transform);
+        assert_eq!(
+            transform.isometry.translation.vector,
+            Vector3::new(2.0, 2.0, 0.0)
+        );
+
+        channel.apply(5.0, &mut transform);
+        assert_eq!(
+            transform.isometry.translation.vector,
+            Vector3::new(2.0, 4.0, 0.0)
+        );
+
+        let channel = translation_channel(Interpolation::Step);
+        channel.apply(2.0, &mut transform);
+        assert_eq!(
+            transform.isometry.translation.vector,
+            Vector3::new(2.0, 0.0, 0.0)
+        );
+    }
+
+    #[test]
+    fn it_loops_and_finishes_clips() {
+        let clip = Arc::new(AnimationClip {
+            name: None,
+            duration: 3.0,
+            channels: vec![translation_channel(Interpolation::Linear)],
+        });
+
+        let mut player = AnimationPlayer::default();
+        player.play(clip.clone()).set_looping(true).set_speed(2.0);
+        player.advance(2.0);
+        assert_eq!(player.time(), Some(1.0));
+        assert!(!player.is_finished());
+
+        player.pause();
+        player.advance(1.0);
+        assert_eq!(player.time(), Some(1.0));
+
+        player.play(clip).resume();
+        player.advance(2.0);
+        assert_eq!(player.time(), Some(3.0));
+        assert!(player.is_finished());
+    }
+}
@@ -1,10 +1,11 @@
 use std::{
     collections::HashMap,
     fmt::Debug,
-    fs::File,
-    io::BufWriter,
     num::NonZero,
-    path::Path,
+    path::{
+        Path,
+        PathBuf,
+    },
     sync::Arc,
 };
 
@@ -23,7 +24,10 @@ use parking_lot::Mutex;
 use serde::Serialize;
 
 use crate::{
-    render::staging::Staging,
+    render::{
+        debug_dump::write_dump,
+        staging::Staging,
+    },
     util::sparse_vec::SparseVec,
     wgpu::{
         blit::{
@@ -50,6 +54,9 @@ pub enum Error {
         limit: u32,
     },
 
+    #[error("Too many textures for the bindless texture array ({limit})")]
+    TooManyTextures { limit: u32 },
+
     #[error(transparent)]
     Image(#[from] image::ImageError),
 
@@ -64,6 +71,11 @@ pub struct AtlasConfig {
     pub size_limit: Option<u32>,
     pub format: wgpu::TextureFormat,
     pub usage: wgpu::TextureUsages,
+
+    /// Enables the bindless path with this many textures in the texture array.
+    ///
+    /// See [`bindless_texture_limit`].
+    pub bindless_texture_limit: Option<u32>,
 }
 
 impl Default for AtlasConfig {
@@ -74,6 +86,7 @@ impl Default for AtlasConfig {
             size_limit: None,
             format: wgpu::TextureFormat::Rgba8UnormSrgb,
             usage: wgpu::TextureUsages::TEXTURE_BINDING,
+            bindless_texture_limit: None,
         }
     }
 }
@@ -96,6 +109,12 @@ pub struct Atlas {
     version: AtlasVersion,
     atlas_texture: wgpu::TextureView,
     data_buffer: TypedArrayBuffer<DataBufferItem>,
+    bindless: Option<BindlessTextures>,
+    dump_directory: Option<PathBuf>,
+
+    /// Copies of the inserted images, to upload them again if the GPU device
+    /// is lost.
+    sources: HashMap<AllocationId, Source>,
 }
 
 impl Atlas {
@@ -107,6 +126,7 @@ impl Atlas {
             size_limit,
             format,
             mut usage,
+            bindless_texture_limit,
         } = config;
 
         let size_limit = size_limit.unwrap_or_else(|| {
@@ -137,6 +157,10 @@ impl Atlas {
             initial_data_buffer_size.get(),
         );
 
+        let bindless = bindless_texture_limit.map(|texture_limit| {
+            BindlessTextures::new(device, texture_limit, initial_data_buffer_size.get())
+        });
+
         Self {
             allocator,
             size: initial_size,
@@ -153,7 +177,72 @@ impl Atlas {
             version: Default::default(),
             atlas_texture,
             data_buffer,
+            bindless,
+            dump_directory: None,
+            sources: HashMap::new(),
+        }
+    }
+
+    /// Creates the GPU resources of the atlas again on a new device, e.g.
+    /// after the old one was lost.
+    ///
+    /// The allocations are kept, so all handles stay valid. The inserted images
+    /// are uploaded again with the next [`flush`][Self::flush].
+    #[profiling::function]
+    pub fn recreate(
+        &mut self,
+        device: &wgpu::Device,
+        bindless_texture_limit: Option<u32>,
+        staging: &mut Staging,
+    ) {
+        self.handle_drops();
+
+        if self.bindless.is_some() && bindless_texture_limit.is_none() {
+            // textures that don't fit into the atlas texture can't be shown anymore
+            tracing::warn!("The new device doesn't support the bindless path.");
+        }
+
+        self.blitter = Blitter::new(device);
+        self.samplers.clear();
+        self.atlas_texture = allocate_atlas_texture(device, self.size, self.format, self.usage);
+        self.data_buffer = TypedArrayBuffer::with_capacity(
+            device.clone(),
+            "atlas data",
+            wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            self.views.len().max(1),
+        );
+        self.bindless = bindless_texture_limit.map(|texture_limit| {
+            BindlessTextures::new(device, texture_limit, self.views.len().max(1))
+        });
+        self.changes.clear();
+
+        for (allocation_id, source) in &self.sources {
+            let texture_view = match upload_image(&source.image, device, staging) {
+                Ok(texture_view) => texture_view,
+                Err(error) => {
+                    tracing::error!(?allocation_id, "could not upload atlas image: {error}");
+                    continue;
+                }
+            };
+
+            self.allocations[*allocation_id].pending_change = Some(self.changes.len());
+
+            if let Some(bindless) = &mut self.bindless {
+                bindless
+                    .textures
+                    .insert(*allocation_id, texture_view.clone());
+            }
+
+            self.changes.push(Change::Insert {
+                allocation_id: *allocation_id,
+                source_size: Vector2::new(source.image.width(), source.image.height()),
+                source_texture: texture_view,
+                source_offset: Vector2::zeros(),
+                padding_mode: source.padding_mode,
+            });
         }
+
+        self.version.0 += 1;
     }
 
     fn handle_drops(&mut self) {
@@ -177,8 +266,15 @@ impl Atlas {
             if allocation.ref_count == 0 {
                 tracing::debug!(allocation_id = ?view.allocation_id, "removing allocation");
 
-                self.allocator.deallocate(allocation.alloc_id);
+                if let Some(alloc_id) = allocation.alloc_id {
+                    self.allocator.deallocate(alloc_id);
+                }
                 self.allocations.remove(view.allocation_id);
+                self.sources.remove(&view.allocation_id);
+
+                if let Some(bindless) = &mut self.bindless {
+                    bindless.textures.remove(&view.allocation_id);
+                }
             }
         }
     }
@@ -192,39 +288,25 @@ impl Atlas {
     ) -> Result<(AllocationId, ViewId), Error> {
         self.handle_drops();
 
-        // check if image won't ever fit
-        if size.x > self.size_limit || size.y > self.size_limit {
-            return Err(Error::TooLarge {
-                image_size: size,
-                limit: self.size_limit,
+        if let Some(bindless) = &self.bindless
+            && self.allocations.len() >= bindless.texture_limit as usize
+        {
+            return Err(Error::TooManyTextures {
+                limit: bindless.texture_limit,
             });
         }
 
         let allocation_size = size + padding.additional_size();
 
-        // allocate space for image
-        let (allocation_offset, alloc_id) = loop {
-            if let Some(allocation) = self
-                .allocator
-                .allocate(vector2_to_guillotiere(allocation_size))
-            {
-                let allocation_offset = guillotiere_to_vector2(allocation.rectangle.min);
-                let max = guillotiere_to_vector2(allocation.rectangle.max);
-
-                assert_eq!(max - allocation_offset, allocation_size);
-
-                break (allocation_offset, allocation.id);
-            }
-            else if self.size < self.size_limit {
-                // todo: make sure the new size fits the requested size
-                let new_size = (2 * self.size).min(self.size_limit);
-                self.allocator
-                    .grow(vector2_to_guillotiere(Vector2::repeat(new_size)));
-                self.size = new_size;
-            }
-            else {
-                return Err(Error::AtlasFull { limit: self.size });
+        let (allocation_offset, alloc_id) = match self.allocate_packed(allocation_size) {
+            Ok((allocation_offset, alloc_id)) => (allocation_offset, Some(alloc_id)),
+            Err(error) if self.bindless.is_some() => {
+                // with the bindless path the texture is sampled directly, so it doesn't
+                // have to fit into the atlas texture.
+                tracing::debug!(%error, "not packing image into atlas texture");
+                (Vector2::zeros(), None)
             }
+            Err(error) => return Err(error),
         };
 
         let inner_offset = padding.inner_offset();
@@ -248,6 +330,50 @@ impl Atlas {
         Ok((allocation_id, view_id))
     }
 
+    /// Allocates space in the atlas texture.
+    fn allocate_packed(
+        &mut self,
+        allocation_size: Vector2<u32>,
+    ) -> Result<(Vector2<u32>, guillotiere::AllocId), Error> {
+        // check if image won't ever fit
+        if allocation_size.x > self.size_limit || allocation_size.y > self.size_limit {
+            return Err(Error::TooLarge {
+                image_size: allocation_size,
+                limit: self.size_limit,
+            });
+        }
+
+        // allocate space for image
+        loop {
+            if let Some(allocation) = self
+                .allocator
+                .allocate(vector2_to_guillotiere(allocation_size))
+            {
+                let allocation_offset = guillotiere_to_vector2(allocation.rectangle.min);
+                let max = guillotiere_to_vector2(allocation.rectangle.max);
+
+                assert_eq!(max - allocation_offset, allocation_size);
+
+                return Ok((allocation_offset, allocation.id));
+            }
+            else if self.size < self.size_limit {
+                // todo: make sure the new size fits the requested size
+                let new_size = (2 * self.size).min(self.size_limit);
+                self.allocator
+                    .grow(vector2_to_guillotiere(Vector2::repeat(new_size)));
+                self.size = new_size;
+            }
+            else {
+                return Err(Error::AtlasFull { limit: self.size });
+            }
+        }
+    }
+
+    /// Inserts a texture that is already on the GPU.
+    ///
+    /// Unlike with [`insert_image`][Self::insert_image], the atlas has no copy
+    /// of the texture, so it's blank after the atlas was
+    /// [recreated][Self::recreate].
     #[profiling::function]
     pub fn insert_texture(
         &mut self,
@@ -265,6 +391,12 @@ impl Atlas {
             Some(change_index),
         )?;
 
+        if let Some(bindless) = &mut self.bindless {
+            bindless
+                .textures
+                .insert(allocation_id, texture_view.clone());
+        }
+
         self.changes.push(Change::Insert {
             allocation_id,
             source_texture: texture_view,
@@ -290,21 +422,19 @@ impl Atlas {
         device: &wgpu::Device,
         staging: &mut Staging,
     ) -> Result<AtlasHandle, Error> {
-        // upload image to gpu
-        let texture = image.create_texture(
-            "atlas insert",
-            wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
-            MipLevels::One,
-            device,
-            staging,
-        )?;
+        let texture_view = upload_image(image, device, staging)?;
+        let handle = self.insert_texture(texture_view, padding_mode)?;
 
-        let texture_view = texture.create_view(&wgpu::TextureViewDescriptor {
-            label: Some("atlas insert"),
-            ..Default::default()
-        });
+        let allocation_id = self.views[handle.view_id].allocation_id;
+        self.sources.insert(
+            allocation_id,
+            Source {
+                image: image.clone(),
+                padding_mode,
+            },
+        );
 
-        self.insert_texture(texture_view, padding_mode)
+        Ok(handle)
     }
 
     pub fn view(
@@ -407,6 +537,12 @@ impl Atlas {
                     {
                         let allocation = &self.allocations[view.allocation_id];
 
+                        if allocation.alloc_id.is_none() {
+                            // only available through the bindless path
+                            *buffer_entry = Zeroable::zeroed();
+                            continue;
+                        }
+
                         *buffer_entry = DataBufferItem {
                             uv_offset: atlas_size_inv
                                 * (allocation.outer_offset + view.offset).cast::<f32>(),
@@ -419,86 +555,51 @@ impl Atlas {
             );
         }
 
-        // dump atlas texture for debugging
-        {
-            let json_path = Path::new("tmp/atlas.json");
-            let image_path = Path::new("tmp/atlas.png");
-            let size = self.size;
-            tracing::debug!(json = ?json_path, image = ?image_path, ?size, "dumping texture atlas");
-
-            #[derive(Debug, Serialize)]
-            struct JsonDump<'a> {
-                allocations: &'a SparseVec<AllocationId, Allocation>,
-                views: &'a SparseVec<ViewId, View>,
-            }
-            match File::create(&json_path) {
-                Ok(file) => {
-                    let writer = BufWriter::new(file);
-                    if let Err(error) = serde_json::to_writer_pretty(
-                        writer,
-                        &JsonDump {
-                            allocations: &self.allocations,
-                            views: &self.views,
-                        },
-                    ) {
-                        tracing::error!(%error);
-                    }
-                }
-                Err(error) => {
-                    tracing::error!(%error);
-                }
-            }
-
-            let staging_buffer = device.create_buffer(&wgpu::BufferDescriptor {
-                label: Some("atlas read-back staging"),
-                size: (size * size * 4) as wgpu::BufferAddress,
-                usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
-                mapped_at_creation: false,
-            });
+        // update bindless data buffer
+        if let Some(bindless) = &mut self.bindless {
+            let size = self
+                .views
+                .iter()
+                .map(|(view_id, _view)| view_id.0 + 1)
+                .max()
+                .unwrap_or_default();
+
+            bindless.data_buffer.write_all_with(
+                size,
+                |buffer: &mut [BindlessDataBufferItem]| {
+                    buffer.fill(Zeroable::zeroed());
+
+                    for (view_id, view) in self.views.iter() {
+                        let allocation = &self.allocations[view.allocation_id];
 
-            staging.command_encoder_mut().copy_texture_to_buffer(
-                wgpu::TexelCopyTextureInfo {
-                    texture: self.atlas_texture.texture(),
-                    mip_level: 0,
-                    origin: Default::default(),
-                    aspect: Default::default(),
-                },
-                wgpu::TexelCopyBufferInfo {
-                    buffer: &staging_buffer,
-                    layout: wgpu::TexelCopyBufferLayout {
-                        offset: 0,
-                        bytes_per_row: Some(size * 4),
-                        rows_per_image: None,
-                    },
-                },
-                wgpu::Extent3d {
-                    width: size,
-                    height: size,
-                    depth_or_array_layers: 1,
+                        // uv coordinates are relative to the source texture, which doesn't
+                        // have padding.
+                        let source_size_inv = allocation.inner_size.cast::<f32>().map(|x| 1.0 / x);
+                        let padding = allocation.inner_offset - allocation.outer_offset;
+
+                        buffer[view_id.0] = BindlessDataBufferItem {
+                            uv_offset: (view.offset - padding)
+                                .cast::<f32>()
+                                .component_mul(&source_size_inv),
+                            uv_size: view.size.cast::<f32>().component_mul(&source_size_inv),
+                            texture_index: view.allocation_id.0.try_into().unwrap(),
+                            _padding: 0,
+                        };
+                    }
                 },
+                |_new_buffer| {},
+                &mut staging,
             );
+        }
 
-            staging
-                .command_encoder_mut()
-                .map_buffer_on_submit(&staging_buffer.clone(), wgpu::MapMode::Read, .., move |result| {
-                    result.unwrap();
-                    let mapped_range = staging_buffer.get_mapped_range(..);
-
-                    let image = image::ImageBuffer::<image::Rgba<u8>, _>::from_raw(
-                        size, size,
-                        &*mapped_range,
-                    )
-                    .unwrap();
-
-                    if let Err(error) = image.save(image_path) {
-                        tracing::error!(path = %image_path.display(), "couldn't save texture atlas: {error}");
-                    }
-                });
+        if let Some(directory) = &self.dump_directory {
+            self.dump(directory, staging);
         }
 
         self.changes.clear();
 
-        if new_texture || new_data_buffer {
+        // with the bindless path, any insert changes the texture array.
+        if new_texture || new_data_buffer || self.bindless.is_some() {
             self.version.0 += 1;
             true
         }
@@ -507,6 +608,59 @@ impl Atlas {
         }
     }
 
+    /// Dumps the atlas to this directory whenever it's flushed with changes.
+    pub fn set_dump_directory(&mut self, directory: Option<PathBuf>) {
+        self.dump_directory = directory;
+    }
+
+    /// Writes the allocations and views to `atlas.json`, and the atlas texture
+    /// to `atlas.png` once it's read back.
+    pub fn dump(&self, directory: &Path, staging: &mut Staging) {
+        let json_path = directory.join("atlas.json");
+        let image_path = directory.join("atlas.png");
+        let size = self.size;
+        tracing::debug!(json = ?json_path, image = ?image_path, ?size, "dumping texture atlas");
+
+        #[derive(Debug, Serialize)]
+        struct JsonDump<'a> {
+            allocations: &'a SparseVec<AllocationId, Allocation>,
+            views: &'a SparseVec<ViewId, View>,
+        }
+        let json = serde_json::to_vec_pretty(&JsonDump {
+            allocations: &self.allocations,
+            views: &self.views,
+        });
+
+        let readback = staging.read_texture(
+            wgpu::TexelCopyTextureInfo {
+                texture: self.atlas_texture.texture(),
+                mip_level: 0,
+                origin: Default::default(),
+                aspect: Default::default(),
+            },
+            wgpu::Extent3d {
+                width: size,
+                height: size,
+                depth_or_array_layers: 1,
+            },
+        );
+
+        match readback {
+            Ok(readback) => {
+                readback.then(move |result| {
+                    write_dump(&json_path, |path| Ok(std::fs::write(path, json?)?));
+
+                    match result.map(|readback| readback.into_rgba_image()) {
+                        Ok(Some(image)) => write_dump(&image_path, |path| Ok(image.save(path)?)),
+                        Ok(None) => tracing::error!("texture atlas has an unexpected format"),
+                        Err(error) => tracing::error!(%error, "couldn't read back texture atlas"),
+                    }
+                });
+            }
+            Err(error) => tracing::warn!(%error, "couldn't read back texture atlas"),
+        }
+    }
+
     #[inline]
     pub fn version(&self) -> AtlasVersion {
         self.version
@@ -518,10 +672,30 @@ impl Atlas {
             texture: &self.atlas_texture,
             data_buffer: self.data_buffer.buffer(),
             version: self.version,
+            bindless: self.bindless.as_ref(),
         }
     }
 }
 
+fn upload_image(
+    image: &RgbaImage,
+    device: &wgpu::Device,
+    staging: &mut Staging,
+) -> Result<wgpu::TextureView, Error> {
+    let texture = image.create_texture(
+        "atlas insert",
+        wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+        MipLevels::One,
+        device,
+        staging,
+    )?;
+
+    Ok(texture.create_view(&wgpu::TextureViewDescriptor {
+        label: Some("atlas insert"),
+        ..Default::default()
+    }))
+}
+
 impl Drop for Atlas {
     fn drop(&mut self) {
         let mut dropped = self.dropped.lock();
@@ -606,6 +780,132 @@ pub struct AtlasResources<'a> {
     pub texture: &'a wgpu::TextureView,
     pub data_buffer: &'a wgpu::Buffer,
     pub version: AtlasVersion,
+    pub bindless: Option<&'a BindlessTextures>,
+}
+
+/// Features required for the bindless path.
+pub const BINDLESS_FEATURES: wgpu::Features = wgpu::Features::TEXTURE_BINDING_ARRAY
+    .union(wgpu::Features::SAMPLED_TEXTURE_AND_STORAGE_BUFFER_ARRAY_NON_UNIFORM_INDEXING);
+
+/// Upper bound for the number of textures in the bindless texture array.
+///
+/// The bind group always contains this many textures (unused slots are filled
+/// with a placeholder), so we don't want to use whatever huge number the
+/// hardware supports.
+const MAX_BINDLESS_TEXTURES: u32 = 4096;
+
+/// Below this the bindless path isn't worth it.
+const MIN_BINDLESS_TEXTURES: u32 = 256;
+
+/// Number of textures the bindless texture array can have on this device, or
+/// `None` if the device doesn't support the bindless path.
+///
+/// The device must have been created with [`BINDLESS_FEATURES`] and raised
+/// binding array limits (see [`request_bindless_limits`]).
+pub fn bindless_texture_limit(device: &wgpu::Device) -> Option<u32> {
+    if !device.features().contains(BINDLESS_FEATURES) {
+        return None;
+    }
+
+    let limits = device.limits();
+    let texture_limit = MAX_BINDLESS_TEXTURES
+        .min(limits.max_binding_array_elements_per_shader_stage)
+        // the atlas texture and the font textures are bound too
+        .min(
+            limits
+                .max_sampled_textures_per_shader_stage
+                .saturating_sub(16),
+        );
+
+    (texture_limit >= MIN_BINDLESS_TEXTURES).then_some(texture_limit)
+}
+
+/// Raises the limits required for the bindless path, as far as they're
+/// supported.
+pub fn request_bindless_limits(enabled: &mut wgpu::Limits, supported: &wgpu::Limits) {
+    enabled.max_binding_array_elements_per_shader_stage =
+        enabled.max_binding_array_elements_per_shader_stage.max(
+            supported
+                .max_binding_array_elements_per_shader_stage
+                .min(MAX_BINDLESS_TEXTURES),
+        );
+    enabled.max_sampled_textures_per_shader_stage =
+        enabled.max_sampled_textures_per_shader_stage.max(
+            supported
+                .max_sampled_textures_per_shader_stage
+                .min(MAX_BINDLESS_TEXTURES + 16),
+        );
+}
+
+/// Keeps the inserted textures around, so that the mesh shader can sample them
+/// directly from a texture binding array, instead of from the atlas texture.
+///
+/// Textures are indexed by their allocation, the data buffer is indexed by the
+/// texture id (like the atlas data buffer). Textures that don't fit into the
+/// atlas texture are only available this way.
+#[derive(Debug)]
+pub struct BindlessTextures {
+    texture_limit: u32,
+    textures: HashMap<AllocationId, wgpu::TextureView>,
+    placeholder: wgpu::TextureView,
+    data_buffer: TypedArrayBuffer<BindlessDataBufferItem>,
+}
+
+impl BindlessTextures {
+    fn new(device: &wgpu::Device, texture_limit: u32, initial_data_buffer_size: usize) -> Self {
+        let placeholder = device
+            .create_texture(&wgpu::TextureDescriptor {
+                label: Some("bindless placeholder"),
+                size: wgpu::Extent3d {
+                    width: 1,
+                    height: 1,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: wgpu::TextureFormat::Rgba8UnormSrgb,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING,
+                view_formats: &[],
+            })
+            .create_view(&Default::default());
+
+        let data_buffer = TypedArrayBuffer::with_capacity(
+            device.clone(),
+            "atlas bindless data",
+            wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            initial_data_buffer_size,
+        );
+
+        Self {
+            texture_limit,
+            textures: HashMap::new(),
+            placeholder,
+            data_buffer,
+        }
+    }
+
+    #[inline]
+    pub fn texture_limit(&self) -> u32 {
+        self.texture_limit
+    }
+
+    /// All textures of the texture array. This always has
+    /// [`texture_limit`][Self::texture_limit] entries.
+    pub fn texture_views(&self) -> Vec<&wgpu::TextureView> {
+        (0..self.texture_limit as usize)
+            .map(|index| {
+                self.textures
+                    .get(&AllocationId(index))
+                    .unwrap_or(&self.placeholder)
+            })
+            .collect()
+    }
+
+    #[inline]
+    pub fn data_buffer(&self) -> &wgpu::Buffer {
+        self.data_buffer.buffer()
+    }
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
@@ -613,8 +913,9 @@ pub struct AtlasVersion(usize);
 
 #[derive(Clone, Copy, Debug, Serialize)]
 struct Allocation {
+    /// `None` if the allocation isn't packed into the atlas texture.
     #[serde(skip)]
-    alloc_id: guillotiere::AllocId,
+    alloc_id: Option<guillotiere::AllocId>,
 
     #[serde(skip)]
     pending_change: Option<usize>,
@@ -653,6 +954,22 @@ struct DataBufferItem {
     uv_size: Vector2<f32>,
 }
 
+#[derive(Clone, Copy, Debug, Pod, Zeroable)]
+#[repr(C)]
+struct BindlessDataBufferItem {
+    uv_offset: Vector2<f32>,
+    uv_size: Vector2<f32>,
+    texture_index: u32,
+    _padding: u32,
+}
+
+/// An inserted image, kept to upload it again.
+#[derive(Debug)]
+struct Source {
+    image: RgbaImage,
+    padding_mode: Option<PaddingMode>,
+}
+
 #[derive(Debug)]
 enum Change {
     Insert {
@@ -799,6 +1116,10 @@ struct AtlasBlitterTransaction<'a> {
 
 impl<'a> AtlasBlitterTransaction<'a> {
     fn keep(&mut self, old_atlas_texture: &wgpu::TextureView, allocation: &Allocation) {
+        if allocation.alloc_id.is_none() {
+            return;
+        }
+
         let sampler = get_sampler(self.samplers, self.device, SamplerMode::RESIZE);
 
         self.inner.blit(
@@ -819,6 +1140,10 @@ impl<'a> AtlasBlitterTransaction<'a> {
         padding_mode: Option<PaddingMode>,
         allocation: Allocation,
     ) {
+        if allocation.alloc_id.is_none() {
+            return;
+        }
+
         let mut sampler_mode = SamplerMode::RESIZE;
         let mut source_offset = source_offset.cast::<i32>();
         let mut source_size = source_size;
@@ -0,0 +1,545 @@
+//! Textured quads that face the camera, e.g. for particles, name tags or
+//! markers, or that lie on a surface, e.g. for cracks on a block that is being
+//! broken.
+//!
+//! Billboards that face the camera are drawn in the opaque phase. Their
+//! textures come from the [`DefaultAtlas`][crate::render::DefaultAtlas] and
+//! fragments that are mostly transparent are discarded, so that billboards
+//! write depth and don't need to be sorted. For the same reason they're also
+//! drawn in the depth prepass.
+//!
+//! [Surface-aligned][BillboardOrientation::SurfaceAligned] billboards are alpha
+//! blended instead and drawn in the outline phase, so they're depth-tested, but
+//! don't write depth. They should be slightly in front of the surface, so that
+//! they don't z-fight with it.
+
+use bevy_ecs::{
+    component::Component,
+    name::NameOrEntity,
+    query::{
+        Has,
+        ROQueryItem,
+        With,
+    },
+    resource::Resource,
+    schedule::IntoScheduleConfigs,
+    system::{
+        Commands,
+        Local,
+        Populated,
+        Query,
+        Res,
+        ResMut,
+        SystemParamItem,
+    },
+};
+use bytemuck::{
+    Pod,
+    Zeroable,
+};
+use color_eyre::eyre::Error;
+use nalgebra::{
+    Point3,
+    Vector2,
+    Vector4,
+};
+
+use crate::{
+    ecs::{
+        plugin::{
+            Dependencies,
+            Plugin,
+            WorldBuilder,
+        },
+        schedule,
+        transform::GlobalTransform,
+    },
+    render::{
+        RenderSystems,
+        atlas::AtlasHandle,
+        command::{
+            AddRenderFunction,
+            RenderFunction,
+        },
+        pass::{
+            context::RenderPass,
+            main_pass::{
+                DepthPrepass,
+                MainPass,
+                MainPassLayout,
+                MainPassPlugin,
+                MainPassSystems,
+            },
+            phase,
+        },
+        remove_on_gpu_setup,
+        render_target::RenderTarget,
+        staging::Staging,
+        surface::Surface,
+    },
+    wgpu::{
+        WgpuContext,
+        buffer::TypedArrayBuffer,
+    },
+};
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct BillboardPlugin;
+
+impl Plugin for BillboardPlugin {
+    fn dependencies(&self, dependencies: &mut Dependencies) -> Result<(), Error> {
+        dependencies.require_plugin::<MainPassPlugin>();
+        Ok(())
+    }
+
+    fn setup(&self, builder: &mut WorldBuilder) -> Result<(), Error> {
+        builder
+            .add_systems(
+                schedule::GpuSetup,
+                (
+                    (
+                        create_pipeline_layout.after(MainPassSystems::Prepare),
+                        create_billboard_buffer,
+                    )
+                        .in_set(RenderSystems::Setup),
+                    remove_on_gpu_setup::<BillboardPipeline>,
+                ),
+            )
+            .add_systems(
+                schedule::Render,
+                (create_pipeline, update_billboard_buffer).in_set(RenderSystems::BeginFrame),
+            )
+            .add_render_function::<phase::Opaque, _>(RenderBillboards::Opaque)
+            .add_render_function::<phase::DepthPrepass, _>(RenderBillboards::DepthPrepass)
+            .add_render_function::<phase::Outline, _>(RenderBillboards::SurfaceAligned);
+
+        Ok(())
+    }
+}
+
+/// A quad with a texture from the [`DefaultAtlas`][crate::render::DefaultAtlas]
+/// that is centered on the entity.
+#[derive(Clone, Debug, Component)]
+pub struct Billboard {
+    pub texture: AtlasHandle,
+
+    /// Width and height in world units. This is multiplied by the x and y
+    /// scale of the entity's transform.
+    pub size: Vector2<f32>,
+
+    pub orientation: BillboardOrientation,
+}
+
+impl Billboard {
+    pub fn new(texture: AtlasHandle, size: Vector2<f32>) -> Self {
+        Self {
+            texture,
+            size,
+            orientation: Default::default(),
+        }
+    }
+
+    pub fn upright(mut self) -> Self {
+        self.orientation = BillboardOrientation::Upright;
+        self
+    }
+
+    pub fn surface_aligned(mut self) -> Self {
+        self.orientation = BillboardOrientation::SurfaceAligned;
+        self
+    }
+}
+
+/// How a [`Billboard`] is turned.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum BillboardOrientation {
+    /// Always parallel to the screen.
+    #[default]
+    FaceCamera,
+
+    /// Only turns around the world's up axis to face the camera, e.g. for
+    /// beams.
+    Upright,
+
+    /// Lies in the entity's xy plane and faces its z axis, e.g. for decals on
+    /// a block. It's visible from both sides and alpha blended.
+    SurfaceAligned,
+}
+
+/// Set in [`BillboardData::flags`] for [upright][BillboardOrientation::Upright]
+/// billboards.
+const BILLBOARD_FLAG_UPRIGHT: u32 = 1;
+
+/// Set in [`BillboardData::flags`] for
+/// [surface-aligned][BillboardOrientation::SurfaceAligned] billboards.
+const BILLBOARD_FLAG_SURFACE_ALIGNED: u32 = 2;
+
+#[derive(Debug, Resource)]
+struct BillboardLayout {
+    layout: wgpu::PipelineLayout,
+    shader: wgpu::ShaderModule,
+    bind_group_layout: wgpu::BindGroupLayout,
+}
+
+#[derive(Debug, Component)]
+struct BillboardPipeline {
+    pipeline: wgpu::RenderPipeline,
+    depth_prepass: Option<wgpu::RenderPipeline>,
+    surface_aligned: wgpu::RenderPipeline,
+}
+
+/// Billboards that face the camera come first in the buffer, followed by the
+/// surface-aligned ones.
+#[derive(Debug, Resource)]
+struct BillboardBuffer {
+    buffer: TypedArrayBuffer<BillboardData>,
+    bind_group: Option<wgpu::BindGroup>,
+    num_facing_camera: u32,
+    num_surface_aligned: u32,
+}
+
+#[derive(Clone, Copy, Debug, Pod, Zeroable)]
+#[repr(C)]
+struct BillboardData {
+    position: Point3<f32>,
+    texture_id: u32,
+    size: Vector2<f32>,
+    flags: u32,
+    _padding: u32,
+    /// The entity's rotation as a quaternion. Only used for surface-aligned
+    /// billboards.
+    rotation: Vector4<f32>,
+}
+
+fn create_pipeline_layout(
+    wgpu: Res<WgpuContext>,
+    main_pass_layout: Res<MainPassLayout>,
+    mut commands: Commands,
+) {
+    let bind_group_layout =
+        wgpu.device
+            .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("billboard"),
+                entries: &[wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: true },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                }],
+            });
+
+    let layout = wgpu
+        .device
+        .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("billboard"),
+            bind_group_layouts: &[&main_pass_layout.bind_group_layout, &bind_group_layout],
+            immediate_size: 0,
+        });
+
+    let shader = wgpu
+        .device
+        .create_shader_module(wgpu::include_wgsl!("billboard.wgsl"));
+
+    commands.insert_resource(BillboardLayout {
+        layout,
+        shader,
+        bind_group_layout,
+    });
+}
+
+fn create_billboard_buffer(wgpu: Res<WgpuContext>, mut commands: Commands) {
+    let buffer = TypedArrayBuffer::new(
+        wgpu.device.clone(),
+        "billboard buffer",
+        wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+    );
+
+    commands.insert_resource(BillboardBuffer {
+        buffer,
+        bind_group: None,
+        num_facing_camera: 0,
+        num_surface_aligned: 0,
+    });
+}
+
+fn create_pipeline(
+    wgpu: Res<WgpuContext>,
+    pipeline_layout: Res<BillboardLayout>,
+    surfaces: Populated<(NameOrEntity, &Surface)>,
+    cameras: Populated<(
+        NameOrEntity,
+        &RenderTarget,
+        Has<DepthPrepass>,
+        Option<&BillboardPipeline>,
+    )>,
+    main_passes: Query<(), With<MainPass>>,
+    mut commands: Commands,
+) {
+    for (camera_entity, render_target, enable_depth_prepass, pipeline) in cameras {
+        if !main_passes.contains(camera_entity.entity) {
+            continue;
+        }
+
+        // (re)create the pipeline if the camera's depth prepass was toggled
+        if pipeline.is_some_and(|pipeline| pipeline.depth_prepass.is_some() == enable_depth_prepass)
+        {
+            continue;
+        }
+
+        if let Ok((surface_entity, surface)) = surfaces.get(render_target.0) {
+            tracing::debug!(surface = %surface_entity, camera = %camera_entity, "creating billboard render pipeline for surface");
+
+            let primitive = wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: None,
+                unclipped_depth: false,
+                polygon_mode: wgpu::PolygonMode::Fill,
+                conservative: false,
+            };
+
+            let pipeline = wgpu
+                .device
+                .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                    label: Some("billboard"),
+                    layout: Some(&pipeline_layout.layout),
+                    vertex: wgpu::VertexState {
+                        module: &pipeline_layout.shader,
+                        entry_point: Some("billboard_vertex"),
+                        compilation_options: Default::default(),
+                        buffers: &[],
+                    },
+                    primitive,
+                    depth_stencil: Some(wgpu::DepthStencilState {
+                        format: surface.depth_format(),
+                        depth_write_enabled: !enable_depth_prepass,
+                        depth_compare: if enable_depth_prepass {
+                            wgpu::CompareFunction::Equal
+                        }
+                        else {
+                            surface.depth_compare(wgpu::CompareFunction::Less)
+                        },
+                        stencil: Default::default(),
+                        bias: Default::default(),
+                    }),
+                    multisample: Default::default(),
+                    fragment: Some(wgpu::FragmentState {
+                        module: &pipeline_layout.shader,
+                        entry_point: Some("billboard_fragment"),
+                        compilation_options: Default::default(),
+                        targets: &[Some(wgpu::ColorTargetState {
+                            format: surface.surface_format(),
+                            blend: None,
+                            write_mask: wgpu::ColorWrites::ALL,
+                        })],
+                    }),
+                    multiview_mask: None,
+                    cache: None,
+                });
+
+            // the depth prepass needs a fragment shader too, to discard transparent
+            // fragments
+            let depth_prepass = enable_depth_prepass.then(|| {
+                wgpu.device
+                    .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                        label: Some("billboard/depth-prepass"),
+                        layout: Some(&pipeline_layout.layout),
+                        vertex: wgpu::VertexState {
+                            module: &pipeline_layout.shader,
+                            entry_point: Some("billboard_vertex"),
+                            compilation_options: Default::default(),
+                            buffers: &[],
+                        },
+                        primitive,
+                        depth_stencil: Some(wgpu::DepthStencilState {
+                            format: surface.depth_format(),
+                            depth_write_enabled: true,
+                            depth_compare: surface.depth_compare(wgpu::CompareFunction::Less),
+                            stencil: Default::default(),
+                            bias: Default::default(),
+                        }),
+                        multisample: Default::default(),
+                        fragment: Some(wgpu::FragmentState {
+                            module: &pipeline_layout.shader,
+                            entry_point: Some("billboard_depth_prepass_fragment"),
+                            compilation_options: Default::default(),
+                            targets: &[],
+                        }),
+                        multiview_mask: None,
+                        cache: None,
+                    })
+            });
+
+            let surface_aligned =
+                wgpu.device
+                    .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                        label: Some("billboard/surface-aligned"),
+                        layout: Some(&pipeline_layout.layout),
+                        vertex: wgpu::VertexState {
+                            module: &pipeline_layout.shader,
+                            entry_point: Some("billboard_vertex"),
+                            compilation_options: Default::default(),
+                            buffers: &[],
+                        },
+                        primitive,
+                        depth_stencil: Some(wgpu::DepthStencilState {
+                            format: surface.depth_format(),
+                            depth_write_enabled: false,
+                            depth_compare: surface.depth_compare(wgpu::CompareFunction::LessEqual),
+                            stencil: Default::default(),
+                            bias: Default::default(),
+                        }),
+                        multisample: Default::default(),
+                        fragment: Some(wgpu::FragmentState {
+                            module: &pipeline_layout.shader,
+                            entry_point: Some("billboard_blended_fragment"),
+                            compilation_options: Default::default(),
+                            targets: &[Some(wgpu::ColorTargetState {
+                                format: surface.surface_format(),
+                                blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                                write_mask: wgpu::ColorWrites::ALL,
+                            })],
+                        }),
+                        multiview_mask: None,
+                        cache: None,
+                    });
+
+            commands
+                .entity(camera_entity.entity)
+                .insert(BillboardPipeline {
+                    pipeline,
+                    depth_prepass,
+                    surface_aligned,
+                });
+        }
+    }
+}
+
+/// Writes all billboards into the billboard buffer.
+///
+/// This runs every frame, because billboards are cheap and otherwise we'd
+/// have to track removed billboards too.
+#[profiling::function]
+fn update_billboard_buffer(
+    wgpu: Res<WgpuContext>,
+    layout: Res<BillboardLayout>,
+    mut billboard_buffer: ResMut<BillboardBuffer>,
+    billboards: Query<(&Billboard, &GlobalTransform)>,
+    mut billboard_data: Local<Vec<BillboardData>>,
+    mut staging: ResMut<Staging>,
+) {
+    assert!(billboard_data.is_empty());
+
+    let billboard_data_for = |(billboard, transform): (&Billboard, &GlobalTransform)| {
+        BillboardData {
+            position: transform.isometry.translation.vector.into(),
+            texture_id: billboard.texture.id(),
+            size: billboard.size.component_mul(&transform.scale.xy()),
+            flags: match billboard.orientation {
+                BillboardOrientation::FaceCamera => 0,
+                BillboardOrientation::Upright => BILLBOARD_FLAG_UPRIGHT,
+                BillboardOrientation::SurfaceAligned => BILLBOARD_FLAG_SURFACE_ALIGNED,
+            },
+            rotation: transform.isometry.rotation.coords,
+            ..Zeroable::zeroed()
+        }
+    };
+
+    // the surface-aligned billboards are drawn in another phase, so they're
+    // written after the others
+    billboard_data.extend(
+        billboards
+            .iter()
+            .filter(|(billboard, _)| billboard.orientation != BillboardOrientation::SurfaceAligned)
+            .map(billboard_data_for),
+    );
+    let num_facing_camera = billboard_data.len();
+    billboard_data.extend(
+        billboards
+            .iter()
+            .filter(|(billboard, _)| billboard.orientation == BillboardOrientation::SurfaceAligned)
+            .map(billboard_data_for),
+    );
+
+    let billboard_buffer = &mut *billboard_buffer;
+    billboard_buffer.num_facing_camera = num_facing_camera.try_into().unwrap();
+    billboard_buffer.num_surface_aligned = (billboard_data.len() - num_facing_camera)
+        .try_into()
+        .unwrap();
+
+    if billboard_data.is_empty() {
+        // nothing to draw, and bindings can't be empty anyway
+        return;
+    }
+
+    let reallocated = billboard_buffer
+        .buffer
+        .write_all(&billboard_data, |_| {}, &mut *staging);
+
+    if reallocated || billboard_buffer.bind_group.is_none() {
+        billboard_buffer.bind_group =
+            Some(wgpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("billboard"),
+                layout: &layout.bind_group_layout,
+                entries: &[wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: billboard_buffer.buffer.buffer().as_entire_binding(),
+                }],
+            }));
+    }
+
+    billboard_data.clear();
+}
+
+#[derive(Debug)]
+enum RenderBillboards {
+    Opaque,
+    DepthPrepass,
+    SurfaceAligned,
+}
+
+impl RenderFunction for RenderBillboards {
+    type Param = Res<'static, BillboardBuffer>;
+    type ViewQuery = &'static BillboardPipeline;
+    type ItemQuery = ();
+
+    #[profiling::function]
+    fn render(
+        &self,
+        param: SystemParamItem<Self::Param>,
+        render_pass: &mut RenderPass<'_>,
+        view: ROQueryItem<Self::ViewQuery>,
+        items: Query<Self::ItemQuery>,
+    ) {
+        let _ = items;
+        let billboard_buffer = param;
+
+        let num_facing_camera = billboard_buffer.num_facing_camera;
+        let (pipeline, billboards) = match self {
+            Self::Opaque => (Some(&view.pipeline), 0..num_facing_camera),
+            Self::DepthPrepass => (view.depth_prepass.as_ref(), 0..num_facing_camera),
+            Self::SurfaceAligned => {
+                (
+                    Some(&view.surface_aligned),
+                    num_facing_camera..(num_facing_camera + billboard_buffer.num_surface_aligned),
+                )
+            }
+        };
+
+        if !billboards.is_empty()
+            && let Some(pipeline) = pipeline
+            && let Some(bind_group) = &billboard_buffer.bind_group
+        {
+            let span = render_pass.enter_span("billboard");
+            render_pass.set_pipeline(pipeline);
+            render_pass.set_bind_group(1, Some(bind_group), &[]);
+            render_pass.draw((billboards.start * 6)..(billboards.end * 6), 0..1);
+            render_pass.exit_span(span);
+        }
+    }
+}
@@ -0,0 +1,183 @@
+
+struct MainPassUniform {
+    camera: Camera,
+    time: f32,
+    exposure: f32,
+    // padding: 8 bytes
+    light: Light,
+    fog: Fog,
+    overlay: Overlay,
+}
+
+struct Camera {
+    projection: mat4x4f,
+    projection_inverse: mat4x4f,
+    view: mat4x4f,
+    view_inverse: mat4x4f,
+    position: vec4f,
+}
+
+struct Light {
+    sun_direction: vec4f,
+    sun_color: vec4f,
+    moon_direction: vec4f,
+    moon_color: vec4f,
+    ambient_color: vec4f,
+}
+
+struct Fog {
+    // alpha is 0 if there is no fog
+    color: vec4f,
+    start: f32,
+    end: f32,
+    // padding: 8 bytes
+}
+
+struct Overlay {
+    // alpha is 0 if there is no overlay
+    color: vec4f,
+    distortion: f32,
+    // padding: 12 bytes
+}
+
+@group(0)
+@binding(0)
+var<uniform> main_pass_uniform: MainPassUniform;
+
+@group(0)
+@binding(1)
+var default_sampler: sampler;
+
+@group(0)
+@binding(2)
+var atlas_texture: texture_2d<f32>;
+
+struct AtlasEntry {
+    uv_offset: vec2f,
+    uv_size: vec2f,
+}
+
+@group(0)
+@binding(3)
+var<storage, read> atlas_data: array<AtlasEntry>;
+
+struct BillboardData {
+    position: vec3f,
+    texture_id: u32,
+    size: vec2f,
+    flags: u32,
+    // padding: 4 bytes
+    rotation: vec4f,
+}
+
+@group(1)
+@binding(0)
+var<storage, read> billboard_data: array<BillboardData>;
+
+
+const QUAD_VERTICES = array(
+    vec2f(0, 0), vec2f(0, 1), vec2f(1, 0),
+    vec2f(1, 1), vec2f(1, 0), vec2f(0, 1),
+);
+
+// fragments with less alpha are discarded, so that billboards can write depth
+const ALPHA_CUTOFF: f32 = 0.5;
+
+// the billboard only turns around the y axis
+const BILLBOARD_FLAG_UPRIGHT: u32 = 1;
+
+// the billboard is turned by its rotation instead of facing the camera
+const BILLBOARD_FLAG_SURFACE_ALIGNED: u32 = 2;
+
+@vertex
+fn billboard_vertex(@builtin(vertex_index) vertex_index: u32) -> BillboardOutput {
+    let billboard = billboard_data[vertex_index / 6];
+
+    let uv = QUAD_VERTICES[vertex_index % 6];
+
+    // the texture's v axis points down, but the camera's y axis points up
+    let vertex_offset = billboard.size * (vec2f(1, -1) * uv + vec2f(-0.5, 0.5));
+
+    var position: vec4f;
+    if (billboard.flags & BILLBOARD_FLAG_SURFACE_ALIGNED) != 0 {
+        // span the quad in the entity's xy plane
+        let offset = quaternion_rotate(billboard.rotation, vec3f(vertex_offset, 0));
+        position = vec4f(billboard.position + offset, 1);
+        position = main_pass_uniform.camera.projection * main_pass_uniform.camera.view * position;
+    }
+    else if (billboard.flags & BILLBOARD_FLAG_UPRIGHT) != 0 {
+        // span the quad in world space, with its normal pointing horizontally at the camera
+        let to_camera = main_pass_uniform.camera.position.xyz - billboard.position;
+        var right = cross(vec3f(0, 1, 0), to_camera);
+        if dot(right, right) < 1e-6 {
+            // the camera is right above or below the billboard
+            right = vec3f(1, 0, 0);
+        }
+        right = normalize(right);
+
+        position = vec4f(billboard.position + vertex_offset.x * right + vec3f(0, vertex_offset.y, 0), 1);
+        position = main_pass_uniform.camera.projection * main_pass_uniform.camera.view * position;
+    }
+    else {
+        // transform the center into the camera frame and span the quad there, so that it
+        // always faces the camera
+        position = main_pass_uniform.camera.view * vec4f(billboard.position, 1);
+        position += vec4f(vertex_offset, 0, 0);
+        position = main_pass_uniform.camera.projection * position;
+    }
+
+    return BillboardOutput(position, uv, billboard.texture_id);
+}
+
+struct BillboardOutput {
+    @builtin(position)
+    position: vec4f,
+
+    @location(0)
+    uv: vec2f,
+
+    @location(1)
+    @interpolate(flat, either)
+    texture_id: u32,
+}
+
+@fragment
+fn billboard_fragment(input: BillboardOutput) -> @location(0) vec4f {
+    let uv = atlas_map_uv(input.texture_id, input.uv);
+    let color = textureSample(atlas_texture, default_sampler, uv);
+
+    if color.a < ALPHA_CUTOFF {
+        discard;
+    }
+
+    return vec4f(color.rgb * main_pass_uniform.exposure, 1);
+}
+
+@fragment
+fn billboard_blended_fragment(input: BillboardOutput) -> @location(0) vec4f {
+    let uv = atlas_map_uv(input.texture_id, input.uv);
+    let color = textureSample(atlas_texture, default_sampler, uv);
+
+    return vec4f(color.rgb * main_pass_uniform.exposure, color.a);
+}
+
+@fragment
+fn billboard_depth_prepass_fragment(input: BillboardOutput) {
+    let uv = atlas_map_uv(input.texture_id, input.uv);
+    let color = textureSample(atlas_texture, default_sampler, uv);
+
+    if color.a < ALPHA_CUTOFF {
+        discard;
+    }
+}
+
+
+fn atlas_map_uv(texture_id: u32, uv: vec2f) -> vec2f {
+    let entry = atlas_data[texture_id];
+    return entry.uv_offset + (uv % vec2f(1)) * entry.uv_size;
+}
+
+fn quaternion_rotate(q: vec4f, v: vec3f) -> vec3f {
+    let t = 2 * cross(q.xyz, v);
+    return v + q.w * t + cross(q.xyz, t);
+}
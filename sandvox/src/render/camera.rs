@@ -1,4 +1,5 @@
 use bevy_ecs::{
+    change_detection::DetectChanges,
     component::Component,
     entity::Entity,
     query::{
@@ -11,7 +12,9 @@ use bevy_ecs::{
     system::{
         Commands,
         Populated,
+        Query,
     },
+    world::Ref,
 };
 use bytemuck::{
     Pod,
@@ -19,8 +22,10 @@ use bytemuck::{
 };
 use color_eyre::eyre::Error;
 use nalgebra::{
+    Isometry3,
     Matrix4,
     Point2,
+    Point3,
     Vector2,
     Vector4,
 };
@@ -42,10 +47,14 @@ use crate::{
         render_target::{
             RenderSources,
             RenderTarget,
+            Viewport,
+            pixel_viewport,
         },
+        surface::Surface,
     },
 };
 
+#[derive(Clone, Copy, Debug, Default)]
 pub struct CameraPlugin;
 
 impl Plugin for CameraPlugin {
@@ -69,7 +78,14 @@ pub struct Camera {
     pub aspect_ratio: f32,
     pub fovy: f32,
     pub z_near: f32,
+
+    /// Distance to the far plane. This can be infinite.
     pub z_far: f32,
+
+    /// Map `z_near` to depth 1 and `z_far` to depth 0.
+    ///
+    /// This is set from the surface the camera renders to.
+    pub reverse_z: bool,
 }
 
 impl Camera {
@@ -81,7 +97,13 @@ impl Camera {
     }
 
     pub fn projection(&self) -> CameraProjection {
-        CameraProjection::new(self.aspect_ratio, self.fovy, self.z_near, self.z_far)
+        CameraProjection::new(
+            self.aspect_ratio,
+            self.fovy,
+            self.z_near,
+            self.z_far,
+            self.reverse_z,
+        )
     }
 
     /// Returns angles (horizontal, vertical) that a point makes with the focal
@@ -118,11 +140,19 @@ impl Camera {
 /// a/s   0    0     0
 ///   0 1/s    0     0
 ///   0   0    0     1
-///   0   0 1/c1 -1/c2
+///   0   0 1/c1 c2/c1
 /// ```
 ///
 /// where `s = 1 / tan(fovy /2)` and a is the aspect ratio.
 ///
+/// With reverse-Z `z_near` and `z_far` are swapped in the formulas for `c1` and
+/// `c2`, such that `z_near -> 1` and `z_far -> 0`. Together with a floating
+/// point depth buffer this distributes the precision much more evenly over the
+/// depth range.
+///
+/// For an infinite `z_far` we take the limits: `c2 = -1` and `c1 = -z_near`,
+/// or with reverse-Z `c2 = 0` and `c1 = z_near`.
+///
 /// [1]: https://learnwebgl.brown37.net/08_projections/projections_perspective.html
 #[derive(Clone, Copy, Debug, Component)]
 pub struct CameraProjection {
@@ -133,10 +163,27 @@ pub struct CameraProjection {
 }
 
 impl CameraProjection {
-    pub fn new(aspect_ratio: f32, fovy: f32, z_near: f32, z_far: f32) -> Self {
+    pub fn new(aspect_ratio: f32, fovy: f32, z_near: f32, z_far: f32, reverse_z: bool) -> Self {
         let s = 1.0 / (0.5 * fovy).tan();
-        let c2 = z_far / (z_near - z_far);
-        let c1 = z_near * c2;
+
+        let (c1, c2) = if z_far.is_infinite() {
+            if reverse_z {
+                (z_near, 0.0)
+            }
+            else {
+                (-z_near, -1.0)
+            }
+        }
+        else {
+            let (z_near, z_far) = if reverse_z {
+                (z_far, z_near)
+            }
+            else {
+                (z_near, z_far)
+            };
+            let c2 = z_far / (z_near - z_far);
+            (z_near * c2, c2)
+        };
 
         Self {
             a: aspect_ratio,
@@ -163,7 +210,7 @@ impl CameraProjection {
             vector.x * self.a * s_inv,
             vector.y * s_inv,
             vector.w,
-            vector.z / self.c1 - vector.w / self.c2,
+            (vector.z + vector.w * self.c2) / self.c1,
         )
     }
 
@@ -183,19 +230,32 @@ impl CameraProjection {
         matrix_inv.m22 = 1.0 / self.s;
         matrix_inv.m34 = 1.0;
         matrix_inv.m43 = 1.0 / self.c1;
-        matrix_inv.m44 = -1.0 / self.c2;
+        matrix_inv.m44 = self.c2 / self.c1;
         matrix_inv
     }
 }
 
 fn update_cameras(
-    windows: Populated<(&WindowSize, &RenderSources), Changed<WindowSize>>,
-    mut cameras: Populated<&mut Camera>,
+    windows: Populated<(Ref<WindowSize>, Option<&Surface>, &RenderSources)>,
+    mut cameras: Query<(&mut Camera, Option<Ref<Viewport>>)>,
 ) {
-    for (window_size, render_sources) in windows {
+    for (window_size, surface, render_sources) in windows {
+        let reverse_z = surface.is_some_and(|surface| surface.reverse_z());
+
         for entity in render_sources.iter() {
-            if let Ok(mut projection) = cameras.get_mut(entity) {
-                projection.set_viewport(window_size.size);
+            if let Ok((mut camera, viewport)) = cameras.get_mut(entity) {
+                if camera.reverse_z != reverse_z {
+                    camera.reverse_z = reverse_z;
+                }
+
+                if window_size.is_changed()
+                    || viewport
+                        .as_ref()
+                        .is_some_and(|viewport| viewport.is_changed())
+                {
+                    let viewport = pixel_viewport(viewport.as_deref(), window_size.size);
+                    camera.set_viewport(viewport.size);
+                }
             }
         }
     }
@@ -223,27 +283,40 @@ fn update_camera_matrices(
         (
             &CameraProjection,
             &GlobalTransform,
+            Option<&CameraOffset>,
             // todo: this should also work for other passes that require this camera matrix
             &mut MainPassUniform,
         ),
         Or<(
             Changed<CameraProjection>,
             Changed<GlobalTransform>,
+            Changed<CameraOffset>,
             Changed<RenderTarget>,
         )>,
     >,
 ) {
-    for (projection, transform, mut main_pass_uniform) in cameras {
+    for (projection, transform, offset, mut main_pass_uniform) in cameras {
+        let isometry = offset.map_or(transform.isometry, |offset| transform.isometry * offset.0);
+
         main_pass_uniform.data.camera = CameraData {
             projection: projection.to_matrix(),
             projection_inverse: projection.to_inverse(),
-            view: transform.isometry.inverse().to_homogeneous(),
-            view_inverse: transform.isometry.to_homogeneous(),
-            position: transform.position().to_homogeneous(),
+            view: isometry.inverse().to_homogeneous(),
+            view_inverse: isometry.to_homogeneous(),
+            position: Point3::from(isometry.translation.vector).to_homogeneous(),
         };
     }
 }
 
+/// Moves the view of a camera relative to its transform, e.g. for view
+/// bobbing or camera shake.
+///
+/// Unlike changing the transform, this only affects what is rendered: the
+/// position used for gameplay, chunk loading and culling stays the same, so
+/// offsets should be small.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Component)]
+pub struct CameraOffset(pub Isometry3<f32>);
+
 #[derive(Clone, Copy, Debug, Component)]
 pub struct FrustrumCulled {
     pub aabb: Aabb,
@@ -258,3 +331,52 @@ pub struct CameraData {
     pub view_inverse: Matrix4<f32>,
     pub position: Vector4<f32>,
 }
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::Vector4;
+
+    use crate::render::camera::CameraProjection;
+
+    fn depth(projection: &CameraProjection, z: f32) -> f32 {
+        let projected = projection.project(Vector4::new(0.0, 0.0, z, 1.0));
+        projected.z / projected.w
+    }
+
+    #[test]
+    fn it_maps_near_and_far_planes() {
+        let fovy = 60f32.to_radians();
+
+        let projection = CameraProjection::new(1.0, fovy, 0.1, 1000.0, false);
+        assert!(depth(&projection, 0.1).abs() < 1e-6);
+        assert!((depth(&projection, 1000.0) - 1.0).abs() < 1e-6);
+
+        let projection = CameraProjection::new(1.0, fovy, 0.1, 1000.0, true);
+        assert!((depth(&projection, 0.1) - 1.0).abs() < 1e-6);
+        assert!(depth(&projection, 1000.0).abs() < 1e-6);
+        assert!(depth(&projection, 10.0) > depth(&projection, 100.0));
+
+        // the inverse undoes the projection
+        let point = Vector4::new(1.0, 2.0, 50.0, 1.0);
+        let unprojected = projection.unproject(projection.project(point));
+        assert!((unprojected / unprojected.w - point).norm() < 1e-3);
+    }
+
+    #[test]
+    fn it_supports_an_infinite_far_plane() {
+        let fovy = 60f32.to_radians();
+
+        for reverse_z in [false, true] {
+            let projection = CameraProjection::new(1.0, fovy, 0.1, f32::INFINITY, reverse_z);
+            let (near, far) = if reverse_z { (1.0, 0.0) } else { (0.0, 1.0) };
+
+            assert!((depth(&projection, 0.1) - near).abs() < 1e-6);
+            assert!((depth(&projection, 1e6) - far).abs() < 1e-6);
+            assert!(projection.to_inverse().iter().all(|x| x.is_finite()));
+
+            let point = Vector4::new(1.0, 2.0, 50.0, 1.0);
+            let unprojected = projection.unproject(projection.project(point));
+            assert!((unprojected / unprojected.w - point).norm() < 1e-3);
+        }
+    }
+}
@@ -0,0 +1,100 @@
+//! Color spaces and color grading.
+//!
+//! Colors that users or assets specify (config files, UI styles, clear colors)
+//! are [`Srgba`], i.e. gamma encoded like in any image editor. Everything that
+//! goes to the GPU (uniforms, vertex data, clear values) is [`LinSrgba`]:
+//! shaders light and blend in linear space, and the sRGB surface encodes on
+//! write. Convert at that boundary with [`ToLinear`], instead of passing the
+//! raw components of an [`Srgba`].
+
+use palette::{
+    LinSrgba,
+    Srgba,
+};
+use serde::{
+    Deserialize,
+    Serialize,
+};
+
+/// Colors that can be converted to linear sRGB for the GPU.
+pub trait ToLinear {
+    fn to_linear(&self) -> LinSrgba<f32>;
+}
+
+impl ToLinear for Srgba<f32> {
+    fn to_linear(&self) -> LinSrgba<f32> {
+        self.into_linear()
+    }
+}
+
+impl ToLinear for Srgba<u8> {
+    fn to_linear(&self) -> LinSrgba<f32> {
+        self.into_linear()
+    }
+}
+
+impl ToLinear for LinSrgba<f32> {
+    fn to_linear(&self) -> LinSrgba<f32> {
+        *self
+    }
+}
+
+/// Converts a color to a [`wgpu::Color`], e.g. for clearing a render target.
+///
+/// wgpu expects linear values, even if the render target has an sRGB format.
+pub fn wgpu_color(color: impl ToLinear) -> wgpu::Color {
+    let color = color.to_linear();
+
+    wgpu::Color {
+        r: color.red.into(),
+        g: color.green.into(),
+        b: color.blue.into(),
+        a: color.alpha.into(),
+    }
+}
+
+/// User adjustments to the final image, applied in the post pass.
+///
+/// These work on gamma encoded values, like the corresponding settings of a
+/// monitor.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ColorGradingConfig {
+    /// Added to all color channels. 0 leaves the image unchanged.
+    #[serde(default)]
+    pub brightness: f32,
+
+    /// Scales the distance of the color channels from middle gray. 1 leaves the
+    /// image unchanged.
+    #[serde(default = "default_contrast")]
+    pub contrast: f32,
+
+    /// Gamma correction. Values above 1 brighten the dark parts of the image.
+    #[serde(default = "default_gamma")]
+    pub gamma: f32,
+}
+
+impl ColorGradingConfig {
+    /// Whether this doesn't change the image at all, in which case the post
+    /// pass is skipped.
+    pub fn is_neutral(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+impl Default for ColorGradingConfig {
+    fn default() -> Self {
+        Self {
+            brightness: 0.0,
+            contrast: default_contrast(),
+            gamma: default_gamma(),
+        }
+    }
+}
+
+fn default_contrast() -> f32 {
+    1.0
+}
+
+fn default_gamma() -> f32 {
+    1.0
+}
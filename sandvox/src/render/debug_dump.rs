@@ -0,0 +1,115 @@
+//! Dumps of GPU resources to files, for debugging.
+//!
+//! Dumps are off by default and enabled per resource in the `debug_dumps`
+//! section of the [`RenderConfig`]. Textures are read back asynchronously and
+//! the files are written once the readback completes, so a dump doesn't stall
+//! the frame.
+
+use std::path::{
+    Path,
+    PathBuf,
+};
+
+use bevy_ecs::system::{
+    Local,
+    Res,
+    ResMut,
+};
+use color_eyre::eyre::Error;
+use serde::{
+    Deserialize,
+    Serialize,
+};
+
+use crate::{
+    paths::Paths,
+    render::{
+        DefaultAtlas,
+        DefaultFont,
+        RenderConfig,
+        staging::Staging,
+    },
+};
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DebugDumpsConfig {
+    /// Relative to the cache directory.
+    #[serde(default = "default_directory")]
+    pub directory: PathBuf,
+
+    /// Dump the texture atlas and its allocations whenever it changes.
+    #[serde(default)]
+    pub atlas: bool,
+
+    /// Dump the glyph atlas of the default font.
+    #[serde(default)]
+    pub font: bool,
+}
+
+impl Default for DebugDumpsConfig {
+    fn default() -> Self {
+        Self {
+            directory: default_directory(),
+            atlas: false,
+            font: false,
+        }
+    }
+}
+
+fn default_directory() -> PathBuf {
+    "debug_dumps".into()
+}
+
+/// Writes a dump file, creating its directory if necessary. Dumps are only
+/// for debugging, so errors are logged instead of returned.
+pub fn write_dump(path: &Path, write: impl FnOnce(&Path) -> Result<(), Error>) {
+    let result = path
+        .parent()
+        .map_or(Ok(()), std::fs::create_dir_all)
+        .map_err(Error::from)
+        .and_then(|()| write(path));
+
+    match result {
+        Ok(()) => tracing::debug!(path = %path.display(), "debug dump written"),
+        Err(error) => tracing::error!(path = %path.display(), "couldn't write debug dump: {error}"),
+    }
+}
+
+/// Applies the [`DebugDumpsConfig`] and dumps the resources that were just
+/// enabled.
+pub(super) fn apply_debug_dumps_config(
+    config: Res<RenderConfig>,
+    paths: Res<Paths>,
+    mut atlas: ResMut<DefaultAtlas>,
+    font: Res<DefaultFont>,
+    mut staging: ResMut<Staging>,
+    mut applied: Local<Option<DebugDumpsConfig>>,
+) {
+    if config.debug_dumps == *applied {
+        return;
+    }
+
+    let previous = std::mem::replace(&mut *applied, config.debug_dumps.clone());
+    let Some(dumps) = &config.debug_dumps
+    else {
+        atlas.set_dump_directory(None);
+        return;
+    };
+
+    let directory = paths.cache_dir().join(&dumps.directory);
+    let was_enabled = |enabled: fn(&DebugDumpsConfig) -> bool| {
+        previous
+            .as_ref()
+            .is_some_and(|previous| previous.directory == dumps.directory && enabled(previous))
+    };
+
+    atlas.set_dump_directory(dumps.atlas.then(|| directory.clone()));
+    if dumps.atlas && !was_enabled(|dumps| dumps.atlas) {
+        atlas.dump(&directory, &mut staging);
+    }
+
+    if dumps.font && !was_enabled(|dumps| dumps.font) {
+        font.dump(&directory, &mut staging);
+    }
+}
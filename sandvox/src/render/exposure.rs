@@ -0,0 +1,557 @@
+//! Exposure and eye adaptation.
+//!
+//! The main pass multiplies everything it renders with the [`Exposure`] of the
+//! camera, so the image is already exposed when it's written to the surface.
+//!
+//! Unless the exposure is set manually in the [`ExposureConfig`], it adapts to
+//! the brightness of the scene: After the main pass a compute shader builds a
+//! histogram of the luminance in the camera's viewport, which is read back a
+//! few frames later. The exposure then moves towards the one that brings the
+//! average luminance to [`TARGET_LUMINANCE`]. Since the histogram is taken
+//! from the exposed image, we undo the exposure that was used for it first.
+//!
+//! The histogram is read from the intermediate texture of the
+//! [post pass](crate::render::pass::post_pass), so surfaces always have one if
+//! the exposure is automatic.
+
+use bevy_ecs::{
+    component::Component,
+    entity::Entity,
+    query::{
+        With,
+        Without,
+    },
+    resource::Resource,
+    schedule::{
+        IntoScheduleConfigs,
+        SystemCondition,
+        common_conditions::resource_exists,
+    },
+    system::{
+        Commands,
+        Populated,
+        Query,
+        Res,
+        ResMut,
+    },
+};
+use bytemuck::{
+    Pod,
+    Zeroable,
+};
+use color_eyre::eyre::Error;
+use nalgebra::Vector2;
+use serde::{
+    Deserialize,
+    Serialize,
+};
+
+use crate::{
+    app::Time,
+    ecs::{
+        plugin::{
+            Dependencies,
+            Plugin,
+            WorldBuilder,
+        },
+        schedule,
+    },
+    render::{
+        RenderConfig,
+        RenderPlugin,
+        RenderSystems,
+        pass::{
+            context::RenderContext,
+            main_pass::{
+                MainPassSystems,
+                MainPassUniform,
+            },
+            ui_pass::UiPassSystems,
+        },
+        readback::{
+            Readback,
+            ReadbackPool,
+        },
+        remove_on_gpu_setup,
+        render_target::{
+            RenderTarget,
+            Viewport,
+            pixel_viewport,
+        },
+        staging::Staging,
+        surface::Surface,
+    },
+    wgpu::{
+        WgpuContext,
+        buffer::WriteStaging,
+        compute::{
+            ComputePipeline,
+            ComputePipelineBuilder,
+        },
+    },
+};
+
+/// Number of bins in the luminance histogram. Must match `NUM_BINS` in
+/// `exposure.wgsl`.
+const NUM_BINS: usize = 64;
+
+/// Range of the luminance histogram (log2 of the exposed luminance).
+const MIN_LOG_LUMINANCE: f32 = -10.0;
+const MAX_LOG_LUMINANCE: f32 = 4.0;
+
+/// The darkest and brightest pixels are ignored, so a few bright spots (e.g.
+/// the sun) don't darken the whole image.
+const IGNORE_DARKEST: f32 = 0.1;
+const IGNORE_BRIGHTEST: f32 = 0.05;
+
+/// The average luminance that the automatic exposure aims for.
+pub const TARGET_LUMINANCE: f32 = 0.3;
+
+const WORKGROUP_SIZE: u32 = 16;
+
+type Histogram = [u32; NUM_BINS];
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ExposureConfig {
+    /// Fixed exposure (in EV). If this isn't set, the exposure adapts to the
+    /// brightness of the scene.
+    #[serde(default)]
+    pub manual: Option<f32>,
+
+    /// Added to the automatic exposure (in EV).
+    #[serde(default)]
+    pub compensation: f32,
+
+    /// Lowest automatic exposure (in EV).
+    #[serde(default = "default_min")]
+    pub min: f32,
+
+    /// Highest automatic exposure (in EV).
+    #[serde(default = "default_max")]
+    pub max: f32,
+
+    /// How fast the exposure adapts. Higher is faster, and at 1 about two
+    /// thirds of the difference are made up in a second.
+    #[serde(default = "default_adaptation_speed")]
+    pub adaptation_speed: f32,
+}
+
+impl Default for ExposureConfig {
+    fn default() -> Self {
+        Self {
+            manual: None,
+            compensation: 0.0,
+            min: default_min(),
+            max: default_max(),
+            adaptation_speed: default_adaptation_speed(),
+        }
+    }
+}
+
+fn default_min() -> f32 {
+    -2.0
+}
+
+fn default_max() -> f32 {
+    2.0
+}
+
+fn default_adaptation_speed() -> f32 {
+    1.5
+}
+
+impl ExposureConfig {
+    pub fn is_automatic(&self) -> bool {
+        self.manual.is_none()
+    }
+
+    /// The exposure (in EV) for a scene with the given average luminance
+    /// (log2).
+    fn target_exposure(&self, log_luminance: f32) -> f32 {
+        (TARGET_LUMINANCE.log2() - log_luminance + self.compensation)
+            .max(self.min)
+            .min(self.max)
+    }
+
+    /// Moves the exposure `ev` towards `target`.
+    fn adapt(&self, ev: f32, target: f32, delta_seconds: f32) -> f32 {
+        ev + (target - ev) * (1.0 - (-self.adaptation_speed * delta_seconds).exp())
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ExposurePlugin;
+
+impl Plugin for ExposurePlugin {
+    fn dependencies(&self, dependencies: &mut Dependencies) -> Result<(), Error> {
+        dependencies.require_plugin::<RenderPlugin>();
+        Ok(())
+    }
+
+    fn setup(&self, builder: &mut WorldBuilder) -> Result<(), Error> {
+        builder
+            .add_systems(
+                schedule::GpuSetup,
+                (
+                    create_histogram_pipeline.in_set(RenderSystems::Setup),
+                    // the exposure is created again together with its buffers
+                    remove_on_gpu_setup::<Exposure>,
+                    remove_on_gpu_setup::<AutoExposure>,
+                ),
+            )
+            .add_systems(
+                schedule::Render,
+                (
+                    (add_exposure, adapt_exposure)
+                        .chain()
+                        .in_set(RenderSystems::BeginFrame),
+                    measure_luminance
+                        .in_set(RenderSystems::Render)
+                        .after(MainPassSystems::Render)
+                        .before(UiPassSystems::Render)
+                        .run_if(resource_exists::<HistogramPipeline>.and(automatic_exposure)),
+                ),
+            );
+
+        Ok(())
+    }
+}
+
+/// Exposure of a camera.
+///
+/// This is added to cameras with a main pass automatically.
+#[derive(Clone, Copy, Debug, PartialEq, Component)]
+pub struct Exposure {
+    /// Exposure value. Colors are multiplied by `2^ev`.
+    pub ev: f32,
+}
+
+impl Exposure {
+    pub fn multiplier(&self) -> f32 {
+        self.ev.exp2()
+    }
+}
+
+/// Measures the luminance for the automatic exposure of a camera.
+#[derive(Debug, Component)]
+struct AutoExposure {
+    uniform_buffer: wgpu::Buffer,
+    histogram_buffer: wgpu::Buffer,
+
+    /// The histogram that is being read back, and the exposure it was taken
+    /// with.
+    readback: Option<(Readback<Histogram>, f32)>,
+
+    /// Exposure that was computed from the last histogram.
+    target: Option<f32>,
+}
+
+#[derive(Clone, Copy, Debug, Pod, Zeroable)]
+#[repr(C)]
+struct HistogramUniform {
+    viewport_offset: Vector2<u32>,
+    viewport_size: Vector2<u32>,
+    min_log_luminance: f32,
+    log_luminance_range: f32,
+    _padding: [u32; 2],
+}
+
+#[derive(Debug, Resource)]
+struct HistogramPipeline {
+    pipeline: ComputePipeline,
+}
+
+fn create_histogram_pipeline(wgpu: Res<WgpuContext>, mut commands: Commands) {
+    let downlevel_flags = wgpu.adapter.get_downlevel_capabilities().flags;
+    if !downlevel_flags.contains(wgpu::DownlevelFlags::COMPUTE_SHADERS) {
+        tracing::info!("Compute shaders not supported. Using a fixed exposure.");
+        return;
+    }
+
+    let shader = wgpu
+        .device
+        .create_shader_module(wgpu::include_wgsl!("exposure.wgsl"));
+
+    let pipeline = ComputePipelineBuilder::new("luminance histogram", &shader, "build_histogram")
+        .uniform::<HistogramUniform>()
+        .binding(wgpu::BindingType::Texture {
+            sample_type: wgpu::TextureSampleType::Float { filterable: false },
+            view_dimension: wgpu::TextureViewDimension::D2,
+            multisampled: false,
+        })
+        .storage::<Histogram>(false)
+        .build(&wgpu.device);
+
+    commands.insert_resource(HistogramPipeline { pipeline });
+}
+
+fn add_exposure(
+    wgpu: Res<WgpuContext>,
+    config: Res<RenderConfig>,
+    pipeline: Option<Res<HistogramPipeline>>,
+    cameras: Populated<Entity, (With<MainPassUniform>, Without<Exposure>)>,
+    mut commands: Commands,
+) {
+    let config = &config.exposure;
+
+    for entity in cameras {
+        let mut entity = commands.entity(entity);
+        entity.insert(Exposure {
+            ev: config.manual.unwrap_or(0.0).max(config.min).min(config.max),
+        });
+
+        if pipeline.is_some() {
+            let uniform_buffer = wgpu.device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("luminance histogram uniform"),
+                size: size_of::<HistogramUniform>() as wgpu::BufferAddress,
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            });
+
+            let histogram_buffer = wgpu.device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("luminance histogram"),
+                size: size_of::<Histogram>() as wgpu::BufferAddress,
+                usage: wgpu::BufferUsages::STORAGE
+                    | wgpu::BufferUsages::COPY_SRC
+                    | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            });
+
+            entity.insert(AutoExposure {
+                uniform_buffer,
+                histogram_buffer,
+                readback: None,
+                target: None,
+            });
+        }
+    }
+}
+
+/// Moves the exposure of every camera towards the target of the last
+/// histogram.
+fn adapt_exposure(
+    config: Res<RenderConfig>,
+    time: Res<Time>,
+    cameras: Populated<(&mut Exposure, Option<&mut AutoExposure>)>,
+) {
+    let config = &config.exposure;
+
+    for (mut exposure, auto_exposure) in cameras {
+        let ev = if let Some(ev) = config.manual {
+            ev
+        }
+        else if let Some(mut auto_exposure) = auto_exposure {
+            let auto_exposure = &mut *auto_exposure;
+
+            if let Some((readback, measured_ev)) = &mut auto_exposure.readback
+                && let Some(result) = readback.try_take()
+            {
+                let measured_ev = *measured_ev;
+                auto_exposure.readback = None;
+
+                match result {
+                    Ok(histogram) => {
+                        if let Some(log_luminance) = average_log_luminance(&histogram) {
+                            auto_exposure.target =
+                                Some(config.target_exposure(log_luminance - measured_ev));
+                        }
+                    }
+                    Err(error) => {
+                        tracing::warn!("could not read back luminance histogram: {error}")
+                    }
+                }
+            }
+
+            auto_exposure.target.map_or(exposure.ev, |target| {
+                config.adapt(exposure.ev, target, time.delta_seconds())
+            })
+        }
+        else {
+            exposure.ev
+        };
+
+        if exposure.ev != ev {
+            exposure.ev = ev;
+        }
+    }
+}
+
+/// Run condition for systems that are only needed with automatic exposure.
+fn automatic_exposure(config: Res<RenderConfig>) -> bool {
+    config.exposure.is_automatic()
+}
+
+/// Builds the luminance histograms of the cameras, after the main pass has
+/// rendered their viewport.
+#[profiling::function]
+fn measure_luminance(
+    wgpu: Res<WgpuContext>,
+    pipeline: Res<HistogramPipeline>,
+    surfaces: Query<&Surface>,
+    cameras: Populated<(
+        &mut AutoExposure,
+        &Exposure,
+        &RenderTarget,
+        Option<&Viewport>,
+    )>,
+    readback_pool: Res<ReadbackPool>,
+    mut render_context: RenderContext,
+    mut staging: ResMut<Staging>,
+) {
+    for (mut auto_exposure, exposure, render_target, viewport) in cameras {
+        // the histogram is only built if it can be read back, and only one readback
+        // per camera is in flight
+        if auto_exposure.readback.is_some() {
+            continue;
+        }
+
+        let Ok(surface) = surfaces.get(render_target.0)
+        else {
+            continue;
+        };
+        let Some(color_texture) = surface.post_pass_texture()
+        else {
+            continue;
+        };
+
+        let viewport = pixel_viewport(viewport, surface.size());
+        if viewport.is_empty() {
+            continue;
+        }
+
+        let uniform = HistogramUniform {
+            viewport_offset: viewport.offset.coords,
+            viewport_size: viewport.size,
+            min_log_luminance: MIN_LOG_LUMINANCE,
+            log_luminance_range: MAX_LOG_LUMINANCE - MIN_LOG_LUMINANCE,
+            _padding: Default::default(),
+        };
+        staging.write_buffer_from_slice(
+            auto_exposure.uniform_buffer.slice(..),
+            bytemuck::bytes_of(&uniform),
+        );
+
+        // the color texture is recreated when the surface is resized, so we just
+        // create the bind group every time.
+        let bind_group = pipeline.pipeline.bind_group(
+            &wgpu.device,
+            [
+                auto_exposure.uniform_buffer.as_entire_binding(),
+                wgpu::BindingResource::TextureView(color_texture),
+                auto_exposure.histogram_buffer.as_entire_binding(),
+            ],
+        );
+
+        let command_encoder = render_context.command_encoder();
+        command_encoder.clear_buffer(&auto_exposure.histogram_buffer, 0, None);
+
+        {
+            let mut compute_pass =
+                command_encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                    label: Some("luminance histogram"),
+                    timestamp_writes: None,
+                });
+            pipeline.pipeline.dispatch(
+                &mut compute_pass,
+                &bind_group,
+                [viewport.size.x, viewport.size.y, 1],
+                [WORKGROUP_SIZE, WORKGROUP_SIZE, 1],
+            );
+        }
+
+        match readback_pool.read_buffer_with(
+            &wgpu.device,
+            command_encoder,
+            auto_exposure.histogram_buffer.slice(..),
+            bytemuck::pod_read_unaligned::<Histogram>,
+        ) {
+            Ok(readback) => auto_exposure.readback = Some((readback, exposure.ev)),
+            Err(error) => tracing::trace!("not reading back luminance histogram: {error}"),
+        }
+    }
+}
+
+/// Average log2 luminance of the pixels in the histogram.
+///
+/// Black pixels and the darkest and brightest few percent are ignored. Returns
+/// `None` if there's nothing left.
+fn average_log_luminance(histogram: &Histogram) -> Option<f32> {
+    let total = histogram[1..]
+        .iter()
+        .map(|count| *count as f32)
+        .sum::<f32>();
+    let low = IGNORE_DARKEST * total;
+    let high = (1.0 - IGNORE_BRIGHTEST) * total;
+
+    let mut cumulative = 0.0;
+    let mut sum = 0.0;
+    let mut weight = 0.0;
+
+    for (bin, count) in histogram.iter().enumerate().skip(1) {
+        let start = cumulative;
+        cumulative += *count as f32;
+
+        // the part of this bin that isn't ignored
+        let count = (cumulative.min(high) - start.max(low)).max(0.0);
+        sum += count * bin_log_luminance(bin);
+        weight += count;
+    }
+
+    (weight > 0.0).then(|| sum / weight)
+}
+
+/// The log2 luminance at the center of a bin.
+fn bin_log_luminance(bin: usize) -> f32 {
+    let t = ((bin as f32 - 0.5) / (NUM_BINS - 2) as f32).min(1.0);
+    MIN_LOG_LUMINANCE + t * (MAX_LOG_LUMINANCE - MIN_LOG_LUMINANCE)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::render::exposure::{
+        ExposureConfig,
+        NUM_BINS,
+        TARGET_LUMINANCE,
+        average_log_luminance,
+        bin_log_luminance,
+    };
+
+    #[test]
+    fn it_averages_the_histogram() {
+        let mut histogram = [0; NUM_BINS];
+        assert_eq!(average_log_luminance(&histogram), None);
+
+        // black pixels are ignored
+        histogram[0] = 1000;
+        histogram[20] = 100;
+        assert_eq!(
+            average_log_luminance(&histogram),
+            Some(bin_log_luminance(20))
+        );
+
+        // and so are a few very bright ones
+        histogram[NUM_BINS - 1] = 5;
+        assert_eq!(
+            average_log_luminance(&histogram),
+            Some(bin_log_luminance(20))
+        );
+
+        histogram[40] = 100;
+        let average = average_log_luminance(&histogram).unwrap();
+        assert!(average > bin_log_luminance(20) && average < bin_log_luminance(40));
+    }
+
+    #[test]
+    fn it_adapts_towards_the_target() {
+        let config = ExposureConfig::default();
+
+        assert_eq!(config.target_exposure(TARGET_LUMINANCE.log2()), 0.0);
+        assert_eq!(config.target_exposure(-20.0), config.max);
+        assert_eq!(config.target_exposure(20.0), config.min);
+
+        let ev = config.adapt(0.0, 1.0, 0.1);
+        assert!(ev > 0.0 && ev < 1.0);
+        assert!(config.adapt(ev, 1.0, 0.1) > ev);
+        assert!((config.adapt(0.0, 1.0, 100.0) - 1.0).abs() < 1e-5);
+    }
+}
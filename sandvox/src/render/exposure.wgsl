@@ -0,0 +1,59 @@
+// must match `NUM_BINS` in exposure.rs
+const NUM_BINS: u32 = 64;
+
+struct HistogramUniform {
+    viewport_offset: vec2u,
+    viewport_size: vec2u,
+    min_log_luminance: f32,
+    log_luminance_range: f32,
+    // padding: 8 bytes
+}
+
+@group(0)
+@binding(0)
+var<uniform> histogram_uniform: HistogramUniform;
+
+@group(0)
+@binding(1)
+var color_texture: texture_2d<f32>;
+
+@group(0)
+@binding(2)
+var<storage, read_write> histogram: array<atomic<u32>, NUM_BINS>;
+
+// workgroup memory is zero initialized
+var<workgroup> workgroup_histogram: array<atomic<u32>, NUM_BINS>;
+
+@compute
+@workgroup_size(16, 16)
+fn build_histogram(
+    @builtin(global_invocation_id) global_id: vec3u,
+    @builtin(local_invocation_index) local_index: u32,
+) {
+    if all(global_id.xy < histogram_uniform.viewport_size) {
+        let position = histogram_uniform.viewport_offset + global_id.xy;
+        let color = textureLoad(color_texture, position, 0).rgb;
+        atomicAdd(&workgroup_histogram[luminance_bin(color)], 1u);
+    }
+
+    workgroupBarrier();
+
+    // merge the workgroup's histogram, so there's less contention on the global one
+    if local_index < NUM_BINS {
+        let count = atomicLoad(&workgroup_histogram[local_index]);
+        if count > 0 {
+            atomicAdd(&histogram[local_index], count);
+        }
+    }
+}
+
+// bin 0 is for black pixels, the others are spread evenly over the log luminance range.
+fn luminance_bin(color: vec3f) -> u32 {
+    let luminance = dot(color, vec3f(0.2126, 0.7152, 0.0722));
+    if luminance < 1e-5 {
+        return 0u;
+    }
+
+    let t = saturate((log2(luminance) - histogram_uniform.min_log_luminance) / histogram_uniform.log_luminance_range);
+    return 1u + u32(t * f32(NUM_BINS - 2));
+}
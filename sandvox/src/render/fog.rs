@@ -0,0 +1,53 @@
+use bevy_ecs::component::Component;
+use bytemuck::{
+    Pod,
+    Zeroable,
+};
+use nalgebra::Vector4;
+use palette::LinSrgb;
+
+/// Distance fog for a camera.
+///
+/// Meshes fade into the fog color between `start` and `end` (in world units
+/// from the camera). This hides where the loaded terrain ends, so the camera's
+/// far plane doesn't have to.
+#[derive(Clone, Copy, Debug, PartialEq, Component)]
+pub struct Fog {
+    pub color: LinSrgb<f32>,
+    pub start: f32,
+    pub end: f32,
+}
+
+impl Fog {
+    /// Fog that ends at `distance` and starts at `start * distance`.
+    pub fn until(distance: f32, start: f32, color: LinSrgb<f32>) -> Self {
+        Self {
+            color,
+            start: start * distance,
+            end: distance,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Pod, Zeroable)]
+#[repr(C)]
+pub struct FogData {
+    /// The alpha channel is 0 if there is no fog.
+    pub color: Vector4<f32>,
+    pub start: f32,
+    pub end: f32,
+    _padding: [u32; 2],
+}
+
+impl From<Option<&Fog>> for FogData {
+    fn from(value: Option<&Fog>) -> Self {
+        value.map_or_else(Zeroable::zeroed, |fog| {
+            Self {
+                color: Vector4::new(fog.color.red, fog.color.green, fog.color.blue, 1.0),
+                start: fog.start,
+                end: fog.end,
+                _padding: Default::default(),
+            }
+        })
+    }
+}
@@ -1,6 +1,16 @@
-use std::time::{
-    Duration,
-    Instant,
+//! Measures the frame rate, and frame time spikes that the average hides.
+//!
+//! Spikes show up as hitching, e.g. when many chunk meshes are uploaded at
+//! once. The 1% and 0.1% lows are taken from the most recent frames, the
+//! maximum frame time from the last measurement interval. While a profile is
+//! captured, the measurements are also recorded as counters in the trace.
+
+use std::{
+    collections::VecDeque,
+    time::{
+        Duration,
+        Instant,
+    },
 };
 
 use bevy_ecs::{
@@ -16,15 +26,18 @@ use color_eyre::eyre::Error;
 use crate::{
     ecs::{
         plugin::{
+            Dependencies,
             Plugin,
             WorldBuilder,
         },
         schedule,
     },
+    profiler::capture,
     render::{
         RenderPlugin,
         RenderSystems,
     },
+    util::percentile,
 };
 
 #[derive(Clone, Copy, Debug, Default)]
@@ -33,11 +46,15 @@ pub struct FpsCounterPlugin {
 }
 
 impl Plugin for FpsCounterPlugin {
+    fn dependencies(&self, dependencies: &mut Dependencies) -> Result<(), Error> {
+        dependencies.require_plugin::<RenderPlugin>();
+        Ok(())
+    }
+
     fn setup(&self, builder: &mut WorldBuilder) -> Result<(), Error> {
         builder
-            .require_plugin::<RenderPlugin>()
             .insert_resource(FpsCounter::default())
-            .insert_resource(FpsCounterState::default())
+            .init_resource::<FpsCounterState>()
             .insert_resource(self.config)
             .add_systems(
                 schedule::Render,
@@ -51,20 +68,34 @@ impl Plugin for FpsCounterPlugin {
 #[derive(Clone, Copy, Debug, Resource)]
 pub struct FpsCounterConfig {
     pub measurement_inverval: Duration,
+
+    /// Frames that take longer than this are counted in
+    /// [`FpsCounter::frames_over_budget`].
+    pub frame_budget: Duration,
+
+    /// Number of recent frames that the lows are taken from. The 0.1% low
+    /// needs at least 1000 frames to mean anything.
+    pub history_length: usize,
 }
 
 impl Default for FpsCounterConfig {
     fn default() -> Self {
         Self {
             measurement_inverval: Duration::from_secs(1),
+            frame_budget: Duration::from_secs(1) / 60,
+            history_length: 1000,
         }
     }
 }
 
-#[derive(Clone, Copy, Debug, Resource)]
+#[derive(Clone, Debug, Resource)]
 struct FpsCounterState {
     start: Instant,
     frame_count: usize,
+    last_frame: Option<Instant>,
+    max_frame_time: Duration,
+    frames_over_budget: u64,
+    frame_times: VecDeque<Duration>,
 }
 
 impl Default for FpsCounterState {
@@ -72,6 +103,10 @@ impl Default for FpsCounterState {
         Self {
             start: Instant::now(),
             frame_count: 0,
+            last_frame: None,
+            max_frame_time: Duration::ZERO,
+            frames_over_budget: 0,
+            frame_times: VecDeque::new(),
         }
     }
 }
@@ -79,6 +114,20 @@ impl Default for FpsCounterState {
 #[derive(Clone, Copy, Debug, Default, Resource)]
 pub struct FpsCounter {
     pub fps: f32,
+
+    /// 1% of the recent frames took at least this long.
+    pub low_1_percent: Duration,
+
+    /// 0.1% of the recent frames took at least this long.
+    pub low_0_1_percent: Duration,
+
+    /// The longest frame of the last measurement interval.
+    pub max_frame_time: Duration,
+
+    /// Number of frames since startup that took longer than
+    /// [`FpsCounterConfig::frame_budget`]. Like the other measurements, this is
+    /// only updated once per interval.
+    pub frames_over_budget: u64,
 }
 
 fn take_measurement(
@@ -86,14 +135,86 @@ fn take_measurement(
     config: Res<FpsCounterConfig>,
     mut counter: ResMut<FpsCounter>,
 ) {
+    let now = Instant::now();
     state.frame_count += 1;
 
-    let now = Instant::now();
+    if let Some(last_frame) = state.last_frame.replace(now) {
+        let frame_time = now - last_frame;
+
+        state.max_frame_time = state.max_frame_time.max(frame_time);
+        if frame_time > config.frame_budget {
+            state.frames_over_budget += 1;
+        }
+
+        while state.frame_times.len() >= config.history_length.max(1) {
+            state.frame_times.pop_front();
+        }
+        state.frame_times.push_back(frame_time);
+    }
+
     let elapsed = now - state.start;
     if elapsed >= config.measurement_inverval {
+        let (low_1_percent, low_0_1_percent) = frame_time_lows(&state.frame_times);
+
         counter.fps = state.frame_count as f32 / elapsed.as_secs_f32();
+        counter.low_1_percent = low_1_percent;
+        counter.low_0_1_percent = low_0_1_percent;
+        counter.max_frame_time = state.max_frame_time;
+        counter.frames_over_budget = state.frames_over_budget;
+
+        if capture::is_capturing() {
+            capture::record_counter(
+                "frame_times",
+                now,
+                serde_json::json!({
+                    "fps": counter.fps,
+                    "low_1_percent_ms": counter.low_1_percent.as_secs_f64() * 1000.0,
+                    "low_0_1_percent_ms": counter.low_0_1_percent.as_secs_f64() * 1000.0,
+                    "max_ms": counter.max_frame_time.as_secs_f64() * 1000.0,
+                    "frames_over_budget": counter.frames_over_budget,
+                }),
+            );
+        }
 
         state.start = now;
         state.frame_count = 0;
+        state.max_frame_time = Duration::ZERO;
+    }
+}
+
+/// Returns the 1% and 0.1% low frame times.
+fn frame_time_lows(frame_times: &VecDeque<Duration>) -> (Duration, Duration) {
+    let mut sorted = frame_times
+        .iter()
+        .map(Duration::as_secs_f64)
+        .collect::<Vec<_>>();
+    sorted.sort_by(f64::total_cmp);
+
+    (
+        Duration::from_secs_f64(percentile(&sorted, 0.99)),
+        Duration::from_secs_f64(percentile(&sorted, 0.999)),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::VecDeque,
+        time::Duration,
+    };
+
+    use crate::render::fps_counter::frame_time_lows;
+
+    #[test]
+    fn it_takes_the_lows_from_the_slowest_frames() {
+        let mut frame_times = VecDeque::new();
+        frame_times.extend([Duration::from_millis(10); 979]);
+        frame_times.extend([Duration::from_millis(50); 19]);
+        frame_times.extend([Duration::from_millis(100); 2]);
+
+        assert_eq!(
+            frame_time_lows(&frame_times),
+            (Duration::from_millis(50), Duration::from_millis(100))
+        );
     }
 }
@@ -0,0 +1,259 @@
+use bevy_ecs::resource::Resource;
+use bytemuck::{
+    Pod,
+    Zeroable,
+};
+use nalgebra::{
+    UnitVector3,
+    Vector3,
+    Vector4,
+};
+use palette::{
+    LinSrgb,
+    Mix,
+    Srgb,
+};
+use serde::{
+    Deserialize,
+    Serialize,
+};
+
+/// Curves that map the sun's position to the scene lighting.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct LightingConfig {
+    /// Ambient light over the course of the day.
+    #[serde(default = "default_ambient")]
+    pub ambient: LightCurve,
+
+    /// Directional sun light over the course of the day.
+    #[serde(default = "default_sun")]
+    pub sun: LightCurve,
+
+    /// Moon light at full moon. This is scaled by the moon phase and only
+    /// contributes at night.
+    #[serde(default = "default_moon")]
+    pub moon: LightColor,
+}
+
+impl Default for LightingConfig {
+    fn default() -> Self {
+        Self {
+            ambient: default_ambient(),
+            sun: default_sun(),
+            moon: default_moon(),
+        }
+    }
+}
+
+fn default_ambient() -> LightCurve {
+    LightCurve::new([
+        (-18.0, LightColor::new(Srgb::new(0.4, 0.45, 0.7), 0.08)),
+        (-6.0, LightColor::new(Srgb::new(0.5, 0.5, 0.7), 0.15)),
+        (0.0, LightColor::new(Srgb::new(1.0, 0.8, 0.7), 0.3)),
+        (10.0, LightColor::new(Srgb::new(1.0, 1.0, 1.0), 0.4)),
+    ])
+}
+
+fn default_sun() -> LightCurve {
+    LightCurve::new([
+        (-6.0, LightColor::new(Srgb::new(1.0, 0.5, 0.2), 0.0)),
+        (0.0, LightColor::new(Srgb::new(1.0, 0.6, 0.3), 0.3)),
+        (10.0, LightColor::new(Srgb::new(1.0, 0.95, 0.85), 0.6)),
+        (30.0, LightColor::new(Srgb::new(1.0, 1.0, 1.0), 0.6)),
+    ])
+}
+
+fn default_moon() -> LightColor {
+    LightColor::new(Srgb::new(0.6, 0.7, 1.0), 0.15)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct LightColor {
+    pub color: Srgb<f32>,
+    pub intensity: f32,
+}
+
+impl LightColor {
+    pub const fn new(color: Srgb<f32>, intensity: f32) -> Self {
+        Self { color, intensity }
+    }
+
+    fn to_linear(self) -> LinSrgb<f32> {
+        self.color.into_linear() * self.intensity
+    }
+}
+
+/// A curve of light colors over the altitude of the sun.
+///
+/// Between the points the light is interpolated linearly. Outside it's clamped
+/// to the first and last point respectively.
+#[derive(Clone, Debug, PartialEq, Default, Serialize, Deserialize)]
+#[serde(from = "Vec<LightCurvePoint>", into = "Vec<LightCurvePoint>")]
+pub struct LightCurve {
+    points: Vec<LightCurvePoint>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct LightCurvePoint {
+    /// Altitude of the sun above the horizon in degrees.
+    pub sun_altitude: f32,
+
+    #[serde(flatten)]
+    pub light: LightColor,
+}
+
+impl LightCurve {
+    pub fn new(points: impl IntoIterator<Item = (f32, LightColor)>) -> Self {
+        points
+            .into_iter()
+            .map(|(sun_altitude, light)| {
+                LightCurvePoint {
+                    sun_altitude,
+                    light,
+                }
+            })
+            .collect::<Vec<_>>()
+            .into()
+    }
+
+    /// Evaluates the curve at the given sun altitude (in degrees).
+    pub fn sample(&self, sun_altitude: f32) -> LinSrgb<f32> {
+        let Some(first) = self.points.first()
+        else {
+            return LinSrgb::new(0.0, 0.0, 0.0);
+        };
+
+        let mut previous = first;
+        if sun_altitude <= previous.sun_altitude {
+            return previous.light.to_linear();
+        }
+
+        for point in &self.points[1..] {
+            if sun_altitude <= point.sun_altitude {
+                let t = (sun_altitude - previous.sun_altitude)
+                    / (point.sun_altitude - previous.sun_altitude);
+                return previous.light.to_linear().mix(point.light.to_linear(), t);
+            }
+            previous = point;
+        }
+
+        previous.light.to_linear()
+    }
+}
+
+impl From<Vec<LightCurvePoint>> for LightCurve {
+    fn from(mut points: Vec<LightCurvePoint>) -> Self {
+        points.sort_by(|a, b| a.sun_altitude.total_cmp(&b.sun_altitude));
+        Self { points }
+    }
+}
+
+impl From<LightCurve> for Vec<LightCurvePoint> {
+    fn from(value: LightCurve) -> Self {
+        value.points
+    }
+}
+
+/// The lights illuminating the scene.
+///
+/// This is written into the main pass uniform, so the mesh shader can use it.
+/// It's also where shadow maps will get the light direction from.
+#[derive(Clone, Copy, Debug, Resource)]
+pub struct SceneLighting {
+    /// Direction towards the sun (in world space).
+    pub sun_direction: UnitVector3<f32>,
+    pub sun_color: LinSrgb<f32>,
+
+    /// Direction towards the moon (in world space).
+    pub moon_direction: UnitVector3<f32>,
+    pub moon_color: LinSrgb<f32>,
+
+    pub ambient_color: LinSrgb<f32>,
+}
+
+impl Default for SceneLighting {
+    fn default() -> Self {
+        Self {
+            sun_direction: UnitVector3::new_normalize(Vector3::new(0.5, 1.0, 0.5)),
+            sun_color: LinSrgb::new(0.6, 0.6, 0.6),
+            moon_direction: -Vector3::y_axis(),
+            moon_color: LinSrgb::new(0.0, 0.0, 0.0),
+            ambient_color: LinSrgb::new(0.4, 0.4, 0.4),
+        }
+    }
+}
+
+impl SceneLighting {
+    /// Computes the lighting from the positions of sun and moon.
+    ///
+    /// `moon_illumination` is the illuminated fraction of the moon.
+    pub fn from_sky(
+        config: &LightingConfig,
+        sun_direction: UnitVector3<f32>,
+        moon_direction: UnitVector3<f32>,
+        moon_illumination: f32,
+    ) -> Self {
+        // +y is up
+        let sun_altitude = sun_direction.y.asin().to_degrees();
+        let moon_altitude = moon_direction.y.asin().to_degrees();
+
+        // the moon only lights the scene once it's risen and the sun has set (civil
+        // twilight)
+        let moon_visible = (moon_altitude / 5.0).clamp(0.0, 1.0);
+        let night = (-sun_altitude / 6.0).clamp(0.0, 1.0);
+
+        Self {
+            sun_direction,
+            sun_color: config.sun.sample(sun_altitude),
+            moon_direction,
+            moon_color: config.moon.to_linear() * (moon_illumination * moon_visible * night),
+            ambient_color: config.ambient.sample(sun_altitude),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Pod, Zeroable)]
+#[repr(C)]
+pub struct LightData {
+    pub sun_direction: Vector4<f32>,
+    pub sun_color: Vector4<f32>,
+    pub moon_direction: Vector4<f32>,
+    pub moon_color: Vector4<f32>,
+    pub ambient_color: Vector4<f32>,
+}
+
+impl From<&SceneLighting> for LightData {
+    fn from(value: &SceneLighting) -> Self {
+        let color = |color: LinSrgb<f32>| Vector4::new(color.red, color.green, color.blue, 1.0);
+
+        Self {
+            sun_direction: value.sun_direction.to_homogeneous(),
+            sun_color: color(value.sun_color),
+            moon_direction: value.moon_direction.to_homogeneous(),
+            moon_color: color(value.moon_color),
+            ambient_color: color(value.ambient_color),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use palette::Srgb;
+
+    use crate::render::lighting::{
+        LightColor,
+        LightCurve,
+    };
+
+    #[test]
+    fn it_interpolates_light_curves() {
+        let curve = LightCurve::new([
+            (10.0, LightColor::new(Srgb::new(1.0, 1.0, 1.0), 1.0)),
+            (-10.0, LightColor::new(Srgb::new(1.0, 1.0, 1.0), 0.0)),
+        ]);
+
+        assert_eq!(curve.sample(-20.0).red, 0.0);
+        assert!((curve.sample(0.0).red - 0.5).abs() < 1e-5);
+        assert_eq!(curve.sample(20.0).red, 1.0);
+    }
+}
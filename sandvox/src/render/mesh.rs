@@ -1,25 +1,31 @@
 use std::{
+    borrow::Cow,
     marker::PhantomData,
     ops::Range,
+    sync::Arc,
 };
 
 use bevy_ecs::{
+    change_detection::DetectChangesMut,
     component::Component,
     entity::Entity,
     name::NameOrEntity,
     query::{
-        Added,
         Changed,
         Has,
         Or,
         ROQueryItem,
         With,
-        Without,
     },
     resource::Resource,
     schedule::{
         IntoScheduleConfigs,
-        common_conditions::any_match_filter,
+        SystemCondition,
+        SystemSet,
+        common_conditions::{
+            any_match_filter,
+            any_with_component,
+        },
     },
     system::{
         Commands,
@@ -41,12 +47,16 @@ use nalgebra::{
     Point2,
     Vector4,
 };
-use wgpu::util::DeviceExt;
+use wgpu::util::{
+    DeviceExt,
+    DrawIndirectArgs,
+};
 
 use crate::{
     collide::Frustrum,
     ecs::{
         plugin::{
+            Dependencies,
             Plugin,
             WorldBuilder,
         },
@@ -54,7 +64,9 @@ use crate::{
         transform::GlobalTransform,
     },
     render::{
+        DefaultAtlas,
         RenderSystems,
+        atlas::AtlasVersion,
         camera::{
             CameraProjection,
             FrustrumCulled,
@@ -63,6 +75,15 @@ use crate::{
             AddRenderFunction,
             RenderFunction,
         },
+        mesh_arena::{
+            MeshArena,
+            MeshArenaAllocation,
+        },
+        mesh_culling::{
+            MeshCulling,
+            MeshCullingBuffer,
+            MeshCullingStatistics,
+        },
         pass::{
             context::RenderPass,
             main_pass::{
@@ -71,9 +92,11 @@ use crate::{
                 MainPassLayout,
                 MainPassPlugin,
                 MainPassSystems,
+                Wireframe,
             },
             phase,
         },
+        remove_on_gpu_setup,
         render_target::RenderTarget,
         staging::Staging,
         surface::Surface,
@@ -84,32 +107,48 @@ use crate::{
     },
 };
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, Default)]
 pub struct MeshPlugin;
 
 impl Plugin for MeshPlugin {
+    fn dependencies(&self, dependencies: &mut Dependencies) -> Result<(), Error> {
+        dependencies.require_plugin::<MainPassPlugin>();
+        Ok(())
+    }
+
     fn setup(&self, builder: &mut WorldBuilder) -> Result<(), Error> {
         builder
-        .require_plugin::<MainPassPlugin>()
         .init_resource::<RenderMeshStatistics>()
             .add_systems(
-                schedule::Startup,
+                schedule::GpuSetup,
                 (
                     create_mesh_pipeline_layout.in_set(RenderSystems::Setup).after(MainPassSystems::Prepare),
+                    create_mesh_arena.in_set(RenderSystems::Setup).after(create_mesh_pipeline_layout),
+                    // whoever created the meshes must create them again, e.g. chunks are meshed
+                    // again and models are imported again
+                    remove_on_gpu_setup::<Mesh>,
                     create_instance_buffer.in_set(RenderSystems::Setup),
+                    remove_on_gpu_setup::<MeshPipeline>,
+                    |mut commands: Commands| commands.remove_resource::<BindlessBindGroup>(),
                 ),
             )
             .add_systems(
                 schedule::Render,
                 (
                     create_mesh_pipeline.in_set(RenderSystems::BeginFrame),
+                    update_bindless_bind_group
+                        .in_set(RenderSystems::BeginFrame)
+                        .after(MainPassSystems::Prepare),
                     update_instance_buffer
                         .in_set(RenderSystems::BeginFrame)
+                        .in_set(MeshSystems::UpdateInstances)
                         .run_if(
                             any_match_filter::<(
                                 With<Mesh>,
-                                Or<(Changed<GlobalTransform>, Added<Mesh>)>,
-                            )>,
+                                Or<(Changed<GlobalTransform>, Changed<Mesh>)>,
+                            )>
+                            // joints can move without the mesh moving
+                            .or(any_with_component::<Skin>),
                         ),
 
                 ),
@@ -121,6 +160,11 @@ impl Plugin for MeshPlugin {
     }
 }
 
+#[derive(Clone, Copy, Debug, SystemSet, PartialEq, Eq, Hash)]
+pub enum MeshSystems {
+    UpdateInstances,
+}
+
 #[derive(Clone, Debug, Default)]
 pub struct MeshBuilder {
     vertices: Vec<Vertex>,
@@ -133,6 +177,14 @@ impl MeshBuilder {
         self.faces.clear();
     }
 
+    pub fn num_vertices(&self) -> usize {
+        self.vertices.len()
+    }
+
+    pub fn num_faces(&self) -> usize {
+        self.faces.len()
+    }
+
     pub fn push(
         &mut self,
         vertices: impl IntoIterator<Item = Vertex>,
@@ -204,9 +256,23 @@ impl MeshBuilder {
                     index_buffer_offset: 0,
                     num_indices,
                 },
+                allocation: None,
             })
         }
     }
+
+    /// Like [`finish`][Self::finish], but sub-allocates the mesh from the
+    /// [`MeshArena`] instead of creating buffers for it.
+    pub fn finish_in_arena(&self, arena: &MeshArena, queue: &wgpu::Queue) -> Option<Mesh> {
+        if self.faces.is_empty() {
+            None
+        }
+        else {
+            assert!(!self.vertices.is_empty());
+
+            Some(arena.insert(queue, &self.vertices, bytemuck::cast_slice(&self.faces)))
+        }
+    }
 }
 
 #[derive(Clone, Copy, Debug, Pod, Zeroable)]
@@ -214,7 +280,16 @@ impl MeshBuilder {
 struct Instance {
     model_matrix: Matrix4<f32>,
     vertex_buffer_offset: u32,
-    _padding: [u32; 3],
+
+    /// Offset of the instance's joint matrices in the joint buffer, or
+    /// [`u32::MAX`] if the mesh isn't skinned.
+    joint_offset: u32,
+
+    _padding: [u32; 2],
+
+    /// Scales normals before the model matrix is applied to them, so that
+    /// they stay perpendicular to non-uniformly scaled surfaces.
+    normal_scale: Vector4<f32>,
 }
 
 #[derive(Clone, Copy, Debug, Pod, Zeroable)]
@@ -224,7 +299,45 @@ pub struct Vertex {
     pub normal: Vector4<f32>,
     pub uv: Point2<f32>,
     pub texture_id: u32,
-    pub padding: u32,
+
+    /// Weights of the [`joints`](Self::joints), packed as 4 normalized `u8`s.
+    ///
+    /// Only used if the mesh has a [`Skin`].
+    pub joint_weights: u32,
+
+    /// Indices into [`Skin::joints`], packed as 4 `u16`s.
+    pub joints: [u32; 2],
+
+    pub padding: [u32; 2],
+}
+
+impl Vertex {
+    pub fn pack_joints(joints: [u16; 4]) -> [u32; 2] {
+        [
+            u32::from(joints[0]) | (u32::from(joints[1]) << 16),
+            u32::from(joints[2]) | (u32::from(joints[3]) << 16),
+        ]
+    }
+
+    pub fn pack_joint_weights(weights: [f32; 4]) -> u32 {
+        u32::from_le_bytes(weights.map(|weight| (weight.clamp(0.0, 1.0) * 255.0).round() as u8))
+    }
+}
+
+/// Makes a mesh deform with the transforms of its joints.
+///
+/// The joint matrices of all skinned meshes are uploaded to a shared joint
+/// buffer each frame, and the vertices are blended between them according to
+/// their [`joints`](Vertex::joints) and
+/// [`joint_weights`](Vertex::joint_weights).
+#[derive(Clone, Debug, Component)]
+pub struct Skin {
+    /// Entities whose [`GlobalTransform`]s drive the joints.
+    pub joints: Vec<Entity>,
+
+    /// Transforms from the mesh's frame to the frame of each joint in its bind
+    /// pose.
+    pub inverse_bind_matrices: Vec<Matrix4<f32>>,
 }
 
 #[derive(Clone, Debug, Component)]
@@ -233,6 +346,9 @@ pub struct Mesh {
     pub index_buffer: wgpu::Buffer,
     pub bind_group: wgpu::BindGroup,
     pub span: MeshBufferSpan,
+
+    /// Keeps the mesh's space allocated, if it's in the [`MeshArena`].
+    pub allocation: Option<Arc<MeshArenaAllocation>>,
 }
 
 impl Mesh {
@@ -253,11 +369,12 @@ pub struct MeshBufferSpan {
 #[derive(Debug, Resource)]
 struct InstanceBuffer {
     buffer: TypedArrayBuffer<Instance>,
+    joint_buffer: TypedArrayBuffer<Matrix4<f32>>,
     bind_group: Option<wgpu::BindGroup>,
 }
 
-#[derive(Clone, Copy, Debug, Component)]
-struct InstanceId(u32);
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Component)]
+pub(super) struct InstanceId(pub(super) u32);
 
 #[derive(Debug, Resource)]
 pub struct MeshPipelineLayout {
@@ -265,12 +382,22 @@ pub struct MeshPipelineLayout {
     shader: wgpu::ShaderModule,
     instance_bind_group_layout: wgpu::BindGroupLayout,
     pub mesh_bind_group_layout: wgpu::BindGroupLayout,
+
+    /// Layout of the texture array, if the atlas uses the bindless path.
+    bindless_bind_group_layout: Option<wgpu::BindGroupLayout>,
+}
+
+/// Bind group for the texture array of the bindless path.
+#[derive(Debug, Resource)]
+struct BindlessBindGroup {
+    bind_group: wgpu::BindGroup,
+    atlas_version: AtlasVersion,
 }
 
 #[derive(Debug, Component)]
-struct MeshPipeline {
+pub(super) struct MeshPipeline {
     opaque: wgpu::RenderPipeline,
-    wireframe: wgpu::RenderPipeline,
+    wireframe: Option<wgpu::RenderPipeline>,
     depth_prepass: Option<wgpu::RenderPipeline>,
 }
 
@@ -278,22 +405,35 @@ struct MeshPipeline {
 fn create_mesh_pipeline_layout(
     wgpu: Res<WgpuContext>,
     main_pass_layout: Res<MainPassLayout>,
+    atlas: Res<DefaultAtlas>,
     mut commands: Commands,
 ) {
     let instance_bind_group_layout =
         wgpu.device
             .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
                 label: Some("instance"),
-                entries: &[wgpu::BindGroupLayoutEntry {
-                    binding: 0,
-                    visibility: wgpu::ShaderStages::VERTEX,
-                    ty: wgpu::BindingType::Buffer {
-                        ty: wgpu::BufferBindingType::Storage { read_only: true },
-                        has_dynamic_offset: false,
-                        min_binding_size: None,
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::VERTEX,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Storage { read_only: true },
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
                     },
-                    count: None,
-                }],
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::VERTEX,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Storage { read_only: true },
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                ],
             });
 
     let mesh_bind_group_layout =
@@ -324,27 +464,80 @@ fn create_mesh_pipeline_layout(
                 ],
             });
 
+    let bindless_bind_group_layout = atlas.resources().bindless.map(|bindless| {
+        tracing::debug!(
+            texture_limit = bindless.texture_limit(),
+            "using bindless textures for meshes"
+        );
+
+        wgpu.device
+            .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("mesh bindless"),
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            multisampled: false,
+                        },
+                        count: Some(bindless.texture_limit().try_into().unwrap()),
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Storage { read_only: true },
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                ],
+            })
+    });
+
+    let mut bind_group_layouts = vec![
+        &main_pass_layout.bind_group_layout,
+        &instance_bind_group_layout,
+        &mesh_bind_group_layout,
+    ];
+    bind_group_layouts.extend(&bindless_bind_group_layout);
+
     let layout = wgpu
         .device
         .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
             label: Some("mesh"),
-            bind_group_layouts: &[
-                &main_pass_layout.bind_group_layout,
-                &instance_bind_group_layout,
-                &mesh_bind_group_layout,
-            ],
+            bind_group_layouts: &bind_group_layouts,
             immediate_size: 0,
         });
 
+    // the bindless fragment shader is only compiled if it's supported
+    let shader_source = if bindless_bind_group_layout.is_some() {
+        Cow::Owned(format!(
+            "{}\n{}",
+            include_str!("mesh.wgsl"),
+            include_str!("mesh_bindless.wgsl")
+        ))
+    }
+    else {
+        Cow::Borrowed(include_str!("mesh.wgsl"))
+    };
+
     let shader = wgpu
         .device
-        .create_shader_module(wgpu::include_wgsl!("mesh.wgsl"));
+        .create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("mesh.wgsl"),
+            source: wgpu::ShaderSource::Wgsl(shader_source),
+        });
 
     commands.insert_resource(MeshPipelineLayout {
         layout,
         shader,
         instance_bind_group_layout,
         mesh_bind_group_layout,
+        bindless_bind_group_layout,
     });
 }
 
@@ -354,17 +547,30 @@ fn create_mesh_pipeline(
     pipeline_layout: Res<MeshPipelineLayout>,
     surfaces: Populated<(NameOrEntity, &Surface)>,
     cameras: Populated<
-        (NameOrEntity, &RenderTarget, Has<DepthPrepass>),
         (
-            // todo: this should really check if there's *any* view that needs to render *anything*
-            // opaque
-            With<MainPass>,
-            Without<MeshPipeline>,
+            NameOrEntity,
+            &RenderTarget,
+            Has<DepthPrepass>,
+            Has<Wireframe>,
+            Option<&MeshPipeline>,
         ),
+        // todo: this should really check if there's *any* view that needs to render *anything*
+        // opaque
+        With<MainPass>,
     >,
     mut commands: Commands,
 ) {
-    for (camera_entity, render_target, enable_depth_prepass) in cameras {
+    for (camera_entity, render_target, enable_depth_prepass, enable_wireframe, pipeline) in cameras
+    {
+        // (re)create the pipeline if the camera's depth prepass or wireframe was
+        // toggled
+        if pipeline.is_some_and(|pipeline| {
+            pipeline.depth_prepass.is_some() == enable_depth_prepass
+                && pipeline.wireframe.is_some() == enable_wireframe
+        }) {
+            continue;
+        }
+
         if let Ok((surface_entity, surface)) = surfaces.get(render_target.0) {
             tracing::debug!(surface = %surface_entity, camera = %camera_entity, "creating mesh render pipeline for surface");
 
@@ -395,7 +601,7 @@ fn create_mesh_pipeline(
                             wgpu::CompareFunction::Equal
                         }
                         else {
-                            wgpu::CompareFunction::Less
+                            surface.depth_compare(wgpu::CompareFunction::Less)
                         },
                         stencil: Default::default(),
                         bias: Default::default(),
@@ -403,7 +609,14 @@ fn create_mesh_pipeline(
                     multisample: Default::default(),
                     fragment: Some(wgpu::FragmentState {
                         module: &pipeline_layout.shader,
-                        entry_point: Some("mesh_shaded_fragment"),
+                        entry_point: Some(
+                            if pipeline_layout.bindless_bind_group_layout.is_some() {
+                                "mesh_bindless_fragment"
+                            }
+                            else {
+                                "mesh_shaded_fragment"
+                            },
+                        ),
                         compilation_options: Default::default(),
                         targets: &[Some(wgpu::ColorTargetState {
                             format: surface.surface_format(),
@@ -415,47 +628,50 @@ fn create_mesh_pipeline(
                     cache: None,
                 });
 
-            let wireframe = wgpu
-                .device
-                .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-                    label: Some("mesh/opaque"),
-                    layout: Some(&pipeline_layout.layout),
-                    vertex: wgpu::VertexState {
-                        module: &pipeline_layout.shader,
-                        entry_point: Some("mesh_wireframe_vertex"),
-                        compilation_options: Default::default(),
-                        buffers: &[],
-                    },
-                    primitive: wgpu::PrimitiveState {
-                        topology: wgpu::PrimitiveTopology::LineList,
-                        strip_index_format: None,
-                        front_face: wgpu::FrontFace::Ccw,
-                        cull_mode: None,
-                        unclipped_depth: false,
-                        polygon_mode: wgpu::PolygonMode::Fill,
-                        conservative: false,
-                    },
-                    depth_stencil: Some(wgpu::DepthStencilState {
-                        format: surface.depth_format(),
-                        depth_write_enabled: false,
-                        depth_compare: wgpu::CompareFunction::LessEqual,
-                        stencil: Default::default(),
-                        bias: Default::default(),
-                    }),
-                    multisample: Default::default(),
-                    fragment: Some(wgpu::FragmentState {
-                        module: &pipeline_layout.shader,
-                        entry_point: Some("mesh_wireframe_fragment"),
-                        compilation_options: Default::default(),
-                        targets: &[Some(wgpu::ColorTargetState {
-                            format: surface.surface_format(),
-                            blend: None,
-                            write_mask: wgpu::ColorWrites::ALL,
-                        })],
-                    }),
-                    multiview_mask: None,
-                    cache: None,
-                });
+            let wireframe = enable_wireframe.then(|| {
+                wgpu.device
+                    .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                        label: Some("mesh/wireframe"),
+                        layout: Some(&pipeline_layout.layout),
+                        vertex: wgpu::VertexState {
+                            module: &pipeline_layout.shader,
+                            entry_point: Some("mesh_wireframe_vertex"),
+                            compilation_options: Default::default(),
+                            buffers: &[],
+                        },
+                        primitive: wgpu::PrimitiveState {
+                            topology: wgpu::PrimitiveTopology::TriangleList,
+                            strip_index_format: None,
+                            front_face: wgpu::FrontFace::Ccw,
+                            // back faces show the edges behind the mesh, if there's nothing
+                            // in front of them
+                            cull_mode: None,
+                            unclipped_depth: false,
+                            polygon_mode: wgpu::PolygonMode::Fill,
+                            conservative: false,
+                        },
+                        depth_stencil: Some(wgpu::DepthStencilState {
+                            format: surface.depth_format(),
+                            depth_write_enabled: false,
+                            depth_compare: surface.depth_compare(wgpu::CompareFunction::LessEqual),
+                            stencil: Default::default(),
+                            bias: Default::default(),
+                        }),
+                        multisample: Default::default(),
+                        fragment: Some(wgpu::FragmentState {
+                            module: &pipeline_layout.shader,
+                            entry_point: Some("mesh_wireframe_fragment"),
+                            compilation_options: Default::default(),
+                            targets: &[Some(wgpu::ColorTargetState {
+                                format: surface.surface_format(),
+                                blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                                write_mask: wgpu::ColorWrites::ALL,
+                            })],
+                        }),
+                        multiview_mask: None,
+                        cache: None,
+                    })
+            });
 
             let depth_prepass = enable_depth_prepass.then(|| {
                 wgpu.device
@@ -480,7 +696,7 @@ fn create_mesh_pipeline(
                         depth_stencil: Some(wgpu::DepthStencilState {
                             format: surface.depth_format(),
                             depth_write_enabled: true,
-                            depth_compare: wgpu::CompareFunction::Less,
+                            depth_compare: surface.depth_compare(wgpu::CompareFunction::Less),
                             stencil: Default::default(),
                             bias: Default::default(),
                         }),
@@ -500,6 +716,63 @@ fn create_mesh_pipeline(
     }
 }
 
+#[profiling::function]
+fn create_mesh_arena(
+    wgpu: Res<WgpuContext>,
+    layout: Res<MeshPipelineLayout>,
+    mut commands: Commands,
+) {
+    commands.insert_resource(MeshArena::new(
+        wgpu.device.clone(),
+        layout.mesh_bind_group_layout.clone(),
+    ));
+}
+
+#[profiling::function]
+fn update_bindless_bind_group(
+    wgpu: Res<WgpuContext>,
+    layout: Res<MeshPipelineLayout>,
+    atlas: Res<DefaultAtlas>,
+    bindless_bind_group: Option<Res<BindlessBindGroup>>,
+    mut commands: Commands,
+) {
+    let Some(bind_group_layout) = &layout.bindless_bind_group_layout
+    else {
+        return;
+    };
+
+    let atlas_resources = atlas.resources();
+    if bindless_bind_group
+        .is_some_and(|bind_group| bind_group.atlas_version == atlas_resources.version)
+    {
+        return;
+    }
+
+    let bindless = atlas_resources
+        .bindless
+        .expect("bindless layout without bindless atlas");
+
+    let bind_group = wgpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("mesh bindless"),
+        layout: bind_group_layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureViewArray(&bindless.texture_views()),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: bindless.data_buffer().as_entire_binding(),
+            },
+        ],
+    });
+
+    commands.insert_resource(BindlessBindGroup {
+        bind_group,
+        atlas_version: atlas_resources.version,
+    });
+}
+
 #[profiling::function]
 fn create_instance_buffer(wgpu: Res<WgpuContext>, mut commands: Commands) {
     let buffer = TypedArrayBuffer::new(
@@ -508,8 +781,15 @@ fn create_instance_buffer(wgpu: Res<WgpuContext>, mut commands: Commands) {
         wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
     );
 
+    let joint_buffer = TypedArrayBuffer::new(
+        wgpu.device.clone(),
+        "mesh joint buffer",
+        wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+    );
+
     commands.insert_resource(InstanceBuffer {
         buffer,
+        joint_buffer,
         bind_group: None,
     });
 }
@@ -520,50 +800,103 @@ fn update_instance_buffer(
     layout: Res<MeshPipelineLayout>,
     mut instance_buffer: ResMut<InstanceBuffer>,
     meshes: Populated<(Entity, &Mesh, &GlobalTransform, Option<&mut InstanceId>)>,
+    skins: Query<&Skin>,
+    joints: Query<&GlobalTransform>,
     mut commands: Commands,
     mut instance_data: Local<Vec<Instance>>,
+    mut joint_data: Local<Vec<Matrix4<f32>>>,
     mut staging: ResMut<Staging>,
 ) {
     // I always forget to clear this! keep this assert :3
     assert!(instance_data.is_empty());
+    assert!(joint_data.is_empty());
 
     // create data for instance buffer
     for (entity, mesh, transform, instance_id) in meshes {
         let id = instance_data.len().try_into().unwrap();
 
+        let model_matrix = transform.to_matrix();
+
+        let joint_offset = if let Ok(skin) = skins.get(entity) {
+            let joint_offset = joint_data.len().try_into().unwrap();
+
+            // the vertices are transformed by the joint matrices first, and then by the
+            // model matrix, so we need to undo the model matrix here.
+            let mesh_inverse = model_matrix.try_inverse().unwrap_or_else(Matrix4::zeros);
+
+            joint_data.extend(skin.joints.iter().zip(&skin.inverse_bind_matrices).map(
+                |(joint, inverse_bind_matrix)| {
+                    let joint_transform = joints
+                        .get(*joint)
+                        .map_or(model_matrix, |joint| joint.to_matrix());
+                    mesh_inverse * joint_transform * inverse_bind_matrix
+                },
+            ));
+
+            joint_offset
+        }
+        else {
+            u32::MAX
+        };
+
+        // normals are transformed with the inverse transpose of the model matrix. the
+        // rotation is orthogonal, so that is the model matrix with the scale applied
+        // twice inversely.
+        let normal_scale = transform
+            .scale
+            .map(|scale| if scale == 0.0 { 0.0 } else { scale.powi(-2) });
+
         instance_data.push(Instance {
-            model_matrix: transform.isometry.to_homogeneous(),
+            model_matrix,
             vertex_buffer_offset: mesh.span.vertex_buffer_offset,
+            joint_offset,
+            normal_scale: normal_scale.push(0.0),
             ..Zeroable::zeroed()
         });
 
         if let Some(mut instance_id) = instance_id {
-            instance_id.0 = id;
+            // GPU culling rebuilds its buffer when instance IDs change
+            instance_id.set_if_neq(InstanceId(id));
         }
         else {
             commands.entity(entity).insert(InstanceId(id));
         }
     }
 
+    // bindings can't be empty
+    if joint_data.is_empty() {
+        joint_data.push(Matrix4::identity());
+    }
+
     let instance_buffer = &mut *instance_buffer;
-    instance_buffer.buffer.write_all(
-        &instance_data,
-        |buffer| {
-            instance_buffer.bind_group =
-                Some(wgpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
-                    label: Some("instance"),
-                    layout: &layout.instance_bind_group_layout,
-                    entries: &[wgpu::BindGroupEntry {
+    let mut reallocated = instance_buffer
+        .buffer
+        .write_all(&instance_data, |_| {}, &mut *staging);
+    reallocated |= instance_buffer
+        .joint_buffer
+        .write_all(&joint_data, |_| {}, &mut *staging);
+
+    if reallocated || instance_buffer.bind_group.is_none() {
+        instance_buffer.bind_group =
+            Some(wgpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("instance"),
+                layout: &layout.instance_bind_group_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
                         binding: 0,
-                        resource: buffer.as_entire_binding(),
-                    }],
-                }));
-        },
-        &mut *staging,
-    );
+                        resource: instance_buffer.buffer.buffer().as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: instance_buffer.joint_buffer.buffer().as_entire_binding(),
+                    },
+                ],
+            }));
+    }
 
     // don't forget!!!!111
     instance_data.clear();
+    joint_data.clear();
 }
 
 struct RenderMeshes<P> {
@@ -595,6 +928,18 @@ trait RenderMeshesForPhase: Send + Sync + 'static {
     fn reset_stats(stats: &mut RenderMeshStatistics) {
         let _ = stats;
     }
+
+    /// Whether the phase draws the indirect arguments written by
+    /// [`MeshCulling`], if the camera has it.
+    #[inline]
+    fn gpu_culled() -> bool {
+        false
+    }
+
+    #[inline]
+    fn count_gpu_stats(stats: &mut RenderMeshStatistics, culling: &MeshCullingStatistics) {
+        let _ = (stats, culling);
+    }
 }
 
 impl RenderMeshesForPhase for phase::Opaque {
@@ -623,6 +968,18 @@ impl RenderMeshesForPhase for phase::Opaque {
     fn reset_stats(stats: &mut RenderMeshStatistics) {
         *stats = Default::default();
     }
+
+    #[inline]
+    fn gpu_culled() -> bool {
+        true
+    }
+
+    #[inline]
+    fn count_gpu_stats(stats: &mut RenderMeshStatistics, culling: &MeshCullingStatistics) {
+        stats.num_rendered += usize::try_from(culling.num_rendered).unwrap();
+        stats.num_culled += usize::try_from(culling.num_culled).unwrap();
+        stats.num_vertices += usize::try_from(culling.num_vertices).unwrap();
+    }
 }
 
 impl RenderMeshesForPhase for phase::Wireframe {
@@ -633,15 +990,12 @@ impl RenderMeshesForPhase for phase::Wireframe {
 
     #[inline]
     fn get_pipeline(pipeline: &MeshPipeline) -> &wgpu::RenderPipeline {
-        &pipeline.wireframe
+        pipeline.wireframe.as_ref().expect("no wireframe pipeline")
     }
 
     #[inline]
-    fn vertices(span: &MeshBufferSpan) -> Range<u32> {
-        // n vertices are n/3 triangles, which require n lines to connect, which require
-        // 2*n vertices
-
-        span.index_buffer_offset..(span.index_buffer_offset + 2 * span.num_indices)
+    fn gpu_culled() -> bool {
+        true
     }
 }
 
@@ -658,6 +1012,11 @@ impl RenderMeshesForPhase for phase::DepthPrepass {
             .as_ref()
             .expect("no depth-prepass pipeline")
     }
+
+    #[inline]
+    fn gpu_culled() -> bool {
+        true
+    }
 }
 
 impl<P> RenderFunction for RenderMeshes<P>
@@ -667,11 +1026,14 @@ where
     type Param = (
         Res<'static, InstanceBuffer>,
         ResMut<'static, RenderMeshStatistics>,
+        Option<Res<'static, BindlessBindGroup>>,
+        Option<Res<'static, MeshCullingBuffer>>,
     );
     type ViewQuery = (
         &'static CameraProjection,
         &'static GlobalTransform,
         &'static MeshPipeline,
+        Option<&'static MeshCulling>,
     );
     type ItemQuery = (
         &'static Mesh,
@@ -681,7 +1043,7 @@ where
 
     #[profiling::function]
     fn prepare(&self, param: SystemParamItem<Self::Param>) {
-        let (_instance_buffer, mut stats) = param;
+        let (_instance_buffer, mut stats, _bindless_bind_group, _culling_buffer) = param;
         P::reset_stats(&mut stats);
     }
 
@@ -693,21 +1055,52 @@ where
         view: ROQueryItem<Self::ViewQuery>,
         items: Query<Self::ItemQuery>,
     ) {
-        let (instance_buffer, mut stats) = param;
+        let (instance_buffer, mut stats, bindless_bind_group, culling_buffer) = param;
 
         if let Some(instance_bind_group) = &instance_buffer.bind_group {
-            let (camera_projection, camera_transform, pipeline) = view;
+            let (camera_projection, camera_transform, pipeline, culling) = view;
 
             let span = render_pass.enter_span(P::scope_label());
 
             render_pass.set_pipeline(P::get_pipeline(pipeline));
             render_pass.set_bind_group(1, instance_bind_group, &[]);
 
+            // all phases share the pipeline layout, so this is bound even if the phase
+            // doesn't sample textures.
+            if let Some(bindless_bind_group) = &bindless_bind_group {
+                render_pass.set_bind_group(3, &bindless_bind_group.bind_group, &[]);
+            }
+
+            if P::gpu_culled()
+                && let Some(culling) = culling
+                && let Some(indirect_buffer) = culling.indirect_buffer()
+                && let Some(culling_buffer) = &culling_buffer
+            {
+                // the meshes were already culled by the compute shader
+                P::count_gpu_stats(&mut stats, &culling.statistics);
+
+                for batch in &culling_buffer.batches {
+                    render_pass.set_bind_group(2, &batch.bind_group, &[]);
+                    render_pass.multi_draw_indirect(
+                        indirect_buffer,
+                        wgpu::BufferAddress::from(batch.draws.start)
+                            * size_of::<DrawIndirectArgs>() as wgpu::BufferAddress,
+                        batch.draws.len().try_into().unwrap(),
+                    );
+                }
+
+                render_pass.exit_span(span);
+                return;
+            }
+
             let camera_frustrum = Frustrum {
                 matrix: camera_projection.to_matrix()
                     * camera_transform.isometry.inverse().to_homogeneous(),
             };
 
+            // meshes in the mesh arena share their bind group
+            let mut current_bind_group = None;
+
             for (mesh, instance_id, cull_aabb) in &items {
                 let cull = cull_aabb
                     .is_some_and(|cull_aabb| !camera_frustrum.intersect_aabb(&cull_aabb.aabb));
@@ -715,7 +1108,10 @@ where
                 P::count_stats(&mut stats, cull, &mesh.span);
 
                 if !cull {
-                    render_pass.set_bind_group(2, &mesh.bind_group, &[]);
+                    if current_bind_group != Some(&mesh.bind_group) {
+                        render_pass.set_bind_group(2, &mesh.bind_group, &[]);
+                        current_bind_group = Some(&mesh.bind_group);
+                    }
                     render_pass.draw(P::vertices(&mesh.span), instance_id.0..(instance_id.0 + 1));
                 }
             }
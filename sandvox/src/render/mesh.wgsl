@@ -3,7 +3,11 @@ const PI: f32 = 3.141592653589793;
 struct MainPassUniform {
     camera: Camera,
     time: f32,
-    // padding: 12 bytes
+    exposure: f32,
+    // padding: 8 bytes
+    light: Light,
+    fog: Fog,
+    overlay: Overlay,
 }
 
 struct Camera {
@@ -14,6 +18,29 @@ struct Camera {
     position: vec4f,
 }
 
+struct Light {
+    sun_direction: vec4f,
+    sun_color: vec4f,
+    moon_direction: vec4f,
+    moon_color: vec4f,
+    ambient_color: vec4f,
+}
+
+struct Fog {
+    // alpha is 0 if there is no fog
+    color: vec4f,
+    start: f32,
+    end: f32,
+    // padding: 8 bytes
+}
+
+struct Overlay {
+    // alpha is 0 if there is no overlay
+    color: vec4f,
+    distortion: f32,
+    // padding: 12 bytes
+}
+
 @group(0)
 @binding(0)
 var<uniform> main_pass_uniform: MainPassUniform;
@@ -42,19 +69,30 @@ struct Vertex {
     normal: vec4f,
     uv: vec2f,
     texture_id: u32,
-    // padding: 4 bytes
+    // 4 x unorm8
+    joint_weights: u32,
+    // 4 x u16
+    joints: vec2u,
+    // padding: 8 bytes
 }
 
 struct Instance {
     model_matrix: mat4x4f,
     vertex_buffer_offset: u32,
-    // padding: 12 bytes
+    // 0xffffffff if the mesh isn't skinned
+    joint_offset: u32,
+    // padding: 8 bytes
+    normal_scale: vec4f,
 }
 
 @group(1)
 @binding(0)
 var<storage, read> instance_buffer: array<Instance>;
 
+@group(1)
+@binding(1)
+var<storage, read> joint_buffer: array<mat4x4f>;
+
 @group(2)
 @binding(0)
 var<storage, read> vertex_buffer: array<Vertex>;
@@ -74,10 +112,11 @@ fn mesh_shaded_vertex(
     let resolved_vertex_index = index_buffer[vertex_index] + instance.vertex_buffer_offset;
     let vertex = vertex_buffer[resolved_vertex_index];
 
-    let world_position = instance.model_matrix * vertex.position;
-    let normal = instance.model_matrix * vertex.normal;
+    let skin_matrix = mesh_skin_matrix(instance, vertex);
+    let world_position = instance.model_matrix * skin_matrix * vertex.position;
+    let normal = instance.model_matrix * (instance.normal_scale * (skin_matrix * vertex.normal));
 
-    let position = main_pass_uniform.camera.projection * main_pass_uniform.camera.view * world_position;
+    let position = mesh_clip_position(world_position);
 
     return ShadedOutput(
         position,
@@ -112,12 +151,7 @@ struct ShadedOutput {
 fn mesh_shaded_fragment(input: ShadedOutput) -> @location(0) vec4f {
     var color: vec4f;
 
-    // todo: use skybox planets as light sources
-    let light_color = vec3f(1);
-    let light_dir = normalize(vec3f(0.5, 1, 0.5));
-
-    let normal = normalize(input.normal.xyz);
-    let brightness = 0.5 * dot(normal, light_dir) + 0.5;
+    let light_color = mesh_light_color(input.normal);
 
     // color sampled from texture
     if input.texture_id < arrayLength(&atlas_data) {
@@ -129,9 +163,24 @@ fn mesh_shaded_fragment(input: ShadedOutput) -> @location(0) vec4f {
         color = vec4f(0.8, 0.8, 0.8, 1);
     }
 
-    color = vec4f(color.rgb * brightness * light_color, 1);
+    color = vec4f(color.rgb * light_color, 1);
+
+    return mesh_expose(mesh_apply_fog(color, input.world_position));
+}
 
-    return color;
+fn mesh_expose(color: vec4f) -> vec4f {
+    return vec4f(color.rgb * main_pass_uniform.exposure, color.a);
+}
+
+fn mesh_apply_fog(color: vec4f, world_position: vec4f) -> vec4f {
+    let fog = main_pass_uniform.fog;
+    if fog.color.a == 0 {
+        return color;
+    }
+
+    let distance = length(world_position.xyz - main_pass_uniform.camera.position.xyz);
+    let amount = saturate((distance - fog.start) / max(fog.end - fog.start, 0.001));
+    return vec4f(mix(color.rgb, fog.color.rgb, amount), color.a);
 }
 
 
@@ -139,51 +188,56 @@ struct WireframeOutput {
     @builtin(position)
     @invariant
     position: vec4f,
+
+    @location(0)
+    barycentric: vec3f,
 }
 
+// width of the wireframe lines in pixels
+const WIREFRAME_LINE_WIDTH: f32 = 1.5;
+
 @vertex
 fn mesh_wireframe_vertex(
     @builtin(vertex_index) vertex_index: u32,
     @builtin(instance_index) instance_index: u32,
 ) -> WireframeOutput {
-    /*
-        0----2
-        |   /
-        | /
-        1
-
-        shader will be called with vertex_index = [0, 1, 2, 3, 4, 5] (2 * number of vertices)
-
-        vertex_index | draw vertex
-                   0 | 0
-                   1 | 1
-                   2 | 1
-                   3 | 2
-                   4 | 2
-                   5 | 0
-
-        `(i + 1) % 6 / 2` gives the vertex indices for lines of a single triangle.
-        `(i / 6) * 3` gives the vertex index for the first index of a triangle.
-    */
-
     let instance = instance_buffer[instance_index];
 
-    var line_vertex_index = ((vertex_index + 1) % 6) / 2 + (vertex_index / 6) * 3;
-    let resolved_vertex_index = index_buffer[line_vertex_index] + instance.vertex_buffer_offset;
+    let resolved_vertex_index = index_buffer[vertex_index] + instance.vertex_buffer_offset;
     let vertex = vertex_buffer[resolved_vertex_index];
 
-    let world_position = instance.model_matrix * vertex.position;
-    let position = main_pass_uniform.camera.projection * main_pass_uniform.camera.view * world_position;
+    let world_position = mesh_model_matrix(instance, vertex) * vertex.position;
+    let position = mesh_clip_position(world_position);
+
+    // the index buffer is pulled per vertex, so every triangle has its own 3 vertices, which
+    // get one barycentric coordinate each.
+    let corner = vertex_index % 3;
+    let barycentric = vec3f(
+        select(0.0, 1.0, corner == 0),
+        select(0.0, 1.0, corner == 1),
+        select(0.0, 1.0, corner == 2),
+    );
 
     return WireframeOutput(
         position,
+        barycentric,
     );
 }
 
 @fragment
 fn mesh_wireframe_fragment(input: WireframeOutput) -> @location(0) vec4f {
-    const plum: vec4f = vec4f(0.86, 0.62, 0.86, 1);
-    return plum;
+    const plum: vec3f = vec3f(0.86, 0.62, 0.86);
+
+    // a fragment is on an edge if one of its barycentric coordinates is close to 0. the
+    // derivatives scale this to pixels and smooth the edge a bit.
+    let distance = input.barycentric / fwidth(input.barycentric);
+    let edge = 1 - smoothstep(0.0, WIREFRAME_LINE_WIDTH, min(distance.x, min(distance.y, distance.z)));
+
+    if edge <= 0 {
+        discard;
+    }
+
+    return vec4f(plum, edge);
 }
 
 
@@ -199,8 +253,8 @@ fn mesh_depth_prepass_vertex(
     let resolved_vertex_index = index_buffer[vertex_index] + instance.vertex_buffer_offset;
     let vertex = vertex_buffer[resolved_vertex_index];
 
-    let world_position = instance.model_matrix * vertex.position;
-    let position = main_pass_uniform.camera.projection * main_pass_uniform.camera.view * world_position;
+    let world_position = mesh_model_matrix(instance, vertex) * vertex.position;
+    let position = mesh_clip_position(world_position);
 
     return DepthPrepassOutput(
         position,
@@ -217,6 +271,59 @@ struct DepthPrepassOutput {
 
 
 
+// model matrix of the instance, blended with the vertex's joints if the mesh is skinned.
+// projects a world position into clip space. with a distorting overlay, e.g. under water, the
+// scene wobbles a bit. all vertex shaders must use this, so that the depth prepass matches.
+fn mesh_clip_position(world_position: vec4f) -> vec4f {
+    var position = main_pass_uniform.camera.projection * main_pass_uniform.camera.view * world_position;
+
+    let distortion = main_pass_uniform.overlay.distortion;
+    if distortion > 0 {
+        let phase = 2 * main_pass_uniform.time + 0.5 * (world_position.x + world_position.y + world_position.z);
+        position.x += distortion * sin(phase) * position.w;
+        position.y += distortion * cos(1.3 * phase) * position.w;
+    }
+
+    return position;
+}
+
+fn mesh_model_matrix(instance: Instance, vertex: Vertex) -> mat4x4f {
+    return instance.model_matrix * mesh_skin_matrix(instance, vertex);
+}
+
+// the vertex's joint matrices blended by their weights, or the identity if the mesh isn't skinned.
+fn mesh_skin_matrix(instance: Instance, vertex: Vertex) -> mat4x4f {
+    if instance.joint_offset == 0xffffffffu {
+        return mat4x4f(
+            1, 0, 0, 0,
+            0, 1, 0, 0,
+            0, 0, 1, 0,
+            0, 0, 0, 1,
+        );
+    }
+
+    let weights = unpack4x8unorm(vertex.joint_weights);
+    let joints = vec4u(
+        vertex.joints.x & 0xffffu,
+        vertex.joints.x >> 16u,
+        vertex.joints.y & 0xffffu,
+        vertex.joints.y >> 16u,
+    ) + vec4u(instance.joint_offset);
+
+    return weights.x * joint_buffer[joints.x]
+        + weights.y * joint_buffer[joints.y]
+        + weights.z * joint_buffer[joints.z]
+        + weights.w * joint_buffer[joints.w];
+}
+
+fn mesh_light_color(normal: vec4f) -> vec3f {
+    let light = main_pass_uniform.light;
+    let n = normalize(normal.xyz);
+    return light.ambient_color.rgb
+        + max(dot(n, light.sun_direction.xyz), 0) * light.sun_color.rgb
+        + max(dot(n, light.moon_direction.xyz), 0) * light.moon_color.rgb;
+}
+
 fn atlas_map_uv(texture_id: u32, uv: vec2f) -> vec2f {
     let entry = atlas_data[texture_id];
     return entry.uv_offset + (uv % vec2f(1)) * entry.uv_size;
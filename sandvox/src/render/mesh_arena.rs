@@ -0,0 +1,377 @@
+use std::{
+    ops::Range,
+    sync::Arc,
+};
+
+use bevy_ecs::resource::Resource;
+use parking_lot::Mutex;
+
+use crate::render::mesh::{
+    Mesh,
+    MeshBufferSpan,
+    Vertex,
+};
+
+/// Number of vertices in a regular arena page (12 MiB).
+const PAGE_VERTICES: u32 = 0x40_000;
+
+/// Number of indices in a regular arena page (1.5 MiB).
+///
+/// Meshes made of quads have 6 indices per 4 vertices.
+const PAGE_INDICES: u32 = PAGE_VERTICES / 2 * 3;
+
+/// Sub-allocates mesh buffers from large shared buffers.
+///
+/// Meshes in the same page share their vertex and index buffer, and their bind
+/// group. The shader already adds the vertex offset of the
+/// [`MeshBufferSpan`] to the indices, so the indices of a mesh stay relative
+/// to its first vertex.
+///
+/// Meshes that don't fit into a regular page get a page of their own.
+///
+/// This is cheap to clone, and can be used from background tasks.
+#[derive(Clone, Debug, Resource)]
+pub struct MeshArena {
+    device: wgpu::Device,
+    bind_group_layout: wgpu::BindGroupLayout,
+    state: Arc<Mutex<MeshArenaState>>,
+}
+
+impl MeshArena {
+    pub fn new(device: wgpu::Device, bind_group_layout: wgpu::BindGroupLayout) -> Self {
+        Self {
+            device,
+            bind_group_layout,
+            state: Default::default(),
+        }
+    }
+
+    /// Allocates space for a mesh and writes its data.
+    ///
+    /// The data is written with [`wgpu::Queue::write_buffer`], so it becomes
+    /// visible with the next submission.
+    pub fn insert(&self, queue: &wgpu::Queue, vertices: &[Vertex], indices: &[u32]) -> Mesh {
+        let num_vertices = u32::try_from(vertices.len()).unwrap();
+        let num_indices = u32::try_from(indices.len()).unwrap();
+        assert!(num_vertices > 0 && num_indices > 0);
+
+        let mut state = self.state.lock();
+
+        let (page_index, vertex_span, index_span) = state
+            .pages
+            .iter_mut()
+            .enumerate()
+            .find_map(|(page_index, page)| {
+                let page = page.as_mut()?;
+                let (vertex_span, index_span) = page.allocate(num_vertices, num_indices)?;
+                Some((page_index, vertex_span, index_span))
+            })
+            .unwrap_or_else(|| {
+                let mut page = MeshArenaPage::new(
+                    &self.device,
+                    &self.bind_group_layout,
+                    num_vertices.max(PAGE_VERTICES),
+                    num_indices.max(PAGE_INDICES),
+                );
+                let (vertex_span, index_span) = page
+                    .allocate(num_vertices, num_indices)
+                    .expect("new page too small");
+                let page_index = state.insert_page(page);
+                (page_index, vertex_span, index_span)
+            });
+
+        let page = state.pages[page_index].as_ref().unwrap();
+
+        queue.write_buffer(
+            &page.vertex_buffer,
+            u64::from(vertex_span.start) * size_of::<Vertex>() as u64,
+            bytemuck::cast_slice(vertices),
+        );
+        queue.write_buffer(
+            &page.index_buffer,
+            u64::from(index_span.start) * size_of::<u32>() as u64,
+            bytemuck::cast_slice(indices),
+        );
+
+        Mesh {
+            vertex_buffer: page.vertex_buffer.clone(),
+            index_buffer: page.index_buffer.clone(),
+            bind_group: page.bind_group.clone(),
+            span: MeshBufferSpan {
+                vertex_buffer_offset: vertex_span.start,
+                num_vertices,
+                index_buffer_offset: index_span.start,
+                num_indices,
+            },
+            allocation: Some(Arc::new(MeshArenaAllocation {
+                state: self.state.clone(),
+                page_index,
+                vertex_span,
+                index_span,
+            })),
+        }
+    }
+
+    /// Whether both are the same arena.
+    pub fn ptr_eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.state, &other.state)
+    }
+
+    pub fn statistics(&self) -> MeshArenaStatistics {
+        let state = self.state.lock();
+
+        let mut statistics = MeshArenaStatistics::default();
+        for page in state.pages.iter().flatten() {
+            statistics.num_pages += 1;
+            statistics.num_meshes += page.num_allocations;
+            statistics.vertex_capacity += page.vertices.capacity as usize;
+            statistics.vertices_used += page.vertices.used() as usize;
+            statistics.index_capacity += page.indices.capacity as usize;
+            statistics.indices_used += page.indices.used() as usize;
+        }
+
+        statistics
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct MeshArenaStatistics {
+    pub num_pages: usize,
+    pub num_meshes: usize,
+    pub vertex_capacity: usize,
+    pub vertices_used: usize,
+    pub index_capacity: usize,
+    pub indices_used: usize,
+}
+
+/// Keeps the space of a mesh in the [`MeshArena`] allocated. The space is
+/// freed when this is dropped.
+#[derive(Debug)]
+pub struct MeshArenaAllocation {
+    state: Arc<Mutex<MeshArenaState>>,
+    page_index: usize,
+    vertex_span: Range<u32>,
+    index_span: Range<u32>,
+}
+
+impl Drop for MeshArenaAllocation {
+    fn drop(&mut self) {
+        let mut state = self.state.lock();
+        state.free(
+            self.page_index,
+            self.vertex_span.clone(),
+            self.index_span.clone(),
+        );
+    }
+}
+
+#[derive(Debug, Default)]
+struct MeshArenaState {
+    pages: Vec<Option<MeshArenaPage>>,
+}
+
+impl MeshArenaState {
+    fn insert_page(&mut self, page: MeshArenaPage) -> usize {
+        if let Some(page_index) = self.pages.iter().position(Option::is_none) {
+            self.pages[page_index] = Some(page);
+            page_index
+        }
+        else {
+            self.pages.push(Some(page));
+            self.pages.len() - 1
+        }
+    }
+
+    fn free(&mut self, page_index: usize, vertex_span: Range<u32>, index_span: Range<u32>) {
+        let page = self.pages[page_index]
+            .as_mut()
+            .expect("allocation in removed page");
+        page.vertices.free(vertex_span);
+        page.indices.free(index_span);
+        page.num_allocations -= 1;
+
+        if page.num_allocations == 0 {
+            // oversized pages are only used for one mesh. of the regular pages we keep
+            // one empty page around, so that meshes that are removed and inserted again
+            // don't allocate a new page every time.
+            let is_oversized =
+                page.vertices.capacity > PAGE_VERTICES || page.indices.capacity > PAGE_INDICES;
+            let other_empty_page = self.pages.iter().enumerate().any(|(other_index, page)| {
+                other_index != page_index
+                    && page.as_ref().is_some_and(|page| page.num_allocations == 0)
+            });
+
+            if is_oversized || other_empty_page {
+                self.pages[page_index] = None;
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+struct MeshArenaPage {
+    vertex_buffer: wgpu::Buffer,
+    index_buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+    vertices: SpanAllocator,
+    indices: SpanAllocator,
+    num_allocations: usize,
+}
+
+impl MeshArenaPage {
+    fn new(
+        device: &wgpu::Device,
+        bind_group_layout: &wgpu::BindGroupLayout,
+        vertex_capacity: u32,
+        index_capacity: u32,
+    ) -> Self {
+        tracing::debug!(
+            vertex_capacity,
+            index_capacity,
+            "allocating mesh arena page"
+        );
+
+        let vertex_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("mesh arena vertices"),
+            size: u64::from(vertex_capacity) * size_of::<Vertex>() as u64,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let index_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("mesh arena indices"),
+            size: u64::from(index_capacity) * size_of::<u32>() as u64,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("mesh arena"),
+            layout: bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: vertex_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: index_buffer.as_entire_binding(),
+                },
+            ],
+        });
+
+        Self {
+            vertex_buffer,
+            index_buffer,
+            bind_group,
+            vertices: SpanAllocator::new(vertex_capacity),
+            indices: SpanAllocator::new(index_capacity),
+            num_allocations: 0,
+        }
+    }
+
+    fn allocate(
+        &mut self,
+        num_vertices: u32,
+        num_indices: u32,
+    ) -> Option<(Range<u32>, Range<u32>)> {
+        let vertex_span = self.vertices.allocate(num_vertices)?;
+        let Some(index_span) = self.indices.allocate(num_indices)
+        else {
+            self.vertices.free(vertex_span);
+            return None;
+        };
+
+        self.num_allocations += 1;
+        Some((vertex_span, index_span))
+    }
+}
+
+/// First-fit allocator for ranges of elements.
+#[derive(Clone, Debug)]
+struct SpanAllocator {
+    capacity: u32,
+
+    /// Free ranges, sorted and never adjacent to each other.
+    free: Vec<Range<u32>>,
+}
+
+impl SpanAllocator {
+    fn new(capacity: u32) -> Self {
+        Self {
+            capacity,
+            free: std::iter::once(0..capacity).collect(),
+        }
+    }
+
+    fn allocate(&mut self, size: u32) -> Option<Range<u32>> {
+        let index = self
+            .free
+            .iter()
+            .position(|range| range.end - range.start >= size)?;
+
+        let range = &mut self.free[index];
+        let allocated = range.start..(range.start + size);
+        range.start += size;
+
+        if range.start == range.end {
+            self.free.remove(index);
+        }
+
+        Some(allocated)
+    }
+
+    fn free(&mut self, range: Range<u32>) {
+        let index = self.free.partition_point(|free| free.end <= range.start);
+
+        let merge_previous = index > 0 && self.free[index - 1].end == range.start;
+        let merge_next = index < self.free.len() && self.free[index].start == range.end;
+
+        match (merge_previous, merge_next) {
+            (true, true) => {
+                self.free[index - 1].end = self.free[index].end;
+                self.free.remove(index);
+            }
+            (true, false) => {
+                self.free[index - 1].end = range.end;
+            }
+            (false, true) => {
+                self.free[index].start = range.start;
+            }
+            (false, false) => {
+                self.free.insert(index, range);
+            }
+        }
+    }
+
+    fn used(&self) -> u32 {
+        self.capacity
+            - self
+                .free
+                .iter()
+                .map(|range| range.end - range.start)
+                .sum::<u32>()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::render::mesh_arena::SpanAllocator;
+
+    #[test]
+    fn it_reuses_and_merges_freed_spans() {
+        let mut allocator = SpanAllocator::new(10);
+
+        let a = allocator.allocate(4).unwrap();
+        let b = allocator.allocate(4).unwrap();
+        assert_eq!(b, 4..8);
+        assert!(allocator.allocate(4).is_none());
+
+        allocator.free(a);
+        allocator.free(b);
+        assert_eq!(allocator.free.len(), 1);
+        assert_eq!(allocator.used(), 0);
+
+        assert_eq!(allocator.allocate(10), Some(0..10));
+    }
+}
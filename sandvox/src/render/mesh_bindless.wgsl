@@ -0,0 +1,37 @@
+// Appended to `mesh.wgsl` if the device supports texture binding arrays.
+
+struct BindlessEntry {
+    uv_offset: vec2f,
+    uv_size: vec2f,
+    texture_index: u32,
+    // padding: 4 bytes
+}
+
+@group(3)
+@binding(0)
+var bindless_textures: binding_array<texture_2d<f32>>;
+
+@group(3)
+@binding(1)
+var<storage, read> bindless_data: array<BindlessEntry>;
+
+@fragment
+fn mesh_bindless_fragment(input: ShadedOutput) -> @location(0) vec4f {
+    var color: vec4f;
+
+    let light_color = mesh_light_color(input.normal);
+
+    // color sampled from the block's own texture
+    if input.texture_id < arrayLength(&bindless_data) {
+        let entry = bindless_data[input.texture_id];
+        let uv = entry.uv_offset + (input.uv % vec2f(1)) * entry.uv_size;
+        color = textureSample(bindless_textures[entry.texture_index], default_sampler, uv);
+    }
+    else {
+        color = vec4f(0.8, 0.8, 0.8, 1);
+    }
+
+    color = vec4f(color.rgb * light_color, 1);
+
+    return mesh_expose(mesh_apply_fog(color, input.world_position));
+}
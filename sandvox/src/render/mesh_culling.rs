@@ -0,0 +1,530 @@
+//! Frustum culling of meshes on the GPU.
+//!
+//! Instead of testing the [`FrustrumCulled`] AABB of every mesh on the CPU, a
+//! compute shader tests all mesh instances for each camera and writes indirect
+//! draw arguments. The opaque and depth prepass phases then draw all meshes
+//! that share a bind group (e.g. all chunks in the [`MeshArena`]) with a single
+//! `multi_draw_indirect`, so the CPU cost doesn't grow with the number of
+//! loaded chunks.
+//!
+//! This needs compute shaders, indirect execution and
+//! [`INDIRECT_FIRST_INSTANCE`][wgpu::Features::INDIRECT_FIRST_INSTANCE]. If
+//! those aren't supported, or [`RenderConfig::gpu_culling`] is disabled, meshes
+//! are culled on the CPU.
+//!
+//! [`MeshArena`]: crate::render::mesh_arena::MeshArena
+
+use std::{
+    collections::HashMap,
+    ops::Range,
+};
+
+use bevy_ecs::{
+    component::Component,
+    entity::Entity,
+    query::{
+        Changed,
+        Or,
+        With,
+        Without,
+    },
+    resource::Resource,
+    schedule::{
+        IntoScheduleConfigs,
+        SystemCondition,
+        common_conditions::{
+            any_component_removed,
+            any_match_filter,
+            resource_exists,
+        },
+    },
+    system::{
+        Commands,
+        Local,
+        Populated,
+        Query,
+        Res,
+        ResMut,
+    },
+};
+use bytemuck::{
+    Pod,
+    Zeroable,
+};
+use color_eyre::eyre::Error;
+use nalgebra::{
+    Matrix4,
+    Point3,
+};
+use wgpu::util::DrawIndirectArgs;
+
+use crate::{
+    ecs::{
+        plugin::{
+            Dependencies,
+            Plugin,
+            WorldBuilder,
+        },
+        schedule,
+        transform::GlobalTransform,
+    },
+    render::{
+        RenderConfig,
+        RenderSystems,
+        camera::{
+            CameraProjection,
+            FrustrumCulled,
+        },
+        mesh::{
+            InstanceId,
+            Mesh,
+            MeshPipeline,
+            MeshPlugin,
+            MeshSystems,
+        },
+        pass::{
+            context::RenderContext,
+            main_pass::MainPassSystems,
+        },
+        readback::{
+            Readback,
+            ReadbackPool,
+        },
+        remove_on_gpu_setup,
+        staging::Staging,
+    },
+    wgpu::{
+        WgpuContext,
+        WgpuContextBuilder,
+        WgpuSystems,
+        buffer::{
+            TypedArrayBuffer,
+            WriteStaging,
+        },
+        compute::{
+            ComputePipeline,
+            ComputePipelineBuilder,
+        },
+    },
+};
+
+/// Features that are needed for GPU culling, in addition to the downlevel
+/// flags in [`GPU_CULLING_DOWNLEVEL_FLAGS`].
+const GPU_CULLING_FEATURES: wgpu::Features = wgpu::Features::INDIRECT_FIRST_INSTANCE;
+
+const GPU_CULLING_DOWNLEVEL_FLAGS: wgpu::DownlevelFlags =
+    wgpu::DownlevelFlags::COMPUTE_SHADERS.union(wgpu::DownlevelFlags::INDIRECT_EXECUTION);
+
+const WORKGROUP_SIZE: u32 = 64;
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct MeshCullingPlugin;
+
+impl Plugin for MeshCullingPlugin {
+    fn dependencies(&self, dependencies: &mut Dependencies) -> Result<(), Error> {
+        dependencies.require_plugin::<MeshPlugin>();
+        Ok(())
+    }
+
+    fn setup(&self, builder: &mut WorldBuilder) -> Result<(), Error> {
+        builder
+            .add_systems(
+                schedule::GpuSetup,
+                (
+                    request_gpu_culling_features
+                        .in_set(WgpuSystems::RequestFeatures)
+                        .run_if(resource_exists::<WgpuContextBuilder>),
+                    create_culling_pipeline.in_set(RenderSystems::Setup),
+                    remove_on_gpu_setup::<MeshCulling>,
+                ),
+            )
+            .add_systems(
+                schedule::Render,
+                (
+                    (
+                        update_culling_cameras,
+                        update_cull_buffer
+                            .after(MeshSystems::UpdateInstances)
+                            .run_if(
+                                any_match_filter::<(
+                                    With<Mesh>,
+                                    Or<(
+                                        Changed<Mesh>,
+                                        Changed<InstanceId>,
+                                        Changed<FrustrumCulled>,
+                                    )>,
+                                )>
+                                    .or(any_component_removed::<Mesh>),
+                            ),
+                    )
+                        .in_set(RenderSystems::BeginFrame),
+                    cull_meshes
+                        .in_set(RenderSystems::Render)
+                        .before(MainPassSystems::Render),
+                )
+                    .run_if(resource_exists::<MeshCullingPipeline>),
+            );
+
+        Ok(())
+    }
+}
+
+/// Culls the meshes for this camera on the GPU.
+///
+/// This is added to cameras automatically if GPU culling is supported and
+/// enabled.
+#[derive(Debug, Component)]
+pub struct MeshCulling {
+    uniform_buffer: wgpu::Buffer,
+    statistics_buffer: wgpu::Buffer,
+    indirect: Option<IndirectBuffer>,
+    readback: Option<Readback<Vec<u8>>>,
+
+    /// Statistics of the most recent culling pass that was read back.
+    ///
+    /// These lag a few frames behind.
+    pub statistics: MeshCullingStatistics,
+}
+
+impl MeshCulling {
+    /// The indirect draw arguments, if the meshes were culled for this
+    /// camera.
+    pub(super) fn indirect_buffer(&self) -> Option<&wgpu::Buffer> {
+        self.indirect.as_ref().map(|indirect| &indirect.buffer)
+    }
+}
+
+#[derive(Debug)]
+struct IndirectBuffer {
+    buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+
+    /// Number of draws that fit into the buffer.
+    capacity: usize,
+
+    /// [`MeshCullingBuffer::version`] that the bind group was created for.
+    version: u64,
+}
+
+#[derive(Clone, Copy, Debug, Default, Pod, Zeroable)]
+#[repr(C)]
+pub struct MeshCullingStatistics {
+    pub num_rendered: u32,
+    pub num_culled: u32,
+    pub num_vertices: u32,
+    _padding: u32,
+}
+
+#[derive(Debug, Resource)]
+struct MeshCullingPipeline {
+    pipeline: ComputePipeline,
+}
+
+/// All mesh instances that are culled, and how they're batched into draw
+/// calls.
+#[derive(Debug, Resource)]
+pub(super) struct MeshCullingBuffer {
+    entries: TypedArrayBuffer<CullEntry>,
+    num_entries: u32,
+
+    /// Ranges of indirect draws that share a mesh bind group.
+    pub(super) batches: Vec<DrawBatch>,
+
+    /// Incremented whenever the entry buffer is reallocated.
+    version: u64,
+}
+
+#[derive(Clone, Debug)]
+pub(super) struct DrawBatch {
+    pub(super) bind_group: wgpu::BindGroup,
+    pub(super) draws: Range<u32>,
+}
+
+#[derive(Clone, Copy, Debug, Pod, Zeroable)]
+#[repr(C)]
+struct CullEntry {
+    aabb_min: Point3<f32>,
+    flags: u32,
+    aabb_max: Point3<f32>,
+    instance_id: u32,
+    first_vertex: u32,
+    vertex_count: u32,
+    _padding: [u32; 2],
+}
+
+/// The entry has an AABB that is tested against the frustum.
+const FLAG_FRUSTUM_CULLED: u32 = 1;
+
+#[derive(Clone, Copy, Debug, Pod, Zeroable)]
+#[repr(C)]
+struct CullingUniform {
+    view_projection: Matrix4<f32>,
+    num_entries: u32,
+    _padding: [u32; 3],
+}
+
+fn request_gpu_culling_features(
+    config: Res<RenderConfig>,
+    mut context_builder: ResMut<WgpuContextBuilder>,
+) {
+    if config.gpu_culling
+        && context_builder
+            .try_request_features(GPU_CULLING_FEATURES)
+            .is_err()
+    {
+        tracing::info!("Indirect first instance not supported. Culling meshes on the CPU.");
+    }
+}
+
+fn create_culling_pipeline(wgpu: Res<WgpuContext>, mut commands: Commands) {
+    let downlevel_flags = wgpu.adapter.get_downlevel_capabilities().flags;
+    if !wgpu.device.features().contains(GPU_CULLING_FEATURES)
+        || !downlevel_flags.contains(GPU_CULLING_DOWNLEVEL_FLAGS)
+    {
+        // the device might have been recreated without the features, and the old
+        // pipeline and buffer belong to the lost device.
+        commands.remove_resource::<MeshCullingPipeline>();
+        commands.remove_resource::<MeshCullingBuffer>();
+        return;
+    }
+
+    let shader = wgpu
+        .device
+        .create_shader_module(wgpu::include_wgsl!("mesh_culling.wgsl"));
+
+    let pipeline = ComputePipelineBuilder::new("mesh culling", &shader, "cull_meshes")
+        .uniform::<CullingUniform>()
+        .storage::<CullEntry>(true)
+        .storage::<DrawIndirectArgs>(false)
+        .storage::<MeshCullingStatistics>(false)
+        .build(&wgpu.device);
+
+    tracing::debug!("culling meshes on the GPU");
+
+    commands.insert_resource(MeshCullingPipeline { pipeline });
+    commands.insert_resource(MeshCullingBuffer {
+        entries: TypedArrayBuffer::new(
+            wgpu.device.clone(),
+            "mesh culling entries",
+            wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+        ),
+        num_entries: 0,
+        batches: vec![],
+        version: 0,
+    });
+}
+
+/// Adds [`MeshCulling`] to cameras that render meshes, or removes it if GPU
+/// culling was disabled.
+fn update_culling_cameras(
+    wgpu: Res<WgpuContext>,
+    config: Res<RenderConfig>,
+    without_culling: Query<Entity, (With<MeshPipeline>, Without<MeshCulling>)>,
+    with_culling: Query<Entity, With<MeshCulling>>,
+    mut commands: Commands,
+) {
+    if config.gpu_culling {
+        for entity in &without_culling {
+            let uniform_buffer = wgpu.device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("mesh culling uniform"),
+                size: size_of::<CullingUniform>() as wgpu::BufferAddress,
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            });
+
+            let statistics_buffer = wgpu.device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("mesh culling statistics"),
+                size: size_of::<MeshCullingStatistics>() as wgpu::BufferAddress,
+                usage: wgpu::BufferUsages::STORAGE
+                    | wgpu::BufferUsages::COPY_SRC
+                    | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            });
+
+            commands.entity(entity).insert(MeshCulling {
+                uniform_buffer,
+                statistics_buffer,
+                indirect: None,
+                readback: None,
+                statistics: Default::default(),
+            });
+        }
+    }
+    else {
+        for entity in &with_culling {
+            commands.entity(entity).remove::<MeshCulling>();
+        }
+    }
+}
+
+/// Writes the culling entries of all meshes, grouped by their bind group.
+#[profiling::function]
+fn update_cull_buffer(
+    mut culling_buffer: ResMut<MeshCullingBuffer>,
+    meshes: Query<(&Mesh, &InstanceId, Option<&FrustrumCulled>)>,
+    mut batches: Local<HashMap<wgpu::BindGroup, Vec<CullEntry>>>,
+    mut entries: Local<Vec<CullEntry>>,
+    mut staging: ResMut<Staging>,
+) {
+    assert!(entries.is_empty());
+
+    for (mesh, instance_id, frustrum_culled) in &meshes {
+        let mut entry = CullEntry {
+            instance_id: instance_id.0,
+            first_vertex: mesh.span.index_buffer_offset,
+            vertex_count: mesh.span.num_indices,
+            ..Zeroable::zeroed()
+        };
+
+        if let Some(frustrum_culled) = frustrum_culled {
+            entry.flags |= FLAG_FRUSTUM_CULLED;
+            entry.aabb_min = frustrum_culled.aabb.min;
+            entry.aabb_max = frustrum_culled.aabb.max;
+        }
+
+        batches
+            .entry(mesh.bind_group.clone())
+            .or_default()
+            .push(entry);
+    }
+
+    let culling_buffer = &mut *culling_buffer;
+    culling_buffer.batches.clear();
+
+    for (bind_group, batch) in batches.drain() {
+        let start = entries.len().try_into().unwrap();
+        entries.extend(batch);
+        let end = entries.len().try_into().unwrap();
+
+        culling_buffer.batches.push(DrawBatch {
+            bind_group,
+            draws: start..end,
+        });
+    }
+
+    culling_buffer.num_entries = entries.len().try_into().unwrap();
+
+    if !entries.is_empty()
+        && culling_buffer
+            .entries
+            .write_all(&entries, |_| {}, &mut *staging)
+    {
+        culling_buffer.version += 1;
+    }
+
+    entries.clear();
+}
+
+/// Runs the culling compute shader for every camera.
+#[profiling::function]
+fn cull_meshes(
+    wgpu: Res<WgpuContext>,
+    pipeline: Res<MeshCullingPipeline>,
+    culling_buffer: Res<MeshCullingBuffer>,
+    cameras: Populated<(&mut MeshCulling, &CameraProjection, &GlobalTransform)>,
+    readback_pool: Res<ReadbackPool>,
+    mut render_context: RenderContext,
+    mut staging: ResMut<Staging>,
+) {
+    for (mut culling, camera_projection, camera_transform) in cameras {
+        let culling = &mut *culling;
+
+        if let Some(readback) = &mut culling.readback
+            && let Some(result) = readback.try_take()
+        {
+            culling.readback = None;
+
+            match result {
+                Ok(data) => culling.statistics = bytemuck::pod_read_unaligned(&data),
+                Err(error) => tracing::warn!("could not read back culling statistics: {error}"),
+            }
+        }
+
+        if culling_buffer.num_entries == 0 {
+            culling.statistics = Default::default();
+            continue;
+        }
+
+        let num_entries = usize::try_from(culling_buffer.num_entries).unwrap();
+
+        // (re)create the indirect buffer if it's too small, or the bind group if the
+        // entry buffer was reallocated.
+        if culling.indirect.as_ref().is_none_or(|indirect| {
+            indirect.capacity < num_entries || indirect.version != culling_buffer.version
+        }) {
+            let capacity = match &culling.indirect {
+                Some(indirect) if indirect.capacity >= num_entries => indirect.capacity,
+                _ => num_entries.next_power_of_two(),
+            };
+
+            let buffer = match culling.indirect.take() {
+                Some(indirect) if indirect.capacity == capacity => indirect.buffer,
+                _ => {
+                    wgpu.device.create_buffer(&wgpu::BufferDescriptor {
+                        label: Some("mesh culling indirect"),
+                        size: (capacity * size_of::<DrawIndirectArgs>()) as wgpu::BufferAddress,
+                        usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::INDIRECT,
+                        mapped_at_creation: false,
+                    })
+                }
+            };
+
+            let bind_group = pipeline.pipeline.bind_group(
+                &wgpu.device,
+                [
+                    culling.uniform_buffer.as_entire_binding(),
+                    culling_buffer.entries.as_entire_binding(),
+                    buffer.as_entire_binding(),
+                    culling.statistics_buffer.as_entire_binding(),
+                ],
+            );
+
+            culling.indirect = Some(IndirectBuffer {
+                buffer,
+                bind_group,
+                capacity,
+                version: culling_buffer.version,
+            });
+        }
+
+        let uniform = CullingUniform {
+            view_projection: camera_projection.to_matrix()
+                * camera_transform.isometry.inverse().to_homogeneous(),
+            num_entries: culling_buffer.num_entries,
+            _padding: Default::default(),
+        };
+        staging.write_buffer_from_slice(
+            culling.uniform_buffer.slice(..),
+            bytemuck::bytes_of(&uniform),
+        );
+
+        let command_encoder = render_context.command_encoder();
+        command_encoder.clear_buffer(&culling.statistics_buffer, 0, None);
+
+        {
+            let mut compute_pass =
+                command_encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                    label: Some("mesh culling"),
+                    timestamp_writes: None,
+                });
+            pipeline.pipeline.dispatch(
+                &mut compute_pass,
+                &culling.indirect.as_ref().unwrap().bind_group,
+                [culling_buffer.num_entries, 1, 1],
+                [WORKGROUP_SIZE, 1, 1],
+            );
+        }
+
+        // only one readback per camera is in flight, so a slow GPU doesn't pile them up
+        if culling.readback.is_none() {
+            match readback_pool.read_buffer(
+                &wgpu.device,
+                command_encoder,
+                culling.statistics_buffer.slice(..),
+            ) {
+                Ok(readback) => culling.readback = Some(readback),
+                Err(error) => tracing::trace!("not reading back culling statistics: {error}"),
+            }
+        }
+    }
+}
@@ -0,0 +1,116 @@
+
+struct CullingUniform {
+    view_projection: mat4x4f,
+    num_entries: u32,
+    // padding: 12 bytes
+}
+
+struct CullEntry {
+    aabb_min: vec3f,
+    flags: u32,
+    aabb_max: vec3f,
+    instance_id: u32,
+    first_vertex: u32,
+    vertex_count: u32,
+    // padding: 8 bytes
+}
+
+struct DrawIndirectArgs {
+    vertex_count: u32,
+    instance_count: u32,
+    first_vertex: u32,
+    first_instance: u32,
+}
+
+struct CullingStatistics {
+    num_rendered: atomic<u32>,
+    num_culled: atomic<u32>,
+    num_vertices: atomic<u32>,
+    // padding: 4 bytes
+}
+
+@group(0)
+@binding(0)
+var<uniform> culling_uniform: CullingUniform;
+
+@group(0)
+@binding(1)
+var<storage, read> cull_entries: array<CullEntry>;
+
+@group(0)
+@binding(2)
+var<storage, read_write> draw_args: array<DrawIndirectArgs>;
+
+@group(0)
+@binding(3)
+var<storage, read_write> statistics: CullingStatistics;
+
+// the entry has an AABB that is tested against the frustum
+const FLAG_FRUSTUM_CULLED: u32 = 1;
+
+
+@compute
+@workgroup_size(64)
+fn cull_meshes(@builtin(global_invocation_id) id: vec3u) {
+    let index = id.x;
+    if index >= culling_uniform.num_entries {
+        return;
+    }
+
+    let entry = cull_entries[index];
+
+    var visible = true;
+    if (entry.flags & FLAG_FRUSTUM_CULLED) != 0 {
+        visible = frustum_intersects_aabb(entry.aabb_min, entry.aabb_max);
+    }
+
+    if visible {
+        atomicAdd(&statistics.num_rendered, 1u);
+        atomicAdd(&statistics.num_vertices, entry.vertex_count);
+    }
+    else {
+        atomicAdd(&statistics.num_culled, 1u);
+    }
+
+    draw_args[index] = DrawIndirectArgs(
+        entry.vertex_count,
+        select(0u, 1u, visible),
+        entry.first_vertex,
+        entry.instance_id,
+    );
+}
+
+// same as `Frustrum::intersect_aabb`: the AABB is culled if all of its corners are
+// outside of the same clip plane.
+fn frustum_intersects_aabb(aabb_min: vec3f, aabb_max: vec3f) -> bool {
+    var outcodes_and = 0x3fu;
+
+    for (var i = 0u; i < 8u; i++) {
+        let corner = select(aabb_min, aabb_max, vec3<bool>((i & 1) != 0, (i & 2) != 0, (i & 4) != 0));
+        let vertex = culling_uniform.view_projection * vec4f(corner, 1);
+
+        var outcode = 0u;
+        if vertex.x < -vertex.w {
+            outcode |= 1u;
+        }
+        if vertex.x > vertex.w {
+            outcode |= 2u;
+        }
+        if vertex.y < -vertex.w {
+            outcode |= 4u;
+        }
+        if vertex.y > vertex.w {
+            outcode |= 8u;
+        }
+        if vertex.z < 0 {
+            outcode |= 16u;
+        }
+        if vertex.z > vertex.w {
+            outcode |= 32u;
+        }
+
+        outcodes_and &= outcode;
+    }
+
+    return outcodes_and == 0;
+}
@@ -1,27 +1,47 @@
+pub mod animation;
 pub mod atlas;
+pub mod billboard;
 pub mod camera;
+pub mod color;
 pub mod command;
+pub mod debug_dump;
+pub mod exposure;
+pub mod fog;
 pub mod fps_counter;
+pub mod lighting;
 pub mod mesh;
+pub mod mesh_arena;
+pub mod mesh_culling;
 pub mod model;
+pub mod outline;
+pub mod overlay;
 pub mod pass;
+pub mod readback;
 pub mod render_target;
+pub mod scene;
 pub mod shadow_map;
 pub mod skybox;
 pub mod staging;
+pub mod star_catalog;
 pub mod surface;
 pub mod text;
+pub mod transient;
 
 use std::path::PathBuf;
 
 use bevy_ecs::{
+    component::Component,
+    entity::Entity,
+    query::With,
     resource::Resource,
     schedule::{
         IntoScheduleConfigs,
         SystemSet,
+        common_conditions::resource_exists,
     },
     system::{
         Commands,
+        Populated,
         Res,
         ResMut,
     },
@@ -33,16 +53,31 @@ use serde::{
 };
 
 use crate::{
+    config::ConfigChanged,
     ecs::{
         plugin::{
+            Dependencies,
             Plugin,
             WorldBuilder,
         },
         schedule,
     },
     render::{
-        atlas::Atlas,
+        atlas::{
+            Atlas,
+            AtlasConfig,
+            BINDLESS_FEATURES,
+            bindless_texture_limit,
+            request_bindless_limits,
+        },
+        color::ColorGradingConfig,
         command::RenderFunctions,
+        debug_dump::{
+            DebugDumpsConfig,
+            apply_debug_dumps_config,
+        },
+        exposure::ExposureConfig,
+        lighting::LightingConfig,
         pass::{
             context::{
                 PendingCommandBuffers,
@@ -52,24 +87,42 @@ use crate::{
                 MainPassPlugin,
                 MainPassSystems,
             },
+            post_pass::PostPassPlugin,
             ui_pass::UiPassSystems,
         },
+        readback::{
+            ReadbackPool,
+            poll_readbacks,
+        },
         staging::{
             Staging,
+            StagingStatistics,
             flush_staging,
             initialize_staging,
         },
         surface::{
+            Surface,
+            SurfaceFormatPreference,
+            apply_surface_config,
             create_surfaces,
             present_surfaces,
             reconfigure_surfaces,
             set_swap_chain_texture,
         },
-        text::Font,
+        text::{
+            Font,
+            FontRendering,
+        },
+        transient::{
+            TransientPool,
+            TransientPoolStatistics,
+            end_transient_frame,
+        },
     },
     util::serde::default_true,
     wgpu::{
         WgpuContext,
+        WgpuContextBuilder,
         WgpuPlugin,
         WgpuSystems,
     },
@@ -81,44 +134,64 @@ pub struct RenderPlugin {
 }
 
 impl Plugin for RenderPlugin {
-    fn setup(&self, builder: &mut WorldBuilder) -> Result<(), Error> {
-        builder
+    fn dependencies(&self, dependencies: &mut Dependencies) -> Result<(), Error> {
+        dependencies
             .require_plugin::<WgpuPlugin>()
             .add_plugin(MainPassPlugin)?
+            .add_plugin(PostPassPlugin)?;
+        Ok(())
+    }
+
+    fn setup(&self, builder: &mut WorldBuilder) -> Result<(), Error> {
+        builder
             // create resources
             .insert_resource(self.config.clone())
-            .init_resource::<PendingCommandBuffers>()
-            // startup systems
+            .init_resource::<StagingStatistics>()
+            .init_resource::<TransientPoolStatistics>()
+            .add_message::<ConfigChanged>()
+            // gpu setup systems
             .add_systems(
-                schedule::Startup,
+                schedule::GpuSetup,
                 (
+                    request_bindless_features
+                        .in_set(WgpuSystems::RequestFeatures)
+                        .run_if(resource_exists::<WgpuContextBuilder>),
                     // initialize rendering
-                    (
-                        initialize_staging,
-                        create_default_resources.after(initialize_staging),
-                    )
+                    (create_pools, initialize_staging, create_default_resources)
+                        .chain()
                         .after(WgpuSystems::CreateContext)
                         .before(RenderSystems::Setup),
                     // flush staging
                     flush_staging.after(RenderSystems::Setup),
+                    remove_on_gpu_setup::<Surface>,
                 ),
             )
+            // uploads of the startup systems
+            .add_systems(schedule::PostStartup, flush_staging)
             // render systems
             .add_systems(
                 schedule::Render,
                 (
-                    (create_surfaces, reconfigure_surfaces).before(RenderSystems::BeginFrame),
+                    (create_surfaces, reconfigure_surfaces, apply_surface_config)
+                        .before(RenderSystems::BeginFrame),
+                    apply_debug_dumps_config.before(RenderSystems::Render),
                     set_swap_chain_texture
                         .after(create_surfaces)
                         .after(reconfigure_surfaces)
+                        .after(apply_surface_config)
                         .before(RenderSystems::Render),
-                    (flush_command_buffers, present_surfaces)
+                    (
+                        flush_command_buffers,
+                        present_surfaces,
+                        poll_readbacks,
+                        end_transient_frame,
+                    )
                         .chain()
                         .after(RenderSystems::EndFrame),
                 ),
             )
             .configure_system_sets(
-                schedule::Startup,
+                schedule::GpuSetup,
                 RenderSystems::Setup.after(WgpuSystems::CreateContext),
             )
             .configure_system_sets(
@@ -142,14 +215,23 @@ pub enum RenderSystems {
     EndFrame,
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize, Resource)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, Resource)]
 pub struct RenderConfig {
     #[serde(default = "default_true")]
     pub vsync: bool,
 
+    /// A BDF font, or a font sheet made with `cargo xtask make-font-sheet`.
     #[serde(default = "default_font")]
     pub default_font: PathBuf,
 
+    /// Only applied at startup.
+    #[serde(default)]
+    pub font_rendering: FontRendering,
+
+    /// Only applied when a surface is created.
+    #[serde(default)]
+    pub surface_format: SurfaceFormatPreference,
+
     /// FOV in degrees
     ///
     /// # TODO
@@ -160,8 +242,55 @@ pub struct RenderConfig {
     #[serde(default = "default_fov")]
     pub fov: f32,
 
+    /// Distance to the camera's far plane (in blocks).
+    ///
+    /// By default the far plane is infinitely far away, and fog hides where
+    /// the loaded terrain ends.
+    #[serde(default)]
+    pub far_plane: Option<f32>,
+
     #[serde(default)]
     pub depth_prepass: bool,
+
+    /// Map the near plane to depth 1 and the far plane to depth 0, with a
+    /// floating point depth buffer. This gives much better depth precision far
+    /// away from the camera, which avoids z-fighting on distant terrain.
+    ///
+    /// Only applied when a surface is created.
+    #[serde(default)]
+    pub reverse_z: bool,
+
+    /// Cull meshes against the camera frustum in a compute shader, if the
+    /// hardware supports it.
+    ///
+    /// The required features are only requested if this is enabled at startup.
+    #[serde(default = "default_true")]
+    pub gpu_culling: bool,
+
+    /// Collect pipeline statistics for the main pass (e.g. fragment shader
+    /// invocations, to measure overdraw), if the hardware supports it.
+    ///
+    /// The required features are only requested if this is enabled at startup.
+    #[serde(default)]
+    pub pipeline_statistics: bool,
+
+    #[serde(default)]
+    pub lighting: LightingConfig,
+
+    #[serde(default)]
+    pub color_grading: ColorGradingConfig,
+
+    #[serde(default)]
+    pub exposure: ExposureConfig,
+
+    /// Sample block textures from a texture array if the hardware supports it,
+    /// instead of packing them into the texture atlas.
+    #[serde(default = "default_true")]
+    pub bindless: bool,
+
+    /// Dump GPU resources to files, if set.
+    #[serde(default)]
+    pub debug_dumps: Option<DebugDumpsConfig>,
 }
 
 impl Default for RenderConfig {
@@ -169,8 +298,19 @@ impl Default for RenderConfig {
         Self {
             vsync: true,
             default_font: default_font(),
+            font_rendering: Default::default(),
+            surface_format: Default::default(),
             fov: default_fov(),
+            far_plane: None,
             depth_prepass: false,
+            reverse_z: false,
+            gpu_culling: true,
+            pipeline_statistics: false,
+            lighting: Default::default(),
+            color_grading: Default::default(),
+            exposure: Default::default(),
+            bindless: true,
+            debug_dumps: None,
         }
     }
 }
@@ -183,18 +323,75 @@ fn default_fov() -> f32 {
     60.0
 }
 
+fn request_bindless_features(
+    config: Res<RenderConfig>,
+    mut context_builder: ResMut<WgpuContextBuilder>,
+) {
+    if !config.bindless {
+        return;
+    }
+
+    let context_builder = &mut *context_builder;
+    if context_builder
+        .try_request_features(BINDLESS_FEATURES)
+        .is_ok()
+    {
+        request_bindless_limits(
+            &mut context_builder.enabled_limits,
+            &context_builder.supported_limits,
+        );
+    }
+    else {
+        tracing::info!("Texture binding arrays not supported. Using the texture atlas.");
+    }
+}
+
+/// The pools hold buffers and textures of the device, so they're created with
+/// it.
+fn create_pools(mut commands: Commands) {
+    commands.insert_resource(PendingCommandBuffers::default());
+    commands.insert_resource(ReadbackPool::default());
+    commands.insert_resource(TransientPool::default());
+}
+
 #[profiling::function]
 fn create_default_resources(
     wgpu: Res<WgpuContext>,
     config: Res<RenderConfig>,
+    atlas: Option<ResMut<DefaultAtlas>>,
     mut commands: Commands,
     mut staging: ResMut<Staging>,
 ) {
     let sampler = wgpu.device.create_sampler(&Default::default());
 
-    let atlas = Atlas::new(&wgpu.device, Default::default());
+    let bindless_texture_limit = config
+        .bindless
+        .then(|| bindless_texture_limit(&wgpu.device))
+        .flatten();
 
-    let font = Font::open(&config.default_font, &wgpu.device, &mut *staging).unwrap_or_else(|e| {
+    if let Some(mut atlas) = atlas {
+        // the device was lost. the atlas is recreated in place, so that the handles
+        // into it stay valid.
+        atlas.recreate(&wgpu.device, bindless_texture_limit, &mut staging);
+    }
+    else {
+        let atlas = Atlas::new(
+            &wgpu.device,
+            AtlasConfig {
+                bindless_texture_limit,
+                ..Default::default()
+            },
+        );
+        commands.insert_resource(DefaultAtlas(atlas));
+    }
+
+    let font = Font::open(
+        &config.default_font,
+        config.font_rendering,
+        &wgpu.device,
+        &mut staging,
+    )
+    .unwrap_or_else(|e| {
         panic!(
             "Error while loading font: {e}: {}",
             config.default_font.display()
@@ -202,10 +399,23 @@ fn create_default_resources(
     });
 
     commands.insert_resource(DefaultSampler(sampler));
-    commands.insert_resource(DefaultAtlas(atlas));
     commands.insert_resource(DefaultFont(font));
 }
 
+/// Removes a component that holds GPU resources, so that it's created again
+/// with the new device.
+///
+/// Systems like this are added to [`schedule::GpuSetup`]. They only find
+/// something to remove when the GPU device was lost.
+pub fn remove_on_gpu_setup<C: Component>(
+    entities: Populated<Entity, With<C>>,
+    mut commands: Commands,
+) {
+    for entity in entities {
+        commands.entity(entity).remove::<C>();
+    }
+}
+
 // todo: make this a resource that contains all the samplers we use
 #[derive(Clone, Debug, Resource)]
 pub struct DefaultSampler(pub wgpu::Sampler);
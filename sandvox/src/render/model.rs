@@ -5,18 +5,28 @@ use std::{
         hash_map,
     },
     marker::PhantomData,
-    path::Path,
+    path::{
+        Path,
+        PathBuf,
+    },
+    sync::Arc,
 };
 
 use bevy_ecs::{
+    component::Component,
     entity::Entity,
-    hierarchy::ChildOf,
+    hierarchy::{
+        ChildOf,
+        Children,
+    },
     name::Name,
     relationship::RelatedSpawnerCommands,
     system::{
         Commands,
         EntityCommands,
+        Populated,
         Res,
+        ResMut,
         SystemParam,
     },
 };
@@ -26,8 +36,16 @@ use color_eyre::eyre::{
     bail,
     eyre,
 };
+use image::{
+    DynamicImage,
+    GrayAlphaImage,
+    GrayImage,
+    RgbImage,
+    RgbaImage,
+};
 use nalgebra::{
     Isometry3,
+    Matrix4,
     Point2,
     Point3,
     Quaternion,
@@ -37,20 +55,184 @@ use nalgebra::{
 };
 
 use crate::{
-    ecs::transform::LocalTransform,
-    render::mesh::{
-        Mesh,
-        MeshBufferSpan,
-        MeshPipelineLayout,
-        Vertex,
+    assets::{
+        Asset,
+        AssetHandle,
+        AssetPlugin,
+        AssetServer,
+    },
+    ecs::{
+        plugin::{
+            Dependencies,
+            Plugin,
+            WorldBuilder,
+        },
+        schedule,
+        transform::LocalTransform,
+    },
+    render::{
+        DefaultAtlas,
+        animation::{
+            AnimationChannel,
+            AnimationClip,
+            AnimationPlayer,
+            AnimationPlugin,
+            Interpolation,
+            Keyframes,
+            ModelAnimations,
+        },
+        atlas::{
+            Atlas,
+            AtlasHandle,
+            Padding,
+            PaddingFill,
+            PaddingMode,
+        },
+        mesh::{
+            Mesh,
+            MeshBufferSpan,
+            MeshPipelineLayout,
+            MeshPlugin,
+            Skin,
+            Vertex,
+        },
+        staging::Staging,
     },
     wgpu::WgpuContext,
 };
 
+/// Reloads models that were loaded with the [`ModelLoader`] when their file is
+/// modified, or when the GPU device was lost.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ModelPlugin;
+
+impl Plugin for ModelPlugin {
+    fn dependencies(&self, dependencies: &mut Dependencies) -> Result<(), Error> {
+        dependencies
+            .require_plugin::<AssetPlugin>()
+            .require_plugin::<MeshPlugin>()
+            .require_plugin::<AnimationPlugin>();
+        Ok(())
+    }
+
+    fn setup(&self, builder: &mut WorldBuilder) -> Result<(), Error> {
+        builder
+            .add_systems(schedule::GpuSetup, reimport_models)
+            .add_systems(schedule::Update, reload_models);
+        Ok(())
+    }
+}
+
+/// A glTF document with its buffers and images.
+///
+/// Buffers and images can be embedded in a GLB file, or be referenced by URI.
+/// URIs are relative to the glTF file.
+#[derive(derive_more::Debug)]
+pub struct Model {
+    #[debug(skip)]
+    pub document: gltf::Document,
+
+    #[debug(skip)]
+    pub buffers: Vec<gltf::buffer::Data>,
+
+    #[debug(skip)]
+    pub images: Vec<RgbaImage>,
+
+    /// External files that buffers and images were loaded from.
+    files: Vec<PathBuf>,
+}
+
+impl Asset for Model {
+    fn load(path: &Path) -> Result<Self, Error> {
+        let gltf::Gltf { document, blob } = gltf::Gltf::open(path)?;
+        let base = path.parent().unwrap_or(Path::new("."));
+
+        let buffers = gltf::import_buffers(&document, Some(base), blob)?;
+        let images = gltf::import_images(&document, Some(base), &buffers)?
+            .into_iter()
+            .map(convert_image)
+            .collect::<Result<Vec<_>, Error>>()?;
+
+        let buffer_uris = document.buffers().filter_map(|buffer| {
+            match buffer.source() {
+                gltf::buffer::Source::Uri(uri) => Some(uri),
+                gltf::buffer::Source::Bin => None,
+            }
+        });
+        let image_uris = document.images().filter_map(|image| {
+            match image.source() {
+                gltf::image::Source::Uri { uri, .. } => Some(uri),
+                gltf::image::Source::View { .. } => None,
+            }
+        });
+        let files = buffer_uris
+            .chain(image_uris)
+            .filter(|uri| !uri.starts_with("data:"))
+            .map(|uri| base.join(uri))
+            .collect();
+
+        Ok(Self {
+            document,
+            buffers,
+            images,
+            files,
+        })
+    }
+
+    fn dependencies(&self) -> Vec<PathBuf> {
+        self.files.clone()
+    }
+}
+
+fn convert_image(image: gltf::image::Data) -> Result<RgbaImage, Error> {
+    let gltf::image::Data {
+        pixels,
+        format,
+        width,
+        height,
+    } = image;
+
+    let image = match format {
+        gltf::image::Format::R8 => {
+            GrayImage::from_raw(width, height, pixels).map(DynamicImage::from)
+        }
+        gltf::image::Format::R8G8 => {
+            GrayAlphaImage::from_raw(width, height, pixels).map(DynamicImage::from)
+        }
+        gltf::image::Format::R8G8B8 => {
+            RgbImage::from_raw(width, height, pixels).map(DynamicImage::from)
+        }
+        gltf::image::Format::R8G8B8A8 => {
+            RgbaImage::from_raw(width, height, pixels).map(DynamicImage::from)
+        }
+        _ => bail!("Unsupported image format: {format:?}"),
+    };
+
+    Ok(image
+        .ok_or_else(|| eyre!("Image data doesn't match its size"))?
+        .into_rgba8())
+}
+
+/// The model that a scene entity was loaded from.
+#[derive(Debug, Component)]
+pub struct ModelAsset {
+    pub model: AssetHandle<Model>,
+    version: u64,
+}
+
+/// Keeps the textures of a model in the atlas.
+#[derive(Debug, Default, Component)]
+pub struct ModelTextures {
+    pub textures: Vec<AtlasHandle>,
+}
+
 #[derive(derive_more::Debug, SystemParam)]
 pub struct ModelLoader<'w, 's> {
     wgpu: Res<'w, WgpuContext>,
     mesh_layout: Res<'w, MeshPipelineLayout>,
+    assets: Res<'w, AssetServer>,
+    atlas: ResMut<'w, DefaultAtlas>,
+    staging: ResMut<'w, Staging>,
 
     #[debug(skip)]
     commands: Commands<'w, 's>,
@@ -58,36 +240,121 @@ pub struct ModelLoader<'w, 's> {
 
 impl<'w, 's> ModelLoader<'w, 's> {
     pub fn load_scene(&mut self, path: impl AsRef<Path>) -> Result<EntityCommands<'_>, Error> {
-        let gltf = gltf::Gltf::open(path)?;
-
-        let mut importer = ModelImporter::new(&gltf)?;
+        let handle = self.assets.load_blocking::<Model>(path);
+        let mut version = 0;
+        let model = handle
+            .get_if_changed(&mut version)
+            .unwrap()
+            .map_err(|error| eyre!("{error}"))?;
+
+        let mut importer = ModelImporter::new(&model)?;
         let mut scene_entity = importer.import_default_scene(&mut self.commands)?;
-        importer.import_meshes(&self.wgpu, &self.mesh_layout, scene_entity.commands_mut())?;
+        let textures = importer.import_meshes(
+            &self.wgpu,
+            &self.mesh_layout,
+            &mut self.atlas,
+            &mut self.staging,
+            scene_entity.commands_mut(),
+        )?;
+        let animations = importer.import_animations()?;
+
+        scene_entity.insert((
+            ModelAsset {
+                model: handle,
+                version,
+            },
+            textures,
+            autoplay(&animations),
+            animations,
+        ));
 
         Ok(scene_entity)
     }
+
+    /// Replaces the nodes of a scene entity with the scene from `model`.
+    fn reload_scene(&mut self, entity: Entity, model: &Model) -> Result<(), Error> {
+        let mut scene_entity = self.commands.entity(entity);
+        scene_entity.despawn_related::<Children>();
+
+        let mut importer = ModelImporter::new(model)?;
+        importer.import_default_scene_into(&mut scene_entity)?;
+        let textures = importer.import_meshes(
+            &self.wgpu,
+            &self.mesh_layout,
+            &mut self.atlas,
+            &mut self.staging,
+            &mut self.commands,
+        )?;
+        let animations = importer.import_animations()?;
+
+        // this drops the old textures. the old clips target the despawned nodes, so we
+        // need to start over with the new ones.
+        self.commands
+            .entity(entity)
+            .insert((textures, autoplay(&animations), animations));
+
+        Ok(())
+    }
+}
+
+/// Creates an [`AnimationPlayer`] that loops the first clip of a model.
+fn autoplay(animations: &ModelAnimations) -> AnimationPlayer {
+    let mut player = AnimationPlayer::default();
+    if let Some(clip) = animations.clips.first() {
+        player.play(clip.clone()).set_looping(true);
+    }
+    player
+}
+
+/// Imports the models again after the GPU device was lost, since their meshes
+/// were removed.
+fn reimport_models(models: Populated<&mut ModelAsset>) {
+    for mut asset in models {
+        asset.version = 0;
+    }
+}
+
+fn reload_models(models: Populated<(Entity, &mut ModelAsset)>, mut model_loader: ModelLoader) {
+    for (entity, mut asset) in models {
+        let asset = &mut *asset;
+        let Some(result) = asset.model.get_if_changed(&mut asset.version)
+        else {
+            continue;
+        };
+
+        if let Err(error) = result
+            .map_err(|error| eyre!("{error}"))
+            .and_then(|model| model_loader.reload_scene(entity, &model))
+        {
+            tracing::error!(path = ?asset.model.path(), "could not reload model: {error}");
+        }
+    }
 }
 
 #[derive(derive_more::Debug)]
 pub struct ModelImporter<'a> {
     #[debug(skip)]
-    gltf: &'a gltf::Gltf,
+    model: &'a Model,
 
     #[debug(skip)]
-    load_meshes: Vec<(Entity, gltf::Mesh<'a>)>,
+    load_meshes: Vec<(Entity, gltf::Mesh<'a>, Option<gltf::Skin<'a>>)>,
 
     label: Option<&'a str>,
 
     node_to_entity: HashMap<usize, Entity>,
+
+    /// Images that have been inserted into the atlas.
+    textures: HashMap<usize, AtlasHandle>,
 }
 
 impl<'a> ModelImporter<'a> {
-    pub fn new(gltf: &'a gltf::Gltf) -> Result<Self, Error> {
+    pub fn new(model: &'a Model) -> Result<Self, Error> {
         Ok(Self {
-            gltf,
+            model,
             load_meshes: vec![],
             label: None,
             node_to_entity: HashMap::new(),
+            textures: HashMap::new(),
         })
     }
 
@@ -106,14 +373,27 @@ impl<'a> ModelImporter<'a> {
     ) -> Result<EntityCommands<'c>, Error> {
         let mut scene_entity = commands.spawn_empty();
 
-        if let Some(name) = scene.name() {
-            scene_entity.insert(Name::new(name.to_owned()));
-        }
-
         // the caller should add a LocalTransform, but stuff doesn't work without one,
         // so we'll add the identity in case the caller forgets
         scene_entity.insert(LocalTransform::identity());
 
+        self.import_scene_into(scene, &mut scene_entity)?;
+
+        Ok(scene_entity)
+    }
+
+    /// Imports a scene into an existing entity.
+    ///
+    /// The nodes of the scene are spawned as children of that entity.
+    pub fn import_scene_into(
+        &mut self,
+        scene: &gltf::Scene<'a>,
+        scene_entity: &mut EntityCommands,
+    ) -> Result<(), Error> {
+        if let Some(name) = scene.name() {
+            scene_entity.insert(Name::new(name.to_owned()));
+        }
+
         let scene_entity_id = scene_entity.id();
         let mut child_spawner =
             RelatedSpawnerCommands::new(scene_entity.commands(), scene_entity_id);
@@ -122,7 +402,7 @@ impl<'a> ModelImporter<'a> {
             self.import_node(&node, &mut child_spawner)?;
         }
 
-        Ok(scene_entity)
+        Ok(())
     }
 
     /// Imports the default scene.
@@ -132,8 +412,25 @@ impl<'a> ModelImporter<'a> {
         &mut self,
         commands: &'c mut Commands,
     ) -> Result<EntityCommands<'c>, Error> {
+        let scene = self.default_scene()?;
+        self.import_scene(&scene, commands)
+    }
+
+    /// Imports the default scene into an existing entity.
+    ///
+    /// See [`import_scene_into`].
+    pub fn import_default_scene_into(
+        &mut self,
+        scene_entity: &mut EntityCommands,
+    ) -> Result<(), Error> {
+        let scene = self.default_scene()?;
+        self.import_scene_into(&scene, scene_entity)
+    }
+
+    fn default_scene(&mut self) -> Result<gltf::Scene<'a>, Error> {
         let scene = self
-            .gltf
+            .model
+            .document
             .default_scene()
             .ok_or_else(|| eyre!("No default scene"))?;
 
@@ -143,7 +440,7 @@ impl<'a> ModelImporter<'a> {
             self.label = Some(name);
         }
 
-        self.import_scene(&scene, commands)
+        Ok(scene)
     }
 
     /// Imports a node.
@@ -176,7 +473,7 @@ impl<'a> ModelImporter<'a> {
 
         // remember for later to add this mesh to this entity
         if let Some(mesh) = node.mesh() {
-            self.load_meshes.push((node_entity_id, mesh));
+            self.load_meshes.push((node_entity_id, mesh, node.skin()));
         }
 
         // import children
@@ -192,14 +489,22 @@ impl<'a> ModelImporter<'a> {
     ///
     /// This loads all meshes for nodes that have been imported. All meshes are
     /// stored in a combined index and vertex buffer, which is shared between
-    /// all entities. This attaches [`Mesh`] components to the entities.
+    /// all entities. This attaches [`Mesh`] components to the entities. If a
+    /// mesh has multiple primitives, a child entity is spawned for each of
+    /// them.
+    ///
+    /// The base color textures of the primitives' materials are inserted into
+    /// the atlas. They're removed again when the returned [`ModelTextures`] is
+    /// dropped.
     pub fn import_meshes(
         &mut self,
         wgpu: &WgpuContext,
         mesh_layout: &MeshPipelineLayout,
+        atlas: &mut Atlas,
+        staging: &mut Staging,
         commands: &mut Commands,
-    ) -> Result<(), Error> {
-        let mut loaded_meshes: HashMap<usize, Option<(MeshBufferSpan, gltf::Primitive<'_>)>> =
+    ) -> Result<ModelTextures, Error> {
+        let mut loaded_meshes: HashMap<usize, Vec<(MeshBufferSpan, gltf::Primitive<'a>)>> =
             HashMap::new();
 
         // initial pass to just reserve space for all buffers and get the total size
@@ -207,35 +512,32 @@ impl<'a> ModelImporter<'a> {
         let mut vertex_buffer_offset = 0;
         let mut index_buffer_offset = 0;
 
-        for (_entity, mesh) in &self.load_meshes {
-            match loaded_meshes.entry(mesh.index()) {
-                hash_map::Entry::Occupied(_occupied_entry) => {}
-                hash_map::Entry::Vacant(vacant_entry) => {
-                    if let Some(primitive) = get_first_tri_primitive(&mesh) {
-                        let num_indices = primitive
-                            .indices()
-                            .unwrap_or_else(|| todo!("Mesh without index buffer"))
-                            .count()
-                            .try_into()
-                            .unwrap();
-                        let num_vertices = get_num_vertices(&primitive)?;
-
-                        let span = MeshBufferSpan {
-                            vertex_buffer_offset,
-                            num_vertices,
-                            index_buffer_offset,
-                            num_indices,
-                        };
-
-                        vertex_buffer_offset += num_vertices;
-                        index_buffer_offset += num_indices;
-
-                        vacant_entry.insert(Some((span, primitive)));
-                    }
-                    else {
-                        vacant_entry.insert(None);
-                    }
+        for (_entity, mesh, _skin) in &self.load_meshes {
+            if let hash_map::Entry::Vacant(vacant_entry) = loaded_meshes.entry(mesh.index()) {
+                let mut primitives = vec![];
+
+                for primitive in get_tri_primitives(mesh) {
+                    let num_vertices = get_num_vertices(&primitive)?;
+
+                    // primitives without indices get generated indices for all vertices
+                    let num_indices = primitive
+                        .indices()
+                        .map_or(num_vertices, |indices| indices.count().try_into().unwrap());
+
+                    let span = MeshBufferSpan {
+                        vertex_buffer_offset,
+                        num_vertices,
+                        index_buffer_offset,
+                        num_indices,
+                    };
+
+                    vertex_buffer_offset += num_vertices;
+                    index_buffer_offset += num_indices;
+
+                    primitives.push((span, primitive));
                 }
+
+                vacant_entry.insert(primitives);
             }
         }
 
@@ -275,11 +577,7 @@ impl<'a> ModelImporter<'a> {
         {
             // fill buffers
 
-            let blob = self
-                .gltf
-                .blob
-                .as_ref()
-                .ok_or_else(|| eyre!("GLTF file without binary blob"))?;
+            let buffers = &self.model.buffers;
 
             let mut vertex_buffer_view = vertex_buffer.get_mapped_range_mut(..);
             let vertex_buffer_view =
@@ -288,12 +586,16 @@ impl<'a> ModelImporter<'a> {
             let mut index_buffer_view = index_buffer.get_mapped_range_mut(..);
             let index_buffer_view = bytemuck::cast_slice_mut::<u8, u32>(&mut *index_buffer_view);
 
-            for (span, primitive) in loaded_meshes
-                .iter()
-                .filter_map(|(_mesh_id, entry)| entry.as_ref())
-            {
-                fill_index_buffer(primitive, blob, index_buffer_view, span)?;
-                fill_vertex_buffer(primitive, blob, vertex_buffer_view, span)?;
+            for (span, primitive) in loaded_meshes.values().flatten() {
+                let texture = self.import_base_color_texture(
+                    &primitive.material(),
+                    atlas,
+                    &wgpu.device,
+                    staging,
+                )?;
+
+                fill_index_buffer(primitive, buffers, index_buffer_view, span)?;
+                fill_vertex_buffer(primitive, buffers, vertex_buffer_view, span, texture)?;
             }
         }
 
@@ -302,27 +604,237 @@ impl<'a> ModelImporter<'a> {
         index_buffer.unmap();
 
         // insert mesh components for each entity
-        for (entity, mesh) in self.load_meshes.drain(..) {
-            if let Some((span, _pimitive)) = loaded_meshes
+        let create_mesh = |span: &MeshBufferSpan| {
+            Mesh {
+                vertex_buffer: vertex_buffer.clone(),
+                index_buffer: index_buffer.clone(),
+                bind_group: bind_group.clone(),
+                span: *span,
+                allocation: None,
+            }
+        };
+
+        for (entity, mesh, skin) in std::mem::take(&mut self.load_meshes) {
+            let primitives = loaded_meshes
                 .get(&mesh.index())
-                .expect("missing load_meshes entry")
-            {
-                commands.entity(entity).insert(Mesh {
-                    vertex_buffer: vertex_buffer.clone(),
-                    index_buffer: index_buffer.clone(),
-                    bind_group: bind_group.clone(),
-                    span: *span,
+                .expect("missing load_meshes entry");
+
+            let skin = skin.map(|skin| self.import_skin(&skin)).transpose()?;
+
+            let insert_mesh = |mut entity: EntityCommands, span: &MeshBufferSpan| {
+                entity.insert(create_mesh(span));
+                if let Some(skin) = &skin {
+                    entity.insert(skin.clone());
+                }
+            };
+
+            match primitives.as_slice() {
+                [] => {}
+                [(span, _primitive)] => {
+                    insert_mesh(commands.entity(entity), span);
+                }
+                _ => {
+                    // an entity can only have one mesh
+                    for (span, _primitive) in primitives {
+                        insert_mesh(
+                            commands.spawn((ChildOf(entity), LocalTransform::identity())),
+                            span,
+                        );
+                    }
+                }
+            }
+        }
+
+        Ok(ModelTextures {
+            textures: self
+                .textures
+                .drain()
+                .map(|(_image, handle)| handle)
+                .collect(),
+        })
+    }
+
+    /// Creates the [`Skin`] for a skinned mesh.
+    ///
+    /// The joints must be part of the imported scene.
+    fn import_skin(&self, skin: &gltf::Skin) -> Result<Skin, Error> {
+        let joints = skin
+            .joints()
+            .map(|joint| {
+                self.node_to_entity
+                    .get(&joint.index())
+                    .copied()
+                    .ok_or_else(|| eyre!("Joint #{} is not in the scene", joint.index()))
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+
+        let inverse_bind_matrices = if let Some(accessor) = skin.inverse_bind_matrices() {
+            read_accessor::<[[f32; 4]; 4]>(&self.model.buffers, &accessor, 1, 0)?
+                .into_iter()
+                .map(convert_matrix)
+                .collect()
+        }
+        else {
+            vec![Matrix4::identity(); joints.len()]
+        };
+
+        if inverse_bind_matrices.len() != joints.len() {
+            bail!(
+                "Skin with {} joints and {} inverse bind matrices",
+                joints.len(),
+                inverse_bind_matrices.len()
+            );
+        }
+
+        Ok(Skin {
+            joints,
+            inverse_bind_matrices,
+        })
+    }
+
+    /// Inserts the base color texture of a material into the atlas.
+    ///
+    /// Returns the texture ID and the index of the texture coordinates it uses.
+    fn import_base_color_texture(
+        &mut self,
+        material: &gltf::Material,
+        atlas: &mut Atlas,
+        device: &wgpu::Device,
+        staging: &mut Staging,
+    ) -> Result<Option<(u32, u32)>, Error> {
+        let Some(info) = material.pbr_metallic_roughness().base_color_texture()
+        else {
+            return Ok(None);
+        };
+
+        let image_index = info.texture().source().index();
+
+        let handle = match self.textures.entry(image_index) {
+            hash_map::Entry::Occupied(occupied_entry) => occupied_entry.into_mut(),
+            hash_map::Entry::Vacant(vacant_entry) => {
+                let image = self
+                    .model
+                    .images
+                    .get(image_index)
+                    .ok_or_else(|| eyre!("Missing image #{image_index}"))?;
+
+                vacant_entry.insert(atlas.insert_image(
+                    image,
+                    Some(PaddingMode {
+                        padding: Padding::uniform(1),
+                        fill: PaddingFill::REPEAT,
+                    }),
+                    device,
+                    staging,
+                )?)
+            }
+        };
+
+        Ok(Some((handle.id(), info.tex_coord())))
+    }
+
+    /// Imports all animations.
+    ///
+    /// This must be called after the scene has been imported, since the
+    /// animations target the entities that were created for the nodes. Channels
+    /// that target nodes outside of the imported scene are skipped.
+    pub fn import_animations(&self) -> Result<ModelAnimations, Error> {
+        let buffers = &self.model.buffers;
+        let mut clips = vec![];
+
+        for animation in self.model.document.animations() {
+            let mut channels = vec![];
+            let mut duration = 0.0f32;
+
+            for channel in animation.channels() {
+                let Some(target) = self
+                    .node_to_entity
+                    .get(&channel.target().node().index())
+                    .copied()
+                else {
+                    continue;
+                };
+
+                let sampler = channel.sampler();
+                let times = read_accessor::<f32>(buffers, &sampler.input(), 1, 0)?;
+
+                // we don't use the tangents of cubic splines and interpolate linearly between
+                // the values instead
+                let (interpolation, stride, offset) = match sampler.interpolation() {
+                    gltf::animation::Interpolation::Step => (Interpolation::Step, 1, 0),
+                    gltf::animation::Interpolation::Linear => (Interpolation::Linear, 1, 0),
+                    gltf::animation::Interpolation::CubicSpline => (Interpolation::Linear, 3, 1),
+                };
+
+                let output = sampler.output();
+                let keyframes = match channel.target().property() {
+                    gltf::animation::Property::Translation => {
+                        Keyframes::Translation(
+                            read_accessor::<[f32; 3]>(buffers, &output, stride, offset)?
+                                .into_iter()
+                                .map(|translation| convert_translation(translation).vector)
+                                .collect(),
+                        )
+                    }
+                    gltf::animation::Property::Rotation => {
+                        Keyframes::Rotation(
+                            read_accessor::<[f32; 4]>(buffers, &output, stride, offset)?
+                                .into_iter()
+                                .map(convert_rotation)
+                                .collect(),
+                        )
+                    }
+                    gltf::animation::Property::Scale => {
+                        Keyframes::Scale(
+                            read_accessor::<[f32; 3]>(buffers, &output, stride, offset)?
+                                .into_iter()
+                                .map(Vector3::from)
+                                .collect(),
+                        )
+                    }
+                    gltf::animation::Property::MorphTargetWeights => {
+                        tracing::debug!("ignoring morph target animation");
+                        continue;
+                    }
+                };
+
+                let num_keyframes = match &keyframes {
+                    Keyframes::Translation(values) | Keyframes::Scale(values) => values.len(),
+                    Keyframes::Rotation(values) => values.len(),
+                };
+                if times.is_empty() || num_keyframes != times.len() {
+                    bail!(
+                        "Animation channel with {} timestamps and {num_keyframes} keyframes",
+                        times.len()
+                    );
+                }
+
+                duration = duration.max(*times.last().unwrap());
+
+                channels.push(AnimationChannel {
+                    target,
+                    interpolation,
+                    times,
+                    keyframes,
                 });
             }
+
+            clips.push(Arc::new(AnimationClip {
+                name: animation.name().map(ToOwned::to_owned),
+                duration,
+                channels,
+            }));
         }
 
-        Ok(())
+        Ok(ModelAnimations { clips })
     }
 }
 
-fn get_first_tri_primitive<'a>(mesh: &gltf::Mesh<'a>) -> Option<gltf::Primitive<'a>> {
+fn get_tri_primitives<'a>(
+    mesh: &gltf::Mesh<'a>,
+) -> impl Iterator<Item = gltf::Primitive<'a>> + use<'a> {
     mesh.primitives()
-        .find(|primitive| primitive.mode() == gltf::mesh::Mode::Triangles)
+        .filter(|primitive| primitive.mode() == gltf::mesh::Mode::Triangles)
 }
 
 fn get_num_vertices(primitive: &gltf::Primitive) -> Result<u32, Error> {
@@ -347,9 +859,10 @@ fn get_num_vertices(primitive: &gltf::Primitive) -> Result<u32, Error> {
 
 fn fill_vertex_buffer(
     primitive: &gltf::Primitive,
-    blob: &[u8],
+    buffers: &[gltf::buffer::Data],
     vertex_buffer_view: &mut [Vertex],
     span: &MeshBufferSpan,
+    texture: Option<(u32, u32)>,
 ) -> Result<(), Error> {
     let positions = primitive
         .get(&gltf::Semantic::Positions)
@@ -361,7 +874,11 @@ fn fill_vertex_buffer(
 
     //let colors = primitive.get(&gltf::Semantic::Colors(0));
 
-    let uvs = primitive.get(&gltf::Semantic::TexCoords(0));
+    let joints = read_joints(primitive, buffers)?;
+    let joint_weights = read_joint_weights(primitive, buffers)?;
+
+    let (texture_id, tex_coord) = texture.unwrap_or((u32::MAX, 0));
+    let uvs = primitive.get(&gltf::Semantic::TexCoords(tex_coord));
 
     let num_vertices = positions.count();
 
@@ -375,13 +892,13 @@ fn fill_vertex_buffer(
     //    assert_eq!(num_vertices, colors.count());
     //}
 
-    let mut positions = BufferReader::<[f32; 3]>::new(blob, &positions)?;
-    let mut normals = BufferReader::<[f32; 3]>::new(blob, &normals)?;
+    let mut positions = BufferReader::<[f32; 3]>::new(buffers, &positions)?;
+    let mut normals = BufferReader::<[f32; 3]>::new(buffers, &normals)?;
     let mut uvs = uvs
-        .map(|uvs| BufferReader::<[f32; 2]>::new(blob, &uvs))
+        .map(|uvs| BufferReader::<[f32; 2]>::new(buffers, &uvs))
         .transpose()?;
     //let mut colors = colors
-    //    .map(|colors| BufferReader::<[f32; 3]>::new(blob, &colors))
+    //    .map(|colors| BufferReader::<[f32; 3]>::new(buffers, &colors))
     //    .transpose()?;
 
     let destination = &mut vertex_buffer_view
@@ -399,15 +916,79 @@ fn fill_vertex_buffer(
         if let Some(uvs) = &mut uvs {
             vertex.uv = Point2::from(uvs.next());
         }
-        vertex.texture_id = u32::MAX;
+        vertex.texture_id = texture_id;
+    }
+
+    if let (Some(joints), Some(joint_weights)) = (joints, joint_weights) {
+        if joints.len() != destination.len() || joint_weights.len() != destination.len() {
+            bail!("Mesh with different attribute counts");
+        }
+
+        for ((vertex, joints), joint_weights) in
+            destination.iter_mut().zip(joints).zip(joint_weights)
+        {
+            vertex.joints = Vertex::pack_joints(joints);
+            vertex.joint_weights = Vertex::pack_joint_weights(joint_weights);
+        }
     }
 
     Ok(())
 }
 
+fn read_joints(
+    primitive: &gltf::Primitive,
+    buffers: &[gltf::buffer::Data],
+) -> Result<Option<Vec<[u16; 4]>>, Error> {
+    let Some(accessor) = primitive.get(&gltf::Semantic::Joints(0))
+    else {
+        return Ok(None);
+    };
+
+    let joints = match accessor.data_type() {
+        gltf::accessor::DataType::U8 => {
+            read_accessor::<[u8; 4]>(buffers, &accessor, 1, 0)?
+                .into_iter()
+                .map(|joints| joints.map(u16::from))
+                .collect()
+        }
+        _ => read_accessor::<[u16; 4]>(buffers, &accessor, 1, 0)?,
+    };
+
+    Ok(Some(joints))
+}
+
+fn read_joint_weights(
+    primitive: &gltf::Primitive,
+    buffers: &[gltf::buffer::Data],
+) -> Result<Option<Vec<[f32; 4]>>, Error> {
+    let Some(accessor) = primitive.get(&gltf::Semantic::Weights(0))
+    else {
+        return Ok(None);
+    };
+
+    // integer weights are normalized
+    let weights = match accessor.data_type() {
+        gltf::accessor::DataType::U8 => {
+            read_accessor::<[u8; 4]>(buffers, &accessor, 1, 0)?
+                .into_iter()
+                .map(|weights| weights.map(|weight| f32::from(weight) / f32::from(u8::MAX)))
+                .collect()
+        }
+        gltf::accessor::DataType::U16 => {
+            read_accessor::<[u16; 4]>(buffers, &accessor, 1, 0)?
+                .into_iter()
+                .map(|weights| weights.map(|weight| f32::from(weight) / f32::from(u16::MAX)))
+                .collect()
+        }
+        _ => read_accessor::<[f32; 4]>(buffers, &accessor, 1, 0)?,
+    };
+
+    Ok(Some(weights))
+}
+
 fn fill_index_buffer(
     primitive: &gltf::Primitive,
-    blob: &[u8],
+    buffers: &[gltf::buffer::Data],
     index_buffer_view: &mut [u32],
     span: &MeshBufferSpan,
 ) -> Result<(), Error> {
@@ -423,22 +1004,28 @@ fn fill_index_buffer(
             .for_each(|x| *x = source.next().into());
     }
 
-    let indices = primitive
-        .indices()
-        .unwrap_or_else(|| todo!("Mesh without index buffer"));
-    let view = indices
-        .view()
-        .ok_or_else(|| eyre!("Missing view for index buffer"))?;
-
     let destination = &mut index_buffer_view[usize::try_from(span.index_buffer_offset).unwrap()..]
         [..usize::try_from(span.num_indices).unwrap()];
 
+    let Some(indices) = primitive.indices()
+    else {
+        // non-indexed primitives draw their vertices in order
+        destination
+            .iter_mut()
+            .zip(0..)
+            .for_each(|(index, i)| *index = i);
+        return Ok(());
+    };
+
     match indices.data_type() {
+        gltf::accessor::DataType::U8 => {
+            copy_index_buffer_inner(BufferReader::<u8>::new(buffers, &indices)?, destination)
+        }
         gltf::accessor::DataType::U16 => {
-            copy_index_buffer_inner(BufferReader::<u16>::new_unchecked(blob, &view), destination)
+            copy_index_buffer_inner(BufferReader::<u16>::new(buffers, &indices)?, destination)
         }
         gltf::accessor::DataType::U32 => {
-            copy_index_buffer_inner(BufferReader::<u32>::new_unchecked(blob, &view), destination)
+            copy_index_buffer_inner(BufferReader::<u32>::new(buffers, &indices)?, destination)
         }
         _ => {
             bail!(
@@ -451,40 +1038,64 @@ fn fill_index_buffer(
     Ok(())
 }
 
-struct BufferReader<'a, T> {
-    blob_slice: &'a [u8],
+/// Reads every `stride`-th element of an accessor, starting at `offset`.
+///
+/// The stride and offset are used to skip the tangents of cubic spline
+/// keyframes.
+fn read_accessor<T>(
+    buffers: &[gltf::buffer::Data],
+    accessor: &gltf::Accessor,
     stride: usize,
-    _marker: PhantomData<fn() -> T>,
+    offset: usize,
+) -> Result<Vec<T>, Error>
+where
+    T: GltfType + AnyBitPattern,
+{
+    let mut reader = BufferReader::<T>::new(buffers, accessor)?;
+
+    Ok(
+        std::iter::from_fn(|| (reader.len() > 0).then(|| reader.next()))
+            .skip(offset)
+            .step_by(stride)
+            .collect(),
+    )
 }
 
-impl<'a, T> BufferReader<'a, T> {
-    fn new_unchecked(blob: &'a [u8], view: &gltf::buffer::View) -> Self {
-        let blob_slice = &blob[view.offset()..][..view.length()];
-        let stride = view.stride().unwrap_or(size_of::<T>());
-
-        Self {
-            blob_slice,
-            stride,
-            _marker: PhantomData,
-        }
-    }
-
-    #[inline]
-    fn len(&self) -> usize {
-        self.blob_slice.len() / self.stride
-    }
+struct BufferReader<'a, T> {
+    data: &'a [u8],
+    stride: usize,
+    remaining: usize,
+    _marker: PhantomData<fn() -> T>,
 }
 
 impl<'a, T> BufferReader<'a, T>
 where
     T: GltfType,
 {
-    fn new(blob: &'a [u8], accessor: &gltf::Accessor) -> Result<Self, Error> {
+    fn new(buffers: &'a [gltf::buffer::Data], accessor: &gltf::Accessor) -> Result<Self, Error> {
         let view = accessor
             .view()
             .ok_or_else(|| eyre!("Missing view for accessor #{}", accessor.index()))?;
         T::validate(accessor)?;
-        Ok(Self::new_unchecked(blob, &view))
+
+        let buffer = buffers
+            .get(view.buffer().index())
+            .ok_or_else(|| eyre!("Missing buffer #{}", view.buffer().index()))?;
+        let data = &buffer[view.offset()..][..view.length()][accessor.offset()..];
+
+        Ok(Self {
+            data,
+            stride: view.stride().unwrap_or(size_of::<T>()),
+            remaining: accessor.count(),
+            _marker: PhantomData,
+        })
+    }
+}
+
+impl<'a, T> BufferReader<'a, T> {
+    #[inline]
+    fn len(&self) -> usize {
+        self.remaining
     }
 }
 
@@ -494,8 +1105,16 @@ where
 {
     #[inline]
     fn next(&mut self) -> T {
-        let value = *bytemuck::from_bytes::<T>(&self.blob_slice[..size_of::<T>()]);
-        self.blob_slice = &self.blob_slice[self.stride..];
+        assert!(self.remaining > 0, "read past the end of the accessor");
+
+        // external buffers aren't necessarily aligned
+        let value = bytemuck::pod_read_unaligned::<T>(&self.data[..size_of::<T>()]);
+
+        self.remaining -= 1;
+        if self.remaining > 0 {
+            self.data = &self.data[self.stride..];
+        }
+
         value
     }
 }
@@ -552,6 +1171,11 @@ macro_rules! impl_gltf_type_for_primitives {
     };
 }
 
+impl GltfType for [[f32; 4]; 4] {
+    const DATA_TYPE: gltf::accessor::DataType = gltf::accessor::DataType::F32;
+    const DIMENSIONS: gltf::accessor::Dimensions = gltf::accessor::Dimensions::Mat4;
+}
+
 impl_gltf_type_for_primitives!(
     i8 => I8,
     u8 => U8,
@@ -562,49 +1186,131 @@ impl_gltf_type_for_primitives!(
 );
 
 fn convert_transform(transform: gltf::scene::Transform) -> LocalTransform {
-    match transform {
-        gltf::scene::Transform::Matrix { matrix: _ } => {
-            // per glTF spec:
-            //
-            // > When matrix is defined, it MUST be decomposable to TRS properties.
-            //
-            // so we can implement this
-            todo!("Node with matrix transform",);
-        }
-        gltf::scene::Transform::Decomposed {
-            translation,
-            rotation,
-            scale,
-        } => {
-                // from the glTF spec:
-                //
-                // > rotation is a unit quaternion value, XYZW, in the local coordinate system, where W is the scalar.
-                //
-                // from nalgebra:
-                //
-                // > This quaternion as a 4D vector of coordinates in the [ x, y, z, w ] storage order.
-                //
-                // glTF uses a right-handed coordinate system with Z pointing in a different direction than we do, so we need to convert here
-
-                // due to change of handedness we negate X, Y, and Z. and because Z is inverted we negate it again.
-                let mut rotation = Quaternion::from(rotation);
-                rotation.coords.x *= -1.0;
-                rotation.coords.y *= -1.0;
-                let rotation = UnitQuaternion::new_unchecked(rotation);
-
-                let mut translation = Translation3::from(translation);
-                translation.z *= -1.0;
-
-                if scale != [1.0, 1.0, 1.0] {
-                    todo!("scaling: {scale:?}");
-                }
+    // per glTF spec:
+    //
+    // > When matrix is defined, it MUST be decomposable to TRS properties.
+    //
+    // so we can always work with the decomposed transform.
+    //
+    // from the glTF spec:
+    //
+    // > rotation is a unit quaternion value, XYZW, in the local coordinate system,
+    // > where W is the scalar.
+    //
+    // from nalgebra:
+    //
+    // > This quaternion as a 4D vector of coordinates in the [ x, y, z, w ] storage
+    // > order.
+    //
+    // glTF uses a right-handed coordinate system with Z pointing in a different
+    // direction than we do, so we need to convert here
+    let (translation, rotation, scale) = transform.decomposed();
+
+    LocalTransform {
+        isometry: Isometry3::from_parts(
+            convert_translation(translation),
+            convert_rotation(rotation),
+        ),
+        // flipping Z doesn't change the scale along it
+        scale: Vector3::from(scale),
+    }
+}
 
-                LocalTransform {
-                    isometry: Isometry3::from_parts(
-                        translation,
-                        rotation,
-                    ),
-                }
-            }
+/// Converts a glTF translation to our coordinate system.
+fn convert_translation(translation: [f32; 3]) -> Translation3<f32> {
+    let mut translation = Translation3::from(translation);
+    translation.z *= -1.0;
+    translation
+}
+
+/// Converts a glTF matrix (column-major) to our coordinate system.
+fn convert_matrix(matrix: [[f32; 4]; 4]) -> Matrix4<f32> {
+    // flip Z before and after
+    let flip_z = Matrix4::new_nonuniform_scaling(&Vector3::new(1.0, 1.0, -1.0));
+    flip_z * Matrix4::from(matrix) * flip_z
+}
+
+/// Converts a glTF rotation to our coordinate system.
+fn convert_rotation(rotation: [f32; 4]) -> UnitQuaternion<f32> {
+    // due to change of handedness we negate X, Y, and Z. and because Z is inverted
+    // we negate it again.
+    let mut rotation = Quaternion::from(rotation);
+    rotation.coords.x *= -1.0;
+    rotation.coords.y *= -1.0;
+
+    // animation keyframes aren't necessarily normalized
+    UnitQuaternion::new_normalize(rotation)
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_ecs::world::World;
+    use nalgebra::Vector3;
+
+    use crate::{
+        assets::Asset,
+        render::{
+            animation::Keyframes,
+            model::{
+                Model,
+                ModelImporter,
+            },
+        },
+    };
+
+    #[test]
+    fn it_imports_animations_from_an_external_buffer() {
+        let dir = std::env::temp_dir();
+        let name = format!("sandvox-model-test-{}", std::process::id());
+        let bin_path = dir.join(format!("{name}.bin"));
+        let gltf_path = dir.join(format!("{name}.gltf"));
+
+        let data: [f32; 8] = [0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 2.0, 3.0];
+        std::fs::write(&bin_path, bytemuck::cast_slice(&data)).unwrap();
+        std::fs::write(
+            &gltf_path,
+            format!(
+                r#"{{
+                    "asset": {{ "version": "2.0" }},
+                    "scene": 0,
+                    "scenes": [{{ "nodes": [0] }}],
+                    "nodes": [{{ "name": "node" }}],
+                    "buffers": [{{ "uri": "{name}.bin", "byteLength": 32 }}],
+                    "bufferViews": [
+                        {{ "buffer": 0, "byteOffset": 0, "byteLength": 8 }},
+                        {{ "buffer": 0, "byteOffset": 8, "byteLength": 24 }}
+                    ],
+                    "accessors": [
+                        {{ "bufferView": 0, "componentType": 5126, "count": 2, "type": "SCALAR", "min": [0.0], "max": [1.0] }},
+                        {{ "bufferView": 1, "componentType": 5126, "count": 2, "type": "VEC3" }}
+                    ],
+                    "animations": [{{
+                        "channels": [{{ "sampler": 0, "target": {{ "node": 0, "path": "translation" }} }}],
+                        "samplers": [{{ "input": 0, "output": 1 }}]
+                    }}]
+                }}"#
+            ),
+        )
+        .unwrap();
+
+        let model = Model::load(&gltf_path).unwrap();
+        assert_eq!(model.dependencies(), vec![bin_path.clone()]);
+
+        let mut world = World::new();
+        let mut commands = world.commands();
+        let mut importer = ModelImporter::new(&model).unwrap();
+        importer.import_default_scene(&mut commands).unwrap();
+        let animations = importer.import_animations().unwrap();
+
+        let animation = &animations.clips[0];
+        assert_eq!(animation.duration, 1.0);
+        let Keyframes::Translation(translations) = &animation.channels[0].keyframes
+        else {
+            panic!("expected translation keyframes");
+        };
+        assert_eq!(translations[1], Vector3::new(1.0, 2.0, -3.0));
+
+        std::fs::remove_file(bin_path).unwrap();
+        std::fs::remove_file(gltf_path).unwrap();
     }
 }
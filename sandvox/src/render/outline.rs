@@ -0,0 +1,387 @@
+//! Box outlines, e.g. to highlight the block that the player is looking at.
+//!
+//! Outlines are drawn as lines in their own phase after the opaque geometry
+//! and the skybox. They're depth-tested, but don't write depth. Boxes can also
+//! be filled with a translucent color, e.g. to show a selected region.
+
+use bevy_ecs::{
+    component::Component,
+    name::NameOrEntity,
+    query::{
+        ROQueryItem,
+        With,
+        Without,
+    },
+    resource::Resource,
+    schedule::IntoScheduleConfigs,
+    system::{
+        Commands,
+        Local,
+        Populated,
+        Query,
+        Res,
+        ResMut,
+        SystemParamItem,
+    },
+};
+use bytemuck::{
+    Pod,
+    Zeroable,
+};
+use color_eyre::eyre::Error;
+use nalgebra::{
+    Matrix4,
+    Vector3,
+};
+use palette::{
+    LinSrgba,
+    Srgba,
+};
+
+use crate::{
+    ecs::{
+        plugin::{
+            Dependencies,
+            Plugin,
+            WorldBuilder,
+        },
+        schedule,
+        transform::GlobalTransform,
+    },
+    render::{
+        RenderSystems,
+        command::{
+            AddRenderFunction,
+            RenderFunction,
+        },
+        pass::{
+            context::RenderPass,
+            main_pass::{
+                MainPass,
+                MainPassLayout,
+                MainPassPlugin,
+                MainPassSystems,
+            },
+            phase,
+        },
+        remove_on_gpu_setup,
+        render_target::RenderTarget,
+        staging::Staging,
+        surface::Surface,
+    },
+    wgpu::{
+        WgpuContext,
+        buffer::TypedArrayBuffer,
+    },
+};
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct OutlinePlugin;
+
+impl Plugin for OutlinePlugin {
+    fn dependencies(&self, dependencies: &mut Dependencies) -> Result<(), Error> {
+        dependencies.require_plugin::<MainPassPlugin>();
+        Ok(())
+    }
+
+    fn setup(&self, builder: &mut WorldBuilder) -> Result<(), Error> {
+        builder
+            .add_systems(
+                schedule::GpuSetup,
+                (
+                    (
+                        create_pipeline_layout.after(MainPassSystems::Prepare),
+                        create_outline_buffer,
+                    )
+                        .in_set(RenderSystems::Setup),
+                    remove_on_gpu_setup::<OutlinePipeline>,
+                ),
+            )
+            .add_systems(
+                schedule::Render,
+                (create_pipeline, update_outline_buffer).in_set(RenderSystems::BeginFrame),
+            )
+            .add_render_function::<phase::Outline, _>(RenderOutlines);
+
+        Ok(())
+    }
+}
+
+/// Draws the edges of a box that is centered on the entity.
+#[derive(Clone, Copy, Debug, Component)]
+pub struct Outline {
+    /// Size of the box. This is multiplied by the scale of the entity's
+    /// transform.
+    pub size: Vector3<f32>,
+
+    pub color: Srgba<f32>,
+
+    /// Color of the box's faces, which are drawn behind the edges.
+    pub fill: Option<Srgba<f32>>,
+}
+
+#[derive(Debug, Resource)]
+struct OutlineLayout {
+    layout: wgpu::PipelineLayout,
+    shader: wgpu::ShaderModule,
+    bind_group_layout: wgpu::BindGroupLayout,
+}
+
+#[derive(Debug, Component)]
+struct OutlinePipeline {
+    pipeline: wgpu::RenderPipeline,
+    fill_pipeline: wgpu::RenderPipeline,
+}
+
+#[derive(Debug, Resource)]
+struct OutlineBuffer {
+    buffer: TypedArrayBuffer<OutlineData>,
+    bind_group: Option<wgpu::BindGroup>,
+    num_outlines: u32,
+    any_filled: bool,
+}
+
+#[derive(Clone, Copy, Debug, Pod, Zeroable)]
+#[repr(C)]
+struct OutlineData {
+    model_matrix: Matrix4<f32>,
+    color: LinSrgba<f32>,
+
+    /// The alpha channel is 0 if the outline isn't filled.
+    fill_color: LinSrgba<f32>,
+}
+
+/// Number of vertices per outline: 12 edges with 2 vertices each.
+const VERTICES_PER_OUTLINE: u32 = 24;
+
+/// Number of vertices per filled box: 6 faces with 2 triangles each.
+const VERTICES_PER_FILL: u32 = 36;
+
+fn create_pipeline_layout(
+    wgpu: Res<WgpuContext>,
+    main_pass_layout: Res<MainPassLayout>,
+    mut commands: Commands,
+) {
+    let bind_group_layout =
+        wgpu.device
+            .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("outline"),
+                entries: &[wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: true },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                }],
+            });
+
+    let layout = wgpu
+        .device
+        .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("outline"),
+            bind_group_layouts: &[&main_pass_layout.bind_group_layout, &bind_group_layout],
+            immediate_size: 0,
+        });
+
+    let shader = wgpu
+        .device
+        .create_shader_module(wgpu::include_wgsl!("outline.wgsl"));
+
+    commands.insert_resource(OutlineLayout {
+        layout,
+        shader,
+        bind_group_layout,
+    });
+}
+
+fn create_outline_buffer(wgpu: Res<WgpuContext>, mut commands: Commands) {
+    let buffer = TypedArrayBuffer::new(
+        wgpu.device.clone(),
+        "outline buffer",
+        wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+    );
+
+    commands.insert_resource(OutlineBuffer {
+        buffer,
+        bind_group: None,
+        num_outlines: 0,
+        any_filled: false,
+    });
+}
+
+fn create_pipeline(
+    wgpu: Res<WgpuContext>,
+    pipeline_layout: Res<OutlineLayout>,
+    surfaces: Populated<(NameOrEntity, &Surface)>,
+    cameras: Populated<(NameOrEntity, &RenderTarget), Without<OutlinePipeline>>,
+    main_passes: Query<(), With<MainPass>>,
+    mut commands: Commands,
+) {
+    for (camera_entity, render_target) in cameras {
+        if !main_passes.contains(camera_entity.entity) {
+            continue;
+        }
+
+        if let Ok((surface_entity, surface)) = surfaces.get(render_target.0) {
+            tracing::debug!(surface = %surface_entity, camera = %camera_entity, "creating outline render pipeline for surface");
+
+            let create_pipeline = |label, entry_point, topology| {
+                wgpu.device
+                    .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                        label: Some(label),
+                        layout: Some(&pipeline_layout.layout),
+                        vertex: wgpu::VertexState {
+                            module: &pipeline_layout.shader,
+                            entry_point: Some(entry_point),
+                            compilation_options: Default::default(),
+                            buffers: &[],
+                        },
+                        // faces are visible from the inside too
+                        primitive: wgpu::PrimitiveState {
+                            topology,
+                            strip_index_format: None,
+                            front_face: wgpu::FrontFace::Ccw,
+                            cull_mode: None,
+                            unclipped_depth: false,
+                            polygon_mode: wgpu::PolygonMode::Fill,
+                            conservative: false,
+                        },
+                        depth_stencil: Some(wgpu::DepthStencilState {
+                            format: surface.depth_format(),
+                            depth_write_enabled: false,
+                            depth_compare: surface.depth_compare(wgpu::CompareFunction::LessEqual),
+                            stencil: Default::default(),
+                            bias: Default::default(),
+                        }),
+                        multisample: Default::default(),
+                        fragment: Some(wgpu::FragmentState {
+                            module: &pipeline_layout.shader,
+                            entry_point: Some("outline_fragment"),
+                            compilation_options: Default::default(),
+                            targets: &[Some(wgpu::ColorTargetState {
+                                format: surface.surface_format(),
+                                blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                                write_mask: wgpu::ColorWrites::ALL,
+                            })],
+                        }),
+                        multiview_mask: None,
+                        cache: None,
+                    })
+            };
+
+            let pipeline = create_pipeline(
+                "outline",
+                "outline_vertex",
+                wgpu::PrimitiveTopology::LineList,
+            );
+            let fill_pipeline = create_pipeline(
+                "outline fill",
+                "fill_vertex",
+                wgpu::PrimitiveTopology::TriangleList,
+            );
+
+            commands
+                .entity(camera_entity.entity)
+                .insert(OutlinePipeline {
+                    pipeline,
+                    fill_pipeline,
+                });
+        }
+    }
+}
+
+/// Writes all outlines into the outline buffer.
+///
+/// Like billboards, outlines are written every frame, so that we don't have to
+/// track removed outlines.
+#[profiling::function]
+fn update_outline_buffer(
+    wgpu: Res<WgpuContext>,
+    layout: Res<OutlineLayout>,
+    mut outline_buffer: ResMut<OutlineBuffer>,
+    outlines: Query<(&Outline, &GlobalTransform)>,
+    mut outline_data: Local<Vec<OutlineData>>,
+    mut staging: ResMut<Staging>,
+) {
+    assert!(outline_data.is_empty());
+
+    outline_data.extend(outlines.iter().map(|(outline, transform)| {
+        OutlineData {
+            model_matrix: transform.to_matrix() * Matrix4::new_nonuniform_scaling(&outline.size),
+            color: outline.color.into_linear(),
+            fill_color: outline
+                .fill
+                .map_or_else(Zeroable::zeroed, |fill| fill.into_linear()),
+        }
+    }));
+
+    let outline_buffer = &mut *outline_buffer;
+    outline_buffer.num_outlines = outline_data.len().try_into().unwrap();
+    outline_buffer.any_filled = outlines.iter().any(|(outline, _)| outline.fill.is_some());
+
+    if outline_data.is_empty() {
+        return;
+    }
+
+    let reallocated = outline_buffer
+        .buffer
+        .write_all(&outline_data, |_| {}, &mut *staging);
+
+    if reallocated || outline_buffer.bind_group.is_none() {
+        outline_buffer.bind_group =
+            Some(wgpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("outline"),
+                layout: &layout.bind_group_layout,
+                entries: &[wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: outline_buffer.buffer.buffer().as_entire_binding(),
+                }],
+            }));
+    }
+
+    outline_data.clear();
+}
+
+#[derive(Debug)]
+struct RenderOutlines;
+
+impl RenderFunction for RenderOutlines {
+    type Param = Res<'static, OutlineBuffer>;
+    type ViewQuery = &'static OutlinePipeline;
+    type ItemQuery = ();
+
+    #[profiling::function]
+    fn render(
+        &self,
+        param: SystemParamItem<Self::Param>,
+        render_pass: &mut RenderPass<'_>,
+        view: ROQueryItem<Self::ViewQuery>,
+        items: Query<Self::ItemQuery>,
+    ) {
+        let _ = items;
+        let outline_buffer = param;
+
+        if outline_buffer.num_outlines > 0
+            && let Some(bind_group) = &outline_buffer.bind_group
+        {
+            let span = render_pass.enter_span("outline");
+            render_pass.set_bind_group(1, Some(bind_group), &[]);
+
+            if outline_buffer.any_filled {
+                // outlines without fill are drawn with 0 alpha
+                render_pass.set_pipeline(&view.fill_pipeline);
+                render_pass.draw(0..(outline_buffer.num_outlines * VERTICES_PER_FILL), 0..1);
+            }
+
+            render_pass.set_pipeline(&view.pipeline);
+            render_pass.draw(
+                0..(outline_buffer.num_outlines * VERTICES_PER_OUTLINE),
+                0..1,
+            );
+            render_pass.exit_span(span);
+        }
+    }
+}
@@ -0,0 +1,122 @@
+
+struct MainPassUniform {
+    camera: Camera,
+    time: f32,
+    exposure: f32,
+    // padding: 8 bytes
+    light: Light,
+    fog: Fog,
+    overlay: Overlay,
+}
+
+struct Camera {
+    projection: mat4x4f,
+    projection_inverse: mat4x4f,
+    view: mat4x4f,
+    view_inverse: mat4x4f,
+    position: vec4f,
+}
+
+struct Light {
+    sun_direction: vec4f,
+    sun_color: vec4f,
+    moon_direction: vec4f,
+    moon_color: vec4f,
+    ambient_color: vec4f,
+}
+
+struct Fog {
+    // alpha is 0 if there is no fog
+    color: vec4f,
+    start: f32,
+    end: f32,
+    // padding: 8 bytes
+}
+
+struct Overlay {
+    // alpha is 0 if there is no overlay
+    color: vec4f,
+    distortion: f32,
+    // padding: 12 bytes
+}
+
+@group(0)
+@binding(0)
+var<uniform> main_pass_uniform: MainPassUniform;
+
+struct OutlineData {
+    model_matrix: mat4x4f,
+    color: vec4f,
+    // alpha is 0 if the outline isn't filled
+    fill_color: vec4f,
+}
+
+@group(1)
+@binding(0)
+var<storage, read> outline_data: array<OutlineData>;
+
+
+// corners of the unit cube are indexed by their coordinates: bit 0 is x, bit 1 is y
+// and bit 2 is z. each edge connects two corners that differ in one bit.
+const EDGE_CORNERS = array(
+    // x
+    0u, 1u, 2u, 3u, 4u, 5u, 6u, 7u,
+    // y
+    0u, 2u, 1u, 3u, 4u, 6u, 5u, 7u,
+    // z
+    0u, 4u, 1u, 5u, 2u, 6u, 3u, 7u,
+);
+
+// two triangles for each face, with the corners indexed like above
+const FACE_CORNERS = array(
+    // -x, +x
+    0u, 2u, 6u, 0u, 6u, 4u,
+    1u, 3u, 7u, 1u, 7u, 5u,
+    // -y, +y
+    0u, 1u, 5u, 0u, 5u, 4u,
+    2u, 3u, 7u, 2u, 7u, 6u,
+    // -z, +z
+    0u, 1u, 3u, 0u, 3u, 2u,
+    4u, 5u, 7u, 4u, 7u, 6u,
+);
+
+@vertex
+fn outline_vertex(@builtin(vertex_index) vertex_index: u32) -> OutlineOutput {
+    let outline = outline_data[vertex_index / 24];
+    let corner = EDGE_CORNERS[vertex_index % 24];
+    return OutlineOutput(corner_position(outline, corner), outline.color);
+}
+
+@vertex
+fn fill_vertex(@builtin(vertex_index) vertex_index: u32) -> OutlineOutput {
+    let outline = outline_data[vertex_index / 36];
+    let corner = FACE_CORNERS[vertex_index % 36];
+    return OutlineOutput(corner_position(outline, corner), outline.fill_color);
+}
+
+fn corner_position(outline: OutlineData, corner: u32) -> vec4f {
+    let local_position = vec3f(
+        f32(corner & 1),
+        f32((corner >> 1) & 1),
+        f32((corner >> 2) & 1),
+    ) - 0.5;
+
+    return main_pass_uniform.camera.projection
+        * main_pass_uniform.camera.view
+        * outline.model_matrix
+        * vec4f(local_position, 1);
+}
+
+struct OutlineOutput {
+    @builtin(position)
+    position: vec4f,
+
+    @location(0)
+    @interpolate(flat, either)
+    color: vec4f,
+}
+
+@fragment
+fn outline_fragment(input: OutlineOutput) -> @location(0) vec4f {
+    return input.color;
+}
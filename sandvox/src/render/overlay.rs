@@ -0,0 +1,248 @@
+//! Fullscreen overlays, e.g. to tint the screen while the camera is under
+//! water.
+//!
+//! The overlay is blended over everything else in its own phase at the end of
+//! the main pass. Its distortion is applied by the mesh shaders, which wobble
+//! the geometry a bit.
+
+use bevy_ecs::{
+    component::Component,
+    name::NameOrEntity,
+    query::{
+        ROQueryItem,
+        With,
+        Without,
+    },
+    resource::Resource,
+    schedule::IntoScheduleConfigs,
+    system::{
+        Commands,
+        Populated,
+        Query,
+        Res,
+        SystemParamItem,
+    },
+};
+use bytemuck::{
+    Pod,
+    Zeroable,
+};
+use color_eyre::eyre::Error;
+use nalgebra::Vector4;
+use palette::LinSrgba;
+
+use crate::{
+    ecs::{
+        plugin::{
+            Dependencies,
+            Plugin,
+            WorldBuilder,
+        },
+        schedule,
+    },
+    render::{
+        RenderSystems,
+        command::{
+            AddRenderFunction,
+            RenderFunction,
+        },
+        pass::{
+            context::RenderPass,
+            main_pass::{
+                MainPass,
+                MainPassLayout,
+                MainPassPlugin,
+                MainPassSystems,
+            },
+            phase,
+        },
+        remove_on_gpu_setup,
+        render_target::RenderTarget,
+        surface::Surface,
+    },
+    wgpu::WgpuContext,
+};
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct OverlayPlugin;
+
+impl Plugin for OverlayPlugin {
+    fn dependencies(&self, dependencies: &mut Dependencies) -> Result<(), Error> {
+        dependencies.require_plugin::<MainPassPlugin>();
+        Ok(())
+    }
+
+    fn setup(&self, builder: &mut WorldBuilder) -> Result<(), Error> {
+        builder
+            .add_systems(
+                schedule::GpuSetup,
+                (
+                    create_pipeline_layout
+                        .after(MainPassSystems::Prepare)
+                        .in_set(RenderSystems::Setup),
+                    remove_on_gpu_setup::<OverlayPipeline>,
+                ),
+            )
+            .add_systems(
+                schedule::Render,
+                create_pipeline.in_set(RenderSystems::BeginFrame),
+            )
+            .add_render_function::<phase::Overlay, _>(RenderOverlay);
+
+        Ok(())
+    }
+}
+
+/// Tints and distorts everything a camera sees.
+#[derive(Clone, Copy, Debug, PartialEq, Component)]
+pub struct ScreenOverlay {
+    /// The tint is blended over the scene with the color's alpha.
+    pub color: LinSrgba<f32>,
+
+    /// How much the scene wobbles, as a fraction of the screen size.
+    pub distortion: f32,
+}
+
+#[derive(Clone, Copy, Debug, Pod, Zeroable)]
+#[repr(C)]
+pub struct OverlayData {
+    /// The alpha channel is 0 if there is no overlay.
+    pub color: Vector4<f32>,
+    pub distortion: f32,
+    _padding: [u32; 3],
+}
+
+impl From<Option<&ScreenOverlay>> for OverlayData {
+    fn from(value: Option<&ScreenOverlay>) -> Self {
+        value.map_or_else(Zeroable::zeroed, |overlay| {
+            Self {
+                color: Vector4::new(
+                    overlay.color.red,
+                    overlay.color.green,
+                    overlay.color.blue,
+                    overlay.color.alpha,
+                ),
+                distortion: overlay.distortion,
+                _padding: Default::default(),
+            }
+        })
+    }
+}
+
+#[derive(Debug, Resource)]
+struct OverlayLayout {
+    layout: wgpu::PipelineLayout,
+    shader: wgpu::ShaderModule,
+}
+
+#[derive(Debug, Component)]
+struct OverlayPipeline {
+    pipeline: wgpu::RenderPipeline,
+}
+
+fn create_pipeline_layout(
+    wgpu: Res<WgpuContext>,
+    main_pass_layout: Res<MainPassLayout>,
+    mut commands: Commands,
+) {
+    let layout = wgpu
+        .device
+        .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("overlay"),
+            bind_group_layouts: &[&main_pass_layout.bind_group_layout],
+            immediate_size: 0,
+        });
+
+    let shader = wgpu
+        .device
+        .create_shader_module(wgpu::include_wgsl!("overlay.wgsl"));
+
+    commands.insert_resource(OverlayLayout { layout, shader });
+}
+
+fn create_pipeline(
+    wgpu: Res<WgpuContext>,
+    pipeline_layout: Res<OverlayLayout>,
+    surfaces: Populated<(NameOrEntity, &Surface)>,
+    cameras: Populated<(NameOrEntity, &RenderTarget), Without<OverlayPipeline>>,
+    main_passes: Query<(), With<MainPass>>,
+    mut commands: Commands,
+) {
+    for (camera_entity, render_target) in cameras {
+        if !main_passes.contains(camera_entity.entity) {
+            continue;
+        }
+
+        if let Ok((surface_entity, surface)) = surfaces.get(render_target.0) {
+            tracing::debug!(surface = %surface_entity, camera = %camera_entity, "creating overlay render pipeline for surface");
+
+            let pipeline = wgpu
+                .device
+                .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                    label: Some("overlay"),
+                    layout: Some(&pipeline_layout.layout),
+                    vertex: wgpu::VertexState {
+                        module: &pipeline_layout.shader,
+                        entry_point: Some("overlay_vertex"),
+                        compilation_options: Default::default(),
+                        buffers: &[],
+                    },
+                    primitive: Default::default(),
+                    // the main pass has a depth attachment, but the overlay covers everything
+                    depth_stencil: Some(wgpu::DepthStencilState {
+                        format: surface.depth_format(),
+                        depth_write_enabled: false,
+                        depth_compare: wgpu::CompareFunction::Always,
+                        stencil: Default::default(),
+                        bias: Default::default(),
+                    }),
+                    multisample: Default::default(),
+                    fragment: Some(wgpu::FragmentState {
+                        module: &pipeline_layout.shader,
+                        entry_point: Some("overlay_fragment"),
+                        compilation_options: Default::default(),
+                        targets: &[Some(wgpu::ColorTargetState {
+                            format: surface.surface_format(),
+                            blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                            write_mask: wgpu::ColorWrites::ALL,
+                        })],
+                    }),
+                    multiview_mask: None,
+                    cache: None,
+                });
+
+            commands
+                .entity(camera_entity.entity)
+                .insert(OverlayPipeline { pipeline });
+        }
+    }
+}
+
+/// Draws the overlay for cameras with a [`ScreenOverlay`].
+#[derive(Debug)]
+struct RenderOverlay;
+
+impl RenderFunction for RenderOverlay {
+    type Param = ();
+    type ViewQuery = (&'static OverlayPipeline, &'static ScreenOverlay);
+    type ItemQuery = ();
+
+    #[profiling::function]
+    fn render(
+        &self,
+        param: SystemParamItem<Self::Param>,
+        render_pass: &mut RenderPass<'_>,
+        view: ROQueryItem<Self::ViewQuery>,
+        items: Query<Self::ItemQuery>,
+    ) {
+        let _ = (param, items);
+        let (pipeline, overlay) = view;
+
+        if overlay.color.alpha > 0.0 {
+            let span = render_pass.enter_span("overlay");
+            render_pass.set_pipeline(&pipeline.pipeline);
+            render_pass.draw(0..3, 0..1);
+            render_pass.exit_span(span);
+        }
+    }
+}
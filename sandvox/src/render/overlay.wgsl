@@ -0,0 +1,73 @@
+
+struct MainPassUniform {
+    camera: Camera,
+    time: f32,
+    exposure: f32,
+    // padding: 8 bytes
+    light: Light,
+    fog: Fog,
+    overlay: Overlay,
+}
+
+struct Camera {
+    projection: mat4x4f,
+    projection_inverse: mat4x4f,
+    view: mat4x4f,
+    view_inverse: mat4x4f,
+    position: vec4f,
+}
+
+struct Light {
+    sun_direction: vec4f,
+    sun_color: vec4f,
+    moon_direction: vec4f,
+    moon_color: vec4f,
+    ambient_color: vec4f,
+}
+
+struct Fog {
+    // alpha is 0 if there is no fog
+    color: vec4f,
+    start: f32,
+    end: f32,
+    // padding: 8 bytes
+}
+
+struct Overlay {
+    // alpha is 0 if there is no overlay
+    color: vec4f,
+    distortion: f32,
+    // padding: 12 bytes
+}
+
+@group(0)
+@binding(0)
+var<uniform> main_pass_uniform: MainPassUniform;
+
+
+@vertex
+fn overlay_vertex(@builtin(vertex_index) vertex_index: u32) -> OverlayOutput {
+    // a triangle that covers the whole screen
+    let uv = vec2f(f32((vertex_index << 1) & 2), f32(vertex_index & 2));
+    let position = uv * 2 - 1;
+    return OverlayOutput(vec4f(position, 0, 1), position);
+}
+
+struct OverlayOutput {
+    @builtin(position)
+    position: vec4f,
+
+    @location(0)
+    ndc: vec2f,
+}
+
+@fragment
+fn overlay_fragment(input: OverlayOutput) -> @location(0) vec4f {
+    let overlay = main_pass_uniform.overlay;
+
+    // a bit denser towards the edges of the screen
+    let vignette = 1 + 0.5 * smoothstep(0.5, 1.5, length(input.ndc));
+    let alpha = saturate(overlay.color.a * vignette);
+
+    return vec4f(overlay.color.rgb * main_pass_uniform.exposure, alpha);
+}
@@ -22,7 +22,10 @@ use crate::{
         RenderPassProfiler,
         SpanId,
     },
-    render::staging::Staging,
+    render::staging::{
+        Staging,
+        StagingStatistics,
+    },
     wgpu::WgpuContext,
 };
 
@@ -128,11 +131,19 @@ pub fn flush_command_buffers(
     wgpu: Res<WgpuContext>,
     mut pending: ResMut<PendingCommandBuffers>,
     mut staging: ResMut<Staging>,
+    mut staging_statistics: ResMut<StagingStatistics>,
 ) {
     // we want all the staged transfers to happen first
     //
     // todo: how does queue ordering work exactly?
-    let command_buffers = staging.is_changed().then(|| staging.flush(&wgpu).finish());
+    let (command_buffers, throughput) = if staging.is_changed() {
+        let (command_encoder, throughput) = staging.flush(&wgpu);
+        (Some(command_encoder.finish()), throughput)
+    }
+    else {
+        (None, Default::default())
+    };
+    staging_statistics.end_frame(throughput);
 
     // then take all other pending command buffers
     let command_buffers = command_buffers
@@ -4,6 +4,7 @@ use bevy_ecs::{
     name::NameOrEntity,
     query::{
         Has,
+        QueryData,
         With,
         Without,
     },
@@ -11,7 +12,10 @@ use bevy_ecs::{
     schedule::{
         IntoScheduleConfigs,
         SystemSet,
-        common_conditions::resource_changed,
+        common_conditions::{
+            resource_changed,
+            resource_exists,
+        },
     },
     system::{
         Commands,
@@ -33,6 +37,7 @@ use crate::{
     app::Time,
     ecs::{
         plugin::{
+            Dependencies,
             Plugin,
             WorldBuilder,
         },
@@ -41,6 +46,7 @@ use crate::{
     render::{
         DefaultAtlas,
         DefaultSampler,
+        RenderConfig,
         RenderFunctions,
         RenderPlugin,
         RenderSystems,
@@ -49,17 +55,51 @@ use crate::{
             Camera,
             CameraData,
         },
+        exposure::Exposure,
+        fog::{
+            Fog,
+            FogData,
+        },
+        lighting::{
+            LightData,
+            SceneLighting,
+        },
+        overlay::{
+            OverlayData,
+            ScreenOverlay,
+        },
         pass::{
             context::RenderContext,
             phase,
+            statistics::{
+                MainPassStatistics,
+                MainPassStatisticsData,
+                PipelineStatistics,
+                PipelineStatisticsPool,
+                create_main_pass_statistics,
+                create_pipeline_statistics_pool,
+                request_pipeline_statistics_features,
+            },
+        },
+        remove_on_gpu_setup,
+        render_target::{
+            PixelViewport,
+            RenderTarget,
+            Viewport,
+            pixel_viewport,
         },
-        render_target::RenderTarget,
         staging::Staging,
         surface::Surface,
     },
     wgpu::{
         WgpuContext,
+        WgpuContextBuilder,
+        WgpuSystems,
         buffer::WriteStaging,
+        query::{
+            QuerySetAllocation,
+            QuerySetTransaction,
+        },
     },
 };
 
@@ -67,21 +107,39 @@ use crate::{
 pub struct MainPassPlugin;
 
 impl Plugin for MainPassPlugin {
+    fn dependencies(&self, dependencies: &mut Dependencies) -> Result<(), Error> {
+        dependencies.require_plugin::<RenderPlugin>();
+        Ok(())
+    }
+
     fn setup(&self, builder: &mut WorldBuilder) -> Result<(), Error> {
         builder
-            .require_plugin::<RenderPlugin>()
+            .init_resource::<SceneLighting>()
             .add_systems(
-                schedule::Startup,
+                schedule::GpuSetup,
                 (
-                    (create_layout, create_main_pass).chain(),
-                    update_main_pass_uniform,
-                )
-                    .in_set(MainPassSystems::Prepare),
+                    (
+                        (create_layout, create_main_pass).chain(),
+                        update_main_pass_uniform,
+                        create_pipeline_statistics_pool,
+                    )
+                        .in_set(MainPassSystems::Prepare),
+                    request_pipeline_statistics_features
+                        .in_set(WgpuSystems::RequestFeatures)
+                        .run_if(resource_exists::<WgpuContextBuilder>),
+                    remove_on_gpu_setup::<MainPass>,
+                    remove_on_gpu_setup::<MainPassUniform>,
+                ),
             )
             .add_systems(
                 schedule::Render,
                 (
-                    (create_layout, create_main_pass)
+                    (
+                        create_layout,
+                        create_main_pass,
+                        create_main_pass_statistics
+                            .run_if(resource_exists::<PipelineStatisticsPool>),
+                    )
                         .chain()
                         .in_set(MainPassSystems::Prepare),
                     render_main_pass.in_set(MainPassSystems::Render),
@@ -93,7 +151,7 @@ impl Plugin for MainPassPlugin {
                 ),
             )
             .configure_system_sets(
-                schedule::Startup,
+                schedule::GpuSetup,
                 MainPassSystems::Prepare.in_set(RenderSystems::Setup),
             )
             .configure_system_sets(
@@ -125,14 +183,27 @@ pub struct MainPass {
 
 /// Attach to camera to enable depth prepass
 ///
-/// This must be attached when the camera is created as e.g. the mesh pipeline
-/// only initializes the depth prepass pipeline then.
+/// The depth prepass renders the depth of everything in the opaque phase, so
+/// that the opaque phase only shades visible fragments. Pipelines that depend
+/// on this are recreated when it's attached or removed.
 #[derive(Debug, Component)]
 pub struct DepthPrepass;
 
 /// Attach to camera to render wireframes
-#[derive(Debug, Component)]
-pub struct Wireframe;
+///
+/// Wireframes are drawn in their own debug phase. The edges are computed from
+/// barycentric coordinates in the fragment shader, so the meshes are drawn
+/// with the same triangles as in the opaque phase.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Component)]
+pub enum Wireframe {
+    /// Draw the wireframe on top of the shaded meshes.
+    #[default]
+    Overlay,
+
+    /// Only draw the wireframe. This skips the opaque phase, so e.g.
+    /// billboards aren't drawn either.
+    Only,
+}
 
 #[derive(Debug, Resource)]
 pub struct MainPassLayout {
@@ -150,7 +221,14 @@ pub struct MainPassUniform {
 pub struct MainPassUniformData {
     pub camera: CameraData,
     pub time: f32,
-    _padding: [u32; 3],
+
+    /// Multiplier from the camera's [`Exposure`].
+    pub exposure: f32,
+
+    _padding: [u32; 2],
+    pub light: LightData,
+    pub fog: FogData,
+    pub overlay: OverlayData,
 }
 
 #[profiling::function]
@@ -206,14 +284,27 @@ fn create_layout(wgpu: Res<WgpuContext>, mut commands: Commands) {
     commands.insert_resource(MainPassLayout { bind_group_layout });
 }
 
+/// The camera's components that go into its [`MainPassUniform`].
+#[derive(QueryData)]
+struct CameraEffects {
+    exposure: Option<&'static Exposure>,
+    fog: Option<&'static Fog>,
+    overlay: Option<&'static ScreenOverlay>,
+}
+
 #[profiling::function]
 fn update_main_pass_uniform(
-    uniforms: Populated<&mut MainPassUniform>,
+    uniforms: Populated<(&mut MainPassUniform, CameraEffects)>,
     mut staging: ResMut<Staging>,
     time: Res<Time>,
+    lighting: Res<SceneLighting>,
 ) {
-    for mut uniform in uniforms {
+    for (mut uniform, effects) in uniforms {
         uniform.data.time = time.tick_start_seconds();
+        uniform.data.exposure = effects.exposure.map_or(1.0, Exposure::multiplier);
+        uniform.data.light = LightData::from(&*lighting);
+        uniform.data.fog = FogData::from(effects.fog);
+        uniform.data.overlay = OverlayData::from(effects.overlay);
 
         // update frame uniform buffer
         staging
@@ -296,6 +387,8 @@ struct MainPassRenderFunctions<'w, 's> {
             RenderFunctions<'w, 's, phase::DepthPrepass>,
             RenderFunctions<'w, 's, phase::Wireframe>,
             RenderFunctions<'w, 's, phase::Skybox>,
+            RenderFunctions<'w, 's, phase::Outline>,
+            RenderFunctions<'w, 's, phase::Overlay>,
         ),
     >,
 }
@@ -316,6 +409,14 @@ impl<'w, 's> MainPassRenderFunctions<'w, 's> {
     fn skybox(&mut self) -> RenderFunctions<'_, '_, phase::Skybox> {
         self.set.p3()
     }
+
+    fn outline(&mut self) -> RenderFunctions<'_, '_, phase::Outline> {
+        self.set.p4()
+    }
+
+    fn overlay(&mut self) -> RenderFunctions<'_, '_, phase::Overlay> {
+        self.set.p5()
+    }
 }
 
 #[profiling::function]
@@ -325,8 +426,9 @@ fn render_main_pass(
         (
             NameOrEntity,
             &RenderTarget,
+            Option<&Viewport>,
             &MainPass,
-            Has<Wireframe>,
+            Option<&Wireframe>,
             Has<DepthPrepass>,
         ),
         With<Camera>,
@@ -335,10 +437,17 @@ fn render_main_pass(
     mut render_functions: MainPassRenderFunctions,
     any_wireframe: Query<(), (With<MainPass>, With<Wireframe>)>,
     any_depth_prepass: Query<(), (With<MainPass>, With<DepthPrepass>)>,
+    config: Res<RenderConfig>,
+    statistics_pool: Option<Res<PipelineStatisticsPool>>,
+    statistics: Query<&MainPassStatistics>,
 ) {
     let any_wireframe = !any_wireframe.is_empty();
     let any_depth_prepass = !any_depth_prepass.is_empty();
 
+    let mut statistics_queries = statistics_pool
+        .filter(|_| config.pipeline_statistics)
+        .map(|pool| (pool.0.begin(), vec![]));
+
     // prepare
     render_functions.opaque().prepare();
     if any_depth_prepass {
@@ -349,37 +458,111 @@ fn render_main_pass(
     }
 
     render_functions.skybox().prepare();
+    render_functions.outline().prepare();
+    render_functions.overlay().prepare();
 
-    for (camera_entity, render_target, main_pass, wireframe, depth_prepass) in cameras {
+    for (camera_entity, render_target, viewport, main_pass, wireframe, depth_prepass) in cameras {
         // get target texture (and clear color)
         // todo: this should work with any kind of target texture
         let surface = surfaces.get(render_target.0).unwrap();
+        if surface.surface_texture().is_none() {
+            // no texture to render to this frame
+            continue;
+        }
+
+        let viewport = pixel_viewport(viewport, surface.size());
+        if viewport.is_empty() {
+            continue;
+        }
 
-        if depth_prepass {
+        let camera_statistics = statistics.get(camera_entity.entity).ok();
+        let mut statistics_transaction = statistics_queries
+            .as_mut()
+            .filter(|_| camera_statistics.is_some())
+            .map(|(transaction, _)| transaction);
+
+        let depth_prepass_query = if depth_prepass {
             assert!(any_depth_prepass);
 
             run_z_prepass_on_surface(
                 &mut render_context,
                 &mut render_functions,
                 surface,
+                viewport,
                 main_pass,
                 camera_entity.entity,
-            );
+                statistics_transaction.as_deref_mut(),
+            )
         }
+        else {
+            None
+        };
 
         // !any_wireframe => !wireframe
-        assert!(any_wireframe || !wireframe);
+        assert!(any_wireframe || wireframe.is_none());
 
-        run_main_pass_on_surface(
+        let main_pass_query = run_main_pass_on_surface(
             &mut render_context,
             &mut render_functions,
             surface,
+            viewport,
             main_pass,
             camera_entity.entity,
-            wireframe,
+            wireframe.copied(),
             depth_prepass,
+            statistics_transaction,
         );
+
+        if let (Some((_, cameras)), Some(camera_statistics), Some(main_pass_query)) =
+            (&mut statistics_queries, camera_statistics, main_pass_query)
+        {
+            cameras.push(CameraStatisticsQueries {
+                statistics: camera_statistics.clone(),
+                depth_prepass: depth_prepass_query,
+                main_pass: main_pass_query,
+                num_pixels: u64::from(viewport.size.x) * u64::from(viewport.size.y),
+            });
+        }
     }
+
+    if let Some((transaction, cameras)) = statistics_queries {
+        transaction.finish(render_context.command_encoder(), move |resolved| {
+            for camera in cameras {
+                camera.statistics.set(MainPassStatisticsData {
+                    depth_prepass: camera
+                        .depth_prepass
+                        .map(|query| PipelineStatistics::from_bytes(resolved.get(query))),
+                    main_pass: PipelineStatistics::from_bytes(resolved.get(camera.main_pass)),
+                    num_pixels: camera.num_pixels,
+                });
+            }
+        });
+    }
+}
+
+/// The pipeline statistics queries of one camera, until they're resolved.
+#[derive(Debug)]
+struct CameraStatisticsQueries {
+    statistics: MainPassStatistics,
+    depth_prepass: Option<QuerySetAllocation>,
+    main_pass: QuerySetAllocation,
+    num_pixels: u64,
+}
+
+/// Begins a pipeline statistics query on the render pass, if statistics are
+/// collected.
+fn begin_statistics_query(
+    render_pass: &mut wgpu::RenderPass,
+    transaction: Option<&mut QuerySetTransaction>,
+) -> Option<QuerySetAllocation> {
+    transaction.map(|transaction| {
+        let query = transaction.allocate(1);
+        render_pass.begin_pipeline_statistics_query(
+            transaction.get_query_set(query),
+            query.first_query_index,
+        );
+        query
+    })
 }
 
 #[profiling::function]
@@ -387,9 +570,11 @@ fn run_z_prepass_on_surface(
     render_context: &mut RenderContext,
     render_functions: &mut MainPassRenderFunctions,
     surface: &Surface,
+    viewport: PixelViewport,
     main_pass: &MainPass,
     camera_entity: Entity,
-) {
+    statistics: Option<&mut QuerySetTransaction>,
+) -> Option<QuerySetAllocation> {
     let depth_texture_view = surface.depth_texture();
 
     // create render pass
@@ -400,7 +585,7 @@ fn run_z_prepass_on_surface(
             depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
                 view: depth_texture_view,
                 depth_ops: Some(wgpu::Operations {
-                    load: wgpu::LoadOp::Clear(1.0),
+                    load: wgpu::LoadOp::Clear(surface.far_depth()),
                     store: wgpu::StoreOp::Store,
                 }),
                 stencil_ops: None,
@@ -412,26 +597,41 @@ fn run_z_prepass_on_surface(
         "z-prepass",
     );
 
+    // the depth texture is cleared completely, but cameras that render before this
+    // one are done with it.
+    viewport.set_on_render_pass(&mut render_pass);
+
     // bind frame uniform buffer
     render_pass.set_bind_group(0, Some(&main_pass.bind_group), &[]);
 
+    let query = begin_statistics_query(&mut render_pass, statistics);
+
     // render!
     render_functions
         .depth_prepass()
         .render(&mut render_pass, camera_entity);
+
+    if query.is_some() {
+        render_pass.end_pipeline_statistics_query();
+    }
+
+    query
 }
 
+#[allow(clippy::too_many_arguments)]
 #[profiling::function]
 fn run_main_pass_on_surface(
     render_context: &mut RenderContext,
     render_functions: &mut MainPassRenderFunctions,
     surface: &Surface,
+    viewport: PixelViewport,
     main_pass: &MainPass,
     camera_entity: Entity,
-    wireframe: bool,
+    wireframe: Option<Wireframe>,
     depth_prepass: bool,
-) {
-    let surface_texture_view = surface.surface_texture();
+    statistics: Option<&mut QuerySetTransaction>,
+) -> Option<QuerySetAllocation> {
+    let surface_texture_view = surface.surface_texture()?;
     let depth_texture_view = surface.depth_texture();
 
     // create render pass
@@ -457,7 +657,7 @@ fn run_main_pass_on_surface(
                 }
                 else {
                     wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(1.0),
+                        load: wgpu::LoadOp::Clear(surface.far_depth()),
                         store: wgpu::StoreOp::Discard,
                     }
                 }),
@@ -470,15 +670,21 @@ fn run_main_pass_on_surface(
         "main_pass",
     );
 
+    viewport.set_on_render_pass(&mut render_pass);
+
     // bind frame uniform buffer
     render_pass.set_bind_group(0, Some(&main_pass.bind_group), &[]);
 
+    let query = begin_statistics_query(&mut render_pass, statistics);
+
     // render!
-    render_functions
-        .opaque()
-        .render(&mut render_pass, camera_entity);
+    if wireframe != Some(Wireframe::Only) {
+        render_functions
+            .opaque()
+            .render(&mut render_pass, camera_entity);
+    }
 
-    if wireframe {
+    if wireframe.is_some() {
         render_functions
             .wireframe()
             .render(&mut render_pass, camera_entity);
@@ -487,6 +693,22 @@ fn run_main_pass_on_surface(
     render_functions
         .skybox()
         .render(&mut render_pass, camera_entity);
+
+    // outlines are blended on top of everything, including the sky
+    render_functions
+        .outline()
+        .render(&mut render_pass, camera_entity);
+
+    // the overlay covers the whole scene
+    render_functions
+        .overlay()
+        .render(&mut render_pass, camera_entity);
+
+    if query.is_some() {
+        render_pass.end_pipeline_statistics_query();
+    }
+
+    query
 }
 
 #[profiling::function]
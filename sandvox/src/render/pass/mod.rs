@@ -1,4 +1,6 @@
 pub mod context;
 pub mod main_pass;
 pub mod phase;
+pub mod post_pass;
+pub mod statistics;
 pub mod ui_pass;
@@ -4,11 +4,21 @@ pub struct Opaque;
 #[derive(Debug)]
 pub struct DepthPrepass;
 
+/// Debug phase for cameras with a
+/// [`Wireframe`][crate::render::pass::main_pass::Wireframe].
 #[derive(Debug)]
 pub struct Wireframe;
 
 #[derive(Debug)]
 pub struct Skybox;
 
+#[derive(Debug)]
+pub struct Outline;
+
+/// Fullscreen [overlays][crate::render::overlay::ScreenOverlay] at the end of
+/// the main pass.
+#[derive(Debug)]
+pub struct Overlay;
+
 #[derive(Debug)]
 pub struct Ui;
@@ -0,0 +1,307 @@
+//! The post pass applies the user's [color grading][ColorGradingConfig] to the
+//! final image.
+//!
+//! Surfaces only have a post pass if the color grading actually changes the
+//! image. In that case all other passes render into an intermediate texture
+//! (see [`Surface::surface_texture`]), which the post pass then draws into the
+//! swap chain texture.
+
+use bevy_ecs::{
+    component::Component,
+    name::NameOrEntity,
+    query::Without,
+    resource::Resource,
+    schedule::{
+        IntoScheduleConfigs,
+        SystemSet,
+        common_conditions::resource_changed,
+    },
+    system::{
+        Commands,
+        Populated,
+        Res,
+        ResMut,
+    },
+};
+use bytemuck::{
+    Pod,
+    Zeroable,
+};
+use color_eyre::eyre::Error;
+
+use crate::{
+    ecs::{
+        plugin::{
+            Plugin,
+            WorldBuilder,
+        },
+        schedule,
+    },
+    render::{
+        RenderConfig,
+        RenderSystems,
+        color::ColorGradingConfig,
+        pass::{
+            context::RenderContext,
+            main_pass::MainPassSystems,
+            ui_pass::UiPassSystems,
+        },
+        remove_on_gpu_setup,
+        staging::Staging,
+        surface::Surface,
+    },
+    wgpu::{
+        WgpuContext,
+        buffer::WriteStaging,
+    },
+};
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct PostPassPlugin;
+
+impl Plugin for PostPassPlugin {
+    fn setup(&self, builder: &mut WorldBuilder) -> Result<(), Error> {
+        builder
+            .add_systems(
+                schedule::GpuSetup,
+                (
+                    create_layout.in_set(RenderSystems::Setup),
+                    remove_on_gpu_setup::<PostPassPipeline>,
+                ),
+            )
+            .add_systems(
+                schedule::Render,
+                (
+                    (
+                        create_pipeline,
+                        update_post_pass_uniform.run_if(resource_changed::<RenderConfig>),
+                    )
+                        .in_set(RenderSystems::BeginFrame),
+                    render_post_pass.in_set(PostPassSystems::Render),
+                ),
+            )
+            .configure_system_sets(
+                schedule::Render,
+                PostPassSystems::Render
+                    .in_set(RenderSystems::Render)
+                    .after(MainPassSystems::Render)
+                    .after(UiPassSystems::Render)
+                    .before(RenderSystems::EndFrame),
+            );
+
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, Debug, SystemSet, PartialEq, Eq, Hash)]
+pub enum PostPassSystems {
+    Render,
+}
+
+#[derive(Debug, Resource)]
+struct PostPassLayout {
+    bind_group_layout: wgpu::BindGroupLayout,
+    layout: wgpu::PipelineLayout,
+    shader: wgpu::ShaderModule,
+    uniform_buffer: wgpu::Buffer,
+}
+
+#[derive(Debug, Component)]
+struct PostPassPipeline {
+    pipeline: wgpu::RenderPipeline,
+}
+
+#[derive(Clone, Copy, Debug, Pod, Zeroable)]
+#[repr(C)]
+struct PostPassUniformData {
+    brightness: f32,
+    contrast: f32,
+    gamma: f32,
+    _padding: u32,
+}
+
+impl From<&ColorGradingConfig> for PostPassUniformData {
+    fn from(value: &ColorGradingConfig) -> Self {
+        Self {
+            brightness: value.brightness,
+            contrast: value.contrast,
+            gamma: value.gamma,
+            _padding: 0,
+        }
+    }
+}
+
+#[profiling::function]
+fn create_layout(wgpu: Res<WgpuContext>, mut commands: Commands) {
+    let bind_group_layout =
+        wgpu.device
+            .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("post pass"),
+                entries: &[
+                    // the image that is color graded
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            multisampled: false,
+                        },
+                        count: None,
+                    },
+                    // uniform. contains the color grading
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                ],
+            });
+
+    let layout = wgpu
+        .device
+        .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("post pass"),
+            bind_group_layouts: &[&bind_group_layout],
+            immediate_size: 0,
+        });
+
+    let shader = wgpu
+        .device
+        .create_shader_module(wgpu::include_wgsl!("post_pass.wgsl"));
+
+    let uniform_buffer = wgpu.device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("post pass uniform"),
+        size: size_of::<PostPassUniformData>() as wgpu::BufferAddress,
+        usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::UNIFORM,
+        mapped_at_creation: false,
+    });
+
+    commands.insert_resource(PostPassLayout {
+        bind_group_layout,
+        layout,
+        shader,
+        uniform_buffer,
+    });
+}
+
+#[profiling::function]
+fn create_pipeline(
+    wgpu: Res<WgpuContext>,
+    layout: Res<PostPassLayout>,
+    surfaces: Populated<(NameOrEntity, &Surface), Without<PostPassPipeline>>,
+    mut commands: Commands,
+) {
+    for (surface_entity, surface) in surfaces {
+        if surface.post_pass_texture().is_none() {
+            continue;
+        }
+
+        tracing::debug!(surface = %surface_entity, "creating post pass pipeline for surface");
+
+        let pipeline = wgpu
+            .device
+            .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("post pass"),
+                layout: Some(&layout.layout),
+                vertex: wgpu::VertexState {
+                    module: &layout.shader,
+                    entry_point: Some("post_pass_vertex"),
+                    compilation_options: Default::default(),
+                    buffers: &[],
+                },
+                primitive: Default::default(),
+                depth_stencil: None,
+                multisample: Default::default(),
+                fragment: Some(wgpu::FragmentState {
+                    module: &layout.shader,
+                    entry_point: Some("post_pass_fragment"),
+                    compilation_options: Default::default(),
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: surface.surface_format(),
+                        blend: None,
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                }),
+                multiview_mask: None,
+                cache: None,
+            });
+
+        commands
+            .entity(surface_entity.entity)
+            .insert(PostPassPipeline { pipeline });
+    }
+}
+
+#[profiling::function]
+fn update_post_pass_uniform(
+    config: Res<RenderConfig>,
+    layout: Res<PostPassLayout>,
+    mut staging: ResMut<Staging>,
+) {
+    let data = PostPassUniformData::from(&config.color_grading);
+    staging.write_buffer_from_slice(layout.uniform_buffer.slice(..), bytemuck::bytes_of(&data));
+}
+
+#[profiling::function]
+fn render_post_pass(
+    wgpu: Res<WgpuContext>,
+    mut render_context: RenderContext,
+    layout: Res<PostPassLayout>,
+    surfaces: Populated<(&Surface, &PostPassPipeline)>,
+) {
+    for (surface, pipeline) in surfaces {
+        let (Some(color_texture), Some(swap_chain_texture)) =
+            (surface.post_pass_texture(), surface.swap_chain_texture())
+        else {
+            continue;
+        };
+
+        // the color texture is recreated when the surface is resized, so we just
+        // create the bind group every frame.
+        let bind_group = wgpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("post pass"),
+            layout: &layout.bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(color_texture),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: layout.uniform_buffer.as_entire_binding(),
+                },
+            ],
+        });
+
+        let mut render_pass = render_context.begin_render_pass(
+            &wgpu::RenderPassDescriptor {
+                label: Some("post pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: swap_chain_texture,
+                    depth_slice: None,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        // every pixel is overwritten
+                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+                multiview_mask: None,
+            },
+            "post pass",
+        );
+
+        render_pass.set_pipeline(&pipeline.pipeline);
+        render_pass.set_bind_group(0, Some(&bind_group), &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
@@ -0,0 +1,49 @@
+
+struct ColorGrading {
+    brightness: f32,
+    contrast: f32,
+    gamma: f32,
+    // padding: 4 bytes
+}
+
+@group(0)
+@binding(0)
+var color_texture: texture_2d<f32>;
+
+@group(0)
+@binding(1)
+var<uniform> color_grading: ColorGrading;
+
+
+@vertex
+fn post_pass_vertex(@builtin(vertex_index) vertex_index: u32) -> @builtin(position) vec4f {
+    // a triangle that covers the whole screen
+    let uv = vec2f(f32((vertex_index << 1) & 2), f32(vertex_index & 2));
+    return vec4f(uv * 2 - 1, 0, 1);
+}
+
+@fragment
+fn post_pass_fragment(@builtin(position) position: vec4f) -> @location(0) vec4f {
+    let color = textureLoad(color_texture, vec2u(position.xy), 0);
+
+    // the texture has an sRGB format, so the color is linear. the adjustments work on the
+    // encoded values, like they would on a monitor.
+    var encoded = linear_to_srgb(color.rgb);
+    encoded = (encoded - 0.5) * color_grading.contrast + 0.5 + color_grading.brightness;
+    encoded = pow(clamp(encoded, vec3f(0), vec3f(1)), vec3f(1 / color_grading.gamma));
+
+    return vec4f(srgb_to_linear(encoded), color.a);
+}
+
+
+fn linear_to_srgb(color: vec3f) -> vec3f {
+    let low = color * 12.92;
+    let high = 1.055 * pow(color, vec3f(1 / 2.4)) - 0.055;
+    return select(high, low, color <= vec3f(0.0031308));
+}
+
+fn srgb_to_linear(color: vec3f) -> vec3f {
+    let low = color / 12.92;
+    let high = pow((color + 0.055) / 1.055, vec3f(2.4));
+    return select(high, low, color <= vec3f(0.04045));
+}
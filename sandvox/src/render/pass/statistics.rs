@@ -0,0 +1,161 @@
+//! Pipeline statistics of the main pass, e.g. to measure overdraw.
+//!
+//! This needs
+//! [`PIPELINE_STATISTICS_QUERY`][wgpu::Features::PIPELINE_STATISTICS_QUERY],
+//! which is only requested if [`RenderConfig::pipeline_statistics`] is enabled
+//! at startup. The statistics are read back asynchronously, so they lag a few
+//! frames behind.
+
+use std::sync::Arc;
+
+use bevy_ecs::{
+    component::Component,
+    entity::Entity,
+    query::{
+        With,
+        Without,
+    },
+    resource::Resource,
+    system::{
+        Commands,
+        Populated,
+        Res,
+        ResMut,
+    },
+};
+use parking_lot::Mutex;
+
+use crate::{
+    render::{
+        RenderConfig,
+        pass::main_pass::MainPass,
+    },
+    wgpu::{
+        WgpuContext,
+        WgpuContextBuilder,
+        query::QuerySetPool,
+    },
+};
+
+/// The statistics that are collected. The order of the values in the resolved
+/// queries follows the order of the bits.
+const PIPELINE_STATISTICS_TYPES: wgpu::PipelineStatisticsTypes =
+    wgpu::PipelineStatisticsTypes::VERTEX_SHADER_INVOCATIONS
+        .union(wgpu::PipelineStatisticsTypes::CLIPPER_PRIMITIVES_OUT)
+        .union(wgpu::PipelineStatisticsTypes::FRAGMENT_SHADER_INVOCATIONS);
+
+#[derive(Debug, Resource)]
+pub(super) struct PipelineStatisticsPool(pub QuerySetPool);
+
+/// Pipeline statistics of a single render pass.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct PipelineStatistics {
+    pub vertex_shader_invocations: u64,
+    pub clipper_primitives_out: u64,
+    pub fragment_shader_invocations: u64,
+}
+
+impl PipelineStatistics {
+    pub(super) fn from_bytes(data: &[u8]) -> Self {
+        let [
+            vertex_shader_invocations,
+            clipper_primitives_out,
+            fragment_shader_invocations,
+        ]: [u64; 3] = bytemuck::pod_read_unaligned(data);
+
+        Self {
+            vertex_shader_invocations,
+            clipper_primitives_out,
+            fragment_shader_invocations,
+        }
+    }
+}
+
+/// Pipeline statistics of a camera's main pass.
+///
+/// This is attached to cameras automatically if pipeline statistics are
+/// supported and enabled.
+#[derive(Clone, Debug, Default, Component)]
+pub struct MainPassStatistics {
+    shared: Arc<Mutex<MainPassStatisticsData>>,
+}
+
+impl MainPassStatistics {
+    /// Statistics of the most recent frame that was read back.
+    pub fn get(&self) -> MainPassStatisticsData {
+        *self.shared.lock()
+    }
+
+    pub(super) fn set(&self, data: MainPassStatisticsData) {
+        *self.shared.lock() = data;
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct MainPassStatisticsData {
+    /// Statistics of the depth prepass, if the camera has one.
+    pub depth_prepass: Option<PipelineStatistics>,
+
+    pub main_pass: PipelineStatistics,
+
+    /// Number of pixels in the camera's viewport.
+    pub num_pixels: u64,
+}
+
+impl MainPassStatisticsData {
+    /// Average number of fragment shader invocations per pixel in the main
+    /// pass.
+    ///
+    /// Without a depth prepass this is usually well above 1, since occluded
+    /// geometry is shaded too if it was drawn first.
+    pub fn overdraw(&self) -> f32 {
+        if self.num_pixels == 0 {
+            0.0
+        }
+        else {
+            self.main_pass.fragment_shader_invocations as f32 / self.num_pixels as f32
+        }
+    }
+}
+
+pub(super) fn request_pipeline_statistics_features(
+    config: Res<RenderConfig>,
+    mut context_builder: ResMut<WgpuContextBuilder>,
+) {
+    if config.pipeline_statistics
+        && context_builder
+            .try_request_features(wgpu::Features::PIPELINE_STATISTICS_QUERY)
+            .is_err()
+    {
+        tracing::info!("Pipeline statistics queries not supported.");
+    }
+}
+
+pub(super) fn create_pipeline_statistics_pool(wgpu: Res<WgpuContext>, mut commands: Commands) {
+    if wgpu
+        .device
+        .features()
+        .contains(wgpu::Features::PIPELINE_STATISTICS_QUERY)
+    {
+        commands.insert_resource(PipelineStatisticsPool(QuerySetPool::new(
+            &wgpu.device,
+            wgpu::QueryType::PipelineStatistics(PIPELINE_STATISTICS_TYPES),
+            "pipeline statistics",
+        )));
+    }
+    else {
+        // a pool from a lost device can't be used anymore.
+        commands.remove_resource::<PipelineStatisticsPool>();
+    }
+}
+
+pub(super) fn create_main_pass_statistics(
+    cameras: Populated<Entity, (With<MainPass>, Without<MainPassStatistics>)>,
+    mut commands: Commands,
+) {
+    for entity in cameras {
+        commands
+            .entity(entity)
+            .insert(MainPassStatistics::default());
+    }
+}
@@ -31,6 +31,7 @@ use crate::{
     app::Time,
     ecs::{
         plugin::{
+            Dependencies,
             Plugin,
             WorldBuilder,
         },
@@ -44,11 +45,17 @@ use crate::{
         RenderPlugin,
         RenderSystems,
         atlas::AtlasResources,
+        color::wgpu_color,
         pass::{
             context::RenderContext,
             phase,
         },
-        render_target::RenderTarget,
+        remove_on_gpu_setup,
+        render_target::{
+            RenderTarget,
+            Viewport,
+            pixel_viewport,
+        },
         staging::Staging,
         surface::{
             ClearColor,
@@ -60,7 +67,6 @@ use crate::{
     wgpu::{
         WgpuContext,
         buffer::WriteStaging,
-        srgba_to_wgpu,
     },
 };
 
@@ -68,16 +74,24 @@ use crate::{
 pub struct UiPassPlugin;
 
 impl Plugin for UiPassPlugin {
+    fn dependencies(&self, dependencies: &mut Dependencies) -> Result<(), Error> {
+        dependencies.require_plugin::<RenderPlugin>();
+        Ok(())
+    }
+
     fn setup(&self, builder: &mut WorldBuilder) -> Result<(), Error> {
         builder
-            .require_plugin::<RenderPlugin>()
             .add_systems(
-                schedule::Startup,
+                schedule::GpuSetup,
                 (
-                    (create_layout, create_ui_pass).chain(),
-                    update_ui_pass_uniform,
-                )
-                    .in_set(UiPassSystems::Prepare),
+                    (
+                        (create_layout, create_ui_pass).chain(),
+                        update_ui_pass_uniform,
+                    )
+                        .in_set(UiPassSystems::Prepare),
+                    remove_on_gpu_setup::<UiPass>,
+                    remove_on_gpu_setup::<UiPassUniform>,
+                ),
             )
             .add_systems(
                 schedule::Render,
@@ -94,7 +108,7 @@ impl Plugin for UiPassPlugin {
                 ),
             )
             .configure_system_sets(
-                schedule::Startup,
+                schedule::GpuSetup,
                 UiPassSystems::Prepare.in_set(RenderSystems::Setup),
             )
             .configure_system_sets(
@@ -212,6 +226,13 @@ fn create_layout(wgpu: Res<WgpuContext>, mut commands: Commands) {
                         },
                         count: None,
                     },
+                    // font sampler
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 6,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                        count: None,
+                    },
                 ],
             });
 
@@ -306,6 +327,7 @@ fn update_ui_pass(
 fn render_ui_pass(
     mut render_context: RenderContext,
     views: Populated<(NameOrEntity, &RenderTarget, &UiPass, Option<&ClearColor>), With<ui::View>>,
+    viewports: Query<&Viewport>,
     surfaces: Populated<&Surface>,
     mut render_functions: RenderFunctions<phase::Ui>,
 ) {
@@ -315,7 +337,15 @@ fn render_ui_pass(
         // get target texture (and clear color)
         // todo: this should work with any kind of target texture
         let surface = surfaces.get(render_target.0).unwrap();
-        let surface_texture_view = surface.surface_texture();
+        let Some(surface_texture_view) = surface.surface_texture()
+        else {
+            continue;
+        };
+
+        let viewport = pixel_viewport(viewports.get(camera_entity.entity).ok(), surface.size());
+        if viewport.is_empty() {
+            continue;
+        }
 
         // create render pass
         let mut render_pass = render_context.begin_render_pass(
@@ -327,7 +357,7 @@ fn render_ui_pass(
                     resolve_target: None,
                     ops: wgpu::Operations {
                         load: clear_color.map_or(wgpu::LoadOp::Load, |color| {
-                            wgpu::LoadOp::Clear(srgba_to_wgpu(color.0))
+                            wgpu::LoadOp::Clear(wgpu_color(color.0))
                         }),
                         store: wgpu::StoreOp::Store,
                     },
@@ -340,6 +370,8 @@ fn render_ui_pass(
             "ui pass",
         );
 
+        viewport.set_on_render_pass(&mut render_pass);
+
         // bind frame uniform buffer
         render_pass.set_bind_group(0, Some(&ui_pass.bind_group), &[]);
 
@@ -389,6 +421,10 @@ fn create_bind_group(
                     font_resources.data_buffer.as_entire_buffer_binding(),
                 ),
             },
+            wgpu::BindGroupEntry {
+                binding: 6,
+                resource: wgpu::BindingResource::Sampler(font_resources.sampler),
+            },
         ],
     })
 }
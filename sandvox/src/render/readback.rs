@@ -0,0 +1,509 @@
+//! Asynchronous reading of buffers and textures from the GPU.
+//!
+//! Readbacks are recorded into a command encoder and complete some frames
+//! after the commands were submitted. The staging buffers are reused, and the
+//! number of readbacks in flight is limited, so that a slow GPU doesn't pile up
+//! readbacks.
+
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::Arc,
+    task::{
+        Context,
+        Poll,
+        Waker,
+    },
+};
+
+use bevy_ecs::{
+    resource::Resource,
+    system::Res,
+};
+use parking_lot::Mutex;
+
+use crate::wgpu::WgpuContext;
+
+/// Default for how many readbacks can be in flight at once.
+const DEFAULT_MAX_IN_FLIGHT: usize = 8;
+
+/// Staging buffers are allocated in powers of two, but at least this size.
+const MIN_BUFFER_SIZE: wgpu::BufferAddress = 0x1000;
+
+/// Free buffers larger than this are not kept around.
+const MAX_REUSED_BUFFER_SIZE: wgpu::BufferAddress = 0x1_000_000;
+
+#[derive(Clone, Debug, PartialEq, Eq, thiserror::Error)]
+pub enum ReadbackError {
+    #[error("Too many readbacks in flight (max {max_in_flight})")]
+    TooManyInFlight { max_in_flight: usize },
+
+    #[error("Mapping the readback buffer failed: {0}")]
+    Map(String),
+
+    #[error("Texture format {0:?} can't be read back")]
+    UnsupportedFormat(wgpu::TextureFormat),
+
+    #[error("The readback's commands were never submitted")]
+    NotSubmitted,
+}
+
+/// Pool of staging buffers for readbacks.
+#[derive(Clone, Debug, Resource)]
+pub struct ReadbackPool {
+    inner: Arc<Mutex<ReadbackPoolState>>,
+}
+
+#[derive(Debug)]
+struct ReadbackPoolState {
+    free_buffers: Vec<wgpu::Buffer>,
+    in_flight: usize,
+    max_in_flight: usize,
+}
+
+impl Default for ReadbackPool {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_IN_FLIGHT)
+    }
+}
+
+impl ReadbackPool {
+    pub fn new(max_in_flight: usize) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(ReadbackPoolState {
+                free_buffers: vec![],
+                in_flight: 0,
+                max_in_flight,
+            })),
+        }
+    }
+
+    pub fn in_flight(&self) -> usize {
+        self.inner.lock().in_flight
+    }
+
+    /// Reads back a slice of a buffer.
+    ///
+    /// The buffer must have `COPY_SRC` usage, and offset and size of the slice
+    /// must be multiples of `COPY_BUFFER_ALIGNMENT`.
+    pub fn read_buffer(
+        &self,
+        device: &wgpu::Device,
+        command_encoder: &mut wgpu::CommandEncoder,
+        source: wgpu::BufferSlice,
+    ) -> Result<Readback<Vec<u8>>, ReadbackError> {
+        self.read_buffer_with(device, command_encoder, source, |data| data.to_vec())
+    }
+
+    /// Like [`read_buffer`](Self::read_buffer), but converts the data with
+    /// `read` while the staging buffer is mapped.
+    pub fn read_buffer_with<T>(
+        &self,
+        device: &wgpu::Device,
+        command_encoder: &mut wgpu::CommandEncoder,
+        source: wgpu::BufferSlice,
+        read: impl FnOnce(&[u8]) -> T + Send + 'static,
+    ) -> Result<Readback<T>, ReadbackError>
+    where
+        T: Send + 'static,
+    {
+        let size = source.size().get();
+        assert!(
+            size.is_multiple_of(wgpu::COPY_BUFFER_ALIGNMENT)
+                && source.offset().is_multiple_of(wgpu::COPY_BUFFER_ALIGNMENT),
+            "readback source must be aligned to `COPY_BUFFER_ALIGNMENT`"
+        );
+
+        let buffer = self.acquire(device, size)?;
+
+        command_encoder.copy_buffer_to_buffer(source.buffer(), source.offset(), &buffer, 0, size);
+
+        Ok(self.map_on_submit(command_encoder, buffer, size, read))
+    }
+
+    /// Reads back a region of a texture.
+    ///
+    /// The texture must have `COPY_SRC` usage. The row padding required for
+    /// the copy is removed from the data.
+    pub fn read_texture(
+        &self,
+        device: &wgpu::Device,
+        command_encoder: &mut wgpu::CommandEncoder,
+        source: wgpu::TexelCopyTextureInfo,
+        size: wgpu::Extent3d,
+    ) -> Result<Readback<TextureReadback>, ReadbackError> {
+        let format = source.texture.format();
+        let layout = TextureReadbackLayout::new(format, source.aspect, size)
+            .ok_or(ReadbackError::UnsupportedFormat(format))?;
+
+        let buffer = self.acquire(device, layout.padded_size())?;
+
+        command_encoder.copy_texture_to_buffer(
+            source,
+            wgpu::TexelCopyBufferInfo {
+                buffer: &buffer,
+                layout: wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(layout.padded_bytes_per_row),
+                    rows_per_image: Some(layout.rows_per_image),
+                },
+            },
+            size,
+        );
+
+        Ok(
+            self.map_on_submit(command_encoder, buffer, layout.padded_size(), move |data| {
+                TextureReadback {
+                    format,
+                    size,
+                    bytes_per_row: layout.unpadded_bytes_per_row,
+                    data: layout.unpad(data),
+                }
+            }),
+        )
+    }
+
+    fn acquire(
+        &self,
+        device: &wgpu::Device,
+        size: wgpu::BufferAddress,
+    ) -> Result<wgpu::Buffer, ReadbackError> {
+        let mut state = self.inner.lock();
+
+        if state.in_flight >= state.max_in_flight {
+            return Err(ReadbackError::TooManyInFlight {
+                max_in_flight: state.max_in_flight,
+            });
+        }
+        state.in_flight += 1;
+
+        let buffer_size = size.next_power_of_two().max(MIN_BUFFER_SIZE);
+
+        let buffer = if let Some(index) = state
+            .free_buffers
+            .iter()
+            .position(|buffer| buffer.size() == buffer_size)
+        {
+            state.free_buffers.swap_remove(index)
+        }
+        else {
+            drop(state);
+
+            tracing::debug!(size = buffer_size, "allocating readback buffer");
+
+            device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("readback"),
+                size: buffer_size,
+                usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+                mapped_at_creation: false,
+            })
+        };
+
+        Ok(buffer)
+    }
+
+    fn map_on_submit<T>(
+        &self,
+        command_encoder: &mut wgpu::CommandEncoder,
+        buffer: wgpu::Buffer,
+        size: wgpu::BufferAddress,
+        read: impl FnOnce(&[u8]) -> T + Send + 'static,
+    ) -> Readback<T>
+    where
+        T: Send + 'static,
+    {
+        let readback = Readback::default();
+        let pending = PendingReadback {
+            pool: self.clone(),
+            buffer: buffer.clone(),
+            slot: readback.slot.clone(),
+            result: None,
+        };
+
+        command_encoder.map_buffer_on_submit(&buffer, wgpu::MapMode::Read, ..size, move |result| {
+            let result = result
+                .map(|()| {
+                    let output = read(&pending.buffer.get_mapped_range(..size));
+                    pending.buffer.unmap();
+                    output
+                })
+                .map_err(|error| ReadbackError::Map(error.to_string()));
+            pending.finish(result);
+        });
+
+        readback
+    }
+
+    fn release(&self, buffer: wgpu::Buffer, reuse: bool) {
+        let mut state = self.inner.lock();
+        state.in_flight -= 1;
+
+        if reuse
+            && buffer.size() <= MAX_REUSED_BUFFER_SIZE
+            && state.free_buffers.len() < state.max_in_flight
+        {
+            state.free_buffers.push(buffer);
+        }
+    }
+}
+
+/// A readback whose buffer is in flight.
+///
+/// The buffer is returned to the pool and the readback completed when this is
+/// dropped. Usually that's after the buffer was mapped, but if the command
+/// encoder is dropped without being submitted, the callback that owns this is
+/// dropped without being called.
+struct PendingReadback<T> {
+    pool: ReadbackPool,
+    buffer: wgpu::Buffer,
+    slot: Arc<Mutex<ReadbackSlot<T>>>,
+    result: Option<Result<T, ReadbackError>>,
+}
+
+impl<T> PendingReadback<T> {
+    fn finish(mut self, result: Result<T, ReadbackError>) {
+        self.result = Some(result);
+    }
+}
+
+impl<T> Drop for PendingReadback<T> {
+    fn drop(&mut self) {
+        let result = self
+            .result
+            .take()
+            .unwrap_or(Err(ReadbackError::NotSubmitted));
+
+        // a buffer that failed to map might be in a bad state. one that was never
+        // submitted is fine.
+        let reuse = !matches!(result, Err(ReadbackError::Map(_)));
+        self.pool.release(self.buffer.clone(), reuse);
+
+        self.slot.lock().complete(result);
+    }
+}
+
+/// Polls the device while readbacks are in flight, so that they complete
+/// promptly.
+pub(super) fn poll_readbacks(wgpu: Res<WgpuContext>, pool: Res<ReadbackPool>) {
+    if pool.in_flight() > 0
+        && let Err(error) = wgpu.device.poll(wgpu::PollType::Poll)
+    {
+        tracing::error!(?error, "device poll failed");
+    }
+}
+
+/// Handle to a pending readback.
+///
+/// This can either be polled from a system with
+/// [`try_take`](Self::try_take), or awaited.
+#[derive(Debug)]
+pub struct Readback<T> {
+    slot: Arc<Mutex<ReadbackSlot<T>>>,
+}
+
+impl<T> Default for Readback<T> {
+    fn default() -> Self {
+        Self {
+            slot: Arc::new(Mutex::new(ReadbackSlot {
+                result: None,
+                waker: None,
+                callback: None,
+            })),
+        }
+    }
+}
+
+impl<T> Readback<T> {
+    /// A readback that has already completed, e.g. because there was nothing to
+    /// read.
+    pub fn ready(result: Result<T, ReadbackError>) -> Self {
+        let readback = Self::default();
+        readback.slot.lock().result = Some(result);
+        readback
+    }
+
+    /// Takes the result if the readback has completed.
+    pub fn try_take(&mut self) -> Option<Result<T, ReadbackError>> {
+        self.slot.lock().result.take()
+    }
+
+    /// Calls `f` with the result once the readback completes.
+    ///
+    /// `f` is called from whichever thread polls the device.
+    pub fn then(self, f: impl FnOnce(Result<T, ReadbackError>) + Send + 'static) {
+        let mut slot = self.slot.lock();
+
+        if let Some(result) = slot.result.take() {
+            drop(slot);
+            f(result);
+        }
+        else {
+            slot.callback = Some(Box::new(f));
+        }
+    }
+}
+
+impl<T> Future for Readback<T> {
+    type Output = Result<T, ReadbackError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut slot = self.slot.lock();
+
+        if let Some(result) = slot.result.take() {
+            Poll::Ready(result)
+        }
+        else {
+            slot.waker = Some(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+}
+
+#[derive(derive_more::Debug)]
+struct ReadbackSlot<T> {
+    result: Option<Result<T, ReadbackError>>,
+    waker: Option<Waker>,
+    #[debug(skip)]
+    callback: Option<ReadbackCallback<T>>,
+}
+
+type ReadbackCallback<T> = Box<dyn FnOnce(Result<T, ReadbackError>) + Send>;
+
+impl<T> ReadbackSlot<T> {
+    fn complete(&mut self, result: Result<T, ReadbackError>) {
+        if let Some(callback) = self.callback.take() {
+            callback(result);
+        }
+        else {
+            self.result = Some(result);
+            if let Some(waker) = self.waker.take() {
+                waker.wake();
+            }
+        }
+    }
+}
+
+/// Texture data that was read back.
+#[derive(Clone, Debug)]
+pub struct TextureReadback {
+    pub format: wgpu::TextureFormat,
+    pub size: wgpu::Extent3d,
+
+    /// Bytes per row of texel blocks in `data`. Rows are tightly packed.
+    pub bytes_per_row: u32,
+
+    pub data: Vec<u8>,
+}
+
+impl TextureReadback {
+    /// Converts the readback to an image, if it's 2D and in an 8-bit RGBA
+    /// format.
+    pub fn into_rgba_image(self) -> Option<image::RgbaImage> {
+        match self.format {
+            wgpu::TextureFormat::Rgba8Unorm | wgpu::TextureFormat::Rgba8UnormSrgb
+                if self.size.depth_or_array_layers == 1 =>
+            {
+                image::RgbaImage::from_raw(self.size.width, self.size.height, self.data)
+            }
+            _ => None,
+        }
+    }
+
+    /// Converts the readback to a grayscale image, if it's 2D and in an 8-bit
+    /// single channel format.
+    pub fn into_luma_image(self) -> Option<image::GrayImage> {
+        match self.format {
+            wgpu::TextureFormat::R8Unorm if self.size.depth_or_array_layers == 1 => {
+                image::GrayImage::from_raw(self.size.width, self.size.height, self.data)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// How texture data is laid out in the readback buffer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct TextureReadbackLayout {
+    unpadded_bytes_per_row: u32,
+    padded_bytes_per_row: u32,
+    rows_per_image: u32,
+    num_images: u32,
+}
+
+impl TextureReadbackLayout {
+    fn new(
+        format: wgpu::TextureFormat,
+        aspect: wgpu::TextureAspect,
+        size: wgpu::Extent3d,
+    ) -> Option<Self> {
+        let block_size = format.block_copy_size(Some(aspect))?;
+        let (block_width, block_height) = format.block_dimensions();
+
+        let unpadded_bytes_per_row = size.width.div_ceil(block_width) * block_size;
+        let padded_bytes_per_row =
+            unpadded_bytes_per_row.next_multiple_of(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT);
+
+        Some(Self {
+            unpadded_bytes_per_row,
+            padded_bytes_per_row,
+            rows_per_image: size.height.div_ceil(block_height),
+            num_images: size.depth_or_array_layers,
+        })
+    }
+
+    fn num_rows(&self) -> usize {
+        self.rows_per_image as usize * self.num_images as usize
+    }
+
+    fn padded_size(&self) -> wgpu::BufferAddress {
+        self.padded_bytes_per_row as wgpu::BufferAddress * self.num_rows() as wgpu::BufferAddress
+    }
+
+    fn unpad(&self, data: &[u8]) -> Vec<u8> {
+        if self.padded_bytes_per_row == self.unpadded_bytes_per_row {
+            return data[..self.padded_size() as usize].to_vec();
+        }
+
+        let mut unpadded =
+            Vec::with_capacity(self.unpadded_bytes_per_row as usize * self.num_rows());
+        for row in data
+            .chunks_exact(self.padded_bytes_per_row as usize)
+            .take(self.num_rows())
+        {
+            unpadded.extend_from_slice(&row[..self.unpadded_bytes_per_row as usize]);
+        }
+        unpadded
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::render::readback::TextureReadbackLayout;
+
+    #[test]
+    fn it_removes_row_padding() {
+        let layout = TextureReadbackLayout::new(
+            wgpu::TextureFormat::Rgba8Unorm,
+            wgpu::TextureAspect::All,
+            wgpu::Extent3d {
+                width: 3,
+                height: 2,
+                depth_or_array_layers: 1,
+            },
+        )
+        .unwrap();
+
+        assert_eq!(layout.unpadded_bytes_per_row, 12);
+        assert_eq!(layout.padded_bytes_per_row, 256);
+        assert_eq!(layout.padded_size(), 512);
+
+        let mut data = vec![0; 512];
+        data[..12].fill(1);
+        data[256..268].fill(2);
+
+        let unpadded = layout.unpad(&data);
+        assert_eq!(unpadded.len(), 24);
+        assert!(unpadded[..12].iter().all(|byte| *byte == 1));
+        assert!(unpadded[12..].iter().all(|byte| *byte == 2));
+    }
+}
@@ -2,6 +2,10 @@ use bevy_ecs::{
     component::Component,
     entity::Entity,
 };
+use nalgebra::{
+    Point2,
+    Vector2,
+};
 
 // todo: make this an enum that can be more than a window
 #[derive(Clone, Copy, Debug, Component)]
@@ -11,3 +15,140 @@ pub struct RenderTarget(pub Entity);
 #[derive(Clone, Debug, Component)]
 #[relationship_target(relationship = RenderTarget)]
 pub struct RenderSources(Vec<Entity>);
+
+/// Part of the [`RenderTarget`] that a camera or UI view renders to.
+///
+/// Offset and size are relative to the size of the render target, so the
+/// viewport follows the window when it's resized. Without this component the
+/// whole render target is used.
+///
+/// Note that clearing the render target (e.g. with a `ClearColor`) still
+/// clears all of it.
+#[derive(Clone, Copy, Debug, PartialEq, Component)]
+pub struct Viewport {
+    pub offset: Vector2<f32>,
+    pub size: Vector2<f32>,
+}
+
+impl Viewport {
+    pub const FULL: Self = Self {
+        offset: Vector2::new(0.0, 0.0),
+        size: Vector2::new(1.0, 1.0),
+    };
+
+    /// Viewport of player `index` when the render target is split between
+    /// `count` players.
+    ///
+    /// Two players get the top and bottom half, three or four players get a
+    /// quarter each.
+    pub fn split_screen(index: usize, count: usize) -> Self {
+        assert!(index < count, "player index out of range");
+
+        match count {
+            1 => Self::FULL,
+            2 => {
+                Self {
+                    offset: Vector2::new(0.0, 0.5 * index as f32),
+                    size: Vector2::new(1.0, 0.5),
+                }
+            }
+            3 | 4 => {
+                Self {
+                    offset: Vector2::new(0.5 * (index % 2) as f32, 0.5 * (index / 2) as f32),
+                    size: Vector2::new(0.5, 0.5),
+                }
+            }
+            _ => panic!("split screen supports at most 4 players"),
+        }
+    }
+
+    /// The viewport in pixels of a render target with size `target_size`.
+    ///
+    /// The edges are rounded to whole pixels, so that adjacent viewports don't
+    /// overlap or leave gaps.
+    pub fn to_pixels(&self, target_size: Vector2<u32>) -> PixelViewport {
+        let target_size_f32 = target_size.cast::<f32>();
+        let to_pixels = |relative: Vector2<f32>| {
+            relative
+                .component_mul(&target_size_f32)
+                .map(|x| x.round().max(0.0) as u32)
+                .zip_map(&target_size, u32::min)
+        };
+
+        let start = to_pixels(self.offset);
+        let end = to_pixels(self.offset + self.size);
+
+        PixelViewport {
+            offset: start.into(),
+            size: end.zip_map(&start, u32::saturating_sub),
+        }
+    }
+}
+
+impl Default for Viewport {
+    fn default() -> Self {
+        Self::FULL
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PixelViewport {
+    pub offset: Point2<u32>,
+    pub size: Vector2<u32>,
+}
+
+impl PixelViewport {
+    /// The whole render target.
+    pub fn full(target_size: Vector2<u32>) -> Self {
+        Self {
+            offset: Point2::origin(),
+            size: target_size,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.size.x == 0 || self.size.y == 0
+    }
+
+    pub fn set_on_render_pass(&self, render_pass: &mut wgpu::RenderPass) {
+        let offset = self.offset.cast::<f32>();
+        let size = self.size.cast::<f32>();
+        render_pass.set_viewport(offset.x, offset.y, size.x, size.y, 0.0, 1.0);
+    }
+}
+
+/// Viewport of a camera or UI view in pixels.
+pub fn pixel_viewport(viewport: Option<&Viewport>, target_size: Vector2<u32>) -> PixelViewport {
+    viewport.map_or_else(
+        || PixelViewport::full(target_size),
+        |viewport| viewport.to_pixels(target_size),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::{
+        Point2,
+        Vector2,
+    };
+
+    use crate::render::render_target::Viewport;
+
+    #[test]
+    fn split_screen_viewports_tile_the_target() {
+        let target_size = Vector2::new(801, 601);
+
+        let top = Viewport::split_screen(0, 2).to_pixels(target_size);
+        let bottom = Viewport::split_screen(1, 2).to_pixels(target_size);
+        assert_eq!(top.offset, Point2::new(0, 0));
+        assert_eq!(bottom.offset.y, top.size.y);
+        assert_eq!(top.size.y + bottom.size.y, target_size.y);
+        assert_eq!(bottom.size.x, target_size.x);
+
+        let quarters = (0..4).map(|index| Viewport::split_screen(index, 4).to_pixels(target_size));
+        let area = quarters
+            .map(|viewport| viewport.size.x * viewport.size.y)
+            .sum::<u32>();
+        assert_eq!(area, target_size.x * target_size.y);
+    }
+}
@@ -0,0 +1,301 @@
+//! Scene files describe entity hierarchies in RON, so that content doesn't
+//! need to be spawned by code.
+//!
+//! ```ron
+//! (
+//!     entities: [
+//!         (
+//!             name: Some("robot"),
+//!             transform: Some((translation: (0.0, 1.0, 0.0))),
+//!             model: Some("assets/robot_merged.glb"),
+//!         ),
+//!     ],
+//! )
+//! ```
+//!
+//! Entities without a `transform` only get a [`GlobalTransform`], which is
+//! useful for entities that are positioned by a system (e.g. the sun and moon).
+//!
+//! Skybox and planet images are [required assets](crate::assets) that are
+//! loaded in the background, so they show up a few frames after the scene is
+//! spawned.
+
+use std::{
+    collections::HashMap,
+    path::{
+        Path,
+        PathBuf,
+    },
+};
+
+use bevy_ecs::{
+    entity::Entity,
+    hierarchy::ChildOf,
+    name::Name,
+    system::{
+        Commands,
+        Res,
+        SystemParam,
+    },
+};
+use color_eyre::{
+    Section,
+    eyre::Error,
+};
+use nalgebra::{
+    Isometry3,
+    Translation3,
+    UnitQuaternion,
+    Vector3,
+};
+use serde::{
+    Deserialize,
+    Serialize,
+};
+
+use crate::{
+    assets::AssetServer,
+    ecs::transform::{
+        GlobalTransform,
+        LocalTransform,
+    },
+    render::{
+        model::ModelLoader,
+        skybox::{
+            PlanetAsset,
+            SkyboxAsset,
+            SkyboxDaylight,
+        },
+    },
+};
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct SceneDescription {
+    /// Root entities of the scene.
+    #[serde(default)]
+    pub entities: Vec<SceneEntity>,
+}
+
+impl SceneDescription {
+    pub fn from_path(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let ron = std::fs::read_to_string(path).with_note(|| path.display().to_string())?;
+        Self::parse(&ron).with_note(|| path.display().to_string())
+    }
+
+    pub fn parse(ron: &str) -> Result<Self, Error> {
+        Ok(ron::from_str(ron)?)
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct SceneEntity {
+    #[serde(default)]
+    pub name: Option<String>,
+
+    #[serde(default)]
+    pub transform: Option<SceneTransform>,
+
+    /// Path to a glTF model that is spawned as a child of this entity.
+    #[serde(default)]
+    pub model: Option<PathBuf>,
+
+    /// Path to the directory with the skybox faces.
+    #[serde(default)]
+    pub skybox: Option<PathBuf>,
+
+    #[serde(default)]
+    pub planet: Option<ScenePlanet>,
+
+    #[serde(default)]
+    pub children: Vec<SceneEntity>,
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct SceneTransform {
+    #[serde(default)]
+    pub translation: Vector3<f32>,
+
+    /// Euler angles (roll, pitch, yaw) in degrees.
+    #[serde(default)]
+    pub rotation: Vector3<f32>,
+
+    #[serde(default = "default_scale")]
+    pub scale: Vector3<f32>,
+}
+
+impl Default for SceneTransform {
+    fn default() -> Self {
+        Self {
+            translation: Vector3::zeros(),
+            rotation: Vector3::zeros(),
+            scale: default_scale(),
+        }
+    }
+}
+
+fn default_scale() -> Vector3<f32> {
+    Vector3::repeat(1.0)
+}
+
+impl From<SceneTransform> for LocalTransform {
+    fn from(value: SceneTransform) -> Self {
+        let rotation = value.rotation.map(f32::to_radians);
+        Self {
+            isometry: Isometry3::from_parts(
+                Translation3::from(value.translation),
+                UnitQuaternion::from_euler_angles(rotation.x, rotation.y, rotation.z),
+            ),
+            scale: value.scale,
+        }
+    }
+}
+
+/// A planet in the skybox. Must be a child of the skybox entity.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ScenePlanet {
+    pub texture: PathBuf,
+
+    /// Angular diameter in degrees.
+    pub size: f32,
+}
+
+/// Entities spawned from a scene.
+#[derive(Clone, Debug, Default)]
+pub struct SpawnedScene {
+    pub roots: Vec<Entity>,
+    names: HashMap<String, Entity>,
+}
+
+impl SpawnedScene {
+    /// Returns the entity with that name.
+    ///
+    /// If multiple entities have the same name, the last one is returned.
+    pub fn get(&self, name: &str) -> Option<Entity> {
+        self.names.get(name).copied()
+    }
+}
+
+#[derive(derive_more::Debug, SystemParam)]
+pub struct SceneLoader<'w, 's> {
+    // note: this must come before our commands, so that the models are spawned before we attach
+    // them to their parents.
+    model_loader: ModelLoader<'w, 's>,
+
+    assets: Res<'w, AssetServer>,
+
+    #[debug(skip)]
+    commands: Commands<'w, 's>,
+}
+
+impl<'w, 's> SceneLoader<'w, 's> {
+    pub fn load_scene(&mut self, path: impl AsRef<Path>) -> Result<SpawnedScene, Error> {
+        let path = path.as_ref();
+        tracing::debug!(?path, "loading scene");
+
+        let description = SceneDescription::from_path(path)?;
+        self.spawn_scene(&description)
+    }
+
+    pub fn spawn_scene(&mut self, description: &SceneDescription) -> Result<SpawnedScene, Error> {
+        let mut spawned = SpawnedScene::default();
+
+        for entity in &description.entities {
+            let root = self.spawn_entity(entity, None, &mut spawned)?;
+            spawned.roots.push(root);
+        }
+
+        Ok(spawned)
+    }
+
+    fn spawn_entity(
+        &mut self,
+        description: &SceneEntity,
+        parent: Option<Entity>,
+        spawned: &mut SpawnedScene,
+    ) -> Result<Entity, Error> {
+        let mut entity = self.commands.spawn(GlobalTransform::identity());
+
+        if let Some(parent) = parent {
+            entity.insert(ChildOf(parent));
+        }
+
+        if let Some(name) = &description.name {
+            entity.insert(Name::new(name.clone()));
+            spawned.names.insert(name.clone(), entity.id());
+        }
+
+        if let Some(transform) = description.transform {
+            entity.insert(LocalTransform::from(transform));
+        }
+
+        let entity = entity.id();
+
+        if let Some(path) = &description.skybox {
+            self.commands.entity(entity).insert((
+                SkyboxAsset::new(self.assets.load_required(path)),
+                SkyboxDaylight::default(),
+            ));
+        }
+
+        if let Some(planet) = &description.planet {
+            self.commands.entity(entity).insert(PlanetAsset::new(
+                self.assets.load_required(&planet.texture),
+                planet.size.to_radians(),
+            ));
+        }
+
+        if let Some(path) = &description.model {
+            let model = self.model_loader.load_scene(path)?.id();
+            self.commands.entity(model).insert(ChildOf(entity));
+        }
+
+        for child in &description.children {
+            self.spawn_entity(child, Some(entity), spawned)?;
+        }
+
+        Ok(entity)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::Vector3;
+
+    use crate::render::scene::SceneDescription;
+
+    #[test]
+    fn it_parses_a_scene() {
+        let scene = SceneDescription::parse(
+            r#"(
+                entities: [
+                    (
+                        name: Some("sky"),
+                        skybox: Some("assets/skybox"),
+                        children: [
+                            (planet: Some((texture: "sun.png", size: 2.0))),
+                        ],
+                    ),
+                    (transform: Some((translation: (1.0, 2.0, 3.0)))),
+                ],
+            )"#,
+        )
+        .unwrap();
+
+        assert_eq!(scene.entities.len(), 2);
+        assert_eq!(scene.entities[0].name.as_deref(), Some("sky"));
+        assert_eq!(scene.entities[0].children.len(), 1);
+        assert!(scene.entities[0].children[0].planet.is_some());
+        assert_eq!(scene.entities[1].transform.unwrap().translation.z, 3.0);
+        assert_eq!(
+            scene.entities[1].transform.unwrap().scale,
+            Vector3::repeat(1.0)
+        );
+    }
+
+    #[test]
+    fn it_parses_the_skybox_scene() {
+        let scene = SceneDescription::parse(include_str!("../../../assets/skybox.ron")).unwrap();
+        assert_eq!(scene.entities[0].children.len(), 2);
+    }
+}
@@ -1,4 +1,7 @@
-use std::path::Path;
+use std::path::{
+    Path,
+    PathBuf,
+};
 
 use bevy_ecs::{
     change_detection::DetectChanges,
@@ -35,16 +38,33 @@ use bytemuck::{
 };
 use color_eyre::{
     Section,
-    eyre::Error,
+    eyre::{
+        Error,
+        ensure,
+        eyre,
+    },
+};
+use image::{
+    Rgba32FImage,
+    RgbaImage,
 };
-use image::RgbaImage;
 use nalgebra::{
     Matrix4,
     Vector2,
+    Vector3,
+};
+use palette::{
+    LinSrgba,
+    Srgba,
+    WithAlpha,
 };
 use wgpu::util::DeviceExt;
 
 use crate::{
+    assets::{
+        Asset,
+        AssetHandle,
+    },
     ecs::{
         plugin::{
             Plugin,
@@ -54,6 +74,7 @@ use crate::{
         transform::GlobalTransform,
     },
     render::{
+        DefaultAtlas,
         RenderSystems,
         atlas::AtlasHandle,
         command::{
@@ -69,13 +90,16 @@ use crate::{
             },
             phase,
         },
+        remove_on_gpu_setup,
         render_target::RenderTarget,
         staging::Staging,
+        star_catalog::StarCatalog,
         surface::Surface,
     },
     util::{
         format_size,
         image::{
+            HDR_EXTENSIONS,
             ImageLoadExt,
             ImageSizeExt,
         },
@@ -83,6 +107,11 @@ use crate::{
     wgpu::{
         WgpuContext,
         buffer::WriteStaging,
+        image::{
+            MipmapGenerator,
+            mip_level_count_for_size,
+            write_half_floats,
+        },
     },
 };
 
@@ -93,14 +122,20 @@ impl Plugin for SkyboxPlugin {
     fn setup(&self, builder: &mut WorldBuilder) -> Result<(), Error> {
         builder
             .add_systems(
-                schedule::Startup,
+                schedule::GpuSetup,
                 (
-                    create_pipeline_layout
-                        .in_set(RenderSystems::Setup)
-                        .after(MainPassSystems::Prepare),
-                    load_skybox.after(create_pipeline_layout),
-                )
-                    .in_set(RenderSystems::Setup),
+                    (
+                        create_pipeline_layout
+                            .in_set(RenderSystems::Setup)
+                            .after(MainPassSystems::Prepare),
+                        load_skybox.after(create_pipeline_layout),
+                    )
+                        .in_set(RenderSystems::Setup),
+                    reload_skyboxes,
+                    remove_on_gpu_setup::<Skybox>,
+                    remove_on_gpu_setup::<SkyboxPipeline>,
+                    remove_on_gpu_setup::<SkyboxBindGroup>,
+                ),
             )
             .add_systems(
                 schedule::Render,
@@ -108,94 +143,391 @@ impl Plugin for SkyboxPlugin {
                     create_pipeline,
                     load_skybox,
                     update_skybox.run_if(
-                        any_match_filter::<(Changed<GlobalTransform>, With<SkyboxBindGroup>)>.or(
-                            any_match_filter::<(
+                        any_match_filter::<(Changed<GlobalTransform>, With<SkyboxBindGroup>)>
+                            .or(any_match_filter::<(
                                 Or<(Changed<GlobalTransform>, Changed<Planet>)>,
                                 With<Planet>,
-                            )>,
-                        ),
+                            )>)
+                            .or(any_match_filter::<Changed<SkyboxDaylight>>),
                     ),
                 )
                     .in_set(RenderSystems::BeginFrame),),
             )
+            .add_systems(schedule::Update, (update_skyboxes, update_planets))
             .add_render_function::<phase::Skybox, _>(RenderSkybox);
 
         Ok(())
     }
 }
 
+/// Creates the skyboxes again after the GPU device was lost.
+fn reload_skyboxes(skyboxes: Populated<&mut SkyboxAsset>) {
+    for mut asset in skyboxes {
+        asset.version = 0;
+    }
+}
+
+fn update_skyboxes(
+    wgpu: Res<WgpuContext>,
+    skyboxes: Populated<(Entity, &mut SkyboxAsset)>,
+    mut commands: Commands,
+) {
+    for (entity, mut asset) in skyboxes {
+        let asset = &mut *asset;
+        let Some(images) = asset.images.get_if_changed(&mut asset.version)
+        else {
+            continue;
+        };
+
+        let result = images
+            .map_err(|error| eyre!("{error}"))
+            .and_then(|images| Skybox::new(&wgpu, &images));
+
+        match result {
+            Ok(skybox) => {
+                // the bind group is created again by `load_skybox`
+                commands
+                    .entity(entity)
+                    .insert(skybox)
+                    .remove::<SkyboxBindGroup>();
+            }
+            Err(error) => {
+                tracing::error!(path = ?asset.images.path(), "could not create skybox: {error}");
+            }
+        }
+    }
+}
+
+fn update_planets(
+    wgpu: Res<WgpuContext>,
+    mut atlas: ResMut<DefaultAtlas>,
+    mut staging: ResMut<Staging>,
+    planets: Populated<(Entity, &mut PlanetAsset)>,
+    mut commands: Commands,
+) {
+    for (entity, mut asset) in planets {
+        let asset = &mut *asset;
+        let Some(image) = asset.texture.get_if_changed(&mut asset.version)
+        else {
+            continue;
+        };
+
+        let result = image
+            .map_err(|error| eyre!("{error}"))
+            .and_then(|image| Ok(atlas.insert_image(&image, None, &wgpu.device, &mut staging)?));
+
+        match result {
+            Ok(texture) => {
+                tracing::debug!(path = ?asset.texture.path(), ?texture, "loaded texture");
+                commands.entity(entity).insert(Planet {
+                    texture,
+                    size: asset.size,
+                });
+            }
+            Err(error) => {
+                tracing::error!(path = ?asset.texture.path(), "could not load planet: {error}");
+            }
+        }
+    }
+}
+
+/// Seed for the star field that is generated if the skybox doesn't come with a
+/// star catalog.
+const STAR_FIELD_SEED: u64 = 0x5eed_5ba5;
+
+/// Number of stars that are generated if the skybox doesn't come with a star
+/// catalog.
+const STAR_FIELD_COUNT: usize = 4000;
+
 #[derive(Clone, Debug, Component)]
 pub struct Skybox {
     texture: wgpu::TextureView,
+    stars: wgpu::Buffer,
+    num_stars: u32,
 }
 
 impl Skybox {
     #[profiling::function]
     pub fn load(wgpu: &WgpuContext, path: impl AsRef<Path>) -> Result<Self, Error> {
+        let images = SkyboxImages::load(path.as_ref())?;
+        Self::new(wgpu, &images)
+    }
+
+    #[profiling::function]
+    pub fn new(wgpu: &WgpuContext, images: &SkyboxImages) -> Result<Self, Error> {
+        let label = format!("skybox: {}", images.path.display());
+
+        let texture = {
+            profiling::scope!("create_texture");
+
+            let size = wgpu::Extent3d {
+                width: images.size.x,
+                height: images.size.y,
+                depth_or_array_layers: 6,
+            };
+
+            let texture = wgpu.device.create_texture(&wgpu::TextureDescriptor {
+                label: Some(&label),
+                size,
+                mip_level_count: mip_level_count_for_size(&images.size).get(),
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: images.format,
+                usage: MipmapGenerator::USAGE,
+                view_formats: &[],
+            });
+
+            // write the first mip level of all faces and generate the others from it
+            wgpu.queue.write_texture(
+                texture.as_image_copy(),
+                &images.data,
+                wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(images.format.block_copy_size(None).unwrap() * size.width),
+                    rows_per_image: Some(size.height),
+                },
+                size,
+            );
+
+            let mut command_encoder =
+                wgpu.device
+                    .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                        label: Some("skybox mipmaps"),
+                    });
+            MipmapGenerator::new(&wgpu.device).generate(
+                &wgpu.device,
+                &mut command_encoder,
+                &texture,
+            )?;
+            wgpu.queue.submit([command_encoder.finish()]);
+
+            texture
+        };
+
+        let texture = texture.create_view(&wgpu::TextureViewDescriptor {
+            label: Some(&label),
+            dimension: Some(wgpu::TextureViewDimension::Cube),
+            ..wgpu::TextureViewDescriptor::default()
+        });
+
+        let generated;
+        let star_catalog = match &images.star_catalog {
+            Some(star_catalog) => star_catalog,
+            None => {
+                generated = StarCatalog::generate(STAR_FIELD_SEED, STAR_FIELD_COUNT);
+                &generated
+            }
+        };
+
+        let mut star_data = star_catalog
+            .stars
+            .iter()
+            .map(|star| {
+                StarData {
+                    direction: star.direction,
+                    brightness: star.brightness(),
+                }
+            })
+            .collect::<Vec<_>>();
+        let num_stars = star_data.len().try_into().unwrap();
+
+        // an empty storage buffer can't be bound
+        if star_data.is_empty() {
+            star_data.push(StarData::zeroed());
+        }
+
+        let stars = wgpu
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some(&format!("{label}/stars")),
+                contents: bytemuck::cast_slice(&star_data),
+                usage: wgpu::BufferUsages::STORAGE,
+            });
+
+        Ok(Self {
+            texture,
+            stars,
+            num_stars,
+        })
+    }
+}
+
+/// The decoded faces and star catalog of a skybox, loaded from a directory.
+///
+/// The faces are PNG images, or high-dynamic-range Radiance HDR or OpenEXR
+/// images, which are stored as half floats.
+///
+/// The stars are rendered on top of the faces, from the directory's star
+/// catalog if it has one, so that they're in their correct positions, and
+/// otherwise from a random star field.
+#[derive(derive_more::Debug)]
+pub struct SkyboxImages {
+    path: PathBuf,
+    extension: &'static str,
+    size: Vector2<u32>,
+    format: wgpu::TextureFormat,
+
+    /// The faces in cube map layer order.
+    #[debug(skip)]
+    data: Vec<u8>,
+
+    star_catalog: Option<StarCatalog>,
+}
+
+impl Asset for SkyboxImages {
+    #[profiling::function]
+    fn load(path: &Path) -> Result<Self, Error> {
         // note: generate cube map from cylindrical: https://jaxry.github.io/panorama-to-cubemap/
         // layout: https://gpuweb.github.io/gpuweb/#texture-view-creation
 
-        const FACES: [&str; 6] = ["px", "nx", "py", "ny", "pz", "nz"];
-        let path = path.as_ref();
-
         tracing::debug!(?path, "Loading skybox");
 
+        let extension = face_extension(path);
+        let hdr = HDR_EXTENSIONS.contains(&extension);
+
         let mut data = vec![];
         let mut size = Vector2::zeros();
 
-        for (i, face) in FACES.into_iter().enumerate() {
+        for (i, path) in face_paths(path, extension).enumerate() {
             profiling::scope!("load face");
 
-            let path = path.join(format!("{face}.png"));
-            let image = RgbaImage::from_path(&path)
-                .with_note(|| path.display().to_string())
-                .unwrap();
+            let face_size = if hdr {
+                let image =
+                    Rgba32FImage::from_path(&path).with_note(|| path.display().to_string())?;
+                let offset = data.len();
+                data.resize(offset + 2 * image.len(), 0);
+                write_half_floats(image.as_raw(), &mut data[offset..]);
+                image.size()
+            }
+            else {
+                let image = RgbaImage::from_path(&path).with_note(|| path.display().to_string())?;
+                data.extend(image.as_raw());
+                image.size()
+            };
 
             if i == 0 {
-                size = image.size();
+                size = face_size;
             }
             else {
-                assert_eq!(image.size(), size);
+                ensure!(
+                    face_size == size,
+                    "skybox face {} has size {face_size:?}, expected {size:?}",
+                    path.display(),
+                );
             }
+        }
 
-            data.extend(image.as_raw());
+        let format = if hdr {
+            wgpu::TextureFormat::Rgba16Float
         }
+        else {
+            wgpu::TextureFormat::Rgba8UnormSrgb
+        };
 
-        tracing::debug!(size = ?size, bytes = %format_size(data.len()), "skybox");
+        tracing::debug!(size = ?size, ?format, bytes = %format_size(data.len()), "skybox");
 
-        let label = format!("skybox: {}", path.display());
+        let star_catalog_path = path.join(STAR_CATALOG_FILE);
+        let star_catalog = star_catalog_path
+            .exists()
+            .then(|| StarCatalog::load(&star_catalog_path))
+            .transpose()?;
 
-        let texture = {
-            profiling::scope!("create_texture");
+        if let Some(star_catalog) = &star_catalog {
+            tracing::debug!(num_stars = star_catalog.len(), "star catalog");
+        }
 
-            wgpu.device.create_texture_with_data(
-                &wgpu.queue,
-                &wgpu::TextureDescriptor {
-                    label: Some(&label),
-                    size: wgpu::Extent3d {
-                        width: size.x,
-                        height: size.y,
-                        depth_or_array_layers: 6,
-                    },
-                    mip_level_count: 1,
-                    sample_count: 1,
-                    dimension: wgpu::TextureDimension::D2,
-                    format: wgpu::TextureFormat::Rgba8UnormSrgb,
-                    usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
-                    view_formats: &[],
-                },
-                wgpu::util::TextureDataOrder::LayerMajor,
-                &data,
-            )
-        };
+        Ok(Self {
+            path: path.to_owned(),
+            extension,
+            size,
+            format,
+            data,
+            star_catalog,
+        })
+    }
 
-        let texture = texture.create_view(&wgpu::TextureViewDescriptor {
-            label: Some(&label),
-            dimension: Some(wgpu::TextureViewDimension::Cube),
-            ..wgpu::TextureViewDescriptor::default()
-        });
+    fn dependencies(&self) -> Vec<PathBuf> {
+        face_paths(&self.path, self.extension)
+            .chain([self.path.join(STAR_CATALOG_FILE)])
+            .collect()
+    }
+}
+
+const FACES: [&str; 6] = ["px", "nx", "py", "ny", "pz", "nz"];
 
-        Ok(Self { texture })
+/// The skybox faces in cube map layer order.
+fn face_paths(path: &Path, extension: &'static str) -> impl Iterator<Item = PathBuf> {
+    FACES
+        .into_iter()
+        .map(move |face| path.join(format!("{face}.{extension}")))
+}
+
+/// High-dynamic-range faces are used if there are any, and PNG faces
+/// otherwise.
+fn face_extension(path: &Path) -> &'static str {
+    HDR_EXTENSIONS
+        .into_iter()
+        .find(|extension| path.join(format!("{}.{extension}", FACES[0])).exists())
+        .unwrap_or("png")
+}
+
+/// Optional star catalog in the skybox directory.
+const STAR_CATALOG_FILE: &str = "stars.csv";
+
+/// Creates the [`Skybox`] of this entity once its images are loaded, and
+/// again whenever they're reloaded.
+#[derive(Debug, Component)]
+pub struct SkyboxAsset {
+    pub images: AssetHandle<SkyboxImages>,
+    version: u64,
+}
+
+impl SkyboxAsset {
+    pub fn new(images: AssetHandle<SkyboxImages>) -> Self {
+        Self { images, version: 0 }
+    }
+}
+
+/// Creates the [`Planet`] of this entity once its texture is loaded, and
+/// again whenever it's reloaded.
+#[derive(Debug, Component)]
+pub struct PlanetAsset {
+    pub texture: AssetHandle<RgbaImage>,
+
+    /// Angular diameter in radians.
+    pub size: f32,
+
+    version: u64,
+}
+
+impl PlanetAsset {
+    pub fn new(texture: AssetHandle<RgbaImage>, size: f32) -> Self {
+        Self {
+            texture,
+            size,
+            version: 0,
+        }
+    }
+}
+
+/// Controls how the skybox blends between day and night.
+///
+/// At night the skybox texture and stars are visible. During the day they fade
+/// into [`day_color`][Self::day_color].
+#[derive(Clone, Copy, Debug, PartialEq, Component)]
+pub struct SkyboxDaylight {
+    /// How dark the sky is, from 0 (day) to 1 (night).
+    pub night: f32,
+
+    pub day_color: Srgba<f32>,
+}
+
+impl Default for SkyboxDaylight {
+    fn default() -> Self {
+        Self {
+            night: 1.0,
+            day_color: palette::named::LIGHTSKYBLUE.into_format().with_alpha(1.0),
+        }
     }
 }
 
@@ -210,6 +542,7 @@ struct SkyboxBindGroup {
     bind_group: wgpu::BindGroup,
     data_buffer: wgpu::Buffer,
     num_planets: u32,
+    num_stars: u32,
 }
 
 #[derive(Debug, Resource)]
@@ -222,6 +555,7 @@ struct SkyboxLayout {
 #[derive(Debug, Component)]
 struct SkyboxPipeline {
     skybox_pipeline: wgpu::RenderPipeline,
+    star_pipeline: wgpu::RenderPipeline,
     planet_pipeline: wgpu::RenderPipeline,
 }
 
@@ -237,7 +571,7 @@ fn create_pipeline_layout(
                 entries: &[
                     wgpu::BindGroupLayoutEntry {
                         binding: 0,
-                        visibility: wgpu::ShaderStages::VERTEX,
+                        visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
                         ty: wgpu::BindingType::Buffer {
                             ty: wgpu::BufferBindingType::Uniform,
                             has_dynamic_offset: false,
@@ -255,6 +589,16 @@ fn create_pipeline_layout(
                         },
                         count: None,
                     },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 2,
+                        visibility: wgpu::ShaderStages::VERTEX,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Storage { read_only: true },
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
                 ],
             });
 
@@ -299,12 +643,18 @@ fn create_pipeline(
             let skybox_pipeline =
                 wgpu.device
                     .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-                        label: Some("skybox/stars"),
+                        label: Some("skybox/background"),
                         layout: Some(&pipeline_layout.layout),
                         vertex: wgpu::VertexState {
                             module: &pipeline_layout.shader,
                             entry_point: Some("skybox_vertex"),
-                            compilation_options: Default::default(),
+                            compilation_options: wgpu::PipelineCompilationOptions {
+                                constants: &[(
+                                    "REVERSE_Z",
+                                    if surface.reverse_z() { 1.0 } else { 0.0 },
+                                )],
+                                ..Default::default()
+                            },
                             buffers: &[],
                         },
                         primitive: wgpu::PrimitiveState {
@@ -319,7 +669,7 @@ fn create_pipeline(
                         depth_stencil: Some(wgpu::DepthStencilState {
                             format: surface.depth_format(),
                             depth_write_enabled: false,
-                            depth_compare: wgpu::CompareFunction::LessEqual,
+                            depth_compare: surface.depth_compare(wgpu::CompareFunction::LessEqual),
                             stencil: Default::default(),
                             bias: Default::default(),
                         }),
@@ -338,6 +688,62 @@ fn create_pipeline(
                         cache: None,
                     });
 
+            let star_pipeline =
+                wgpu.device
+                    .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                        label: Some("skybox/stars"),
+                        layout: Some(&pipeline_layout.layout),
+                        vertex: wgpu::VertexState {
+                            module: &pipeline_layout.shader,
+                            entry_point: Some("star_vertex"),
+                            compilation_options: wgpu::PipelineCompilationOptions {
+                                constants: &[(
+                                    "REVERSE_Z",
+                                    if surface.reverse_z() { 1.0 } else { 0.0 },
+                                )],
+                                ..Default::default()
+                            },
+                            buffers: &[],
+                        },
+                        primitive: wgpu::PrimitiveState {
+                            topology: wgpu::PrimitiveTopology::TriangleList,
+                            strip_index_format: None,
+                            front_face: wgpu::FrontFace::Ccw,
+                            cull_mode: None,
+                            unclipped_depth: false,
+                            polygon_mode: wgpu::PolygonMode::Fill,
+                            conservative: false,
+                        },
+                        depth_stencil: Some(wgpu::DepthStencilState {
+                            format: surface.depth_format(),
+                            depth_write_enabled: false,
+                            depth_compare: surface.depth_compare(wgpu::CompareFunction::LessEqual),
+                            stencil: Default::default(),
+                            bias: Default::default(),
+                        }),
+                        multisample: Default::default(),
+                        fragment: Some(wgpu::FragmentState {
+                            module: &pipeline_layout.shader,
+                            entry_point: Some("star_fragment"),
+                            compilation_options: Default::default(),
+                            targets: &[Some(wgpu::ColorTargetState {
+                                format: surface.surface_format(),
+                                // stars are additive, so they brighten the sky behind them
+                                blend: Some(wgpu::BlendState {
+                                    color: wgpu::BlendComponent {
+                                        src_factor: wgpu::BlendFactor::SrcAlpha,
+                                        dst_factor: wgpu::BlendFactor::One,
+                                        operation: wgpu::BlendOperation::Add,
+                                    },
+                                    alpha: wgpu::BlendComponent::OVER,
+                                }),
+                                write_mask: wgpu::ColorWrites::ALL,
+                            })],
+                        }),
+                        multiview_mask: None,
+                        cache: None,
+                    });
+
             let planet_pipeline =
                 wgpu.device
                     .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
@@ -346,7 +752,13 @@ fn create_pipeline(
                         vertex: wgpu::VertexState {
                             module: &pipeline_layout.shader,
                             entry_point: Some("planet_vertex"),
-                            compilation_options: Default::default(),
+                            compilation_options: wgpu::PipelineCompilationOptions {
+                                constants: &[(
+                                    "REVERSE_Z",
+                                    if surface.reverse_z() { 1.0 } else { 0.0 },
+                                )],
+                                ..Default::default()
+                            },
                             buffers: &[],
                         },
                         primitive: wgpu::PrimitiveState {
@@ -361,7 +773,7 @@ fn create_pipeline(
                         depth_stencil: Some(wgpu::DepthStencilState {
                             format: surface.depth_format(),
                             depth_write_enabled: false,
-                            depth_compare: wgpu::CompareFunction::LessEqual,
+                            depth_compare: surface.depth_compare(wgpu::CompareFunction::LessEqual),
                             stencil: Default::default(),
                             bias: Default::default(),
                         }),
@@ -384,6 +796,7 @@ fn create_pipeline(
                 .entity(camera_entity.entity)
                 .insert(SkyboxPipeline {
                     skybox_pipeline,
+                    star_pipeline,
                     planet_pipeline,
                 });
         }
@@ -395,14 +808,24 @@ fn load_skybox(
     wgpu: Res<WgpuContext>,
     layout: Res<SkyboxLayout>,
     skyboxes: Populated<
-        (Entity, &Skybox, Option<&GlobalTransform>, Option<&Children>),
+        (
+            Entity,
+            &Skybox,
+            Option<&GlobalTransform>,
+            Option<&SkyboxDaylight>,
+            Option<&Children>,
+        ),
         Without<SkyboxBindGroup>,
     >,
     planets: Query<(&GlobalTransform, &Planet)>,
     mut commands: Commands,
 ) {
-    for (entity, skybox, transform, children) in skyboxes {
-        let mut data = transform.map_or_else(SkyboxData::default, SkyboxData::new);
+    for (entity, skybox, transform, daylight, children) in skyboxes {
+        let daylight = daylight.copied().unwrap_or_default();
+        let mut data = transform.map_or_else(
+            || SkyboxData::new(&GlobalTransform::identity(), &daylight),
+            |transform| SkyboxData::new(transform, &daylight),
+        );
 
         let mut num_planets = 0;
 
@@ -436,6 +859,10 @@ fn load_skybox(
                     binding: 1,
                     resource: wgpu::BindingResource::TextureView(&skybox.texture),
                 },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: skybox.stars.as_entire_binding(),
+                },
             ],
         });
 
@@ -443,6 +870,7 @@ fn load_skybox(
             bind_group,
             data_buffer,
             num_planets: num_planets.try_into().unwrap(),
+            num_stars: skybox.num_stars,
         });
     }
 }
@@ -452,13 +880,17 @@ fn update_skybox(
     skyboxes: Populated<(
         &mut SkyboxBindGroup,
         Ref<GlobalTransform>,
+        Option<Ref<SkyboxDaylight>>,
         Option<&Children>,
     )>,
     planets: Query<(Ref<GlobalTransform>, Ref<Planet>)>,
     mut staging: ResMut<Staging>,
 ) {
-    for (mut bind_group, skybox_transform, children) in skyboxes {
+    for (mut bind_group, skybox_transform, daylight, children) in skyboxes {
         let changed = skybox_transform.is_changed()
+            || daylight
+                .as_ref()
+                .is_some_and(|daylight| daylight.is_changed())
             || children
                 .into_iter()
                 .flatten()
@@ -468,7 +900,8 @@ fn update_skybox(
                 });
 
         if changed {
-            let mut data = SkyboxData::new(&skybox_transform);
+            let daylight = daylight.as_deref().copied().unwrap_or_default();
+            let mut data = SkyboxData::new(&skybox_transform, &daylight);
 
             let mut num_planets = 0;
 
@@ -514,13 +947,20 @@ impl RenderFunction for RenderSkybox {
             let pipeline = view;
 
             {
-                let span = render_pass.enter_span("skybox/stars");
+                let span = render_pass.enter_span("skybox/background");
                 render_pass.set_bind_group(1, Some(&bind_group.bind_group), &[]);
                 render_pass.set_pipeline(&pipeline.skybox_pipeline);
                 render_pass.draw(0..3, 0..1);
                 render_pass.exit_span(span);
             }
 
+            if bind_group.num_stars > 0 {
+                let span = render_pass.enter_span("skybox/stars");
+                render_pass.set_pipeline(&pipeline.star_pipeline);
+                render_pass.draw(0..(bind_group.num_stars * 6), 0..1);
+                render_pass.exit_span(span);
+            }
+
             if bind_group.num_planets > 0 {
                 let span = render_pass.enter_span("skybox/planets");
                 render_pass.set_pipeline(&pipeline.planet_pipeline);
@@ -538,22 +978,19 @@ const MAX_PLANETS: usize = 2;
 struct SkyboxData {
     model_matrix: Matrix4<f32>,
     planets: [PlanetData; MAX_PLANETS],
+    day_color: LinSrgba<f32>,
+    night: f32,
+    _padding: [u32; 3],
 }
 
 impl SkyboxData {
-    fn new(transform: &GlobalTransform) -> Self {
+    fn new(transform: &GlobalTransform, daylight: &SkyboxDaylight) -> Self {
         Self {
-            model_matrix: transform.isometry.to_homogeneous(),
-            planets: Zeroable::zeroed(),
-        }
-    }
-}
-
-impl Default for SkyboxData {
-    fn default() -> Self {
-        Self {
-            model_matrix: Matrix4::identity(),
+            model_matrix: transform.to_matrix(),
             planets: Zeroable::zeroed(),
+            day_color: daylight.day_color.into_linear(),
+            night: daylight.night.clamp(0.0, 1.0),
+            _padding: Default::default(),
         }
     }
 }
@@ -570,10 +1007,17 @@ struct PlanetData {
 impl PlanetData {
     fn new(transform: &GlobalTransform, planet: &Planet) -> Self {
         Self {
-            model_matrix: transform.isometry.to_homogeneous(),
+            model_matrix: transform.to_matrix(),
             texture_id: planet.texture.id(),
             scaling: planet.size,
             _padding: Default::default(),
         }
     }
 }
+
+#[derive(Clone, Copy, Debug, Pod, Zeroable)]
+#[repr(C)]
+struct StarData {
+    direction: Vector3<f32>,
+    brightness: f32,
+}
@@ -1,8 +1,18 @@
 
+// whether the depth buffer is reverse-Z, i.e. the far plane is at depth 0
+override REVERSE_Z: bool = false;
+
+// depth of the sky, just in front of the far plane
+override SKY_DEPTH: f32 = select(0.99999, 0.00001, REVERSE_Z);
+
 struct MainPassUniform {
     camera: Camera,
     time: f32,
-    // padding: 12 bytes
+    exposure: f32,
+    // padding: 8 bytes
+    light: Light,
+    fog: Fog,
+    overlay: Overlay,
 }
 
 struct Camera {
@@ -13,6 +23,29 @@ struct Camera {
     position: vec4f,
 }
 
+struct Light {
+    sun_direction: vec4f,
+    sun_color: vec4f,
+    moon_direction: vec4f,
+    moon_color: vec4f,
+    ambient_color: vec4f,
+}
+
+struct Fog {
+    // alpha is 0 if there is no fog
+    color: vec4f,
+    start: f32,
+    end: f32,
+    // padding: 8 bytes
+}
+
+struct Overlay {
+    // alpha is 0 if there is no overlay
+    color: vec4f,
+    distortion: f32,
+    // padding: 12 bytes
+}
+
 @group(0)
 @binding(0)
 var<uniform> main_pass_uniform: MainPassUniform;
@@ -39,6 +72,9 @@ const MAX_PLANETS: u32 = 2;
 struct SkyboxData {
     model_matrix: mat4x4f,
     planets: array<PlanetData, MAX_PLANETS>,
+    day_color: vec4f,
+    night: f32,
+    // padding 12 bytes
 }
 
 struct PlanetData {
@@ -56,6 +92,15 @@ var<uniform> skybox_data: SkyboxData;
 @binding(1)
 var skybox_texture: texture_cube<f32>;
 
+struct StarData {
+    direction: vec3f,
+    brightness: f32,
+}
+
+@group(1)
+@binding(2)
+var<storage, read> star_data: array<StarData>;
+
 
 
 @vertex
@@ -64,7 +109,7 @@ fn skybox_vertex(@builtin(vertex_index) vertex_index: u32) -> SkyboxOutput {
     let position = vec4f(
         f32((vertex_index & 1) << 2) - 1,
         f32((vertex_index & 2) << 1) - 1,
-        0.99999,
+        SKY_DEPTH,
         1,
     );
 
@@ -88,8 +133,10 @@ struct SkyboxOutput {
 
 @fragment
 fn skybox_fragment(input: SkyboxOutput) -> @location(0) vec4f {
+    // the stars are blended on top of this
     let color = textureSample(skybox_texture, default_sampler, input.texture_position);
-    return color;
+    let sky = mix(skybox_data.day_color, color, skybox_data.night);
+    return vec4f(sky.rgb * main_pass_uniform.exposure, sky.a);
 }
 
 
@@ -98,6 +145,54 @@ const QUAD_VERTICES = array(
     vec2f(1, 1), vec2f(1, 0), vec2f(0, 1),
 );
 
+// star size in view space (at distance 1)
+const STAR_SIZE: f32 = 0.003;
+
+@vertex
+fn star_vertex(@builtin(vertex_index) vertex_index: u32) -> StarOutput {
+    let star = star_data[vertex_index / 6];
+
+    let uv = QUAD_VERTICES[vertex_index % 6];
+    let vertex_offset = STAR_SIZE * (2 * uv - 1);
+
+    // rotate star into world frame, and then into camera frame without translation
+    var position = skybox_data.model_matrix * vec4f(star.direction, 0);
+    position = main_pass_uniform.camera.view * position;
+
+    // add vertex offset so we're actually drawing the relevant vertex
+    position += vec4f(vertex_offset, 0, 0);
+
+    // see planet_vertex
+    position = main_pass_uniform.camera.projection * position;
+    position.x /= position.w;
+    position.y /= position.w;
+    position.z = SKY_DEPTH * sign(position.w);
+    position.w = 1;
+
+    return StarOutput(position, uv, star.brightness * skybox_data.night);
+}
+
+struct StarOutput {
+    @builtin(position)
+    position: vec4f,
+
+    @location(0)
+    uv: vec2f,
+
+    @location(1)
+    @interpolate(flat, either)
+    brightness: f32,
+}
+
+@fragment
+fn star_fragment(input: StarOutput) -> @location(0) vec4f {
+    // round star with soft edge
+    let distance = length(2 * input.uv - 1);
+    let falloff = 1 - smoothstep(0.0, 1.0, distance);
+    return vec4f(vec3f(main_pass_uniform.exposure), input.brightness * falloff);
+}
+
+
 @vertex
 fn planet_vertex(@builtin(vertex_index) vertex_index: u32) -> PlanetOutput {
     let planet = skybox_data.planets[vertex_index / 6];
@@ -126,7 +221,7 @@ fn planet_vertex(@builtin(vertex_index) vertex_index: u32) -> PlanetOutput {
     // no perspective distortion
     position.x /= position.w;
     position.y /= position.w;
-    position.z = 0.99999 * sign(position.w);
+    position.z = SKY_DEPTH * sign(position.w);
     position.w = 1;
 
     return PlanetOutput(position, uv, planet.texture_id);
@@ -147,7 +242,8 @@ struct PlanetOutput {
 @fragment
 fn planet_fragment(input: PlanetOutput) -> @location(0) vec4f {
     let uv = atlas_map_uv(input.texture_id, input.uv);
-    return textureSample(atlas_texture, default_sampler, uv);
+    let color = textureSample(atlas_texture, default_sampler, uv);
+    return vec4f(color.rgb * main_pass_uniform.exposure, color.a);
 }
 
 
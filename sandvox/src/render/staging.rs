@@ -1,3 +1,5 @@
+use std::time::Instant;
+
 use bevy_ecs::{
     resource::Resource,
     system::{
@@ -7,19 +9,34 @@ use bevy_ecs::{
     },
 };
 
-use crate::wgpu::{
-    WgpuContext,
-    buffer::{
-        WriteStaging,
-        WriteStagingBelt,
-        WriteStagingCommit,
-        WriteStagingTransaction,
+use crate::{
+    profiler::capture,
+    render::readback::{
+        Readback,
+        ReadbackError,
+        ReadbackPool,
+        TextureReadback,
+    },
+    wgpu::{
+        WgpuContext,
+        buffer::{
+            StagingThroughput,
+            WriteStaging,
+            WriteStagingBelt,
+            WriteStagingCommit,
+            WriteStagingExt,
+            WriteStagingTransaction,
+        },
     },
 };
 
 /// Initializes a staging transaction before any rendering setup systems run
-pub(super) fn initialize_staging(wgpu: Res<WgpuContext>, mut commands: Commands) {
-    commands.insert_resource(Staging::new(&wgpu));
+pub(super) fn initialize_staging(
+    wgpu: Res<WgpuContext>,
+    readback_pool: Res<ReadbackPool>,
+    mut commands: Commands,
+) {
+    commands.insert_resource(Staging::new(&wgpu, readback_pool.clone()));
 }
 
 /// Flushes the current staging transaction.
@@ -27,8 +44,47 @@ pub(super) fn initialize_staging(wgpu: Res<WgpuContext>, mut commands: Commands)
 /// This is done in the setup schedule after rendering setup systems have run.
 /// During rendering this is done in `end_frames`
 #[profiling::function]
-pub(super) fn flush_staging(wgpu: Res<WgpuContext>, mut staging: ResMut<Staging>) {
-    wgpu.queue.submit([staging.flush(&wgpu).finish()]);
+pub(super) fn flush_staging(
+    wgpu: Res<WgpuContext>,
+    mut staging: ResMut<Staging>,
+    mut statistics: ResMut<StagingStatistics>,
+) {
+    let (command_encoder, throughput) = staging.flush(&wgpu);
+    statistics.total.accumulate(&throughput);
+    wgpu.queue.submit([command_encoder.finish()]);
+}
+
+/// How much data was uploaded through [`Staging`].
+#[derive(Clone, Copy, Debug, Default, Resource)]
+pub struct StagingStatistics {
+    /// Uploads of the last frame.
+    pub frame: StagingThroughput,
+
+    /// Uploads since startup.
+    pub total: StagingThroughput,
+}
+
+impl StagingStatistics {
+    pub(super) fn end_frame(&mut self, frame: StagingThroughput) {
+        profiling::scope!("staging");
+
+        // the numbers only show up in profile captures, so the common path doesn't
+        // allocate.
+        if capture::is_capturing() {
+            capture::record_counter(
+                "staging",
+                Instant::now(),
+                serde_json::json!({
+                    "bytes": frame.bytes,
+                    "copies": frame.copies,
+                    "largest_allocation": frame.largest_allocation,
+                }),
+            );
+        }
+
+        self.frame = frame;
+        self.total.accumulate(&frame);
+    }
 }
 
 // rename to `RenderStaging`? we think this should be only used for rendering
@@ -37,10 +93,12 @@ pub(super) fn flush_staging(wgpu: Res<WgpuContext>, mut staging: ResMut<Staging>
 pub struct Staging {
     staging_transaction:
         WriteStagingTransaction<WriteStagingBelt, wgpu::Device, wgpu::CommandEncoder>,
+    throughput: StagingThroughput,
+    readback_pool: ReadbackPool,
 }
 
 impl Staging {
-    pub fn new(wgpu: &WgpuContext) -> Self {
+    pub fn new(wgpu: &WgpuContext, readback_pool: ReadbackPool) -> Self {
         let command_encoder = wgpu
             .device
             .create_command_encoder(&wgpu::CommandEncoderDescriptor {
@@ -55,6 +113,8 @@ impl Staging {
 
         Self {
             staging_transaction,
+            throughput: Default::default(),
+            readback_pool,
         }
     }
 
@@ -65,9 +125,44 @@ impl Staging {
         &mut self.staging_transaction.command_encoder
     }
 
-    pub(super) fn flush(&mut self, wgpu: &WgpuContext) -> wgpu::CommandEncoder {
-        let staging = std::mem::replace(self, Self::new(&wgpu));
-        staging.staging_transaction.commit()
+    /// Reads back a buffer after the staged copies are submitted.
+    ///
+    /// See [`ReadbackPool::read_buffer`].
+    pub fn read_buffer(
+        &mut self,
+        source: wgpu::BufferSlice,
+    ) -> Result<Readback<Vec<u8>>, ReadbackError> {
+        self.readback_pool.read_buffer(
+            &self.staging_transaction.device,
+            &mut self.staging_transaction.command_encoder,
+            source,
+        )
+    }
+
+    /// Reads back a texture after the staged copies are submitted.
+    ///
+    /// See [`ReadbackPool::read_texture`].
+    pub fn read_texture(
+        &mut self,
+        source: wgpu::TexelCopyTextureInfo,
+        size: wgpu::Extent3d,
+    ) -> Result<Readback<TextureReadback>, ReadbackError> {
+        self.readback_pool.read_texture(
+            &self.staging_transaction.device,
+            &mut self.staging_transaction.command_encoder,
+            source,
+            size,
+        )
+    }
+
+    /// Commits the staging transaction and returns the command encoder with the
+    /// copies, and how much data was staged.
+    pub(super) fn flush(
+        &mut self,
+        wgpu: &WgpuContext,
+    ) -> (wgpu::CommandEncoder, StagingThroughput) {
+        let staging = std::mem::replace(self, Self::new(wgpu, self.readback_pool.clone()));
+        (staging.staging_transaction.commit(), staging.throughput)
     }
 }
 
@@ -79,6 +174,7 @@ impl WriteStaging for Staging {
         with_buffer_slice: impl FnOnce(&mut wgpu::CommandEncoder, wgpu::BufferSlice),
     ) -> wgpu::BufferViewMut {
         self.staging_transaction
+            .track_throughput(&mut self.throughput)
             .view_mut(size, alignment, with_buffer_slice)
     }
 }
@@ -0,0 +1,213 @@
+use std::{
+    f32::consts::TAU,
+    path::Path,
+};
+
+use color_eyre::eyre::{
+    Error,
+    OptionExt,
+    WrapErr,
+};
+use nalgebra::Vector3;
+use rand::{
+    Rng,
+    SeedableRng,
+};
+use rand_xoshiro::Xoroshiro128PlusPlus;
+
+/// Apparent magnitude of the faintest stars we render.
+pub const FAINTEST_MAGNITUDE: f32 = 6.5;
+
+/// Apparent magnitude at which stars are rendered at full brightness.
+pub const BRIGHTEST_MAGNITUDE: f32 = -1.5;
+
+/// Direction to a point on the celestial sphere in the skybox frame, from
+/// right ascension and declination (in radians).
+///
+/// The skybox textures are made from the NASA star maps, which have right
+/// ascension 0h in the center, increasing to the left. This uses the same
+/// mapping, so that stars line up with the rest of the sky, no matter if
+/// they're rendered from a catalog or drawn into the textures by
+/// `xtask make-skybox`.
+pub fn equatorial_to_direction(right_ascension: f32, declination: f32) -> Vector3<f32> {
+    Vector3::new(
+        declination.cos() * right_ascension.sin(),
+        declination.sin(),
+        -declination.cos() * right_ascension.cos(),
+    )
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Star {
+    /// Direction to the star in the skybox frame, see
+    /// [`equatorial_to_direction`].
+    pub direction: Vector3<f32>,
+
+    /// Apparent magnitude
+    pub magnitude: f32,
+
+    /// B-V color index, if known.
+    pub color_index: Option<f32>,
+}
+
+impl Star {
+    /// Creates a star from right ascension and declination (in radians).
+    pub fn from_equatorial(right_ascension: f32, declination: f32, magnitude: f32) -> Self {
+        Self {
+            direction: equatorial_to_direction(right_ascension, declination),
+            magnitude,
+            color_index: None,
+        }
+    }
+
+    /// Relative brightness in `0.0..=1.0`.
+    ///
+    /// Magnitudes are logarithmic, but a linear ramp looks better with the few
+    /// pixels a star covers.
+    pub fn brightness(&self) -> f32 {
+        ((FAINTEST_MAGNITUDE - self.magnitude) / (FAINTEST_MAGNITUDE - BRIGHTEST_MAGNITUDE))
+            .clamp(0.0, 1.0)
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct StarCatalog {
+    pub stars: Vec<Star>,
+}
+
+impl StarCatalog {
+    /// Loads a star catalog from a CSV file.
+    ///
+    /// Each line contains right ascension and declination (in degrees), the
+    /// apparent magnitude and optionally the B-V color index, separated by
+    /// commas. Empty lines and lines starting with `#` are ignored.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)
+            .wrap_err_with(|| format!("Could not read star catalog: {}", path.display()))?;
+        Self::parse(&text)
+    }
+
+    pub fn parse(text: &str) -> Result<Self, Error> {
+        let mut stars = vec![];
+
+        for (line_number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let parse_line = || -> Result<Star, Error> {
+                let mut fields = line.split(',').map(|field| field.trim().parse::<f32>());
+                let mut next_field = || {
+                    fields
+                        .next()
+                        .ok_or_eyre("Missing field")?
+                        .map_err(Error::from)
+                };
+
+                let right_ascension = next_field()?;
+                let declination = next_field()?;
+                let magnitude = next_field()?;
+                let color_index = fields.next().transpose()?;
+
+                Ok(Star {
+                    color_index,
+                    ..Star::from_equatorial(
+                        right_ascension.to_radians(),
+                        declination.to_radians(),
+                        magnitude,
+                    )
+                })
+            };
+
+            stars.push(
+                parse_line()
+                    .wrap_err_with(|| format!("Invalid star in line {}", line_number + 1))?,
+            );
+        }
+
+        Ok(Self { stars })
+    }
+
+    /// Generates a random star field.
+    ///
+    /// Stars are distributed uniformly over the sphere, with faint stars being
+    /// much more common than bright ones.
+    pub fn generate(seed: u64, count: usize) -> Self {
+        let mut rng = Xoroshiro128PlusPlus::seed_from_u64(seed);
+
+        let stars = (0..count)
+            .map(|_| {
+                let right_ascension = rng.random_range(0.0..TAU);
+                let declination = rng.random_range(-1.0f32..=1.0).asin();
+
+                // the number of stars grows roughly exponentially with magnitude
+                let magnitude = FAINTEST_MAGNITUDE
+                    - (FAINTEST_MAGNITUDE - BRIGHTEST_MAGNITUDE) * rng.random::<f32>().powi(6);
+
+                Star::from_equatorial(right_ascension, declination, magnitude)
+            })
+            .collect();
+
+        Self { stars }
+    }
+
+    pub fn len(&self) -> usize {
+        self.stars.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.stars.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::Vector3;
+
+    use crate::render::star_catalog::{
+        StarCatalog,
+        equatorial_to_direction,
+    };
+
+    #[test]
+    fn it_parses_a_catalog() {
+        let catalog =
+            StarCatalog::parse("# ra, dec, magnitude\n\n0, 90, -2.0\n90.0, 0.0, 6.5\n").unwrap();
+
+        assert_eq!(catalog.len(), 2);
+
+        let bright = catalog.stars[0];
+        assert!((bright.direction.y - 1.0).abs() < 1e-5);
+        assert_eq!(bright.brightness(), 1.0);
+
+        let faint = catalog.stars[1];
+        assert!((faint.direction.x - 1.0).abs() < 1e-5);
+        assert_eq!(faint.brightness(), 0.0);
+        assert_eq!(faint.color_index, None);
+
+        let catalog = StarCatalog::parse("101.287, -16.716, -1.46, 0.00").unwrap();
+        assert_eq!(catalog.stars[0].color_index, Some(0.0));
+    }
+
+    #[test]
+    fn it_maps_equatorial_coordinates_like_the_skybox_textures() {
+        // right ascension 0h is in the center of the star maps, which is -z in the
+        // cube map, and increases to the left, towards +x
+        let direction = equatorial_to_direction(0.0, 0.0);
+        assert!((direction - -Vector3::z()).norm() < 1e-5);
+
+        let direction = equatorial_to_direction(90f32.to_radians(), 0.0);
+        assert!((direction - Vector3::x()).norm() < 1e-5);
+
+        let direction = equatorial_to_direction(123f32.to_radians(), 90f32.to_radians());
+        assert!((direction - Vector3::y()).norm() < 1e-5);
+    }
+
+    #[test]
+    fn it_rejects_invalid_lines() {
+        assert!(StarCatalog::parse("1,2").is_err());
+        assert!(StarCatalog::parse("a,b,c").is_err());
+    }
+}
@@ -1,5 +1,6 @@
 use bevy_ecs::{
     component::Component,
+    message::MessageReader,
     name::NameOrEntity,
     query::{
         Changed,
@@ -13,12 +14,17 @@ use bevy_ecs::{
 };
 use nalgebra::Vector2;
 use palette::Srgba;
+use serde::{
+    Deserialize,
+    Serialize,
+};
 
 use crate::{
     app::{
         WindowHandle,
         WindowSize,
     },
+    config::ConfigChanged,
     render::RenderConfig,
     wgpu::WgpuContext,
 };
@@ -49,9 +55,27 @@ pub(super) fn reconfigure_surfaces(
 }
 
 #[profiling::function]
-pub(super) fn set_swap_chain_texture(windows: Populated<&mut Surface>) {
+pub(super) fn apply_surface_config(
+    wgpu: Res<WgpuContext>,
+    config: Res<RenderConfig>,
+    mut config_changed: MessageReader<ConfigChanged>,
+    windows: Populated<&mut Surface>,
+) {
+    if config_changed
+        .read()
+        .any(|changed| *changed == ConfigChanged::Render)
+    {
+        for mut surface in windows {
+            surface.set_vsync(&wgpu, config.vsync);
+            surface.set_post_pass(&wgpu, needs_post_pass(&config));
+        }
+    }
+}
+
+#[profiling::function]
+pub(super) fn set_swap_chain_texture(wgpu: Res<WgpuContext>, windows: Populated<&mut Surface>) {
     for mut surface in windows {
-        surface.ensure_swap_chain_texture();
+        surface.ensure_swap_chain_texture(&wgpu);
     }
 }
 
@@ -66,9 +90,15 @@ pub(super) fn present_surfaces(windows: Populated<&mut Surface>) {
 pub struct Surface {
     surface: wgpu::Surface<'static>,
     config: wgpu::SurfaceConfiguration,
+    format: SurfaceFormat,
     depth_texture: wgpu::TextureView,
     depth_format: wgpu::TextureFormat,
+    reverse_z: bool,
     swap_chain_texture: Option<SwapChainTexture>,
+
+    /// If the surface has a post pass, everything else renders into this
+    /// texture, which the post pass then draws into the swap chain texture.
+    color_texture: Option<wgpu::TextureView>,
 }
 
 impl Surface {
@@ -76,53 +106,65 @@ impl Surface {
         wgpu: &WgpuContext,
         window: &WindowHandle,
         size: Vector2<u32>,
-        config: &RenderConfig,
+        render_config: &RenderConfig,
     ) -> Self {
         let surface = wgpu.instance.create_surface(window.window.clone()).unwrap();
 
         let capabilities = surface.get_capabilities(&wgpu.adapter);
-        let surface_texture_format = *capabilities
-            .formats
-            .iter()
-            .filter(|format| format.is_srgb())
-            .next()
-            .unwrap_or_else(|| {
-                capabilities
-                    .formats
-                    .first()
-                    .expect("Surface has no supported texture formats")
-            });
+        let srgb_views = wgpu
+            .adapter
+            .get_downlevel_capabilities()
+            .flags
+            .contains(wgpu::DownlevelFlags::SURFACE_VIEW_FORMATS);
+        let format = render_config
+            .surface_format
+            .select(&capabilities.formats, srgb_views)
+            .expect("Surface has no supported texture formats");
 
-        tracing::debug!(?size, format = ?surface_texture_format, "created surface");
+        tracing::debug!(?size, ?format, "created surface");
 
         let config = wgpu::SurfaceConfiguration {
             usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
-            format: surface_texture_format,
+            format: format.swap_chain,
             width: size.x,
             height: size.y,
-            present_mode: if config.vsync {
-                wgpu::PresentMode::AutoVsync
+            present_mode: present_mode(render_config.vsync),
+            desired_maximum_frame_latency: 2,
+            alpha_mode: wgpu::CompositeAlphaMode::Auto,
+            view_formats: if format.view == format.swap_chain {
+                vec![]
             }
             else {
-                wgpu::PresentMode::AutoNoVsync
+                vec![format.view]
             },
-            desired_maximum_frame_latency: 2,
-            alpha_mode: wgpu::CompositeAlphaMode::Auto,
-            view_formats: vec![],
         };
         surface.configure(&wgpu.device, &config);
 
         // do we need to pick this from a set of supported ones?
-        let depth_stencil_format = wgpu::TextureFormat::Depth24Plus;
+        let reverse_z = render_config.reverse_z;
+        let depth_stencil_format = if reverse_z {
+            // reverse-Z only helps with a floating point depth buffer
+            wgpu::TextureFormat::Depth32Float
+        }
+        else {
+            wgpu::TextureFormat::Depth24Plus
+        };
         let depth_texture = create_depth_texture(wgpu, size, depth_stencil_format);
 
-        Self {
+        let mut surface = Self {
             surface,
             config,
+            format,
             depth_texture,
             depth_format: depth_stencil_format,
+            reverse_z,
             swap_chain_texture: None,
-        }
+            color_texture: None,
+        };
+
+        surface.set_post_pass(wgpu, needs_post_pass(render_config));
+
+        surface
     }
 
     pub fn size(&self) -> Vector2<u32> {
@@ -138,29 +180,125 @@ impl Surface {
             self.surface.configure(&wgpu.device, &self.config);
 
             self.depth_texture = create_depth_texture(wgpu, size, self.depth_format);
+
+            if self.color_texture.is_some() {
+                self.color_texture = Some(create_color_texture(wgpu, size, self.format.view));
+            }
+        }
+    }
+
+    /// Enables or disables the post pass for this surface.
+    pub fn set_post_pass(&mut self, wgpu: &WgpuContext, enable: bool) {
+        if enable != self.color_texture.is_some() {
+            tracing::debug!(enable, "toggling surface post pass");
+
+            self.color_texture =
+                enable.then(|| create_color_texture(wgpu, self.size(), self.format.view));
         }
     }
 
-    pub fn surface_texture(&self) -> &wgpu::TextureView {
-        let swap_chain_texture = self.swap_chain_texture.as_ref().unwrap();
-        &swap_chain_texture.texture_view
+    pub fn set_vsync(&mut self, wgpu: &WgpuContext, vsync: bool) {
+        let present_mode = present_mode(vsync);
+
+        if present_mode != self.config.present_mode {
+            tracing::debug!(?present_mode, "changing surface present mode");
+
+            self.config.present_mode = present_mode;
+            self.surface.configure(&wgpu.device, &self.config);
+        }
+    }
+
+    /// The texture to render to in this frame.
+    ///
+    /// This is `None` if no texture could be acquired, in which case the frame
+    /// is skipped for this surface. If the surface has a post pass, this is
+    /// the texture that the post pass reads from.
+    pub fn surface_texture(&self) -> Option<&wgpu::TextureView> {
+        let swap_chain_texture = self.swap_chain_texture()?;
+        Some(self.color_texture.as_ref().unwrap_or(swap_chain_texture))
+    }
+
+    /// The texture that will be presented in this frame.
+    pub fn swap_chain_texture(&self) -> Option<&wgpu::TextureView> {
+        self.swap_chain_texture
+            .as_ref()
+            .map(|swap_chain_texture| &swap_chain_texture.texture_view)
+    }
+
+    /// The texture the post pass reads from, if the surface has one.
+    pub fn post_pass_texture(&self) -> Option<&wgpu::TextureView> {
+        self.color_texture.as_ref()
     }
 
     pub fn depth_texture(&self) -> &wgpu::TextureView {
         &self.depth_texture
     }
 
+    /// The format that is rendered to, which all pipelines that render to this
+    /// surface use.
     pub fn surface_format(&self) -> wgpu::TextureFormat {
-        self.config.format
+        self.format.view
+    }
+
+    /// The format of the swap chain texture, which may differ from
+    /// [`surface_format`](Self::surface_format) in whether it's sRGB.
+    pub fn swap_chain_format(&self) -> wgpu::TextureFormat {
+        self.format.swap_chain
     }
 
     pub fn depth_format(&self) -> wgpu::TextureFormat {
         self.depth_format
     }
 
-    pub fn ensure_swap_chain_texture(&mut self) {
-        if self.swap_chain_texture.is_none() {
-            self.swap_chain_texture = Some(SwapChainTexture::new(&self.surface));
+    /// Whether the near plane is at depth 1 and the far plane at depth 0.
+    ///
+    /// Cameras that render to this surface must use a matching projection.
+    pub fn reverse_z(&self) -> bool {
+        self.reverse_z
+    }
+
+    /// The depth of the far plane, which the depth texture is cleared to.
+    pub fn far_depth(&self) -> f32 {
+        if self.reverse_z { 0.0 } else { 1.0 }
+    }
+
+    /// Returns the compare function to use with this surface's depth texture,
+    /// given the one for a regular depth buffer.
+    pub fn depth_compare(&self, compare: wgpu::CompareFunction) -> wgpu::CompareFunction {
+        if self.reverse_z {
+            reverse_depth_compare(compare)
+        }
+        else {
+            compare
+        }
+    }
+
+    pub fn ensure_swap_chain_texture(&mut self, wgpu: &WgpuContext) {
+        if self.swap_chain_texture.is_some() {
+            return;
+        }
+
+        let swap_chain_texture = match SwapChainTexture::new(&self.surface, self.format.view) {
+            Err(wgpu::SurfaceError::Outdated | wgpu::SurfaceError::Lost) => {
+                // try again with a fresh swap chain
+                tracing::debug!("reconfiguring outdated surface");
+                self.surface.configure(&wgpu.device, &self.config);
+                SwapChainTexture::new(&self.surface, self.format.view)
+            }
+            result => result,
+        };
+
+        match swap_chain_texture {
+            Ok(swap_chain_texture) => {
+                self.swap_chain_texture = Some(swap_chain_texture);
+            }
+            Err(wgpu::SurfaceError::OutOfMemory) => {
+                tracing::error!("out of memory while acquiring surface texture");
+                wgpu.device_lost.report("out of memory");
+            }
+            Err(error) => {
+                tracing::warn!(%error, "could not acquire surface texture. skipping frame.");
+            }
         }
     }
 
@@ -171,6 +309,15 @@ impl Surface {
     }
 }
 
+fn present_mode(vsync: bool) -> wgpu::PresentMode {
+    if vsync {
+        wgpu::PresentMode::AutoVsync
+    }
+    else {
+        wgpu::PresentMode::AutoNoVsync
+    }
+}
+
 #[derive(Debug)]
 struct SwapChainTexture {
     surface_texture: wgpu::SurfaceTexture,
@@ -178,21 +325,127 @@ struct SwapChainTexture {
 }
 
 impl SwapChainTexture {
-    fn new(surface: &wgpu::Surface) -> Self {
-        let surface_texture = surface.get_current_texture().unwrap();
+    fn new(
+        surface: &wgpu::Surface,
+        format: wgpu::TextureFormat,
+    ) -> Result<Self, wgpu::SurfaceError> {
+        let surface_texture = surface.get_current_texture()?;
         let texture_view = surface_texture
             .texture
             .create_view(&wgpu::TextureViewDescriptor {
                 label: Some("surface"),
+                format: Some(format),
                 ..Default::default()
             });
-        Self {
+        Ok(Self {
             surface_texture,
             texture_view,
+        })
+    }
+}
+
+/// Which format the swap chain of a surface has.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SurfaceFormatPreference {
+    /// 8-bit sRGB.
+    #[default]
+    Srgb,
+
+    /// 8-bit without sRGB encoding. Colors are still encoded as sRGB when
+    /// rendering, through an sRGB view of the swap chain texture, if the
+    /// hardware supports that.
+    Linear,
+
+    /// Half floats with linear colors, which can be brighter than white on HDR
+    /// displays. Falls back to sRGB, if the surface doesn't support it.
+    Hdr,
+}
+
+impl SurfaceFormatPreference {
+    /// Picks one of the `supported` formats. `srgb_views` is whether the swap
+    /// chain can be viewed with the sRGB variant of its format.
+    pub fn select(
+        &self,
+        supported: &[wgpu::TextureFormat],
+        srgb_views: bool,
+    ) -> Option<SurfaceFormat> {
+        let with_view = |swap_chain: &wgpu::TextureFormat| {
+            SurfaceFormat {
+                swap_chain: *swap_chain,
+                view: if srgb_views {
+                    swap_chain.add_srgb_suffix()
+                }
+                else {
+                    *swap_chain
+                },
+            }
+        };
+
+        let hdr = || {
+            supported
+                .iter()
+                .find(|format| **format == wgpu::TextureFormat::Rgba16Float)
+                .map(with_view)
+        };
+        let srgb = || {
+            supported
+                .iter()
+                .find(|format| format.is_srgb())
+                .map(with_view)
+        };
+        let linear = || {
+            supported
+                .iter()
+                .find(|format| !format.is_srgb() && format.add_srgb_suffix() != **format)
+                .map(with_view)
+        };
+
+        match self {
+            Self::Srgb => srgb().or_else(linear),
+            Self::Linear => linear().or_else(srgb),
+            Self::Hdr => hdr().or_else(srgb).or_else(linear),
         }
+        .or_else(|| supported.first().map(with_view))
     }
 }
 
+/// The formats of a surface.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SurfaceFormat {
+    /// Format of the swap chain textures.
+    pub swap_chain: wgpu::TextureFormat,
+
+    /// Format of the views of the swap chain textures that are rendered to.
+    pub view: wgpu::TextureFormat,
+}
+
+fn create_color_texture(
+    wgpu: &WgpuContext,
+    size: Vector2<u32>,
+    format: wgpu::TextureFormat,
+) -> wgpu::TextureView {
+    let color_texture = wgpu.device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("color texture"),
+        size: wgpu::Extent3d {
+            width: size.x,
+            height: size.y,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+        view_formats: &[],
+    });
+
+    color_texture.create_view(&wgpu::TextureViewDescriptor {
+        label: Some("color texture"),
+        ..Default::default()
+    })
+}
+
 fn create_depth_texture(
     wgpu: &WgpuContext,
     size: Vector2<u32>,
@@ -219,6 +472,23 @@ fn create_depth_texture(
     })
 }
 
+/// Whether surfaces need the intermediate texture of the post pass, for the
+/// color grading or to measure the luminance for the automatic exposure.
+fn needs_post_pass(config: &RenderConfig) -> bool {
+    !config.color_grading.is_neutral() || config.exposure.is_automatic()
+}
+
+/// Flips a depth compare function, such that it does the same with reverse-Z.
+fn reverse_depth_compare(compare: wgpu::CompareFunction) -> wgpu::CompareFunction {
+    match compare {
+        wgpu::CompareFunction::Less => wgpu::CompareFunction::Greater,
+        wgpu::CompareFunction::LessEqual => wgpu::CompareFunction::GreaterEqual,
+        wgpu::CompareFunction::Greater => wgpu::CompareFunction::Less,
+        wgpu::CompareFunction::GreaterEqual => wgpu::CompareFunction::LessEqual,
+        compare => compare,
+    }
+}
+
 #[derive(Clone, Copy, Debug, Component)]
 pub struct ClearColor(pub Srgba<f32>);
 
@@ -227,3 +497,53 @@ impl Default for ClearColor {
         Self(Srgba::new(0.0, 0.0, 0.0, 1.0))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::render::surface::{
+        SurfaceFormat,
+        SurfaceFormatPreference,
+    };
+
+    #[test]
+    fn it_selects_surface_formats() {
+        let supported = [
+            wgpu::TextureFormat::Bgra8Unorm,
+            wgpu::TextureFormat::Bgra8UnormSrgb,
+            wgpu::TextureFormat::Rgba16Float,
+        ];
+
+        assert_eq!(
+            SurfaceFormatPreference::Srgb.select(&supported, true),
+            Some(SurfaceFormat {
+                swap_chain: wgpu::TextureFormat::Bgra8UnormSrgb,
+                view: wgpu::TextureFormat::Bgra8UnormSrgb,
+            })
+        );
+        assert_eq!(
+            SurfaceFormatPreference::Linear.select(&supported, true),
+            Some(SurfaceFormat {
+                swap_chain: wgpu::TextureFormat::Bgra8Unorm,
+                view: wgpu::TextureFormat::Bgra8UnormSrgb,
+            })
+        );
+        assert_eq!(
+            SurfaceFormatPreference::Hdr.select(&supported, true),
+            Some(SurfaceFormat {
+                swap_chain: wgpu::TextureFormat::Rgba16Float,
+                view: wgpu::TextureFormat::Rgba16Float,
+            })
+        );
+
+        // e.g. on the web, where only non-sRGB formats are supported
+        let supported = [wgpu::TextureFormat::Rgba8Unorm];
+        assert_eq!(
+            SurfaceFormatPreference::Hdr.select(&supported, false),
+            Some(SurfaceFormat {
+                swap_chain: wgpu::TextureFormat::Rgba8Unorm,
+                view: wgpu::TextureFormat::Rgba8Unorm,
+            })
+        );
+        assert_eq!(SurfaceFormatPreference::Srgb.select(&[], true), None);
+    }
+}
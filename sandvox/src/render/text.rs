@@ -1,6 +1,9 @@
 use std::{
     collections::HashMap,
-    path::Path,
+    path::{
+        Path,
+        PathBuf,
+    },
 };
 
 use bevy_ecs::component::Component;
@@ -8,7 +11,14 @@ use bytemuck::{
     Pod,
     Zeroable,
 };
-use color_eyre::eyre::Error;
+use color_eyre::eyre::{
+    Error,
+    bail,
+};
+use image::{
+    GenericImage,
+    GrayImage,
+};
 use nalgebra::{
     Point2,
     Vector2,
@@ -17,11 +27,19 @@ use palette::{
     Srgba,
     WithAlpha,
 };
+use serde::{
+    Deserialize,
+    Serialize,
+};
 
 use crate::{
     render::{
+        debug_dump::write_dump,
         staging::Staging,
-        text::bdf::make_font_sheet,
+        text::{
+            bdf::make_font_sheet,
+            sdf::make_sdf,
+        },
     },
     wgpu::{
         TextureSourceLayout,
@@ -61,6 +79,30 @@ impl Default for TextColor {
     }
 }
 
+/// Draws the text a second time, offset and in a different color, behind the
+/// text.
+#[derive(Clone, Copy, Debug, Component)]
+pub struct TextShadow {
+    pub color: Srgba<f32>,
+
+    /// Offset of the shadow in font pixels, i.e. this is scaled with the
+    /// [`TextSize`].
+    pub offset: Vector2<f32>,
+}
+
+/// Draws an outline around every glyph, e.g. to make the text readable on any
+/// background.
+///
+/// The outline is made of copies of the glyph, offset in all 8 directions.
+#[derive(Clone, Copy, Debug, Component)]
+pub struct TextOutline {
+    pub color: Srgba<f32>,
+
+    /// Width of the outline in font pixels, i.e. this is scaled with the
+    /// [`TextSize`].
+    pub width: f32,
+}
+
 #[derive(Clone, Copy, Debug, Component)]
 pub struct TextSize {
     pub scaling: f32,
@@ -72,21 +114,65 @@ impl Default for TextSize {
     }
 }
 
+/// How the glyphs of a [`Font`] are stored and drawn.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FontRendering {
+    /// The glyph bitmaps as they are. Scaled text looks blocky.
+    Bitmap,
+
+    /// Signed distance fields of the glyphs, generated when the font is
+    /// loaded, unless the [`FontSheet`] already contains them. Text has smooth
+    /// edges at any [`TextSize`].
+    #[default]
+    Sdf,
+}
+
 #[derive(Debug)]
 pub struct Font {
     data: FontData,
     texture: wgpu::TextureView,
+    sampler: wgpu::Sampler,
     data_buffer: wgpu::Buffer,
 }
 
 impl Font {
+    /// Opens a BDF font, or a font sheet that was prebuilt with
+    /// [`FontSheet::save`].
     pub fn open(
         path: impl AsRef<Path>,
+        rendering: FontRendering,
         device: &wgpu::Device,
         staging: &mut Staging,
     ) -> Result<Self, Error> {
-        let bdf_data = std::fs::read_to_string(&path)?;
-        let (data, image) = make_font_sheet(&bdf_data)?;
+        let path = path.as_ref();
+
+        let sheet = if path.extension().is_some_and(|extension| extension == "bdf") {
+            FontSheet::from_bdf(&std::fs::read_to_string(path)?)?
+        }
+        else {
+            FontSheet::load(path)?
+        };
+
+        Ok(Self::new(sheet, rendering, device, staging))
+    }
+
+    pub fn new(
+        mut sheet: FontSheet,
+        mut rendering: FontRendering,
+        device: &wgpu::Device,
+        staging: &mut Staging,
+    ) -> Self {
+        match (rendering, sheet.sdf) {
+            (FontRendering::Sdf, false) => sheet = sheet.into_sdf(),
+            (FontRendering::Bitmap, true) => {
+                tracing::warn!("font sheet contains distance fields, rendering them as such");
+                rendering = FontRendering::Sdf;
+            }
+            _ => {}
+        }
+
+        let FontSheet { data, image, .. } = sheet;
 
         // create data buffer containing offsets and uvs for glyphs
         let data_buffer = {
@@ -109,7 +195,10 @@ impl Font {
                     bytemuck::from_bytes_mut(&mut view[..size_of::<FontDataBufferHeader>()]);
                 *view_header = FontDataBufferHeader {
                     num_glyphs: data.glyphs.len().try_into().unwrap(),
-                    _padding: 0,
+                    flags: match rendering {
+                        FontRendering::Bitmap => 0,
+                        FontRendering::Sdf => FONT_FLAG_SDF,
+                    },
                     atlas_size: data.atlas_size,
                 };
 
@@ -138,7 +227,9 @@ impl Font {
                 sample_count: 1,
                 dimension: wgpu::TextureDimension::D2,
                 format: wgpu::TextureFormat::R8Unorm,
-                usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING
+                    | wgpu::TextureUsages::COPY_DST
+                    | wgpu::TextureUsages::COPY_SRC,
                 view_formats: &[],
             });
 
@@ -164,11 +255,24 @@ impl Font {
             })
         };
 
-        Ok(Self {
+        // distance fields are interpolated, bitmaps are not
+        let filter_mode = match rendering {
+            FontRendering::Bitmap => wgpu::FilterMode::Nearest,
+            FontRendering::Sdf => wgpu::FilterMode::Linear,
+        };
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("font"),
+            mag_filter: filter_mode,
+            min_filter: filter_mode,
+            ..Default::default()
+        });
+
+        Self {
             data,
             texture,
+            sampler,
             data_buffer,
-        })
+        }
     }
 
     pub fn glyph_id(&self, character: char) -> Option<GlyphId> {
@@ -196,9 +300,40 @@ impl Font {
         (glyph.offset.into(), glyph.size)
     }
 
+    /// Writes the glyph atlas to `font.png` once it's read back.
+    pub fn dump(&self, directory: &Path, staging: &mut Staging) {
+        let path = directory.join("font.png");
+        tracing::debug!(?path, "dumping font");
+
+        let texture = self.texture.texture();
+        let readback = staging.read_texture(
+            wgpu::TexelCopyTextureInfo {
+                texture,
+                mip_level: 0,
+                origin: Default::default(),
+                aspect: Default::default(),
+            },
+            texture.size(),
+        );
+
+        match readback {
+            Ok(readback) => {
+                readback.then(move |result| {
+                    match result.map(|readback| readback.into_luma_image()) {
+                        Ok(Some(image)) => write_dump(&path, |path| Ok(image.save(path)?)),
+                        Ok(None) => tracing::error!("font texture has an unexpected format"),
+                        Err(error) => tracing::error!(%error, "couldn't read back font texture"),
+                    }
+                });
+            }
+            Err(error) => tracing::warn!(%error, "couldn't read back font texture"),
+        }
+    }
+
     pub fn resources(&self) -> FontResources<'_> {
         FontResources {
             texture: &self.texture,
+            sampler: &self.sampler,
             data_buffer: &self.data_buffer,
         }
     }
@@ -207,9 +342,283 @@ impl Font {
 #[derive(Clone, Copy, Debug)]
 pub struct FontResources<'a> {
     pub texture: &'a wgpu::TextureView,
+    pub sampler: &'a wgpu::Sampler,
     pub data_buffer: &'a wgpu::Buffer,
 }
 
+/// The glyph atlas of a font and where the glyphs are in it.
+///
+/// Font sheets are generated from BDF fonts when they're loaded, or prebuilt
+/// with `cargo xtask make-font-sheet`, which also rasterizes TTF fonts and can
+/// generate the distance fields ahead of time.
+#[derive(Clone, Debug)]
+pub struct FontSheet {
+    data: FontData,
+    image: GrayImage,
+
+    /// Whether the image contains distance fields, which are [`SDF_SCALE`]
+    /// times as large as the atlas.
+    sdf: bool,
+}
+
+impl FontSheet {
+    /// Packs the glyphs into a sheet.
+    ///
+    /// All glyphs are laid out in cells of `cell_size`, and `offset` is the
+    /// position of a glyph within its cell. Text is laid out on a grid of
+    /// `glyph_displacement`.
+    pub fn new(
+        cell_size: Vector2<u32>,
+        glyph_displacement: Vector2<f32>,
+        glyphs: Vec<GlyphBitmap>,
+        replacement: Option<char>,
+    ) -> Self {
+        let layout = SheetLayout::new(glyphs.len(), cell_size, Vector2::repeat(1));
+
+        let mut image = GrayImage::new(layout.sheet_size.x, layout.sheet_size.y);
+        let mut data = FontData {
+            glyphs: Vec::with_capacity(glyphs.len()),
+            codepoints: HashMap::with_capacity(glyphs.len()),
+            replacement_glyph: None,
+            glyph_displacement,
+            atlas_size: layout.sheet_size,
+        };
+
+        for (i, glyph) in glyphs.into_iter().enumerate() {
+            let size = Vector2::new(glyph.bitmap.width(), glyph.bitmap.height());
+            assert!(
+                (glyph.offset + size)
+                    .zip_map(&cell_size, |end, cell| end <= cell)
+                    .iter()
+                    .all(|fits| *fits),
+                "glyph {:?} doesn't fit into its cell",
+                glyph.character
+            );
+
+            let atlas_offset = layout.cell_offset(i as u32) + glyph.offset;
+            image
+                .copy_from(&glyph.bitmap, atlas_offset.x, atlas_offset.y)
+                .unwrap();
+
+            data.glyphs.push(Glyph {
+                atlas_offset,
+                size,
+                offset: glyph.offset,
+            });
+            data.codepoints.insert(glyph.character, GlyphId(i as u32));
+        }
+
+        data.replacement_glyph =
+            replacement.and_then(|character| data.codepoints.get(&character).copied());
+
+        Self {
+            data,
+            image,
+            sdf: false,
+        }
+    }
+
+    pub fn from_bdf(bdf_data: &str) -> Result<Self, Error> {
+        make_font_sheet(bdf_data)
+    }
+
+    /// Replaces the glyph bitmaps with signed distance fields.
+    pub fn into_sdf(self) -> Self {
+        if self.sdf {
+            return self;
+        }
+
+        Self {
+            image: make_sdf(&self.image, SDF_SCALE, SDF_SPREAD),
+            sdf: true,
+            ..self
+        }
+    }
+
+    pub fn num_glyphs(&self) -> usize {
+        self.data.glyphs.len()
+    }
+
+    pub fn image(&self) -> &GrayImage {
+        &self.image
+    }
+
+    /// Loads a font sheet from a TOML file with the glyphs, and the image next
+    /// to it.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let file: FontSheetFile = toml::from_slice(&std::fs::read(path)?)?;
+
+        if file.version != FORMAT_VERSION {
+            bail!("Unsupported font sheet version: {}", file.version);
+        }
+
+        let image_path = path.parent().unwrap_or(Path::new(".")).join(&file.image);
+        let image = image::open(&image_path)?.into_luma8();
+
+        let scale = if file.sdf { SDF_SCALE } else { 1 };
+        if Vector2::new(image.width(), image.height()) != file.atlas_size * scale {
+            bail!(
+                "Expected a {}x{} image: {}",
+                file.atlas_size.x * scale,
+                file.atlas_size.y * scale,
+                image_path.display()
+            );
+        }
+
+        let mut data = FontData {
+            glyphs: Vec::with_capacity(file.glyphs.len()),
+            codepoints: HashMap::with_capacity(file.glyphs.len()),
+            replacement_glyph: None,
+            glyph_displacement: file.glyph_displacement,
+            atlas_size: file.atlas_size,
+        };
+
+        for (i, glyph) in file.glyphs.into_iter().enumerate() {
+            let end = glyph.atlas_offset + glyph.size;
+            if end.x > file.atlas_size.x || end.y > file.atlas_size.y {
+                bail!("Glyph {:?} is outside of the atlas", glyph.character);
+            }
+
+            data.glyphs.push(Glyph {
+                atlas_offset: glyph.atlas_offset,
+                size: glyph.size,
+                offset: glyph.offset,
+            });
+            data.codepoints.insert(glyph.character, GlyphId(i as u32));
+        }
+
+        data.replacement_glyph = file
+            .replacement
+            .and_then(|character| data.codepoints.get(&character).copied());
+
+        Ok(Self {
+            data,
+            image,
+            sdf: file.sdf,
+        })
+    }
+
+    /// Writes the font sheet to a TOML file, and the image to a PNG file next
+    /// to it.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), Error> {
+        let path = path.as_ref();
+        let image_path = path.with_extension("png");
+
+        let mut characters = vec![None; self.data.glyphs.len()];
+        for (character, glyph_id) in &self.data.codepoints {
+            characters[glyph_id.to_index()] = Some(*character);
+        }
+
+        let file = FontSheetFile {
+            version: FORMAT_VERSION,
+            image: image_path.file_name().unwrap().into(),
+            sdf: self.sdf,
+            atlas_size: self.data.atlas_size,
+            glyph_displacement: self.data.glyph_displacement,
+            replacement: self
+                .data
+                .replacement_glyph
+                .and_then(|glyph_id| characters[glyph_id.to_index()]),
+            glyphs: self
+                .data
+                .glyphs
+                .iter()
+                .zip(characters)
+                .map(|(glyph, character)| {
+                    FontSheetGlyph {
+                        character: character.unwrap(),
+                        atlas_offset: glyph.atlas_offset,
+                        size: glyph.size,
+                        offset: glyph.offset,
+                    }
+                })
+                .collect(),
+        };
+
+        std::fs::write(path, toml::to_string(&file)?)?;
+        self.image.save(image_path)?;
+
+        Ok(())
+    }
+}
+
+/// Bitmap of a single glyph, to build a [`FontSheet`].
+#[derive(Clone, Debug)]
+pub struct GlyphBitmap {
+    pub character: char,
+
+    /// Position of the glyph within its cell.
+    pub offset: Vector2<u32>,
+
+    pub bitmap: GrayImage,
+}
+
+/// Increased when the font sheet file format changes.
+const FORMAT_VERSION: u32 = 1;
+
+#[derive(Debug, Serialize, Deserialize)]
+struct FontSheetFile {
+    version: u32,
+
+    /// Path of the image, relative to the font sheet file.
+    image: PathBuf,
+
+    sdf: bool,
+    atlas_size: Vector2<u32>,
+    glyph_displacement: Vector2<f32>,
+    replacement: Option<char>,
+    glyphs: Vec<FontSheetGlyph>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct FontSheetGlyph {
+    character: char,
+    atlas_offset: Vector2<u32>,
+    size: Vector2<u32>,
+    offset: Vector2<u32>,
+}
+
+#[derive(Debug)]
+struct SheetLayout {
+    padding: Vector2<u32>,
+    cell_size: Vector2<u32>,
+    glyphs_per_row: u32,
+    sheet_size: Vector2<u32>,
+}
+
+impl SheetLayout {
+    fn new(num_glyphs: usize, cell_size: Vector2<u32>, padding: Vector2<u32>) -> Self {
+        let padded_cell_size = cell_size + padding;
+
+        let mut glyphs_per_row = (num_glyphs as f32).sqrt().floor() as u32;
+
+        let mut sheet_size = Vector2::new(glyphs_per_row * padded_cell_size.x, 0);
+
+        // make sure width is a multiple of 256 as GPUs tend to use a multiple of it as
+        // row-stride (I think)
+        sheet_size.x = sheet_size
+            .x
+            .next_multiple_of(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT);
+
+        glyphs_per_row = (sheet_size.x - padding.x) / padded_cell_size.x;
+        let num_rows = (num_glyphs as u32).div_ceil(glyphs_per_row);
+        sheet_size.y = num_rows * padded_cell_size.y + padding.y;
+
+        Self {
+            padding,
+            cell_size,
+            glyphs_per_row,
+            sheet_size,
+        }
+    }
+
+    fn cell_offset(&self, i: u32) -> Vector2<u32> {
+        let cell = Vector2::new(i % self.glyphs_per_row, i / self.glyphs_per_row);
+        cell.component_mul(&(self.cell_size + self.padding)) + self.padding
+    }
+}
+
 #[derive(Clone, Debug)]
 struct FontData {
     glyphs: Vec<Glyph>,
@@ -223,7 +632,7 @@ struct FontData {
 #[repr(C)]
 struct FontDataBufferHeader {
     num_glyphs: u32,
-    _padding: u32,
+    flags: u32,
     atlas_size: Vector2<u32>,
 }
 
@@ -235,6 +644,15 @@ struct Glyph {
     offset: Vector2<u32>,
 }
 
+/// Set in the font data header if the texture contains signed distance fields.
+const FONT_FLAG_SDF: u32 = 1;
+
+/// Resolution of the distance fields, in texels per font pixel.
+const SDF_SCALE: u32 = 4;
+
+/// Distance (in font pixels) at which the distance fields saturate.
+const SDF_SPREAD: f32 = 1.0;
+
 #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, derive_more::Into)]
 pub struct GlyphId(u32);
 
@@ -247,8 +665,6 @@ impl GlyphId {
 mod bdf {
     // this might be helpful: https://www.x.org/releases/X11R7.6/doc/xorg-docs/specs/XLFD/xlfd.html#pixel_size
 
-    use std::collections::HashMap;
-
     use color_eyre::eyre::Error;
     use image::{
         GrayImage,
@@ -260,9 +676,8 @@ mod bdf {
     };
 
     use crate::render::text::{
-        FontData,
-        Glyph,
-        GlyphId,
+        FontSheet,
+        GlyphBitmap,
     };
 
     #[derive(Clone, Copy, Debug, Default)]
@@ -295,98 +710,16 @@ mod bdf {
         Vector2::new(coord.x, coord.y)
     }
 
-    #[derive(Debug)]
-    struct SheetLayout {
-        padding: Vector2<u32>,
-        cell_size: Vector2<u32>,
-        glyphs_per_row: u32,
-        sheet_size: Vector2<u32>,
-    }
-
-    impl SheetLayout {
-        fn new(num_glyphs: usize, cell_size: Vector2<u32>, padding: Vector2<u32>) -> Self {
-            let padded_cell_size = cell_size + padding;
-
-            let mut glyphs_per_row = (num_glyphs as f32).sqrt().floor() as u32;
-
-            let mut sheet_size = Vector2::new(glyphs_per_row * padded_cell_size.x, 0);
-
-            // make sure width is a multiple of 256 as GPUs tend to use a multiple of it as
-            // row-stride (I think)
-            sheet_size.x = sheet_size
-                .x
-                .next_multiple_of(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT);
-
-            glyphs_per_row = (sheet_size.x - padding.x) / padded_cell_size.x;
-            let num_rows = (num_glyphs as u32).div_ceil(glyphs_per_row);
-            sheet_size.y = num_rows * padded_cell_size.y + padding.y;
-
-            Self {
-                padding,
-                cell_size,
-                glyphs_per_row,
-                sheet_size,
-            }
-        }
-
-        fn cell_offset(&self, i: u32) -> Vector2<u32> {
-            let cell = Vector2::new(i % self.glyphs_per_row, i / self.glyphs_per_row);
-            cell.component_mul(&(self.cell_size + self.padding)) + self.padding
-        }
-    }
-
-    pub(super) fn make_font_sheet(bdf_data: &str) -> Result<(FontData, GrayImage), Error> {
+    pub(super) fn make_font_sheet(bdf_data: &str) -> Result<FontSheet, Error> {
         const LUMA_FG: Luma<u8> = Luma([255]);
         const LUMA_BG: Luma<u8> = Luma([0]);
 
         let font = bdf_parser::Font::parse(&bdf_data)?;
 
-        // count glyphs with codepoints
-        let num_glyphs = font
-            .glyphs
-            .iter()
-            .filter(|glyph| matches!(glyph.encoding, bdf_parser::Encoding::Standard(_)))
-            .count();
-
-        // create sheet layout
         let global_bbox: Bbox = font.metadata.bounding_box.into();
         //assert_eq!(global_bbox.size(), Vector2::new(6, 13));
 
-        let sheet_layout = SheetLayout::new(num_glyphs, global_bbox.size(), Vector2::repeat(1));
-
-        // create sheet image
-        let mut font_image = GrayImage::from_pixel(
-            sheet_layout.sheet_size.x,
-            sheet_layout.sheet_size.y,
-            LUMA_BG,
-        );
-
-        // create font data
-        let mut font_data = FontData {
-            glyphs: Vec::with_capacity(num_glyphs as usize),
-            codepoints: HashMap::with_capacity(num_glyphs as usize),
-            atlas_size: sheet_layout.sheet_size,
-            glyph_displacement: Vector2::new(
-                font.metadata
-                    .properties
-                    .try_get::<i32>(bdf_parser::Property::FigureWidth)
-                    .unwrap()
-                    .unwrap() as f32,
-                font.metadata
-                    .properties
-                    .try_get::<i32>(bdf_parser::Property::PixelSize)
-                    .unwrap()
-                    .unwrap() as f32,
-            ),
-            replacement_glyph: font
-                .metadata
-                .properties
-                .try_get::<i32>(bdf_parser::Property::DefaultChar)
-                .ok()
-                .flatten()
-                .map(|glyph_id| GlyphId(glyph_id as u32)),
-        };
-        let mut i = 0;
+        let mut glyphs = vec![];
 
         for glyph in font.glyphs.iter() {
             if let bdf_parser::Encoding::Standard(encoding) = glyph.encoding
@@ -406,33 +739,260 @@ mod bdf {
                     glyph_offset.try_cast::<u32>().unwrap()
                 };
 
-                let atlas_offset = sheet_layout.cell_offset(i) + glyph_offset;
+                let bitmap = GrayImage::from_fn(glyph_size.x, glyph_size.y, |x, y| {
+                    let pixel = glyph.pixel(x as usize, y as usize).unwrap_or_default();
+                    if pixel { LUMA_FG } else { LUMA_BG }
+                });
 
-                font_data.glyphs.push(Glyph {
-                    atlas_offset,
-                    size: glyph_size,
+                glyphs.push(GlyphBitmap {
+                    character,
                     offset: glyph_offset,
+                    bitmap,
                 });
+            }
+        }
+
+        let glyph_displacement = Vector2::new(
+            font.metadata
+                .properties
+                .try_get::<i32>(bdf_parser::Property::FigureWidth)
+                .unwrap()
+                .unwrap() as f32,
+            font.metadata
+                .properties
+                .try_get::<i32>(bdf_parser::Property::PixelSize)
+                .unwrap()
+                .unwrap() as f32,
+        );
 
-                font_data.codepoints.insert(character, GlyphId(i));
+        let replacement = font
+            .metadata
+            .properties
+            .try_get::<i32>(bdf_parser::Property::DefaultChar)
+            .ok()
+            .flatten()
+            .and_then(|encoding| char::from_u32(encoding as u32));
+
+        Ok(FontSheet::new(
+            global_bbox.size(),
+            glyph_displacement,
+            glyphs,
+            replacement,
+        ))
+    }
+}
 
-                for y in 0..glyph_size.y {
-                    for x in 0..glyph_size.x {
-                        let pixel = glyph.pixel(x as usize, y as usize).unwrap_or_default();
-                        let target_coord = Vector2::new(x, y) + atlas_offset;
+mod sdf {
+    //! Signed distance fields from bitmaps.
+    //!
+    //! The bitmap is upscaled and the distances are computed with the exact
+    //! euclidean distance transform from [Felzenszwalb and Huttenlocher][1].
+    //!
+    //! [1]: https://cs.brown.edu/people/pfelzens/papers/dt-final.pdf
 
-                        font_image.put_pixel(
-                            target_coord.x,
-                            target_coord.y,
-                            if pixel { LUMA_FG } else { LUMA_BG },
-                        );
-                    }
+    use image::{
+        GrayImage,
+        Luma,
+    };
+
+    /// Creates a signed distance field from `bitmap`, which is `scale` times
+    /// as large.
+    ///
+    /// Pixels of the bitmap are inside if they're at least half bright. The
+    /// field is 0.5 on the edges, and goes to 1 inside and 0 outside at a
+    /// distance of `spread` bitmap pixels.
+    pub(super) fn make_sdf(bitmap: &GrayImage, scale: u32, spread: f32) -> GrayImage {
+        let width = (bitmap.width() * scale) as usize;
+        let height = (bitmap.height() * scale) as usize;
+
+        let is_inside =
+            |x: usize, y: usize| bitmap.get_pixel(x as u32 / scale, y as u32 / scale).0[0] >= 128;
+
+        // squared distances to the nearest inside and outside texel
+        let mut to_inside = vec![0.0; width * height];
+        let mut to_outside = vec![0.0; width * height];
+        for y in 0..height {
+            for x in 0..width {
+                let inside = is_inside(x, y);
+                to_inside[y * width + x] = if inside { 0.0 } else { INFINITY };
+                to_outside[y * width + x] = if inside { INFINITY } else { 0.0 };
+            }
+        }
+
+        let mut transform = DistanceTransform::new(width.max(height));
+        transform.transform_2d(&mut to_inside, width, height);
+        transform.transform_2d(&mut to_outside, width, height);
+
+        GrayImage::from_fn(width as u32, height as u32, |x, y| {
+            let index = y as usize * width + x as usize;
+
+            // the edge is half a texel away from the centers of the texels next to it
+            let distance = if to_outside[index] > 0.0 {
+                to_outside[index].sqrt() - 0.5
+            }
+            else {
+                0.5 - to_inside[index].sqrt()
+            };
+
+            let value = 0.5 + distance / (2.0 * spread * scale as f32);
+            Luma([(value.clamp(0.0, 1.0) * 255.0).round() as u8])
+        })
+    }
+
+    const INFINITY: f32 = 1e20;
+
+    /// Buffers for the 1D distance transform.
+    struct DistanceTransform {
+        input: Vec<f32>,
+        output: Vec<f32>,
+        parabolas: Vec<usize>,
+        boundaries: Vec<f32>,
+    }
+
+    impl DistanceTransform {
+        fn new(max_length: usize) -> Self {
+            Self {
+                input: vec![0.0; max_length],
+                output: vec![0.0; max_length],
+                parabolas: vec![0; max_length],
+                boundaries: vec![0.0; max_length + 1],
+            }
+        }
+
+        /// Transforms the columns and then the rows of `grid`, which contains 0
+        /// for the texels we want the distance to and [`INFINITY`] otherwise.
+        fn transform_2d(&mut self, grid: &mut [f32], width: usize, height: usize) {
+            for x in 0..width {
+                for y in 0..height {
+                    self.input[y] = grid[y * width + x];
+                }
+                self.transform_1d(height);
+                for y in 0..height {
+                    grid[y * width + x] = self.output[y];
                 }
+            }
 
-                i += 1;
+            for y in 0..height {
+                self.input[..width].copy_from_slice(&grid[y * width..][..width]);
+                self.transform_1d(width);
+                grid[y * width..][..width].copy_from_slice(&self.output[..width]);
             }
         }
 
-        Ok((font_data, font_image))
+        /// Computes the lower envelope of the parabolas rooted at the input
+        /// values.
+        fn transform_1d(&mut self, n: usize) {
+            let f = &self.input;
+            let v = &mut self.parabolas;
+            let z = &mut self.boundaries;
+
+            let intersection = |q: usize, p: usize| {
+                ((f[q] + (q * q) as f32) - (f[p] + (p * p) as f32)) / (2 * q - 2 * p) as f32
+            };
+
+            let mut k = 0;
+            v[0] = 0;
+            z[0] = -INFINITY;
+            z[1] = INFINITY;
+
+            for q in 1..n {
+                let mut s = intersection(q, v[k]);
+                while s <= z[k] {
+                    k -= 1;
+                    s = intersection(q, v[k]);
+                }
+                k += 1;
+                v[k] = q;
+                z[k] = s;
+                z[k + 1] = INFINITY;
+            }
+
+            k = 0;
+            for q in 0..n {
+                while z[k + 1] < q as f32 {
+                    k += 1;
+                }
+                let d = q as f32 - v[k] as f32;
+                self.output[q] = d * d + f[v[k]];
+            }
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use image::{
+            GrayImage,
+            Luma,
+        };
+
+        use crate::render::text::sdf::make_sdf;
+
+        #[test]
+        fn it_puts_the_edge_at_half_value() {
+            let mut bitmap = GrayImage::new(3, 3);
+            bitmap.put_pixel(1, 1, Luma([255]));
+
+            let sdf = make_sdf(&bitmap, 4, 1.0);
+            assert_eq!(sdf.dimensions(), (12, 12));
+
+            // center of the pixel is inside, the corners are outside
+            assert!(sdf.get_pixel(6, 6).0[0] > 128);
+            assert_eq!(sdf.get_pixel(0, 0).0[0], 0);
+
+            // the texels on either side of the edge are equally far away from it
+            let inside = sdf.get_pixel(4, 6).0[0] as i32;
+            let outside = sdf.get_pixel(3, 6).0[0] as i32;
+            assert!(((inside - 128) + (outside - 128)).abs() <= 1);
+            assert!(inside > 128 && outside < 128);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{
+        GrayImage,
+        Luma,
+    };
+    use nalgebra::Vector2;
+
+    use crate::render::text::{
+        FontSheet,
+        GlyphBitmap,
+    };
+
+    #[test]
+    fn it_loads_saved_font_sheets() {
+        let glyphs = ['a', 'b', '?']
+            .into_iter()
+            .enumerate()
+            .map(|(i, character)| {
+                GlyphBitmap {
+                    character,
+                    offset: Vector2::new(i as u32, 1),
+                    bitmap: GrayImage::from_pixel(2, 3, Luma([100 * i as u8])),
+                }
+            })
+            .collect();
+        let sheet = FontSheet::new(
+            Vector2::new(4, 4),
+            Vector2::new(4.0, 5.0),
+            glyphs,
+            Some('?'),
+        );
+
+        let path = std::env::temp_dir().join(format!("font-sheet-{}.toml", std::process::id()));
+        sheet.save(&path).unwrap();
+        let loaded = FontSheet::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        std::fs::remove_file(path.with_extension("png")).unwrap();
+
+        assert_eq!(loaded.image, sheet.image);
+        assert_eq!(loaded.data.codepoints, sheet.data.codepoints);
+        assert_eq!(loaded.data.replacement_glyph, sheet.data.replacement_glyph);
+        assert_eq!(loaded.data.atlas_size, sheet.data.atlas_size);
+        for (a, b) in loaded.data.glyphs.iter().zip(&sheet.data.glyphs) {
+            assert_eq!(bytemuck::bytes_of(a), bytemuck::bytes_of(b));
+        }
     }
 }
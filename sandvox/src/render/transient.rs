@@ -0,0 +1,355 @@
+//! Textures and buffers that are only needed for part of a frame.
+//!
+//! Passes acquire them from the [`TransientPool`] by descriptor, and they're
+//! returned to the pool when the handle is dropped. A pass that runs later in
+//! the same frame, or in a later frame, then gets the same allocation, if it
+//! asks for the same descriptor. The commands that use a resource are
+//! submitted in the order of the passes, so a later pass only overwrites the
+//! contents after the earlier pass is done with them.
+//!
+//! Resources that weren't used for [`MAX_UNUSED_FRAMES`] frames are freed.
+
+use std::{
+    collections::HashMap,
+    hash::Hash,
+    sync::Arc,
+};
+
+use bevy_ecs::{
+    resource::Resource,
+    system::{
+        Res,
+        ResMut,
+    },
+};
+use parking_lot::Mutex;
+
+/// How many frames free resources are kept around.
+pub const MAX_UNUSED_FRAMES: u64 = 3;
+
+#[derive(Clone, Debug, Default, Resource)]
+pub struct TransientPool {
+    inner: Arc<Mutex<TransientPoolState>>,
+}
+
+#[derive(Debug, Default)]
+struct TransientPoolState {
+    frame: u64,
+    textures: FreeList<TextureKey, wgpu::Texture>,
+    buffers: FreeList<BufferKey, wgpu::Buffer>,
+}
+
+impl TransientPool {
+    /// Returns a texture matching the descriptor, reusing a free one if
+    /// possible.
+    ///
+    /// The label is only used when a new texture is created.
+    pub fn texture(
+        &self,
+        device: &wgpu::Device,
+        descriptor: &wgpu::TextureDescriptor,
+    ) -> TransientTexture {
+        let key = TextureKey::new(descriptor);
+        let texture = self
+            .inner
+            .lock()
+            .textures
+            .acquire(&key, || device.create_texture(descriptor));
+
+        TransientTexture {
+            texture: Some(texture),
+            key,
+            pool: self.clone(),
+        }
+    }
+
+    /// Returns a buffer matching the descriptor, reusing a free one if
+    /// possible.
+    ///
+    /// The label is only used when a new buffer is created. The buffer can't
+    /// be mapped at creation.
+    pub fn buffer(
+        &self,
+        device: &wgpu::Device,
+        descriptor: &wgpu::BufferDescriptor,
+    ) -> TransientBuffer {
+        assert!(
+            !descriptor.mapped_at_creation,
+            "transient buffers can't be mapped at creation"
+        );
+
+        let key = BufferKey {
+            size: descriptor.size,
+            usage: descriptor.usage,
+        };
+        let buffer = self
+            .inner
+            .lock()
+            .buffers
+            .acquire(&key, || device.create_buffer(descriptor));
+
+        TransientBuffer {
+            buffer: Some(buffer),
+            key,
+            pool: self.clone(),
+        }
+    }
+
+    pub fn statistics(&self) -> TransientPoolStatistics {
+        let state = self.inner.lock();
+        TransientPoolStatistics {
+            textures: state.textures.statistics(|key| key.memory_footprint()),
+            buffers: state.buffers.statistics(|key| key.size),
+        }
+    }
+
+    /// Frees resources that weren't used for a while, and starts counting
+    /// the allocations of the next frame.
+    fn end_frame(&self) {
+        let mut state = self.inner.lock();
+        state.frame += 1;
+        let frame = state.frame;
+        state.textures.end_frame(frame);
+        state.buffers.end_frame(frame);
+    }
+}
+
+/// A texture from the [`TransientPool`]. It's returned to the pool when this
+/// is dropped.
+#[derive(Debug)]
+pub struct TransientTexture {
+    texture: Option<wgpu::Texture>,
+    key: TextureKey,
+    pool: TransientPool,
+}
+
+impl TransientTexture {
+    pub fn texture(&self) -> &wgpu::Texture {
+        self.texture.as_ref().unwrap()
+    }
+}
+
+impl Drop for TransientTexture {
+    fn drop(&mut self) {
+        if let Some(texture) = self.texture.take() {
+            let mut state = self.pool.inner.lock();
+            let frame = state.frame;
+            state.textures.release(self.key.clone(), texture, frame);
+        }
+    }
+}
+
+/// A buffer from the [`TransientPool`]. It's returned to the pool when this is
+/// dropped.
+#[derive(Debug)]
+pub struct TransientBuffer {
+    buffer: Option<wgpu::Buffer>,
+    key: BufferKey,
+    pool: TransientPool,
+}
+
+impl TransientBuffer {
+    pub fn buffer(&self) -> &wgpu::Buffer {
+        self.buffer.as_ref().unwrap()
+    }
+}
+
+impl Drop for TransientBuffer {
+    fn drop(&mut self) {
+        if let Some(buffer) = self.buffer.take() {
+            let mut state = self.pool.inner.lock();
+            let frame = state.frame;
+            state.buffers.release(self.key, buffer, frame);
+        }
+    }
+}
+
+/// The descriptor of a texture, without the label.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+struct TextureKey {
+    size: wgpu::Extent3d,
+    mip_level_count: u32,
+    sample_count: u32,
+    dimension: wgpu::TextureDimension,
+    format: wgpu::TextureFormat,
+    usage: wgpu::TextureUsages,
+    view_formats: Vec<wgpu::TextureFormat>,
+}
+
+impl TextureKey {
+    fn new(descriptor: &wgpu::TextureDescriptor) -> Self {
+        Self {
+            size: descriptor.size,
+            mip_level_count: descriptor.mip_level_count,
+            sample_count: descriptor.sample_count,
+            dimension: descriptor.dimension,
+            format: descriptor.format,
+            usage: descriptor.usage,
+            view_formats: descriptor.view_formats.to_vec(),
+        }
+    }
+
+    fn memory_footprint(&self) -> u64 {
+        (0..self.mip_level_count)
+            .map(|level| {
+                let size = self.size.mip_level_size(level, self.dimension);
+                self.format.theoretical_memory_footprint(size)
+            })
+            .sum::<u64>()
+            * u64::from(self.sample_count)
+    }
+}
+
+/// The descriptor of a buffer, without the label.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+struct BufferKey {
+    size: wgpu::BufferAddress,
+    usage: wgpu::BufferUsages,
+}
+
+#[derive(Debug)]
+struct FreeList<K, R> {
+    /// Free resources, with the frame in which they were last used.
+    free: HashMap<K, Vec<(R, u64)>>,
+
+    /// Number of resources per key, free or in use.
+    allocated: HashMap<K, usize>,
+
+    /// Statistics of the current frame.
+    reused: usize,
+    created: usize,
+}
+
+impl<K, R> Default for FreeList<K, R> {
+    fn default() -> Self {
+        Self {
+            free: HashMap::new(),
+            allocated: HashMap::new(),
+            reused: 0,
+            created: 0,
+        }
+    }
+}
+
+impl<K, R> FreeList<K, R>
+where
+    K: Clone + Eq + Hash,
+{
+    fn acquire(&mut self, key: &K, create: impl FnOnce() -> R) -> R {
+        if let Some((resource, _)) = self.free.get_mut(key).and_then(|free| free.pop()) {
+            self.reused += 1;
+            resource
+        }
+        else {
+            self.created += 1;
+            *self.allocated.entry(key.clone()).or_default() += 1;
+            create()
+        }
+    }
+
+    fn release(&mut self, key: K, resource: R, frame: u64) {
+        self.free.entry(key).or_default().push((resource, frame));
+    }
+
+    fn end_frame(&mut self, frame: u64) {
+        let allocated = &mut self.allocated;
+
+        self.free.retain(|key, free| {
+            free.retain(|(_, last_used)| {
+                let keep = last_used + MAX_UNUSED_FRAMES >= frame;
+                if !keep && let Some(count) = allocated.get_mut(key) {
+                    *count -= 1;
+                }
+                keep
+            });
+            !free.is_empty()
+        });
+        allocated.retain(|_, count| *count > 0);
+
+        self.reused = 0;
+        self.created = 0;
+    }
+
+    fn statistics(&self, size: impl Fn(&K) -> u64) -> TransientResourceStatistics {
+        TransientResourceStatistics {
+            allocated: self.allocated.values().sum(),
+            free: self.free.values().map(Vec::len).sum(),
+            bytes: self
+                .allocated
+                .iter()
+                .map(|(key, count)| size(key) * *count as u64)
+                .sum(),
+            reused: self.reused,
+            created: self.created,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Resource)]
+pub struct TransientPoolStatistics {
+    pub textures: TransientResourceStatistics,
+    pub buffers: TransientResourceStatistics,
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TransientResourceStatistics {
+    /// Number of resources in the pool, free or in use.
+    pub allocated: usize,
+
+    /// Number of resources that are not in use.
+    pub free: usize,
+
+    /// Approximate size of all resources in the pool (in bytes).
+    pub bytes: u64,
+
+    /// How many resources were reused during the last frame.
+    pub reused: usize,
+
+    /// How many resources were created during the last frame.
+    pub created: usize,
+}
+
+/// Records the statistics of the frame and frees unused resources.
+pub(super) fn end_transient_frame(
+    pool: Res<TransientPool>,
+    mut statistics: ResMut<TransientPoolStatistics>,
+) {
+    *statistics = pool.statistics();
+    pool.end_frame();
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::render::transient::{
+        FreeList,
+        MAX_UNUSED_FRAMES,
+    };
+
+    #[test]
+    fn it_reuses_and_frees_resources() {
+        let mut free_list = FreeList::<u32, &str>::default();
+
+        let a = free_list.acquire(&1, || "a");
+        let b = free_list.acquire(&1, || "b");
+        free_list.release(1, a, 0);
+
+        // reused in the same frame
+        assert_eq!(free_list.acquire(&2, || "c"), "c");
+        assert_eq!(free_list.acquire(&1, || "d"), "a");
+        free_list.release(1, "a", 0);
+        free_list.release(1, b, 0);
+
+        let statistics = free_list.statistics(|_| 10);
+        assert_eq!(statistics.allocated, 3);
+        assert_eq!(statistics.free, 2);
+        assert_eq!(statistics.bytes, 30);
+        assert_eq!((statistics.reused, statistics.created), (1, 3));
+
+        free_list.end_frame(MAX_UNUSED_FRAMES);
+        assert_eq!(free_list.statistics(|_| 10).free, 2);
+
+        free_list.end_frame(MAX_UNUSED_FRAMES + 1);
+        let statistics = free_list.statistics(|_| 10);
+        assert_eq!((statistics.allocated, statistics.free), (1, 0));
+    }
+}
@@ -83,7 +83,7 @@ impl Plugin for SoundPlugin {
     }
 }
 
-#[derive(Clone, Debug, Default, Serialize, Deserialize, Resource)]
+#[derive(Clone, Debug, PartialEq, Default, Serialize, Deserialize, Resource)]
 #[serde(deny_unknown_fields)]
 pub struct SoundConfig {
     pub host: Option<String>,
@@ -99,7 +99,7 @@ pub struct SoundConfig {
     pub music_volume: Volume,
 }
 
-#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 #[serde(transparent)]
 pub struct Volume(pub f32);
 
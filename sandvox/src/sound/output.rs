@@ -1,4 +1,13 @@
-use std::sync::Arc;
+use std::{
+    sync::{
+        Arc,
+        atomic::{
+            AtomicU32,
+            Ordering,
+        },
+    },
+    time::Duration,
+};
 
 use bevy_ecs::{
     resource::Resource,
@@ -14,9 +23,12 @@ use color_eyre::eyre::{
     eyre,
 };
 use rodio::{
+    ChannelCount,
     DeviceSinkBuilder,
     DeviceTrait,
     MixerDeviceSink,
+    Sample,
+    SampleRate,
     Source,
     cpal::{
         self,
@@ -24,10 +36,16 @@ use rodio::{
     },
 };
 
-use crate::sound::{
-    SoundConfig,
-    Volume,
-    sounds::SoundSource,
+use crate::{
+    sound::{
+        SoundConfig,
+        Volume,
+        sounds::SoundSource,
+    },
+    util::stats_alloc::{
+        MemoryTag,
+        memory_scope,
+    },
 };
 
 #[derive(Clone, derive_more::Debug, Resource)]
@@ -35,8 +53,18 @@ pub struct SoundOutput {
     #[debug(skip)]
     sink: Arc<MixerDeviceSink>,
     master_volume: Volume,
+
+    /// Cutoff frequency (in Hz) of the low-pass filter that is applied to all
+    /// sounds.
+    muffle: Arc<AtomicU32>,
 }
 
+/// Cutoff frequency of the low-pass filter when sounds aren't muffled.
+const UNMUFFLED_CUTOFF: u32 = 20_000;
+
+/// How often playing sounds pick up a changed cutoff frequency.
+const MUFFLE_UPDATE_PERIOD: Duration = Duration::from_millis(50);
+
 impl SoundOutput {
     pub fn new(config: &SoundConfig) -> Result<Self, Error> {
         let host = config.host.as_ref().map_or_else(
@@ -60,6 +88,7 @@ impl SoundOutput {
         Ok(Self {
             sink: Arc::new(sink),
             master_volume: config.master_volume,
+            muffle: Arc::new(AtomicU32::new(UNMUFFLED_CUTOFF)),
         })
     }
 
@@ -67,10 +96,88 @@ impl SoundOutput {
         let mixer = self.sink.mixer();
 
         match source {
-            SoundSource::Buffered(buffered) => mixer.add(buffered.amplify(self.master_volume.0)),
-            SoundSource::Streaming(decoder) => mixer.add(decoder.amplify(self.master_volume.0)),
+            SoundSource::Buffered(buffered) => {
+                mixer.add(
+                    self.muffled(AudioMemoryScope(buffered))
+                        .amplify(self.master_volume.0),
+                )
+            }
+            SoundSource::Streaming(decoder) => {
+                mixer.add(
+                    self.muffled(AudioMemoryScope(decoder))
+                        .amplify(self.master_volume.0),
+                )
+            }
         }
     }
+
+    /// Muffles all sounds with a low-pass filter, e.g. while the player is
+    /// under water.
+    ///
+    /// This also applies to sounds that are already playing. Pass `None` to
+    /// stop muffling.
+    pub fn set_muffle(&self, cutoff: Option<u32>) {
+        self.muffle
+            .store(cutoff.unwrap_or(UNMUFFLED_CUTOFF), Ordering::Relaxed);
+    }
+
+    fn muffled<S>(&self, source: S) -> impl Source + use<S>
+    where
+        S: Source + Send + 'static,
+    {
+        let muffle = self.muffle.clone();
+
+        source
+            .low_pass(UNMUFFLED_CUTOFF)
+            .periodic_access(MUFFLE_UPDATE_PERIOD, move |filter| {
+                // the filter becomes unstable above the nyquist frequency
+                let nyquist = filter.sample_rate().get() / 2;
+                let cutoff = muffle.load(Ordering::Relaxed).min(nyquist * 9 / 10);
+                filter.to_low_pass(cutoff);
+            })
+    }
+}
+
+/// Tags the allocations of a source with [`MemoryTag::Audio`].
+///
+/// Sources are decoded lazily on the audio thread, while they're played.
+struct AudioMemoryScope<S>(S);
+
+impl<S: Source> Iterator for AudioMemoryScope<S> {
+    type Item = Sample;
+
+    #[inline]
+    fn next(&mut self) -> Option<Sample> {
+        let _memory_scope = memory_scope(MemoryTag::Audio);
+        self.0.next()
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl<S: Source> Source for AudioMemoryScope<S> {
+    #[inline]
+    fn current_span_len(&self) -> Option<usize> {
+        self.0.current_span_len()
+    }
+
+    #[inline]
+    fn channels(&self) -> ChannelCount {
+        self.0.channels()
+    }
+
+    #[inline]
+    fn sample_rate(&self) -> SampleRate {
+        self.0.sample_rate()
+    }
+
+    #[inline]
+    fn total_duration(&self) -> Option<Duration> {
+        self.0.total_duration()
+    }
 }
 
 /// System that configures the [`SoundOutput`]
@@ -25,7 +25,13 @@ use rodio::{
     source::Buffered,
 };
 
-use crate::sound::sounds::config::SoundDef;
+use crate::{
+    sound::sounds::config::SoundDef,
+    util::stats_alloc::{
+        MemoryTag,
+        memory_scope,
+    },
+};
 
 #[derive(Clone, Debug, Resource)]
 pub struct Sounds {
@@ -118,6 +124,8 @@ impl Sound {
     }
 
     pub fn decoder(&self) -> Result<Decoder<File>, Error> {
+        let _memory_scope = memory_scope(MemoryTag::Audio);
+
         tracing::debug!(path = ?self.path, "reading sound file");
         let file = File::open(&self.path).with_note(|| self.path.display().to_string())?;
         let decoder = Decoder::new_vorbis(file).with_note(|| self.path.display().to_string())?;
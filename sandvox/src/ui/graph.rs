@@ -0,0 +1,99 @@
+use bevy_ecs::{
+    component::Component,
+    name::NameOrEntity,
+    query::Changed,
+    schedule::IntoScheduleConfigs,
+    system::Populated,
+};
+use nalgebra::{
+    Point2,
+    Vector2,
+};
+use palette::Srgba;
+
+use crate::{
+    ecs::{
+        plugin::WorldBuilder,
+        schedule,
+    },
+    ui::{
+        FinalLayout,
+        RenderBufferBuilder,
+        Root,
+        UiSystems,
+        view::View,
+    },
+};
+
+/// Stacked bar graph.
+///
+/// Each bar is drawn from the bottom up, with one segment per value. The
+/// segments are colored with the color of the same index.
+#[derive(Clone, Debug, Default, Component)]
+pub struct BarGraph {
+    pub bars: Vec<Vec<f32>>,
+    pub colors: Vec<Srgba<f32>>,
+
+    /// Value at the top of the graph. Larger bars are cut off.
+    pub max_value: f32,
+}
+
+pub(super) fn setup_graph_systems(builder: &mut WorldBuilder) {
+    builder.add_systems(
+        schedule::Render,
+        (
+            request_redraw.before(UiSystems::Render),
+            render_bar_graphs.in_set(UiSystems::Render),
+        ),
+    );
+}
+
+fn request_redraw(nodes: Populated<&Root, Changed<BarGraph>>, mut views: Populated<&mut View>) {
+    for root in nodes {
+        let mut view = views.get_mut(root.root).unwrap();
+        view.render = true;
+    }
+}
+
+fn render_bar_graphs(
+    nodes: Populated<(NameOrEntity, &BarGraph, &FinalLayout, &Root)>,
+    mut views: Populated<(&View, &mut RenderBufferBuilder)>,
+) {
+    for (entity, graph, final_layout, root) in nodes {
+        let (view, mut render_buffer_builder) = views.get_mut(root.root).unwrap();
+
+        if !view.render || graph.bars.is_empty() || graph.max_value <= 0.0 {
+            continue;
+        }
+
+        tracing::trace!(%entity, bars = graph.bars.len(), "render bar graph");
+
+        let offset = Point2::new(final_layout.location.x, final_layout.location.y);
+        let size = Vector2::new(final_layout.size.width, final_layout.size.height);
+        let bar_width = size.x / graph.bars.len() as f32;
+        let scale = size.y / graph.max_value;
+
+        for (i, bar) in graph.bars.iter().enumerate() {
+            let x = offset.x + i as f32 * bar_width;
+            let mut height = 0.0;
+
+            for (value, color) in bar.iter().zip(graph.colors.iter().cycle()) {
+                let segment_height = (value * scale).min(size.y - height);
+                if segment_height <= 0.0 {
+                    break;
+                }
+
+                height += segment_height;
+
+                render_buffer_builder
+                    .push_quad(
+                        Point2::new(x, offset.y + size.y - height),
+                        Vector2::new(bar_width, segment_height),
+                        final_layout.depth + 1,
+                        Some(*color),
+                    )
+                    .set_solid_color();
+            }
+        }
+    }
+}
@@ -57,6 +57,10 @@ use crate::{
         UiSystems,
         view::View,
     },
+    util::stats_alloc::{
+        MemoryTag,
+        memory_scope,
+    },
 };
 
 pub trait LeafMeasure: Send + Sync + 'static {
@@ -118,6 +122,8 @@ fn calculate_tree_layouts<L>(mut tree: TreeInner<L>, views: Populated<(Entity, &
 where
     L: LeafMeasure,
 {
+    let _memory_scope = memory_scope(MemoryTag::Ui);
+
     for (entity, view) in views.iter() {
         let mut tree = Tree {
             inner: &mut tree,
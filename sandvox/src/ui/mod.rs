@@ -1,7 +1,10 @@
+mod graph;
 mod layout;
 mod render;
 mod sprites;
 mod text;
+mod theme;
+mod toast;
 mod view;
 
 use bevy_ecs::{
@@ -19,6 +22,7 @@ use bevy_ecs::{
 use color_eyre::eyre::Error;
 
 pub use crate::ui::{
+    graph::BarGraph,
     layout::{
         FinalLayout,
         LayoutCache,
@@ -32,13 +36,28 @@ pub use crate::ui::{
     },
     sprites::{
         Background,
+        Opacity,
         Sprites,
     },
+    theme::{
+        Palette,
+        PaletteKind,
+        PaletteRole,
+        ThemeColor,
+        ThemeConfig,
+    },
+    toast::{
+        Severity,
+        Toast,
+        Toasts,
+    },
     view::View,
 };
 use crate::{
+    assets::AssetPlugin,
     ecs::{
         plugin::{
+            Dependencies,
             Plugin,
             WorldBuilder,
         },
@@ -49,6 +68,7 @@ use crate::{
         UiPassSystems,
     },
     ui::{
+        graph::setup_graph_systems,
         layout::{
             LayoutConfig,
             setup_layout_systems,
@@ -59,6 +79,8 @@ use crate::{
             TextLeafMeasure,
             setup_text_systems,
         },
+        theme::setup_theme_systems,
+        toast::setup_toast_systems,
         view::setup_view_systems,
     },
 };
@@ -67,6 +89,13 @@ use crate::{
 pub struct UiPlugin;
 
 impl Plugin for UiPlugin {
+    fn dependencies(&self, dependencies: &mut Dependencies) -> Result<(), Error> {
+        dependencies
+            .require_plugin::<AssetPlugin>()
+            .add_plugin(UiPassPlugin)?;
+        Ok(())
+    }
+
     fn setup(&self, builder: &mut WorldBuilder) -> Result<(), Error> {
         setup_view_systems(builder);
         setup_layout_systems(
@@ -78,9 +107,11 @@ impl Plugin for UiPlugin {
         setup_render_systems(builder);
         setup_text_systems(builder);
         setup_sprite_systems(builder);
+        setup_graph_systems(builder);
+        setup_theme_systems(builder);
+        setup_toast_systems(builder);
 
         builder
-            .add_plugin(UiPassPlugin)?
             .configure_system_sets(
                 schedule::Render,
                 UiSystems::Layout.before(UiSystems::Render),
@@ -56,6 +56,7 @@ use crate::{
                 UiPassSystems,
             },
         },
+        remove_on_gpu_setup,
         render_target::RenderTarget,
         staging::Staging,
         surface::Surface,
@@ -65,6 +66,10 @@ use crate::{
         UiSystems,
         view::View,
     },
+    util::stats_alloc::{
+        MemoryTag,
+        memory_scope,
+    },
     wgpu::{
         WgpuContext,
         buffer::TypedArrayBuffer,
@@ -75,10 +80,14 @@ use crate::{
 pub(super) fn setup_render_systems(builder: &mut WorldBuilder) {
     builder
         .add_systems(
-            schedule::Startup,
-            create_layout
-                .in_set(RenderSystems::Setup)
-                .after(UiPassSystems::Prepare),
+            schedule::GpuSetup,
+            (
+                create_layout
+                    .in_set(RenderSystems::Setup)
+                    .after(UiPassSystems::Prepare),
+                remove_on_gpu_setup::<UiPipeline>,
+                remove_on_gpu_setup::<RenderBuffer>,
+            ),
         )
         .add_systems(
             schedule::Render,
@@ -216,7 +225,8 @@ fn create_pipeline(
                             compilation_options: Default::default(),
                             targets: &[Some(wgpu::ColorTargetState {
                                 format: surface.surface_format(),
-                                blend: None,
+                                // for the smooth edges of SDF glyphs
+                                blend: Some(wgpu::BlendState::ALPHA_BLENDING),
                                 write_mask: wgpu::ColorWrites::ALL,
                             })],
                         }),
@@ -235,13 +245,19 @@ fn create_pipeline(
 #[profiling::function]
 fn create_render_buffer(
     wgpu: Res<WgpuContext>,
-    views: Populated<NameOrEntity, (With<UiPass>, Without<RenderBuffer>)>,
+    views: Populated<(NameOrEntity, Option<&mut View>), (With<UiPass>, Without<RenderBuffer>)>,
     mut commands: Commands,
 ) {
     // todo: remove stale buffers (e.g. from a surface that is not a ui render
     // target anymore)
 
-    for view_entity in views {
+    for (view_entity, view) in views {
+        // the render buffer is also created again after the device was lost, and then
+        // the ui has to be rendered into it again.
+        if let Some(mut view) = view {
+            view.render = true;
+        }
+
         tracing::debug!(viewport = %view_entity, "creating render buffer");
 
         commands.entity(view_entity.entity).insert((
@@ -271,6 +287,8 @@ fn flush_render_buffers(
 ) {
     tracing::trace!("flusing render buffers");
 
+    let _memory_scope = memory_scope(MemoryTag::Ui);
+
     for (mut render_buffer, mut render_buffer_builder) in render_buffers {
         // sort quads by order
         render_buffer_builder.sort();
@@ -443,7 +461,9 @@ impl RenderBufferBuilder {
     }
 
     fn sort(&mut self) {
-        self.quads.sort_unstable_by_key(|quad| quad.order);
+        // this needs to be stable, because quads with the same order are drawn in the
+        // order they were pushed (e.g. text shadows)
+        self.quads.sort_by_key(|quad| quad.order);
     }
 
     fn layers(&self) -> impl Iterator<Item = Range<u32>> {
@@ -502,6 +522,14 @@ impl<'a> QuadBuilder<'a> {
         self.quad.texture_id = glyph_id;
         self
     }
+
+    /// Fills the quad with its tint.
+    pub fn set_solid_color(&mut self) -> &mut Self {
+        const SOLID_COLOR: u32 = 0xffff_fffe;
+
+        self.quad.texture_id = SOLID_COLOR;
+        self
+    }
 }
 
 #[derive(Debug, Component)]
@@ -27,7 +27,7 @@ var<storage, read> atlas_data: array<AtlasEntry>;
 
 struct FontData {
     num_glyphs: u32,
-    // padding: 4 bytes
+    flags: u32,
     atlas_size: vec2u,
     glyphs: array<FontGlyph>
 }
@@ -46,8 +46,18 @@ var font_texture: texture_2d<f32>;
 @binding(5)
 var<storage, read> font_data: FontData;
 
+@group(0)
+@binding(6)
+var font_sampler: sampler;
+
+// the font texture contains signed distance fields instead of bitmaps
+const FONT_FLAG_SDF: u32 = 1;
+
 const GLYPH_BIT: u32 = 0x80000000;
 
+// quads with this texture ID are filled with their tint
+const SOLID_COLOR: u32 = 0xfffffffe;
+
 struct Quad {
     position: vec2f,
     size: vec2f,
@@ -82,6 +92,9 @@ fn debug_vertex(@builtin(vertex_index) vertex_index: u32) -> DebugVertexOutput {
     if quad.texture_id == 0xffffffff {
         color = vec4f(1, 0, 0, 1);
     }
+    else if quad.texture_id == SOLID_COLOR {
+        color = vec4f(1, 1, 0, 1);
+    }
     else if (quad.texture_id & GLYPH_BIT) == 0 {
         color = vec4f(0, 0, 1, 1);
     }
@@ -155,7 +168,10 @@ struct QuadVertexOutput {
 
 @fragment
 fn quad_fragment(input: QuadVertexOutput) -> @location(0) vec4f {
-    if (input.texture_id & GLYPH_BIT) == 0 {
+    if input.texture_id == SOLID_COLOR {
+        return input.tint;
+    }
+    else if (input.texture_id & GLYPH_BIT) == 0 {
         // atlas texture
 
         let atlas_id = input.texture_id;
@@ -167,7 +183,8 @@ fn quad_fragment(input: QuadVertexOutput) -> @location(0) vec4f {
             discard;
         }
 
-        return color;
+        // only the alpha of the tint is used, e.g. to fade out panels
+        return vec4f(color.rgb, color.a * input.tint.a);
     }
     else {
         // font glyph
@@ -175,13 +192,26 @@ fn quad_fragment(input: QuadVertexOutput) -> @location(0) vec4f {
         let glyph_id = input.texture_id & (~GLYPH_BIT);
         let uv = glyph_map_uv(glyph_id, input.uv);
 
-        let luma = textureSample(font_texture, default_sampler, uv).r;
+        let value = textureSample(font_texture, font_sampler, uv).r;
 
-        if luma < 0.5 {
-            discard;
+        if (font_data.flags & FONT_FLAG_SDF) != 0 {
+            // the edge is at 0.5. smooth it over about one screen pixel.
+            let smoothing = 0.5 * fwidth(value);
+            let coverage = smoothstep(0.5 - smoothing, 0.5 + smoothing, value);
+
+            if coverage <= 0.0 {
+                discard;
+            }
+
+            return vec4f(input.tint.rgb, input.tint.a * coverage);
         }
+        else {
+            if value < 0.5 {
+                discard;
+            }
 
-        return input.tint;
+            return input.tint;
+        }
     }
 }
 
@@ -4,23 +4,33 @@ use std::{
         hash_map,
     },
     ops::Index,
-    path::Path,
+    path::{
+        Path,
+        PathBuf,
+    },
 };
 
 use bevy_ecs::{
     component::Component,
     name::NameOrEntity,
-    query::Changed,
+    query::{
+        Changed,
+        Or,
+    },
     resource::Resource,
     schedule::IntoScheduleConfigs,
     system::{
         Commands,
         Populated,
+        Query,
         Res,
         ResMut,
     },
 };
-use color_eyre::eyre::Error;
+use color_eyre::eyre::{
+    Error,
+    eyre,
+};
 use image::{
     GenericImageView,
     RgbaImage,
@@ -29,9 +39,15 @@ use nalgebra::{
     Point2,
     Vector2,
 };
+use palette::Srgba;
 use serde::Deserialize;
 
 use crate::{
+    assets::{
+        Asset,
+        AssetHandle,
+        AssetServer,
+    },
     ecs::{
         plugin::WorldBuilder,
         schedule,
@@ -56,7 +72,10 @@ use crate::{
         sprites::ui_defs::MarginDef,
         view::View,
     },
-    util::image::ImageLoadExt,
+    util::image::{
+        ImageLoadExt,
+        ImageSizeExt,
+    },
     wgpu::WgpuContext,
 };
 
@@ -64,6 +83,7 @@ use crate::{
 pub struct Sprites {
     sprites: Vec<Sprite>,
     by_name: HashMap<String, SpriteId>,
+    by_emoji: HashMap<char, SpriteId>,
 }
 
 impl Sprites {
@@ -78,29 +98,108 @@ impl Sprites {
         self.by_name.get(name).copied()
     }
 
+    /// The sprite that is drawn for an emoji in text, if the font doesn't
+    /// have a glyph for it.
+    ///
+    /// Sprites are used for emoji if they have an `emoji` key in `ui.toml`.
+    pub fn emoji(&self, character: char) -> Option<&Sprite> {
+        self.by_emoji
+            .get(&character)
+            .map(|sprite_id| &self[*sprite_id])
+    }
+
     pub fn load(
         path: impl AsRef<Path>,
         device: &wgpu::Device,
         atlas: &mut Atlas,
         staging: &mut Staging,
     ) -> Result<Self, Error> {
-        let toml_directory = path.as_ref().parent().unwrap();
-        let toml = std::fs::read(&path)?;
+        let images = SpriteImages::load(path.as_ref())?;
+        Self::new(&images, device, atlas, staging)
+    }
+
+    pub fn new(
+        images: &SpriteImages,
+        device: &wgpu::Device,
+        atlas: &mut Atlas,
+        staging: &mut Staging,
+    ) -> Result<Self, Error> {
+        let mut sprites = Sprites::default();
+
+        for (name, sprite_image) in &images.sprites {
+            let atlas_handle = atlas.insert_image(
+                &sprite_image.image,
+                Some(PaddingMode {
+                    padding: Padding::uniform(1),
+                    fill: PaddingFill::TRANSPARENT,
+                }),
+                device,
+                staging,
+            )?;
+
+            let nine_patch = sprite_image
+                .nine_patch
+                .map(|margin| NinePatch::new(&atlas_handle, atlas, margin));
+
+            let sprite_id = sprites.insert(
+                name.clone(),
+                Sprite {
+                    atlas_handle,
+                    nine_patch,
+                    padding: sprite_image.nine_patch,
+                    size: sprite_image.image.size(),
+                },
+            );
+
+            if let Some(emoji) = sprite_image.emoji {
+                sprites.by_emoji.insert(emoji, sprite_id);
+            }
+        }
+
+        Ok(sprites)
+    }
+
+    fn name_of(&self, atlas_handle: &AtlasHandle) -> Option<&str> {
+        self.by_name.iter().find_map(|(name, sprite_id)| {
+            (self[*sprite_id].atlas_handle.id() == atlas_handle.id()).then_some(name.as_str())
+        })
+    }
+}
+
+/// Sprites cut out of their source images, but not yet inserted into the
+/// atlas.
+#[derive(derive_more::Debug)]
+pub struct SpriteImages {
+    #[debug(skip)]
+    sprites: Vec<(String, SpriteImage)>,
+    sources: Vec<PathBuf>,
+}
+
+struct SpriteImage {
+    image: RgbaImage,
+    nine_patch: Option<Margin>,
+    emoji: Option<char>,
+}
+
+impl Asset for SpriteImages {
+    fn load(path: &Path) -> Result<Self, Error> {
+        let toml_directory = path.parent().unwrap();
+        let toml = std::fs::read(path)?;
         let ui_defs: ui_defs::SpriteDefs = toml::from_slice(&toml)?;
 
         let mut image_cache = HashMap::new();
-        let mut sprites = Sprites::default();
+        let mut sprites = Vec::with_capacity(ui_defs.sprites.len());
 
         for (name, sprite_def) in ui_defs.sprites {
-            let image = match image_cache.entry(sprite_def.source.clone()) {
+            let image = match image_cache.entry(toml_directory.join(&sprite_def.source)) {
                 hash_map::Entry::Occupied(occupied) => occupied.into_mut(),
                 hash_map::Entry::Vacant(vacant) => {
-                    let image = RgbaImage::from_path(toml_directory.join(&sprite_def.source))?;
+                    let image = RgbaImage::from_path(vacant.key())?;
                     vacant.insert(image)
                 }
             };
 
-            let sub_image = image
+            let image = image
                 .view(
                     sprite_def.x,
                     sprite_def.y,
@@ -109,21 +208,8 @@ impl Sprites {
                 )
                 .to_image();
 
-            let atlas_handle = atlas.insert_image(
-                &sub_image,
-                Some(PaddingMode {
-                    padding: Padding::uniform(1),
-                    fill: PaddingFill::TRANSPARENT,
-                }),
-                device,
-                staging,
-            )?;
-
-            let mut nine_patch = None;
-            let mut padding = None;
-
-            if let Some(margin) = sprite_def.nine_patch {
-                let margin = match margin {
+            let nine_patch = sprite_def.nine_patch.map(|margin| {
+                match margin {
                     MarginDef::SingleMargin { margin } => {
                         Margin {
                             left: margin,
@@ -132,24 +218,27 @@ impl Sprites {
                             bottom: margin,
                         }
                     }
-                };
-
-                nine_patch = Some(NinePatch::new(&atlas_handle, atlas, margin));
-                padding = Some(margin);
-            };
+                }
+            });
 
-            sprites.insert(
+            sprites.push((
                 name,
-                Sprite {
-                    atlas_handle,
+                SpriteImage {
+                    image,
                     nine_patch,
-                    padding,
-                    size: Vector2::new(sprite_def.width, sprite_def.height),
+                    emoji: sprite_def.emoji,
                 },
-            );
+            ));
         }
 
-        Ok(sprites)
+        Ok(Self {
+            sprites,
+            sources: image_cache.into_keys().collect(),
+        })
+    }
+
+    fn dependencies(&self) -> Vec<PathBuf> {
+        self.sources.clone()
     }
 }
 
@@ -222,6 +311,18 @@ pub struct Background {
     pub pixel_size: f32,
 }
 
+/// Makes a [`Background`] translucent, e.g. to fade it out.
+#[derive(Clone, Copy, Debug, PartialEq, Component)]
+pub struct Opacity {
+    pub opacity: f32,
+}
+
+impl Opacity {
+    fn tint(&self) -> Srgba<f32> {
+        Srgba::new(1.0, 1.0, 1.0, self.opacity.clamp(0.0, 1.0))
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct NinePatch {
     patches: [[AtlasHandle; 3]; 3],
@@ -293,6 +394,7 @@ impl NinePatch {
         size: Vector2<f32>,
         depth: u32,
         pixel_size: f32,
+        tint: Option<Srgba<f32>>,
     ) {
         fn patch_sizes(size: f32, margin_low: f32, margin_high: f32) -> [f32; 3] {
             let mut spacings = [0.0; 3];
@@ -326,7 +428,7 @@ impl NinePatch {
                         cursor,
                         Vector2::new(horizontal[x], vertical[y]),
                         depth,
-                        None,
+                        tint,
                     )
                     .set_atlas_texture(&self.patches[y][x]);
                 cursor.x += horizontal[x];
@@ -340,6 +442,7 @@ impl NinePatch {
 pub(super) fn setup_sprite_systems(builder: &mut WorldBuilder) {
     builder
         .add_systems(schedule::Startup, load_sprites.in_set(RenderSystems::Setup))
+        .add_systems(schedule::Update, reload_sprites)
         .add_systems(
             schedule::Render,
             (
@@ -349,19 +452,78 @@ pub(super) fn setup_sprite_systems(builder: &mut WorldBuilder) {
         );
 }
 
+/// Sprites are needed for the loading screen, so they're loaded synchronously,
+/// but they're still hot-reloaded.
+#[derive(Debug, Resource)]
+struct SpritesAsset {
+    handle: AssetHandle<SpriteImages>,
+    version: u64,
+}
+
 fn load_sprites(
     wgpu: Res<WgpuContext>,
+    assets: Res<AssetServer>,
     mut atlas: ResMut<DefaultAtlas>,
     mut staging: ResMut<Staging>,
     mut commands: Commands,
 ) {
     // todo: hard-coded asset path
-    let path = Path::new("assets/ui.toml");
-    let sprites = Sprites::load(path, &wgpu.device, &mut atlas.0, &mut *staging).unwrap();
+    let handle = assets.load_blocking::<SpriteImages>("assets/ui.toml");
+    let mut version = 0;
+    let images = handle
+        .get_if_changed(&mut version)
+        .unwrap()
+        .unwrap_or_else(|e| panic!("Error while loading sprites: {e}"));
+
+    let sprites = Sprites::new(&images, &wgpu.device, &mut atlas.0, &mut staging).unwrap();
     commands.insert_resource(sprites);
+    commands.insert_resource(SpritesAsset { handle, version });
 }
 
-fn request_redraw(nodes: Populated<&Root, Changed<Background>>, mut views: Populated<&mut View>) {
+/// Replaces the sprites when they're reloaded, including the sprites of
+/// [`Background`]s.
+fn reload_sprites(
+    wgpu: Res<WgpuContext>,
+    mut asset: ResMut<SpritesAsset>,
+    mut sprites: ResMut<Sprites>,
+    mut atlas: ResMut<DefaultAtlas>,
+    mut staging: ResMut<Staging>,
+    backgrounds: Query<&mut Background>,
+) {
+    let asset = &mut *asset;
+    let Some(result) = asset.handle.get_if_changed(&mut asset.version)
+    else {
+        return;
+    };
+
+    let new_sprites = match result
+        .map_err(|error| eyre!("{error}"))
+        .and_then(|images| Sprites::new(&images, &wgpu.device, &mut atlas.0, &mut staging))
+    {
+        Ok(new_sprites) => new_sprites,
+        Err(error) => {
+            tracing::error!("could not reload sprites: {error}");
+            return;
+        }
+    };
+
+    // backgrounds have a copy of their sprite, so we look it up by name in the new
+    // sprites.
+    for mut background in backgrounds {
+        if let Some(sprite) = sprites
+            .name_of(&background.sprite.atlas_handle)
+            .and_then(|name| new_sprites.lookup(name))
+        {
+            background.sprite = new_sprites[sprite].clone();
+        }
+    }
+
+    *sprites = new_sprites;
+}
+
+type BackgroundChanged = Or<(Changed<Background>, Changed<Opacity>)>;
+
+fn request_redraw(nodes: Populated<&Root, BackgroundChanged>, mut views: Populated<&mut View>) {
     for root in nodes {
         let mut view = views.get_mut(root.root).unwrap();
         view.render = true;
@@ -369,10 +531,16 @@ fn request_redraw(nodes: Populated<&Root, Changed<Background>>, mut views: Popul
 }
 
 fn render_sprites(
-    nodes: Populated<(NameOrEntity, &Background, &FinalLayout, &Root)>,
+    nodes: Populated<(
+        NameOrEntity,
+        &Background,
+        Option<&Opacity>,
+        &FinalLayout,
+        &Root,
+    )>,
     mut views: Populated<(&View, &mut RenderBufferBuilder)>,
 ) {
-    for (entity, background, final_layout, root) in nodes {
+    for (entity, background, opacity, final_layout, root) in nodes {
         let (view, mut render_buffer_builder) = views.get_mut(root.root).unwrap();
 
         if view.render {
@@ -388,6 +556,8 @@ fn render_sprites(
                 "render background"
             );
 
+            let tint = opacity.map(Opacity::tint);
+
             if let Some(nine_patch) = &background.sprite.nine_patch {
                 nine_patch.render(
                     &mut render_buffer_builder,
@@ -395,11 +565,12 @@ fn render_sprites(
                     size,
                     final_layout.depth,
                     background.pixel_size,
+                    tint,
                 );
             }
             else {
                 render_buffer_builder
-                    .push_quad(offset, size, final_layout.depth, None)
+                    .push_quad(offset, size, final_layout.depth, tint)
                     .set_atlas_texture(&background.sprite.atlas_handle);
             }
         }
@@ -427,6 +598,10 @@ mod ui_defs {
         pub width: u32,
         pub height: u32,
         pub nine_patch: Option<MarginDef>,
+
+        /// Draw this sprite for the character in text, if the font doesn't
+        /// have it.
+        pub emoji: Option<char>,
     }
 
     #[derive(Debug, Deserialize)]
@@ -5,6 +5,7 @@ use bevy_ecs::{
     entity::Entity,
     query::{
         Changed,
+        Has,
         Or,
         QueryData,
         Without,
@@ -17,7 +18,12 @@ use bevy_ecs::{
         Res,
     },
 };
-use nalgebra::Vector2;
+use itertools::Itertools;
+use nalgebra::{
+    Point2,
+    Vector2,
+};
+use palette::Srgba;
 use taffy::{
     AvailableSpace,
     Size,
@@ -33,6 +39,8 @@ use crate::{
         text::{
             Text,
             TextColor,
+            TextOutline,
+            TextShadow,
             TextSize,
         },
     },
@@ -41,12 +49,26 @@ use crate::{
         LayoutCache,
         LeafMeasure,
         Root,
+        Sprites,
         UiSystems,
         render::RenderBufferBuilder,
+        theme::{
+            ThemeColor,
+            ThemeConfig,
+        },
         view::View,
     },
+    util::stats_alloc::{
+        MemoryTag,
+        memory_scope,
+    },
 };
 
+/// Number of glyph cells that an emoji takes up, like wide characters in a
+/// terminal. Emoji are drawn from [sprites][Sprites::emoji], if the font
+/// doesn't have them.
+const EMOJI_WIDTH: usize = 2;
+
 pub(super) fn setup_text_systems(builder: &mut WorldBuilder) {
     builder.add_systems(
         schedule::Render,
@@ -121,10 +143,32 @@ impl LeafMeasure for TextLeafMeasure {
     }
 }
 
-fn request_redraw(
-    nodes: Populated<&Root, Or<(Changed<TextBuffer>, Changed<TextSize>)>>,
-    mut views: Populated<&mut View>,
-) {
+/// The components that change how a text looks, but not its layout.
+#[derive(QueryData)]
+struct TextStyle {
+    color: Option<&'static TextColor>,
+    shadow: Option<&'static TextShadow>,
+    outline: Option<&'static TextOutline>,
+    themed: Has<ThemeColor>,
+}
+
+type TextChanged = Or<(
+    Changed<TextBuffer>,
+    Changed<TextSize>,
+    Changed<TextColor>,
+    Changed<TextShadow>,
+    Changed<TextOutline>,
+)>;
+
+/// A copy of every glyph that is drawn, with an offset in font pixels and a
+/// color.
+#[derive(Clone, Copy, Debug)]
+struct GlyphLayer {
+    offset: Vector2<f32>,
+    color: Option<Srgba<f32>>,
+}
+
+fn request_redraw(nodes: Populated<&Root, TextChanged>, mut views: Populated<&mut View>) {
     for root in nodes {
         let mut view = views.get_mut(root.root).unwrap();
         view.render = true;
@@ -133,20 +177,23 @@ fn request_redraw(
 
 fn render_texts(
     font: Res<DefaultFont>,
+    sprites: Res<Sprites>,
+    theme: Res<ThemeConfig>,
     nodes: Populated<(
         Entity,
         &Text,
         &TextBuffer,
         Option<&TextSize>,
-        Option<&TextColor>,
+        TextStyle,
         &FinalLayout,
         &Root,
     )>,
     mut views: Populated<(&View, &mut RenderBufferBuilder)>,
+    mut glyph_layers: Local<Vec<GlyphLayer>>,
 ) {
     let displacement = font.glyph_displacement();
 
-    for (entity, text, text_buffer, text_size, text_color, final_layout, root) in nodes {
+    for (entity, text, text_buffer, text_size, text_style, final_layout, root) in nodes {
         let (view, mut render_buffer_builder) = views.get_mut(root.root).unwrap();
 
         if view.render {
@@ -161,7 +208,35 @@ fn render_texts(
             let displacement = displacement * text_size;
             let width_constraint = (content_size.x / displacement.x).floor().max(0.0) as usize;
 
-            let text_color = text_color.copied().map(|color| color.color);
+            // every glyph is drawn once per layer. quads with the same order are drawn in
+            // the order they're pushed, so shadow and outline go first.
+            glyph_layers.clear();
+            if let Some(text_shadow) = text_style.shadow {
+                glyph_layers.push(GlyphLayer {
+                    offset: text_shadow.offset,
+                    color: Some(text_shadow.color),
+                });
+            }
+            let text_outline = if text_style.themed {
+                theme.text_outline().or(text_style.outline.copied())
+            }
+            else {
+                text_style.outline.copied()
+            };
+            if let Some(text_outline) = text_outline {
+                for (x, y) in (-1..=1).cartesian_product(-1..=1) {
+                    if x != 0 || y != 0 {
+                        glyph_layers.push(GlyphLayer {
+                            offset: Vector2::new(x as f32, y as f32) * text_outline.width,
+                            color: Some(text_outline.color),
+                        });
+                    }
+                }
+            }
+            glyph_layers.push(GlyphLayer {
+                offset: Vector2::zeros(),
+                color: text_style.color.map(|color| color.color),
+            });
 
             tracing::trace!(?entity, text = ?text.text, ?content_offset, ?content_size, depth = ?final_layout.depth, "render text");
 
@@ -176,19 +251,52 @@ fn render_texts(
                             offset.cast::<f32>().component_mul(&displacement) + content_offset;
 
                         for character in text.text[span.clone()].chars() {
-                            if let Some(glyph_id) = font.glyph_id_or_replacement(character) {
-                                // we have these available in the shader, so we could add this there
-                                // (we used to do this).
-                                let (glyph_offset, glyph_size) = font.glyph_bbox(glyph_id);
-
+                            let emoji = font
+                                .glyph_id(character)
+                                .is_none()
+                                .then(|| sprites.emoji(character))
+                                .flatten();
+
+                            if let Some(emoji) = emoji {
+                                // fit the sprite into its cells, keeping the aspect ratio
+                                let cell_size = Vector2::new(
+                                    EMOJI_WIDTH as f32 * displacement.x,
+                                    displacement.y,
+                                );
+                                let sprite_size = emoji.size.cast::<f32>();
+                                let sprite_size = sprite_size
+                                    * (cell_size.x / sprite_size.x)
+                                        .min(cell_size.y / sprite_size.y);
+
+                                // emoji are already colored, so they don't get a shadow or
+                                // outline
                                 render_buffer_builder
                                     .push_quad(
-                                        glyph_offset.cast::<f32>() * text_size + offset,
-                                        glyph_size.cast::<f32>() * text_size,
+                                        Point2::from(offset + 0.5 * (cell_size - sprite_size)),
+                                        sprite_size,
                                         final_layout.depth,
-                                        text_color,
+                                        None,
                                     )
-                                    .set_glyph_texture(glyph_id);
+                                    .set_atlas_texture(&emoji.atlas_handle);
+
+                                offset.x += cell_size.x;
+                            }
+                            else if let Some(glyph_id) = font.glyph_id_or_replacement(character) {
+                                // we have these available in the shader, so we could add this there
+                                // (we used to do this).
+                                let (glyph_offset, glyph_size) = font.glyph_bbox(glyph_id);
+
+                                for layer in &*glyph_layers {
+                                    render_buffer_builder
+                                        .push_quad(
+                                            (glyph_offset.cast::<f32>() + layer.offset) * text_size
+                                                + offset,
+                                            glyph_size.cast::<f32>() * text_size,
+                                            final_layout.depth,
+                                            layer.color,
+                                        )
+                                        .set_glyph_texture(glyph_id);
+                                }
 
                                 offset.x += displacement.x;
                             }
@@ -212,6 +320,7 @@ fn render_texts(
 /// the text measure function runs.
 fn compute_text_layouts(
     font: Res<DefaultFont>,
+    sprites: Res<Sprites>,
     texts: Populated<
         (Entity, &Text, Option<&mut TextBuffer>, &mut LayoutCache),
         Or<(Changed<Text>, Without<TextBuffer>)>,
@@ -219,6 +328,8 @@ fn compute_text_layouts(
     mut commands: Commands,
     mut layout_run_buffer: Local<Vec<TextBufferChunk>>,
 ) {
+    let _memory_scope = memory_scope(MemoryTag::Ui);
+
     for (entity, text, computed_text_layout, mut layout_cache) in texts {
         tracing::trace!(?entity, text = text.text, "layout text");
 
@@ -252,7 +363,17 @@ fn compute_text_layouts(
                     }
                 }
                 _ => {
-                    if let Some(_glyph_id) = font.glyph_id(character) {
+                    let width = if font.glyph_id(character).is_some() {
+                        Some(1)
+                    }
+                    else if sprites.emoji(character).is_some() {
+                        Some(EMOJI_WIDTH)
+                    }
+                    else {
+                        None
+                    };
+
+                    if let Some(width) = width {
                         let end_index = characters
                             .peek()
                             .map_or_else(|| text.text.len(), |(index, _)| *index);
@@ -261,12 +382,12 @@ fn compute_text_layouts(
                             layout_run_buffer.last_mut()
                         {
                             span.end = end_index;
-                            *num_glyphs += 1;
+                            *num_glyphs += width;
                         }
                         else {
                             layout_run_buffer.push(TextBufferChunk::Glyphs {
                                 span: start_index..end_index,
-                                num_glyphs: 1,
+                                num_glyphs: width,
                             });
                         }
                     }
@@ -314,6 +435,8 @@ impl TextBuffer {
 enum TextBufferChunk {
     Glyphs {
         span: Range<usize>,
+        /// Width in glyph cells. This is more than the number of characters if
+        /// there are emoji.
         num_glyphs: usize,
     },
     Spaces {
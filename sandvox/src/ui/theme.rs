@@ -0,0 +1,236 @@
+//! UI themes.
+//!
+//! Instead of picking colors themselves, UI nodes can attach a [`ThemeColor`]
+//! with the role their color plays. The actual color then comes from the
+//! [palette][PaletteKind] selected in the [`ThemeConfig`], so players with
+//! color vision deficiencies can pick one that they can tell apart.
+
+use bevy_ecs::{
+    change_detection::DetectChanges,
+    component::Component,
+    entity::Entity,
+    resource::Resource,
+    schedule::IntoScheduleConfigs,
+    system::{
+        Commands,
+        Query,
+        Res,
+    },
+    world::Ref,
+};
+use palette::{
+    Srgb,
+    Srgba,
+    WithAlpha,
+};
+use serde::{
+    Deserialize,
+    Serialize,
+};
+
+use crate::{
+    ecs::{
+        plugin::WorldBuilder,
+        schedule,
+    },
+    render::text::{
+        TextColor,
+        TextOutline,
+    },
+    ui::UiSystems,
+};
+
+pub(super) fn setup_theme_systems(builder: &mut WorldBuilder) {
+    builder
+        .init_resource::<ThemeConfig>()
+        .add_systems(schedule::Render, apply_theme.before(UiSystems::Layout));
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Resource, Serialize, Deserialize)]
+pub struct ThemeConfig {
+    #[serde(default)]
+    pub palette: PaletteKind,
+
+    /// Draw an outline around all themed text, so it's readable on any
+    /// background. This replaces the text's own [`TextOutline`].
+    #[serde(default)]
+    pub high_contrast: bool,
+}
+
+impl ThemeConfig {
+    pub fn color(&self, role: PaletteRole) -> Srgba<f32> {
+        let palette = self.palette.palette();
+
+        let color = match role {
+            PaletteRole::Text if self.high_contrast => palette::named::WHITE,
+            PaletteRole::Text => palette.text,
+            PaletteRole::Positive => palette.positive,
+            PaletteRole::Negative => palette.negative,
+            PaletteRole::Warning => palette.warning,
+            PaletteRole::Series(index) => palette.series[index % palette.series.len()],
+        };
+
+        color.into_format().with_alpha(1.0)
+    }
+
+    /// The outline that themed text gets.
+    pub fn text_outline(&self) -> Option<TextOutline> {
+        self.high_contrast.then(|| {
+            TextOutline {
+                color: palette::named::BLACK.into_format().with_alpha(1.0),
+                width: 1.0,
+            }
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PaletteKind {
+    #[default]
+    Default,
+
+    /// For red-green color blindness with a weak green response.
+    Deuteranopia,
+
+    /// For red-green color blindness with a weak red response. Reds are
+    /// avoided, since they look dark.
+    Protanopia,
+
+    /// For blue-yellow color blindness.
+    Tritanopia,
+}
+
+impl PaletteKind {
+    pub fn palette(&self) -> &'static Palette {
+        match self {
+            Self::Default => &DEFAULT_PALETTE,
+            Self::Deuteranopia => &DEUTERANOPIA_PALETTE,
+            Self::Protanopia => &PROTANOPIA_PALETTE,
+            Self::Tritanopia => &TRITANOPIA_PALETTE,
+        }
+    }
+}
+
+/// What a color is used for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum PaletteRole {
+    Text,
+
+    /// Something good, e.g. a success message.
+    Positive,
+
+    /// Something bad, e.g. an error.
+    Negative,
+
+    Warning,
+
+    /// Colors that must be told apart from each other, e.g. the series in a
+    /// graph.
+    Series(usize),
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Palette {
+    pub text: Srgb<u8>,
+    pub positive: Srgb<u8>,
+    pub negative: Srgb<u8>,
+    pub warning: Srgb<u8>,
+    pub series: [Srgb<u8>; 6],
+}
+
+const DEFAULT_PALETTE: Palette = Palette {
+    text: palette::named::WHITESMOKE,
+    positive: palette::named::LIMEGREEN,
+    negative: palette::named::TOMATO,
+    warning: palette::named::GOLD,
+    series: [
+        palette::named::ORANGE,
+        palette::named::DEEPSKYBLUE,
+        palette::named::LIMEGREEN,
+        palette::named::ORCHID,
+        palette::named::GOLD,
+        palette::named::TOMATO,
+    ],
+};
+
+// the color vision deficiency palettes are based on the Okabe-Ito palette
+// (https://jfly.uni-koeln.de/color/), which is distinguishable with any of them.
+
+const DEUTERANOPIA_PALETTE: Palette = Palette {
+    text: palette::named::WHITESMOKE,
+    positive: Srgb::new(0x56, 0xb4, 0xe9),
+    negative: Srgb::new(0xd5, 0x5e, 0x00),
+    warning: Srgb::new(0xf0, 0xe4, 0x42),
+    series: [
+        Srgb::new(0xe6, 0x9f, 0x00),
+        Srgb::new(0x56, 0xb4, 0xe9),
+        Srgb::new(0x00, 0x9e, 0x73),
+        Srgb::new(0xcc, 0x79, 0xa7),
+        Srgb::new(0xf0, 0xe4, 0x42),
+        Srgb::new(0x00, 0x72, 0xb2),
+    ],
+};
+
+const PROTANOPIA_PALETTE: Palette = Palette {
+    text: palette::named::WHITESMOKE,
+    positive: Srgb::new(0x56, 0xb4, 0xe9),
+    negative: Srgb::new(0xe6, 0x9f, 0x00),
+    warning: Srgb::new(0xf0, 0xe4, 0x42),
+    series: [
+        Srgb::new(0xe6, 0x9f, 0x00),
+        Srgb::new(0x56, 0xb4, 0xe9),
+        Srgb::new(0xf0, 0xe4, 0x42),
+        Srgb::new(0x00, 0x72, 0xb2),
+        Srgb::new(0xcc, 0x79, 0xa7),
+        Srgb::new(0x00, 0x9e, 0x73),
+    ],
+};
+
+const TRITANOPIA_PALETTE: Palette = Palette {
+    text: palette::named::WHITESMOKE,
+    positive: Srgb::new(0x00, 0xa6, 0xa6),
+    negative: Srgb::new(0xe4, 0x1a, 0x1c),
+    warning: Srgb::new(0xff, 0x8c, 0x8c),
+    series: [
+        Srgb::new(0xe4, 0x1a, 0x1c),
+        Srgb::new(0x00, 0xa6, 0xa6),
+        Srgb::new(0xff, 0x8c, 0x8c),
+        Srgb::new(0x00, 0x5f, 0x73),
+        Srgb::new(0xa0, 0xa0, 0xa0),
+        Srgb::new(0x9e, 0x00, 0x59),
+    ],
+};
+
+/// Colors a text node with a color from the [theme][ThemeConfig].
+///
+/// This overrides the node's [`TextColor`] and, in high-contrast mode, its
+/// [`TextOutline`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Component)]
+pub struct ThemeColor {
+    pub role: PaletteRole,
+}
+
+impl From<PaletteRole> for ThemeColor {
+    fn from(value: PaletteRole) -> Self {
+        Self { role: value }
+    }
+}
+
+fn apply_theme(
+    theme: Res<ThemeConfig>,
+    nodes: Query<(Entity, Ref<ThemeColor>)>,
+    mut commands: Commands,
+) {
+    for (entity, theme_color) in nodes {
+        if !theme.is_changed() && !theme_color.is_changed() {
+            continue;
+        }
+
+        // the outline is applied when the text is rendered, but changing the color
+        // also makes sure the text is redrawn.
+        commands.entity(entity).insert(TextColor {
+            color: theme.color(theme_color.role),
+        });
+    }
+}
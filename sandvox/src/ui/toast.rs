@@ -0,0 +1,271 @@
+//! Short notifications that fade out after a while.
+//!
+//! Any system can push a [`Toast`] to the [`Toasts`] resource. They're shown as
+//! panels stacked in the bottom right corner of the [`View`], with the newest
+//! at the bottom.
+
+use std::time::{
+    Duration,
+    Instant,
+};
+
+use bevy_ecs::{
+    component::Component,
+    entity::Entity,
+    hierarchy::{
+        ChildOf,
+        Children,
+    },
+    name::Name,
+    query::With,
+    resource::Resource,
+    schedule::IntoScheduleConfigs,
+    system::{
+        Commands,
+        Populated,
+        Query,
+        Res,
+        ResMut,
+        Single,
+    },
+};
+use taffy::prelude::{
+    TaffyAuto,
+    TaffyZero,
+};
+
+use crate::{
+    app::Time,
+    ecs::{
+        plugin::WorldBuilder,
+        schedule,
+    },
+    render::text::{
+        Text,
+        TextColor,
+        TextSize,
+    },
+    ui::{
+        Background,
+        Opacity,
+        PaletteRole,
+        Sprites,
+        Style,
+        ThemeColor,
+        ThemeConfig,
+        UiSystems,
+        View,
+    },
+};
+
+const PIXEL_SIZE: f32 = 2.0;
+
+/// Toasts fade out during the end of their timeout.
+const FADE_DURATION: Duration = Duration::from_millis(500);
+
+/// Older toasts are removed when there are more than this.
+const MAX_TOASTS: usize = 5;
+
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+
+pub(super) fn setup_toast_systems(builder: &mut WorldBuilder) {
+    builder.init_resource::<Toasts>().add_systems(
+        schedule::Render,
+        (show_toasts.run_if(has_pending_toasts), fade_toasts)
+            .chain()
+            .before(UiSystems::Layout),
+    );
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Severity {
+    #[default]
+    Info,
+    Success,
+    Warning,
+    Error,
+}
+
+impl Severity {
+    fn role(&self) -> PaletteRole {
+        match self {
+            Self::Info => PaletteRole::Text,
+            Self::Success => PaletteRole::Positive,
+            Self::Warning => PaletteRole::Warning,
+            Self::Error => PaletteRole::Negative,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Toast {
+    pub message: String,
+    pub severity: Severity,
+
+    /// How long the toast is shown, including fading out.
+    pub timeout: Duration,
+}
+
+impl Toast {
+    pub fn new(severity: Severity, message: impl Into<String>) -> Self {
+        Self {
+            message: message.into(),
+            severity,
+            timeout: DEFAULT_TIMEOUT,
+        }
+    }
+
+    pub fn info(message: impl Into<String>) -> Self {
+        Self::new(Severity::Info, message)
+    }
+
+    pub fn success(message: impl Into<String>) -> Self {
+        Self::new(Severity::Success, message)
+    }
+
+    pub fn warning(message: impl Into<String>) -> Self {
+        Self::new(Severity::Warning, message)
+    }
+
+    pub fn error(message: impl Into<String>) -> Self {
+        Self::new(Severity::Error, message)
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+}
+
+/// Toasts that were pushed, but aren't shown yet.
+#[derive(Debug, Default, Resource)]
+pub struct Toasts {
+    pending: Vec<Toast>,
+}
+
+impl Toasts {
+    pub fn push(&mut self, toast: Toast) {
+        tracing::debug!(message = toast.message, severity = ?toast.severity, "toast");
+        self.pending.push(toast);
+    }
+}
+
+/// Node that the toast panels are stacked in.
+#[derive(Clone, Copy, Debug, Component)]
+struct ToastStack;
+
+#[derive(Clone, Copy, Debug, Component)]
+struct ToastPanel {
+    severity: Severity,
+    expires: Instant,
+}
+
+fn has_pending_toasts(toasts: Res<Toasts>) -> bool {
+    !toasts.pending.is_empty()
+}
+
+fn show_toasts(
+    mut toasts: ResMut<Toasts>,
+    time: Res<Time>,
+    sprites: Res<Sprites>,
+    view: Single<Entity, With<View>>,
+    stack: Option<Single<Entity, With<ToastStack>>>,
+    children: Query<&Children>,
+    mut commands: Commands,
+) {
+    let pending = std::mem::take(&mut toasts.pending);
+    let pending = &pending[pending.len().saturating_sub(MAX_TOASTS)..];
+
+    let stack = if let Some(stack) = stack {
+        let stack = *stack;
+
+        // make room for the new toasts
+        let panels = children.get(stack).map_or(&[][..], |panels| &**panels);
+        let num_removed = (panels.len() + pending.len()).saturating_sub(MAX_TOASTS);
+        for panel in &panels[..num_removed] {
+            commands.entity(*panel).despawn();
+        }
+
+        stack
+    }
+    else {
+        let mut style = Style::default();
+        style.display = taffy::style::Display::Flex;
+        style.flex_direction = taffy::style::FlexDirection::Column;
+        style.align_items = Some(taffy::style::AlignItems::End);
+        style.position = taffy::Position::Absolute;
+        style.inset = taffy::Rect {
+            left: taffy::LengthPercentageAuto::AUTO,
+            right: taffy::LengthPercentageAuto::ZERO,
+            top: taffy::LengthPercentageAuto::AUTO,
+            bottom: taffy::LengthPercentageAuto::ZERO,
+        };
+
+        commands
+            .spawn((Name::new("toasts"), style, ToastStack, ChildOf(*view)))
+            .id()
+    };
+
+    let sprite = &sprites["panel"];
+
+    for toast in pending {
+        let mut style = Style::default();
+        if let Some(padding) = sprite.padding(PIXEL_SIZE) {
+            style.padding = padding;
+        }
+
+        commands
+            .spawn((
+                Name::new("toast"),
+                style,
+                Background {
+                    sprite: sprite.clone(),
+                    pixel_size: PIXEL_SIZE,
+                },
+                Opacity { opacity: 1.0 },
+                ToastPanel {
+                    severity: toast.severity,
+                    expires: time.tick_start + toast.timeout,
+                },
+                ChildOf(stack),
+            ))
+            .with_child((
+                Name::new("toast_message"),
+                Text::from(toast.message.clone()),
+                TextSize {
+                    scaling: PIXEL_SIZE,
+                },
+                ThemeColor::from(toast.severity.role()),
+                Style::default(),
+            ));
+    }
+}
+
+fn fade_toasts(
+    time: Res<Time>,
+    theme: Res<ThemeConfig>,
+    panels: Populated<(Entity, &ToastPanel, &mut Opacity, &Children)>,
+    mut texts: Query<&mut TextColor>,
+    mut commands: Commands,
+) {
+    for (entity, panel, mut opacity, children) in panels {
+        let remaining = panel.expires.saturating_duration_since(time.tick_start);
+        if remaining.is_zero() {
+            commands.entity(entity).despawn();
+            continue;
+        }
+
+        let alpha = remaining.as_secs_f32() / FADE_DURATION.as_secs_f32();
+        if alpha < 1.0 {
+            opacity.opacity = alpha;
+
+            let mut color = theme.color(panel.severity.role());
+            color.alpha = alpha;
+            for child in children {
+                if let Ok(mut text_color) = texts.get_mut(*child) {
+                    text_color.color = color;
+                }
+            }
+        }
+    }
+}
@@ -1,9 +1,13 @@
 use bevy_ecs::{
+    change_detection::DetectChanges,
     component::Component,
-    query::Changed,
     relationship::RelationshipTarget,
     schedule::IntoScheduleConfigs,
-    system::Populated,
+    system::{
+        Populated,
+        Query,
+    },
+    world::Ref,
 };
 use nalgebra::Vector2;
 
@@ -15,7 +19,11 @@ use crate::{
     },
     render::{
         pass::ui_pass::UiPassUniform,
-        render_target::RenderSources,
+        render_target::{
+            RenderSources,
+            Viewport,
+            pixel_viewport,
+        },
     },
     ui::UiSystems,
 };
@@ -33,16 +41,23 @@ pub(super) fn setup_view_systems(builder: &mut WorldBuilder) {
     );
 }
 
+/// Sizes the views to their window, or to their [`Viewport`] in the window.
 #[profiling::function]
 fn update_views_from_windows(
-    windows: Populated<(&WindowSize, &RenderSources), Changed<WindowSize>>,
-    mut views: Populated<(&mut View, &mut UiPassUniform)>,
+    windows: Populated<(Ref<WindowSize>, &RenderSources)>,
+    mut views: Query<(&mut View, &mut UiPassUniform, Option<Ref<Viewport>>)>,
 ) {
     for (window_size, render_sources) in windows {
         for entity in render_sources.iter() {
-            if let Ok((mut viewport, mut ui_pass_uniform)) = views.get_mut(entity) {
-                viewport.size = window_size.size;
-                ui_pass_uniform.data.viewport_size = window_size.size;
+            if let Ok((mut view, mut ui_pass_uniform, viewport)) = views.get_mut(entity)
+                && (window_size.is_changed()
+                    || viewport
+                        .as_ref()
+                        .is_some_and(|viewport| viewport.is_changed()))
+            {
+                let size = pixel_viewport(viewport.as_deref(), window_size.size).size;
+                view.size = size;
+                ui_pass_uniform.data.viewport_size = size;
             }
         }
     }
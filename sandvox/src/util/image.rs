@@ -29,3 +29,15 @@ impl ImageLoadExt for image::RgbaImage {
         Ok(image.to_rgba8())
     }
 }
+
+/// High-dynamic-range images, e.g. Radiance HDR or OpenEXR files, are decoded
+/// without clamping. Their colors are linear.
+impl ImageLoadExt for image::Rgba32FImage {
+    fn from_path<P: AsRef<Path>>(path: P) -> Result<Self, image::ImageError> {
+        let image = image::ImageReader::open(path)?.decode()?;
+        Ok(image.to_rgba32f())
+    }
+}
+
+/// File extensions of the supported high-dynamic-range image formats.
+pub const HDR_EXTENSIONS: [&str; 2] = ["hdr", "exr"];
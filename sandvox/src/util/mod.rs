@@ -8,12 +8,16 @@ pub mod stats_alloc;
 #[cfg(feature = "tokio")]
 pub mod tokio;
 
-use std::ops::{
-    Add,
-    Bound,
-    Mul,
-    Range,
-    RangeBounds,
+use std::{
+    ops::{
+        Add,
+        Bound,
+        Mul,
+        Range,
+        RangeBounds,
+    },
+    path::Path,
+    time::SystemTime,
 };
 
 pub fn normalize_index_bounds(range: impl RangeBounds<usize>, len: usize) -> Range<usize> {
@@ -41,6 +45,13 @@ where
     x0 * (1.0 - t) + x1 * t
 }
 
+/// When the file at `path` was last modified, or `None` if it doesn't exist.
+pub fn modified_time(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
 pub fn format_size<T>(value: T) -> humansize::SizeFormatter<T, humansize::FormatSizeOptions>
 where
     T: humansize::ToF64 + humansize::Unsigned,
@@ -48,6 +59,16 @@ where
     humansize::SizeFormatter::new(value, humansize::BINARY)
 }
 
+/// Nearest-rank percentile of sorted values.
+pub fn percentile(sorted: &[f64], p: f64) -> f64 {
+    if sorted.is_empty() {
+        return 0.0;
+    }
+
+    let rank = (p * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
 #[macro_export]
 macro_rules! define_atomic_id {
     ($name:ident) => {
@@ -1,6 +1,9 @@
 use std::ops::Mul;
 
-use nalgebra::Point;
+use nalgebra::{
+    Point,
+    SVector,
+};
 use rand::{
     Rng,
     distr::{
@@ -11,6 +14,22 @@ use rand::{
 
 pub trait NoiseFn<Point> {
     fn evaluate_at(&self, point: Point) -> f32;
+
+    /// Evaluates the noise at all `points`, e.g. for all columns of a chunk.
+    ///
+    /// The combinators override this to apply themselves to the whole batch,
+    /// instead of going through all of them for every point. The results are
+    /// the same as with [`evaluate_at`](Self::evaluate_at).
+    fn evaluate_batch(&self, points: &[Point], output: &mut [f32])
+    where
+        Point: Copy,
+    {
+        assert_eq!(points.len(), output.len());
+
+        for (point, output) in points.iter().zip(output) {
+            *output = self.evaluate_at(*point);
+        }
+    }
 }
 
 impl<Point> NoiseFn<Point> for f32 {
@@ -18,6 +37,11 @@ impl<Point> NoiseFn<Point> for f32 {
         let _ = point;
         *self
     }
+
+    fn evaluate_batch(&self, points: &[Point], output: &mut [f32]) {
+        assert_eq!(points.len(), output.len());
+        output.fill(*self);
+    }
 }
 
 pub trait NoiseFnExt {
@@ -32,6 +56,12 @@ pub trait NoiseFnExt {
     fn with_frequency<Frequency>(self, frequency: Frequency) -> WithFrequency<Self, Frequency>
     where
         Self: Sized;
+
+    /// Offsets the points by `warp` (evaluated with a different offset on
+    /// each axis), scaled by `strength`.
+    fn with_domain_warp<Warp>(self, warp: Warp, strength: f32) -> DomainWarp<Self, Warp>
+    where
+        Self: Sized;
 }
 
 impl<T> NoiseFnExt for T {
@@ -61,46 +91,132 @@ impl<T> NoiseFnExt for T {
             inner: self,
         }
     }
-}
 
-macro_rules! impl_wrapper {
-    ($name:ident => $inner:ident) => {
-        #[derive(Clone, Copy, Debug)]
-        pub struct $name {
-            inner: ::noise::$inner,
+    fn with_domain_warp<Warp>(self, warp: Warp, strength: f32) -> DomainWarp<Self, Warp>
+    where
+        Self: Sized,
+    {
+        DomainWarp {
+            warp,
+            strength,
+            inner: self,
         }
+    }
+}
 
-        impl NoiseFn<f32> for $name {
-            fn evaluate_at(&self, point: f32) -> f32 {
-                ::noise::NoiseFn::get(&self.inner, [point as f64]) as f32
-            }
-        }
+#[derive(Clone, Copy, Debug)]
+pub struct PerlinNoise {
+    inner: ::noise::Perlin,
 
-        impl_wrapper!(@impl_for($name, 1));
-        impl_wrapper!(@impl_for($name, 2));
-        impl_wrapper!(@impl_for($name, 3));
-        impl_wrapper!(@impl_for($name, 4));
+    /// The permutation table of `inner`, for [`evaluate_batch`] in 2D.
+    ///
+    /// [`evaluate_batch`]: NoiseFn::evaluate_batch
+    permutation: [u8; 256],
+}
 
+impl PerlinNoise {
+    pub fn new(seed: u32) -> Self {
+        // hashing a single value just looks it up in the table
+        let table = ::noise::permutationtable::PermutationTable::new(seed);
+        let permutation = std::array::from_fn(|i| {
+            ::noise::permutationtable::NoiseHasher::hash(&table, &[i as isize]) as u8
+        });
 
-        impl Distribution<$name> for StandardUniform {
-            fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> $name {
-                $name {
-                    inner: ::noise::$inner::new(rng.random()),
-                }
-            }
+        Self {
+            inner: ::noise::Perlin::new(seed),
+            permutation,
         }
+    }
+}
 
-    };
-    (@impl_for($name:ident, $n:expr)) => {
-        impl NoiseFn<Point<f32, $n>> for $name {
+impl NoiseFn<f32> for PerlinNoise {
+    fn evaluate_at(&self, point: f32) -> f32 {
+        ::noise::NoiseFn::get(&self.inner, [point as f64]) as f32
+    }
+}
+
+macro_rules! impl_perlin_for {
+    ($n:expr) => {
+        impl NoiseFn<Point<f32, $n>> for PerlinNoise {
             fn evaluate_at(&self, point: Point<f32, $n>) -> f32 {
                 ::noise::NoiseFn::get(&self.inner, point.cast::<f64>().into()) as f32
             }
         }
+    };
+}
+
+impl_perlin_for!(1);
+impl_perlin_for!(3);
+impl_perlin_for!(4);
+
+impl NoiseFn<Point<f32, 2>> for PerlinNoise {
+    fn evaluate_at(&self, point: Point<f32, 2>) -> f32 {
+        ::noise::NoiseFn::get(&self.inner, point.cast::<f64>().into()) as f32
+    }
+
+    /// Same as `noise`'s `perlin_2d`, operation for operation, so that the
+    /// results are identical, but without going through its generic vector
+    /// types and slice hashing for every point.
+    ///
+    /// This is about as fast as `noise`'s. It isn't vectorized, since it would
+    /// have to use `f64` lanes to give the same results, which isn't faster
+    /// without AVX2.
+    fn evaluate_batch(&self, points: &[Point<f32, 2>], output: &mut [f32]) {
+        const SCALE_FACTOR: f64 = 2.0 / std::f64::consts::SQRT_2;
+
+        assert_eq!(points.len(), output.len());
+
+        let hash = |x: isize, y: isize| {
+            self.permutation
+                [usize::from(self.permutation[(x & 0xff) as usize]) ^ (y & 0xff) as usize]
+        };
+
+        let gradient = |hash: u8, x: f64, y: f64| {
+            match hash & 0b11 {
+                0 => x + y,
+                1 => -x + y,
+                2 => x - y,
+                _ => -x - y,
+            }
+        };
+
+        let quintic = |x: f64| {
+            let x = x.clamp(0.0, 1.0);
+            x * x * x * (x * (x * 6.0 - 15.0) + 10.0)
+        };
+
+        let linear = |a: f64, b: f64, alpha: f64| b * alpha + a * (1.0 - alpha);
+
+        for (point, output) in points.iter().zip(output) {
+            let [x, y] = [f64::from(point.x), f64::from(point.y)];
+
+            // `noise` rounds down like this, so 0 is in the cell at -1
+            let floor = |x: f64| if x <= 0.0 { x as isize - 1 } else { x as isize };
+            let (corner_x, corner_y) = (floor(x), floor(y));
+            let (dx, dy) = (x - corner_x as f64, y - corner_y as f64);
+
+            let g00 = gradient(hash(corner_x, corner_y), dx, dy);
+            let g10 = gradient(hash(corner_x + 1, corner_y), dx - 1.0, dy);
+            let g01 = gradient(hash(corner_x, corner_y + 1), dx, dy - 1.0);
+            let g11 = gradient(hash(corner_x + 1, corner_y + 1), dx - 1.0, dy - 1.0);
+
+            let (curve_x, curve_y) = (quintic(dx), quintic(dy));
+            let result = linear(
+                linear(g00, g01, curve_y),
+                linear(g10, g11, curve_y),
+                curve_x,
+            ) * SCALE_FACTOR;
+
+            *output = result.clamp(-1.0, 1.0) as f32;
+        }
     }
 }
 
-impl_wrapper!(PerlinNoise => Perlin);
+impl Distribution<PerlinNoise> for StandardUniform {
+    fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> PerlinNoise {
+        PerlinNoise::new(rng.random())
+    }
+}
 
 #[derive(Clone, Copy, Debug)]
 pub struct WithAmplitude<Inner> {
@@ -121,6 +237,17 @@ where
     fn evaluate_at(&self, point: Point) -> f32 {
         self.amplitude * self.inner.evaluate_at(point)
     }
+
+    fn evaluate_batch(&self, points: &[Point], output: &mut [f32])
+    where
+        Point: Copy,
+    {
+        self.inner.evaluate_batch(points, output);
+
+        for output in output {
+            *output *= self.amplitude;
+        }
+    }
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -142,6 +269,17 @@ where
     fn evaluate_at(&self, point: Point) -> f32 {
         self.bias + self.inner.evaluate_at(point)
     }
+
+    fn evaluate_batch(&self, points: &[Point], output: &mut [f32])
+    where
+        Point: Copy,
+    {
+        self.inner.evaluate_batch(points, output);
+
+        for output in output {
+            *output += self.bias;
+        }
+    }
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -158,6 +296,18 @@ where
     fn evaluate_at(&self, point: Point) -> f32 {
         self.inner.evaluate_at(self.frequency * point)
     }
+
+    fn evaluate_batch(&self, points: &[Point], output: &mut [f32])
+    where
+        Point: Copy,
+    {
+        let points = points
+            .iter()
+            .map(|point| self.frequency * *point)
+            .collect::<Vec<_>>();
+
+        self.inner.evaluate_batch(&points, output);
+    }
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -172,6 +322,16 @@ where
     fn evaluate_at(&self, point: Point) -> f32 {
         self.0.evaluate_at(point) + self.1.evaluate_at(point)
     }
+
+    fn evaluate_batch(&self, points: &[Point], output: &mut [f32]) {
+        let mut other = vec![0.0; output.len()];
+        self.0.evaluate_batch(points, output);
+        self.1.evaluate_batch(points, &mut other);
+
+        for (output, other) in output.iter_mut().zip(other) {
+            *output += other;
+        }
+    }
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -186,6 +346,16 @@ where
     fn evaluate_at(&self, point: Point) -> f32 {
         self.0.evaluate_at(point) * self.1.evaluate_at(point)
     }
+
+    fn evaluate_batch(&self, points: &[Point], output: &mut [f32]) {
+        let mut other = vec![0.0; output.len()];
+        self.0.evaluate_batch(points, output);
+        self.1.evaluate_batch(points, &mut other);
+
+        for (output, other) in output.iter_mut().zip(other) {
+            *output *= other;
+        }
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -231,6 +401,300 @@ where
             .map(|inner| inner.evaluate_at(point))
             .sum()
     }
+
+    fn evaluate_batch(&self, points: &[Point], output: &mut [f32]) {
+        // same initial value as `f32::sum`
+        output.fill(-0.0);
+        let mut octave_output = vec![0.0; output.len()];
+
+        for octave in &self.octaves {
+            octave.evaluate_batch(points, &mut octave_output);
+
+            for (output, octave_output) in output.iter_mut().zip(&octave_output) {
+                *output += octave_output;
+            }
+        }
+    }
 }
 
 pub type Octave<T> = WithAmplitude<WithFrequency<T, f32>>;
+
+/// Ridged multifractal noise (Musgrave).
+///
+/// Each octave is folded at 0 (`offset - |noise|`) and squared, which gives
+/// sharp ridges. Octaves are weighted by the previous octave, so there's more
+/// detail on the ridges than in the valleys. Values are in `[0, sum of
+/// amplitudes]` with the default offset.
+#[derive(Clone, Debug)]
+pub struct RidgedNoise<Inner> {
+    pub octaves: Box<[Octave<Inner>]>,
+    pub offset: f32,
+    pub gain: f32,
+}
+
+impl<Inner> RidgedNoise<Inner> {
+    pub fn new(
+        inner: impl FnMut() -> Inner,
+        octaves: usize,
+        base_frequency: f32,
+        lacunarity: f32,
+        persistence: f32,
+    ) -> Self {
+        let FractalNoise { octaves } =
+            FractalNoise::new(inner, octaves, base_frequency, lacunarity, persistence);
+
+        Self {
+            octaves,
+            offset: 1.0,
+            gain: 2.0,
+        }
+    }
+}
+
+impl<Inner, Point> NoiseFn<Point> for RidgedNoise<Inner>
+where
+    Point: Copy,
+    Inner: NoiseFn<Point>,
+    f32: Mul<Point, Output = Point>,
+{
+    fn evaluate_at(&self, point: Point) -> f32 {
+        ridged_sum(
+            self.octaves
+                .iter()
+                .map(|octave| (octave.amplitude, octave.inner.evaluate_at(point))),
+            self.offset,
+            self.gain,
+        )
+    }
+
+    fn evaluate_batch(&self, points: &[Point], output: &mut [f32]) {
+        let mut values = vec![vec![0.0; output.len()]; self.octaves.len()];
+        for (octave, values) in self.octaves.iter().zip(&mut values) {
+            octave.inner.evaluate_batch(points, values);
+        }
+
+        for (i, output) in output.iter_mut().enumerate() {
+            *output = ridged_sum(
+                self.octaves
+                    .iter()
+                    .zip(&values)
+                    .map(|(octave, values)| (octave.amplitude, values[i])),
+                self.offset,
+                self.gain,
+            );
+        }
+    }
+}
+
+/// Sums the `(amplitude, value)` of each octave.
+fn ridged_sum(octaves: impl Iterator<Item = (f32, f32)>, offset: f32, gain: f32) -> f32 {
+    let mut weight = 1.0;
+    let mut sum = 0.0;
+
+    for (amplitude, value) in octaves {
+        let signal = (offset - value.abs()).powi(2) * weight;
+
+        weight = (signal * gain).clamp(0.0, 1.0);
+        sum += amplitude * signal;
+    }
+
+    sum
+}
+
+/// Cellular noise: Space is divided into unit cells with a random feature point
+/// each.
+#[derive(Clone, Copy, Debug)]
+pub struct WorleyNoise {
+    pub seed: u32,
+    pub output: WorleyOutput,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum WorleyOutput {
+    /// Distance to the nearest feature point.
+    #[default]
+    Distance,
+
+    /// Difference between the distances to the 2 nearest feature points, which
+    /// is 0 on the borders between cells.
+    Edge,
+
+    /// A random value in `[-1, 1]` for the cell of the nearest feature point,
+    /// e.g. to pick biomes.
+    Value,
+}
+
+impl WorleyNoise {
+    pub fn new(seed: u32, output: WorleyOutput) -> Self {
+        Self { seed, output }
+    }
+
+    pub fn with_output(self, output: WorleyOutput) -> Self {
+        Self { output, ..self }
+    }
+
+    fn hash_cell<const D: usize>(&self, cell: &[i32; D]) -> u32 {
+        cell.iter()
+            .fold(self.seed, |hash, coordinate| mix(hash ^ *coordinate as u32))
+    }
+}
+
+impl<const D: usize> NoiseFn<Point<f32, D>> for WorleyNoise {
+    fn evaluate_at(&self, point: Point<f32, D>) -> f32 {
+        let base_cell = point.coords.map(f32::floor);
+
+        let mut nearest = (f32::INFINITY, 0);
+        let mut second_nearest = f32::INFINITY;
+
+        // the feature point can be anywhere in a cell, so the nearest one can be in
+        // any neighboring cell.
+        for mut offsets in 0..3usize.pow(D as u32) {
+            let cell = SVector::<f32, D>::from_fn(|_, _| {
+                let offset = (offsets % 3) as f32 - 1.0;
+                offsets /= 3;
+                offset
+            }) + base_cell;
+
+            let hash = self.hash_cell(&cell.map(|x| x as i32).into());
+            let feature_point = cell
+                + SVector::<f32, D>::from_fn(|i, _| {
+                    to_unit_interval(mix(hash.wrapping_add(i as u32)))
+                });
+
+            let distance = (feature_point - point.coords).norm();
+            if distance < nearest.0 {
+                second_nearest = nearest.0;
+                nearest = (distance, hash);
+            }
+            else if distance < second_nearest {
+                second_nearest = distance;
+            }
+        }
+
+        match self.output {
+            WorleyOutput::Distance => nearest.0,
+            WorleyOutput::Edge => second_nearest - nearest.0,
+            WorleyOutput::Value => 2.0 * to_unit_interval(mix(!nearest.1)) - 1.0,
+        }
+    }
+}
+
+impl Distribution<WorleyNoise> for StandardUniform {
+    fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> WorleyNoise {
+        WorleyNoise::new(rng.random(), Default::default())
+    }
+}
+
+/// Integer hash (from the murmur3 finalizer).
+fn mix(mut x: u32) -> u32 {
+    x ^= x >> 16;
+    x = x.wrapping_mul(0x85ebca6b);
+    x ^= x >> 13;
+    x = x.wrapping_mul(0xc2b2ae35);
+    x ^= x >> 16;
+    x
+}
+
+fn to_unit_interval(x: u32) -> f32 {
+    (x >> 8) as f32 / (1 << 24) as f32
+}
+
+/// Evaluates the inner noise at points that are offset by another noise.
+///
+/// This distorts the inner noise, e.g. to make terrain look eroded.
+#[derive(Clone, Copy, Debug)]
+pub struct DomainWarp<Inner, Warp> {
+    pub warp: Warp,
+    pub strength: f32,
+    pub inner: Inner,
+}
+
+impl<Inner, Warp, const D: usize> NoiseFn<Point<f32, D>> for DomainWarp<Inner, Warp>
+where
+    Inner: NoiseFn<Point<f32, D>>,
+    Warp: NoiseFn<Point<f32, D>>,
+{
+    fn evaluate_at(&self, point: Point<f32, D>) -> f32 {
+        // the warp is sampled an arbitrary distance apart for each axis, so that
+        // they're warped independently
+        let offset = SVector::<f32, D>::from_fn(|i, _| {
+            self.warp
+                .evaluate_at(point + SVector::repeat(17.3 * i as f32))
+        });
+
+        self.inner.evaluate_at(point + self.strength * offset)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::{
+        Point2,
+        Point3,
+    };
+    use rand::{
+        Rng,
+        SeedableRng,
+    };
+    use rand_xoshiro::Xoroshiro128PlusPlus;
+
+    use crate::util::noise::{
+        FractalNoise,
+        NoiseFn,
+        NoiseFnExt,
+        PerlinNoise,
+        RidgedNoise,
+        WorleyNoise,
+        WorleyOutput,
+    };
+
+    #[test]
+    fn batches_give_the_same_results() {
+        let mut rng = Xoroshiro128PlusPlus::seed_from_u64(1);
+
+        let points = (0..4096)
+            .map(|i| Point2::new((i % 64) as f32 - 32.0, (i / 64) as f32 * 0.7 - 20.0))
+            .chain([Point2::new(0.0, 0.0), Point2::new(-1.0, 1.0)])
+            .collect::<Vec<_>>();
+        let mut output = vec![0.0; points.len()];
+
+        let mut check = |noise: &dyn NoiseFn<Point2<f32>>| {
+            noise.evaluate_batch(&points, &mut output);
+            for (point, output) in points.iter().zip(&output) {
+                assert_eq!(noise.evaluate_at(*point).to_bits(), output.to_bits());
+            }
+        };
+
+        check(&rng.random::<PerlinNoise>().with_frequency(0.37));
+        check(
+            &FractalNoise::<PerlinNoise>::new(|| rng.random(), 4, 1.0 / 128.0, 2.0, 0.5)
+                .with_amplitude(32.0)
+                .with_bias(2.0),
+        );
+        check(&RidgedNoise::<PerlinNoise>::new(
+            || rng.random(),
+            3,
+            1.0 / 16.0,
+            2.0,
+            0.5,
+        ));
+    }
+
+    #[test]
+    fn worley_noise_finds_the_nearest_feature_point() {
+        let noise = WorleyNoise::new(1234, WorleyOutput::Distance);
+
+        for i in 0..1000 {
+            let point = Point3::new(i as f32 * 0.37, i as f32 * -0.11, i as f32 * 0.05);
+
+            // the feature point of the point's own cell is at most a diagonal away
+            let distance = noise.evaluate_at(point);
+            assert!((0.0..=3f32.sqrt()).contains(&distance));
+
+            assert!(noise.with_output(WorleyOutput::Edge).evaluate_at(point) >= 0.0);
+            assert!(
+                (-1.0..=1.0).contains(&noise.with_output(WorleyOutput::Value).evaluate_at(point))
+            );
+        }
+    }
+}
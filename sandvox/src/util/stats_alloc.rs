@@ -1,3 +1,12 @@
+//! Global allocator that counts the allocated bytes.
+//!
+//! With the `memory-tags` feature, allocations can be tagged with a
+//! [`MemoryTag`] by entering a [`memory_scope`]. The tag is stored in front of
+//! the allocation, so the bytes are attributed to the tag until the allocation
+//! is freed, no matter where it is freed. This costs one alignment unit per
+//! allocation, so it's off by default and everything counts as
+//! [`MemoryTag::Untagged`].
+
 use std::{
     alloc::{
         AllocError,
@@ -6,7 +15,9 @@ use std::{
         Layout,
         System,
     },
+    cell::Cell,
     cmp,
+    marker::PhantomData,
     ptr::NonNull,
     sync::atomic::{
         self,
@@ -17,14 +28,100 @@ use std::{
 #[global_allocator]
 static GLOBAL: StatsAllocator<System> = StatsAllocator::new(System);
 
+thread_local! {
+    static CURRENT_TAG: Cell<MemoryTag> = const { Cell::new(MemoryTag::Untagged) };
+}
+
+/// Whether allocations are attributed to their [`MemoryTag`].
+pub const MEMORY_TAGS_ENABLED: bool = cfg!(feature = "memory-tags");
+
 pub fn bytes_allocated() -> usize {
     GLOBAL.bytes_allocated()
 }
 
+/// Bytes currently allocated with `tag`.
+///
+/// Without [`MEMORY_TAGS_ENABLED`] all bytes are [`MemoryTag::Untagged`].
+pub fn bytes_allocated_with_tag(tag: MemoryTag) -> usize {
+    GLOBAL.bytes_allocated_with_tag(tag)
+}
+
+/// Subsystem that owns an allocation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[repr(u8)]
+pub enum MemoryTag {
+    Untagged,
+    Chunks,
+    Meshing,
+    Ui,
+    Audio,
+}
+
+impl MemoryTag {
+    pub const COUNT: usize = 5;
+
+    pub const ALL: [Self; Self::COUNT] = [
+        Self::Untagged,
+        Self::Chunks,
+        Self::Meshing,
+        Self::Ui,
+        Self::Audio,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Untagged => "untagged",
+            Self::Chunks => "chunks",
+            Self::Meshing => "meshing",
+            Self::Ui => "ui",
+            Self::Audio => "audio",
+        }
+    }
+
+    #[inline]
+    fn from_u8(value: u8) -> Self {
+        Self::ALL
+            .get(usize::from(value))
+            .copied()
+            .unwrap_or(Self::Untagged)
+    }
+}
+
+/// Tags all allocations on this thread with `tag` until the returned guard is
+/// dropped.
+pub fn memory_scope(tag: MemoryTag) -> MemoryScope {
+    MemoryScope {
+        previous: CURRENT_TAG.replace(tag),
+        _not_send: PhantomData,
+    }
+}
+
+/// Runs `f` with all its allocations tagged with `tag`.
+pub fn with_memory_tag<R>(tag: MemoryTag, f: impl FnOnce() -> R) -> R {
+    let _scope = memory_scope(tag);
+    f()
+}
+
+/// Restores the previous [`MemoryTag`] when dropped.
+#[must_use]
+#[derive(Debug)]
+pub struct MemoryScope {
+    previous: MemoryTag,
+    // the scope belongs to the thread it was entered on
+    _not_send: PhantomData<*const ()>,
+}
+
+impl Drop for MemoryScope {
+    fn drop(&mut self) {
+        CURRENT_TAG.set(self.previous);
+    }
+}
+
 #[derive(Debug)]
 struct StatsAllocator<A> {
     inner: A,
     bytes_allocated: AtomicUsize,
+    bytes_allocated_by_tag: [AtomicUsize; MemoryTag::COUNT],
 }
 
 impl<A> StatsAllocator<A> {
@@ -33,6 +130,7 @@ impl<A> StatsAllocator<A> {
         Self {
             inner,
             bytes_allocated: AtomicUsize::new(0),
+            bytes_allocated_by_tag: [const { AtomicUsize::new(0) }; MemoryTag::COUNT],
         }
     }
 
@@ -40,38 +138,112 @@ impl<A> StatsAllocator<A> {
     pub fn bytes_allocated(&self) -> usize {
         self.bytes_allocated.load(atomic::Ordering::Relaxed)
     }
+
+    #[inline]
+    pub fn bytes_allocated_with_tag(&self, tag: MemoryTag) -> usize {
+        self.bytes_allocated_by_tag[tag as usize].load(atomic::Ordering::Relaxed)
+    }
 }
 
 impl<A> StatsAllocator<A> {
     #[inline]
-    fn increment_bytes_allocated(&self, size: usize) {
+    fn increment_bytes_allocated(&self, tag: MemoryTag, size: usize) {
         self.bytes_allocated
             .fetch_add(size, atomic::Ordering::Relaxed);
+        self.bytes_allocated_by_tag[tag as usize].fetch_add(size, atomic::Ordering::Relaxed);
     }
 
     #[inline]
-    fn decrement_bytes_allocated(&self, size: usize) {
+    fn decrement_bytes_allocated(&self, tag: MemoryTag, size: usize) {
         self.bytes_allocated
             .fetch_sub(size, atomic::Ordering::Relaxed);
+        self.bytes_allocated_by_tag[tag as usize].fetch_sub(size, atomic::Ordering::Relaxed);
+    }
+}
+
+/// The tag of new allocations on this thread.
+#[inline]
+fn current_tag() -> MemoryTag {
+    if MEMORY_TAGS_ENABLED {
+        CURRENT_TAG.get()
+    }
+    else {
+        MemoryTag::Untagged
     }
 }
 
+/// Size of the header in front of an allocation with `layout`.
+///
+/// The header stores the tag in its last byte, right in front of the
+/// allocation. It spans a whole alignment unit, so the allocation stays
+/// aligned. Without [`MEMORY_TAGS_ENABLED`] there is no header and the layout
+/// is passed through unchanged.
+#[inline]
+fn header_size(layout: &Layout) -> usize {
+    if MEMORY_TAGS_ENABLED {
+        layout.align()
+    }
+    else {
+        0
+    }
+}
+
+/// Layout of an allocation with its header.
+#[inline]
+fn layout_with_header(layout: &Layout) -> Option<Layout> {
+    Layout::from_size_align(
+        layout.size().checked_add(header_size(layout))?,
+        layout.align(),
+    )
+    .ok()
+}
+
+/// Writes the header of a fresh allocation and returns the pointer to the
+/// allocation after it.
+#[inline]
+unsafe fn write_header(base: *mut u8, layout: &Layout, tag: MemoryTag) -> *mut u8 {
+    if base.is_null() || !MEMORY_TAGS_ENABLED {
+        return base;
+    }
+
+    unsafe {
+        let ptr = base.add(header_size(layout));
+        ptr.sub(1).write(tag as u8);
+        ptr
+    }
+}
+
+/// Reads the header of an allocation and returns its tag and base pointer.
+#[inline]
+unsafe fn read_header(ptr: *mut u8, layout: &Layout) -> (MemoryTag, *mut u8) {
+    if !MEMORY_TAGS_ENABLED {
+        return (MemoryTag::Untagged, ptr);
+    }
+
+    unsafe {
+        let tag = MemoryTag::from_u8(ptr.sub(1).read());
+        (tag, ptr.sub(header_size(layout)))
+    }
+}
+
+// allocations through the `Allocator` API are not tagged, since that would
+// change the layout the inner allocator sees.
 unsafe impl<A: Allocator> Allocator for StatsAllocator<A> {
     #[inline]
     fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
-        self.increment_bytes_allocated(layout.size());
+        self.increment_bytes_allocated(MemoryTag::Untagged, layout.size());
         self.inner.allocate(layout)
     }
 
     #[inline]
     unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
-        self.decrement_bytes_allocated(layout.size());
+        self.decrement_bytes_allocated(MemoryTag::Untagged, layout.size());
         unsafe { self.inner.deallocate(ptr, layout) }
     }
 
     #[inline]
     fn allocate_zeroed(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
-        self.increment_bytes_allocated(layout.size());
+        self.increment_bytes_allocated(MemoryTag::Untagged, layout.size());
         self.inner.allocate_zeroed(layout)
     }
 
@@ -82,7 +254,7 @@ unsafe impl<A: Allocator> Allocator for StatsAllocator<A> {
         old_layout: Layout,
         new_layout: Layout,
     ) -> Result<NonNull<[u8]>, AllocError> {
-        self.increment_bytes_allocated(new_layout.size() - old_layout.size());
+        self.increment_bytes_allocated(MemoryTag::Untagged, new_layout.size() - old_layout.size());
         unsafe { self.inner.grow(ptr, old_layout, new_layout) }
     }
 
@@ -93,7 +265,7 @@ unsafe impl<A: Allocator> Allocator for StatsAllocator<A> {
         old_layout: Layout,
         new_layout: Layout,
     ) -> Result<NonNull<[u8]>, AllocError> {
-        self.increment_bytes_allocated(new_layout.size() - old_layout.size());
+        self.increment_bytes_allocated(MemoryTag::Untagged, new_layout.size() - old_layout.size());
         unsafe { self.inner.grow_zeroed(ptr, old_layout, new_layout) }
     }
 
@@ -104,7 +276,7 @@ unsafe impl<A: Allocator> Allocator for StatsAllocator<A> {
         old_layout: Layout,
         new_layout: Layout,
     ) -> Result<NonNull<[u8]>, AllocError> {
-        self.decrement_bytes_allocated(old_layout.size() - new_layout.size());
+        self.decrement_bytes_allocated(MemoryTag::Untagged, old_layout.size() - new_layout.size());
         unsafe { self.inner.shrink(ptr, old_layout, new_layout) }
     }
 
@@ -120,32 +292,99 @@ unsafe impl<A: Allocator> Allocator for StatsAllocator<A> {
 unsafe impl<A: GlobalAlloc> GlobalAlloc for StatsAllocator<A> {
     #[inline]
     unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
-        self.increment_bytes_allocated(layout.size());
-        unsafe { self.inner.alloc(layout) }
+        let Some(layout_with_header) = layout_with_header(&layout)
+        else {
+            return std::ptr::null_mut();
+        };
+
+        let tag = current_tag();
+        let ptr = unsafe { write_header(self.inner.alloc(layout_with_header), &layout, tag) };
+        if !ptr.is_null() {
+            self.increment_bytes_allocated(tag, layout.size());
+        }
+        ptr
     }
 
     #[inline]
     unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
-        self.increment_bytes_allocated(layout.size());
-        unsafe { self.inner.alloc_zeroed(layout) }
+        let Some(layout_with_header) = layout_with_header(&layout)
+        else {
+            return std::ptr::null_mut();
+        };
+
+        let tag = current_tag();
+        let ptr =
+            unsafe { write_header(self.inner.alloc_zeroed(layout_with_header), &layout, tag) };
+        if !ptr.is_null() {
+            self.increment_bytes_allocated(tag, layout.size());
+        }
+        ptr
     }
 
     #[inline]
     unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
-        self.decrement_bytes_allocated(layout.size());
         unsafe {
-            self.inner.dealloc(ptr, layout);
+            let (tag, base) = read_header(ptr, &layout);
+            self.decrement_bytes_allocated(tag, layout.size());
+            self.inner
+                .dealloc(base, layout_with_header(&layout).unwrap_unchecked());
         }
     }
 
     #[inline]
     unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let Some(new_size_with_header) = new_size.checked_add(header_size(&layout))
+        else {
+            return std::ptr::null_mut();
+        };
+
+        // the header is moved along with the allocation, so it keeps its tag.
+        let (tag, base) = unsafe { read_header(ptr, &layout) };
+        let base = unsafe {
+            self.inner.realloc(
+                base,
+                layout_with_header(&layout).unwrap_unchecked(),
+                new_size_with_header,
+            )
+        };
+        if base.is_null() {
+            return base;
+        }
+
         let old_size = layout.size();
         match new_size.cmp(&old_size) {
-            cmp::Ordering::Less => self.decrement_bytes_allocated(old_size - new_size),
-            cmp::Ordering::Greater => self.increment_bytes_allocated(new_size - old_size),
+            cmp::Ordering::Less => self.decrement_bytes_allocated(tag, old_size - new_size),
+            cmp::Ordering::Greater => self.increment_bytes_allocated(tag, new_size - old_size),
             _ => {}
         }
-        unsafe { self.inner.realloc(ptr, layout, new_size) }
+
+        unsafe { base.add(header_size(&layout)) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::util::stats_alloc::{
+        MemoryTag,
+        bytes_allocated_with_tag,
+        with_memory_tag,
+    };
+
+    #[test]
+    #[cfg_attr(not(feature = "memory-tags"), ignore = "needs the memory-tags feature")]
+    fn it_attributes_allocations_to_their_tag_until_freed() {
+        // nothing else allocates with this tag in the tests
+        let tag = MemoryTag::Audio;
+        let before = bytes_allocated_with_tag(tag);
+
+        let mut buffer = with_memory_tag(tag, || Vec::<u8>::with_capacity(1000));
+        assert_eq!(bytes_allocated_with_tag(tag) - before, 1000);
+
+        // growing outside of the scope keeps the tag
+        buffer.reserve_exact(2000);
+        assert_eq!(bytes_allocated_with_tag(tag) - before, 2000);
+
+        drop(buffer);
+        assert_eq!(bytes_allocated_with_tag(tag), before);
     }
 }
@@ -0,0 +1,153 @@
+use bevy_ecs::{
+    entity::Entity,
+    system::{
+        Query,
+        Res,
+        SystemParam,
+    },
+};
+use nalgebra::{
+    Point3,
+    Vector3,
+};
+
+use crate::voxel::{
+    Voxel,
+    chunk::{
+        Chunk,
+        ChunkShape,
+    },
+    chunk_map::ChunkMap,
+    edit::ChunkEditBatch,
+};
+
+/// Access to individual voxels by their world position.
+///
+/// Only chunks that are loaded and have voxel data can be accessed.
+#[derive(SystemParam)]
+pub struct Voxels<'w, 's, V, S>
+where
+    V: Voxel,
+    S: ChunkShape,
+{
+    chunk_map: Res<'w, ChunkMap>,
+    chunks: Query<'w, 's, &'static mut Chunk<V, S>>,
+}
+
+impl<'w, 's, V, S> Voxels<'w, 's, V, S>
+where
+    V: Voxel,
+    S: ChunkShape + Default,
+{
+    pub fn get(&self, position: Point3<i32>) -> Option<&V> {
+        let (chunk_entity, offset) = self.locate(position)?;
+        let chunk = self.chunks.get(chunk_entity).ok()?;
+        chunk.get(offset)
+    }
+
+    /// Returns the voxel that contains a point, e.g. the camera's position.
+    #[inline]
+    pub fn get_at(&self, point: &Point3<f32>) -> Option<&V> {
+        self.get(block_containing(point))
+    }
+
+    /// Replaces a voxel and returns the old one.
+    ///
+    /// Returns `None` if the chunk containing the voxel is not loaded. This
+    /// will trigger change detection on the chunk, so it will be remeshed.
+    pub fn set(&mut self, position: Point3<i32>, voxel: V) -> Option<V> {
+        let (chunk_entity, offset) = self.locate(position)?;
+        let mut chunk = self.chunks.get_mut(chunk_entity).ok()?;
+        Some(chunk.set(offset, voxel))
+    }
+
+    /// Applies a batch of writes and returns the replaced voxels with their
+    /// positions.
+    ///
+    /// Every touched chunk is only modified once. Writes to chunks that are not
+    /// loaded are skipped.
+    pub fn apply(&mut self, batch: ChunkEditBatch<V, S>) -> Vec<(Point3<i32>, V)> {
+        let chunk_size: i32 = S::default().side_length().try_into().unwrap();
+
+        let mut replaced = Vec::with_capacity(batch.len());
+        for (chunk_position, edits) in batch.into_chunks() {
+            let Some(chunk_entity) = self.chunk_map.get(chunk_position)
+            else {
+                continue;
+            };
+            let Ok(mut chunk) = self.chunks.get_mut(chunk_entity)
+            else {
+                continue;
+            };
+
+            let origin = chunk_position * chunk_size;
+            let chunk = &mut *chunk;
+
+            replaced.extend(edits.into_iter().map(|(offset, voxel)| {
+                (
+                    origin + offset.coords.cast::<i32>(),
+                    chunk.set(offset, voxel),
+                )
+            }));
+        }
+
+        replaced
+    }
+
+    fn locate(&self, position: Point3<i32>) -> Option<(Entity, Point3<u16>)> {
+        let (chunk_position, offset) = split_position(&S::default(), position);
+        let chunk_entity = self.chunk_map.get(chunk_position)?;
+        Some((chunk_entity, offset))
+    }
+}
+
+/// Splits a world position into the chunk position and the offset within that
+/// chunk.
+pub fn split_position<S>(shape: &S, position: Point3<i32>) -> (Point3<i32>, Point3<u16>)
+where
+    S: ChunkShape,
+{
+    let chunk_size: i32 = shape.side_length().try_into().unwrap();
+
+    let chunk_position = position.map(|c| c.div_euclid(chunk_size));
+    let offset = position.map(|c| c.rem_euclid(chunk_size) as u16);
+
+    (chunk_position, offset)
+}
+
+/// The center of a block in world coordinates.
+#[inline]
+pub fn block_center(position: Point3<i32>) -> Point3<f32> {
+    position.cast::<f32>() + Vector3::repeat(0.5)
+}
+
+/// The position of the block that contains a point in world coordinates.
+#[inline]
+pub fn block_containing(point: &Point3<f32>) -> Point3<i32> {
+    point.map(|c| c.floor() as i32)
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::Point3;
+
+    use crate::voxel::access::{
+        block_center,
+        block_containing,
+    };
+
+    #[test]
+    fn it_finds_the_block_containing_a_point() {
+        assert_eq!(
+            block_containing(&Point3::new(0.5, 1.0, 2.99)),
+            Point3::new(0, 1, 2)
+        );
+        assert_eq!(
+            block_containing(&Point3::new(-0.5, -1.0, -2.01)),
+            Point3::new(-1, -1, -3)
+        );
+
+        let block = Point3::new(-3, 7, -1);
+        assert_eq!(block_containing(&block_center(block)), block);
+    }
+}
@@ -13,7 +13,7 @@ use nalgebra::Point3;
 ///
 /// The chunk data itself is reference-counted. Thus cloning the [`Chunk`] is
 /// cheap. Modification might copy the data if there are multiple references to
-/// the chunk. (Todo: modification is not implemented yet)
+/// the chunk.
 ///
 /// Internally the data is layout in Z-order to improve cache coherency.
 #[derive(derive_more::Debug, Clone, Component)]
@@ -42,6 +42,19 @@ where
         Self { voxels, shape }
     }
 
+    /// Creates a chunk where all voxels are `voxel`.
+    pub fn filled(shape: S, voxel: V) -> Self
+    where
+        V: Clone,
+    {
+        let side_length = shape.side_length();
+        let num_voxels = side_length * side_length * side_length;
+
+        let voxels = std::iter::repeat_n(voxel, num_voxels).collect::<Arc<[V]>>();
+
+        Self { voxels, shape }
+    }
+
     pub fn iter(&self) -> impl Iterator<Item = (Point3<u16>, &V)> {
         self.voxels
             .iter()
@@ -53,6 +66,36 @@ where
     pub fn get(&self, point: Point3<u16>) -> Option<&V> {
         self.voxels.get(self.shape.encode(point))
     }
+
+    /// Returns a mutable reference to a voxel.
+    ///
+    /// If the chunk data is shared with other [`Chunk`]s, it will be copied
+    /// first.
+    #[inline]
+    pub fn get_mut(&mut self, point: Point3<u16>) -> Option<&mut V>
+    where
+        V: Clone,
+    {
+        let index = self.shape.encode(point);
+        Arc::make_mut(&mut self.voxels).get_mut(index)
+    }
+
+    /// Replaces a voxel and returns the old one.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `point` is outside of the chunk.
+    #[inline]
+    pub fn set(&mut self, point: Point3<u16>, voxel: V) -> V
+    where
+        V: Clone,
+    {
+        let Some(slot) = self.get_mut(point)
+        else {
+            panic!("voxel position out of bounds: {point:?}");
+        };
+        std::mem::replace(slot, voxel)
+    }
 }
 
 impl<V, S> Chunk<V, S> {
@@ -86,6 +129,19 @@ impl<V, S> AsRef<[V]> for Chunk<V, S> {
     }
 }
 
+/// Mutable access to the voxels in the order of the chunk's shape.
+///
+/// If the chunk data is shared with other [`Chunk`]s, it will be copied first.
+impl<V, S> AsMut<[V]> for Chunk<V, S>
+where
+    V: Clone,
+{
+    #[inline]
+    fn as_mut(&mut self) -> &mut [V] {
+        Arc::make_mut(&mut self.voxels)
+    }
+}
+
 pub trait ChunkShape: Clone + Send + Sync + 'static {
     fn side_length(&self) -> usize;
     fn encode(&self, point: Point3<u16>) -> usize;
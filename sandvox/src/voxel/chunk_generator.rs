@@ -6,6 +6,7 @@ use std::{
 use bevy_ecs::{
     component::Component,
     entity::Entity,
+    message::MessageWriter,
     resource::Resource,
     schedule::{
         IntoScheduleConfigs,
@@ -38,6 +39,10 @@ use crate::{
         },
         schedule,
     },
+    util::stats_alloc::{
+        MemoryTag,
+        memory_scope,
+    },
     voxel::{
         Voxel,
         chunk::{
@@ -45,6 +50,8 @@ use crate::{
             ChunkShape,
         },
         chunk_map::{
+            ChunkGenerated,
+            ChunkLoaded,
             ChunkPosition,
             ChunkStatistics,
         },
@@ -83,15 +90,18 @@ where
     fn setup(&self, builder: &mut WorldBuilder) -> Result<(), Error> {
         builder.configure_background_task_queue::<GenerateChunkTask<V, S, G>>(self.task_config);
 
-        builder.add_systems(
-            schedule::Update,
-            (
-                make_chunk_generator_shared::<V, S, G>.run_if(resource_exists::<G>),
-                dispatch_chunk_generation::<V, S, G>
-                    .run_if(resource_exists::<SharedChunkGenerator<G>>),
-            )
-                .chain(),
-        );
+        builder
+            .add_message::<ChunkLoaded>()
+            .add_message::<ChunkGenerated>()
+            .add_systems(
+                schedule::Update,
+                (
+                    make_chunk_generator_shared::<V, S, G>.run_if(resource_exists::<G>),
+                    dispatch_chunk_generation::<V, S, G>
+                        .run_if(resource_exists::<SharedChunkGenerator<G>>),
+                )
+                    .chain(),
+            );
 
         Ok(())
     }
@@ -120,11 +130,26 @@ fn dispatch_chunk_generation<V, S, G>(
     chunk_generator: Res<SharedChunkGenerator<G>>,
     chunks: Query<(Entity, &ChunkPosition, &GenerateChunk<S>)>,
     mut commands: Commands,
+    mut chunk_generated: MessageWriter<ChunkGenerated>,
 ) where
     V: Voxel,
     S: ChunkShape,
     G: ChunkGenerator<V, S>,
 {
+    // discarded chunks stay empty, so we're done with them right away
+    for (entity, position, generate_chunk) in &chunks {
+        if chunk_generator
+            .0
+            .early_discard(position.0, &generate_chunk.shape)
+        {
+            commands.entity(entity).remove::<GenerateChunk<S>>();
+            chunk_generated.write(ChunkGenerated {
+                entity,
+                position: position.0,
+            });
+        }
+    }
+
     background_tasks.push_tasks(
         chunks
             .iter()
@@ -161,19 +186,37 @@ where
     S: ChunkShape,
     G: ChunkGenerator<V, S>,
 {
+    const NAME: &'static str = "generate chunk";
+
     fn run(self, world_modifications: &mut CommandQueue) {
-        if let Some(chunk) = self
+        let _memory_scope = memory_scope(MemoryTag::Chunks);
+
+        let chunk = self
             .chunk_generator
-            .generate_chunk(self.position, self.shape)
-        {
-            world_modifications.push(move |world: &mut World| {
+            .generate_chunk(self.position, self.shape);
+
+        world_modifications.push(move |world: &mut World| {
+            world.write_message(ChunkGenerated {
+                entity: self.entity,
+                position: self.position,
+            });
+
+            if let Some(chunk) = chunk {
                 let mut chunk_statistics = world.resource_mut::<ChunkStatistics>();
                 chunk_statistics.num_chunks_loaded += 1;
                 chunk_statistics.bytes_chunks_loaded += chunk.byte_size();
 
                 world.commands().entity(self.entity).insert(chunk);
-            });
-        }
+
+                // the chunk might have been unloaded while it was generated
+                if world.get_entity(self.entity).is_ok() {
+                    world.write_message(ChunkLoaded {
+                        entity: self.entity,
+                        position: self.position,
+                    });
+                }
+            }
+        });
     }
 }
 
@@ -9,10 +9,7 @@ use bevy_ecs::{
         MessageReader,
     },
     resource::Resource,
-    system::{
-        Query,
-        ResMut,
-    },
+    system::ResMut,
     world::DeferredWorld,
 };
 use color_eyre::eyre::Error;
@@ -26,12 +23,16 @@ use crate::ecs::{
     schedule,
 };
 
+#[derive(Clone, Copy, Debug, Default)]
 pub struct ChunkMapPlugin;
 
 impl Plugin for ChunkMapPlugin {
     fn setup(&self, builder: &mut WorldBuilder) -> Result<(), Error> {
         builder
             .add_message::<ChunkMapMessage>()
+            .add_message::<ChunkLoaded>()
+            .add_message::<ChunkGenerated>()
+            .add_message::<ChunkUnloaded>()
             .insert_resource(ChunkMap::default())
             .insert_resource(ChunkStatistics::default())
             .add_systems(schedule::Update, update_chunk_map);
@@ -60,38 +61,78 @@ impl ChunkMap {
 pub struct ChunkPosition(pub Point3<i32>);
 
 fn chunk_added(mut world: DeferredWorld, context: HookContext) {
+    let position = world.get::<ChunkPosition>(context.entity).unwrap().0;
+
     world.write_message(ChunkMapMessage::Added {
         entity: context.entity,
+        position,
     });
 }
 
 fn chunk_removed(mut world: DeferredWorld, context: HookContext) {
+    let position = world.get::<ChunkPosition>(context.entity).unwrap().0;
+
     world.write_message(ChunkMapMessage::Removed {
         entity: context.entity,
+        position,
+    });
+    world.write_message(ChunkUnloaded {
+        entity: context.entity,
+        position,
     });
 }
 
+/// Sent when the voxel data of a chunk becomes available.
+#[derive(Clone, Copy, Debug, Message)]
+pub struct ChunkLoaded {
+    pub entity: Entity,
+    pub position: Point3<i32>,
+}
+
+/// Sent when the chunk generator is done with a chunk.
+///
+/// Unlike [`ChunkLoaded`] this is also sent for chunks that the generator
+/// left empty.
+#[derive(Clone, Copy, Debug, Message)]
+pub struct ChunkGenerated {
+    pub entity: Entity,
+    pub position: Point3<i32>,
+}
+
+/// Sent when a chunk is removed from the world.
+///
+/// The chunk entity might already be despawned when this is read.
+#[derive(Clone, Copy, Debug, Message)]
+pub struct ChunkUnloaded {
+    pub entity: Entity,
+    pub position: Point3<i32>,
+}
+
+/// The position is sent along, since the chunk might be despawned by the time
+/// the message is read.
 #[derive(Clone, Copy, Debug, Message)]
 enum ChunkMapMessage {
-    Added { entity: Entity },
-    Removed { entity: Entity },
+    Added {
+        entity: Entity,
+        position: Point3<i32>,
+    },
+    Removed {
+        entity: Entity,
+        position: Point3<i32>,
+    },
 }
 
-fn update_chunk_map(
-    mut messages: MessageReader<ChunkMapMessage>,
-    mut chunk_map: ResMut<ChunkMap>,
-    chunks: Query<&mut ChunkPosition>,
-) {
+fn update_chunk_map(mut messages: MessageReader<ChunkMapMessage>, mut chunk_map: ResMut<ChunkMap>) {
     for message in messages.read() {
-        match message {
-            ChunkMapMessage::Added { entity } => {
-                let position = chunks.get(*entity).unwrap().0;
-                chunk_map.map.insert(position, *entity);
+        match *message {
+            ChunkMapMessage::Added { entity, position } => {
+                chunk_map.map.insert(position, entity);
             }
-            ChunkMapMessage::Removed { entity } => {
-                let position = chunks.get(*entity).unwrap().0;
-
-                chunk_map.map.remove(&position);
+            ChunkMapMessage::Removed { entity, position } => {
+                // another chunk might have been added at the same position already
+                if chunk_map.map.get(&position) == Some(&entity) {
+                    chunk_map.map.remove(&position);
+                }
             }
         }
     }
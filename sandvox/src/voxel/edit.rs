@@ -0,0 +1,105 @@
+use std::collections::HashMap;
+
+use nalgebra::Point3;
+
+use crate::voxel::{
+    access::split_position,
+    chunk::ChunkShape,
+};
+
+/// Many voxel writes that are applied at once with
+/// [`Voxels::apply`][crate::voxel::access::Voxels::apply].
+///
+/// The writes are grouped by chunk, so that every chunk is only modified (and
+/// remeshed) once, no matter how many of its voxels change. This is used for
+/// edits that touch a lot of voxels, e.g. explosions, pasting schematics or
+/// filling regions.
+#[derive(Clone, Debug)]
+pub struct ChunkEditBatch<V, S> {
+    shape: S,
+    chunks: HashMap<Point3<i32>, Vec<(Point3<u16>, V)>>,
+    len: usize,
+}
+
+impl<V, S> Default for ChunkEditBatch<V, S>
+where
+    S: Default,
+{
+    fn default() -> Self {
+        Self {
+            shape: S::default(),
+            chunks: HashMap::new(),
+            len: 0,
+        }
+    }
+}
+
+impl<V, S> ChunkEditBatch<V, S>
+where
+    S: ChunkShape,
+{
+    /// Sets the voxel at `position`. Later writes to the same position win.
+    pub fn set(&mut self, position: Point3<i32>, voxel: V) {
+        let (chunk_position, offset) = split_position(&self.shape, position);
+        self.chunks
+            .entry(chunk_position)
+            .or_default()
+            .push((offset, voxel));
+        self.len += 1;
+    }
+
+    /// Sets all voxels between `min` and `max` (inclusive).
+    pub fn fill(&mut self, min: Point3<i32>, max: Point3<i32>, voxel: V)
+    where
+        V: Clone,
+    {
+        for z in min.z..=max.z {
+            for y in min.y..=max.y {
+                for x in min.x..=max.x {
+                    self.set(Point3::new(x, y, z), voxel.clone());
+                }
+            }
+        }
+    }
+
+    /// Number of writes.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Number of chunks that are touched by the writes.
+    pub fn num_chunks(&self) -> usize {
+        self.chunks.len()
+    }
+
+    pub(super) fn into_chunks(self) -> impl Iterator<Item = (Point3<i32>, Vec<(Point3<u16>, V)>)> {
+        self.chunks.into_iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::Point3;
+
+    use crate::voxel::{
+        chunk::LinearShape,
+        edit::ChunkEditBatch,
+    };
+
+    #[test]
+    fn it_groups_writes_by_chunk() {
+        let mut batch = ChunkEditBatch::<u8, LinearShape<4>>::default();
+
+        batch.fill(Point3::new(0, 0, 0), Point3::new(3, 3, 3), 1);
+        assert_eq!(batch.len(), 64);
+        assert_eq!(batch.num_chunks(), 1);
+
+        batch.fill(Point3::new(-1, 0, 0), Point3::new(4, 0, 0), 2);
+        assert_eq!(batch.len(), 70);
+        assert_eq!(batch.num_chunks(), 3);
+    }
+}
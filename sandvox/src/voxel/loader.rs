@@ -1,6 +1,15 @@
+use std::{
+    collections::HashSet,
+    time::{
+        Duration,
+        Instant,
+    },
+};
+
 use bevy_ecs::{
     component::Component,
     entity::Entity,
+    message::MessageReader,
     query::{
         Added,
         Changed,
@@ -42,6 +51,7 @@ use crate::{
         chunk::ChunkShape,
         chunk_generator::GenerateChunk,
         chunk_map::{
+            ChunkGenerated,
             ChunkMap,
             ChunkPosition,
         },
@@ -66,7 +76,10 @@ where
                     create_chunk_loader_states::<S>,
                     update_chunk_loader_states::<S>,
                     remove_chunk_loader_states,
+                    start_pregeneration::<S>,
+                    update_pregeneration,
                 )
+                    .chain()
                     .after(TransformSystems::Propagate),
             );
 
@@ -152,6 +165,114 @@ fn remove_chunk_loader_states(
     }
 }
 
+/// Generates all chunks in a range ahead of time, e.g. so a server can warm up
+/// a world before players join.
+///
+/// Progress is logged, and the entity is despawned when all chunks are
+/// generated.
+///
+/// Chunks are never unloaded, and not saved to the world file either, so the
+/// pregenerated chunks stay loaded until the game exits. Callers must limit the
+/// range accordingly, like the `pregenerate` command does.
+#[derive(Clone, Copy, Debug, Component)]
+pub struct PregenerateChunks {
+    /// Chunk position at the center of the range.
+    pub center: Point3<i32>,
+
+    pub radius: Vector3<u32>,
+}
+
+/// How often pregeneration progress is logged.
+const PREGENERATION_REPORT_INTERVAL: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Component)]
+struct PregenerationState {
+    remaining: HashSet<Point3<i32>>,
+    total: usize,
+    start_time: Instant,
+    last_report: Instant,
+}
+
+fn start_pregeneration<S>(
+    pregenerations: Query<(Entity, &PregenerateChunks), Added<PregenerateChunks>>,
+    pending_chunks: Query<(), With<GenerateChunk<S>>>,
+    mut load_chunks: LoadChunks<S>,
+) where
+    S: ChunkShape,
+{
+    for (entity, pregenerate) in &pregenerations {
+        // chunks that are loaded already don't need to be generated. chunks that are
+        // being generated right now are counted as done too, since we have no way to
+        // tell them apart.
+        let remaining = all_chunks_in_range(pregenerate.center, pregenerate.radius)
+            .filter(|chunk_position| {
+                load_chunks
+                    .chunk_map
+                    .get(*chunk_position)
+                    .is_none_or(|chunk| pending_chunks.contains(chunk))
+            })
+            .collect::<HashSet<_>>();
+
+        tracing::info!(
+            center = ?pregenerate.center,
+            radius = ?pregenerate.radius,
+            num_chunks = remaining.len(),
+            "pregenerating chunks"
+        );
+
+        load_chunks.load_all(remaining.iter().copied());
+
+        let now = Instant::now();
+        load_chunks
+            .commands
+            .entity(entity)
+            .insert(PregenerationState {
+                total: remaining.len(),
+                remaining,
+                start_time: now,
+                last_report: now,
+            });
+    }
+}
+
+fn update_pregeneration(
+    mut chunk_generated: MessageReader<ChunkGenerated>,
+    pregenerations: Query<(Entity, &mut PregenerationState)>,
+    mut commands: Commands,
+) {
+    let chunk_generated = chunk_generated.read().collect::<Vec<_>>();
+    let now = Instant::now();
+
+    for (entity, mut state) in pregenerations {
+        for message in &chunk_generated {
+            state.remaining.remove(&message.position);
+        }
+
+        let elapsed = now - state.start_time;
+
+        if state.remaining.is_empty() {
+            tracing::info!(total = state.total, ?elapsed, "pregeneration done");
+            commands.entity(entity).despawn();
+        }
+        else if now - state.last_report >= PREGENERATION_REPORT_INTERVAL {
+            let done = state.total - state.remaining.len();
+            let eta =
+                (done > 0).then(|| elapsed.mul_f64(state.remaining.len() as f64 / done as f64));
+
+            tracing::info!(
+                done,
+                total = state.total,
+                percent = format_args!("{:.1}", 100.0 * done as f32 / state.total as f32),
+                ?elapsed,
+                ?eta,
+                "pregenerating chunks"
+            );
+
+            state.last_report = now;
+        }
+    }
+}
+
 #[derive(Debug, Resource)]
 struct ChunkLoaderShape<S>(S);
 
@@ -200,7 +321,10 @@ where
     }
 }
 
-fn chunk_position_from_transform<S>(shape: &S, transform: &GlobalTransform) -> Point3<i32>
+pub(crate) fn chunk_position_from_transform<S>(
+    shape: &S,
+    transform: &GlobalTransform,
+) -> Point3<i32>
 where
     S: ChunkShape,
 {
@@ -215,7 +339,8 @@ where
     .into()
 }
 
-fn all_chunks_in_range(
+/// All chunk positions within `radius` of `position`.
+pub(crate) fn all_chunks_in_range(
     position: Point3<i32>,
     radius: Vector3<u32>,
 ) -> impl Iterator<Item = Point3<i32>> {
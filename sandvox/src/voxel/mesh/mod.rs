@@ -13,14 +13,24 @@ use bevy_ecs::{
     query::{
         Changed,
         Or,
+        With,
         Without,
     },
     resource::Resource,
+    schedule::{
+        IntoScheduleConfigs,
+        common_conditions::{
+            resource_exists,
+            resource_exists_and_changed,
+        },
+    },
     system::{
         Commands,
         Local,
         Populated,
+        Query,
         Res,
+        ResMut,
     },
     world::{
         CommandQueue,
@@ -44,17 +54,24 @@ use crate::{
             WorldBuilderBackgroundTaskExt,
         },
         plugin::{
+            Dependencies,
             Plugin,
             WorldBuilder,
         },
         schedule,
         workspace::Workspaces,
     },
-    render::mesh::{
-        MeshBuilder,
-        MeshPipelineLayout,
-        MeshPlugin,
-        Vertex,
+    render::{
+        mesh::{
+            MeshBuilder,
+            MeshPlugin,
+            Vertex,
+        },
+        mesh_arena::MeshArena,
+    },
+    util::stats_alloc::{
+        MemoryTag,
+        memory_scope,
     },
     voxel::{
         BlockFace,
@@ -96,12 +113,26 @@ where
     D: Resource + Clone + VoxelData<V>,
     M: ChunkMesher<V, S>,
 {
+    fn dependencies(&self, dependencies: &mut Dependencies) -> Result<(), Error> {
+        dependencies.require_plugin::<MeshPlugin>();
+        Ok(())
+    }
+
     fn setup(&self, builder: &mut WorldBuilder) -> Result<(), Error> {
         builder.configure_background_task_queue::<MeshChunkTask<V, S, D, M>>(self.task_config);
 
         builder
-            .add_plugin(MeshPlugin)?
-            .add_systems(schedule::Update, dispatch_chunk_meshing::<V, S, D, M>);
+            .init_resource::<ChunkMeshGeneration>()
+            .add_systems(schedule::GpuSetup, invalidate_chunk_meshes)
+            .add_systems(
+                schedule::Update,
+                (
+                    invalidate_chunk_meshes.run_if(resource_exists_and_changed::<D>),
+                    // the voxel data might still be loading
+                    dispatch_chunk_meshing::<V, S, D, M>.run_if(resource_exists::<D>),
+                )
+                    .chain(),
+            );
 
         Ok(())
     }
@@ -110,6 +141,28 @@ where
 #[derive(Clone, Copy, Debug, Default, Component)]
 struct ChunkMeshed;
 
+/// Incremented whenever the chunk meshes are invalidated.
+///
+/// Meshing tasks that were dispatched before that still use the old voxel data
+/// (e.g. texture IDs of atlas views that were freed), so their results are
+/// discarded.
+#[derive(Clone, Copy, Debug, Default, Resource)]
+struct ChunkMeshGeneration(u64);
+
+/// Meshes all chunks again when the voxel data changed, e.g. because the block
+/// textures were reloaded, or when the GPU device was lost.
+fn invalidate_chunk_meshes(
+    chunks: Query<Entity, With<ChunkMeshed>>,
+    mut generation: ResMut<ChunkMeshGeneration>,
+    mut commands: Commands,
+) {
+    generation.0 += 1;
+
+    for entity in chunks {
+        commands.entity(entity).remove::<ChunkMeshed>();
+    }
+}
+
 #[derive(Clone, Copy, Debug, Default, Component)]
 struct MeshChunkTaskDispatched;
 
@@ -121,8 +174,9 @@ where
     entity: Entity,
     chunk: Chunk<V, S>,
     wgpu: WgpuContext,
-    mesh_bind_group_layout: wgpu::BindGroupLayout,
+    mesh_arena: MeshArena,
     voxel_data: D,
+    generation: u64,
     workspaces: Workspaces<(MeshBuilder, M)>,
 }
 
@@ -133,7 +187,11 @@ where
     M: ChunkMesher<V, S>,
     D: VoxelData<V> + Send + Sync + 'static,
 {
+    const NAME: &'static str = "mesh chunk";
+
     fn run(self, world_modifications: &mut CommandQueue) {
+        let _memory_scope = memory_scope(MemoryTag::Meshing);
+
         let mut workspace = self
             .workspaces
             .get_or_init(|| (MeshBuilder::default(), M::new(self.chunk.shape())));
@@ -145,14 +203,33 @@ where
         let time = t_start.elapsed();
         tracing::trace!(entity = ?self.entity, ?time, "meshed chunk");
 
-        let mesh = mesh_builder.finish(
-            &self.wgpu,
-            &format!("chunk {:?}", self.entity),
-            &self.mesh_bind_group_layout,
-        );
+        let mesh = mesh_builder.finish_in_arena(&self.mesh_arena, &self.wgpu.queue);
         mesh_builder.clear();
 
         world_modifications.push(move |world: &mut World| {
+            if !world
+                .get_resource::<MeshArena>()
+                .is_some_and(|mesh_arena| mesh_arena.ptr_eq(&self.mesh_arena))
+            {
+                // the mesh was allocated in the arena of a lost device, so the chunk has to be
+                // meshed again.
+                world
+                    .commands()
+                    .entity(self.entity)
+                    .remove::<MeshChunkTaskDispatched>();
+                return;
+            }
+
+            if world.resource::<ChunkMeshGeneration>().0 != self.generation {
+                // the voxel data changed while the chunk was being meshed, so the mesh might
+                // refer to textures that don't exist anymore.
+                world
+                    .commands()
+                    .entity(self.entity)
+                    .remove::<MeshChunkTaskDispatched>();
+                return;
+            }
+
             if let Some(mesh) = &mesh {
                 let mut chunk_statistics = world.resource_mut::<ChunkStatistics>();
                 chunk_statistics.num_chunks_meshed += 1;
@@ -182,7 +259,8 @@ fn dispatch_chunk_meshing<V, S, D, M>(
     >,
     voxel_data: Res<D>,
     workspaces: Local<Workspaces<(MeshBuilder, M)>>,
-    mesh_layout: Res<MeshPipelineLayout>,
+    mesh_arena: Res<MeshArena>,
+    generation: Res<ChunkMeshGeneration>,
     mut commands: Commands,
 ) where
     V: Voxel,
@@ -198,8 +276,9 @@ fn dispatch_chunk_meshing<V, S, D, M>(
             chunk: chunk.clone(),
             wgpu: wgpu.clone(),
             voxel_data: voxel_data.clone(),
+            generation: generation.0,
             workspaces: workspaces.clone(),
-            mesh_bind_group_layout: mesh_layout.mesh_bind_group_layout.clone(),
+            mesh_arena: mesh_arena.clone(),
         }
     }));
 }
@@ -312,7 +391,9 @@ impl UnorientedQuad {
                 normal,
                 uv: Point2::from(uvs[i]).cast(),
                 texture_id,
-                padding: 0,
+                joint_weights: 0,
+                joints: [0; 2],
+                padding: [0; 2],
             }
         });
 
@@ -1,8 +1,11 @@
+pub mod access;
 pub mod chunk;
 pub mod chunk_generator;
 pub mod chunk_map;
+pub mod edit;
 pub mod loader;
 pub mod mesh;
+pub mod raycast;
 
 use std::fmt::Debug;
 
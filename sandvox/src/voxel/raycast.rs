@@ -0,0 +1,129 @@
+use nalgebra::{
+    Point3,
+    Vector3,
+};
+
+use crate::voxel::BlockFace;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RaycastHit {
+    /// The block that was hit.
+    pub block: Point3<i32>,
+
+    /// The face of the block through which the ray entered it.
+    ///
+    /// This is `None` if the ray started inside the block.
+    pub face: Option<BlockFace>,
+
+    /// Distance along the ray until the hit.
+    pub distance: f32,
+}
+
+impl RaycastHit {
+    /// The block adjacent to the face that was hit, i.e. where a block would
+    /// be placed.
+    pub fn adjacent(&self) -> Option<Point3<i32>> {
+        self.face
+            .map(|face| self.block + face.neighbor().cast::<i32>())
+    }
+}
+
+/// Casts a ray through the voxel grid.
+///
+/// This walks all blocks that the ray intersects (Amanatides & Woo) and
+/// returns the first block for which `is_solid` returns `true`.
+pub fn raycast(
+    origin: Point3<f32>,
+    direction: Vector3<f32>,
+    max_distance: f32,
+    mut is_solid: impl FnMut(Point3<i32>) -> bool,
+) -> Option<RaycastHit> {
+    let direction = direction.try_normalize(f32::EPSILON)?;
+
+    let mut block = origin.map(|c| c.floor() as i32);
+    let step = direction.map(|c| if c > 0.0 { 1 } else { -1 });
+
+    // distance along the ray to cross one block in each axis
+    let t_delta = direction.map(|c| (1.0 / c).abs());
+
+    // distance along the ray to the next block boundary in each axis
+    let mut t_max = Vector3::from_fn(|i, _| {
+        if direction[i] == 0.0 {
+            f32::INFINITY
+        }
+        else {
+            let boundary = if step[i] > 0 {
+                block[i] as f32 + 1.0
+            }
+            else {
+                block[i] as f32
+            };
+            (boundary - origin[i]) / direction[i]
+        }
+    });
+
+    let mut face = None;
+    let mut distance = 0.0;
+
+    while distance <= max_distance {
+        if is_solid(block) {
+            return Some(RaycastHit {
+                block,
+                face,
+                distance,
+            });
+        }
+
+        let axis = t_max.imin();
+        distance = t_max[axis];
+        t_max[axis] += t_delta[axis];
+        block[axis] += step[axis];
+
+        // we entered the block through the face opposing our step direction
+        face = Some(match (axis, step[axis] > 0) {
+            (0, true) => BlockFace::Left,
+            (0, false) => BlockFace::Right,
+            (1, true) => BlockFace::Down,
+            (1, false) => BlockFace::Up,
+            (2, true) => BlockFace::Front,
+            _ => BlockFace::Back,
+        });
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::{
+        Point3,
+        Vector3,
+    };
+
+    use crate::voxel::{
+        BlockFace,
+        raycast::raycast,
+    };
+
+    #[test]
+    fn it_hits_the_first_solid_block() {
+        let hit = raycast(Point3::new(0.5, 0.5, 0.5), Vector3::x(), 10.0, |block| {
+            block.x >= 3
+        })
+        .unwrap();
+
+        assert_eq!(hit.block, Point3::new(3, 0, 0));
+        assert_eq!(hit.face, Some(BlockFace::Left));
+        assert_eq!(hit.adjacent(), Some(Point3::new(2, 0, 0)));
+        assert!((hit.distance - 2.5).abs() < 1e-5);
+    }
+
+    #[test]
+    fn it_respects_max_distance() {
+        let hit = raycast(Point3::new(0.5, 0.5, 0.5), -Vector3::y(), 4.0, |block| {
+            block.y <= -10
+        });
+
+        assert!(hit.is_none());
+    }
+}
@@ -137,9 +137,12 @@ pub trait WriteStagingCommit {
 pub trait WriteStagingExt: WriteStaging {
     fn track_throughput<'a, 'b>(
         &'a mut self,
-        bytes: &'b mut wgpu::BufferAddress,
+        throughput: &'b mut StagingThroughput,
     ) -> TrackThroughput<'b, &'a mut Self> {
-        TrackThroughput { inner: self, bytes }
+        TrackThroughput {
+            inner: self,
+            throughput,
+        }
     }
 }
 
@@ -332,10 +335,36 @@ where
     }
 }
 
+/// Amount of data staged, as counted by [`TrackThroughput`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct StagingThroughput {
+    pub bytes: wgpu::BufferAddress,
+
+    /// Number of staged copies.
+    pub copies: usize,
+
+    /// Size of the largest single allocation.
+    pub largest_allocation: wgpu::BufferAddress,
+}
+
+impl StagingThroughput {
+    pub fn record(&mut self, size: wgpu::BufferSize) {
+        self.bytes += size.get();
+        self.copies += 1;
+        self.largest_allocation = self.largest_allocation.max(size.get());
+    }
+
+    pub fn accumulate(&mut self, other: &Self) {
+        self.bytes += other.bytes;
+        self.copies += other.copies;
+        self.largest_allocation = self.largest_allocation.max(other.largest_allocation);
+    }
+}
+
 #[derive(Debug)]
 pub struct TrackThroughput<'a, Transaction> {
     pub inner: Transaction,
-    pub bytes: &'a mut wgpu::BufferAddress,
+    pub throughput: &'a mut StagingThroughput,
 }
 
 impl<'a, Transaction> WriteStaging for TrackThroughput<'a, Transaction>
@@ -348,7 +377,7 @@ where
         alignment: wgpu::BufferSize,
         with_buffer_slice: impl FnOnce(&mut wgpu::CommandEncoder, wgpu::BufferSlice),
     ) -> wgpu::BufferViewMut {
-        *self.bytes += size.get();
+        self.throughput.record(size);
         self.inner.view_mut(size, alignment, with_buffer_slice)
     }
 }
@@ -435,9 +464,33 @@ struct StagingPoolInner {
     /// Minimum size of an individual chunk
     chunk_size: wgpu::BufferSize,
     chunk_label: Cow<'static, str>,
+    limits: StagingLimits,
     state: RwLock<StagingPoolState>,
 }
 
+/// Limits that keep a single large upload from growing the [`StagingPool`]
+/// permanently.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct StagingLimits {
+    /// Free chunks larger than the chunk size are destroyed once they're back
+    /// from the GPU, unless all free chunks together are at most this many
+    /// bytes.
+    pub free_reserve: wgpu::BufferAddress,
+
+    /// Allocations larger than this don't use the pool at all, but get a
+    /// one-shot buffer that is dropped after the transaction is committed.
+    pub oversize_threshold: wgpu::BufferAddress,
+}
+
+impl Default for StagingLimits {
+    fn default() -> Self {
+        Self {
+            free_reserve: wgpu::BufferAddress::MAX,
+            oversize_threshold: wgpu::BufferAddress::MAX,
+        }
+    }
+}
+
 #[derive(Debug, Default)]
 struct StagingPoolState {
     /// Chunks that are back from the GPU and ready to be mapped for write and
@@ -447,6 +500,44 @@ struct StagingPoolState {
     total_allocated_count: usize,
     total_allocated_bytes: u64,
     total_staged_bytes: u64,
+    total_trimmed_count: usize,
+    total_bypass_count: usize,
+    total_bypass_bytes: u64,
+}
+
+impl StagingPoolState {
+    /// Destroys oversized free chunks, largest first, until the free chunks fit
+    /// into the reserve.
+    fn trim(&mut self, chunk_size: wgpu::BufferSize, free_reserve: wgpu::BufferAddress) {
+        let mut free_bytes = self
+            .free_chunks
+            .iter()
+            .map(|chunk| chunk.buffer.size())
+            .sum::<wgpu::BufferAddress>();
+
+        while free_bytes > free_reserve {
+            let Some(index) = self
+                .free_chunks
+                .iter()
+                .enumerate()
+                .filter(|(_, chunk)| chunk.buffer.size() > chunk_size.get())
+                .max_by_key(|(_, chunk)| chunk.buffer.size())
+                .map(|(index, _)| index)
+            else {
+                break;
+            };
+
+            let chunk = self.free_chunks.swap_remove(index);
+            let size = chunk.buffer.size();
+            tracing::debug!(?size, "freeing oversized staging buffer");
+
+            free_bytes -= size;
+            self.total_allocated_count -= 1;
+            self.total_allocated_bytes -= size;
+            self.total_trimmed_count += 1;
+            chunk.buffer.destroy();
+        }
+    }
 }
 
 impl Default for StagingPool {
@@ -457,10 +548,19 @@ impl Default for StagingPool {
 
 impl StagingPool {
     pub fn new(chunk_size: wgpu::BufferSize, chunk_label: impl Into<Cow<'static, str>>) -> Self {
+        Self::with_limits(chunk_size, chunk_label, Default::default())
+    }
+
+    pub fn with_limits(
+        chunk_size: wgpu::BufferSize,
+        chunk_label: impl Into<Cow<'static, str>>,
+        limits: StagingLimits,
+    ) -> Self {
         Self {
             inner: Arc::new(StagingPoolInner {
                 chunk_size,
                 chunk_label: chunk_label.into(),
+                limits,
                 state: RwLock::new(Default::default()),
             }),
         }
@@ -479,6 +579,9 @@ impl StagingPool {
             total_allocation_count: state.total_allocated_count,
             total_allocation_bytes: state.total_allocated_bytes,
             total_staged_bytes: state.total_staged_bytes,
+            total_trimmed_count: state.total_trimmed_count,
+            total_bypass_count: state.total_bypass_count,
+            total_bypass_bytes: state.total_bypass_bytes,
         }
     }
 }
@@ -492,6 +595,10 @@ pub struct WriteStagingBelt {
     /// Note: if the WriteStagingBelt is dropped while it has active chunks
     /// (i.e. finish wasn't called), the chunks will not be reused.
     active_chunks: Vec<Chunk>,
+
+    /// One-shot buffers for allocations above
+    /// [`StagingLimits::oversize_threshold`].
+    oversized_buffers: Vec<wgpu::Buffer>,
 }
 
 impl WriteStagingBelt {
@@ -503,10 +610,13 @@ impl WriteStagingBelt {
         Self {
             pool,
             active_chunks: vec![],
+            oversized_buffers: vec![],
         }
     }
 
     fn discard_impl(&mut self) {
+        self.oversized_buffers.clear();
+
         let mut state = self.pool.inner.state.write();
         state.in_flight_count -= self.active_chunks.len();
         state
@@ -526,6 +636,30 @@ impl StagingBufferProvider for WriteStagingBelt {
         alignment: wgpu::BufferSize,
         f: impl FnOnce(wgpu::BufferSlice<'_>) -> R,
     ) -> R {
+        if size.get() > self.pool.inner.limits.oversize_threshold {
+            // a chunk this big would stay in the pool, so we bypass it
+            tracing::debug!(?size, "allocating oversized staging buffer");
+
+            // buffers that are mapped at creation must have a size that is a multiple of
+            // 4
+            let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("oversized write staging"),
+                size: wgpu::util::align_to(size.get(), wgpu::COPY_BUFFER_ALIGNMENT),
+                usage: wgpu::BufferUsages::MAP_WRITE | wgpu::BufferUsages::COPY_SRC,
+                mapped_at_creation: true,
+            });
+
+            {
+                let mut state = self.pool.inner.state.write();
+                state.total_bypass_count += 1;
+                state.total_bypass_bytes += size.get();
+            }
+
+            let output = f(buffer.slice(..size.get()));
+            self.oversized_buffers.push(buffer);
+            return output;
+        }
+
         let chunk_index = self
             .active_chunks
             .iter()
@@ -542,7 +676,10 @@ impl StagingBufferProvider for WriteStagingBelt {
                     state.free_chunks.swap_remove(index)
                 }
                 else {
-                    let size = self.pool.inner.chunk_size.get().max(size.get());
+                    let size = wgpu::util::align_to(
+                        self.pool.inner.chunk_size.get().max(size.get()),
+                        wgpu::COPY_BUFFER_ALIGNMENT,
+                    );
                     state.total_allocated_count += 1;
                     state.total_allocated_bytes += size;
                     drop(state);
@@ -580,6 +717,11 @@ impl StagingBufferProvider for WriteStagingBelt {
             chunk.buffer.unmap();
         }
 
+        // the command encoder keeps these alive until the copies are done
+        for buffer in self.oversized_buffers.drain(..) {
+            buffer.unmap();
+        }
+
         let inflight_chunks =
             InflightChunks::new(self.pool.clone(), std::mem::take(&mut self.active_chunks));
 
@@ -590,7 +732,7 @@ impl StagingBufferProvider for WriteStagingBelt {
     }
 
     fn discard(&mut self) {
-        if !self.active_chunks.is_empty() {
+        if !self.active_chunks.is_empty() || !self.oversized_buffers.is_empty() {
             self.discard_impl()
         }
     }
@@ -598,7 +740,7 @@ impl StagingBufferProvider for WriteStagingBelt {
 
 impl Drop for WriteStagingBelt {
     fn drop(&mut self) {
-        if !self.active_chunks.is_empty() {
+        if !self.active_chunks.is_empty() || !self.oversized_buffers.is_empty() {
             tracing::warn!("WriteStagingBelt not committed. Staging buffers will not be mapped.");
             self.discard_impl();
         }
@@ -699,6 +841,7 @@ mod inflight {
                         state.in_flight_count -= 1;
                         state.total_staged_bytes += allocated;
                         state.free_chunks.push(chunk);
+                        state.trim(pool.inner.chunk_size, pool.inner.limits.free_reserve);
                     }
                 });
             }
@@ -721,6 +864,13 @@ pub struct StagingPoolInfo {
     pub total_allocation_count: usize,
     pub total_allocation_bytes: u64,
     pub total_staged_bytes: u64,
+
+    /// Number of oversized chunks that were freed.
+    pub total_trimmed_count: usize,
+
+    /// Number of allocations that bypassed the pool.
+    pub total_bypass_count: usize,
+    pub total_bypass_bytes: u64,
 }
 
 #[derive(Debug)]
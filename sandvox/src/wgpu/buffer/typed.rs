@@ -13,6 +13,11 @@ use std::{
 use bytemuck::Pod;
 
 use crate::{
+    render::readback::{
+        Readback,
+        ReadbackError,
+        ReadbackPool,
+    },
     util::{
         normalize_index_bounds,
         oneshot,
@@ -20,6 +25,51 @@ use crate::{
     wgpu::buffer::WriteStaging,
 };
 
+/// How the capacity of a [`TypedArrayBuffer`] grows when it's too small.
+///
+/// The new capacity is always at least the requested number of elements.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BufferGrowth {
+    /// Double the current capacity.
+    #[default]
+    Double,
+
+    /// Round up to the next power of two.
+    PowerOfTwo,
+
+    /// Grow the current capacity by 50%. This wastes less memory than
+    /// doubling, at the cost of more reallocations.
+    OneAndHalf,
+
+    /// Grow to exactly the requested number of elements.
+    Exact,
+}
+
+impl BufferGrowth {
+    pub fn new_capacity(&self, current_capacity: usize, num_elements: usize) -> usize {
+        let capacity = match self {
+            Self::Double => current_capacity * 2,
+            Self::PowerOfTwo => num_elements.next_power_of_two(),
+            Self::OneAndHalf => current_capacity + current_capacity / 2,
+            Self::Exact => num_elements,
+        };
+        capacity.max(num_elements)
+    }
+}
+
+/// Counts reallocations of a [`TypedArrayBuffer`], to diagnose buffer churn.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TypedArrayBufferStatistics {
+    /// How often the buffer was reallocated to grow.
+    pub grow_count: usize,
+
+    /// How often the buffer was reallocated to shrink.
+    pub shrink_count: usize,
+
+    /// Total size of all buffers allocated for this buffer (in bytes).
+    pub total_allocated_bytes: wgpu::BufferAddress,
+}
+
 // note: this is intentionally not Clone
 #[derive(Debug)]
 pub struct TypedArrayBuffer<T> {
@@ -27,6 +77,8 @@ pub struct TypedArrayBuffer<T> {
     device: wgpu::Device,
     label: Cow<'static, str>,
     usage: wgpu::BufferUsages,
+    growth: BufferGrowth,
+    statistics: TypedArrayBufferStatistics,
     _phantom: PhantomData<[T]>,
 }
 
@@ -46,6 +98,8 @@ impl<T> TypedArrayBuffer<T> {
             device: device.clone(),
             label,
             usage,
+            growth: Default::default(),
+            statistics: Default::default(),
             _phantom: PhantomData,
         };
 
@@ -65,14 +119,16 @@ impl<T> TypedArrayBuffer<T> {
         assert!(capacity >= num_elements);
 
         if capacity != 0 {
-            self.inner = Some(TypedArrayBufferInner::new::<T>(
+            let inner = TypedArrayBufferInner::new::<T>(
                 &self.device,
                 &self.label,
                 capacity,
                 num_elements,
                 self.usage,
                 mapped_at_creation,
-            ));
+            );
+            self.statistics.total_allocated_bytes += inner.buffer.size();
+            self.inner = Some(inner);
         }
 
         old_inner
@@ -109,6 +165,15 @@ impl<T> TypedArrayBuffer<T> {
         self.inner.as_ref().map(|inner| &inner.buffer)
     }
 
+    /// Binds the whole buffer.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the buffer has size 0, like [`buffer`](Self::buffer).
+    pub fn as_entire_binding(&self) -> wgpu::BindingResource<'_> {
+        self.buffer().as_entire_binding()
+    }
+
     pub fn len(&self) -> usize {
         self.inner.as_ref().map_or(0, |inner| inner.num_elements)
     }
@@ -134,6 +199,23 @@ impl<T> TypedArrayBuffer<T> {
     pub fn is_allocated(&self) -> bool {
         self.inner.is_some()
     }
+
+    pub fn with_growth(mut self, growth: BufferGrowth) -> Self {
+        self.growth = growth;
+        self
+    }
+
+    pub fn set_growth(&mut self, growth: BufferGrowth) {
+        self.growth = growth;
+    }
+
+    pub fn growth(&self) -> BufferGrowth {
+        self.growth
+    }
+
+    pub fn statistics(&self) -> &TypedArrayBufferStatistics {
+        &self.statistics
+    }
 }
 
 impl<T> TypedArrayBuffer<T>
@@ -221,6 +303,41 @@ where
             })
     }
 
+    /// Reads back the elements in `range` without blocking.
+    ///
+    /// Unlike [`read_view`](Self::read_view) this doesn't submit and wait. The
+    /// copy is recorded into `command_encoder`, and the readback completes some
+    /// time after it was submitted. The buffer needs
+    /// [`COPY_SRC`](wgpu::BufferUsages::COPY_SRC) usage.
+    pub fn read_back(
+        &self,
+        range: impl RangeBounds<usize>,
+        readback_pool: &ReadbackPool,
+        command_encoder: &mut wgpu::CommandEncoder,
+    ) -> Result<Readback<Vec<T>>, ReadbackError>
+    where
+        T: Send,
+    {
+        let Some((index_range, inner)) = self.inner.as_ref().and_then(|inner| {
+            let index_range = normalize_index_bounds(range, inner.num_elements);
+            (!index_range.is_empty()).then_some((index_range, inner))
+        })
+        else {
+            return Ok(Readback::ready(Ok(vec![])));
+        };
+
+        let alignment = StagingBufferAlignment::from_unaligned_buffer_range_typed::<T>(index_range);
+
+        readback_pool.read_buffer_with(
+            &self.device,
+            command_encoder,
+            inner
+                .buffer
+                .slice(alignment.buffer_start..alignment.buffer_end),
+            move |data| bytemuck::pod_collect_to_vec(&data[alignment.staging_range()]),
+        )
+    }
+
     pub fn write_view<'buffer, S>(
         &'buffer mut self,
         range: impl RangeBounds<usize>,
@@ -264,7 +381,7 @@ where
     pub fn resize<F>(
         &mut self,
         num_elements: usize,
-        mut on_reallocate: Option<F>,
+        on_reallocate: Option<F>,
         read_from_old_buffer: Option<&wgpu::Queue>,
     ) -> bool
     where
@@ -273,35 +390,129 @@ where
         let current_capacity = self.capacity();
 
         if num_elements > current_capacity {
-            // todo: make this a generic parameter?
-            let new_capacity = (current_capacity * 2).max(num_elements);
+            let new_capacity = self.growth.new_capacity(current_capacity, num_elements);
+
+            self.statistics.grow_count += 1;
+            tracing::trace!(
+                label = %self.label,
+                current_capacity,
+                new_capacity,
+                "growing buffer"
+            );
+
+            self.reallocate(
+                new_capacity,
+                num_elements,
+                on_reallocate,
+                read_from_old_buffer,
+            );
+
+            true
+        }
+        else {
+            if let Some(inner) = &mut self.inner {
+                inner.num_elements = num_elements;
+            }
+            else {
+                assert_eq!(num_elements, 0);
+                assert_eq!(current_capacity, 0);
+            }
+            false
+        }
+    }
+
+    /// Reallocates the buffer, so that its capacity matches its length.
+    ///
+    /// The arguments are the same as for [`resize`][Self::resize]. Without an
+    /// `on_reallocate` closure the contents of the buffer are lost.
+    ///
+    /// This returns `true` if a reallocation did take place.
+    pub fn shrink_to_fit<F>(
+        &mut self,
+        on_reallocate: Option<F>,
+        read_from_old_buffer: Option<&wgpu::Queue>,
+    ) -> bool
+    where
+        F: FnMut(Option<&[T]>, &mut [T], &wgpu::Buffer),
+    {
+        self.shrink_if_below(1.0, on_reallocate, read_from_old_buffer)
+    }
+
+    /// Shrinks the buffer to fit, if less than `ratio` of its capacity is
+    /// used.
+    ///
+    /// See [`shrink_to_fit`][Self::shrink_to_fit].
+    pub fn shrink_if_below<F>(
+        &mut self,
+        ratio: f32,
+        on_reallocate: Option<F>,
+        read_from_old_buffer: Option<&wgpu::Queue>,
+    ) -> bool
+    where
+        F: FnMut(Option<&[T]>, &mut [T], &wgpu::Buffer),
+    {
+        let num_elements = self.len();
+        let current_capacity = self.capacity();
+
+        if num_elements < current_capacity
+            && (num_elements as f32) < ratio * current_capacity as f32
+        {
+            self.statistics.shrink_count += 1;
+            tracing::trace!(
+                label = %self.label,
+                current_capacity,
+                new_capacity = num_elements,
+                "shrinking buffer"
+            );
+
+            self.reallocate(
+                num_elements,
+                num_elements,
+                on_reallocate,
+                read_from_old_buffer,
+            );
+
+            true
+        }
+        else {
+            false
+        }
+    }
 
-            let old_inner =
-                self.allocate_inner(new_capacity, num_elements, on_reallocate.is_some());
+    fn reallocate<F>(
+        &mut self,
+        new_capacity: usize,
+        num_elements: usize,
+        mut on_reallocate: Option<F>,
+        read_from_old_buffer: Option<&wgpu::Queue>,
+    ) where
+        F: FnMut(Option<&[T]>, &mut [T], &wgpu::Buffer),
+    {
+        let old_inner = self.allocate_inner(new_capacity, num_elements, on_reallocate.is_some());
 
-            if let Some(on_reallocate) = &mut on_reallocate {
-                let can_read = self.usage.contains(wgpu::BufferUsages::COPY_SRC);
+        if let Some(on_reallocate) = &mut on_reallocate {
+            let can_read = self.usage.contains(wgpu::BufferUsages::COPY_SRC);
 
-                let old_view = read_from_old_buffer.and_then(|queue| {
-                    can_read
-                        .then(|| {
-                            old_inner.as_ref().map(|inner| {
+            let old_view = read_from_old_buffer.and_then(|queue| {
+                can_read
+                    .then(|| {
+                        old_inner.as_ref().and_then(|inner| {
+                            let num_elements = inner.num_elements.min(num_elements);
+                            (num_elements > 0).then(|| {
                                 TypedArrayBufferReadView::new(
-                                    0..inner.num_elements,
+                                    0..num_elements,
                                     inner,
                                     &self.device,
                                     queue,
                                 )
                             })
                         })
-                        .flatten()
-                });
-
-                let new_inner = self
-                    .inner
-                    .as_ref()
-                    .expect("we just reallocated with larger capacity");
+                    })
+                    .flatten()
+            });
 
+            // a buffer shrunk to 0 elements has no underlying buffer
+            if let Some(new_inner) = &self.inner {
                 // note: this unmaps the buffer
                 new_inner.with_mapped_mut(|new_view| {
                     on_reallocate(
@@ -311,18 +522,6 @@ where
                     );
                 });
             }
-
-            true
-        }
-        else {
-            if let Some(inner) = &mut self.inner {
-                inner.num_elements = num_elements;
-            }
-            else {
-                assert_eq!(num_elements, 0);
-                assert_eq!(current_capacity, 0);
-            }
-            false
         }
     }
 
@@ -720,3 +919,18 @@ pub fn buffer_usage_needs_padding(usage: wgpu::BufferUsages) -> bool {
 pub fn is_buffer_copy_aligned(index: wgpu::BufferAddress) -> bool {
     (index & BUFFER_COPY_ALIGN_MASK) == 0
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::wgpu::buffer::BufferGrowth;
+
+    #[test]
+    fn it_grows_at_least_to_the_requested_size() {
+        assert_eq!(BufferGrowth::Double.new_capacity(8, 9), 16);
+        assert_eq!(BufferGrowth::Double.new_capacity(8, 100), 100);
+        assert_eq!(BufferGrowth::PowerOfTwo.new_capacity(8, 100), 128);
+        assert_eq!(BufferGrowth::OneAndHalf.new_capacity(8, 9), 12);
+        assert_eq!(BufferGrowth::OneAndHalf.new_capacity(0, 1), 1);
+        assert_eq!(BufferGrowth::Exact.new_capacity(8, 9), 9);
+    }
+}
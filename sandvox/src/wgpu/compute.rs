@@ -0,0 +1,189 @@
+//! Compute pipelines with a single bind group.
+//!
+//! The bindings are numbered in the order they're added to the
+//! [`ComputePipelineBuilder`], and the resources for a bind group are passed in
+//! the same order.
+
+use std::borrow::Cow;
+
+#[derive(Debug)]
+pub struct ComputePipelineBuilder<'a> {
+    label: Cow<'static, str>,
+    module: &'a wgpu::ShaderModule,
+    entry_point: &'a str,
+    entries: Vec<wgpu::BindGroupLayoutEntry>,
+    constants: Vec<(&'a str, f64)>,
+}
+
+impl<'a> ComputePipelineBuilder<'a> {
+    pub fn new(
+        label: impl Into<Cow<'static, str>>,
+        module: &'a wgpu::ShaderModule,
+        entry_point: &'a str,
+    ) -> Self {
+        Self {
+            label: label.into(),
+            module,
+            entry_point,
+            entries: vec![],
+            constants: vec![],
+        }
+    }
+
+    /// Adds a binding of any type.
+    pub fn binding(mut self, ty: wgpu::BindingType) -> Self {
+        self.entries.push(wgpu::BindGroupLayoutEntry {
+            binding: self.entries.len().try_into().unwrap(),
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty,
+            count: None,
+        });
+        self
+    }
+
+    /// Adds a uniform buffer binding for a `T`.
+    pub fn uniform<T>(self) -> Self {
+        self.binding(wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Uniform,
+            has_dynamic_offset: false,
+            min_binding_size: min_binding_size::<T>(),
+        })
+    }
+
+    /// Adds a storage buffer binding for a `T`, or an array of `T`.
+    pub fn storage<T>(self, read_only: bool) -> Self {
+        self.binding(wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Storage { read_only },
+            has_dynamic_offset: false,
+            min_binding_size: min_binding_size::<T>(),
+        })
+    }
+
+    /// Sets a pipeline-overridable constant.
+    pub fn constant(mut self, name: &'a str, value: f64) -> Self {
+        self.constants.push((name, value));
+        self
+    }
+
+    pub fn build(self, device: &wgpu::Device) -> ComputePipeline {
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some(&self.label),
+            entries: &self.entries,
+        });
+
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some(&self.label),
+            bind_group_layouts: &[&bind_group_layout],
+            immediate_size: 0,
+        });
+
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some(&self.label),
+            layout: Some(&layout),
+            module: self.module,
+            entry_point: Some(self.entry_point),
+            compilation_options: wgpu::PipelineCompilationOptions {
+                constants: &self.constants,
+                ..Default::default()
+            },
+            cache: None,
+        });
+
+        ComputePipeline {
+            label: self.label,
+            num_bindings: self.entries.len(),
+            bind_group_layout,
+            pipeline,
+        }
+    }
+}
+
+fn min_binding_size<T>() -> Option<wgpu::BufferSize> {
+    wgpu::BufferSize::new(size_of::<T>() as wgpu::BufferAddress)
+}
+
+#[derive(Clone, Debug)]
+pub struct ComputePipeline {
+    label: Cow<'static, str>,
+    num_bindings: usize,
+    bind_group_layout: wgpu::BindGroupLayout,
+    pipeline: wgpu::ComputePipeline,
+}
+
+impl ComputePipeline {
+    pub fn bind_group_layout(&self) -> &wgpu::BindGroupLayout {
+        &self.bind_group_layout
+    }
+
+    pub fn pipeline(&self) -> &wgpu::ComputePipeline {
+        &self.pipeline
+    }
+
+    /// Creates a bind group with one resource per binding, in the order the
+    /// bindings were added.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the number of resources doesn't match the number of
+    /// bindings.
+    pub fn bind_group<'r>(
+        &self,
+        device: &wgpu::Device,
+        resources: impl IntoIterator<Item = wgpu::BindingResource<'r>>,
+    ) -> wgpu::BindGroup {
+        let entries = resources
+            .into_iter()
+            .enumerate()
+            .map(|(binding, resource)| {
+                wgpu::BindGroupEntry {
+                    binding: binding.try_into().unwrap(),
+                    resource,
+                }
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            entries.len(),
+            self.num_bindings,
+            "wrong number of resources for compute pipeline {}",
+            self.label
+        );
+
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some(&self.label),
+            layout: &self.bind_group_layout,
+            entries: &entries,
+        })
+    }
+
+    /// Dispatches enough workgroups of `workgroup_size` to cover
+    /// `num_invocations`.
+    pub fn dispatch(
+        &self,
+        compute_pass: &mut wgpu::ComputePass,
+        bind_group: &wgpu::BindGroup,
+        num_invocations: [u32; 3],
+        workgroup_size: [u32; 3],
+    ) {
+        let [x, y, z] = workgroup_count(num_invocations, workgroup_size);
+        compute_pass.set_pipeline(&self.pipeline);
+        compute_pass.set_bind_group(0, bind_group, &[]);
+        compute_pass.dispatch_workgroups(x, y, z);
+    }
+}
+
+/// Number of workgroups needed to cover `num_invocations` on each axis.
+pub fn workgroup_count(num_invocations: [u32; 3], workgroup_size: [u32; 3]) -> [u32; 3] {
+    std::array::from_fn(|i| num_invocations[i].div_ceil(workgroup_size[i]))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::wgpu::compute::workgroup_count;
+
+    #[test]
+    fn it_rounds_up_to_whole_workgroups() {
+        assert_eq!(workgroup_count([100, 1, 1], [64, 1, 1]), [2, 1, 1]);
+        assert_eq!(workgroup_count([64, 17, 0], [64, 8, 1]), [1, 3, 0]);
+    }
+}
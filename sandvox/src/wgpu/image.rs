@@ -3,8 +3,10 @@ use std::{
     num::NonZero,
 };
 
+use half::f16;
 use image::{
-    RgbaImage,
+    ImageBuffer,
+    Rgba,
     imageops::FilterType,
 };
 use nalgebra::Vector2;
@@ -63,28 +65,16 @@ pub trait ImageTextureExt {
     fn generate_mip_levels<E>(
         &self,
         mip_levels: impl Iterator<Item = MipLevel>,
-        for_each: impl FnMut(u32, Vector2<u32>, &RgbaImage) -> Result<(), E>,
+        for_each: impl FnMut(u32, Vector2<u32>, &Self) -> Result<(), E>,
     ) -> Result<(), E>;
 }
 
-impl ImageTextureExt for RgbaImage {
+impl<P> ImageTextureExt for ImageBuffer<P, Vec<P::Subpixel>>
+where
+    P: TexturePixel,
+{
     fn texture_format(&self) -> Result<wgpu::TextureFormat, UnsupportedColorSpace> {
-        let cicp = self.color_space();
-
-        if cicp.primaries == image::metadata::CicpColorPrimaries::SRgb {
-            match cicp.transfer {
-                image::metadata::CicpTransferCharacteristics::Linear => {
-                    Ok(wgpu::TextureFormat::Rgba8Unorm)
-                }
-                image::metadata::CicpTransferCharacteristics::SRgb => {
-                    Ok(wgpu::TextureFormat::Rgba8UnormSrgb)
-                }
-                _ => Err(UnsupportedColorSpace { cicp }),
-            }
-        }
-        else {
-            Err(UnsupportedColorSpace { cicp })
-        }
+        P::texture_format(self.color_space())
     }
 
     fn texture_descriptor<'a>(
@@ -151,11 +141,10 @@ impl ImageTextureExt for RgbaImage {
             image_size, mip_size,
             "provided image size ({image_size:?}) doesn't match texture size at this mip level ({mip_size:?} @ {mip_level})"
         );
-        assert_eq!(samples.layout.channel_stride, 1, "channel stride not 4");
+        assert_eq!(samples.layout.channel_stride, 1, "channel stride not 1");
         assert_eq!(samples.layout.width_stride, 4, "width stride not 4");
 
-        const BYTES_PER_PIXEL: usize = 4;
-        let bytes_per_row_unpadded: u32 = samples.layout.width * BYTES_PER_PIXEL as u32;
+        let bytes_per_row_unpadded: u32 = samples.layout.width * P::BYTES_PER_PIXEL;
         let bytes_per_row_padded =
             wgpu::util::align_to(bytes_per_row_unpadded, wgpu::COPY_BYTES_PER_ROW_ALIGNMENT);
 
@@ -180,9 +169,13 @@ impl ImageTextureExt for RgbaImage {
         let mut source_offset = 0;
         let mut destination_offset = 0;
         let n = bytes_per_row_unpadded as usize;
+        let m = 4 * samples.layout.width as usize;
 
         for _ in 0..image_size.y {
-            view[destination_offset..][..n].copy_from_slice(&samples.samples[source_offset..][..n]);
+            P::write_row(
+                &samples.samples[source_offset..][..m],
+                &mut view[destination_offset..][..n],
+            );
             source_offset += samples.layout.height_stride;
             destination_offset += bytes_per_row_padded as usize;
         }
@@ -191,7 +184,7 @@ impl ImageTextureExt for RgbaImage {
     fn generate_mip_levels<E>(
         &self,
         mip_levels: impl Iterator<Item = MipLevel>,
-        mut for_each: impl FnMut(u32, Vector2<u32>, &RgbaImage) -> Result<(), E>,
+        mut for_each: impl FnMut(u32, Vector2<u32>, &Self) -> Result<(), E>,
     ) -> Result<(), E> {
         let mut image_buffer;
         let mut previous_level = self;
@@ -218,6 +211,76 @@ impl ImageTextureExt for RgbaImage {
     }
 }
 
+/// RGBA pixels that can be uploaded into a texture.
+pub trait TexturePixel: image::Pixel + 'static {
+    const BYTES_PER_PIXEL: u32;
+
+    fn texture_format(
+        cicp: image::metadata::Cicp,
+    ) -> Result<wgpu::TextureFormat, UnsupportedColorSpace>;
+
+    /// Writes a row of subpixels in the texture format.
+    fn write_row(source: &[Self::Subpixel], destination: &mut [u8]);
+}
+
+impl TexturePixel for Rgba<u8> {
+    const BYTES_PER_PIXEL: u32 = 4;
+
+    fn texture_format(
+        cicp: image::metadata::Cicp,
+    ) -> Result<wgpu::TextureFormat, UnsupportedColorSpace> {
+        if cicp.primaries == image::metadata::CicpColorPrimaries::SRgb {
+            match cicp.transfer {
+                image::metadata::CicpTransferCharacteristics::Linear => {
+                    Ok(wgpu::TextureFormat::Rgba8Unorm)
+                }
+                image::metadata::CicpTransferCharacteristics::SRgb => {
+                    Ok(wgpu::TextureFormat::Rgba8UnormSrgb)
+                }
+                _ => Err(UnsupportedColorSpace { cicp }),
+            }
+        }
+        else {
+            Err(UnsupportedColorSpace { cicp })
+        }
+    }
+
+    fn write_row(source: &[u8], destination: &mut [u8]) {
+        destination.copy_from_slice(source);
+    }
+}
+
+/// Float images are stored as half floats, which are filterable without
+/// extra features, and are assumed to be linear.
+impl TexturePixel for Rgba<f32> {
+    const BYTES_PER_PIXEL: u32 = 8;
+
+    fn texture_format(
+        cicp: image::metadata::Cicp,
+    ) -> Result<wgpu::TextureFormat, UnsupportedColorSpace> {
+        if cicp.primaries == image::metadata::CicpColorPrimaries::SRgb {
+            Ok(wgpu::TextureFormat::Rgba16Float)
+        }
+        else {
+            Err(UnsupportedColorSpace { cicp })
+        }
+    }
+
+    fn write_row(source: &[f32], destination: &mut [u8]) {
+        write_half_floats(source, destination);
+    }
+}
+
+/// Converts `source` into little-endian half floats. Values that are too large
+/// become infinity.
+pub fn write_half_floats(source: &[f32], destination: &mut [u8]) {
+    assert_eq!(2 * source.len(), destination.len());
+
+    for (value, bytes) in source.iter().zip(destination.as_chunks_mut::<2>().0) {
+        *bytes = f16::from_f32(*value).to_le_bytes();
+    }
+}
+
 #[derive(Clone, Copy, Debug, thiserror::Error)]
 #[error("Unsupported color space: primaries={:?}, transfer={:?}", .cicp.primaries, .cicp.transfer)]
 pub struct UnsupportedColorSpace {
@@ -310,6 +373,245 @@ pub fn mip_level_count_for_size(size: &Vector2<u32>) -> NonZero<u32> {
     NonZero::new(1 + size.checked_ilog2().unwrap_or_default()).unwrap()
 }
 
+/// Generates mipmaps on the GPU.
+///
+/// Each mip level is downsampled from the previous one with a box filter in a
+/// compute shader. sRGB textures are filtered in linear color space.
+///
+/// Only [`Rgba8Unorm`][wgpu::TextureFormat::Rgba8Unorm],
+/// [`Rgba8UnormSrgb`][wgpu::TextureFormat::Rgba8UnormSrgb] and
+/// [`Rgba16Float`][wgpu::TextureFormat::Rgba16Float] textures are supported,
+/// and they need the [`MipmapGenerator::USAGE`] usages.
+///
+/// sRGB textures can't be storage textures, so the shader writes into a
+/// scratch texture, which is then copied into the mip levels.
+#[derive(Debug)]
+pub struct MipmapGenerator {
+    linear_pipeline: DownsamplePipeline,
+    srgb_pipeline: DownsamplePipeline,
+    float_pipeline: DownsamplePipeline,
+}
+
+impl MipmapGenerator {
+    /// Usages a texture needs to generate mipmaps for it.
+    pub const USAGE: wgpu::TextureUsages =
+        wgpu::TextureUsages::TEXTURE_BINDING.union(wgpu::TextureUsages::COPY_DST);
+
+    pub fn new(device: &wgpu::Device) -> Self {
+        let shader = device.create_shader_module(wgpu::include_wgsl!("mipmap.wgsl"));
+
+        let create_pipeline = |label, destination_binding, scratch_format, srgb: bool| {
+            let bind_group_layout =
+                device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                    label: Some(label),
+                    entries: &[
+                        wgpu::BindGroupLayoutEntry {
+                            binding: 0,
+                            visibility: wgpu::ShaderStages::COMPUTE,
+                            ty: wgpu::BindingType::Texture {
+                                sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                                view_dimension: wgpu::TextureViewDimension::D2,
+                                multisampled: false,
+                            },
+                            count: None,
+                        },
+                        wgpu::BindGroupLayoutEntry {
+                            binding: destination_binding,
+                            visibility: wgpu::ShaderStages::COMPUTE,
+                            ty: wgpu::BindingType::StorageTexture {
+                                access: wgpu::StorageTextureAccess::WriteOnly,
+                                format: scratch_format,
+                                view_dimension: wgpu::TextureViewDimension::D2,
+                            },
+                            count: None,
+                        },
+                    ],
+                });
+
+            let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some(label),
+                bind_group_layouts: &[&bind_group_layout],
+                immediate_size: 0,
+            });
+
+            let entry_point = if scratch_format == wgpu::TextureFormat::Rgba16Float {
+                "downsample_float"
+            }
+            else {
+                "downsample"
+            };
+
+            let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some(label),
+                layout: Some(&pipeline_layout),
+                module: &shader,
+                entry_point: Some(entry_point),
+                compilation_options: wgpu::PipelineCompilationOptions {
+                    constants: &[("SRGB", if srgb { 1.0 } else { 0.0 })],
+                    ..Default::default()
+                },
+                cache: None,
+            });
+
+            DownsamplePipeline {
+                bind_group_layout,
+                pipeline,
+                scratch_format,
+                destination_binding,
+            }
+        };
+
+        Self {
+            linear_pipeline: create_pipeline(
+                "mipmap/linear",
+                1,
+                wgpu::TextureFormat::Rgba8Unorm,
+                false,
+            ),
+            srgb_pipeline: create_pipeline("mipmap/srgb", 1, wgpu::TextureFormat::Rgba8Unorm, true),
+            float_pipeline: create_pipeline(
+                "mipmap/float",
+                2,
+                wgpu::TextureFormat::Rgba16Float,
+                false,
+            ),
+        }
+    }
+
+    /// Fills mip levels 1 and up from mip level 0, for all array layers.
+    pub fn generate(
+        &self,
+        device: &wgpu::Device,
+        command_encoder: &mut wgpu::CommandEncoder,
+        texture: &wgpu::Texture,
+    ) -> Result<(), UnsupportedMipmapFormat> {
+        let format = texture.format();
+        let pipeline = match format {
+            wgpu::TextureFormat::Rgba8Unorm => &self.linear_pipeline,
+            wgpu::TextureFormat::Rgba8UnormSrgb => &self.srgb_pipeline,
+            wgpu::TextureFormat::Rgba16Float => &self.float_pipeline,
+            _ => return Err(UnsupportedMipmapFormat { format }),
+        };
+
+        let mip_level_count = texture.mip_level_count();
+        if mip_level_count < 2 {
+            return Ok(());
+        }
+
+        // scratch texture with mip level `i` corresponding to mip level `i + 1` of
+        // the texture.
+        let scratch_size = texture.size().mip_level_size(1, texture.dimension());
+        let scratch = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("mipmap/scratch"),
+            size: wgpu::Extent3d {
+                depth_or_array_layers: 1,
+                ..scratch_size
+            },
+            mip_level_count: mip_level_count - 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: pipeline.scratch_format,
+            usage: wgpu::TextureUsages::STORAGE_BINDING | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+
+        for array_layer in 0..texture.depth_or_array_layers() {
+            for mip_level in 1..mip_level_count {
+                let source = texture.create_view(&wgpu::TextureViewDescriptor {
+                    label: Some("mipmap/source"),
+                    dimension: Some(wgpu::TextureViewDimension::D2),
+                    base_mip_level: mip_level - 1,
+                    mip_level_count: Some(1),
+                    base_array_layer: array_layer,
+                    array_layer_count: Some(1),
+                    ..Default::default()
+                });
+                let destination = scratch.create_view(&wgpu::TextureViewDescriptor {
+                    label: Some("mipmap/destination"),
+                    base_mip_level: mip_level - 1,
+                    mip_level_count: Some(1),
+                    ..Default::default()
+                });
+
+                let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    label: Some("mipmap"),
+                    layout: &pipeline.bind_group_layout,
+                    entries: &[
+                        wgpu::BindGroupEntry {
+                            binding: 0,
+                            resource: wgpu::BindingResource::TextureView(&source),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: pipeline.destination_binding,
+                            resource: wgpu::BindingResource::TextureView(&destination),
+                        },
+                    ],
+                });
+
+                let size = scratch
+                    .size()
+                    .mip_level_size(mip_level - 1, scratch.dimension());
+
+                {
+                    let mut compute_pass =
+                        command_encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                            label: Some("mipmap"),
+                            timestamp_writes: None,
+                        });
+                    compute_pass.set_pipeline(&pipeline.pipeline);
+                    compute_pass.set_bind_group(0, &bind_group, &[]);
+                    compute_pass.dispatch_workgroups(
+                        size.width.div_ceil(8),
+                        size.height.div_ceil(8),
+                        1,
+                    );
+                }
+
+                // sRGB and non-sRGB formats are copy-compatible
+                command_encoder.copy_texture_to_texture(
+                    wgpu::TexelCopyTextureInfo {
+                        texture: &scratch,
+                        mip_level: mip_level - 1,
+                        origin: Default::default(),
+                        aspect: Default::default(),
+                    },
+                    wgpu::TexelCopyTextureInfo {
+                        texture,
+                        mip_level,
+                        origin: wgpu::Origin3d {
+                            x: 0,
+                            y: 0,
+                            z: array_layer,
+                        },
+                        aspect: Default::default(),
+                    },
+                    size,
+                );
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+struct DownsamplePipeline {
+    bind_group_layout: wgpu::BindGroupLayout,
+    pipeline: wgpu::ComputePipeline,
+
+    /// Storage format that is copy-compatible with the texture.
+    scratch_format: wgpu::TextureFormat,
+
+    /// Binding of the storage texture in the shader.
+    destination_binding: u32,
+}
+
+#[derive(Clone, Copy, Debug, thiserror::Error)]
+#[error("Can't generate mipmaps for texture format: {format:?}")]
+pub struct UnsupportedMipmapFormat {
+    pub format: wgpu::TextureFormat,
+}
+
 #[cfg(test)]
 mod tests {
     use std::num::NonZero;
@@ -320,6 +622,7 @@ mod tests {
     use crate::wgpu::image::{
         MipLevel,
         MipLevels,
+        write_half_floats,
     };
 
     #[test]
@@ -417,4 +720,14 @@ mod tests {
             }
         );
     }
+
+    #[test]
+    fn half_floats() {
+        let mut bytes = [0; 10];
+        write_half_floats(&[1.0, -2.5, 0.0, 65504.0, 1e6], &mut bytes);
+        assert_eq!(
+            bytes,
+            [0x00, 0x3c, 0x00, 0xc1, 0x00, 0x00, 0xff, 0x7b, 0x00, 0x7c]
+        );
+    }
 }
@@ -0,0 +1,74 @@
+// Whether the texture stores sRGB. The storage texture can't have an sRGB format, so we
+// have to encode the color ourselves.
+override SRGB: bool = false;
+
+@group(0)
+@binding(0)
+var source: texture_2d<f32>;
+
+@group(0)
+@binding(1)
+var destination: texture_storage_2d<rgba8unorm, write>;
+
+// for float textures, which are always linear. a pipeline only uses one of the
+// destinations.
+@group(0)
+@binding(2)
+var destination_float: texture_storage_2d<rgba16float, write>;
+
+@compute
+@workgroup_size(8, 8)
+fn downsample(@builtin(global_invocation_id) id: vec3u) {
+    let size = textureDimensions(destination);
+    if id.x >= size.x || id.y >= size.y {
+        return;
+    }
+
+    var color = average(id.xy);
+    if SRGB {
+        color = vec4f(linear_to_srgb(color.rgb), color.a);
+    }
+
+    textureStore(destination, id.xy, color);
+}
+
+@compute
+@workgroup_size(8, 8)
+fn downsample_float(@builtin(global_invocation_id) id: vec3u) {
+    let size = textureDimensions(destination_float);
+    if id.x >= size.x || id.y >= size.y {
+        return;
+    }
+
+    textureStore(destination_float, id.xy, average(id.xy));
+}
+
+fn average(position: vec2u) -> vec4f {
+    // for odd sizes the last row/column is clamped. this slightly over-weights it,
+    // but is good enough for mipmaps.
+    let max_source = textureDimensions(source) - vec2u(1);
+    let base = position * 2;
+
+    // the source view has the sRGB format (if any), so these are linear colors. the
+    // colors are weighted by alpha, so that transparent texels don't darken the
+    // result.
+    var sum = vec4f(0);
+    for (var y = 0u; y < 2; y++) {
+        for (var x = 0u; x < 2; x++) {
+            let texel = textureLoad(source, min(base + vec2u(x, y), max_source), 0);
+            sum += vec4f(texel.rgb * texel.a, texel.a);
+        }
+    }
+
+    var color = sum / 4;
+    if color.a > 0 {
+        color = vec4f(color.rgb / color.a, color.a);
+    }
+    return color;
+}
+
+fn linear_to_srgb(color: vec3f) -> vec3f {
+    let low = color * 12.92;
+    let high = 1.055 * pow(color, vec3f(1.0 / 2.4)) - 0.055;
+    return select(high, low, color <= vec3f(0.0031308));
+}
@@ -1,9 +1,11 @@
 pub mod blit;
 pub mod buffer;
+pub mod compute;
 pub mod image;
 pub mod query;
 
 use std::{
+    fmt::Display,
     num::NonZero,
     sync::Arc,
 };
@@ -13,16 +15,15 @@ use bevy_ecs::{
     schedule::{
         IntoScheduleConfigs,
         SystemSet,
+        common_conditions::resource_exists,
     },
     system::Commands,
     world::World,
 };
 use color_eyre::eyre::Error;
 use nalgebra::Vector2;
-use palette::{
-    LinSrgba,
-    Srgba,
-};
+use palette::LinSrgba;
+use parking_lot::Mutex;
 use serde::{
     Deserialize,
     Serialize,
@@ -41,6 +42,7 @@ use crate::{
         wgpu::WgpuProfiler,
     },
     wgpu::buffer::{
+        StagingLimits,
         StagingPool,
         WriteStaging,
     },
@@ -53,10 +55,15 @@ pub struct WgpuPlugin {
 
 impl Plugin for WgpuPlugin {
     fn setup(&self, builder: &mut WorldBuilder) -> Result<(), Error> {
+        // kept around to create the context again if the device is lost
+        builder.insert_resource(self.config.clone());
+
+        // creates the context from a builder, initially and after the device was lost.
         let context_builder = WgpuContextBuilder::new(self.config.clone())?;
         builder.insert_resource(context_builder).add_systems(
-            schedule::Startup,
+            schedule::GpuSetup,
             create_wgpu_context
+                .run_if(resource_exists::<WgpuContextBuilder>)
                 .in_set(WgpuSystems::CreateContext)
                 .after(WgpuSystems::RequestFeatures),
         );
@@ -72,6 +79,9 @@ fn create_wgpu_context(mut commands: Commands) {
         let profiler = world.get_resource::<Profiler>();
 
         let context = context_builder.build(profiler).unwrap();
+        if let Some(profiler) = &context.profiler {
+            world.insert_resource(profiler.timings().clone());
+        }
         world.insert_resource(context);
     })
 }
@@ -82,7 +92,7 @@ pub enum WgpuSystems {
     RequestFeatures,
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, Resource)]
 pub struct WgpuConfig {
     #[serde(default = "default_backends", with = "crate::util::serde::backends")]
     pub backends: wgpu::Backends,
@@ -90,11 +100,40 @@ pub struct WgpuConfig {
     #[serde(default, with = "crate::util::serde::power_preference")]
     pub power_preference: wgpu::PowerPreference,
 
+    /// Use the adapter whose name contains this (case-insensitive).
+    ///
+    /// `sandvox wgpu-info` lists the available adapters.
+    #[serde(default)]
+    pub adapter_name: Option<String>,
+
+    /// Use the adapter with this index in the list of available adapters.
+    ///
+    /// If [`adapter_name`][Self::adapter_name] is also set, the adapter must
+    /// match both.
+    #[serde(default)]
+    pub adapter_index: Option<usize>,
+
     #[serde(default = "default_staging_chunk_size")]
     pub staging_chunk_size: wgpu::BufferSize,
 
+    /// How many bytes of free staging buffers are kept around, if they're
+    /// larger than the chunk size.
+    #[serde(default = "default_staging_free_reserve")]
+    pub staging_free_reserve: wgpu::BufferAddress,
+
+    /// Uploads larger than this use their own staging buffer instead of the
+    /// staging pool.
+    #[serde(default = "default_staging_oversize_threshold")]
+    pub staging_oversize_threshold: wgpu::BufferAddress,
+
     #[serde(default)]
     pub memory_hints: MemoryHints,
+
+    /// Measure the GPU time of render passes, even without a profiler.
+    ///
+    /// The timings are shown in the GPU timings overlay.
+    #[serde(default)]
+    pub gpu_timings: bool,
 }
 
 impl Default for WgpuConfig {
@@ -102,8 +141,13 @@ impl Default for WgpuConfig {
         Self {
             backends: default_backends(),
             power_preference: Default::default(),
+            adapter_name: None,
+            adapter_index: None,
             staging_chunk_size: default_staging_chunk_size(),
+            staging_free_reserve: default_staging_free_reserve(),
+            staging_oversize_threshold: default_staging_oversize_threshold(),
             memory_hints: Default::default(),
+            gpu_timings: false,
         }
     }
 }
@@ -117,6 +161,16 @@ fn default_staging_chunk_size() -> wgpu::BufferSize {
     const { wgpu::BufferSize::new(0x100_000).unwrap() }
 }
 
+fn default_staging_free_reserve() -> wgpu::BufferAddress {
+    // 16 MiB
+    0x1_000_000
+}
+
+fn default_staging_oversize_threshold() -> wgpu::BufferAddress {
+    // 16 MiB
+    0x1_000_000
+}
+
 #[derive(Debug, Resource)]
 pub struct WgpuContextBuilder {
     pub config: WgpuConfig,
@@ -131,16 +185,32 @@ pub struct WgpuContextBuilder {
 
 impl WgpuContextBuilder {
     pub fn new(config: WgpuConfig) -> Result<Self, Error> {
+        pollster::block_on(Self::new_async(config))
+    }
+
+    pub async fn new_async(config: WgpuConfig) -> Result<Self, Error> {
         let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor {
             backends: config.backends,
             ..Default::default()
         });
 
-        // fixme: this won't do on web
-        let adapter = pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
-            power_preference: config.power_preference,
-            ..Default::default()
-        }))?;
+        let adapter = if config.adapter_name.is_some() || config.adapter_index.is_some() {
+            select_adapter(
+                &instance,
+                config.backends,
+                config.adapter_name.as_deref(),
+                config.adapter_index,
+            )
+            .await?
+        }
+        else {
+            instance
+                .request_adapter(&wgpu::RequestAdapterOptions {
+                    power_preference: config.power_preference,
+                    ..Default::default()
+                })
+                .await?
+        };
 
         let adapter_info = adapter.get_info();
 
@@ -182,40 +252,43 @@ impl WgpuContextBuilder {
         self.try_request_features(features).unwrap()
     }
 
-    pub fn build(mut self, mut profiler: Option<&Profiler>) -> Result<WgpuContext, Error> {
-        if profiler.is_some() {
-            if self
+    pub fn build(self, profiler: Option<&Profiler>) -> Result<WgpuContext, Error> {
+        pollster::block_on(self.build_async(profiler))
+    }
+
+    pub async fn build_async(
+        mut self,
+        mut profiler: Option<&Profiler>,
+    ) -> Result<WgpuContext, Error> {
+        let mut gpu_timings = self.config.gpu_timings;
+
+        if (profiler.is_some() || gpu_timings)
+            && self
                 .try_request_features(
                     wgpu::Features::TIMESTAMP_QUERY
                         | wgpu::Features::TIMESTAMP_QUERY_INSIDE_ENCODERS
                         | wgpu::Features::TIMESTAMP_QUERY_INSIDE_PASSES,
                 )
                 .is_err()
-            {
-                tracing::warn!("Timestamp queries not available. Won't profile GPU.");
-                profiler = None;
-            }
+        {
+            tracing::warn!("Timestamp queries not available. Won't profile GPU.");
+            profiler = None;
+            gpu_timings = false;
         }
 
-        // fixme: this won't do on web
-        let (device, queue) = pollster::block_on(async {
-            // these might need to be modified
-
-            let (device, queue) = self
-                .adapter
-                .request_device(&wgpu::DeviceDescriptor {
-                    required_features: self.enabled_features,
-                    required_limits: self.enabled_limits,
-                    memory_hints: match self.config.memory_hints {
-                        MemoryHints::Performance => wgpu::MemoryHints::Performance,
-                        MemoryHints::MemoryUsage => wgpu::MemoryHints::MemoryUsage,
-                    },
-                    ..Default::default()
-                })
-                .await?;
-
-            Ok::<_, Error>((device, queue))
-        })?;
+        // these might need to be modified
+        let (device, queue) = self
+            .adapter
+            .request_device(&wgpu::DeviceDescriptor {
+                required_features: self.enabled_features,
+                required_limits: self.enabled_limits,
+                memory_hints: match self.config.memory_hints {
+                    MemoryHints::Performance => wgpu::MemoryHints::Performance,
+                    MemoryHints::MemoryUsage => wgpu::MemoryHints::MemoryUsage,
+                },
+                ..Default::default()
+            })
+            .await?;
 
         let info = WgpuInfo {
             adapter: self.adapter_info,
@@ -223,14 +296,34 @@ impl WgpuContextBuilder {
             limits: device.limits(),
             timestamp_period: queue.get_timestamp_period(),
         };
+        crate::crash::set_adapter(&info.adapter);
+
+        let staging_pool = StagingPool::with_limits(
+            self.config.staging_chunk_size,
+            "staging pool",
+            StagingLimits {
+                free_reserve: self.config.staging_free_reserve,
+                oversize_threshold: self.config.staging_oversize_threshold,
+            },
+        );
 
-        let staging_pool = StagingPool::new(self.config.staging_chunk_size, "staging pool");
-
-        let profiler =
-            profiler.map(|profiler| WgpuProfiler::new(&device, info.timestamp_period, profiler));
+        let profiler = (profiler.is_some() || gpu_timings)
+            .then(|| WgpuProfiler::new(&device, info.timestamp_period, profiler));
 
         tracing::info!(adapter = info.adapter.name, backend = ?info.adapter.backend, "Created wgpu context");
 
+        let device_lost = DeviceLost::default();
+        device.set_device_lost_callback({
+            let device_lost = device_lost.clone();
+            move |reason, message| {
+                // the device is also "lost" when it's dropped
+                if reason != wgpu::DeviceLostReason::Destroyed {
+                    tracing::error!(?reason, message, "device lost");
+                    device_lost.report(message);
+                }
+            }
+        });
+
         Ok(WgpuContext {
             instance: self.instance,
             adapter: self.adapter,
@@ -239,10 +332,100 @@ impl WgpuContextBuilder {
             staging_pool,
             info: Arc::new(info),
             profiler,
+            device_lost,
+        })
+    }
+}
+
+/// Lists the adapters available for the given backends.
+///
+/// The index of an adapter in this list is what
+/// [`WgpuConfig::adapter_index`] refers to.
+pub async fn available_adapters(
+    instance: &wgpu::Instance,
+    backends: wgpu::Backends,
+) -> Vec<wgpu::Adapter> {
+    instance.enumerate_adapters(backends).await
+}
+
+async fn select_adapter(
+    instance: &wgpu::Instance,
+    backends: wgpu::Backends,
+    name: Option<&str>,
+    index: Option<usize>,
+) -> Result<wgpu::Adapter, NoMatchingAdapter> {
+    let adapters = available_adapters(instance, backends).await;
+    let name_lowercase = name.map(str::to_lowercase);
+
+    let position = adapters.iter().enumerate().position(|(i, adapter)| {
+        index.is_none_or(|index| index == i)
+            && name_lowercase
+                .as_ref()
+                .is_none_or(|name| adapter.get_info().name.to_lowercase().contains(name))
+    });
+
+    if let Some(position) = position {
+        let adapter = adapters.into_iter().nth(position).unwrap();
+        tracing::debug!(
+            index = position,
+            name = adapter.get_info().name,
+            "selected adapter"
+        );
+        Ok(adapter)
+    }
+    else {
+        Err(NoMatchingAdapter {
+            name: name.map(ToOwned::to_owned),
+            index,
+            candidates: adapters.iter().map(|adapter| adapter.get_info()).collect(),
         })
     }
 }
 
+#[derive(Debug, thiserror::Error)]
+pub struct NoMatchingAdapter {
+    pub name: Option<String>,
+    pub index: Option<usize>,
+    pub candidates: Vec<wgpu::AdapterInfo>,
+}
+
+impl Display for NoMatchingAdapter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "No adapter matches")?;
+        if let Some(name) = &self.name {
+            write!(f, " name {name:?}")?;
+        }
+        if let Some(index) = self.index {
+            write!(f, " index {index}")?;
+        }
+
+        if self.candidates.is_empty() {
+            write!(f, ". No adapters available.")
+        }
+        else {
+            writeln!(f, ". Available adapters:")?;
+            for (index, info) in self.candidates.iter().enumerate() {
+                writeln!(f, "  {index}: {}", AdapterSummary(info))?;
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Formats adapter info as a single line, e.g. for listing adapters.
+#[derive(Clone, Copy, Debug)]
+pub struct AdapterSummary<'a>(pub &'a wgpu::AdapterInfo);
+
+impl Display for AdapterSummary<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} ({:?}, {:?})",
+            self.0.name, self.0.backend, self.0.device_type
+        )
+    }
+}
+
 #[derive(Debug, thiserror::Error)]
 #[error(
     "The following features were requested, but are not supported by the adapter: {unsupported:?}"
@@ -260,6 +443,28 @@ pub struct WgpuContext {
     pub staging_pool: StagingPool,
     pub info: Arc<WgpuInfo>,
     pub profiler: Option<WgpuProfiler>,
+    pub device_lost: DeviceLost,
+}
+
+/// Records whether the device was lost.
+///
+/// Once this is set, the context can't be used anymore and must be recreated
+/// together with all GPU resources.
+#[derive(Clone, Debug, Default)]
+pub struct DeviceLost {
+    reason: Arc<Mutex<Option<String>>>,
+}
+
+impl DeviceLost {
+    /// Marks the device as lost. Only the first reason is kept.
+    pub fn report(&self, reason: impl Into<String>) {
+        self.reason.lock().get_or_insert_with(|| reason.into());
+    }
+
+    /// Returns the reason if the device was lost.
+    pub fn reason(&self) -> Option<String> {
+        self.reason.lock().clone()
+    }
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -270,7 +475,7 @@ pub struct WgpuInfo {
     pub timestamp_period: f32,
 }
 
-#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize)]
+#[derive(Clone, Copy, Debug, PartialEq, Default, Serialize, Deserialize)]
 pub enum MemoryHints {
     #[default]
     Performance,
@@ -389,12 +594,3 @@ impl TextureSourceLayout {
         }
     }
 }
-
-pub fn srgba_to_wgpu(color: Srgba<f32>) -> wgpu::Color {
-    wgpu::Color {
-        r: color.red as f64,
-        g: color.green as f64,
-        b: color.blue as f64,
-        a: color.alpha as f64,
-    }
-}